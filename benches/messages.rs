@@ -0,0 +1,55 @@
+//! Parsing updates borrowed and owned
+//!
+//! Run with `cargo bench --bench messages`. `tests/borrowed_memory.rs`
+//! checks the allocations the borrowed parser saves.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use indi_rs::message::borrowed::MessageRef;
+use indi_rs::message::new::OneBlob;
+use indi_rs::message::set::SetBlobVector;
+use indi_rs::message::MessageType;
+use indi_rs::property::PropertyState;
+
+const NUMBER_UPDATE: &str = r#"<setNumberVector device="Telescope Simulator" name="EQUATORIAL_EOD_COORD" state="Busy" timeout="60" timestamp="2026-10-17T01:02:03">
+<oneNumber name="RA">5.5916666666666668</oneNumber>
+<oneNumber name="DEC">-5.3911111111111110</oneNumber>
+</setNumberVector>"#;
+
+/// Update carrying an uncompressed 16 bit frame of 1280x960 pixels
+fn blob_update() -> String {
+    let image = (0..1280 * 960 * 2)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    MessageType::SetBlobVector(SetBlobVector {
+        device: "CCD Simulator".to_string(),
+        name: "CCD1".to_string(),
+        state: Some(PropertyState::Ok),
+        timeout: None,
+        timestamp: None,
+        message: None,
+        elements: vec![OneBlob::new("CCD1", ".fits", &image)],
+    })
+    .to_xml()
+    .unwrap()
+}
+
+fn parse(c: &mut Criterion) {
+    let blob = blob_update();
+    for (name, xml) in [("setNumberVector", NUMBER_UPDATE), ("setBLOBVector", &blob)] {
+        let mut group = c.benchmark_group(name);
+        group.throughput(Throughput::Bytes(xml.len() as u64));
+        group.bench_function("MessageType", |b| {
+            b.iter(|| black_box(xml).parse::<MessageType>().unwrap())
+        });
+        group.bench_function("MessageRef", |b| {
+            b.iter(|| MessageRef::parse(black_box(xml)).unwrap())
+        });
+        group.bench_function("MessageRef::into_owned", |b| {
+            b.iter(|| MessageRef::parse(black_box(xml)).unwrap().into_owned())
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
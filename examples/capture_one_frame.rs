@@ -0,0 +1,63 @@
+//! Take one exposure and save the image
+//!
+//! ```sh
+//! cargo run --example capture_one_frame -- --exposure 2.5 --output frame.fits
+//! ```
+
+mod support;
+
+use clap::Parser;
+use indi_rs::devices::{Camera, FrameType};
+use indi_rs::error::Result;
+use std::path::PathBuf;
+use std::time::Duration;
+use support::ServerArgs;
+use tracing::info;
+
+/// Property identifying a camera
+const CCD_EXPOSURE: &str = "CCD_EXPOSURE";
+
+#[derive(Parser, Debug)]
+#[command(about = "Capture a single light frame")]
+struct Cli {
+    #[command(flatten)]
+    server: ServerArgs,
+
+    /// Camera device, defaults to the first camera found
+    #[arg(short, long)]
+    device: Option<String>,
+
+    /// Exposure time in seconds
+    #[arg(short, long, default_value_t = 1.0)]
+    exposure: f64,
+
+    /// File to write, the driver's format suffix is appended if missing
+    #[arg(short, long, default_value = "frame")]
+    output: PathBuf,
+
+    /// Show debug output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    support::init_tracing(cli.verbose);
+
+    let (client, devices) = cli.server.connect().await?;
+    let device = support::find_device(&devices, CCD_EXPOSURE, cli.device.as_deref())?;
+    let camera = Camera::new(client, device);
+
+    camera.set_frame_type(FrameType::Light).await?;
+    info!("Exposing {} for {} s", camera.device(), cli.exposure);
+    let blob = camera.expose(Duration::from_secs_f64(cli.exposure)).await?;
+
+    let mut output = cli.output;
+    if output.extension().is_none() {
+        output.set_extension(blob.format.trim_start_matches('.'));
+    }
+    tokio::fs::write(&output, &blob.data).await?;
+    info!("Wrote {} bytes to {}", blob.data.len(), output.display());
+    Ok(())
+}
@@ -0,0 +1,49 @@
+//! Connect to a server and list its devices and properties
+//!
+//! ```sh
+//! cargo run --example connect_and_list -- -H localhost
+//! ```
+
+mod support;
+
+use clap::Parser;
+use indi_rs::error::Result;
+use support::ServerArgs;
+
+#[derive(Parser, Debug)]
+#[command(about = "List the devices and properties of an INDI server")]
+struct Cli {
+    #[command(flatten)]
+    server: ServerArgs,
+
+    /// Show debug output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    support::init_tracing(cli.verbose);
+
+    let (client, devices) = cli.server.connect().await?;
+    let state = client.state();
+    let state = state.lock().await;
+    for device in &devices {
+        match &device.driver {
+            Some(driver) => println!(
+                "{} ({} {})",
+                device.name,
+                driver.name.as_deref().unwrap_or("unknown driver"),
+                driver.version.as_deref().unwrap_or("")
+            ),
+            None => println!("{}", device.name),
+        }
+        for name in &device.properties {
+            if let Some(property) = state.get_property(&device.name, name) {
+                println!("  {} [{:?}] = {}", name, property.state, property.value);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,606 @@
+//! Driver for Moonlite focusers, and a reference for writing drivers
+//!
+//! Speaks the Moonlite serial protocol: commands like `:GP#` are answered
+//! with hexadecimal values terminated by `#`. The focuser defines the
+//! standard focuser properties while connected; the port is selected with
+//! `DEVICE_PORT` and `DEVICE_BAUD_RATE`, and `CONFIG_PROCESS` saves them to
+//! `~/.indi/Moonlite_config.xml`. Build it and let indiserver run it:
+//!
+//! ```sh
+//! cargo build --release --features serial --example moonlite
+//! indiserver target/release/examples/moonlite
+//! ```
+
+use async_trait::async_trait;
+use indi_rs::driver::config::ConfigGuard;
+use indi_rs::driver::connection::{Connectable, ConnectionGuard, SerialConnection};
+use indi_rs::driver::IndiDriver;
+use indi_rs::error::{Error, Result};
+use indi_rs::format::parse_number;
+use indi_rs::message::definition::{
+    DefNumber, DefNumberVector, DefSwitch, DefSwitchVector, DefText, DefTextVector,
+};
+use indi_rs::message::new::{
+    NewNumberVector, NewSwitchVector, NewTextVector, OneNumber, OneSwitch,
+};
+use indi_rs::message::set::{SetNumberVector, SetSwitchVector};
+use indi_rs::message::MessageType;
+use indi_rs::property::timestamp::INDITimestamp;
+use indi_rs::property::{PropertyPerm, PropertyState, SwitchRule, SwitchState};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio_serial::SerialStream;
+use tracing::{info, warn};
+
+/// Device name, also naming the configuration file
+const DEVICE: &str = "Moonlite";
+/// Port used until the user selects one
+const DEFAULT_PORT: &str = "/dev/ttyUSB0";
+/// Baud rate of Moonlite controllers
+const DEFAULT_BAUD_RATE: u32 = 9600;
+/// Interface bit of a focuser in `DRIVER_INFO`
+const FOCUSER_INTERFACE: u32 = 8;
+/// Time between reads of the focuser state
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Time allowed for the controller to answer
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+/// Highest position the 16 bit controller can reach
+const MAX_POSITION: f64 = 65535.0;
+/// Group of the focuser properties
+const GROUP: &str = "Main Control";
+
+/// Moonlite focuser on a serial port
+struct Moonlite {
+    serial: SerialConnection,
+    port: Option<BufReader<SerialStream>>,
+    position: u16,
+    moving: bool,
+    temperature: f64,
+    half_step: bool,
+    outward: bool,
+}
+
+impl Moonlite {
+    fn new() -> Self {
+        Self {
+            serial: SerialConnection::new(DEVICE, DEFAULT_PORT, DEFAULT_BAUD_RATE),
+            port: None,
+            position: 0,
+            moving: false,
+            temperature: 0.0,
+            half_step: false,
+            outward: true,
+        }
+    }
+
+    /// Send `:<command>#`, which the controller does not answer
+    async fn command(&mut self, command: &str) -> Result<()> {
+        let port = self
+            .port
+            .as_mut()
+            .ok_or_else(|| Error::Protocol("Not connected".to_string()))?;
+        port.get_mut()
+            .write_all(format!(":{}#", command).as_bytes())
+            .await?;
+        port.get_mut().flush().await?;
+        Ok(())
+    }
+
+    /// Send `:<command>#` and parse the hexadecimal reply
+    async fn query(&mut self, command: &str) -> Result<u16> {
+        self.command(command).await?;
+        let port = self
+            .port
+            .as_mut()
+            .ok_or_else(|| Error::Protocol("Not connected".to_string()))?;
+        let mut reply = Vec::new();
+        tokio::time::timeout(REPLY_TIMEOUT, port.read_until(b'#', &mut reply))
+            .await
+            .map_err(|_| Error::Timeout(format!("No reply to :{}#", command)))??;
+        let reply = String::from_utf8_lossy(&reply);
+        let value = reply.trim_end_matches('#');
+        u16::from_str_radix(value, 16)
+            .map_err(|_| Error::ParseError(format!("Invalid reply to :{}#: {}", command, reply)))
+    }
+
+    /// Read position, motion, temperature and step mode
+    async fn read_state(&mut self) -> Result<()> {
+        self.position = self.query("GP").await?;
+        self.moving = self.query("GI").await? == 0x01;
+        // Temperatures are converted on request, in two's complement half
+        // degrees
+        self.command("C").await?;
+        self.temperature = f64::from(self.query("GT").await? as i16) / 2.0;
+        self.half_step = self.query("GH").await? == 0xFF;
+        Ok(())
+    }
+
+    /// Start moving to `target`
+    async fn goto(&mut self, target: f64) -> Result<()> {
+        let target = target.round().clamp(0.0, MAX_POSITION) as u16;
+        self.command(&format!("SN{:04X}", target)).await?;
+        self.command("FG").await?;
+        self.moving = true;
+        Ok(())
+    }
+
+    fn position_update(&self, state: PropertyState, message: Option<String>) -> MessageType {
+        set_number(
+            "ABS_FOCUS_POSITION",
+            state,
+            message,
+            &[("FOCUS_ABSOLUTE_POSITION", f64::from(self.position))],
+        )
+    }
+
+    async fn on_absolute(&mut self, update: &NewNumberVector) -> Vec<MessageType> {
+        let Some(target) = number_element(&update.elements, "FOCUS_ABSOLUTE_POSITION") else {
+            return vec![self.position_update(PropertyState::Alert, None)];
+        };
+        match self.goto(target).await {
+            Ok(()) => vec![self.position_update(PropertyState::Busy, None)],
+            Err(e) => vec![self.position_update(PropertyState::Alert, Some(e.to_string()))],
+        }
+    }
+
+    async fn on_relative(&mut self, update: &NewNumberVector) -> Vec<MessageType> {
+        let Some(steps) = number_element(&update.elements, "FOCUS_RELATIVE_POSITION") else {
+            return Vec::new();
+        };
+        let direction = if self.outward { 1.0 } else { -1.0 };
+        let target = f64::from(self.position) + direction * steps;
+        let (state, message) = match self.goto(target).await {
+            Ok(()) => (PropertyState::Busy, None),
+            Err(e) => (PropertyState::Alert, Some(e.to_string())),
+        };
+        vec![
+            set_number(
+                "REL_FOCUS_POSITION",
+                state,
+                message.clone(),
+                &[("FOCUS_RELATIVE_POSITION", steps)],
+            ),
+            self.position_update(state, message),
+        ]
+    }
+
+    async fn on_sync(&mut self, update: &NewNumberVector) -> Vec<MessageType> {
+        let Some(position) = number_element(&update.elements, "FOCUS_SYNC_VALUE") else {
+            return Vec::new();
+        };
+        let position = position.round().clamp(0.0, MAX_POSITION) as u16;
+        let result = self.command(&format!("SP{:04X}", position)).await;
+        let (state, message) = match result {
+            Ok(()) => {
+                self.position = position;
+                (PropertyState::Ok, None)
+            }
+            Err(e) => (PropertyState::Alert, Some(e.to_string())),
+        };
+        vec![
+            set_number(
+                "FOCUS_SYNC",
+                state,
+                message,
+                &[("FOCUS_SYNC_VALUE", f64::from(position))],
+            ),
+            self.position_update(PropertyState::Ok, None),
+        ]
+    }
+
+    async fn on_abort(&mut self) -> Vec<MessageType> {
+        let (state, message) = match self.command("FQ").await {
+            Ok(()) => {
+                self.moving = false;
+                (PropertyState::Ok, None)
+            }
+            Err(e) => (PropertyState::Alert, Some(e.to_string())),
+        };
+        vec![
+            set_switch("FOCUS_ABORT_MOTION", state, message, &[("ABORT", false)]),
+            self.position_update(PropertyState::Idle, None),
+        ]
+    }
+
+    async fn on_step_mode(&mut self, update: &NewSwitchVector) -> Vec<MessageType> {
+        let half_step = switch_on(&update.elements, "FOCUS_HALF_STEP");
+        let command = if half_step { "SH" } else { "SF" };
+        let (state, message) = match self.command(command).await {
+            Ok(()) => {
+                self.half_step = half_step;
+                (PropertyState::Ok, None)
+            }
+            Err(e) => (PropertyState::Alert, Some(e.to_string())),
+        };
+        vec![set_switch(
+            "FOCUS_STEP_MODE",
+            state,
+            message,
+            &[
+                ("FOCUS_HALF_STEP", self.half_step),
+                ("FOCUS_FULL_STEP", !self.half_step),
+            ],
+        )]
+    }
+}
+
+#[async_trait]
+impl Connectable for Moonlite {
+    fn device(&self) -> &str {
+        DEVICE
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        self.port = Some(BufReader::new(self.serial.open()?));
+        let connected = async {
+            let version = self.query("GV").await?;
+            info!(
+                "Connected to Moonlite firmware {:02X} on {}",
+                version,
+                self.serial.port()
+            );
+            self.read_state().await
+        };
+        if let Err(e) = connected.await {
+            self.port = None;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        self.port = None;
+        Ok(())
+    }
+
+    async fn define_disconnected(&mut self) -> Vec<MessageType> {
+        let mut definitions = vec![driver_info()];
+        definitions.extend(self.serial.definitions());
+        definitions
+    }
+}
+
+#[async_trait]
+impl IndiDriver for Moonlite {
+    /// Properties of the connected focuser
+    async fn define_properties(&mut self) -> Vec<MessageType> {
+        vec![
+            number_vector(
+                "ABS_FOCUS_POSITION",
+                "Absolute Position",
+                PropertyPerm::Rw,
+                &[(
+                    "FOCUS_ABSOLUTE_POSITION",
+                    "Steps",
+                    "%.0f",
+                    0.0,
+                    MAX_POSITION,
+                    1000.0,
+                    f64::from(self.position),
+                )],
+            ),
+            number_vector(
+                "REL_FOCUS_POSITION",
+                "Relative Position",
+                PropertyPerm::Rw,
+                &[(
+                    "FOCUS_RELATIVE_POSITION",
+                    "Steps",
+                    "%.0f",
+                    0.0,
+                    50000.0,
+                    1000.0,
+                    0.0,
+                )],
+            ),
+            switch_vector(
+                "FOCUS_MOTION",
+                "Direction",
+                SwitchRule::OneOfMany,
+                &[
+                    ("FOCUS_INWARD", "Focus In", !self.outward),
+                    ("FOCUS_OUTWARD", "Focus Out", self.outward),
+                ],
+            ),
+            switch_vector(
+                "FOCUS_ABORT_MOTION",
+                "Abort Motion",
+                SwitchRule::AtMostOne,
+                &[("ABORT", "Abort", false)],
+            ),
+            number_vector(
+                "FOCUS_SYNC",
+                "Sync",
+                PropertyPerm::Rw,
+                &[(
+                    "FOCUS_SYNC_VALUE",
+                    "Steps",
+                    "%.0f",
+                    0.0,
+                    MAX_POSITION,
+                    1000.0,
+                    f64::from(self.position),
+                )],
+            ),
+            number_vector(
+                "FOCUS_TEMPERATURE",
+                "Temperature",
+                PropertyPerm::Ro,
+                &[(
+                    "TEMPERATURE",
+                    "Celsius",
+                    "%6.2f",
+                    -50.0,
+                    70.0,
+                    0.0,
+                    self.temperature,
+                )],
+            ),
+            switch_vector(
+                "FOCUS_STEP_MODE",
+                "Step Mode",
+                SwitchRule::OneOfMany,
+                &[
+                    ("FOCUS_HALF_STEP", "Half Step", self.half_step),
+                    ("FOCUS_FULL_STEP", "Full Step", !self.half_step),
+                ],
+            ),
+        ]
+    }
+
+    async fn on_new_text(&mut self, update: NewTextVector) -> Vec<MessageType> {
+        self.serial.on_new_text(&update).unwrap_or_default()
+    }
+
+    async fn on_new_number(&mut self, update: NewNumberVector) -> Vec<MessageType> {
+        match update.name.as_str() {
+            "ABS_FOCUS_POSITION" => self.on_absolute(&update).await,
+            "REL_FOCUS_POSITION" => self.on_relative(&update).await,
+            "FOCUS_SYNC" => self.on_sync(&update).await,
+            _ => Vec::new(),
+        }
+    }
+
+    async fn on_new_switch(&mut self, update: NewSwitchVector) -> Vec<MessageType> {
+        if let Some(messages) = self.serial.on_new_switch(&update) {
+            return messages;
+        }
+        match update.name.as_str() {
+            "FOCUS_MOTION" => {
+                self.outward = switch_on(&update.elements, "FOCUS_OUTWARD");
+                vec![set_switch(
+                    "FOCUS_MOTION",
+                    PropertyState::Ok,
+                    None,
+                    &[
+                        ("FOCUS_INWARD", !self.outward),
+                        ("FOCUS_OUTWARD", self.outward),
+                    ],
+                )]
+            }
+            "FOCUS_ABORT_MOTION" => self.on_abort().await,
+            "FOCUS_STEP_MODE" => self.on_step_mode(&update).await,
+            _ => Vec::new(),
+        }
+    }
+
+    fn poll_interval(&self) -> Option<Duration> {
+        Some(POLL_INTERVAL)
+    }
+
+    /// Report the position while moving, and the temperature
+    async fn poll(&mut self) -> Vec<MessageType> {
+        let (position, moving, temperature) = (self.position, self.moving, self.temperature);
+        if let Err(e) = self.read_state().await {
+            warn!("Failed to read the focuser state: {}", e);
+            return vec![self.position_update(PropertyState::Alert, Some(e.to_string()))];
+        }
+        let mut messages = Vec::new();
+        if self.position != position || self.moving != moving {
+            let state = if self.moving {
+                PropertyState::Busy
+            } else {
+                PropertyState::Ok
+            };
+            messages.push(self.position_update(state, None));
+        }
+        if (self.temperature - temperature).abs() >= 0.5 {
+            messages.push(set_number(
+                "FOCUS_TEMPERATURE",
+                PropertyState::Ok,
+                None,
+                &[("TEMPERATURE", self.temperature)],
+            ));
+        }
+        messages
+    }
+}
+
+/// Number element: name, label, format, min, max, step, value
+type Number<'a> = (&'a str, &'a str, &'a str, f64, f64, f64, f64);
+
+fn number_vector(
+    name: &str,
+    label: &str,
+    perm: PropertyPerm,
+    numbers: &[Number<'_>],
+) -> MessageType {
+    MessageType::DefNumberVector(DefNumberVector {
+        device: DEVICE.to_string(),
+        name: name.to_string(),
+        label: label.to_string(),
+        group: GROUP.to_string(),
+        state: PropertyState::Idle,
+        perm,
+        timeout: 60,
+        timestamp: INDITimestamp::now().to_string(),
+        message: None,
+        numbers: numbers
+            .iter()
+            .map(|(name, label, format, min, max, step, value)| DefNumber {
+                name: name.to_string(),
+                label: label.to_string(),
+                format: format.to_string(),
+                min: min.to_string(),
+                max: max.to_string(),
+                step: step.to_string(),
+                value: value.to_string(),
+            })
+            .collect(),
+    })
+}
+
+/// Definition of a switch vector from `(name, label, on)` elements
+fn switch_vector(
+    name: &str,
+    label: &str,
+    rule: SwitchRule,
+    switches: &[(&str, &str, bool)],
+) -> MessageType {
+    MessageType::DefSwitchVector(DefSwitchVector {
+        device: DEVICE.to_string(),
+        name: name.to_string(),
+        label: label.to_string(),
+        group: GROUP.to_string(),
+        state: PropertyState::Idle,
+        perm: PropertyPerm::Rw,
+        rule,
+        timeout: 60,
+        timestamp: INDITimestamp::now().to_string(),
+        message: None,
+        switches: switches
+            .iter()
+            .map(|(name, label, on)| DefSwitch {
+                name: name.to_string(),
+                label: label.to_string(),
+                state: switch_state(*on),
+            })
+            .collect(),
+    })
+}
+
+/// Definition of the read-only `DRIVER_INFO` text vector
+fn driver_info() -> MessageType {
+    let texts = [
+        ("DRIVER_NAME", "Name", DEVICE.to_string()),
+        ("DRIVER_EXEC", "Exec", "moonlite".to_string()),
+        (
+            "DRIVER_VERSION",
+            "Version",
+            env!("CARGO_PKG_VERSION").to_string(),
+        ),
+        (
+            "DRIVER_INTERFACE",
+            "Interface",
+            FOCUSER_INTERFACE.to_string(),
+        ),
+    ];
+    MessageType::DefTextVector(DefTextVector {
+        device: DEVICE.to_string(),
+        name: "DRIVER_INFO".to_string(),
+        label: "Driver Info".to_string(),
+        group: "General Info".to_string(),
+        state: PropertyState::Idle,
+        perm: PropertyPerm::Ro,
+        timeout: 0,
+        timestamp: INDITimestamp::now().to_string(),
+        message: None,
+        texts: texts
+            .into_iter()
+            .map(|(name, label, value)| DefText {
+                name: name.to_string(),
+                label: label.to_string(),
+                value,
+            })
+            .collect(),
+    })
+}
+
+fn set_number(
+    name: &str,
+    state: PropertyState,
+    message: Option<String>,
+    values: &[(&str, f64)],
+) -> MessageType {
+    MessageType::SetNumberVector(SetNumberVector {
+        device: DEVICE.to_string(),
+        name: name.to_string(),
+        state: Some(state),
+        timeout: None,
+        timestamp: Some(INDITimestamp::now().to_string()),
+        message,
+        elements: values
+            .iter()
+            .map(|(name, value)| OneNumber {
+                name: name.to_string(),
+                value: value.to_string(),
+            })
+            .collect(),
+    })
+}
+
+/// Update of a switch vector from `(name, on)` elements
+fn set_switch(
+    name: &str,
+    state: PropertyState,
+    message: Option<String>,
+    values: &[(&str, bool)],
+) -> MessageType {
+    MessageType::SetSwitchVector(SetSwitchVector {
+        device: DEVICE.to_string(),
+        name: name.to_string(),
+        state: Some(state),
+        timeout: None,
+        timestamp: Some(INDITimestamp::now().to_string()),
+        message,
+        elements: values
+            .iter()
+            .map(|(name, on)| OneSwitch {
+                name: name.to_string(),
+                value: switch_state(*on),
+            })
+            .collect(),
+    })
+}
+
+/// Value of element `name` in a `newNumberVector`
+fn number_element(elements: &[OneNumber], name: &str) -> Option<f64> {
+    elements
+        .iter()
+        .find(|element| element.name == name)
+        .and_then(|element| parse_number(&element.value).ok())
+}
+
+/// Returns true if element `name` of a `newSwitchVector` is on
+fn switch_on(elements: &[OneSwitch], name: &str) -> bool {
+    elements
+        .iter()
+        .any(|element| element.name == name && element.value == SwitchState::On)
+}
+
+fn switch_state(on: bool) -> SwitchState {
+    if on {
+        SwitchState::On
+    } else {
+        SwitchState::Off
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // stdout carries the protocol
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .with_writer(std::io::stderr)
+        .init();
+
+    // Restoring a position would move the focuser on connect
+    let driver = ConfigGuard::new(ConnectionGuard::new(Moonlite::new()), DEVICE)?.exclude(&[
+        "ABS_FOCUS_POSITION",
+        "REL_FOCUS_POSITION",
+        "FOCUS_SYNC",
+        "FOCUS_ABORT_MOTION",
+    ]);
+    indi_rs::driver::run(driver).await
+}
@@ -0,0 +1,92 @@
+//! Park the mount and close the dome when the weather turns bad
+//!
+//! Watches the `WEATHER_STATUS` property of a weather station; when the
+//! driver reports it as `Alert` the mount is parked and, if a dome is
+//! given, its shutter is closed.
+//!
+//! ```sh
+//! cargo run --example park_on_rain -- --weather "Weather Simulator" --dome "Dome Simulator"
+//! ```
+
+mod support;
+
+use clap::Parser;
+use indi_rs::devices::{Dome, Telescope};
+use indi_rs::error::Result;
+use indi_rs::message::MessageType;
+use indi_rs::property::PropertyState;
+use support::ServerArgs;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// Overall weather status of a weather station
+const WEATHER_STATUS: &str = "WEATHER_STATUS";
+/// Property identifying a mount
+const EQUATORIAL_EOD_COORD: &str = "EQUATORIAL_EOD_COORD";
+
+#[derive(Parser, Debug)]
+#[command(about = "Park the mount when the weather station raises an alert")]
+struct Cli {
+    #[command(flatten)]
+    server: ServerArgs,
+
+    /// Weather station device, defaults to the first one found
+    #[arg(short, long)]
+    weather: Option<String>,
+
+    /// Mount device, defaults to the first one found
+    #[arg(short, long)]
+    mount: Option<String>,
+
+    /// Dome device whose shutter is closed as well
+    #[arg(short, long)]
+    dome: Option<String>,
+
+    /// Show debug output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    support::init_tracing(cli.verbose);
+
+    let (client, devices) = cli.server.connect().await?;
+    let weather = support::find_device(&devices, WEATHER_STATUS, cli.weather.as_deref())?;
+    let mount = support::find_device(&devices, EQUATORIAL_EOD_COORD, cli.mount.as_deref())?;
+    let telescope = Telescope::new(client.clone(), mount);
+    let dome = cli.dome.map(|dome| Dome::new(client.clone(), dome));
+
+    let (alerts, mut alert) = mpsc::channel(1);
+    client.on_update(move |message| {
+        let alerts = alerts.clone();
+        let weather = weather.clone();
+        async move {
+            if let MessageType::SetLightVector(status) = message.as_ref() {
+                if status.device == weather
+                    && status.name == WEATHER_STATUS
+                    && status.state == Some(PropertyState::Alert)
+                {
+                    // A full channel means an alert is already pending
+                    let _ = alerts.try_send(());
+                }
+            }
+        }
+    });
+
+    info!("Watching the weather, press Ctrl-C to stop");
+    tokio::select! {
+        _ = alert.recv() => {
+            warn!("Weather alert, parking {}", telescope.device());
+            telescope.park().await?;
+            if let Some(dome) = dome {
+                info!("Closing {}", dome.device());
+                dome.close_shutter().await?;
+            }
+            info!("Observatory is safe");
+        }
+        _ = tokio::signal::ctrl_c() => info!("Stopped"),
+    }
+    Ok(())
+}
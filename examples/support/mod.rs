@@ -0,0 +1,74 @@
+//! Helpers shared by the examples
+//!
+//! Every example connects to one INDI server given on the command line and
+//! logs through `tracing`. Not every example uses every helper.
+
+#![allow(dead_code)]
+
+use clap::Args;
+use indi_rs::client::{Client, ClientConfig, DeviceInfo};
+use indi_rs::error::{Error, Result};
+use std::time::Duration;
+use tracing::info;
+
+/// Time allowed for the server to send its property definitions
+pub const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Command line options selecting the INDI server
+#[derive(Args, Debug, Clone)]
+pub struct ServerArgs {
+    /// INDI server host
+    #[arg(short = 'H', long, default_value = "localhost")]
+    pub host: String,
+
+    /// INDI server port
+    #[arg(short = 'P', long, default_value_t = ClientConfig::DEFAULT_PORT)]
+    pub port: u16,
+}
+
+impl ServerArgs {
+    /// Connect to the server and wait for the device definitions
+    pub async fn connect(&self) -> Result<(Client, Vec<DeviceInfo>)> {
+        let client = Client::builder()
+            .host(&self.host)
+            .port(self.port)
+            .build()
+            .await?;
+        let devices = client.discover(DISCOVERY_TIMEOUT).await?;
+        info!(
+            "Connected to {}:{}, found {} devices",
+            self.host,
+            self.port,
+            devices.len()
+        );
+        Ok((client, devices))
+    }
+}
+
+/// Log to stdout, including debug output when `verbose` is set
+pub fn init_tracing(verbose: bool) {
+    let level = if verbose {
+        tracing::Level::DEBUG
+    } else {
+        tracing::Level::INFO
+    };
+    tracing_subscriber::fmt().with_max_level(level).init();
+}
+
+/// Name of the device defining `property`, or `preferred` if given
+///
+/// Picks the first matching device when several define the property.
+pub fn find_device(
+    devices: &[DeviceInfo],
+    property: &str,
+    preferred: Option<&str>,
+) -> Result<String> {
+    if let Some(device) = preferred {
+        return Ok(device.to_string());
+    }
+    devices
+        .iter()
+        .find(|device| device.properties.iter().any(|p| p == property))
+        .map(|device| device.name.clone())
+        .ok_or_else(|| Error::Property(format!("No device defines {}", property)))
+}
@@ -0,0 +1,133 @@
+//! Backend for a browser dashboard
+//!
+//! Serves the property tree of an INDI server over WebSocket as JSON. A new
+//! connection first receives a `snapshot` of all properties, followed by a
+//! `property` message for every definition or update and a `delete`
+//! message when a driver removes properties.
+//!
+//! ```sh
+//! cargo run --example websocket_dashboard --features serde_json -- --listen 127.0.0.1:8080
+//! ```
+
+mod support;
+
+use clap::Parser;
+use futures_util::SinkExt;
+use indi_rs::client::{Client, ClientEvent};
+use indi_rs::error::{Error, Result};
+use indi_rs::message::MessageType;
+use serde_json::json;
+use std::net::SocketAddr;
+use support::ServerArgs;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::error::RecvError;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{info, warn};
+
+#[derive(Parser, Debug)]
+#[command(about = "Stream INDI properties to WebSocket clients")]
+struct Cli {
+    #[command(flatten)]
+    server: ServerArgs,
+
+    /// Address to accept WebSocket connections on
+    #[arg(short, long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// Show debug output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+/// Device and property name of a definition or update
+fn property_key(message: &MessageType) -> Option<(&str, &str)> {
+    match message {
+        MessageType::DefTextVector(m) => Some((&m.device, &m.name)),
+        MessageType::DefNumberVector(m) => Some((&m.device, &m.name)),
+        MessageType::DefSwitchVector(m) => Some((&m.device, &m.name)),
+        MessageType::DefLightVector(m) => Some((&m.device, &m.name)),
+        MessageType::DefBlobVector(m) => Some((&m.device, &m.name)),
+        MessageType::SetTextVector(m) => Some((&m.device, &m.name)),
+        MessageType::SetNumberVector(m) => Some((&m.device, &m.name)),
+        MessageType::SetSwitchVector(m) => Some((&m.device, &m.name)),
+        MessageType::SetLightVector(m) => Some((&m.device, &m.name)),
+        MessageType::SetBlobVector(m) => Some((&m.device, &m.name)),
+        _ => None,
+    }
+}
+
+/// JSON message announcing the change made by `message`, if any
+async fn change(client: &Client, message: &MessageType) -> Result<Option<serde_json::Value>> {
+    if let MessageType::DelProperty(del) = message {
+        return Ok(Some(json!({
+            "type": "delete",
+            "device": del.device,
+            "name": del.name,
+        })));
+    }
+    let Some((device, name)) = property_key(message) else {
+        return Ok(None);
+    };
+    let state = client.state();
+    let state = state.lock().await;
+    let Some(property) = state.get_property(device, name) else {
+        return Ok(None);
+    };
+    let property =
+        serde_json::to_value(property).map_err(|e| Error::SerializationError(e.to_string()))?;
+    Ok(Some(json!({ "type": "property", "property": property })))
+}
+
+/// Stream the property tree to one WebSocket client until it disconnects
+async fn serve(client: Client, stream: TcpStream) -> Result<()> {
+    let closed = |e| Error::Message(format!("WebSocket error: {}", e));
+    let mut socket = tokio_tungstenite::accept_async(stream)
+        .await
+        .map_err(closed)?;
+    // Subscribe before taking the snapshot so no change is missed
+    let mut events = client.subscribe();
+    let snapshot = client.state().lock().await.snapshot()?;
+    let snapshot = json!({ "type": "snapshot", "state": snapshot });
+    socket
+        .send(Message::text(snapshot.to_string()))
+        .await
+        .map_err(closed)?;
+
+    loop {
+        let message = match events.recv().await {
+            Ok(ClientEvent::Message(message)) => message,
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Dashboard client missed {} updates", skipped);
+                continue;
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        };
+        if let Some(change) = change(&client, &message).await? {
+            socket
+                .send(Message::text(change.to_string()))
+                .await
+                .map_err(closed)?;
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    support::init_tracing(cli.verbose);
+
+    let (client, _) = cli.server.connect().await?;
+    let listener = TcpListener::bind(cli.listen).await?;
+    info!("Serving dashboard updates on ws://{}", cli.listen);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        info!("Dashboard client {} connected", peer);
+        let client = client.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve(client, stream).await {
+                info!("Dashboard client {} disconnected: {}", peer, e);
+            }
+        });
+    }
+}
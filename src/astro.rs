@@ -0,0 +1,346 @@
+//! Astronomical coordinate types
+//!
+//! [`RightAscension`], [`Declination`] and [`AltAz`] convert between the
+//! sexagesimal strings INDI drivers send and plain hours or degrees, and
+//! [`Equatorial`] and [`AltAz`] read and write the standard
+//! `EQUATORIAL_EOD_COORD` and `HORIZONTAL_COORD` mount properties.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::client::{Client, ClientState};
+use crate::error::{Error, Result};
+use crate::format::{parse_number, NumberFormat};
+use crate::property::Property;
+
+/// Equatorial coordinates of date property of a mount
+pub const EQUATORIAL_EOD_COORD: &str = "EQUATORIAL_EOD_COORD";
+/// Right ascension element of [`EQUATORIAL_EOD_COORD`], in hours
+pub const RA: &str = "RA";
+/// Declination element of [`EQUATORIAL_EOD_COORD`], in degrees
+pub const DEC: &str = "DEC";
+/// Horizontal coordinates property of a mount
+pub const HORIZONTAL_COORD: &str = "HORIZONTAL_COORD";
+/// Altitude element of [`HORIZONTAL_COORD`], in degrees
+pub const ALT: &str = "ALT";
+/// Azimuth element of [`HORIZONTAL_COORD`], in degrees
+pub const AZ: &str = "AZ";
+
+/// Right ascension, kept in `0..24` hours
+///
+/// Displays as `hh:mm:ss`; a precision adds decimals to the seconds, so
+/// `{:.1}` gives `hh:mm:ss.s`.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct RightAscension(f64);
+
+impl RightAscension {
+    /// Right ascension of `hours`, wrapped into `0..24`
+    pub fn from_hours(hours: f64) -> Self {
+        Self(hours.rem_euclid(24.0))
+    }
+
+    /// Right ascension of `degrees`, wrapped into `0..360`
+    pub fn from_degrees(degrees: f64) -> Self {
+        Self::from_hours(degrees / 15.0)
+    }
+
+    /// Hours, `0..24`
+    pub fn hours(&self) -> f64 {
+        self.0
+    }
+
+    /// Degrees, `0..360`
+    pub fn degrees(&self) -> f64 {
+        self.0 * 15.0
+    }
+}
+
+impl FromStr for RightAscension {
+    type Err = Error;
+
+    /// Parse decimal or sexagesimal hours, e.g. `12:34:56.7`
+    fn from_str(text: &str) -> Result<Self> {
+        Ok(Self::from_hours(parse_number(text)?))
+    }
+}
+
+impl fmt::Display for RightAscension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_sexagesimal(f, self.0)
+    }
+}
+
+/// Declination, `-90..=90` degrees
+///
+/// Displays as `dd:mm:ss` like [`RightAscension`].
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Declination(f64);
+
+impl Declination {
+    /// Declination of `degrees`
+    ///
+    /// Fails if `degrees` is outside `-90..=90`.
+    pub fn from_degrees(degrees: f64) -> Result<Self> {
+        check_latitude("Declination", degrees)?;
+        Ok(Self(degrees))
+    }
+
+    /// Degrees, `-90..=90`
+    pub fn degrees(&self) -> f64 {
+        self.0
+    }
+}
+
+impl FromStr for Declination {
+    type Err = Error;
+
+    /// Parse decimal or sexagesimal degrees, e.g. `-20:30:00`
+    fn from_str(text: &str) -> Result<Self> {
+        Self::from_degrees(parse_number(text)?)
+    }
+}
+
+impl fmt::Display for Declination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_sexagesimal(f, self.0)
+    }
+}
+
+/// Equatorial coordinates of date
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Equatorial {
+    /// Right ascension
+    pub ra: RightAscension,
+    /// Declination
+    pub dec: Declination,
+}
+
+impl Equatorial {
+    /// Coordinates of an `EQUATORIAL_EOD_COORD` property
+    pub fn from_property(property: &Property) -> Result<Self> {
+        Ok(Self {
+            ra: RightAscension::from_hours(element(property, RA)?),
+            dec: Declination::from_degrees(element(property, DEC)?)?,
+        })
+    }
+
+    /// Read `EQUATORIAL_EOD_COORD` of `device` from the client state
+    pub async fn read(client: &Client, device: &str) -> Result<Self> {
+        let state = client.state();
+        let state = state.lock().await;
+        Self::from_property(defined(&state, device, EQUATORIAL_EOD_COORD)?)
+    }
+
+    /// Send these coordinates to `EQUATORIAL_EOD_COORD` of `device`
+    ///
+    /// What the mount does with them depends on its `ON_COORD_SET`
+    /// switch; use [`Telescope`](crate::devices::Telescope) to slew and
+    /// wait for the result.
+    pub async fn write(&self, client: &Client, device: &str) -> Result<()> {
+        client
+            .set_number(
+                device,
+                EQUATORIAL_EOD_COORD,
+                &[(RA, self.ra.hours()), (DEC, self.dec.degrees())],
+            )
+            .await
+    }
+}
+
+impl fmt::Display for Equatorial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.ra, f)?;
+        f.write_str(" ")?;
+        fmt::Display::fmt(&self.dec, f)
+    }
+}
+
+/// Horizontal coordinates: altitude `-90..=90` and azimuth `0..360`
+/// degrees
+///
+/// Displays as `alt az`, both as `dd:mm:ss` like [`RightAscension`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AltAz {
+    alt: f64,
+    az: f64,
+}
+
+impl AltAz {
+    /// Coordinates of `alt` and `az` degrees, with `az` wrapped into
+    /// `0..360`
+    ///
+    /// Fails if `alt` is outside `-90..=90`.
+    pub fn new(alt: f64, az: f64) -> Result<Self> {
+        check_latitude("Altitude", alt)?;
+        Ok(Self {
+            alt,
+            az: az.rem_euclid(360.0),
+        })
+    }
+
+    /// Altitude in degrees
+    pub fn alt(&self) -> f64 {
+        self.alt
+    }
+
+    /// Azimuth in degrees
+    pub fn az(&self) -> f64 {
+        self.az
+    }
+
+    /// Coordinates of a `HORIZONTAL_COORD` property
+    pub fn from_property(property: &Property) -> Result<Self> {
+        Self::new(element(property, ALT)?, element(property, AZ)?)
+    }
+
+    /// Read `HORIZONTAL_COORD` of `device` from the client state
+    pub async fn read(client: &Client, device: &str) -> Result<Self> {
+        let state = client.state();
+        let state = state.lock().await;
+        Self::from_property(defined(&state, device, HORIZONTAL_COORD)?)
+    }
+
+    /// Send these coordinates to `HORIZONTAL_COORD` of `device`
+    pub async fn write(&self, client: &Client, device: &str) -> Result<()> {
+        client
+            .set_number(device, HORIZONTAL_COORD, &[(ALT, self.alt), (AZ, self.az)])
+            .await
+    }
+}
+
+impl FromStr for AltAz {
+    type Err = Error;
+
+    /// Parse altitude and azimuth separated by a comma, e.g.
+    /// `45:30:00, 180`
+    fn from_str(text: &str) -> Result<Self> {
+        let (alt, az) = text
+            .split_once(',')
+            .ok_or_else(|| Error::ParseError(format!("Expected 'alt, az', got '{}'", text)))?;
+        Self::new(parse_number(alt)?, parse_number(az)?)
+    }
+}
+
+impl fmt::Display for AltAz {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_sexagesimal(f, self.alt)?;
+        f.write_str(" ")?;
+        write_sexagesimal(f, self.az)
+    }
+}
+
+/// Write `value` as `%.6m`, or with the formatter's precision as decimals
+/// of the seconds
+fn write_sexagesimal(f: &mut fmt::Formatter<'_>, value: f64) -> fmt::Result {
+    let precision = match f.precision() {
+        None | Some(0) => 6,
+        Some(decimals) => 7 + decimals,
+    };
+    let format: NumberFormat = format!("%.{}m", precision)
+        .parse()
+        .map_err(|_| fmt::Error)?;
+    f.write_str(&format.format(value))
+}
+
+/// Fail unless `degrees` is within `-90..=90`
+fn check_latitude(what: &str, degrees: f64) -> Result<()> {
+    match (-90.0..=90.0).contains(&degrees) {
+        true => Ok(()),
+        false => Err(Error::ParseError(format!(
+            "{} {} is outside -90..90 degrees",
+            what, degrees
+        ))),
+    }
+}
+
+/// Value of number `element` of a coordinate property
+fn element(property: &Property, element: &str) -> Result<f64> {
+    property.value.number(element).ok_or_else(|| {
+        Error::Property(format!(
+            "{}.{} has no number element {}",
+            property.device, property.name, element
+        ))
+    })
+}
+
+/// Property `name` of `device` in the client state
+fn defined<'a>(state: &'a ClientState, device: &str, name: &str) -> Result<&'a Property> {
+    state
+        .get_property(device, name)
+        .ok_or_else(|| Error::Property(format!("{}.{} is not defined", device, name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::{mock_server, wait_for_property};
+    use crate::client::wait_for_ok;
+    use std::time::Duration;
+
+    #[test]
+    fn test_sexagesimal_round_trip() {
+        let ra: RightAscension = "12:34:56.7".parse().unwrap();
+        assert!((ra.hours() - (12.0 + 34.0 / 60.0 + 56.7 / 3600.0)).abs() < 1e-12);
+        assert_eq!(ra.to_string(), "12:34:57");
+        assert_eq!(format!("{:.1}", ra), "12:34:56.7");
+        assert_eq!(RightAscension::from_degrees(-15.0).hours(), 23.0);
+        assert_eq!(RightAscension::from_hours(25.5).degrees(), 22.5);
+
+        let dec: Declination = "-20 30".parse().unwrap();
+        assert_eq!(dec.degrees(), -20.5);
+        assert_eq!(dec.to_string(), "-20:30:00");
+        assert_eq!(format!("{:.2}", dec), "-20:30:00.00");
+        assert!("91".parse::<Declination>().is_err());
+        assert!("north".parse::<Declination>().is_err());
+
+        let horizontal: AltAz = "45:30, -90".parse().unwrap();
+        assert_eq!((horizontal.alt(), horizontal.az()), (45.5, 270.0));
+        assert_eq!(horizontal.to_string(), "45:30:00 270:00:00");
+        assert!(AltAz::new(-90.5, 0.0).is_err());
+        assert!("45".parse::<AltAz>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_write_coordinates() {
+        let config = mock_server(
+            r#"<defNumberVector device="Mount" name="EQUATORIAL_EOD_COORD" state="Idle" perm="rw"><defNumber name="RA" format="%010.6m" min="0" max="24" step="0">6:30:00</defNumber><defNumber name="DEC" format="%010.6m" min="-90" max="90" step="0">-10.25</defNumber></defNumberVector>
+<defNumberVector device="Mount" name="HORIZONTAL_COORD" state="Idle" perm="rw"><defNumber name="ALT" format="%010.6m" min="-90" max="90" step="0">30</defNumber><defNumber name="AZ" format="%010.6m" min="0" max="360" step="0">120</defNumber></defNumberVector>
+"#,
+            "newNumberVector",
+            r#"<setNumberVector device="Mount" name="HORIZONTAL_COORD" state="Ok"><oneNumber name="ALT">60</oneNumber><oneNumber name="AZ">10</oneNumber></setNumberVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        wait_for_property(&client, "Mount", HORIZONTAL_COORD).await;
+
+        let equatorial = Equatorial::read(&client, "Mount").await.unwrap();
+        assert_eq!(equatorial.ra.hours(), 6.5);
+        assert_eq!(equatorial.dec.degrees(), -10.25);
+        assert_eq!(equatorial.to_string(), "6:30:00 -10:15:00");
+        assert_eq!(
+            AltAz::read(&client, "Mount").await.unwrap(),
+            AltAz::new(30.0, 120.0).unwrap()
+        );
+
+        let mut events = client.subscribe();
+        AltAz::new(60.0, 10.0)
+            .unwrap()
+            .write(&client, "Mount")
+            .await
+            .unwrap();
+        wait_for_ok(
+            &mut events,
+            "Mount",
+            HORIZONTAL_COORD,
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            AltAz::read(&client, "Mount").await.unwrap(),
+            AltAz::new(60.0, 10.0).unwrap()
+        );
+        assert!(Equatorial::read(&client, "Dome").await.is_err());
+    }
+}
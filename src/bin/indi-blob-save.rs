@@ -0,0 +1,199 @@
+use clap::Parser;
+use indi_rs::client::connection::Connection;
+use indi_rs::client::{Client, ClientConfig, ClientEvent};
+use indi_rs::message::new::OneBlob;
+use indi_rs::message::set::SetBlobVector;
+use indi_rs::message::MessageType;
+use indi_rs::property::timestamp::INDITimestamp;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+/// Save BLOBs received from INDI devices to files
+///
+/// Files are named `device_property_element_timestamp` with the BLOB format
+/// as extension, e.g. `CCD_Simulator_CCD1_CCD1_20240101T220000.000.fits`.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// INDI server host
+    #[arg(short = 'H', long, default_value = "localhost")]
+    host: String,
+
+    /// INDI server port
+    #[arg(short = 'p', long, default_value_t = 7624)]
+    port: u16,
+
+    /// Directory to write the files to
+    #[arg(short = 'o', long, default_value = ".")]
+    dir: PathBuf,
+
+    /// Only save BLOBs of this property
+    #[arg(short = 'P', long)]
+    property: Option<String>,
+
+    /// Exit after saving this many files
+    #[arg(short = 'n', long)]
+    count: Option<usize>,
+
+    /// Decompress `.z` payloads before writing them
+    #[cfg(feature = "zlib")]
+    #[arg(short = 'z', long)]
+    decompress: bool,
+
+    /// Log protocol traffic
+    #[arg(short = 'v', long)]
+    verbose: bool,
+
+    /// Devices to receive BLOBs from
+    #[arg(required = true)]
+    devices: Vec<String>,
+}
+
+/// Replace characters that are awkward in file names
+fn file_name_part(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// File name stem and extension for `blob` of `vector`, in the format of
+/// the written bytes
+fn file_name(vector: &SetBlobVector, blob: &OneBlob, format: &str) -> (String, String) {
+    // Drivers timestamp their vectors; fall back to the arrival time
+    let timestamp = vector
+        .timestamp
+        .as_deref()
+        .and_then(|ts| ts.parse::<INDITimestamp>().ok())
+        .unwrap_or_else(INDITimestamp::now);
+    let format = if format.is_empty() || format.starts_with('.') {
+        format.to_string()
+    } else {
+        format!(".{}", format)
+    };
+    let stem = format!(
+        "{}_{}_{}_{}",
+        file_name_part(&vector.device),
+        file_name_part(&vector.name),
+        file_name_part(&blob.name),
+        timestamp.datetime().format("%Y%m%dT%H%M%S%.3f"),
+    );
+    (stem, file_name_part(&format))
+}
+
+/// Write `data` to `stem` with `extension` in `dir`, numbering the stem if
+/// the name is taken
+async fn write_new(
+    dir: &Path,
+    (stem, extension): (String, String),
+    data: &[u8],
+) -> std::io::Result<PathBuf> {
+    let mut path = dir.join(format!("{}{}", stem, extension));
+    for n in 1.. {
+        match tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+        {
+            Ok(mut file) => {
+                tokio::io::AsyncWriteExt::write_all(&mut file, data).await?;
+                break;
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                path = dir.join(format!("{}-{}{}", stem, n, extension));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(path)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    tracing_subscriber::fmt()
+        .with_max_level(if args.verbose {
+            tracing::Level::DEBUG
+        } else {
+            tracing::Level::INFO
+        })
+        .with_target(false)
+        .init();
+
+    tokio::fs::create_dir_all(&args.dir).await?;
+
+    let config = ClientConfig::new(args.host.clone(), args.port);
+    info!(
+        "Connecting to INDI server at {}:{}",
+        config.host, config.port
+    );
+    let mut client = Client::new(config).await?;
+    let mut events = client.subscribe();
+    for device in &args.devices {
+        client.get_properties(Some(device), None).await?;
+        client
+            .enable_blob(device, args.property.as_deref(), "Also")
+            .await?;
+    }
+
+    let mut saved = 0;
+    while args.count.map_or(true, |count| saved < count) {
+        let message = match events.recv().await {
+            Ok(ClientEvent::Message(message)) => message,
+            Ok(ClientEvent::Disconnected) | Err(RecvError::Closed) => break,
+            Ok(_) => continue,
+            Err(RecvError::Lagged(n)) => {
+                warn!("Missed {} messages, BLOBs may have been dropped", n);
+                continue;
+            }
+        };
+        let MessageType::SetBlobVector(vector) = &*message else {
+            continue;
+        };
+        if !args.devices.contains(&vector.device)
+            || args.property.as_ref().is_some_and(|p| *p != vector.name)
+        {
+            continue;
+        }
+
+        for blob in &vector.elements {
+            if blob.value.is_empty() {
+                continue;
+            }
+            #[cfg(feature = "zlib")]
+            let (data, format) = if args.decompress {
+                (blob.get_data(), blob.data_format())
+            } else {
+                (blob.get_raw_data(), blob.format.as_str())
+            };
+            #[cfg(not(feature = "zlib"))]
+            let (data, format) = (blob.get_raw_data(), blob.format.as_str());
+            let data = match data {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Skipping {}.{}: {}", vector.name, blob.name, e);
+                    continue;
+                }
+            };
+
+            let path = write_new(&args.dir, file_name(vector, blob, format), &data).await?;
+            info!("Saved {} bytes to {}", data.len(), path.display());
+            saved += 1;
+            if args.count.is_some_and(|count| saved >= count) {
+                break;
+            }
+        }
+    }
+
+    Connection::disconnect(&mut client).await?;
+    Ok(())
+}
@@ -0,0 +1,483 @@
+use clap::Parser;
+use indi_rs::client::{Client, ClientEvent, ClientState};
+use indi_rs::format::parse_number;
+use indi_rs::property::{PropertyState, PropertyValue, SwitchState};
+use std::collections::BTreeSet;
+use std::process::ExitCode;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{timeout_at, Instant};
+use tracing::{debug, error};
+
+/// Evaluate an expression over live INDI property values
+///
+/// Elements are referenced as `"device.property.element"` in double
+/// quotes; the pseudo element `_STATE` gives the property state as 0 for
+/// Idle, 1 Ok, 2 Busy and 3 Alert. Switches are 1 when On, lights have the
+/// value of their state. Supports `+ - * / % ^`, comparisons, `&& || !`,
+/// the constants `pi` and `e`, and `abs sqrt exp ln log10 sin cos tan
+/// asin acos atan floor ceil round min max pow`, with angles in degrees.
+///
+/// Exits with 0 if the expression is true (not 0), 1 if false and 2 on
+/// errors, like indilib's `indi_eval`.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// INDI server host
+    #[arg(short = 'H', long, default_value = "localhost")]
+    host: String,
+
+    /// INDI server port
+    #[arg(short = 'p', long, default_value_t = 7624)]
+    port: u16,
+
+    /// Wait until the expression becomes true
+    #[arg(short = 'w', long)]
+    wait: bool,
+
+    /// Longest time in seconds to wait for the elements and, with `-w`,
+    /// for the expression to become true
+    #[arg(short = 't', long, default_value_t = 2.0)]
+    timeout: f64,
+
+    /// Print the final value of the expression
+    #[arg(short = 'o', long)]
+    print: bool,
+
+    /// Print the value every time it is evaluated
+    #[arg(short = 'e', long)]
+    each: bool,
+
+    /// Log protocol traffic to stderr
+    #[arg(short = 'v', long)]
+    verbose: bool,
+
+    /// Expression to evaluate
+    expression: String,
+}
+
+/// Parsed expression
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    /// `device`, `property`, `element`
+    Element(String, String, String),
+    Unary(char, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+/// Binary operators by precedence, loosest first
+const PRECEDENCE: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["==", "!="],
+    &["<=", ">=", "<", ">"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+/// Recursive descent parser over the expression text
+struct ExprParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn parse(text: &'a str) -> Result<Expr, String> {
+        let mut parser = Self { text, pos: 0 };
+        let expr = parser.binary(0)?;
+        parser.skip_space();
+        match parser.rest().is_empty() {
+            true => Ok(expr),
+            false => Err(format!("Unexpected '{}'", parser.rest())),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_space(&mut self) {
+        self.pos = self.text.len() - self.rest().trim_start().len();
+    }
+
+    /// Consume `token` if it comes next
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            return true;
+        }
+        false
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        let Some(operators) = PRECEDENCE.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        'operands: loop {
+            for operator in *operators {
+                if self.eat(operator) {
+                    let right = self.binary(level + 1)?;
+                    left = Expr::Binary(operator, Box::new(left), Box::new(right));
+                    continue 'operands;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        for operator in ['-', '!', '+'] {
+            if self.eat(&operator.to_string()) {
+                return Ok(Expr::Unary(operator, Box::new(self.unary()?)));
+            }
+        }
+        let base = self.primary()?;
+        match self.eat("^") {
+            true => Ok(Expr::Binary("^", Box::new(base), Box::new(self.unary()?))),
+            false => Ok(base),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        self.skip_space();
+        let rest = self.rest();
+        if self.eat("(") {
+            let expr = self.binary(0)?;
+            return match self.eat(")") {
+                true => Ok(expr),
+                false => Err("Missing ')'".to_string()),
+            };
+        }
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').ok_or("Missing closing '\"'")?;
+            let name = &quoted[..end];
+            self.pos += end + 2;
+            let mut parts = name.splitn(3, '.');
+            return match (parts.next(), parts.next(), parts.next()) {
+                (Some(device), Some(property), Some(element)) => Ok(Expr::Element(
+                    device.to_string(),
+                    property.to_string(),
+                    element.to_string(),
+                )),
+                _ => Err(format!(
+                    "Expected \"device.property.element\", got \"{}\"",
+                    name
+                )),
+            };
+        }
+        if rest.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+            let mut end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            if let Some(exponent) = rest[end..].strip_prefix(['e', 'E']) {
+                let unsigned = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+                let digits = unsigned
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(unsigned.len());
+                if digits > 0 {
+                    end = rest.len() - unsigned.len() + digits;
+                }
+            }
+            self.pos += end;
+            let number = &rest[..end];
+            return number
+                .parse()
+                .map(Expr::Number)
+                .map_err(|_| format!("Invalid number '{}'", number));
+        }
+        let length = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let word = &rest[..length];
+        if word.is_empty() {
+            return Err(match rest.is_empty() {
+                true => "Unexpected end of expression".to_string(),
+                false => format!("Unexpected '{}'", rest),
+            });
+        }
+        self.pos += length;
+        match word {
+            "pi" => return Ok(Expr::Number(std::f64::consts::PI)),
+            "e" => return Ok(Expr::Number(std::f64::consts::E)),
+            _ => (),
+        }
+        if !self.eat("(") {
+            return Err(format!("Unknown name '{}'", word));
+        }
+        let mut args = Vec::new();
+        if !self.eat(")") {
+            loop {
+                args.push(self.binary(0)?);
+                if self.eat(")") {
+                    break;
+                }
+                if !self.eat(",") {
+                    return Err(format!("Expected ',' or ')' in call of {}", word));
+                }
+            }
+        }
+        Ok(Expr::Call(word.to_string(), args))
+    }
+}
+
+impl Expr {
+    /// `device`/`property` pairs the expression reads
+    fn properties(&self, out: &mut BTreeSet<(String, String)>) {
+        match self {
+            Expr::Number(_) => (),
+            Expr::Element(device, property, _) => {
+                out.insert((device.clone(), property.clone()));
+            }
+            Expr::Unary(_, operand) => operand.properties(out),
+            Expr::Binary(_, left, right) => {
+                left.properties(out);
+                right.properties(out);
+            }
+            Expr::Call(_, args) => args.iter().for_each(|arg| arg.properties(out)),
+        }
+    }
+
+    /// Evaluate with element values from `state`
+    fn eval(&self, state: &ClientState) -> Result<f64, String> {
+        let truth = |value: bool| if value { 1.0 } else { 0.0 };
+        Ok(match self {
+            Expr::Number(number) => *number,
+            Expr::Element(device, property, element) => {
+                element_value(state, device, property, element)?
+            }
+            Expr::Unary(operator, operand) => {
+                let value = operand.eval(state)?;
+                match operator {
+                    '-' => -value,
+                    '!' => truth(value == 0.0),
+                    _ => value,
+                }
+            }
+            Expr::Binary(operator, left, right) => {
+                let left = left.eval(state)?;
+                // Short-circuit so the other side may be undefined
+                match *operator {
+                    "&&" if left == 0.0 => return Ok(0.0),
+                    "||" if left != 0.0 => return Ok(1.0),
+                    _ => (),
+                }
+                let right = right.eval(state)?;
+                match *operator {
+                    "&&" | "||" => truth(right != 0.0),
+                    "==" => truth(left == right),
+                    "!=" => truth(left != right),
+                    "<=" => truth(left <= right),
+                    ">=" => truth(left >= right),
+                    "<" => truth(left < right),
+                    ">" => truth(left > right),
+                    "+" => left + right,
+                    "-" => left - right,
+                    "*" => left * right,
+                    "/" => left / right,
+                    "%" => left % right,
+                    _ => left.powf(right),
+                }
+            }
+            Expr::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(state))
+                    .collect::<Result<Vec<_>, _>>()?;
+                call(name, &args)?
+            }
+        })
+    }
+}
+
+/// Apply function `name`
+fn call(name: &str, args: &[f64]) -> Result<f64, String> {
+    let unary: Option<fn(f64) -> f64> = match name {
+        "abs" => Some(f64::abs),
+        "sqrt" => Some(f64::sqrt),
+        "exp" => Some(f64::exp),
+        "ln" => Some(f64::ln),
+        "log10" => Some(f64::log10),
+        "sin" => Some(|x: f64| x.to_radians().sin()),
+        "cos" => Some(|x: f64| x.to_radians().cos()),
+        "tan" => Some(|x: f64| x.to_radians().tan()),
+        "asin" => Some(|x: f64| x.asin().to_degrees()),
+        "acos" => Some(|x: f64| x.acos().to_degrees()),
+        "atan" => Some(|x: f64| x.atan().to_degrees()),
+        "floor" => Some(f64::floor),
+        "ceil" => Some(f64::ceil),
+        "round" => Some(f64::round),
+        _ => None,
+    };
+    let binary: Option<fn(f64, f64) -> f64> = match name {
+        "min" => Some(f64::min),
+        "max" => Some(f64::max),
+        "pow" => Some(f64::powf),
+        _ => None,
+    };
+    match (unary, binary, args) {
+        (Some(function), _, [x]) => Ok(function(*x)),
+        (_, Some(function), [x, y]) => Ok(function(*x, *y)),
+        (Some(_), _, _) => Err(format!("{} takes one argument", name)),
+        (_, Some(_), _) => Err(format!("{} takes two arguments", name)),
+        _ => Err(format!("Unknown function '{}'", name)),
+    }
+}
+
+/// Numeric value of an element, or of the property state for `_STATE`
+fn element_value(
+    state: &ClientState,
+    device: &str,
+    name: &str,
+    element: &str,
+) -> Result<f64, String> {
+    let state_value = |state: PropertyState| match state {
+        PropertyState::Idle => 0.0,
+        PropertyState::Ok => 1.0,
+        PropertyState::Busy => 2.0,
+        PropertyState::Alert => 3.0,
+    };
+    let property = state
+        .get_property(device, name)
+        .ok_or_else(|| format!("{}.{} is not defined", device, name))?;
+    if element == "_STATE" {
+        return Ok(state_value(property.state));
+    }
+    let missing = || format!("{}.{} has no element {}", device, name, element);
+    match &property.value {
+        PropertyValue::NumberVector(_) => property.value.number(element).ok_or_else(missing),
+        PropertyValue::SwitchVector(..) => match property.value.switch(element) {
+            Some(SwitchState::On) => Ok(1.0),
+            Some(SwitchState::Off) => Ok(0.0),
+            None => Err(missing()),
+        },
+        PropertyValue::TextVector(_) => {
+            let text = property.value.text(element).ok_or_else(missing)?;
+            parse_number(text).map_err(|e| e.to_string())
+        }
+        PropertyValue::Light(_) => property
+            .elements
+            .iter()
+            .flatten()
+            .find(|light| light.name == element)
+            .map(|light| state_value(light.state))
+            .ok_or_else(missing),
+        _ => Err(format!("{}.{} has no numeric value", device, name)),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+
+    tracing_subscriber::fmt()
+        .with_max_level(if args.verbose {
+            tracing::Level::DEBUG
+        } else {
+            tracing::Level::WARN
+        })
+        .with_target(false)
+        .with_writer(std::io::stderr)
+        .init();
+
+    match run(&args).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            error!("{}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Evaluate the expression, returning whether it ended up true
+async fn run(args: &Args) -> Result<bool, Box<dyn std::error::Error>> {
+    let expr = ExprParser::parse(&args.expression)?;
+    let mut properties = BTreeSet::new();
+    expr.properties(&mut properties);
+
+    let client = Client::builder()
+        .host(args.host.clone())
+        .port(args.port)
+        .build()
+        .await?;
+    let mut events = client.subscribe();
+    for (device, property) in &properties {
+        client.get_properties(Some(device), Some(property)).await?;
+    }
+
+    let deadline = Instant::now() + Duration::from_secs_f64(args.timeout);
+    let value = loop {
+        // Undefined elements are retried until the deadline
+        let value = expr.eval(&*client.state().lock().await);
+        match &value {
+            Ok(value) if args.each => println!("{}", value),
+            Ok(_) => (),
+            Err(e) => debug!("{}", e),
+        }
+        if matches!(value, Ok(value) if !args.wait || value != 0.0) {
+            break value;
+        }
+        match timeout_at(deadline, events.recv()).await {
+            Ok(Ok(ClientEvent::Disconnected)) | Ok(Err(RecvError::Closed)) => {
+                return Err("Connection closed".into())
+            }
+            Ok(_) => continue,
+            Err(_) => break value,
+        }
+    }?;
+
+    if args.print && !args.each {
+        println!("{}", value);
+    }
+    Ok(value != 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str) -> Result<f64, String> {
+        ExprParser::parse(text)?.eval(&ClientState::default())
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7.0));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9.0));
+        assert_eq!(eval("-2 ^ 2"), Ok(-4.0));
+        assert_eq!(eval("2 ^ 3 ^ 2"), Ok(512.0));
+        assert_eq!(eval("7 % 4 - 1.5e1"), Ok(-12.0));
+        assert_eq!(eval("1e-3 * 1000"), Ok(1.0));
+        assert_eq!(eval("max(1, 2) + abs(-3) + round(sin(30) * 10)"), Ok(10.0));
+        assert_eq!(eval("1 < 2 && !(3 <= 2) || 0"), Ok(1.0));
+        assert_eq!(eval("2 != 2"), Ok(0.0));
+        assert_eq!(eval("0 && \"A.B.C\""), Ok(0.0));
+        assert!(eval("\"A.B.C\" > 1").is_err());
+        assert!(eval("1 +").is_err());
+        assert!(eval("(1").is_err());
+        assert!(eval("foo(1)").is_err());
+        assert!(eval("min(1)").is_err());
+        assert!(eval("\"A.B\"").is_err());
+    }
+
+    #[test]
+    fn test_referenced_properties() {
+        let expr = ExprParser::parse(
+            r#""Mount.EQUATORIAL_EOD_COORD._STATE" == 1 && "CCD.CCD_TEMP.VALUE" < -9.5"#,
+        )
+        .unwrap();
+        let mut properties = BTreeSet::new();
+        expr.properties(&mut properties);
+        assert_eq!(
+            properties.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("CCD".to_string(), "CCD_TEMP".to_string()),
+                ("Mount".to_string(), "EQUATORIAL_EOD_COORD".to_string()),
+            ]
+        );
+    }
+}
@@ -1,10 +1,15 @@
 use clap::Parser;
 use indi_rs::client::connection::Connection;
-use indi_rs::client::message::MessageHandler;
 use indi_rs::client::{Client, ClientConfig};
-use tracing::{debug, info};
+use indi_rs::property::{Property, PropertyValue};
+use std::io::Write;
+use std::time::Duration;
+use tracing::info;
 
 /// INDI getProperties command line tool
+///
+/// Prints `device.property.element=value` for every element matching one
+/// of the patterns, like indilib's `indi_getprop`.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -16,79 +21,225 @@ struct Args {
     #[arg(short = 'p', long, default_value_t = 7624)]
     port: u16,
 
-    /// Device name (optional)
-    #[arg(short = 'd', long)]
-    device: Option<String>,
+    /// Longest time in seconds to wait for definitions
+    #[arg(short = 't', long, default_value_t = 2.0)]
+    timeout: f64,
 
-    /// Property name (optional)
-    #[arg(short = 'n', long)]
-    property: Option<String>,
+    /// Time in milliseconds without new definitions after which all are
+    /// considered received
+    #[arg(short = 's', long, default_value_t = 250)]
+    settle: u64,
 
-    /// Wait time in seconds before disconnecting
-    #[arg(short = 'w', long, default_value_t = 2)]
-    wait: u64,
+    /// Print values only, without `device.property.element=`
+    #[arg(short = '1', long)]
+    values_only: bool,
+
+    /// Print a JSON object of devices, properties and element values
+    #[cfg(feature = "serde_json")]
+    #[arg(long, conflicts_with = "values_only")]
+    json: bool,
+
+    /// Log protocol traffic to stderr
+    #[arg(short = 'v', long)]
+    verbose: bool,
+
+    /// `device.property.element` patterns, `*` matches any run of
+    /// characters and `?` a single one
+    #[arg(default_value = "*.*.*")]
+    patterns: Vec<String>,
+}
+
+/// `device.property.element` pattern
+#[derive(Debug)]
+struct Pattern {
+    device: String,
+    property: String,
+    element: String,
+}
+
+impl Pattern {
+    /// Split at the first two dots; missing parts match anything
+    fn parse(pattern: &str) -> Self {
+        let mut parts = pattern.splitn(3, '.');
+        let mut part = || parts.next().unwrap_or("*").to_string();
+        Self {
+            device: part(),
+            property: part(),
+            element: part(),
+        }
+    }
+
+    fn matches(&self, device: &str, property: &str, element: &str) -> bool {
+        glob(&self.device, device) && glob(&self.property, property) && glob(&self.element, element)
+    }
+}
+
+/// Match `text` against a pattern of `*` and `?` wildcards
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the text it has consumed up to
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, consumed)) => {
+                    p = star;
+                    t = consumed + 1;
+                    backtrack = Some((star, consumed + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Element value as printed
+enum Value {
+    Number(f64),
+    Text(String),
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+/// Elements of `property` and their values, in definition order
+///
+/// BLOBs have no value in the client state and are listed with the format
+/// of the last one received.
+fn elements(property: &Property) -> Vec<(String, Value)> {
+    match &property.value {
+        PropertyValue::NumberVector(numbers) => numbers
+            .iter()
+            .map(|number| (number.name.clone(), Value::Number(number.value)))
+            .collect(),
+        PropertyValue::TextVector(texts) => texts
+            .iter()
+            .map(|text| (text.name.clone(), Value::Text(text.value.clone())))
+            .collect(),
+        PropertyValue::SwitchVector(_, switches) => switches
+            .iter()
+            .map(|switch| (switch.name.clone(), Value::Text(switch.state.to_string())))
+            .collect(),
+        PropertyValue::BlobVector(blobs) => blobs
+            .iter()
+            .map(|blob| (blob.name.clone(), Value::Text(blob.format.clone())))
+            .collect(),
+        _ => property
+            .elements
+            .iter()
+            .flatten()
+            .map(|element| (element.name.clone(), Value::Text(element.value.to_string())))
+            .collect(),
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Parse command line arguments
     let args = Args::parse();
 
-    // Initialize logging
+    // Values go to stdout, logs to stderr
     tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::DEBUG)
+        .with_max_level(if args.verbose {
+            tracing::Level::DEBUG
+        } else {
+            tracing::Level::WARN
+        })
         .with_target(false)
-        .with_thread_ids(true)
-        .with_file(true)
-        .with_line_number(true)
+        .with_writer(std::io::stderr)
         .init();
 
-    // Create a client config with settings from arguments
-    let config = ClientConfig {
-        host: args.host.clone(),
-        port: args.port,
-    };
+    let patterns = args
+        .patterns
+        .iter()
+        .map(|pattern| Pattern::parse(pattern))
+        .collect::<Vec<_>>();
 
+    let config = ClientConfig::new(args.host.clone(), args.port);
     info!(
         "Connecting to INDI server at {}:{}",
         config.host, config.port
     );
-
-    // Connect to the INDI server
     let mut client = Client::new(config).await?;
 
-    // Build getProperties message
-    let mut message = String::from("<getProperties version='1.7'");
-    if let Some(device) = args.device {
-        message.push_str(&format!(" device='{}'", device));
-    }
-    if let Some(property) = args.property {
-        message.push_str(&format!(" name='{}'", property));
+    client
+        .discover_with(
+            Duration::from_millis(args.settle),
+            Duration::from_secs_f64(args.timeout),
+        )
+        .await?;
+
+    let matching = {
+        let state = client.state();
+        let state = state.lock().await;
+        let mut properties = state
+            .properties
+            .values()
+            .flat_map(|properties| properties.values())
+            .collect::<Vec<_>>();
+        properties.sort_by(|a, b| (&a.device, &a.name).cmp(&(&b.device, &b.name)));
+        properties
+            .into_iter()
+            .flat_map(|property| {
+                elements(property)
+                    .into_iter()
+                    .filter(|(element, _)| {
+                        patterns.iter().any(|pattern| {
+                            pattern.matches(&property.device, &property.name, element)
+                        })
+                    })
+                    .map(|(element, value)| {
+                        (
+                            property.device.clone(),
+                            property.name.clone(),
+                            element,
+                            value,
+                        )
+                    })
+            })
+            .collect::<Vec<_>>()
+    };
+    Connection::disconnect(&mut client).await?;
+    if matching.is_empty() {
+        return Err("No matching properties".into());
     }
-    message.push_str("/>\n");
-
-    // Send getProperties message
-    debug!("Sending message: {}", message.trim());
-    client.send_message(&message).await?;
-
-    info!("Sent getProperties message to server");
 
-    // Start reading messages in a separate task
-    let client_clone = client.clone();
-    tokio::spawn(async move {
-        info!("Starting message reader task");
-        if let Err(e) = client_clone.read_messages().await {
-            eprintln!("Error reading messages: {}", e);
+    #[cfg(feature = "serde_json")]
+    if args.json {
+        let mut json = serde_json::json!({});
+        for (device, property, element, value) in matching {
+            json[device][property][element] = match value {
+                Value::Number(number) => serde_json::json!(number),
+                Value::Text(text) => serde_json::Value::String(text),
+            };
         }
-    });
-
-    // Wait for responses
-    info!("Waiting for responses...");
-    tokio::time::sleep(tokio::time::Duration::from_secs(args.wait)).await;
-
-    // Disconnect from server
-    Connection::disconnect(&mut client).await?;
-    info!("Disconnected from server");
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
 
+    let mut out = std::io::stdout().lock();
+    for (device, property, element, value) in matching {
+        if args.values_only {
+            writeln!(out, "{}", value)?;
+        } else {
+            writeln!(out, "{}.{}.{}={}", device, property, element, value)?;
+        }
+    }
     Ok(())
 }
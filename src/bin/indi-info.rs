@@ -1,8 +1,10 @@
 use clap::Parser;
 use indi_rs::{
-    client::{Client, ClientConfig},
+    client::{Client, DeviceInterface, Proxy},
+    devices::Camera,
     error::Result,
 };
+use std::time::Duration;
 use tracing::info;
 
 #[derive(Parser, Debug)]
@@ -16,6 +18,10 @@ struct Args {
     #[arg(short = 'P', long, default_value_t = 7624)]
     port: u16,
 
+    /// Proxy to connect through, e.g. socks5://gateway:1080
+    #[arg(long)]
+    proxy: Option<Proxy>,
+
     /// Device to connect to
     #[arg(short, long)]
     device: Option<String>,
@@ -25,27 +31,43 @@ struct Args {
     exposure: Option<f64>,
 }
 
-async fn process_camera(_client: &mut Client, device: &str, _exposure: Option<f64>) -> Result<()> {
+async fn process_camera(client: &mut Client, device: &str, exposure: Option<f64>) -> Result<()> {
     info!("Processing camera {}", device);
+    if let Some(exposure) = exposure {
+        let camera = Camera::new(client.clone(), device);
+        let blob = camera.expose(Duration::from_secs_f64(exposure)).await?;
+        info!(
+            "Received {} bytes of {} image data from {}",
+            blob.data.len(),
+            blob.format,
+            device
+        );
+    }
     Ok(())
 }
 
-async fn find_cameras(_client: &mut Client) -> Result<Vec<String>> {
-    Ok(vec![])
+async fn find_cameras(client: &mut Client) -> Result<Vec<String>> {
+    let devices = client.discover(Duration::from_secs(5)).await?;
+    Ok(devices
+        .into_iter()
+        .filter(|device| {
+            device.interfaces().contains(DeviceInterface::CCD)
+                || device.properties.iter().any(|name| name == "CCD_EXPOSURE")
+        })
+        .map(|device| device.name)
+        .collect())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
-    // Create client config
-    let config = ClientConfig {
-        host: args.host,
-        port: args.port,
-    };
-
     // Connect to INDI server
-    let mut client = Client::new(config).await?;
+    let mut builder = Client::builder().host(args.host).port(args.port);
+    if let Some(proxy) = args.proxy {
+        builder = builder.proxy(proxy);
+    }
+    let mut client = builder.build().await?;
 
     // Process specific device or find all cameras
     if let Some(device) = args.device {
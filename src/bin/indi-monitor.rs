@@ -0,0 +1,315 @@
+use clap::Parser;
+use indi_rs::client::connection::Connection;
+use indi_rs::client::{Client, ClientConfig, ClientEvent, ClientState};
+use indi_rs::message::MessageType;
+use indi_rs::property::{Property, PropertyState, PropertyValue};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+
+/// Most device messages kept in the log
+const LOG_CAPACITY: usize = 1000;
+
+/// Live INDI terminal dashboard
+///
+/// Lists the devices of a server with their properties by group, colored by
+/// state, and the messages the devices send.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// INDI server host
+    #[arg(short = 'H', long, default_value = "localhost")]
+    host: String,
+
+    /// INDI server port
+    #[arg(short = 'p', long, default_value_t = 7624)]
+    port: u16,
+
+    /// Only show this device
+    #[arg(short, long)]
+    device: Option<String>,
+}
+
+/// Dashboard state besides the client's
+#[derive(Default)]
+struct App {
+    devices: ListState,
+    /// Lines scrolled down in the property pane
+    scroll: u16,
+    /// Device messages, newest last
+    log: VecDeque<String>,
+    /// Lines scrolled up from the end of the log
+    log_scroll: usize,
+    connected: bool,
+}
+
+impl App {
+    fn push_log(&mut self, line: String) {
+        if self.log.len() == LOG_CAPACITY {
+            self.log.pop_front();
+        }
+        self.log.push_back(line);
+    }
+
+    /// Log the message of a device or of a property update
+    fn record(&mut self, message: &MessageType, state: &ClientState) {
+        let text = match message {
+            MessageType::Message(message) => message.message.clone(),
+            MessageType::DelProperty(_) => None,
+            _ if message.kind().is_definition() || message.kind().is_set() => message
+                .device()
+                .zip(message.name())
+                .and_then(|(device, name)| state.get_property(device, name))
+                .and_then(|property| property.message.clone()),
+            _ => None,
+        };
+        let Some(text) = text else {
+            return;
+        };
+        let timestamp = message.timestamp().unwrap_or_default();
+        let line = match message.device() {
+            Some(device) => format!("{} [{}] {}", timestamp, device, text),
+            None => format!("{} {}", timestamp, text),
+        };
+        self.push_log(line.trim_start().to_string());
+    }
+}
+
+fn state_color(state: PropertyState) -> Color {
+    match state {
+        PropertyState::Idle => Color::Gray,
+        PropertyState::Ok => Color::Green,
+        PropertyState::Busy => Color::Yellow,
+        PropertyState::Alert => Color::Red,
+    }
+}
+
+/// `name = value` of each element, numbers in their own format
+fn element_lines(property: &Property) -> Vec<(String, String)> {
+    match &property.value {
+        PropertyValue::NumberVector(numbers) => numbers
+            .iter()
+            .map(|number| {
+                let value = number.format_value().trim_start().to_string();
+                (number.name.clone(), value)
+            })
+            .collect(),
+        PropertyValue::TextVector(texts) => texts
+            .iter()
+            .map(|text| (text.name.clone(), text.value.clone()))
+            .collect(),
+        PropertyValue::SwitchVector(_, switches) => switches
+            .iter()
+            .map(|switch| (switch.name.clone(), switch.state.to_string()))
+            .collect(),
+        PropertyValue::BlobVector(blobs) => blobs
+            .iter()
+            .map(|blob| (blob.name.clone(), format!("[BLOB {}]", blob.format)))
+            .collect(),
+        _ => property
+            .elements
+            .iter()
+            .flatten()
+            .map(|element| (element.name.clone(), element.value.to_string()))
+            .collect(),
+    }
+}
+
+fn draw(frame: &mut Frame, app: &mut App, state: &ClientState, devices: &[String]) {
+    let [main, log] =
+        Layout::vertical([Constraint::Min(5), Constraint::Length(10)]).areas(frame.area());
+    let [list, properties] =
+        Layout::horizontal([Constraint::Length(28), Constraint::Min(20)]).areas(main);
+
+    let title = if app.connected {
+        " Devices "
+    } else {
+        " Devices (disconnected) "
+    };
+    let items = devices
+        .iter()
+        .map(|device| ListItem::new(device.as_str()))
+        .collect::<Vec<_>>();
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED)),
+        list,
+        &mut app.devices,
+    );
+
+    let device = app.devices.selected().and_then(|i| devices.get(i));
+    let mut lines = Vec::new();
+    for (group, members) in device.map(|d| state.grouped(d)).unwrap_or_default() {
+        let group = if group.is_empty() { "Other" } else { &group };
+        lines.push(Line::styled(
+            group.to_string(),
+            Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+        ));
+        for property in members {
+            lines.push(Line::from(vec![
+                Span::styled("● ", Style::new().fg(state_color(property.state))),
+                Span::raw(
+                    property
+                        .label
+                        .as_deref()
+                        .unwrap_or(&property.name)
+                        .to_string(),
+                ),
+                Span::styled(
+                    format!(" ({})", property.name),
+                    Style::new().fg(Color::DarkGray),
+                ),
+            ]));
+            for (name, value) in element_lines(property) {
+                lines.push(Line::raw(format!("    {} = {}", name, value)));
+            }
+        }
+    }
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(Block::bordered().title(format!(" {} ", device.map_or("", |d| d))))
+            .scroll((app.scroll, 0)),
+        properties,
+    );
+
+    let height = log.height.saturating_sub(2) as usize;
+    app.log_scroll = app.log_scroll.min(app.log.len().saturating_sub(height));
+    let end = app.log.len() - app.log_scroll;
+    let start = end.saturating_sub(height);
+    let lines = app
+        .log
+        .range(start..end)
+        .map(|line| Line::raw(line.as_str()))
+        .collect::<Vec<_>>();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Messages ")),
+        log,
+    );
+}
+
+/// Handle a key press, `false` to quit
+fn handle_key(app: &mut App, code: KeyCode, devices: usize) -> bool {
+    match code {
+        KeyCode::Char('q') | KeyCode::Esc => return false,
+        KeyCode::Up | KeyCode::Char('k') => {
+            app.devices.select_previous();
+            app.scroll = 0;
+        }
+        KeyCode::Down | KeyCode::Char('j')
+            if app.devices.selected().is_some_and(|i| i + 1 < devices) =>
+        {
+            app.devices.select_next();
+            app.scroll = 0;
+        }
+        KeyCode::PageDown => app.scroll = app.scroll.saturating_add(10),
+        KeyCode::PageUp => app.scroll = app.scroll.saturating_sub(10),
+        KeyCode::Char('[') => app.log_scroll += 1,
+        KeyCode::Char(']') => app.log_scroll = app.log_scroll.saturating_sub(1),
+        _ => (),
+    }
+    true
+}
+
+async fn run(
+    terminal: &mut DefaultTerminal,
+    client: &Client,
+    filter: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut events = client.subscribe();
+    client.get_properties(filter, None).await?;
+
+    // crossterm input blocks, read it on its own thread
+    let (keys, mut key_rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                if keys.send(Some(key.code)).is_err() {
+                    return;
+                }
+            }
+            Ok(Event::Resize(..)) => {
+                if keys.send(None).is_err() {
+                    return;
+                }
+            }
+            Ok(_) => (),
+            Err(_) => return,
+        }
+    });
+
+    let state = client.state();
+    let mut app = App {
+        connected: true,
+        ..App::default()
+    };
+    let mut refresh = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        let devices = {
+            let state = state.lock().await;
+            let mut devices = state
+                .properties
+                .keys()
+                .filter(|device| filter.map_or(true, |filter| filter == *device))
+                .cloned()
+                .collect::<Vec<_>>();
+            devices.sort();
+            if app.devices.selected().is_none() && !devices.is_empty() {
+                app.devices.select(Some(0));
+            }
+            terminal.draw(|frame| draw(frame, &mut app, &state, &devices))?;
+            devices
+        };
+
+        tokio::select! {
+            key = key_rx.recv() => match key {
+                Some(Some(code)) => {
+                    if !handle_key(&mut app, code, devices.len()) {
+                        return Ok(());
+                    }
+                }
+                Some(None) => (),
+                None => return Ok(()),
+            },
+            event = events.recv() => match event {
+                Ok(ClientEvent::Message(message)) => {
+                    app.record(&message, &*state.lock().await);
+                }
+                Ok(ClientEvent::ConnectionLost) => {
+                    app.connected = false;
+                    app.push_log("Connection lost".to_string());
+                }
+                Ok(ClientEvent::Reconnected) => {
+                    app.connected = true;
+                    app.push_log("Reconnected".to_string());
+                }
+                Ok(ClientEvent::Disconnected) | Err(RecvError::Closed) => {
+                    app.connected = false;
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => (),
+            },
+            _ = refresh.tick() => (),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let config = ClientConfig::new(args.host.clone(), args.port);
+    let mut client = Client::new(config).await?;
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &client, args.device.as_deref()).await;
+    ratatui::restore();
+
+    Connection::disconnect(&mut client).await?;
+    result
+}
@@ -0,0 +1,190 @@
+use clap::Parser;
+use indi_rs::message::stream::Framer;
+use quick_xml::events::{BytesText, Event};
+use quick_xml::{Reader, Writer};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, warn};
+
+/// Bytes read from either side at once
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// INDI protocol sniffing proxy
+///
+/// Accepts clients on one port, connects each to the INDI server and prints
+/// every message exchanged, with BLOB payloads left out.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Port clients connect to
+    #[arg(short = 'l', long, default_value_t = 7625)]
+    listen: u16,
+
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    bind: String,
+
+    /// INDI server host
+    #[arg(short = 'H', long, default_value = "localhost")]
+    host: String,
+
+    /// INDI server port
+    #[arg(short = 'p', long, default_value_t = 7624)]
+    port: u16,
+
+    /// Print each message on one line instead of indented
+    #[arg(short = 'c', long)]
+    compact: bool,
+
+    /// Print BLOB payloads instead of their length
+    #[arg(long)]
+    blobs: bool,
+}
+
+/// Direction of a relayed stream
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    ToServer,
+    ToClient,
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::ToServer => f.write_str("client -> server"),
+            Direction::ToClient => f.write_str("server -> client"),
+        }
+    }
+}
+
+/// Re-indent `frame`, replacing `oneBLOB` payloads by their length if
+/// `redact` is set
+fn pretty(frame: &[u8], compact: bool, redact: bool) -> Result<String, Box<dyn std::error::Error>> {
+    let mut reader = Reader::from_reader(frame);
+    reader.config_mut().trim_text(true);
+    let mut writer = if compact {
+        Writer::new(Vec::new())
+    } else {
+        Writer::new_with_indent(Vec::new(), b' ', 2)
+    };
+    let mut in_blob = false;
+    loop {
+        match reader.read_event()? {
+            Event::Eof => break,
+            Event::Start(start) => {
+                in_blob = start.name().as_ref() == b"oneBLOB";
+                writer.write_event(Event::Start(start))?;
+            }
+            Event::End(end) => {
+                in_blob = false;
+                writer.write_event(Event::End(end))?;
+            }
+            Event::Text(text) if redact && in_blob => {
+                let redacted = format!("[{} base64 bytes]", text.len());
+                writer.write_event(Event::Text(BytesText::new(&redacted)))?;
+            }
+            event => writer.write_event(event)?,
+        }
+    }
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+/// Forward bytes from `from` to `to` unchanged, printing each message
+async fn relay(
+    mut from: OwnedReadHalf,
+    mut to: OwnedWriteHalf,
+    session: u64,
+    direction: Direction,
+    args: &Args,
+) -> std::io::Result<()> {
+    let mut framer = Framer::new();
+    let mut chunk = vec![0u8; READ_CHUNK_SIZE];
+    loop {
+        let read = from.read(&mut chunk).await?;
+        if read == 0 {
+            return to.shutdown().await;
+        }
+        to.write_all(&chunk[..read]).await?;
+        framer.push(&chunk[..read]);
+        while let Some(frame) = framer.next_frame() {
+            let xml = pretty(&frame, args.compact, !args.blobs)
+                .unwrap_or_else(|_| String::from_utf8_lossy(&frame).into_owned());
+            println!(
+                "{} #{} {}\n{}",
+                chrono::Local::now().format("%H:%M:%S%.3f"),
+                session,
+                direction,
+                xml
+            );
+        }
+    }
+}
+
+/// Connect `client` to the server and relay both ways until either side
+/// closes
+async fn session(client: TcpStream, session: u64, args: Arc<Args>) {
+    let server = match TcpStream::connect((args.host.as_str(), args.port)).await {
+        Ok(server) => server,
+        Err(e) => {
+            warn!(
+                "#{}: Failed to connect to {}:{}: {}",
+                session, args.host, args.port, e
+            );
+            return;
+        }
+    };
+    let (client_read, client_write) = client.into_split();
+    let (server_read, server_write) = server.into_split();
+    let result = tokio::select! {
+        result = relay(client_read, server_write, session, Direction::ToServer, &args) => result,
+        result = relay(server_read, client_write, session, Direction::ToClient, &args) => result,
+    };
+    match result {
+        Ok(()) => info!("#{}: Connection closed", session),
+        Err(e) => info!("#{}: Connection closed: {}", session, e),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Arc::new(Args::parse());
+
+    // Messages go to stdout, connection events to stderr
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .with_writer(std::io::stderr)
+        .init();
+
+    let listener = TcpListener::bind((args.bind.as_str(), args.listen)).await?;
+    info!(
+        "Listening on {}:{}, forwarding to {}:{}",
+        args.bind, args.listen, args.host, args.port
+    );
+    let mut id = 0;
+    loop {
+        let (client, addr) = listener.accept().await?;
+        id += 1;
+        info!("#{}: Client connected from {}", id, addr);
+        tokio::spawn(session(client, id, args.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pretty_redacts_blobs() {
+        let frame = b"<setBLOBVector device=\"CCD\" name=\"CCD1\">\n<oneBLOB name=\"CCD1\" size=\"3\" format=\".fits\">\nAAAA\n</oneBLOB>\n</setBLOBVector>";
+        let xml = pretty(frame, false, true).unwrap();
+        assert_eq!(
+            xml,
+            "<setBLOBVector device=\"CCD\" name=\"CCD1\">\n  <oneBLOB name=\"CCD1\" size=\"3\" format=\".fits\">[4 base64 bytes]</oneBLOB>\n</setBLOBVector>"
+        );
+        let xml = pretty(frame, true, false).unwrap();
+        assert!(xml.contains(">AAAA</oneBLOB></setBLOBVector>"));
+    }
+}
@@ -0,0 +1,89 @@
+use clap::{ArgAction, Parser, ValueEnum};
+use indi_rs::drivers::simulator::{CcdSimulator, TelescopeSimulator};
+use indi_rs::error::Result;
+use indi_rs::server::process::ShutdownPolicy;
+use indi_rs::server::{Server, ServerConfig};
+use tracing::{info, Level};
+
+/// Pure Rust INDI server
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Port to listen on
+    #[arg(short = 'p', long, default_value_t = 7624)]
+    port: u16,
+
+    /// Most clients connected at once
+    #[arg(short = 'c', long)]
+    max_clients: Option<usize>,
+
+    /// FIFO accepting `start` and `stop` driver commands
+    #[cfg(unix)]
+    #[arg(short = 'f', long)]
+    fifo: Option<std::path::PathBuf>,
+
+    /// Built-in simulator to serve, may be repeated
+    #[arg(short = 's', long = "simulator", value_enum)]
+    simulators: Vec<Simulator>,
+
+    /// More log output, may be repeated
+    #[arg(short = 'v', long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Driver executables to launch
+    drivers: Vec<String>,
+}
+
+/// Simulators built into indi-rs
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Simulator {
+    /// CCD Simulator
+    Ccd,
+    /// Telescope Simulator
+    Telescope,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let level = match args.verbose {
+        0 => Level::WARN,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false)
+        .init();
+
+    let mut config = ServerConfig::new(format!("0.0.0.0:{}", args.port));
+    config.max_clients = args.max_clients;
+    let server = Server::new(config);
+
+    for simulator in args.simulators {
+        match simulator {
+            Simulator::Ccd => server.register_driver(CcdSimulator::default()),
+            Simulator::Telescope => server.register_driver(TelescopeSimulator::default()),
+        };
+    }
+    for driver in &args.drivers {
+        server.add_driver(driver, &[]).await?;
+        info!("Started driver {}", driver);
+    }
+    #[cfg(unix)]
+    if let Some(fifo) = &args.fifo {
+        server.listen_fifo(fifo)?;
+    }
+
+    info!("Listening on port {}", args.port);
+    tokio::select! {
+        result = server.start() => result,
+        _ = tokio::signal::ctrl_c() => {
+            info!("Shutting down");
+            server.shutdown(ShutdownPolicy::default()).await;
+            Ok(())
+        }
+    }
+}
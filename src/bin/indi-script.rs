@@ -0,0 +1,494 @@
+use clap::Parser;
+use indi_rs::client::connection::Connection;
+use indi_rs::client::{Client, ClientConfig, ClientEvent};
+use indi_rs::format::parse_number;
+use indi_rs::message::new::{NewTextVector, OneText};
+use indi_rs::message::MessageType;
+use indi_rs::property::{PropertyState, PropertyValue, SwitchState};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{timeout_at, Instant};
+use tracing::{info, warn};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Run a sequence of INDI steps from a TOML or YAML file
+///
+/// A script is a list of `steps`, each one of `set`, `wait`, `blob`,
+/// `sleep` and `repeat`:
+///
+/// ```toml
+/// [[steps]]
+/// set = { device = "CCD Simulator", property = "CCD_EXPOSURE", values = { CCD_EXPOSURE_VALUE = 5 } }
+/// [[steps]]
+/// blob = { device = "CCD Simulator", property = "CCD1", dir = "frames" }
+/// ```
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about)]
+struct Args {
+    /// INDI server host
+    #[arg(short = 'H', long, default_value = "localhost")]
+    host: String,
+
+    /// INDI server port
+    #[arg(short = 'p', long, default_value_t = 7624)]
+    port: u16,
+
+    /// Parse the script and exit without connecting
+    #[arg(long)]
+    check: bool,
+
+    /// Log protocol traffic
+    #[arg(short = 'v', long)]
+    verbose: bool,
+
+    /// Script file, YAML if it ends in `.yaml` or `.yml`, TOML otherwise
+    script: PathBuf,
+}
+
+/// Default time in seconds a step may wait for the server
+fn default_timeout() -> f64 {
+    60.0
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Script {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Step {
+    /// Send new element values
+    Set(SetStep),
+    /// Wait until a property reaches a state
+    Wait(WaitStep),
+    /// Wait for the next BLOB of a property, optionally saving it
+    Blob(BlobStep),
+    /// Pause for a number of seconds
+    Sleep(f64),
+    /// Run nested steps several times
+    Repeat(RepeatStep),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SetStep {
+    device: String,
+    property: String,
+    values: BTreeMap<String, Value>,
+    /// Wait for the driver to leave `Busy`, failing on `Alert`
+    #[serde(default)]
+    wait: bool,
+    #[serde(default = "default_timeout")]
+    timeout: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WaitStep {
+    device: String,
+    property: String,
+    state: PropertyState,
+    #[serde(default = "default_timeout")]
+    timeout: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlobStep {
+    device: String,
+    property: String,
+    /// Directory to save the BLOB to
+    dir: Option<PathBuf>,
+    #[serde(default = "default_timeout")]
+    timeout: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RepeatStep {
+    count: usize,
+    steps: Vec<Step>,
+}
+
+/// Element value as written in the script
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Value {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    /// Number, parsing sexagesimal text such as `12:30:00`
+    fn number(&self) -> Result<f64> {
+        match self {
+            Value::Number(number) => Ok(*number),
+            Value::Text(text) => Ok(parse_number(text)?),
+            Value::Bool(_) => Err("Expected a number".into()),
+        }
+    }
+
+    fn switch(&self) -> Result<SwitchState> {
+        match self {
+            Value::Bool(true) => Ok(SwitchState::On),
+            Value::Bool(false) => Ok(SwitchState::Off),
+            Value::Text(text) => Ok(text.parse()?),
+            Value::Number(_) => Err("Expected On, Off or a boolean".into()),
+        }
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Value::Bool(value) => value.to_string(),
+            Value::Number(number) => number.to_string(),
+            Value::Text(text) => text.clone(),
+        }
+    }
+}
+
+impl Script {
+    fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let yaml = path
+            .extension()
+            .is_some_and(|extension| extension == "yaml" || extension == "yml");
+        if yaml {
+            Self::from_yaml(&text)
+        } else {
+            Ok(toml::from_str(&text)?)
+        }
+    }
+
+    /// Parse YAML with steps written as `set: {...}` like in TOML, rather
+    /// than serde_yaml's `!set` tags
+    fn from_yaml(text: &str) -> Result<Self> {
+        let deserializer = serde_yaml::Deserializer::from_str(text);
+        Ok(serde_yaml::with::singleton_map_recursive::deserialize(
+            deserializer,
+        )?)
+    }
+}
+
+/// `(device, property)` of every BLOB step, to enable BLOBs for up front
+fn blob_properties(steps: &[Step], out: &mut Vec<(String, String)>) {
+    for step in steps {
+        match step {
+            Step::Blob(blob) => {
+                let key = (blob.device.clone(), blob.property.clone());
+                if !out.contains(&key) {
+                    out.push(key);
+                }
+            }
+            Step::Repeat(repeat) => blob_properties(&repeat.steps, out),
+            _ => (),
+        }
+    }
+}
+
+/// Wait for an update of `device`.`property` that ends the wait
+///
+/// With a `target` the current state is checked first and the wait ends
+/// once the property is in it; without one it ends at the next state that
+/// is not `Busy`. `Alert` fails unless it is the target.
+async fn wait_for_state(
+    client: &Client,
+    events: &mut broadcast::Receiver<ClientEvent>,
+    device: &str,
+    property: &str,
+    target: Option<PropertyState>,
+    timeout: Duration,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    let mut check = target.is_some();
+    loop {
+        if check {
+            let state = client.state();
+            let state = state.lock().await;
+            if let Some(current) = state.get_property(device, property) {
+                match (current.state, target) {
+                    (state, Some(target)) if state == target => return Ok(()),
+                    (PropertyState::Alert, _) => {
+                        return Err(format!(
+                            "{}.{} is Alert: {}",
+                            device,
+                            property,
+                            current.message.as_deref().unwrap_or_default()
+                        )
+                        .into())
+                    }
+                    (PropertyState::Busy, _) | (_, Some(_)) => (),
+                    (_, None) => return Ok(()),
+                }
+            }
+        }
+        check = match timeout_at(deadline, events.recv()).await {
+            Ok(Ok(ClientEvent::Message(message))) => {
+                message.kind().is_set()
+                    && message.device() == Some(device)
+                    && message.name() == Some(property)
+            }
+            Ok(Ok(ClientEvent::Disconnected)) | Ok(Err(RecvError::Closed)) => {
+                return Err("Disconnected from the server".into())
+            }
+            Ok(Err(RecvError::Lagged(_))) => true,
+            Ok(Ok(_)) => false,
+            Err(_) => return Err(format!("Timed out waiting for {}.{}", device, property).into()),
+        };
+    }
+}
+
+async fn set(client: &Client, step: &SetStep) -> Result<()> {
+    let timeout = Duration::from_secs_f64(step.timeout);
+    let property = client
+        .get_properties_batch(&[(&step.device, &step.property)], timeout)
+        .await?
+        .remove(0)?;
+    let element = |name: &String, value: &Value| -> String {
+        format!("{}.{}.{} = {:?}", step.device, step.property, name, value)
+    };
+
+    let mut events = client.subscribe();
+    match &property.value {
+        PropertyValue::NumberVector(_) => {
+            let values = step
+                .values
+                .iter()
+                .map(|(name, value)| {
+                    let number = value
+                        .number()
+                        .map_err(|e| format!("{}: {}", element(name, value), e))?;
+                    Ok((name.as_str(), number))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            client
+                .set_number(&step.device, &step.property, &values)
+                .await?;
+        }
+        PropertyValue::SwitchVector(_, _) => {
+            let values = step
+                .values
+                .iter()
+                .map(|(name, value)| {
+                    let state = value
+                        .switch()
+                        .map_err(|e| format!("{}: {}", element(name, value), e))?;
+                    Ok((name.as_str(), state))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            client
+                .set_switch(&step.device, &step.property, &values)
+                .await?;
+        }
+        PropertyValue::TextVector(_) => {
+            client
+                .send(&MessageType::NewTextVector(NewTextVector {
+                    device: step.device.clone(),
+                    name: step.property.clone(),
+                    timestamp: None,
+                    elements: step
+                        .values
+                        .iter()
+                        .map(|(name, value)| OneText {
+                            name: name.clone(),
+                            value: value.text(),
+                        })
+                        .collect(),
+                }))
+                .await?;
+        }
+        _ => {
+            return Err(format!("{}.{} cannot be set", step.device, step.property).into());
+        }
+    }
+    if step.wait {
+        wait_for_state(
+            client,
+            &mut events,
+            &step.device,
+            &step.property,
+            None,
+            timeout,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Wait for the next BLOB of the step's property and save its elements
+async fn blob(client: &Client, step: &BlobStep) -> Result<()> {
+    let mut events = client.subscribe();
+    let deadline = Instant::now() + Duration::from_secs_f64(step.timeout);
+    let vector = loop {
+        match timeout_at(deadline, events.recv()).await {
+            Ok(Ok(ClientEvent::Message(message))) => match &*message {
+                MessageType::SetBlobVector(vector)
+                    if vector.device == step.device && vector.name == step.property =>
+                {
+                    break vector.clone();
+                }
+                _ => (),
+            },
+            Ok(Ok(ClientEvent::Disconnected)) | Ok(Err(RecvError::Closed)) => {
+                return Err("Disconnected from the server".into())
+            }
+            Ok(Ok(_)) => (),
+            Ok(Err(RecvError::Lagged(n))) => warn!("Missed {} messages, the BLOB may be lost", n),
+            Err(_) => {
+                return Err(format!(
+                    "Timed out waiting for a BLOB of {}.{}",
+                    step.device, step.property
+                )
+                .into())
+            }
+        }
+    };
+
+    let Some(dir) = &step.dir else {
+        info!("Received BLOB {}.{}", step.device, step.property);
+        return Ok(());
+    };
+    tokio::fs::create_dir_all(dir).await?;
+    let timestamp = chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f");
+    for element in vector.elements.iter().filter(|e| !e.value.is_empty()) {
+        let data = element.get_data()?;
+        let name = format!(
+            "{}_{}_{}_{}{}",
+            step.device,
+            step.property,
+            element.name,
+            timestamp,
+            element.data_format()
+        )
+        .replace(
+            |c: char| !(c.is_ascii_alphanumeric() || "-_.".contains(c)),
+            "_",
+        );
+        let path = dir.join(name);
+        tokio::fs::write(&path, &data).await?;
+        info!("Saved {} bytes to {}", data.len(), path.display());
+    }
+    Ok(())
+}
+
+async fn run(client: &Client, steps: &[Step]) -> Result<()> {
+    for step in steps {
+        match step {
+            Step::Set(step) => {
+                info!("Setting {}.{}", step.device, step.property);
+                set(client, step).await?;
+            }
+            Step::Wait(step) => {
+                info!(
+                    "Waiting for {}.{} to be {:?}",
+                    step.device, step.property, step.state
+                );
+                let mut events = client.subscribe();
+                wait_for_state(
+                    client,
+                    &mut events,
+                    &step.device,
+                    &step.property,
+                    Some(step.state),
+                    Duration::from_secs_f64(step.timeout),
+                )
+                .await?;
+            }
+            Step::Blob(step) => {
+                info!("Waiting for a BLOB of {}.{}", step.device, step.property);
+                blob(client, step).await?;
+            }
+            Step::Sleep(seconds) => {
+                info!("Sleeping {} s", seconds);
+                tokio::time::sleep(Duration::from_secs_f64(*seconds)).await;
+            }
+            Step::Repeat(repeat) => {
+                for i in 1..=repeat.count {
+                    info!("Repetition {}/{}", i, repeat.count);
+                    Box::pin(run(client, &repeat.steps)).await?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    tracing_subscriber::fmt()
+        .with_max_level(if args.verbose {
+            tracing::Level::DEBUG
+        } else {
+            tracing::Level::INFO
+        })
+        .with_target(false)
+        .init();
+
+    let script = Script::load(&args.script)
+        .map_err(|e| format!("Invalid script {}: {}", args.script.display(), e))?;
+    if args.check {
+        println!("{:#?}", script.steps);
+        return Ok(());
+    }
+
+    let config = ClientConfig::new(args.host.clone(), args.port);
+    info!(
+        "Connecting to INDI server at {}:{}",
+        config.host, config.port
+    );
+    let mut client = Client::new(config).await?;
+    client.get_properties(None, None).await?;
+    let mut blobs = Vec::new();
+    blob_properties(&script.steps, &mut blobs);
+    for (device, property) in &blobs {
+        client.enable_blob(device, Some(property), "Also").await?;
+    }
+
+    let result = run(&client, &script.steps).await;
+    Connection::disconnect(&mut client).await?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let toml = r#"
+            [[steps]]
+            set = { device = "CCD", property = "CCD_EXPOSURE", values = { CCD_EXPOSURE_VALUE = 5 }, wait = true }
+            [[steps]]
+            repeat = { count = 2, steps = [{ sleep = 1.5 }, { blob = { device = "CCD", property = "CCD1" } }] }
+        "#;
+        let script = toml::from_str::<Script>(toml).unwrap();
+        let yaml = r#"
+steps:
+  - set: { device: CCD, property: CCD_EXPOSURE, values: { CCD_EXPOSURE_VALUE: 5 }, wait: true }
+  - repeat:
+      count: 2
+      steps:
+        - sleep: 1.5
+        - blob: { device: CCD, property: CCD1 }
+"#;
+        let from_yaml = Script::from_yaml(yaml).unwrap();
+        assert_eq!(format!("{:?}", script), format!("{:?}", from_yaml));
+
+        let Step::Set(set) = &script.steps[0] else {
+            panic!("Expected a set step");
+        };
+        assert!(set.wait);
+        assert_eq!(set.values["CCD_EXPOSURE_VALUE"].number().unwrap(), 5.0);
+        let mut blobs = Vec::new();
+        blob_properties(&script.steps, &mut blobs);
+        assert_eq!(blobs, [("CCD".to_string(), "CCD1".to_string())]);
+    }
+}
@@ -0,0 +1,266 @@
+//! Calibration frame capture
+//!
+//! [`CalibrationPlanner`] takes bias, dark and flat frames with a
+//! [`Camera`], coordinating an optional [`FlatPanel`] so the dust cover is
+//! closed and the light box is in the right state for each frame type.
+//! Flat exposures are found with a feedback loop on a user-supplied image
+//! statistic, typically the mean ADU of the frame. Frames are written to one
+//! directory per frame type.
+
+use crate::devices::{Blob, Camera, FlatPanel, FrameType};
+use crate::error::{Error, Result};
+use chrono::Utc;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info};
+
+/// Exposure search for flat frames
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlatTarget {
+    /// Desired value of the image statistic
+    pub mean: f64,
+    /// Accepted relative deviation from `mean`, e.g. `0.1` for ±10 %
+    pub tolerance: f64,
+    /// First exposure tried
+    pub initial_exposure: Duration,
+    /// Shortest exposure allowed
+    pub min_exposure: Duration,
+    /// Longest exposure allowed
+    pub max_exposure: Duration,
+    /// Test exposures taken before giving up
+    pub max_iterations: usize,
+}
+
+impl FlatTarget {
+    /// Target `mean` within ±10 %, searching 1 ms to 30 s from 1 s
+    pub fn new(mean: f64) -> Self {
+        Self {
+            mean,
+            tolerance: 0.1,
+            initial_exposure: Duration::from_secs(1),
+            min_exposure: Duration::from_millis(1),
+            max_exposure: Duration::from_secs(30),
+            max_iterations: 10,
+        }
+    }
+
+    /// Returns true if `value` is close enough to the target
+    pub fn accepts(&self, value: f64) -> bool {
+        (value - self.mean).abs() <= self.mean * self.tolerance
+    }
+
+    /// Exposure to try after `exposure` produced `value`
+    ///
+    /// Assumes the statistic scales linearly with exposure time.
+    pub fn next_exposure(&self, exposure: Duration, value: f64) -> Duration {
+        let factor = if value > 0.0 { self.mean / value } else { 2.0 };
+        exposure
+            .mul_f64(factor)
+            .clamp(self.min_exposure, self.max_exposure)
+    }
+}
+
+/// Frames written by a calibration routine
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalibrationReport {
+    /// Frame type captured
+    pub frame_type: FrameType,
+    /// Exposure used for the frames
+    pub exposure: Duration,
+    /// Written files in capture order
+    pub frames: Vec<PathBuf>,
+}
+
+/// Captures calibration frames and organizes them by frame type
+#[derive(Debug, Clone)]
+pub struct CalibrationPlanner {
+    camera: Camera,
+    panel: Option<FlatPanel>,
+    output: PathBuf,
+    interval: Duration,
+}
+
+impl CalibrationPlanner {
+    /// Create a planner writing below `output`
+    pub fn new(camera: Camera, output: impl Into<PathBuf>) -> Self {
+        Self {
+            camera,
+            panel: None,
+            output: output.into(),
+            interval: Duration::ZERO,
+        }
+    }
+
+    /// Sets the flat panel/dust cover to coordinate
+    pub fn with_panel(mut self, panel: FlatPanel) -> Self {
+        self.panel = Some(panel);
+        self
+    }
+
+    /// Sets the pause between consecutive frames
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Capture `count` bias frames
+    pub async fn bias(&self, count: usize) -> Result<CalibrationReport> {
+        self.prepare_dark().await?;
+        self.sequence(FrameType::Bias, Duration::ZERO, count).await
+    }
+
+    /// Capture `count` dark frames of `exposure`
+    pub async fn darks(&self, count: usize, exposure: Duration) -> Result<CalibrationReport> {
+        self.prepare_dark().await?;
+        self.sequence(FrameType::Dark, exposure, count).await
+    }
+
+    /// Find the flat exposure for `target` and capture `count` flat frames
+    ///
+    /// `statistic` computes the value compared against
+    /// [`FlatTarget::mean`] from a downloaded frame. Requires a panel.
+    pub async fn flats<F>(
+        &self,
+        count: usize,
+        target: FlatTarget,
+        statistic: F,
+    ) -> Result<CalibrationReport>
+    where
+        F: Fn(&Blob) -> f64,
+    {
+        let panel = self
+            .panel
+            .as_ref()
+            .ok_or_else(|| Error::Message("Flat frames need a flat panel".to_string()))?;
+        panel.close_cover().await?;
+        panel.set_light(true).await?;
+        self.camera.set_frame_type(FrameType::Flat).await?;
+
+        let result = async {
+            let exposure = self.find_flat_exposure(&target, &statistic).await?;
+            self.sequence(FrameType::Flat, exposure, count).await
+        }
+        .await;
+        panel.set_light(false).await?;
+        result
+    }
+
+    /// Run test exposures until the statistic is within tolerance
+    async fn find_flat_exposure<F>(&self, target: &FlatTarget, statistic: &F) -> Result<Duration>
+    where
+        F: Fn(&Blob) -> f64,
+    {
+        let mut exposure = target.initial_exposure;
+        for _ in 0..target.max_iterations {
+            let value = statistic(&self.camera.expose(exposure).await?);
+            debug!("Flat test exposure {:?} gave {}", exposure, value);
+            if target.accepts(value) {
+                info!("Using flat exposure {:?}", exposure);
+                return Ok(exposure);
+            }
+            exposure = target.next_exposure(exposure, value);
+        }
+        Err(Error::Message(format!(
+            "Flat exposure did not reach {} within {} tries",
+            target.mean, target.max_iterations
+        )))
+    }
+
+    /// Close the cover and switch the panel off for bias and dark frames
+    async fn prepare_dark(&self) -> Result<()> {
+        if let Some(panel) = &self.panel {
+            panel.close_cover().await?;
+            panel.set_light(false).await?;
+        }
+        Ok(())
+    }
+
+    /// Capture and store `count` frames of one type
+    async fn sequence(
+        &self,
+        frame_type: FrameType,
+        exposure: Duration,
+        count: usize,
+    ) -> Result<CalibrationReport> {
+        self.camera.set_frame_type(frame_type).await?;
+        let mut frames = Vec::with_capacity(count);
+        for index in 0..count {
+            if index > 0 && !self.interval.is_zero() {
+                tokio::time::sleep(self.interval).await;
+            }
+            let blob = self.camera.expose(exposure).await?;
+            let path = frame_path(&self.output, frame_type, exposure, index, &blob.format);
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&path, &blob.data).await?;
+            debug!("Wrote {}", path.display());
+            frames.push(path);
+        }
+        Ok(CalibrationReport {
+            frame_type,
+            exposure,
+            frames,
+        })
+    }
+}
+
+/// Path of a calibration frame: `<output>/<type>/<type>_<exp>s_<time>_<n><format>`
+fn frame_path(
+    output: &Path,
+    frame_type: FrameType,
+    exposure: Duration,
+    index: usize,
+    format: &str,
+) -> PathBuf {
+    output.join(frame_type.as_str()).join(format!(
+        "{}_{:.3}s_{}_{:03}{}",
+        frame_type.as_str(),
+        exposure.as_secs_f64(),
+        Utc::now().format("%Y%m%dT%H%M%S"),
+        index,
+        format
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flat_exposure_feedback() {
+        let target = FlatTarget::new(30000.0);
+        assert!(target.accepts(28000.0));
+        assert!(!target.accepts(20000.0));
+
+        let exposure = Duration::from_secs(1);
+        assert_eq!(
+            target.next_exposure(exposure, 15000.0),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            target.next_exposure(exposure, 60000.0),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            target.next_exposure(Duration::from_secs(20), 1000.0),
+            target.max_exposure
+        );
+        assert_eq!(target.next_exposure(exposure, 0.0), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_frame_path() {
+        let path = frame_path(
+            Path::new("/data"),
+            FrameType::Dark,
+            Duration::from_secs(60),
+            7,
+            ".fits",
+        );
+        assert!(path.starts_with("/data/dark"));
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("dark_60.000s_"));
+        assert!(name.ends_with("_007.fits"));
+    }
+}
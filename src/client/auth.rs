@@ -0,0 +1,128 @@
+use crate::error::{Error, Result};
+use quick_xml::de::from_str;
+use quick_xml::se::to_string;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Credential presented to a server before any INDI traffic
+///
+/// Sent as a single `<auth user="..." password="..."/>` or
+/// `<auth token="..."/>` line right after connecting. Servers without
+/// authentication ignore the line; see
+/// [`AuthConfig`](crate::server::auth::AuthConfig) for the server side.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credential {
+    /// Username and password
+    Password {
+        /// Username
+        user: String,
+        /// Password
+        password: String,
+    },
+    /// Opaque access token
+    Token(String),
+}
+
+impl fmt::Debug for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Password { user, .. } => f
+                .debug_struct("Password")
+                .field("user", user)
+                .field("password", &"***")
+                .finish(),
+            Self::Token(_) => f.debug_tuple("Token").field(&"***").finish(),
+        }
+    }
+}
+
+impl Credential {
+    /// Username and password credential
+    pub fn password(user: impl Into<String>, password: impl Into<String>) -> Self {
+        Self::Password {
+            user: user.into(),
+            password: password.into(),
+        }
+    }
+
+    /// Token credential
+    pub fn token(token: impl Into<String>) -> Self {
+        Self::Token(token.into())
+    }
+
+    /// The `<auth>` line announcing this credential
+    pub(crate) fn preamble(&self) -> Result<String> {
+        let preamble = match self {
+            Self::Password { user, password } => Preamble {
+                user: Some(user.clone()),
+                password: Some(password.clone()),
+                token: None,
+            },
+            Self::Token(token) => Preamble {
+                user: None,
+                password: None,
+                token: Some(token.clone()),
+            },
+        };
+        let xml = to_string(&preamble).map_err(|e| Error::SerializationError(e.to_string()))?;
+        Ok(format!("{}\n", xml))
+    }
+
+    /// Parse an `<auth>` line
+    pub(crate) fn from_preamble(line: &str) -> Result<Self> {
+        let line = line.trim();
+        // The deserializer does not check the element name
+        if !line.starts_with("<auth ") {
+            return Err(Error::ParseError("Expected an auth preamble".to_string()));
+        }
+        let preamble = from_str::<Preamble>(line)
+            .map_err(|e| Error::ParseError(format!("Invalid auth preamble: {}", e)))?;
+        match preamble {
+            Preamble {
+                user: Some(user),
+                password: Some(password),
+                token: None,
+            } => Ok(Self::Password { user, password }),
+            Preamble {
+                user: None,
+                password: None,
+                token: Some(token),
+            } => Ok(Self::Token(token)),
+            _ => Err(Error::ParseError(
+                "Auth preamble needs either user and password or a token".to_string(),
+            )),
+        }
+    }
+}
+
+/// Wire format of a [`Credential`]
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "auth")]
+struct Preamble {
+    #[serde(rename = "@user", default, skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+    #[serde(rename = "@password", default, skip_serializing_if = "Option::is_none")]
+    password: Option<String>,
+    #[serde(rename = "@token", default, skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preamble_round_trip() {
+        for credential in [
+            Credential::password("observer", "s3cr\"et<"),
+            Credential::token("abc123"),
+        ] {
+            let line = credential.preamble().unwrap();
+            assert!(line.starts_with("<auth ") && line.ends_with('\n'));
+            assert_eq!(Credential::from_preamble(&line).unwrap(), credential);
+        }
+        assert!(!format!("{:?}", Credential::password("observer", "hunter2")).contains("hunter2"));
+        assert!(Credential::from_preamble(r#"<auth user="observer"/>"#).is_err());
+        assert!(Credential::from_preamble(r#"<getProperties version="1.7"/>"#).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use crate::client::wait::wait_for_all_ok;
+use crate::client::Client;
+use crate::error::{Error, Result};
+use crate::message::MessageType;
+use std::collections::HashSet;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tracing::debug;
+
+/// Completion of a batch of property updates
+///
+/// Resolves once every property of the batch has been reported `Ok`, or
+/// fails with the first `Alert` or when the batch timeout expires. The
+/// updates have already been sent when this is returned, so it can be
+/// awaited later or dropped for fire-and-forget behavior.
+#[must_use = "the batch is only tracked while this future is awaited"]
+pub struct BatchCompletion {
+    wait: Pin<Box<dyn Future<Output = Result<()>> + Send>>,
+}
+
+impl std::fmt::Debug for BatchCompletion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BatchCompletion").finish_non_exhaustive()
+    }
+}
+
+impl Future for BatchCompletion {
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.wait.as_mut().poll(cx)
+    }
+}
+
+/// Device and property name of a `new*Vector` message
+fn new_vector_key(message: &MessageType) -> Option<(String, String)> {
+    let (device, name) = match message {
+        MessageType::NewTextVector(new) => (&new.device, &new.name),
+        MessageType::NewNumberVector(new) => (&new.device, &new.name),
+        MessageType::NewSwitchVector(new) => (&new.device, &new.name),
+        _ => return None,
+    };
+    Some((device.clone(), name.clone()))
+}
+
+impl Client {
+    /// Send several `new*Vector` messages as one batch
+    ///
+    /// All messages are sent before this returns. The returned
+    /// [`BatchCompletion`] resolves when the driver has reported every
+    /// targeted property as `Ok`, and fails on the first `Alert` or after
+    /// `timeout`. Messages other than `new*Vector` are rejected before
+    /// anything is sent.
+    pub async fn set_properties_batch(
+        &self,
+        messages: &[MessageType],
+        timeout: Duration,
+    ) -> Result<BatchCompletion> {
+        let pending = messages
+            .iter()
+            .map(|message| {
+                new_vector_key(message).ok_or_else(|| {
+                    Error::Message(format!("Not a new*Vector message: {:?}", message))
+                })
+            })
+            .collect::<Result<HashSet<_>>>()?;
+
+        // Subscribe before sending so no acknowledgement can be missed
+        let mut events = self.subscribe();
+        for message in messages {
+            self.send(message).await?;
+        }
+        debug!("Sent batch of {} updates", messages.len());
+
+        Ok(BatchCompletion {
+            wait: Box::pin(async move { wait_for_all_ok(&mut events, pending, timeout).await }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::mock_server;
+    use crate::message::new::{NewNumberVector, NewSwitchVector, OneNumber, OneSwitch};
+    use crate::property::SwitchState;
+
+    fn batch() -> Vec<MessageType> {
+        vec![
+            MessageType::NewNumberVector(NewNumberVector {
+                device: "CCD Simulator".to_string(),
+                name: "CCD_TEMPERATURE".to_string(),
+                timestamp: None,
+                elements: vec![OneNumber {
+                    name: "CCD_TEMPERATURE_VALUE".to_string(),
+                    value: "-10".to_string(),
+                }],
+            }),
+            MessageType::NewSwitchVector(NewSwitchVector {
+                device: "CCD Simulator".to_string(),
+                name: "CCD_COOLER".to_string(),
+                timestamp: None,
+                elements: vec![OneSwitch {
+                    name: "COOLER_ON".to_string(),
+                    value: SwitchState::On,
+                }],
+            }),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_set_properties_batch() {
+        let config = mock_server(
+            "",
+            "newSwitchVector",
+            r#"<setSwitchVector device="CCD Simulator" name="CCD_COOLER" state="Ok"/>
+<setNumberVector device="CCD Simulator" name="CCD_TEMPERATURE" state="Busy"/>
+<setNumberVector device="CCD Simulator" name="CCD_TEMPERATURE" state="Ok"/>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let completion = client
+            .set_properties_batch(&batch(), Duration::from_secs(5))
+            .await
+            .unwrap();
+        completion.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_set_properties_batch_alert() {
+        let config = mock_server(
+            "",
+            "newSwitchVector",
+            r#"<setSwitchVector device="CCD Simulator" name="CCD_COOLER" state="Alert" message="Cooler fault"/>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let completion = client
+            .set_properties_batch(&batch(), Duration::from_secs(5))
+            .await
+            .unwrap();
+        match completion.await {
+            Err(Error::Property(message)) => assert_eq!(message, "Cooler fault"),
+            result => panic!("Expected Alert, got {:?}", result),
+        }
+
+        let invalid = [MessageType::GetProperties(
+            crate::message::basic::GetProperties {
+                version: "1.7".to_string(),
+                device: None,
+                name: None,
+            },
+        )];
+        assert!(client
+            .set_properties_batch(&invalid, Duration::from_secs(1))
+            .await
+            .is_err());
+    }
+}
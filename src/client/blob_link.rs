@@ -0,0 +1,144 @@
+use crate::client::{BlobPolicy, Client, ClientConfig};
+use crate::error::Result;
+use crate::message::basic::{EnableBlob, GetProperties};
+use crate::message::stream::Framer;
+use crate::message::MessageType;
+use crate::PROTOCOL_VERSION;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// Second connection to the server that only carries BLOBs
+///
+/// Every device defining a BLOB property is subscribed with
+/// `enableBLOB Only`, so large images never queue up in front of control
+/// traffic on the main connection.
+#[derive(Debug)]
+pub(crate) struct BlobLink {
+    writer: Mutex<OwnedWriteHalf>,
+    /// Devices subscribed on the current connection
+    devices: std::sync::Mutex<HashSet<String>>,
+    task: std::sync::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl BlobLink {
+    /// Open the connection, returning its read half for
+    /// [`Client::spawn_blob_link`]
+    pub(crate) async fn connect(config: &ClientConfig) -> Result<(Self, OwnedReadHalf)> {
+        let (read_half, write_half) = super::connect(config).await?.into_split();
+        let link = Self {
+            writer: Mutex::new(write_half),
+            devices: std::sync::Mutex::new(HashSet::new()),
+            task: std::sync::Mutex::new(None),
+        };
+        Ok((link, read_half))
+    }
+
+    /// Ask for the BLOBs of `device` on this connection, once per device
+    pub(crate) async fn subscribe(&self, device: &str) {
+        let first = self
+            .devices
+            .lock()
+            .is_ok_and(|mut devices| devices.insert(device.to_string()));
+        if first {
+            if let Err(e) = self.request_blobs(device).await {
+                debug!("Failed to enable BLOBs for {}: {}", device, e);
+            }
+        }
+    }
+
+    /// Send the `getProperties` and `enableBLOB Only` for `device`
+    ///
+    /// Servers only forward updates of devices the client asked about.
+    async fn request_blobs(&self, device: &str) -> Result<()> {
+        let get_properties = MessageType::GetProperties(GetProperties {
+            version: PROTOCOL_VERSION.to_string(),
+            device: Some(device.to_string()),
+            name: None,
+        });
+        let enable_blob = MessageType::EnableBlob(EnableBlob {
+            device: device.to_string(),
+            name: None,
+            mode: BlobPolicy::Only.as_str().to_string(),
+        });
+        let mut writer = self.writer.lock().await;
+        for message in [get_properties, enable_blob] {
+            writer.write_all(message.to_xml()?.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+        }
+        Ok(())
+    }
+
+    /// Stop reading and close the connection
+    pub(crate) async fn close(&self) {
+        if let Some(task) = self.task.lock().ok().and_then(|mut task| task.take()) {
+            task.abort();
+        }
+        if let Err(e) = self.writer.lock().await.shutdown().await {
+            debug!("Failed to shut down BLOB connection: {}", e);
+        }
+    }
+}
+
+impl Client {
+    /// Start reading the BLOB connection in a task of its own
+    pub(super) fn spawn_blob_link(&self, link: Arc<BlobLink>, read_half: OwnedReadHalf) {
+        let client = self.clone();
+        let task = tokio::spawn({
+            let link = link.clone();
+            async move { client.blob_link_task(link, read_half).await }
+        });
+        if let Ok(mut slot) = link.task.lock() {
+            *slot = Some(task);
+        }
+    }
+
+    /// Read BLOBs, reconnecting if configured, until the connection is gone
+    ///
+    /// After a reconnect the devices subscribed before are subscribed again.
+    async fn blob_link_task(&self, link: Arc<BlobLink>, mut reader: OwnedReadHalf) {
+        loop {
+            if let Err(e) = self.read_blob_link(&mut reader).await {
+                debug!("Error reading BLOB connection: {}", e);
+            }
+            let Some(policy) = self.config.reconnect else {
+                break;
+            };
+            let Some((stream, _)) = self.connect_with_backoff(policy).await else {
+                break;
+            };
+            let (read_half, write_half) = stream.into_split();
+            *link.writer.lock().await = write_half;
+            reader = read_half;
+            let devices = link
+                .devices
+                .lock()
+                .map(|mut devices| std::mem::take(&mut *devices))
+                .unwrap_or_default();
+            for device in devices {
+                link.subscribe(&device).await;
+            }
+        }
+        debug!("BLOB connection closed");
+    }
+
+    /// Handle the BLOBs arriving on `reader` until it closes
+    async fn read_blob_link(&self, reader: &mut OwnedReadHalf) -> Result<()> {
+        let mut framer = Framer::new();
+        let mut chunk = vec![0u8; 64 * 1024];
+        let mut blob_stream = None;
+        loop {
+            let n = reader.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(());
+            }
+            self.update_metrics(|metrics| metrics.bytes_received += n as u64);
+            framer.push(&chunk[..n]);
+            self.drain_frames(&mut framer, &mut blob_stream, true).await;
+        }
+    }
+}
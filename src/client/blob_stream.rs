@@ -0,0 +1,439 @@
+use crate::client::Client;
+use crate::error::{Error, Result};
+use crate::message::new::OneBlob;
+use crate::message::set::SetBlobVector;
+use crate::message::MessageType;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tracing::{debug, warn};
+
+/// Future returned by a [`BlobOpener`]
+type OpenFuture = Pin<Box<dyn Future<Output = Result<BlobTarget>> + Send>>;
+
+/// Opens the destination of a streamed BLOB
+#[derive(Clone)]
+pub(crate) struct BlobOpener(Arc<dyn Fn(BlobInfo) -> OpenFuture + Send + Sync>);
+
+impl fmt::Debug for BlobOpener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BlobOpener")
+    }
+}
+
+/// Incoming BLOB about to be streamed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobInfo {
+    /// Device name
+    pub device: String,
+    /// Property name
+    pub property: String,
+    /// Element name
+    pub element: String,
+    /// Format announced by the driver, e.g. `.fits`
+    pub format: String,
+    /// Decoded size announced by the driver in bytes
+    pub size: usize,
+}
+
+/// Destination of a streamed BLOB
+pub struct BlobTarget {
+    writer: Box<dyn AsyncWrite + Send + Unpin>,
+    path: Option<PathBuf>,
+}
+
+impl BlobTarget {
+    /// Write the decoded BLOB to `writer`
+    pub fn new(writer: impl AsyncWrite + Send + Unpin + 'static) -> Self {
+        Self {
+            writer: Box::new(writer),
+            path: None,
+        }
+    }
+
+    /// Sets the path reported in the [`BlobHandle`]
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+}
+
+impl fmt::Debug for BlobTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlobTarget")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// BLOB written to its target, reported as [`ClientEvent::BlobStored`](super::ClientEvent::BlobStored)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobHandle {
+    /// Device name
+    pub device: String,
+    /// Property name
+    pub property: String,
+    /// Element name
+    pub element: String,
+    /// Format announced by the driver
+    pub format: String,
+    /// Decoded bytes written
+    pub size: u64,
+    /// File the BLOB was written to, if the target named one
+    pub path: Option<PathBuf>,
+}
+
+/// Result of feeding data to a [`BlobStream`]
+#[derive(Debug)]
+pub(crate) enum Step {
+    /// The vector is not complete yet
+    NeedMore,
+    /// The vector is complete; the message carries the metadata with empty
+    /// payloads
+    Done(Box<MessageType>, Vec<BlobHandle>),
+}
+
+/// BLOB element being decoded
+struct Element {
+    blob: OneBlob,
+    target: Option<BlobTarget>,
+    /// Base64 characters not yet decoded, fewer than four after each feed
+    carry: Vec<u8>,
+    written: u64,
+}
+
+impl Element {
+    /// Decode complete base64 groups of `text` and write them out
+    async fn feed(&mut self, text: &[u8]) {
+        self.carry
+            .extend(text.iter().filter(|c| !c.is_ascii_whitespace()));
+        let complete = self.carry.len() / 4 * 4;
+        let groups = self.carry.drain(..complete).collect::<Vec<_>>();
+        self.write(&groups).await;
+    }
+
+    /// Decode and write `groups`, dropping the target on failure
+    async fn write(&mut self, groups: &[u8]) {
+        let Some(target) = self.target.as_mut() else {
+            return;
+        };
+        let result = match STANDARD.decode(groups) {
+            Ok(data) => target
+                .writer
+                .write_all(&data)
+                .await
+                .map(|()| data.len() as u64)
+                .map_err(Error::from),
+            Err(e) => Err(Error::ParseError(format!("Invalid BLOB payload: {}", e))),
+        };
+        match result {
+            Ok(written) => self.written += written,
+            Err(e) => {
+                warn!("Discarding BLOB {}: {}", self.blob.name, e);
+                self.target = None;
+            }
+        }
+    }
+
+    /// Write the rest of the payload and close the target
+    async fn finish(mut self, vector: &SetBlobVector) -> Option<BlobHandle> {
+        let rest = std::mem::take(&mut self.carry);
+        self.write(&rest).await;
+        let mut target = self.target?;
+        if let Err(e) = target.writer.shutdown().await {
+            warn!("Failed to close BLOB {}: {}", self.blob.name, e);
+            return None;
+        }
+        Some(BlobHandle {
+            device: vector.device.clone(),
+            property: vector.name.clone(),
+            element: self.blob.name,
+            format: self.blob.format,
+            size: self.written,
+            path: target.path,
+        })
+    }
+}
+
+/// Incremental decoder of one `setBLOBVector`
+///
+/// Payloads are decoded as they arrive and written to the target returned
+/// by the opener, so the encoded BLOB is never held in memory as a whole.
+pub(crate) struct BlobStream {
+    vector: SetBlobVector,
+    current: Option<Element>,
+    handles: Vec<BlobHandle>,
+}
+
+/// Offset of the `>` closing the tag at the start of `buf`
+fn tag_end(buf: &[u8]) -> Option<usize> {
+    let mut quote = None;
+    for (i, &c) in buf.iter().enumerate().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, b'"' | b'\'') => quote = Some(c),
+            (None, b'>') => return Some(i),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Parse a start tag as an empty element
+fn parse_start_tag<T: serde::de::DeserializeOwned>(tag: &[u8]) -> Result<T> {
+    let tag = std::str::from_utf8(tag)?;
+    let open = tag.trim_end_matches('>').trim_end_matches('/');
+    Ok(quick_xml::de::from_str(&format!("{}/>", open))?)
+}
+
+impl BlobStream {
+    /// Start streaming if `buf` begins with a complete `setBLOBVector` start
+    /// tag, consuming it
+    ///
+    /// Returns `None` if the tag is incomplete or the vector is empty; those
+    /// are left to the regular parser.
+    pub(crate) fn start(buf: &mut Vec<u8>) -> Result<Option<Self>> {
+        if !buf.starts_with(b"<setBLOBVector") {
+            return Ok(None);
+        }
+        let Some(end) = tag_end(buf) else {
+            return Ok(None);
+        };
+        if buf[end - 1] == b'/' {
+            return Ok(None);
+        }
+        let vector = parse_start_tag::<SetBlobVector>(&buf[..=end])?;
+        buf.drain(..=end);
+        debug!("Streaming BLOBs of {}.{}", vector.device, vector.name);
+        Ok(Some(Self {
+            vector,
+            current: None,
+            handles: Vec::new(),
+        }))
+    }
+
+    /// Consume as much of `buf` as possible
+    pub(crate) async fn advance(&mut self, buf: &mut Vec<u8>, opener: &BlobOpener) -> Result<Step> {
+        loop {
+            if let Some(element) = self.current.as_mut() {
+                let text = buf.iter().position(|&c| c == b'<').unwrap_or(buf.len());
+                element.feed(&buf[..text]).await;
+                buf.drain(..text);
+                let Some(end) = tag_end(buf) else {
+                    return Ok(Step::NeedMore);
+                };
+                if !buf.starts_with(b"</oneBLOB") {
+                    return Err(Error::ParseError(format!(
+                        "Unexpected markup in BLOB {}",
+                        element.blob.name
+                    )));
+                }
+                buf.drain(..=end);
+                if let Some(element) = self.current.take() {
+                    self.handles.extend(element.finish(&self.vector).await);
+                }
+                continue;
+            }
+
+            let whitespace = buf.iter().take_while(|c| c.is_ascii_whitespace()).count();
+            buf.drain(..whitespace);
+            let Some(end) = tag_end(buf) else {
+                return Ok(Step::NeedMore);
+            };
+            if buf.starts_with(b"</setBLOBVector") {
+                buf.drain(..=end);
+                let message = Box::new(MessageType::SetBlobVector(self.vector.clone()));
+                return Ok(Step::Done(message, std::mem::take(&mut self.handles)));
+            }
+            if !buf.starts_with(b"<oneBLOB") {
+                return Err(Error::ParseError(format!(
+                    "Unexpected markup in {}.{}",
+                    self.vector.device, self.vector.name
+                )));
+            }
+            let empty = buf[end - 1] == b'/';
+            let blob = parse_start_tag::<OneBlob>(&buf[..=end])?;
+            buf.drain(..=end);
+            let info = BlobInfo {
+                device: self.vector.device.clone(),
+                property: self.vector.name.clone(),
+                element: blob.name.clone(),
+                format: blob.format.clone(),
+                size: blob.size,
+            };
+            let target = match (opener.0)(info).await {
+                Ok(target) => Some(target),
+                Err(e) => {
+                    warn!("Discarding BLOB {}: {}", blob.name, e);
+                    None
+                }
+            };
+            self.vector.elements.push(blob.clone());
+            let element = Element {
+                blob,
+                target,
+                carry: Vec::new(),
+                written: 0,
+            };
+            if empty {
+                self.handles.extend(element.finish(&self.vector).await);
+            } else {
+                self.current = Some(element);
+            }
+        }
+    }
+}
+
+/// Replace characters that are awkward in file names
+fn file_name_part(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+impl Client {
+    /// Stream incoming BLOBs to targets returned by `open`
+    ///
+    /// Payloads of `setBLOBVector` messages are decoded while they arrive
+    /// and written to the target, instead of being kept in memory. Each
+    /// written BLOB is reported as
+    /// [`ClientEvent::BlobStored`](super::ClientEvent::BlobStored); the
+    /// `setBLOBVector` message itself is still published, with empty
+    /// payloads. BLOBs whose target cannot be opened are discarded.
+    pub fn stream_blobs<F, Fut>(&self, open: F)
+    where
+        F: Fn(BlobInfo) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<BlobTarget>> + Send + 'static,
+    {
+        let opener = BlobOpener(Arc::new(move |info| Box::pin(open(info))));
+        if let Ok(mut current) = self.blob_opener.write() {
+            *current = Some(opener);
+        }
+    }
+
+    /// Stream incoming BLOBs into files in `dir`
+    ///
+    /// Files are named after device, element and arrival time, with the
+    /// format announced by the driver as extension. See
+    /// [`Client::stream_blobs`].
+    pub fn stream_blobs_to_dir(&self, dir: impl Into<PathBuf>) {
+        let dir = dir.into();
+        self.stream_blobs(move |info| {
+            let dir = dir.clone();
+            async move {
+                tokio::fs::create_dir_all(&dir).await?;
+                let path = dir.join(format!(
+                    "{}_{}_{}{}",
+                    file_name_part(&info.device),
+                    file_name_part(&info.element),
+                    chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+                    file_name_part(&info.format)
+                ));
+                let file = tokio::fs::File::create(&path).await?;
+                Ok(BlobTarget::new(file).with_path(path))
+            }
+        });
+    }
+
+    /// Keep incoming BLOBs in memory again
+    pub fn stop_streaming_blobs(&self) {
+        if let Ok(mut current) = self.blob_opener.write() {
+            *current = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::mock_server;
+    use crate::client::ClientEvent;
+    use std::time::Duration;
+
+    fn memory_opener() -> BlobOpener {
+        BlobOpener(Arc::new(|info| {
+            Box::pin(async move {
+                let path = format!("{}.{}", info.property, info.element);
+                Ok(BlobTarget::new(tokio::io::sink()).with_path(path))
+            })
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_incremental_decode() {
+        let payload = STANDARD.encode(b"hello, BLOB world");
+        let xml = format!(
+            "<setBLOBVector device=\"CCD\" name=\"CCD1\" state=\"Ok\">\n<oneBLOB name=\"CCD1\" size=\"17\" format=\".fits\">\n{}\n{}\n</oneBLOB>\n<oneBLOB name=\"EMPTY\" size=\"0\" format=\".fits\"/>\n</setBLOBVector>\ntrailing",
+            &payload[..10],
+            &payload[10..]
+        );
+        let opener = memory_opener();
+        let mut buf = Vec::new();
+        let mut stream = None;
+        let mut done = None;
+        // Feed a few bytes at a time to exercise every split point
+        for chunk in xml.as_bytes().chunks(3) {
+            buf.extend_from_slice(chunk);
+            if stream.is_none() {
+                stream = BlobStream::start(&mut buf).unwrap();
+            }
+            if let Some(s) = stream.as_mut() {
+                if let Step::Done(message, handles) = s.advance(&mut buf, &opener).await.unwrap() {
+                    done = Some((message, handles));
+                    break;
+                }
+            }
+        }
+        let (message, handles) = done.unwrap();
+        let MessageType::SetBlobVector(set) = *message else {
+            panic!("Expected setBLOBVector");
+        };
+        assert_eq!(set.elements.len(), 2);
+        assert!(set.elements.iter().all(|blob| blob.value.is_empty()));
+        assert_eq!(handles.len(), 2);
+        assert_eq!(handles[0].size, 17);
+        assert_eq!(handles[0].path, Some(PathBuf::from("CCD1.CCD1")));
+        assert_eq!(handles[1].size, 0);
+    }
+
+    #[tokio::test]
+    async fn test_stream_blobs_to_dir() {
+        let config = mock_server(
+            r#"<defBLOBVector device="CCD Simulator" name="CCD1" state="Idle" perm="ro"><defBLOB name="CCD1"/></defBLOBVector>
+<setBLOBVector device="CCD Simulator" name="CCD1" state="Ok"><oneBLOB name="CCD1" size="5" format=".fits">aGVsbG8=</oneBLOB></setBLOBVector>
+"#,
+            "",
+            "",
+        )
+        .await;
+        let dir = std::env::temp_dir().join(format!("indi-rs-blobs-{}", std::process::id()));
+        let client = Client::new(config).await.unwrap();
+        let mut events = client.subscribe();
+        client.stream_blobs_to_dir(&dir);
+
+        let handle = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let ClientEvent::BlobStored(handle) = events.recv().await.unwrap() {
+                    return handle;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        let path = handle.path.unwrap();
+        assert!(path.to_string_lossy().ends_with(".fits"));
+        assert_eq!(tokio::fs::read(&path).await.unwrap(), b"hello");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+use crate::client::{
+    BlobPolicy, Client, ClientConfig, Credential, KeepAlive, OverflowPolicy, Proxy, RangeCheck,
+    ReconnectPolicy,
+};
+use crate::error::Result;
+use crate::property::timestamp::TimestampPolicy;
+use std::time::Duration;
+
+/// Fluent construction of a [`Client`]
+///
+/// ```no_run
+/// # async fn example() -> indi_rs::Result<()> {
+/// use indi_rs::client::{BlobPolicy, Client, ReconnectPolicy};
+///
+/// let client = Client::builder()
+///     .host("observatory.local")
+///     .port(7624)
+///     .reconnect(ReconnectPolicy::default())
+///     .blob_policy(BlobPolicy::Also)
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    config: ClientConfig,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<ClientConfig> for ClientBuilder {
+    fn from(config: ClientConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ClientBuilder {
+    /// Start from the defaults, connecting to `localhost` on the default port
+    pub fn new() -> Self {
+        Self {
+            config: ClientConfig::new("localhost", ClientConfig::DEFAULT_PORT),
+        }
+    }
+
+    /// Sets the host to connect to
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.config.host = host.into();
+        self
+    }
+
+    /// Sets the port to connect to
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = port;
+        self
+    }
+
+    /// Sets the time allowed to establish the connection
+    pub fn connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.connect_timeout = timeout;
+        self
+    }
+
+    /// Sets the time the server may stay silent before the connection fails
+    pub fn read_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.read_idle_timeout = timeout;
+        self
+    }
+
+    /// Sets the time allowed to write one message
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.write_timeout = timeout;
+        self
+    }
+
+    /// Probe the server when it stays quiet
+    pub fn keepalive(mut self, keepalive: KeepAlive) -> Self {
+        self.config.keepalive = Some(keepalive);
+        self
+    }
+
+    /// Sets the outgoing buffer size and what happens when it is full
+    pub fn send_queue(mut self, capacity: usize, overflow: OverflowPolicy) -> Self {
+        self.config.send_queue_capacity = capacity;
+        self.config.overflow = overflow;
+        self
+    }
+
+    /// Reconnect after the connection is lost
+    pub fn reconnect(mut self, reconnect: ReconnectPolicy) -> Self {
+        self.config.reconnect = Some(reconnect);
+        self
+    }
+
+    /// Request BLOB delivery for every device defining a BLOB property
+    pub fn blob_policy(mut self, blob_policy: BlobPolicy) -> Self {
+        self.config.blob_policy = Some(blob_policy);
+        self
+    }
+
+    /// Receive BLOBs on a second connection, see
+    /// [`ClientConfig::blob_connection`]
+    pub fn blob_connection(mut self, blob_connection: bool) -> Self {
+        self.config.blob_connection = blob_connection;
+        self
+    }
+
+    /// Check outgoing values against property definitions, see
+    /// [`RangeCheck`]
+    pub fn range_check(mut self, range_check: RangeCheck) -> Self {
+        self.config.range_check = range_check;
+        self
+    }
+
+    /// Keep the last `depth` values of every property, see
+    /// [`Client::history`]
+    pub fn history_depth(mut self, depth: usize) -> Self {
+        self.config.history_depth = depth;
+        self
+    }
+
+    /// Stamp outgoing updates that have no timestamp, see
+    /// [`TimestampPolicy`]
+    pub fn timestamp_policy(mut self, policy: TimestampPolicy) -> Self {
+        self.config.timestamp_policy = policy;
+        self
+    }
+
+    /// Tunnel the connection through `proxy`
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.config.proxy = Some(proxy);
+        self
+    }
+
+    /// Authenticate to the server with `credential`
+    pub fn credential(mut self, credential: Credential) -> Self {
+        self.config.credential = Some(credential);
+        self
+    }
+
+    /// Configuration built so far
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Connect to the server
+    pub async fn build(self) -> Result<Client> {
+        Client::new(self.config).await
+    }
+}
+
+impl Client {
+    /// Start building a client, see [`ClientBuilder`]
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let builder = Client::builder()
+            .host("observatory.local")
+            .port(7000)
+            .write_timeout(None)
+            .reconnect(ReconnectPolicy::default())
+            .blob_policy(BlobPolicy::Only);
+        let config = builder.config();
+        assert_eq!(config.host, "observatory.local");
+        assert_eq!(config.port, 7000);
+        assert_eq!(config.write_timeout, None);
+        assert_eq!(
+            config.connect_timeout,
+            Some(ClientConfig::DEFAULT_CONNECT_TIMEOUT)
+        );
+        assert_eq!(config.reconnect, Some(ReconnectPolicy::default()));
+        assert_eq!(config.blob_policy, Some(BlobPolicy::Only));
+    }
+}
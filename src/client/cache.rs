@@ -0,0 +1,83 @@
+use crate::client::Client;
+use crate::error::{Error, Result};
+use crate::storage::Storage;
+
+impl Client {
+    /// Storage key of the property cache of this client's server
+    pub fn cache_key(&self) -> String {
+        format!("cache/{}_{}", self.config.host, self.config.port)
+    }
+
+    /// Load the properties last saved with [`save_cache`](Self::save_cache)
+    ///
+    /// Properties the server has not defined yet are added to the state
+    /// right away, so a GUI can render the device tree before the server
+    /// answers `getProperties`. Live definitions replace them as they
+    /// arrive; see [`ClientState::reconcile_cache`](super::ClientState::reconcile_cache)
+    /// for dropping those the server no longer has. Returns the number of
+    /// properties loaded, 0 if nothing was saved.
+    pub async fn load_cache(&self, storage: &(impl Storage + Sync)) -> Result<usize> {
+        let Some(data) = storage.get(&self.cache_key()).await? else {
+            return Ok(0);
+        };
+        let snapshot = serde_json::from_slice(&data)
+            .map_err(|e| Error::ParseError(format!("Invalid property cache: {}", e)))?;
+        self.state.lock().await.restore_cached(&snapshot)
+    }
+
+    /// Save the current properties for [`load_cache`](Self::load_cache)
+    pub async fn save_cache(&self, storage: &(impl Storage + Sync)) -> Result<()> {
+        let snapshot = self.state.lock().await.snapshot()?;
+        let data =
+            serde_json::to_vec(&snapshot).map_err(|e| Error::SerializationError(e.to_string()))?;
+        storage.put(&self.cache_key(), &data).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::{mock_server, wait_for_property};
+    use crate::storage::MemoryStorage;
+
+    #[tokio::test]
+    async fn test_property_cache() {
+        let storage = MemoryStorage::new();
+        let config = mock_server(
+            r#"<defTextVector device="Mount" name="DRIVER_INFO" state="Idle" perm="ro"><defText name="DRIVER_NAME">Mount Simulator</defText></defTextVector>
+<defSwitchVector device="Mount" name="CONNECTION" state="Ok" perm="rw" rule="OneOfMany"><defSwitch name="CONNECT">On</defSwitch><defSwitch name="DISCONNECT">Off</defSwitch></defSwitchVector>
+"#,
+            "",
+            "",
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        assert_eq!(client.load_cache(&storage).await.unwrap(), 0);
+        wait_for_property(&client, "Mount", "CONNECTION").await;
+        client.save_cache(&storage).await.unwrap();
+        let saved = storage.get(&client.cache_key()).await.unwrap().unwrap();
+
+        // A second session against a server that lost one property
+        let restarted = mock_server(
+            r#"<defTextVector device="Mount" name="DRIVER_INFO" state="Idle" perm="ro"><defText name="DRIVER_NAME">Mount Simulator</defText></defTextVector>
+"#,
+            "",
+            "",
+        )
+        .await;
+        let client = Client::new(restarted).await.unwrap();
+        // Mock servers listen on a new port, so move the cache over
+        storage.put(&client.cache_key(), &saved).await.unwrap();
+        wait_for_property(&client, "Mount", "DRIVER_INFO").await;
+        assert_eq!(client.load_cache(&storage).await.unwrap(), 1);
+        let state = client.state();
+        let mut state = state.lock().await;
+        assert!(!state.is_cached("Mount", "DRIVER_INFO"));
+        assert!(state.is_cached("Mount", "CONNECTION"));
+        assert_eq!(
+            state.reconcile_cache(),
+            vec![("Mount".to_string(), "CONNECTION".to_string())]
+        );
+        assert!(state.get_property("Mount", "CONNECTION").is_none());
+    }
+}
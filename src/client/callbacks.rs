@@ -0,0 +1,275 @@
+use crate::client::Client;
+use crate::message::MessageType;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::mpsc;
+
+/// Boxed async callback invoked with a received message
+type Callback =
+    Arc<dyn Fn(Arc<MessageType>) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// Callbacks due for a message, run in order on the callback task
+type Job = (Vec<Callback>, Arc<MessageType>);
+
+/// Identifier of a registered callback, used to remove it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallbackId(u64);
+
+/// Kind of message a callback is registered for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Define,
+    Update,
+    Delete,
+    Message,
+}
+
+impl Kind {
+    fn of(message: &MessageType) -> Option<Self> {
+        match message {
+            MessageType::DefTextVector(_)
+            | MessageType::DefNumberVector(_)
+            | MessageType::DefSwitchVector(_)
+            | MessageType::DefLightVector(_)
+            | MessageType::DefBlobVector(_) => Some(Kind::Define),
+            MessageType::SetTextVector(_)
+            | MessageType::SetNumberVector(_)
+            | MessageType::SetSwitchVector(_)
+            | MessageType::SetLightVector(_)
+            | MessageType::SetBlobVector(_) => Some(Kind::Update),
+            MessageType::DelProperty(_) => Some(Kind::Delete),
+            MessageType::Message(_) => Some(Kind::Message),
+            _ => None,
+        }
+    }
+}
+
+/// Registered callbacks of a client
+#[derive(Default)]
+pub(crate) struct Callbacks {
+    next_id: u64,
+    entries: Vec<(CallbackId, Kind, Callback)>,
+    /// Queue of the task running the callbacks, spawned with the first job
+    jobs: OnceLock<mpsc::UnboundedSender<Job>>,
+}
+
+impl std::fmt::Debug for Callbacks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Callbacks")
+            .field("count", &self.entries.len())
+            .finish()
+    }
+}
+
+impl Callbacks {
+    /// Callbacks interested in `message`, in registration order
+    pub(crate) fn matching(&self, message: &MessageType) -> Vec<Callback> {
+        let Some(kind) = Kind::of(message) else {
+            return Vec::new();
+        };
+        self.entries
+            .iter()
+            .filter(|(_, k, _)| *k == kind)
+            .map(|(_, _, callback)| callback.clone())
+            .collect()
+    }
+}
+
+impl Client {
+    /// Register an async callback for property definitions
+    ///
+    /// Callbacks run one at a time, in the order the messages arrived, on a
+    /// task of their own after the client state has been updated. They may
+    /// send commands and await the server's reply; a slow callback delays
+    /// later callbacks, but not the processing of incoming messages.
+    pub fn on_define<F, Fut>(&self, callback: F) -> CallbackId
+    where
+        F: Fn(Arc<MessageType>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.register(Kind::Define, callback)
+    }
+
+    /// Register an async callback for `set*Vector` updates
+    ///
+    /// Runs like the callbacks of [`Client::on_define`].
+    pub fn on_update<F, Fut>(&self, callback: F) -> CallbackId
+    where
+        F: Fn(Arc<MessageType>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.register(Kind::Update, callback)
+    }
+
+    /// Register an async callback for `delProperty`
+    pub fn on_delete<F, Fut>(&self, callback: F) -> CallbackId
+    where
+        F: Fn(Arc<MessageType>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.register(Kind::Delete, callback)
+    }
+
+    /// Register an async callback for `message` elements
+    pub fn on_message<F, Fut>(&self, callback: F) -> CallbackId
+    where
+        F: Fn(Arc<MessageType>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.register(Kind::Message, callback)
+    }
+
+    /// Remove a registered callback
+    ///
+    /// Returns false if no callback with `id` was registered.
+    pub fn remove_callback(&self, id: CallbackId) -> bool {
+        let Ok(mut callbacks) = self.callbacks.write() else {
+            return false;
+        };
+        let before = callbacks.entries.len();
+        callbacks.entries.retain(|(entry, _, _)| *entry != id);
+        callbacks.entries.len() != before
+    }
+
+    fn register<F, Fut>(&self, kind: Kind, callback: F) -> CallbackId
+    where
+        F: Fn(Arc<MessageType>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let callback: Callback = Arc::new(move |message| Box::pin(callback(message)));
+        let mut callbacks = self
+            .callbacks
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        callbacks.next_id += 1;
+        let id = CallbackId(callbacks.next_id);
+        callbacks.entries.push((id, kind, callback));
+        id
+    }
+
+    /// Queue the callbacks registered for `message` on the callback task
+    pub(crate) fn queue_callbacks(&self, message: &Arc<MessageType>) {
+        let Ok(registry) = self.callbacks.read() else {
+            return;
+        };
+        let callbacks = registry.matching(message);
+        if callbacks.is_empty() {
+            return;
+        }
+        let jobs = registry.jobs.get_or_init(spawn_callback_task);
+        // The task only ends if a callback panicked
+        let _ = jobs.send((callbacks, message.clone()));
+    }
+}
+
+/// Spawn the task running queued callbacks, which ends with the client
+fn spawn_callback_task() -> mpsc::UnboundedSender<Job> {
+    let (jobs, mut queue) = mpsc::unbounded_channel::<Job>();
+    tokio::spawn(async move {
+        while let Some((callbacks, message)) = queue.recv().await {
+            for callback in callbacks {
+                callback(message.clone()).await;
+            }
+        }
+    });
+    jobs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::mock_server;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_callbacks() {
+        let config = mock_server(
+            "",
+            "getProperties",
+            r#"<defTextVector device="Mount" name="INFO" state="Idle" perm="ro">
+<defText name="NAME">mount</defText>
+</defTextVector>
+<message device="Mount" message="Hello"/>
+<setTextVector device="Mount" name="INFO"><oneText name="NAME">renamed</oneText></setTextVector>
+<delProperty device="Mount"/>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let define = tx.clone();
+        client.on_define(move |_| {
+            let tx = define.clone();
+            async move { tx.send("define").unwrap() }
+        });
+        let update = tx.clone();
+        client.on_update(move |_| {
+            let tx = update.clone();
+            async move { tx.send("update").unwrap() }
+        });
+        let delete = tx.clone();
+        client.on_delete(move |_| {
+            let tx = delete.clone();
+            async move { tx.send("delete").unwrap() }
+        });
+        let removed = client.on_message(move |_| {
+            let tx = tx.clone();
+            async move { tx.send("message").unwrap() }
+        });
+        assert!(client.remove_callback(removed));
+        assert!(!client.remove_callback(removed));
+
+        client.get_properties(None, None).await.unwrap();
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(rx.recv().await.unwrap());
+        }
+        assert_eq!(received, ["define", "update", "delete"]);
+    }
+
+    #[tokio::test]
+    async fn test_callback_awaits_later_message() {
+        let config = mock_server(
+            "",
+            "getProperties",
+            r#"<defTextVector device="Mount" name="INFO" state="Idle" perm="ro">
+<defText name="NAME">mount</defText>
+</defTextVector>
+<setTextVector device="Mount" name="INFO"><oneText name="NAME">renamed</oneText></setTextVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        // Waits for an update received after the definition it runs for,
+        // which needs the connection to keep reading meanwhile
+        let waiting = client.clone();
+        client.on_define(move |_| {
+            let (client, tx) = (waiting.clone(), tx.clone());
+            async move {
+                loop {
+                    let name = client
+                        .state()
+                        .lock()
+                        .await
+                        .get_property("Mount", "INFO")
+                        .and_then(|property| property.value.text("NAME").map(str::to_string));
+                    if name.as_deref() == Some("renamed") {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                tx.send(()).unwrap();
+            }
+        });
+
+        client.get_properties(None, None).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -1,10 +1,192 @@
+use crate::client::{Credential, Proxy};
+use crate::error::Error;
+use crate::property::timestamp::TimestampPolicy;
+use std::str::FromStr;
+use std::time::Duration;
+
 /// Client configuration
+///
+/// Usually built with [`Client::builder`](super::Client::builder). New
+/// options are added over time, so the struct can only be created through
+/// [`ClientConfig::new`] or the builder.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct ClientConfig {
     /// Host to connect to
     pub host: String,
     /// Port to connect to
     pub port: u16,
+    /// Time allowed to establish the connection, `None` waits indefinitely
+    pub connect_timeout: Option<Duration>,
+    /// Time the server may stay silent before the connection is considered
+    /// dead, `None` disables the check
+    ///
+    /// Idle INDI servers send nothing, so only set this when the server is
+    /// known to produce regular updates.
+    pub read_idle_timeout: Option<Duration>,
+    /// Time allowed to queue and write one message to the server, `None`
+    /// waits indefinitely
+    ///
+    /// A write that times out may have sent part of the message, so the
+    /// connection is closed; with `reconnect` set, the message is sent again
+    /// on the next connection.
+    pub write_timeout: Option<Duration>,
+    /// Keepalive probing of the server, `None` disables it
+    pub keepalive: Option<KeepAlive>,
+    /// Number of outgoing messages buffered ahead of the connection
+    pub send_queue_capacity: usize,
+    /// Handling of messages sent while the outgoing buffer is full
+    pub overflow: OverflowPolicy,
+    /// Reconnection after the server closed the connection, `None` gives up
+    /// immediately
+    pub reconnect: Option<ReconnectPolicy>,
+    /// BLOB handling requested for every device defining a BLOB property,
+    /// `None` keeps the server default (`Never`)
+    pub blob_policy: Option<BlobPolicy>,
+    /// Proxy the connection is tunneled through, `None` connects directly
+    pub proxy: Option<Proxy>,
+    /// Credential presented to the server before any INDI traffic
+    pub credential: Option<Credential>,
+    /// Receive BLOBs on a second connection with `enableBLOB Only`
+    ///
+    /// Image transfers then never delay control traffic. Takes precedence
+    /// over `blob_policy`, the main connection keeps the server default.
+    pub blob_connection: bool,
+    /// Check of outgoing numbers and switches against the cached definition
+    pub range_check: RangeCheck,
+    /// Values kept per property for [`Client::history`](super::Client::history),
+    /// 0 keeps none
+    pub history_depth: usize,
+    /// Stamping of outgoing `new*Vector` messages sent without a timestamp
+    pub timestamp_policy: TimestampPolicy,
+}
+
+/// Reconnection with exponential backoff
+///
+/// After the connection is lost the client waits `initial_delay`, doubling
+/// the wait after every failed attempt up to `max_delay`. Once reconnected
+/// it asks for all properties again so the state catches up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// Wait before the first attempt
+    pub initial_delay: Duration,
+    /// Longest wait between attempts
+    pub max_delay: Duration,
+    /// Attempts before giving up, `None` retries forever
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+/// INDI BLOB handling mode, sent with `enableBLOB`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlobPolicy {
+    /// Send no BLOBs
+    #[default]
+    Never,
+    /// Send BLOBs along with all other updates
+    Also,
+    /// Send only BLOBs
+    Only,
+}
+
+impl BlobPolicy {
+    /// Mode as written in `enableBLOB`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlobPolicy::Never => "Never",
+            BlobPolicy::Also => "Also",
+            BlobPolicy::Only => "Only",
+        }
+    }
+}
+
+impl FromStr for BlobPolicy {
+    type Err = Error;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.trim() {
+            "Never" => Ok(BlobPolicy::Never),
+            "Also" => Ok(BlobPolicy::Also),
+            "Only" => Ok(BlobPolicy::Only),
+            other => Err(Error::ParseError(format!("Invalid BLOB mode: {}", other))),
+        }
+    }
+}
+
+/// Handling of outgoing messages when the send queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Wait for space, up to the write timeout, so no command is lost
+    #[default]
+    Block,
+    /// Discard the oldest queued message, e.g. for streams of guide pulses
+    /// where only the latest command matters
+    DropOldest,
+    /// Fail the send immediately
+    Error,
+}
+
+/// Check of outgoing values against the cached property definition
+///
+/// Numbers are checked against `min`, `max` and `step`, switches against
+/// the rule of the vector. Properties that are not defined yet are sent
+/// unchecked. Many drivers treat `step` as a hint for spin boxes only, e.g.
+/// accepting 0.2 s exposures with a step of 1, hence the check is off by
+/// default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RangeCheck {
+    /// Send values unchecked
+    #[default]
+    Off,
+    /// Fail with [`Error::OutOfRange`] instead of sending
+    Reject,
+    /// Move numbers to the closest allowed value; switches are rejected
+    Clamp,
+}
+
+/// Message used to probe a quiet server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeepAliveProbe {
+    /// `pingRequest`, answered with `pingReply` by recent servers
+    #[default]
+    Ping,
+    /// `getProperties` for an already known property, for servers that do
+    /// not implement ping
+    GetProperties,
+}
+
+/// Keepalive and dead-peer detection settings
+///
+/// When nothing has been received for `interval` a probe is sent. If the
+/// server still stays silent for `timeout` after that, the connection is
+/// considered lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepAlive {
+    /// Silence after which the server is probed
+    pub interval: Duration,
+    /// Time the server has to answer a probe
+    pub timeout: Duration,
+    /// Message used as probe
+    pub probe: KeepAliveProbe,
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+            probe: KeepAliveProbe::default(),
+        }
+    }
 }
 
 impl ClientConfig {
@@ -13,9 +195,111 @@ impl ClientConfig {
         Self {
             host: host.into(),
             port,
+            connect_timeout: Some(Self::DEFAULT_CONNECT_TIMEOUT),
+            read_idle_timeout: None,
+            write_timeout: Some(Self::DEFAULT_WRITE_TIMEOUT),
+            keepalive: None,
+            send_queue_capacity: Self::DEFAULT_SEND_QUEUE_CAPACITY,
+            overflow: OverflowPolicy::default(),
+            reconnect: None,
+            blob_policy: None,
+            proxy: None,
+            credential: None,
+            blob_connection: false,
+            range_check: RangeCheck::default(),
+            history_depth: 0,
+            timestamp_policy: TimestampPolicy::default(),
         }
     }
 
+    /// Sets the time allowed to establish the connection
+    pub fn with_connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Sets the time the server may stay silent before the connection fails
+    pub fn with_read_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.read_idle_timeout = timeout;
+        self
+    }
+
+    /// Sets the time allowed to write one message
+    pub fn with_write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// Sets keepalive probing of the server
+    pub fn with_keepalive(mut self, keepalive: Option<KeepAlive>) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    /// Sets the outgoing buffer size and what happens when it is full
+    pub fn with_send_queue(mut self, capacity: usize, overflow: OverflowPolicy) -> Self {
+        self.send_queue_capacity = capacity;
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets reconnection after the connection is lost
+    pub fn with_reconnect(mut self, reconnect: Option<ReconnectPolicy>) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// Sets the BLOB handling requested from the server
+    pub fn with_blob_policy(mut self, blob_policy: Option<BlobPolicy>) -> Self {
+        self.blob_policy = blob_policy;
+        self
+    }
+
+    /// Sets the proxy the connection is tunneled through
+    pub fn with_proxy(mut self, proxy: Option<Proxy>) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    /// Sets the credential presented to the server
+    pub fn with_credential(mut self, credential: Option<Credential>) -> Self {
+        self.credential = credential;
+        self
+    }
+
+    /// Sets whether BLOBs are received on a second connection
+    pub fn with_blob_connection(mut self, blob_connection: bool) -> Self {
+        self.blob_connection = blob_connection;
+        self
+    }
+
+    /// Sets the check of outgoing values against property definitions
+    pub fn with_range_check(mut self, range_check: RangeCheck) -> Self {
+        self.range_check = range_check;
+        self
+    }
+
+    /// Sets the number of values kept per property
+    pub fn with_history_depth(mut self, depth: usize) -> Self {
+        self.history_depth = depth;
+        self
+    }
+
+    /// Sets the stamping of outgoing updates
+    pub fn with_timestamp_policy(mut self, policy: TimestampPolicy) -> Self {
+        self.timestamp_policy = policy;
+        self
+    }
+
     /// Default INDI server port (7624)
     pub const DEFAULT_PORT: u16 = 7624;
+
+    /// Default time allowed to establish the connection
+    pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Default time allowed to write one message
+    pub const DEFAULT_WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Default number of outgoing messages buffered
+    pub const DEFAULT_SEND_QUEUE_CAPACITY: usize = 256;
 }
@@ -0,0 +1,280 @@
+use crate::client::{definition_key, Client, ClientEvent};
+use crate::error::{Error, Result};
+use crate::property::{Property, PropertyValue};
+use bitflags::bitflags;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Driver identification property
+const DRIVER_INFO: &str = "DRIVER_INFO";
+/// Driver name element of [`DRIVER_INFO`]
+const DRIVER_NAME: &str = "DRIVER_NAME";
+/// Driver executable element of [`DRIVER_INFO`]
+const DRIVER_EXEC: &str = "DRIVER_EXEC";
+/// Driver version element of [`DRIVER_INFO`]
+const DRIVER_VERSION: &str = "DRIVER_VERSION";
+/// Driver interface bitmask element of [`DRIVER_INFO`]
+const DRIVER_INTERFACE: &str = "DRIVER_INTERFACE";
+
+bitflags! {
+    /// INDI device interfaces, as announced in `DRIVER_INTERFACE`
+    ///
+    /// Bits unknown to this crate are kept, so newer interfaces survive a
+    /// round trip through [`DeviceInterface::bits`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct DeviceInterface: u32 {
+        /// Telescope mount
+        const TELESCOPE = 1 << 0;
+        /// Camera
+        const CCD = 1 << 1;
+        /// Guide port
+        const GUIDER = 1 << 2;
+        /// Focuser
+        const FOCUSER = 1 << 3;
+        /// Filter wheel
+        const FILTER = 1 << 4;
+        /// Dome
+        const DOME = 1 << 5;
+        /// GPS receiver
+        const GPS = 1 << 6;
+        /// Weather station
+        const WEATHER = 1 << 7;
+        /// Adaptive optics
+        const AO = 1 << 8;
+        /// Dust cap
+        const DUSTCAP = 1 << 9;
+        /// Flat panel
+        const LIGHTBOX = 1 << 10;
+        /// Detector, e.g. a photometer
+        const DETECTOR = 1 << 11;
+        /// Field rotator
+        const ROTATOR = 1 << 12;
+        /// Spectrograph
+        const SPECTROGRAPH = 1 << 13;
+        /// Correlator of an interferometer
+        const CORRELATOR = 1 << 14;
+        /// Auxiliary device
+        const AUX = 1 << 15;
+        /// Digital outputs
+        const OUTPUT = 1 << 16;
+        /// Digital or analog inputs
+        const INPUT = 1 << 17;
+
+        const _ = !0;
+    }
+}
+
+impl std::str::FromStr for DeviceInterface {
+    type Err = Error;
+
+    /// Parse the decimal `DRIVER_INTERFACE` value
+    fn from_str(value: &str) -> Result<Self> {
+        value
+            .trim()
+            .parse()
+            .map(Self::from_bits_retain)
+            .map_err(|_| Error::ParseError(format!("Invalid driver interface '{}'", value)))
+    }
+}
+
+/// Driver details reported in `DRIVER_INFO`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DriverInfo {
+    /// Driver name
+    pub name: Option<String>,
+    /// Driver executable
+    pub exec: Option<String>,
+    /// Driver version
+    pub version: Option<String>,
+    /// INDI device interfaces the driver implements
+    pub interface: Option<DeviceInterface>,
+}
+
+impl DriverInfo {
+    /// Parse a `DRIVER_INFO` property
+    fn from_property(property: &Property) -> Option<Self> {
+        let texts = &property.value;
+        if !matches!(texts, PropertyValue::TextVector(_)) {
+            return None;
+        }
+        Some(Self {
+            name: texts.text(DRIVER_NAME).map(str::to_string),
+            exec: texts.text(DRIVER_EXEC).map(str::to_string),
+            version: texts.text(DRIVER_VERSION).map(str::to_string),
+            interface: texts
+                .text(DRIVER_INTERFACE)
+                .and_then(|value| value.trim().parse().ok()),
+        })
+    }
+}
+
+/// Device found by [`Client::discover`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// Device name
+    pub name: String,
+    /// Driver details, if the device defines `DRIVER_INFO`
+    pub driver: Option<DriverInfo>,
+    /// Names of the defined properties, sorted
+    pub properties: Vec<String>,
+}
+
+impl DeviceInfo {
+    /// Interfaces the device implements, empty if its driver does not
+    /// announce them
+    ///
+    /// Use e.g. `interfaces().contains(DeviceInterface::CCD)` to find all
+    /// cameras.
+    pub fn interfaces(&self) -> DeviceInterface {
+        self.driver
+            .as_ref()
+            .and_then(|driver| driver.interface)
+            .unwrap_or_default()
+    }
+}
+
+impl Client {
+    /// Time without new definitions after which discovery is complete
+    pub const DISCOVERY_SETTLE: Duration = Duration::from_millis(500);
+
+    /// Request all properties and wait until the definitions have settled
+    ///
+    /// Returns once no `def*Vector` has arrived for
+    /// [`DISCOVERY_SETTLE`](Self::DISCOVERY_SETTLE), or after `timeout` with
+    /// whatever was defined by then. Devices are sorted by name.
+    pub async fn discover(&self, timeout: Duration) -> Result<Vec<DeviceInfo>> {
+        self.discover_with(Self::DISCOVERY_SETTLE, timeout).await
+    }
+
+    /// [`Client::discover`] with definitions settled after `settle` without
+    /// a new one, e.g. longer for servers with slow drivers
+    pub async fn discover_with(
+        &self,
+        settle: Duration,
+        timeout: Duration,
+    ) -> Result<Vec<DeviceInfo>> {
+        // Subscribe before sending so no definition can be missed
+        let mut events = self.subscribe();
+        self.get_properties(None, None).await?;
+
+        let deadline = Instant::now() + timeout;
+        let mut last_definition = Instant::now();
+        loop {
+            let settled = last_definition + settle;
+            match tokio::time::timeout_at(settled.min(deadline), events.recv()).await {
+                Ok(Ok(ClientEvent::Message(message))) => {
+                    if definition_key(&message).is_some() {
+                        last_definition = Instant::now();
+                    }
+                }
+                Ok(Ok(_)) => {}
+                Ok(Err(RecvError::Lagged(skipped))) => {
+                    warn!("Missed {} events during discovery", skipped);
+                    last_definition = Instant::now();
+                }
+                Ok(Err(RecvError::Closed)) => {
+                    return Err(Error::Protocol("Connection closed".to_string()))
+                }
+                Err(_) => {
+                    if settled > deadline {
+                        debug!("Discovery did not settle within {:?}", timeout);
+                    }
+                    break;
+                }
+            }
+        }
+
+        let state = self.state.lock().await;
+        let mut devices = state
+            .properties
+            .iter()
+            .map(|(device, properties)| {
+                let mut names = properties.keys().cloned().collect::<Vec<_>>();
+                names.sort();
+                DeviceInfo {
+                    name: device.clone(),
+                    driver: properties
+                        .get(DRIVER_INFO)
+                        .and_then(DriverInfo::from_property),
+                    properties: names,
+                }
+            })
+            .collect::<Vec<_>>();
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(devices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::mock_server;
+
+    #[tokio::test]
+    async fn test_discover() {
+        let config = mock_server(
+            "",
+            "getProperties",
+            r#"<defTextVector device="CCD" name="DRIVER_INFO" state="Idle" perm="ro">
+<defText name="DRIVER_NAME">CCD Simulator</defText>
+<defText name="DRIVER_EXEC">indi_simulator_ccd</defText>
+<defText name="DRIVER_VERSION">1.0</defText>
+<defText name="DRIVER_INTERFACE">22</defText>
+</defTextVector>
+<defNumberVector device="CCD" name="CCD_TEMPERATURE" state="Idle" perm="rw">
+<defNumber name="CCD_TEMPERATURE_VALUE" format="%5.2f" min="-50" max="50" step="0">20</defNumber>
+</defNumberVector>
+<defTextVector device="Focuser" name="INFO" state="Idle" perm="ro">
+<defText name="NAME">focuser</defText>
+</defTextVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let devices = client.discover(Duration::from_secs(5)).await.unwrap();
+
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].name, "CCD");
+        assert_eq!(devices[0].properties, ["CCD_TEMPERATURE", "DRIVER_INFO"]);
+        let driver = devices[0].driver.as_ref().unwrap();
+        assert_eq!(driver.name.as_deref(), Some("CCD Simulator"));
+        assert_eq!(driver.exec.as_deref(), Some("indi_simulator_ccd"));
+        assert_eq!(
+            driver.interface,
+            Some(DeviceInterface::CCD | DeviceInterface::GUIDER | DeviceInterface::FILTER)
+        );
+        assert!(devices[0].interfaces().contains(DeviceInterface::CCD));
+        assert!(devices[1].interfaces().is_empty());
+        assert_eq!(
+            "1073741825".parse::<DeviceInterface>().unwrap().bits(),
+            (1 << 30) | 1
+        );
+        assert!("camera".parse::<DeviceInterface>().is_err());
+        assert_eq!(devices[1].name, "Focuser");
+        assert!(devices[1].driver.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_discover_with_settle() {
+        let config = mock_server(
+            "",
+            "getProperties",
+            r#"<defTextVector device="Focuser" name="INFO" state="Idle" perm="ro">
+<defText name="NAME">focuser</defText>
+</defTextVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let start = Instant::now();
+        let devices = client
+            .discover_with(Duration::from_millis(50), Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(devices.len(), 1);
+        assert!(start.elapsed() < Client::DISCOVERY_SETTLE);
+    }
+}
@@ -0,0 +1,30 @@
+use crate::client::{BlobHandle, CommandRejected};
+use crate::message::MessageType;
+use crate::validation::Rejection;
+use std::sync::Arc;
+
+/// Event emitted by the client connection task
+///
+/// Events are delivered through a broadcast channel, see
+/// [`Client::subscribe`](super::Client::subscribe). Messages are shared via
+/// `Arc` so large BLOB payloads are not copied for every subscriber.
+#[derive(Debug, Clone)]
+pub enum ClientEvent {
+    /// A message was received from the server and applied to the client state
+    Message(Arc<MessageType>),
+    /// An outgoing update was refused by a validator and not sent
+    Rejected(Rejection),
+    /// A driver answered one of our updates with `Alert`
+    CommandRejected(CommandRejected),
+    /// The server stopped answering keepalive probes
+    ConnectionLost,
+    /// A BLOB was written to disk, see
+    /// [`Client::stream_blobs`](super::Client::stream_blobs)
+    BlobStored(BlobHandle),
+    /// The connection was established again after being lost, see
+    /// [`ReconnectPolicy`](super::ReconnectPolicy)
+    Reconnected,
+    /// The connection was closed, by [`Connection::disconnect`](super::connection::Connection::disconnect)
+    /// or by the server
+    Disconnected,
+}
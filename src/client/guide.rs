@@ -0,0 +1,86 @@
+use crate::client::{wait_for_ok, Client};
+use crate::error::Result;
+use std::time::Duration;
+use tracing::debug;
+
+/// North/south timed guide property of a telescope
+const TELESCOPE_TIMED_GUIDE_NS: &str = "TELESCOPE_TIMED_GUIDE_NS";
+/// East/west timed guide property of a telescope
+const TELESCOPE_TIMED_GUIDE_WE: &str = "TELESCOPE_TIMED_GUIDE_WE";
+
+/// Time allowed on top of the pulse duration for the driver to report completion
+const PULSE_COMPLETION_MARGIN: Duration = Duration::from_secs(5);
+
+/// Direction of a guide pulse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuideDirection {
+    /// Guide north (increasing declination)
+    North,
+    /// Guide south (decreasing declination)
+    South,
+    /// Guide east
+    East,
+    /// Guide west
+    West,
+}
+
+impl GuideDirection {
+    /// Property and element names used for this direction
+    fn property(self) -> (&'static str, &'static str) {
+        match self {
+            GuideDirection::North => (TELESCOPE_TIMED_GUIDE_NS, "TIMED_GUIDE_N"),
+            GuideDirection::South => (TELESCOPE_TIMED_GUIDE_NS, "TIMED_GUIDE_S"),
+            GuideDirection::East => (TELESCOPE_TIMED_GUIDE_WE, "TIMED_GUIDE_E"),
+            GuideDirection::West => (TELESCOPE_TIMED_GUIDE_WE, "TIMED_GUIDE_W"),
+        }
+    }
+}
+
+impl Client {
+    /// Send a guide pulse of `ms` milliseconds and wait for it to complete
+    ///
+    /// Writes the matching `TELESCOPE_TIMED_GUIDE_NS`/`WE` element and waits
+    /// until the driver reports the property as `Ok`.
+    pub async fn pulse_guide(
+        &self,
+        device: &str,
+        direction: GuideDirection,
+        ms: u32,
+    ) -> Result<()> {
+        let (name, element) = direction.property();
+        let mut events = self.subscribe();
+        self.set_number(device, name, &[(element, ms as f64)])
+            .await?;
+        debug!("Guiding {} {:?} for {} ms", device, direction, ms);
+
+        let timeout = Duration::from_millis(ms as u64) + PULSE_COMPLETION_MARGIN;
+        wait_for_ok(&mut events, device, name, timeout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::mock_server;
+
+    #[tokio::test]
+    async fn test_pulse_guide() {
+        let config = mock_server(
+            "",
+            r#"<oneNumber name="TIMED_GUIDE_W">250</oneNumber>"#,
+            r#"<setNumberVector device="Telescope Simulator" name="TELESCOPE_TIMED_GUIDE_WE" state="Busy"/>
+<setNumberVector device="Telescope Simulator" name="TELESCOPE_TIMED_GUIDE_WE" state="Ok">
+<oneNumber name="TIMED_GUIDE_W">0</oneNumber>
+<oneNumber name="TIMED_GUIDE_E">0</oneNumber>
+</setNumberVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+
+        client
+            .pulse_guide("Telescope Simulator", GuideDirection::West, 250)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,118 @@
+use crate::client::Client;
+use crate::property::{timestamp, Property, PropertyState, PropertyValue};
+use std::collections::{HashMap, VecDeque};
+
+/// Value and state of a property at one point in time
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertySample {
+    /// Timestamp of the update, or the time it was received if the server
+    /// sent none
+    pub timestamp: String,
+    /// Property state
+    pub state: PropertyState,
+    /// Property value
+    pub value: PropertyValue,
+}
+
+/// Last values of every property, oldest first
+#[derive(Debug, Default)]
+pub(crate) struct PropertyHistory {
+    /// Samples kept per property, 0 records nothing
+    depth: usize,
+    samples: HashMap<(String, String), VecDeque<PropertySample>>,
+}
+
+impl PropertyHistory {
+    pub(crate) fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            samples: HashMap::new(),
+        }
+    }
+
+    /// Record the current value of `property`, dropping the oldest sample
+    /// beyond the depth
+    pub(crate) fn record(&mut self, property: &Property) {
+        if self.depth == 0 {
+            return;
+        }
+        let key = (property.device.clone(), property.name.clone());
+        let samples = self.samples.entry(key).or_default();
+        if samples.len() == self.depth {
+            samples.pop_front();
+        }
+        let timestamp = match property.timestamp.is_empty() {
+            true => timestamp::generate(),
+            false => property.timestamp.clone(),
+        };
+        samples.push_back(PropertySample {
+            timestamp,
+            state: property.state,
+            value: property.value.clone(),
+        });
+    }
+
+    /// Samples of `device`/`name`, oldest first
+    pub(crate) fn get(&self, device: &str, name: &str) -> Vec<PropertySample> {
+        self.samples
+            .get(&(device.to_string(), name.to_string()))
+            .map(|samples| samples.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Forget `name` of `device`, or all its properties if `name` is `None`
+    pub(crate) fn remove(&mut self, device: &str, name: Option<&str>) {
+        self.samples
+            .retain(|(d, n), _| d != device || name.is_some_and(|name| n != name));
+    }
+}
+
+impl Client {
+    /// Recent values of a property, oldest first
+    ///
+    /// Every definition and update is recorded, up to
+    /// [`ClientConfig::history_depth`](super::ClientConfig::history_depth)
+    /// samples per property, e.g. to graph the focuser position or CCD
+    /// temperature. Empty if the history is disabled or the property was
+    /// never defined.
+    pub async fn history(&self, device: &str, name: &str) -> Vec<PropertySample> {
+        self.state.lock().await.history(device, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::{mock_server, wait_for_property};
+
+    #[tokio::test]
+    async fn test_history() {
+        let config = mock_server(
+            r#"<defNumberVector device="Focuser" name="ABS_FOCUS_POSITION" state="Idle" perm="rw" timestamp="2024-01-01T00:00:00"><defNumber name="FOCUS_ABSOLUTE_POSITION" format="%.0f" min="0" max="50000" step="1">100</defNumber></defNumberVector>
+<setNumberVector device="Focuser" name="ABS_FOCUS_POSITION" state="Busy" timestamp="2024-01-01T00:00:01"><oneNumber name="FOCUS_ABSOLUTE_POSITION">200</oneNumber></setNumberVector>
+<setNumberVector device="Focuser" name="ABS_FOCUS_POSITION" state="Ok" timestamp="2024-01-01T00:00:02"><oneNumber name="FOCUS_ABSOLUTE_POSITION">300</oneNumber></setNumberVector>
+"#,
+            "getProperties",
+            "",
+        )
+        .await;
+        let client = Client::new(config.with_history_depth(2)).await.unwrap();
+        wait_for_property(&client, "Focuser", "ABS_FOCUS_POSITION").await;
+        let mut history = Vec::new();
+        for _ in 0..100 {
+            history = client.history("Focuser", "ABS_FOCUS_POSITION").await;
+            if history.last().map(|sample| sample.state) == Some(PropertyState::Ok) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].timestamp, "2024-01-01T00:00:01");
+        assert_eq!(history[0].state, PropertyState::Busy);
+        assert_eq!(
+            history[1].value.number("FOCUS_ABSOLUTE_POSITION"),
+            Some(300.0)
+        );
+        assert!(client.history("Focuser", "UNKNOWN").await.is_empty());
+    }
+}
@@ -0,0 +1,248 @@
+use crate::client::{ClientState, RangeCheck};
+use crate::error::{Error, Result};
+use crate::format::parse_number;
+use crate::message::new::{NewNumberVector, NewSwitchVector};
+use crate::message::MessageType;
+use crate::property::{NumberElement, PropertyValue, SwitchRule, SwitchState};
+
+/// Distance from the step grid still accepted, relative to the step
+const STEP_TOLERANCE: f64 = 1e-9;
+
+/// Check a `newNumberVector` or `newSwitchVector` against the definition
+/// cached in `state`
+///
+/// Returns the message to send instead of `message` if values were
+/// clamped. Properties and elements that are not defined are not checked.
+pub(crate) fn check(
+    state: &ClientState,
+    message: &MessageType,
+    mode: RangeCheck,
+) -> Result<Option<MessageType>> {
+    match (mode, message) {
+        (RangeCheck::Off, _) => Ok(None),
+        (_, MessageType::NewNumberVector(new)) => check_numbers(state, new, mode),
+        (_, MessageType::NewSwitchVector(new)) => {
+            check_switches(state, new)?;
+            Ok(None)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn check_numbers(
+    state: &ClientState,
+    new: &NewNumberVector,
+    mode: RangeCheck,
+) -> Result<Option<MessageType>> {
+    let Some(PropertyValue::NumberVector(numbers)) = state
+        .get_property(&new.device, &new.name)
+        .map(|property| &property.value)
+    else {
+        return Ok(None);
+    };
+    let mut clamped = new.clone();
+    let mut changed = false;
+    for one in &mut clamped.elements {
+        let Some(number) = numbers.iter().find(|number| number.name == one.name) else {
+            continue;
+        };
+        let value = parse_number(&one.value)?;
+        let reason = if !in_range(number, value) {
+            format!("{} is outside {}..{}", value, number.min, number.max)
+        } else if snap(number, value) != value {
+            format!(
+                "{} is not a multiple of {} from {}",
+                value, number.step, number.min
+            )
+        } else {
+            continue;
+        };
+        if mode == RangeCheck::Reject {
+            return Err(out_of_range(&new.device, &new.name, &one.name, reason));
+        }
+        let mut allowed = snap(number, value);
+        if !in_range(number, allowed) {
+            allowed = allowed.clamp(number.min, number.max);
+        }
+        one.value = allowed.to_string();
+        changed = true;
+    }
+    Ok(changed.then_some(MessageType::NewNumberVector(clamped)))
+}
+
+/// Returns true if `value` is within the limits of `number`
+///
+/// A range with `min` not below `max` is unlimited; drivers define numbers
+/// without limits as `0..0`.
+fn in_range(number: &NumberElement, value: f64) -> bool {
+    number.min >= number.max || (number.min..=number.max).contains(&value)
+}
+
+/// `value` moved to the closest step from `min`, unchanged if it is already
+/// on a step or the number has none
+fn snap(number: &NumberElement, value: f64) -> f64 {
+    if number.step <= 0.0 {
+        return value;
+    }
+    let snapped = number.min + ((value - number.min) / number.step).round() * number.step;
+    if (snapped - value).abs() <= STEP_TOLERANCE * number.step {
+        value
+    } else {
+        snapped
+    }
+}
+
+/// Check the switches turned on against the rule of the vector
+///
+/// Like drivers do, turning one switch of a `OneOfMany` or `AtMostOne`
+/// vector on turns the others off. So only updates turning several on, or
+/// leaving a `OneOfMany` vector with none on, are refused.
+fn check_switches(state: &ClientState, new: &NewSwitchVector) -> Result<()> {
+    let Some(PropertyValue::SwitchVector(rule, switches)) = state
+        .get_property(&new.device, &new.name)
+        .map(|property| &property.value)
+    else {
+        return Ok(());
+    };
+    if *rule == SwitchRule::AnyOfMany {
+        return Ok(());
+    }
+    let turned_on = |name: &str| {
+        new.elements
+            .iter()
+            .any(|one| one.name == name && one.value == SwitchState::On)
+    };
+    let turned_off = |name: &str| {
+        new.elements
+            .iter()
+            .any(|one| one.name == name && one.value == SwitchState::Off)
+    };
+    let mut on = new
+        .elements
+        .iter()
+        .filter(|one| one.value == SwitchState::On);
+    if let (Some(_), Some(second)) = (on.next(), on.next()) {
+        let reason = format!("{:?} allows only one switch on", rule);
+        return Err(out_of_range(&new.device, &new.name, &second.name, reason));
+    }
+    let none_on = !switches.iter().any(|switch| {
+        turned_on(&switch.name) || (switch.state == SwitchState::On && !turned_off(&switch.name))
+    });
+    match new.elements.first() {
+        Some(first) if *rule == SwitchRule::OneOfMany && none_on => Err(out_of_range(
+            &new.device,
+            &new.name,
+            &first.name,
+            "OneOfMany needs one switch on".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn out_of_range(device: &str, name: &str, element: &str, reason: String) -> Error {
+    Error::OutOfRange {
+        device: device.to_string(),
+        name: name.to_string(),
+        element: element.to_string(),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::{mock_server, wait_for_property};
+    use crate::client::Client;
+    use std::str::FromStr;
+
+    fn state() -> ClientState {
+        let mut state = ClientState::new();
+        for xml in [
+            r#"<defNumberVector device="CCD" name="CCD_GAIN" state="Idle" perm="rw"><defNumber name="GAIN" format="%.0f" min="0" max="300" step="10">100</defNumber><defNumber name="OFFSET" format="%.0f" min="0" max="0" step="0">10</defNumber></defNumberVector>"#,
+            r#"<defSwitchVector device="Mount" name="SLEW_RATE" state="Idle" perm="rw" rule="OneOfMany"><defSwitch name="SLOW">On</defSwitch><defSwitch name="FAST">Off</defSwitch></defSwitchVector>"#,
+        ] {
+            state.update(&MessageType::from_str(xml).unwrap()).unwrap();
+        }
+        state
+    }
+
+    fn gain(value: &str) -> MessageType {
+        MessageType::from_str(&format!(
+            r#"<newNumberVector device="CCD" name="CCD_GAIN"><oneNumber name="GAIN">{}</oneNumber><oneNumber name="OFFSET">-5</oneNumber></newNumberVector>"#,
+            value
+        ))
+        .unwrap()
+    }
+
+    fn slew_rate(slow: &str, fast: &str) -> MessageType {
+        MessageType::from_str(&format!(
+            r#"<newSwitchVector device="Mount" name="SLEW_RATE"><oneSwitch name="SLOW">{}</oneSwitch><oneSwitch name="FAST">{}</oneSwitch></newSwitchVector>"#,
+            slow, fast
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_range_check() {
+        let state = state();
+        assert!(check(&state, &gain("120"), RangeCheck::Reject)
+            .unwrap()
+            .is_none());
+        assert!(check(&state, &gain("3000"), RangeCheck::Off)
+            .unwrap()
+            .is_none());
+        let error = check(&state, &gain("3000"), RangeCheck::Reject).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "CCD.CCD_GAIN.GAIN: 3000 is outside 0..300"
+        );
+        let error = check(&state, &gain("125"), RangeCheck::Reject).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "CCD.CCD_GAIN.GAIN: 125 is not a multiple of 10 from 0"
+        );
+
+        for (value, expected) in [("3000", "300"), ("-1", "0"), ("124", "120")] {
+            let Some(MessageType::NewNumberVector(clamped)) =
+                check(&state, &gain(value), RangeCheck::Clamp).unwrap()
+            else {
+                panic!("Expected a clamped update");
+            };
+            assert_eq!(clamped.elements[0].value, expected);
+            assert_eq!(clamped.elements[1].value, "-5");
+        }
+
+        assert!(check(&state, &slew_rate("Off", "On"), RangeCheck::Reject).is_ok());
+        let error = check(&state, &slew_rate("On", "On"), RangeCheck::Clamp).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Mount.SLEW_RATE.FAST: OneOfMany allows only one switch on"
+        );
+        assert!(check(&state, &slew_rate("Off", "Off"), RangeCheck::Reject).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_clamps_values() {
+        let greeting = r#"<defNumberVector device="CCD" name="CCD_GAIN" state="Idle" perm="rw"><defNumber name="GAIN" format="%.0f" min="0" max="300" step="1">100</defNumber></defNumberVector>
+"#;
+        let config = mock_server(greeting, "newNumberVector", "").await;
+        let client = Client::new(config.with_range_check(RangeCheck::Reject))
+            .await
+            .unwrap();
+        wait_for_property(&client, "CCD", "CCD_GAIN").await;
+        let result = client
+            .set_number("CCD", "CCD_GAIN", &[("GAIN", 3000.0)])
+            .await;
+        assert!(matches!(result, Err(Error::OutOfRange { element, .. }) if element == "GAIN"));
+
+        let config = mock_server(greeting, "newNumberVector", "").await;
+        let client = Client::new(config.with_range_check(RangeCheck::Clamp))
+            .await
+            .unwrap();
+        wait_for_property(&client, "CCD", "CCD_GAIN").await;
+        client
+            .set_number("CCD", "CCD_GAIN", &[("GAIN", 3000.0)])
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Round-trip times of updates to one property
+///
+/// Measured from sending a `new*Vector` until the driver reports `Ok` or
+/// `Alert` for the property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LatencyStats {
+    /// Completed round trips
+    pub count: u64,
+    /// Most recent round trip
+    pub last: Duration,
+    /// Shortest round trip
+    pub min: Duration,
+    /// Longest round trip
+    pub max: Duration,
+    /// Sum of all round trips
+    pub total: Duration,
+}
+
+impl LatencyStats {
+    /// Mean round trip, zero if none completed
+    pub fn mean(&self) -> Duration {
+        match u32::try_from(self.count) {
+            Ok(0) => Duration::ZERO,
+            Ok(count) => self.total / count,
+            Err(_) => self.total.div_f64(self.count as f64),
+        }
+    }
+
+    /// Add a completed round trip
+    fn record(&mut self, latency: Duration) {
+        self.min = if self.count == 0 {
+            latency
+        } else {
+            self.min.min(latency)
+        };
+        self.max = self.max.max(latency);
+        self.last = latency;
+        self.total += latency;
+        self.count += 1;
+    }
+}
+
+/// Traffic counters of a client connection
+///
+/// Returned by [`Client::metrics`](super::Client::metrics) as a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ClientMetrics {
+    /// Bytes written to the server
+    pub bytes_sent: u64,
+    /// Bytes read from the server
+    pub bytes_received: u64,
+    /// Messages parsed successfully
+    pub messages_parsed: u64,
+    /// Frames that could not be parsed
+    pub parse_errors: u64,
+    /// Outgoing messages discarded by [`OverflowPolicy::DropOldest`](super::OverflowPolicy::DropOldest)
+    pub send_queue_dropped: u64,
+    /// Round-trip times by device and property name
+    pub latencies: HashMap<(String, String), LatencyStats>,
+}
+
+impl ClientMetrics {
+    /// Round-trip times of `device`/`name`, if any update completed
+    pub fn latency(&self, device: &str, name: &str) -> Option<&LatencyStats> {
+        self.latencies.get(&(device.to_string(), name.to_string()))
+    }
+
+    /// Add a completed round trip for `device`/`name`
+    pub(crate) fn record_latency(&mut self, device: String, name: String, latency: Duration) {
+        self.latencies
+            .entry((device, name))
+            .or_default()
+            .record(latency);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::mock_server;
+    use crate::client::{wait_for_ok, Client};
+    use crate::property::SwitchState;
+
+    #[test]
+    fn test_latency_stats() {
+        let mut stats = LatencyStats::default();
+        assert_eq!(stats.mean(), Duration::ZERO);
+        for ms in [30, 10, 20] {
+            stats.record(Duration::from_millis(ms));
+        }
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.min, Duration::from_millis(10));
+        assert_eq!(stats.max, Duration::from_millis(30));
+        assert_eq!(stats.last, Duration::from_millis(20));
+        assert_eq!(stats.mean(), Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn test_client_metrics() {
+        let config = mock_server(
+            "<delProperty/>\n",
+            "newSwitchVector",
+            r#"<setSwitchVector device="CCD" name="CONNECTION" state="Ok"><oneSwitch name="CONNECT">On</oneSwitch></setSwitchVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let mut events = client.subscribe();
+        client
+            .set_switch("CCD", "CONNECTION", &[("CONNECT", SwitchState::On)])
+            .await
+            .unwrap();
+        wait_for_ok(&mut events, "CCD", "CONNECTION", Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        let metrics = client.metrics();
+        assert!(metrics.bytes_sent > 0);
+        assert!(metrics.bytes_received > 0);
+        assert_eq!(metrics.messages_parsed, 1);
+        assert_eq!(metrics.parse_errors, 1);
+        assert_eq!(metrics.latency("CCD", "CONNECTION").unwrap().count, 1);
+    }
+}
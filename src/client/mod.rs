@@ -1,27 +1,117 @@
-use crate::error::Result;
-use crate::message::Message;
-use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use crate::debug::DebugOptions;
+use crate::error::{Error, Result};
+use crate::message::basic::{EnableBlob, GetProperties, Message, PingReply, PingRequest};
+use crate::message::new::{NewNumberVector, NewSwitchVector, OneNumber, OneSwitch};
+use crate::message::stream::Framer;
+use crate::message::MessageType;
+use crate::property::timestamp::TimestampPolicy;
+use crate::property::{Property, PropertyState, SwitchState};
+use crate::validation::Validators;
+use crate::PROTOCOL_VERSION;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
 use tokio::net::{
     tcp::{OwnedReadHalf, OwnedWriteHalf},
     TcpStream,
 };
-use tokio::sync::Mutex;
-use tracing::{debug, error};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch, Mutex, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, error, warn};
 
+/// Credentials presented to authenticating servers
+mod auth;
+/// Batched property updates for INDI client
+mod batch;
+/// Second connection carrying only BLOBs
+mod blob_link;
+/// Streaming of incoming BLOBs to disk
+mod blob_stream;
+/// Fluent construction of INDI clients
+mod builder;
+/// Persistent cache of the property tree
+#[cfg(feature = "serde_json")]
+mod cache;
+/// Async callback registration for INDI client
+mod callbacks;
 /// Configuration module for INDI client
 mod config;
 /// Connection handling for INDI protocol
 pub mod connection;
+/// Device discovery for INDI client
+mod discover;
+/// Events emitted by the INDI client
+mod event;
+/// Pulse guiding for INDI client
+mod guide;
+/// Recent values of properties
+mod history;
+/// Checks of outgoing values against property definitions
+mod limits;
 /// Message handling module for INDI client
 pub mod message;
+/// Traffic and latency metrics for INDI client
+mod metrics;
+/// Watch channels of single properties
+mod observe;
+/// Connections to several servers with a merged device namespace
+pub mod pool;
+/// SOCKS5 and HTTP proxy tunneling of client connections
+mod proxy;
+/// Bounded queue of outgoing messages
+mod queue;
+/// Classification of updates rejected by drivers
+mod rejected;
+/// Search index over property and element names
+mod search;
 /// State management module for INDI client
 mod state;
+/// Test helpers for INDI client
+#[cfg(test)]
+pub(crate) mod testing;
+/// Undo history of property writes
+mod undo;
+/// Helpers for awaiting driver acknowledgements
+mod wait;
 
+pub use self::auth::Credential;
+pub use self::batch::BatchCompletion;
+pub use self::blob_stream::{BlobHandle, BlobInfo, BlobTarget};
+use self::blob_stream::{BlobOpener, BlobStream, Step};
+pub use self::builder::ClientBuilder;
+pub use self::callbacks::CallbackId;
+use self::callbacks::Callbacks;
 use self::connection::Connection;
 pub use self::message::MessageHandler;
-pub use config::ClientConfig;
+use blob_link::BlobLink;
+pub use config::{
+    BlobPolicy, ClientConfig, KeepAlive, KeepAliveProbe, OverflowPolicy, RangeCheck,
+    ReconnectPolicy,
+};
+pub use discover::{DeviceInfo, DeviceInterface, DriverInfo};
+pub use event::ClientEvent;
+pub use guide::GuideDirection;
+pub use history::PropertySample;
+pub use metrics::{ClientMetrics, LatencyStats};
+use observe::Watchers;
+pub use proxy::{Proxy, ProxyKind};
+use queue::{Pushed, SendQueue};
+use rejected::PendingCommands;
+pub use rejected::{CommandRejected, RejectionKind};
+pub use search::{SearchHit, SearchIndex};
 pub use state::ClientState;
+use undo::UndoHistory;
+pub(crate) use wait::wait_for_ok;
+use wait::{set_vector_state, wait_for_final_state};
+
+/// Capacity of the client event channel
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+/// Start of the only messages read from a BLOB connection
+const BLOB_ROOT: &[u8] = b"<setBLOBVector";
 
 /// INDI client implementation
 ///
@@ -34,28 +124,108 @@ pub use state::ClientState;
 pub struct Client {
     config: ClientConfig,
     state: Arc<Mutex<ClientState>>,
-    reader: Arc<Mutex<BufReader<OwnedReadHalf>>>,
     writer: Arc<Mutex<BufWriter<OwnedWriteHalf>>>,
+    events: broadcast::Sender<ClientEvent>,
+    validators: Arc<RwLock<Validators>>,
+    callbacks: Arc<RwLock<Callbacks>>,
+    pending: Arc<std::sync::Mutex<PendingCommands>>,
+    metrics: Arc<std::sync::Mutex<ClientMetrics>>,
+    greeting: Arc<std::sync::Mutex<Greeting>>,
+    queue: Arc<SendQueue>,
+    tasks: Arc<std::sync::Mutex<Option<ConnectionTasks>>>,
+    search: Arc<RwLock<SearchIndex>>,
+    /// Generation of the connection, bumped on every reconnect; `None` once
+    /// the connection is gone for good
+    link: Arc<watch::Sender<Option<u64>>>,
+    /// Devices BLOB delivery was requested for on this connection
+    blob_devices: Arc<std::sync::Mutex<HashSet<String>>>,
+    /// Ends the reader after a write timed out part way through a message
+    write_failed: Arc<Notify>,
+    debug: Arc<DebugOptions>,
+    undo: Arc<std::sync::Mutex<UndoHistory>>,
+    blob_opener: Arc<std::sync::RwLock<Option<BlobOpener>>>,
+    watchers: Arc<std::sync::Mutex<Watchers>>,
+    blob_link: Option<Arc<BlobLink>>,
+}
+
+/// Background tasks serving the connection, taken on disconnect
+#[derive(Debug)]
+struct ConnectionTasks {
+    reader: JoinHandle<()>,
+    writer: JoinHandle<()>,
+}
+
+/// Traffic received before the client's first `getProperties`
+#[derive(Debug, Default)]
+struct Greeting {
+    complete: bool,
+    messages: Vec<Message>,
 }
 
 impl Client {
     /// Create a new client
+    ///
+    /// Connects to the server and spawns the connection task which parses
+    /// incoming messages, applies them to the client state and publishes
+    /// them as [`ClientEvent`]s.
     pub async fn new(config: ClientConfig) -> Result<Self> {
-        debug!("Connecting to {}:{}", config.host, config.port);
-        let stream = TcpStream::connect((config.host.as_str(), config.port)).await?;
+        let stream = connect(&config).await?;
         let (read_half, write_half) = stream.into_split();
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let blob_link = match config.blob_connection {
+            true => Some(BlobLink::connect(&config).await?),
+            false => None,
+        };
+        let (blob_link, blob_reader) = match blob_link {
+            Some((link, reader)) => (Some(Arc::new(link)), Some(reader)),
+            None => (None, None),
+        };
 
-        Ok(Self {
-            config,
-            state: Arc::new(Mutex::new(ClientState::default())),
-            reader: Arc::new(Mutex::new(BufReader::new(read_half))),
+        let client = Self {
+            state: Arc::new(Mutex::new(ClientState::with_history_depth(
+                config.history_depth,
+            ))),
             writer: Arc::new(Mutex::new(BufWriter::new(write_half))),
-        })
-    }
+            events,
+            validators: Arc::new(RwLock::new(Validators::new())),
+            callbacks: Arc::new(RwLock::new(Callbacks::default())),
+            pending: Arc::new(std::sync::Mutex::new(PendingCommands::default())),
+            metrics: Arc::new(std::sync::Mutex::new(ClientMetrics::default())),
+            greeting: Arc::new(std::sync::Mutex::new(Greeting::default())),
+            queue: Arc::new(SendQueue::new(config.send_queue_capacity, config.overflow)),
+            tasks: Arc::new(std::sync::Mutex::new(None)),
+            search: Arc::new(RwLock::new(SearchIndex::new())),
+            link: Arc::new(watch::Sender::new(Some(0))),
+            blob_devices: Arc::new(std::sync::Mutex::new(HashSet::new())),
+            write_failed: Arc::new(Notify::new()),
+            debug: Arc::new(DebugOptions::new()),
+            undo: Arc::new(std::sync::Mutex::new(UndoHistory::default())),
+            blob_opener: Arc::new(std::sync::RwLock::new(None)),
+            watchers: Arc::new(std::sync::Mutex::new(Watchers::default())),
+            blob_link,
+            config,
+        };
+        client.state.lock().await.connected = true;
+
+        let writer_client = client.clone();
+        let writer = tokio::spawn(async move {
+            if let Err(e) = writer_client.writer_task().await {
+                error!(
+                    "Error writing to server {}:{}: {}",
+                    writer_client.config.host, writer_client.config.port, e
+                );
+            }
+        });
+        let task_client = client.clone();
+        let reader = tokio::spawn(async move { task_client.reader_task(read_half).await });
+        if let Ok(mut tasks) = client.tasks.lock() {
+            *tasks = Some(ConnectionTasks { reader, writer });
+        }
+        if let (Some(link), Some(reader)) = (&client.blob_link, blob_reader) {
+            client.spawn_blob_link(link.clone(), reader);
+        }
 
-    /// Get reader
-    pub fn reader(&self) -> Arc<Mutex<BufReader<OwnedReadHalf>>> {
-        self.reader.clone()
+        Ok(client)
     }
 
     /// Get writer
@@ -68,61 +238,1212 @@ impl Client {
         self.state.clone()
     }
 
-    /// Read messages from the server
-    pub async fn read_messages(&self) -> Result<()> {
+    /// Protocol debugging switches of this connection
+    ///
+    /// Flags take effect immediately, e.g. to log raw XML while chasing a
+    /// failure without reconnecting.
+    pub fn debug_options(&self) -> &DebugOptions {
+        &self.debug
+    }
+
+    /// Snapshot of the traffic counters and round-trip times
+    pub fn metrics(&self) -> ClientMetrics {
+        self.metrics
+            .lock()
+            .map(|metrics| metrics.clone())
+            .unwrap_or_default()
+    }
+
+    /// Site-wide messages the server sent before our first `getProperties`
+    ///
+    /// Some servers announce themselves with a burst of messages right after
+    /// the connection is accepted. Definitions in that burst are applied to
+    /// the state like any other; unsupported elements are skipped.
+    pub fn server_greeting(&self) -> Vec<Message> {
+        self.greeting
+            .lock()
+            .map(|greeting| greeting.messages.clone())
+            .unwrap_or_default()
+    }
+
+    /// Subscribe to events received from the server
+    pub fn subscribe(&self) -> broadcast::Receiver<ClientEvent> {
+        self.events.subscribe()
+    }
+
+    /// Send a message to the server
+    ///
+    /// Numbers and switches are checked against the cached definition as
+    /// set by [`ClientConfig::range_check`] and stamped as set by
+    /// [`ClientConfig::timestamp_policy`]. `new*Vector` messages are then
+    /// checked against the registered validators; a refused update is not
+    /// sent, is published as [`ClientEvent::Rejected`] and fails with
+    /// [`Error::Rejected`].
+    pub async fn send(&self, message: &MessageType) -> Result<()> {
+        self.dispatch(message, true).await
+    }
+
+    /// Validate and queue `message`, recording the values it overwrites for
+    /// [`Client::undo_last`] if `remember` is set
+    async fn dispatch(&self, message: &MessageType, remember: bool) -> Result<()> {
+        let clamped = limits::check(&*self.state.lock().await, message, self.config.range_check)?;
+        let message = clamped.as_ref().unwrap_or(message);
+        let stamped = match self.config.timestamp_policy {
+            TimestampPolicy::Off => None,
+            policy => {
+                let mut stamped = message.clone();
+                stamped.stamp(policy);
+                Some(stamped)
+            }
+        };
+        let message = stamped.as_ref().unwrap_or(message);
+        let verdict = self
+            .validators
+            .read()
+            .map_err(|_| Error::Message("Validator registry poisoned".to_string()))?
+            .validate(message);
+        if let Err(rejection) = verdict {
+            debug!("Refusing to send update: {}", rejection);
+            // Having no subscribers is not an error
+            let _ = self.events.send(ClientEvent::Rejected(rejection.clone()));
+            return Err(Error::Rejected(rejection));
+        }
+        let restore = if remember {
+            undo::restoring_update(&*self.state.lock().await, message)
+        } else {
+            None
+        };
+        self.write_message(&message.to_xml()?).await?;
+        if let Some(restore) = restore {
+            if let Ok(mut undo) = self.undo.lock() {
+                undo.push(restore);
+            }
+        }
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.sent(message);
+        }
+        if matches!(message, MessageType::GetProperties(_)) {
+            if let Ok(mut greeting) = self.greeting.lock() {
+                greeting.complete = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Register a validator run before updates to `device`/`name` are sent
+    ///
+    /// The validator receives the `new*Vector` message and returns
+    /// `Err(reason)` to refuse it.
+    pub fn add_validator<F>(&self, device: &str, name: &str, validator: F)
+    where
+        F: Fn(&MessageType) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        if let Ok(mut validators) = self.validators.write() {
+            validators.add(device, name, validator);
+        }
+    }
+
+    /// Request property definitions, optionally scoped to a device and property
+    pub async fn get_properties(&self, device: Option<&str>, name: Option<&str>) -> Result<()> {
+        self.send(&MessageType::GetProperties(GetProperties {
+            version: PROTOCOL_VERSION.to_string(),
+            device: device.map(str::to_string),
+            name: name.map(str::to_string),
+        }))
+        .await
+    }
+
+    /// Request several properties and wait for their definitions
+    ///
+    /// Sends one scoped `getProperties` per distinct `(device, name)` pair
+    /// that is not already defined, then waits until every definition has
+    /// arrived or `timeout` expires. Results are returned in the order of
+    /// `items`; properties that were not defined in time yield
+    /// [`Error::Timeout`].
+    pub async fn get_properties_batch(
+        &self,
+        items: &[(&str, &str)],
+        timeout: Duration,
+    ) -> Result<Vec<Result<Property>>> {
+        // Subscribe before sending so no definition can be missed
+        let mut events = self.subscribe();
+        let mut pending = {
+            let state = self.state.lock().await;
+            items
+                .iter()
+                .filter(|(device, name)| state.get_property(device, name).is_none())
+                .map(|(device, name)| (device.to_string(), name.to_string()))
+                .collect::<HashSet<_>>()
+        };
+        for (device, name) in &pending {
+            self.get_properties(Some(device), Some(name)).await?;
+        }
+
+        let wait = async {
+            while !pending.is_empty() {
+                match events.recv().await {
+                    Ok(ClientEvent::Message(message)) => {
+                        if let Some(key) = definition_key(&message) {
+                            pending.remove(&key);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Missed {} events while waiting for definitions", skipped);
+                        // Definitions may have been among the skipped events
+                        let state = self.state.lock().await;
+                        pending.retain(|(device, name)| state.get_property(device, name).is_none());
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        };
+        // Missing properties are reported per item below
+        let _ = tokio::time::timeout(timeout, wait).await;
+
+        let state = self.state.lock().await;
+        Ok(items
+            .iter()
+            .map(|(device, name)| {
+                state
+                    .get_property(device, name)
+                    .cloned()
+                    .ok_or_else(|| Error::Timeout(format!("{}.{} was not defined", device, name)))
+            })
+            .collect())
+    }
+
+    /// Control BLOB delivery for a device or one of its properties
+    ///
+    /// `mode` is one of the INDI BLOB handling modes `Never`, `Also` or `Only`.
+    pub async fn enable_blob(&self, device: &str, name: Option<&str>, mode: &str) -> Result<()> {
+        self.send(&MessageType::EnableBlob(EnableBlob {
+            device: device.to_string(),
+            name: name.map(str::to_string),
+            mode: mode.to_string(),
+        }))
+        .await
+    }
+
+    /// Send new values for elements of a number vector
+    pub async fn set_number(&self, device: &str, name: &str, values: &[(&str, f64)]) -> Result<()> {
+        self.send(&MessageType::NewNumberVector(NewNumberVector {
+            device: device.to_string(),
+            name: name.to_string(),
+            timestamp: None,
+            elements: values
+                .iter()
+                .map(|(element, value)| OneNumber {
+                    name: element.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+        }))
+        .await
+    }
+
+    /// Send new states for elements of a switch vector
+    pub async fn set_switch(
+        &self,
+        device: &str,
+        name: &str,
+        values: &[(&str, SwitchState)],
+    ) -> Result<()> {
+        self.send(&MessageType::NewSwitchVector(NewSwitchVector {
+            device: device.to_string(),
+            name: name.to_string(),
+            timestamp: None,
+            elements: values
+                .iter()
+                .map(|(element, value)| OneSwitch {
+                    name: element.to_string(),
+                    value: *value,
+                })
+                .collect(),
+        }))
+        .await
+    }
+
+    /// Switch one element and wait for the driver to acknowledge it
+    ///
+    /// Resolves with the property state of the first `setSwitchVector` that
+    /// is not `Busy`. An `Alert` fails with the driver's message; no answer
+    /// within [`Client::ACKNOWLEDGE_TIMEOUT`] fails with [`Error::Timeout`].
+    pub async fn set_switch_and_wait(
+        &self,
+        device: &str,
+        name: &str,
+        element: &str,
+        state: SwitchState,
+    ) -> Result<PropertyState> {
+        let mut events = self.subscribe();
+        self.set_switch(device, name, &[(element, state)]).await?;
+        let acknowledged =
+            wait_for_final_state(&mut events, device, name, Self::ACKNOWLEDGE_TIMEOUT).await?;
+        match acknowledged {
+            Some(state) => Ok(state),
+            None => Ok(self
+                .state
+                .lock()
+                .await
+                .get_property(device, name)
+                .map_or(PropertyState::Idle, |property| property.state)),
+        }
+    }
+
+    /// Time a driver has to acknowledge [`Client::set_switch_and_wait`]
+    pub const ACKNOWLEDGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// Queue a raw XML message for the server
+    ///
+    /// Applies [`ClientConfig::overflow`] when the send queue is full; a
+    /// blocked send fails after the write timeout.
+    async fn write_message(&self, message: &str) -> Result<()> {
+        let push = self.queue.push(message.to_string());
+        let pushed = match self.config.write_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, push).await.map_err(|_| {
+                Error::Timeout(format!(
+                    "Send queue to {}:{} stayed full for {:?}",
+                    self.config.host, self.config.port, timeout
+                ))
+            })??,
+            None => push.await?,
+        };
+        if pushed == Pushed::DroppedOldest {
+            warn!(
+                "Send queue to {}:{} is full, dropped the oldest message",
+                self.config.host, self.config.port
+            );
+            self.update_metrics(|metrics| metrics.send_queue_dropped += 1);
+        }
+        Ok(())
+    }
+
+    /// Write queued messages to the server until the queue is closed
+    async fn writer_task(&self) -> Result<()> {
+        let result = async {
+            while let Some(message) = self.queue.pop().await {
+                debug!(
+                    "Sending message to {}:{}: {}",
+                    self.config.host,
+                    self.config.port,
+                    message.trim()
+                );
+                self.debug.log_xml(&self.peer(), true, &message);
+                loop {
+                    let generation = *self.link.borrow();
+                    let Err(e) = self.write_one(&message).await else {
+                        break;
+                    };
+                    if self.config.reconnect.is_none() {
+                        return Err(e);
+                    }
+                    // Keep the message for the next connection
+                    debug!("Write failed, waiting for reconnect: {}", e);
+                    let mut link = self.link.subscribe();
+                    let reconnected = link
+                        .wait_for(|current| *current != generation)
+                        .await
+                        .is_ok_and(|current| current.is_some());
+                    if !reconnected {
+                        return Err(e);
+                    }
+                }
+                self.update_metrics(|metrics| metrics.bytes_sent += message.len() as u64 + 1);
+            }
+            Ok(())
+        }
+        .await;
+        // Later sends fail instead of queueing for a dead connection
+        self.queue.close();
+        result
+    }
+
+    /// Server address for log messages
+    fn peer(&self) -> String {
+        format!("{}:{}", self.config.host, self.config.port)
+    }
+
+    /// Write one message to the socket, within the write timeout
+    ///
+    /// A write that times out may have sent part of the message, and
+    /// nothing written after it would parse, so the connection is closed.
+    async fn write_one(&self, message: &str) -> Result<()> {
+        let mut writer = self.writer.lock().await;
+        let write = async {
+            writer.write_all(message.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+            writer.flush().await
+        };
+        let Some(timeout) = self.config.write_timeout else {
+            return Ok(write.await?);
+        };
+        match tokio::time::timeout(timeout, write).await {
+            Ok(written) => Ok(written?),
+            Err(_) => {
+                // Skips the buffered rest of the message, shutting the
+                // socket down does not wait for the server to read
+                if let Err(e) = writer.get_mut().shutdown().await {
+                    debug!("Failed to shut down connection: {}", e);
+                }
+                self.write_failed.notify_one();
+                Err(Error::Timeout(format!(
+                    "Writing to {}:{} took longer than {:?}",
+                    self.config.host, self.config.port, timeout
+                )))
+            }
+        }
+    }
+
+    /// Serve the connection, reconnecting if configured, until it is gone
+    async fn reader_task(&self, mut read_half: OwnedReadHalf) {
+        loop {
+            if let Err(e) = self.connection_task(read_half).await {
+                error!(
+                    "Error reading from server {}:{}: {}",
+                    self.config.host, self.config.port, e
+                );
+            }
+            self.state.lock().await.connected = false;
+            // Having no subscribers is not an error
+            let _ = self.events.send(ClientEvent::Disconnected);
+            let Some(policy) = self.config.reconnect else {
+                break;
+            };
+            match self.reconnect(policy).await {
+                Some(reconnected) => read_half = reconnected,
+                None => break,
+            }
+        }
+        self.link.send_replace(None);
+        self.queue.close();
+    }
+
+    /// Connect again with backoff, returning the new read half
+    ///
+    /// Returns `None` once `policy` gives up.
+    async fn reconnect(&self, policy: ReconnectPolicy) -> Option<OwnedReadHalf> {
+        let (stream, attempts) = self.connect_with_backoff(policy).await?;
+        let (read_half, write_half) = stream.into_split();
+        *self.writer.lock().await = BufWriter::new(write_half);
+        if let Ok(mut devices) = self.blob_devices.lock() {
+            devices.clear();
+        }
+        self.state.lock().await.connected = true;
+        self.link
+            .send_modify(|generation| *generation = generation.map(|g| g + 1));
+        debug!(
+            "Reconnected to {}:{} after {} attempts",
+            self.config.host, self.config.port, attempts
+        );
+        // Having no subscribers is not an error
+        let _ = self.events.send(ClientEvent::Reconnected);
+        // The server only sends definitions on request
+        if let Err(e) = self.get_properties(None, None).await {
+            debug!("Failed to request properties after reconnect: {}", e);
+        }
+        Some(read_half)
+    }
+
+    /// Open a new connection, waiting longer after every failed attempt
+    ///
+    /// Returns the stream and the number of attempts it took, or `None`
+    /// once `policy` gives up.
+    async fn connect_with_backoff(&self, policy: ReconnectPolicy) -> Option<(TcpStream, u32)> {
+        let mut delay = policy.initial_delay;
+        let mut attempts = 0;
+        loop {
+            if policy.max_attempts.is_some_and(|max| attempts >= max) {
+                warn!(
+                    "Giving up reconnecting to {}:{} after {} attempts",
+                    self.config.host, self.config.port, attempts
+                );
+                return None;
+            }
+            attempts += 1;
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(policy.max_delay);
+
+            match connect(&self.config).await {
+                Ok(stream) => return Some((stream, attempts)),
+                Err(e) => debug!("Reconnect attempt {} failed: {}", attempts, e),
+            }
+        }
+    }
+
+    /// Read and dispatch messages from the server until the connection closes
+    ///
+    /// Also enforces the read idle timeout and, if configured, probes a
+    /// quiet server and reports [`ClientEvent::ConnectionLost`] when it does
+    /// not answer.
+    async fn connection_task(&self, mut reader: OwnedReadHalf) -> Result<()> {
         debug!(
             "Starting message reader for {}:{}",
             self.config.host, self.config.port
         );
-        let mut buf = Vec::new();
+        let keepalive = self.config.keepalive;
+        let mut ticker = keepalive.map(|k| {
+            let period = (k.interval.min(k.timeout) / 2).max(Duration::from_millis(10));
+            tokio::time::interval(period)
+        });
+        let mut last_received = Instant::now();
+        let mut probe_sent = false;
+        let mut probes = 0u64;
+
+        let mut framer = Framer::new();
+        let mut chunk = vec![0u8; 64 * 1024];
+        let mut blob_stream: Option<(BlobStream, BlobOpener)> = None;
         loop {
-            let mut reader = self.reader.lock().await;
-            match reader.read_until(b'>', &mut buf).await {
-                Ok(0) => {
-                    debug!("Server closed connection");
-                    break;
+            let tick = async {
+                match ticker.as_mut() {
+                    Some(ticker) => ticker.tick().await,
+                    None => std::future::pending().await,
                 }
-                Ok(_) => {
-                    let message = Message::new(String::from_utf8_lossy(&buf).to_string());
-                    debug!("Received message: {}", message.content);
-                    buf.clear();
+            };
+            let read = async {
+                match self.config.read_idle_timeout {
+                    Some(timeout) => {
+                        tokio::time::timeout_at(last_received + timeout, reader.read(&mut chunk))
+                            .await
+                            .map_err(|_| {
+                                Error::Timeout(format!(
+                                    "No data from {}:{} for {:?}",
+                                    self.config.host, self.config.port, timeout
+                                ))
+                            })?
+                            .map_err(Error::from)
+                    }
+                    None => reader.read(&mut chunk).await.map_err(Error::from),
                 }
-                Err(e) => {
-                    error!(
-                        "Error reading from server {}:{}: {}",
-                        self.config.host, self.config.port, e
-                    );
-                    return Err(e.into());
+            };
+
+            tokio::select! {
+                n = read => {
+                    let n = n?;
+                    if n == 0 {
+                        debug!("Server closed connection");
+                        break;
+                    }
+                    last_received = Instant::now();
+                    probe_sent = false;
+                    self.update_metrics(|metrics| metrics.bytes_received += n as u64);
+                    framer.push(&chunk[..n]);
+                    self.drain_frames(&mut framer, &mut blob_stream, false).await;
+                }
+                _ = self.write_failed.notified() => {
+                    return Err(Error::Timeout(format!(
+                        "Closed connection to {}:{} after a write timed out",
+                        self.config.host, self.config.port
+                    )));
+                }
+                _ = tick => {
+                    let Some(keepalive) = keepalive else { continue };
+                    let silent = last_received.elapsed();
+                    if probe_sent && silent >= keepalive.interval + keepalive.timeout {
+                        warn!(
+                            "Server {}:{} did not answer keepalive",
+                            self.config.host, self.config.port
+                        );
+                        // Having no subscribers is not an error
+                        let _ = self.events.send(ClientEvent::ConnectionLost);
+                        return Err(Error::Timeout(format!(
+                            "No answer from {}:{} for {:?}",
+                            self.config.host, self.config.port, silent
+                        )));
+                    }
+                    if !probe_sent && silent >= keepalive.interval {
+                        probes += 1;
+                        self.send_probe(keepalive.probe, probes).await?;
+                        probe_sent = true;
+                    }
                 }
             }
         }
         Ok(())
     }
+
+    /// Handle the complete messages in `framer`
+    ///
+    /// BLOBs are streamed to their target while an opener is set. With
+    /// `blobs_only`, anything but `setBLOBVector` is dropped.
+    async fn drain_frames(
+        &self,
+        framer: &mut Framer,
+        blob_stream: &mut Option<(BlobStream, BlobOpener)>,
+        blobs_only: bool,
+    ) {
+        loop {
+            if let Some((stream, opener)) = blob_stream.as_mut() {
+                match stream.advance(framer.buffer_mut(), opener).await {
+                    Ok(Step::NeedMore) => break,
+                    Ok(Step::Done(message, handles)) => {
+                        *blob_stream = None;
+                        self.update_metrics(|metrics| metrics.messages_parsed += 1);
+                        self.handle_message(*message).await;
+                        for handle in handles {
+                            let _ = self.events.send(ClientEvent::BlobStored(handle));
+                        }
+                    }
+                    Err(e) => {
+                        debug!("Failed to stream BLOB: {}", e);
+                        self.update_metrics(|metrics| metrics.parse_errors += 1);
+                        *blob_stream = None;
+                    }
+                }
+                continue;
+            }
+            framer.skip_unsupported();
+            let opener = self
+                .blob_opener
+                .read()
+                .ok()
+                .and_then(|o| o.clone())
+                .filter(|_| framer.at_message_start());
+            if let Some(opener) = opener {
+                match BlobStream::start(framer.buffer_mut()) {
+                    Ok(Some(stream)) => {
+                        *blob_stream = Some((stream, opener));
+                        continue;
+                    }
+                    Ok(None) => (),
+                    Err(e) => debug!("Failed to stream BLOB: {}", e),
+                }
+            }
+            let Some(frame) = framer.next_frame() else {
+                break;
+            };
+            if blobs_only && !frame.starts_with(BLOB_ROOT) {
+                continue;
+            }
+            self.handle_frame(&String::from_utf8_lossy(&frame)).await;
+        }
+    }
+
+    /// Send a keepalive probe to the server
+    async fn send_probe(&self, probe: KeepAliveProbe, uid: u64) -> Result<()> {
+        if probe == KeepAliveProbe::GetProperties {
+            let known = {
+                let state = self.state.lock().await;
+                state.properties.iter().find_map(|(device, properties)| {
+                    properties
+                        .keys()
+                        .next()
+                        .map(|name| (device.clone(), name.clone()))
+                })
+            };
+            if let Some((device, name)) = known {
+                return self.get_properties(Some(&device), Some(&name)).await;
+            }
+        }
+        self.send(&MessageType::PingRequest(PingRequest {
+            uid: uid.to_string(),
+        }))
+        .await
+    }
+
+    /// Apply `update` to the metrics
+    fn update_metrics(&self, update: impl FnOnce(&mut ClientMetrics)) {
+        if let Ok(mut metrics) = self.metrics.lock() {
+            update(&mut metrics);
+        }
+    }
+
+    /// Parse a single framed message, apply it to the state and publish it
+    async fn handle_frame(&self, frame: &str) {
+        self.debug.log_xml(&self.peer(), false, frame);
+        let message = match MessageType::from_str(frame.trim()) {
+            Ok(message) => message,
+            Err(e) => {
+                debug!("Failed to parse message: {}", e);
+                self.update_metrics(|metrics| metrics.parse_errors += 1);
+                return;
+            }
+        };
+        self.update_metrics(|metrics| metrics.messages_parsed += 1);
+        self.handle_message(message).await;
+    }
+
+    /// Apply a received message to the state and publish it
+    async fn handle_message(&self, message: MessageType) {
+        if let MessageType::Message(text) = &message {
+            if let Ok(mut greeting) = self.greeting.lock() {
+                if !greeting.complete && text.device.is_none() {
+                    greeting.messages.push(text.clone());
+                }
+            }
+        }
+        if let MessageType::PingRequest(ping) = &message {
+            let reply = MessageType::PingReply(PingReply {
+                uid: ping.uid.clone(),
+            });
+            if let Err(e) = self.send(&reply).await {
+                debug!("Failed to answer ping: {}", e);
+            }
+        }
+        self.debug.log_blobs(&message);
+        {
+            let mut state = self.state.lock().await;
+            let transition = set_vector_state(&message).and_then(|(device, name, to, _)| {
+                let from = state.get_property(device, name)?.state;
+                Some((device, name, from, to?))
+            });
+            if let Err(e) = state.update(&message) {
+                debug!("Failed to update state: {}", e);
+            }
+            if let Some((device, name, from, to)) = transition {
+                self.debug.log_transition(device, name, from, to);
+            }
+            if let Ok(mut watchers) = self.watchers.lock() {
+                watchers.notify(&state, &message);
+            }
+        }
+        if let Ok(mut search) = self.search.write() {
+            search.update(&message);
+        }
+        if let (Some(link), MessageType::DefBlobVector(def)) = (&self.blob_link, &message) {
+            link.subscribe(&def.device).await;
+        } else if let (Some(policy), MessageType::DefBlobVector(def)) =
+            (self.config.blob_policy, &message)
+        {
+            let first = self
+                .blob_devices
+                .lock()
+                .is_ok_and(|mut devices| devices.insert(def.device.clone()));
+            if first {
+                if let Err(e) = self.enable_blob(&def.device, None, policy.as_str()).await {
+                    debug!("Failed to enable BLOBs for {}: {}", def.device, e);
+                }
+            }
+        }
+        let completed = match self.pending.lock() {
+            Ok(mut pending) => pending.received(&message),
+            Err(_) => None,
+        };
+        let rejected = completed.and_then(|completed| {
+            self.update_metrics(|metrics| {
+                metrics.record_latency(completed.device, completed.name, completed.latency)
+            });
+            completed.rejected
+        });
+        let message = Arc::new(message);
+        self.queue_callbacks(&message);
+        // Having no subscribers is not an error
+        let _ = self.events.send(ClientEvent::Message(message));
+        if let Some(rejected) = rejected {
+            debug!("{}", rejected);
+            let _ = self.events.send(ClientEvent::CommandRejected(rejected));
+        }
+    }
+}
+
+/// Open the connection to the server, through the proxy if one is
+/// configured, within the connect timeout
+async fn connect(config: &ClientConfig) -> Result<TcpStream> {
+    let connect = async {
+        let mut stream = match &config.proxy {
+            Some(proxy) => {
+                debug!(
+                    "Connecting to {}:{} through {:?} proxy {}:{}",
+                    config.host, config.port, proxy.kind, proxy.host, proxy.port
+                );
+                let mut stream = TcpStream::connect((proxy.host.as_str(), proxy.port)).await?;
+                proxy.tunnel(&mut stream, &config.host, config.port).await?;
+                stream
+            }
+            None => {
+                debug!("Connecting to {}:{}", config.host, config.port);
+                TcpStream::connect((config.host.as_str(), config.port)).await?
+            }
+        };
+        if let Some(credential) = &config.credential {
+            stream.write_all(credential.preamble()?.as_bytes()).await?;
+        }
+        Ok(stream)
+    };
+    match config.connect_timeout {
+        Some(timeout) => tokio::time::timeout(timeout, connect).await.map_err(|_| {
+            Error::Timeout(format!(
+                "Connecting to {}:{} took longer than {:?}",
+                config.host, config.port, timeout
+            ))
+        })?,
+        None => connect.await,
+    }
+}
+
+/// Device and property name of a definition message
+pub(crate) fn definition_key(message: &MessageType) -> Option<(String, String)> {
+    if !message.kind().is_definition() {
+        return None;
+    }
+    Some((message.device()?.to_string(), message.name()?.to_string()))
 }
 
 impl Connection for Client {
+    /// Close the connection and stop its background tasks
+    ///
+    /// Stops reading, writes the messages still queued (within the write
+    /// timeout), shuts the socket down and publishes
+    /// [`ClientEvent::Disconnected`]. Disconnecting twice is a no-op.
     async fn disconnect(&mut self) -> Result<()> {
+        let tasks = self.tasks.lock().ok().and_then(|mut tasks| tasks.take());
+        let Some(ConnectionTasks { reader, mut writer }) = tasks else {
+            return Ok(());
+        };
         debug!(
             "Disconnecting from server {}:{}",
             self.config.host, self.config.port
         );
+        // The reader reports a connection closed by the server itself
+        let closed_by_server = !self.state.lock().await.connected;
+        reader.abort();
+        // Stop a writer waiting for a reconnect
+        self.link.send_replace(None);
+
+        // Let the writer drain the queue, then close the socket
+        self.queue.close();
+        let drained = match self.config.write_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, &mut writer).await.is_ok(),
+            None => (&mut writer).await.is_ok(),
+        };
+        if !drained {
+            warn!(
+                "Dropping unsent messages to {}:{}",
+                self.config.host, self.config.port
+            );
+            writer.abort();
+        }
+        if let Err(e) = self.writer.lock().await.shutdown().await {
+            debug!("Failed to shut down connection: {}", e);
+        }
+        if let Some(link) = &self.blob_link {
+            link.close().await;
+        }
+
+        self.state.lock().await.connected = false;
+        if !closed_by_server {
+            // Having no subscribers is not an error
+            let _ = self.events.send(ClientEvent::Disconnected);
+        }
         Ok(())
     }
 }
 
 impl MessageHandler for Client {
     async fn send_message(&mut self, message: &str) -> Result<()> {
-        debug!(
-            "Sending message to {}:{}: {}",
-            self.config.host,
-            self.config.port,
-            message.trim()
-        );
-        let writer = self.writer();
-        let mut writer = writer.lock().await;
-        writer.write_all(message.as_bytes()).await?;
-        writer.write_all(b"\n").await?;
-        writer.flush().await?;
-        Ok(())
+        self.write_message(message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::connection::Connection;
+    use crate::client::testing::mock_server;
+    use crate::client::testing::wait_for_property;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_disconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut lines = BufReader::new(socket).lines();
+            let mut received = Vec::new();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                received.push(line);
+            }
+            received
+        });
+
+        let mut client = Client::new(ClientConfig::new(addr.ip().to_string(), addr.port()))
+            .await
+            .unwrap();
+        assert!(client.state().lock().await.connected);
+        let mut events = client.subscribe();
+        client.get_properties(None, None).await.unwrap();
+        client.disconnect().await.unwrap();
+
+        // Queued messages are flushed before the socket is shut down
+        let received = server.await.unwrap();
+        assert_eq!(received.len(), 1);
+        assert!(received[0].contains("getProperties"));
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            ClientEvent::Disconnected
+        ));
+        assert!(!client.state().lock().await.connected);
+        assert!(client.get_properties(None, None).await.is_err());
+        client.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_and_blob_policy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            // The first connection drops right away
+            drop(listener.accept().await.unwrap());
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = socket.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                if line.contains("getProperties") {
+                    writer
+                        .write_all(br#"<defBLOBVector device="CCD" name="CCD1" state="Idle" perm="ro"><defBLOB name="CCD1"/></defBLOBVector>
+"#)
+                        .await
+                        .unwrap();
+                }
+                if line.contains("enableBLOB") {
+                    return line;
+                }
+            }
+            panic!("No enableBLOB received");
+        });
+
+        let client = Client::builder()
+            .host(addr.ip().to_string())
+            .port(addr.port())
+            .reconnect(ReconnectPolicy {
+                initial_delay: Duration::from_millis(10),
+                max_delay: Duration::from_millis(50),
+                max_attempts: Some(5),
+            })
+            .blob_policy(BlobPolicy::Also)
+            .build()
+            .await
+            .unwrap();
+        let mut events = client.subscribe();
+        loop {
+            if let ClientEvent::Reconnected = events.recv().await.unwrap() {
+                break;
+            }
+        }
+        let line = tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(line.contains(r#"device="CCD""#));
+        assert!(line.contains("Also"));
+        wait_for_property(&client, "CCD", "CCD1").await;
+        assert!(client.state().lock().await.connected);
+    }
+
+    #[tokio::test]
+    async fn test_blob_connection() {
+        const DEFINITION: &[u8] = br#"<defBLOBVector device="CCD" name="CCD1" state="Idle" perm="ro"><defBLOB name="CCD1"/></defBLOBVector>
+"#;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (main, _) = listener.accept().await.unwrap();
+            let (blobs, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = main.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let line = lines.next_line().await.unwrap().unwrap();
+            assert!(line.contains("getProperties"));
+            writer.write_all(DEFINITION).await.unwrap();
+
+            let (reader, mut writer) = blobs.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let subscribed = [
+                lines.next_line().await.unwrap().unwrap(),
+                lines.next_line().await.unwrap().unwrap(),
+            ];
+            // Definitions on the BLOB connection must not reach the client
+            writer.write_all(DEFINITION).await.unwrap();
+            writer
+                .write_all(br#"<setBLOBVector device="CCD" name="CCD1" state="Ok"><oneBLOB name="CCD1" size="3" format=".raw">AQID</oneBLOB></setBLOBVector>
+"#)
+                .await
+                .unwrap();
+            subscribed
+        });
+
+        let client = Client::builder()
+            .host(addr.ip().to_string())
+            .port(addr.port())
+            .blob_connection(true)
+            .build()
+            .await
+            .unwrap();
+        let mut events = client.subscribe();
+        client.get_properties(None, None).await.unwrap();
+        let mut definitions = 0;
+        let blob = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let ClientEvent::Message(message) = events.recv().await.unwrap() {
+                    match &*message {
+                        MessageType::DefBlobVector(_) => definitions += 1,
+                        MessageType::SetBlobVector(set) => return set.clone(),
+                        _ => (),
+                    }
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(blob.elements[0].get_data().unwrap(), vec![1, 2, 3]);
+        assert_eq!(definitions, 1);
+
+        let subscribed = server.await.unwrap();
+        assert!(subscribed[0].contains("getProperties"));
+        assert!(subscribed[0].contains(r#"device="CCD""#));
+        assert!(subscribed[1].contains("enableBLOB"));
+        assert!(subscribed[1].contains("Only"));
+    }
+
+    #[tokio::test]
+    async fn test_set_switch_and_wait() {
+        let config = mock_server(
+            "",
+            "newSwitchVector",
+            r#"<setSwitchVector device="Mount" name="TELESCOPE_PARK" state="Busy"><oneSwitch name="PARK">On</oneSwitch></setSwitchVector>
+<setSwitchVector device="Mount" name="OTHER" state="Alert"><oneSwitch name="X">On</oneSwitch></setSwitchVector>
+<setSwitchVector device="Mount" name="TELESCOPE_PARK" state="Ok"><oneSwitch name="PARK">On</oneSwitch></setSwitchVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let state = client
+            .set_switch_and_wait("Mount", "TELESCOPE_PARK", "PARK", SwitchState::On)
+            .await
+            .unwrap();
+        assert_eq!(state, PropertyState::Ok);
+
+        let config = mock_server(
+            "",
+            "newSwitchVector",
+            r#"<setSwitchVector device="Mount" name="TELESCOPE_PARK" state="Alert" message="Mount is not aligned"><oneSwitch name="PARK">Off</oneSwitch></setSwitchVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let error = client
+            .set_switch_and_wait("Mount", "TELESCOPE_PARK", "PARK", SwitchState::On)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Mount is not aligned"));
+    }
+
+    #[tokio::test]
+    async fn test_garbage_tolerant_greeting() {
+        let config = mock_server(
+            r#"<?xml version="1.0"?>
+<INDI>
+<serverInfo version="0.9"><unsupported/></serverInfo>
+<message message="Welcome"/>
+<message device="CCD" message="Driver loaded"/>
+<defTextVector device="CCD" name="INFO" state="Idle" perm="ro">
+<defText name="NAME">ccd</defText>
+</defTextVector>
+"#,
+            "getProperties",
+            r#"<message message="Late"/>
+<defTextVector device="CCD" name="LATE" state="Idle" perm="ro"><defText name="NAME">late</defText></defTextVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        wait_for_property(&client, "CCD", "INFO").await;
+        client.get_properties(None, None).await.unwrap();
+        wait_for_property(&client, "CCD", "LATE").await;
+
+        let greeting = client.server_greeting();
+        assert_eq!(greeting.len(), 1);
+        assert_eq!(greeting[0].message.as_deref(), Some("Welcome"));
+    }
+
+    #[tokio::test]
+    async fn test_get_properties_batch() {
+        let config = mock_server(
+            "",
+            "getProperties",
+            r#"<defNumberVector device="Telescope Simulator" name="EQUATORIAL_EOD_COORD" state="Idle" perm="rw">
+<defNumber name="RA" format="%10.6m" min="0" max="24" step="0">1.5</defNumber>
+</defNumberVector>
+<defSwitchVector device="Telescope Simulator" name="TELESCOPE_PARK" state="Idle" perm="rw" rule="OneOfMany">
+<defSwitch name="PARK">Off</defSwitch>
+<defSwitch name="UNPARK">On</defSwitch>
+</defSwitchVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+
+        let results = client
+            .get_properties_batch(
+                &[
+                    ("Telescope Simulator", "EQUATORIAL_EOD_COORD"),
+                    ("Telescope Simulator", "TELESCOPE_PARK"),
+                    ("Telescope Simulator", "TELESCOPE_ABORT_MOTION"),
+                ],
+                Duration::from_millis(200),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 3);
+        let coords = results[0].as_ref().unwrap();
+        assert_eq!(coords.value.number("RA"), Some(1.5));
+        assert_eq!(results[1].as_ref().unwrap().name, "TELESCOPE_PARK");
+        assert!(matches!(results[2], Err(Error::Timeout(_))));
+    }
+
+    #[tokio::test]
+    async fn test_validator_rejects_update() {
+        let config = mock_server("", "newNumberVector", "").await;
+        let client = Client::new(config).await.unwrap();
+        client.add_validator("CCD Simulator", "CCD_TEMPERATURE", |message| {
+            let MessageType::NewNumberVector(new) = message else {
+                return Ok(());
+            };
+            match new.elements[0].value.parse::<f64>() {
+                Ok(target) if target < -40.0 => Err("Target below -40".to_string()),
+                _ => Ok(()),
+            }
+        });
+        let mut events = client.subscribe();
+
+        client
+            .set_number(
+                "CCD Simulator",
+                "CCD_TEMPERATURE",
+                &[("CCD_TEMPERATURE_VALUE", -10.0)],
+            )
+            .await
+            .unwrap();
+        let result = client
+            .set_number(
+                "CCD Simulator",
+                "CCD_TEMPERATURE",
+                &[("CCD_TEMPERATURE_VALUE", -60.0)],
+            )
+            .await;
+        assert!(matches!(result, Err(Error::Rejected(r)) if r.reason == "Target below -40"));
+        match events.recv().await.unwrap() {
+            ClientEvent::Rejected(rejection) => assert_eq!(rejection.name, "CCD_TEMPERATURE"),
+            event => panic!("Expected Rejected, got {:?}", event),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_policy() {
+        let config = mock_server(
+            "",
+            r#"timestamp=""#,
+            r#"<setNumberVector device="Focuser" name="ABS_FOCUS_POSITION" state="Ok"/>
+"#,
+        )
+        .await;
+        let client = Client::new(config.with_timestamp_policy(TimestampPolicy::Decimals(3)))
+            .await
+            .unwrap();
+        let mut events = client.subscribe();
+        client
+            .set_number(
+                "Focuser",
+                "ABS_FOCUS_POSITION",
+                &[("FOCUS_ABSOLUTE_POSITION", 100.0)],
+            )
+            .await
+            .unwrap();
+        wait::wait_for_ok(
+            &mut events,
+            "Focuser",
+            "ABS_FOCUS_POSITION",
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_keepalive() {
+        let keepalive = KeepAlive {
+            interval: Duration::from_millis(50),
+            timeout: Duration::from_millis(50),
+            probe: KeepAliveProbe::Ping,
+        };
+
+        let config = mock_server("", "pingRequest", "<pingReply uid=\"1\"/>\n").await;
+        let client = Client::new(config.with_keepalive(Some(keepalive)))
+            .await
+            .unwrap();
+        let mut events = client.subscribe();
+        let answered = tokio::time::timeout(Duration::from_millis(300), async {
+            loop {
+                if let Ok(ClientEvent::ConnectionLost) = events.recv().await {
+                    break;
+                }
+            }
+        });
+        assert!(answered.await.is_err());
+
+        let config = mock_server("", "never sent", "").await;
+        let client = Client::new(config.with_keepalive(Some(keepalive)))
+            .await
+            .unwrap();
+        let mut events = client.subscribe();
+        let lost = tokio::time::timeout(Duration::from_secs(2), events.recv()).await;
+        assert!(matches!(lost, Ok(Ok(ClientEvent::ConnectionLost))));
+    }
+
+    /// Listener that accepts connections and then neither reads nor writes
+    async fn silent_server() -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_connect_timeout() {
+        // The proxy accepts the connection but never answers the handshake
+        let proxy = silent_server().await;
+        let config = ClientConfig::new("observatory.local", ClientConfig::DEFAULT_PORT)
+            .with_proxy(Some(crate::client::Proxy::socks5(
+                proxy.ip().to_string(),
+                proxy.port(),
+            )))
+            .with_connect_timeout(Some(Duration::from_millis(100)));
+        let connecting = tokio::time::timeout(Duration::from_secs(5), Client::new(config));
+        assert!(matches!(connecting.await, Ok(Err(Error::Timeout(_)))));
+    }
+
+    #[tokio::test]
+    async fn test_read_idle_timeout() {
+        let addr = silent_server().await;
+        let config = ClientConfig::new(addr.ip().to_string(), addr.port())
+            .with_read_idle_timeout(Some(Duration::from_millis(100)));
+        let client = Client::new(config).await.unwrap();
+        let mut events = client.subscribe();
+        let closed = tokio::time::timeout(Duration::from_secs(5), events.recv()).await;
+        assert!(matches!(closed, Ok(Ok(ClientEvent::Disconnected))));
+        assert!(!client.state().lock().await.connected);
+    }
+
+    #[tokio::test]
+    async fn test_write_timeout() {
+        let addr = silent_server().await;
+        let config = ClientConfig::new(addr.ip().to_string(), addr.port())
+            .with_write_timeout(Some(Duration::from_millis(100)));
+        let mut client = Client::new(config).await.unwrap();
+        let mut events = client.subscribe();
+
+        // Far more than the socket buffers hold, the server never reads
+        let message = format!("<message message=\"{}\"/>", "x".repeat(64 << 20));
+        client.send_message(&message).await.unwrap();
+        let closed = tokio::time::timeout(Duration::from_secs(5), events.recv()).await;
+        assert!(matches!(closed, Ok(Ok(ClientEvent::Disconnected))));
+        assert!(client.get_properties(None, None).await.is_err());
     }
 }
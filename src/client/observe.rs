@@ -0,0 +1,109 @@
+use crate::client::{definition_key, set_vector_state, Client, ClientState};
+use crate::error::{Error, Result};
+use crate::message::MessageType;
+use crate::property::Property;
+use std::collections::HashMap;
+use tokio::sync::watch;
+
+/// Watch channels of single properties, by device and name
+#[derive(Debug, Default)]
+pub(crate) struct Watchers {
+    properties: HashMap<(String, String), watch::Sender<Property>>,
+}
+
+impl Watchers {
+    /// Publish the property changed by `message`, as stored in `state`
+    ///
+    /// Channels without receivers are dropped. Deleting a property closes
+    /// its channel.
+    pub(crate) fn notify(&mut self, state: &ClientState, message: &MessageType) {
+        if self.properties.is_empty() {
+            return;
+        }
+        if let MessageType::DelProperty(del) = message {
+            self.properties.retain(|(device, name), _| {
+                device != &del.device || del.name.as_ref().is_some_and(|n| n != name)
+            });
+            return;
+        }
+        let key = definition_key(message).or_else(|| {
+            set_vector_state(message)
+                .map(|(device, name, ..)| (device.to_string(), name.to_string()))
+        });
+        let Some(key) = key else {
+            return;
+        };
+        let Some(sender) = self.properties.get(&key) else {
+            return;
+        };
+        if sender.receiver_count() == 0 {
+            self.properties.remove(&key);
+        } else if let Some(property) = state.get_property(&key.0, &key.1) {
+            sender.send_replace(property.clone());
+        }
+    }
+}
+
+impl Client {
+    /// Observe a single property
+    ///
+    /// The receiver starts with the current property and is updated on
+    /// every definition and update of it, so a UI can follow e.g. the mount
+    /// position without filtering [`ClientEvent`](super::ClientEvent)s.
+    /// The channel closes when the server deletes the property. Fails if
+    /// the property is not defined.
+    pub async fn watch(&self, device: &str, name: &str) -> Result<watch::Receiver<Property>> {
+        // Hold the state lock so no update slips in before registration
+        let state = self.state.lock().await;
+        let property = state
+            .get_property(device, name)
+            .ok_or_else(|| Error::Property(format!("{}.{} is not defined", device, name)))?;
+        let mut watchers = self
+            .watchers
+            .lock()
+            .map_err(|_| Error::Message("Property watchers are unavailable".to_string()))?;
+        let sender = watchers
+            .properties
+            .entry((device.to_string(), name.to_string()))
+            .or_insert_with(|| watch::Sender::new(property.clone()));
+        Ok(sender.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::{mock_server, wait_for_property};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_watch() {
+        let config = mock_server(
+            r#"<defNumberVector device="Mount" name="EQUATORIAL_EOD_COORD" state="Idle" perm="rw"><defNumber name="RA" format="%010.6m" min="0" max="24" step="0">1.5</defNumber><defNumber name="DEC" format="%010.6m" min="-90" max="90" step="0">20</defNumber></defNumberVector>
+"#,
+            "getProperties",
+            r#"<setNumberVector device="Mount" name="EQUATORIAL_EOD_COORD" state="Busy"><oneNumber name="RA">2.25</oneNumber></setNumberVector>
+<delProperty device="Mount" name="EQUATORIAL_EOD_COORD"/>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        assert!(client.watch("Mount", "EQUATORIAL_EOD_COORD").await.is_err());
+        wait_for_property(&client, "Mount", "EQUATORIAL_EOD_COORD").await;
+        let mut position = client.watch("Mount", "EQUATORIAL_EOD_COORD").await.unwrap();
+        let ra = |property: &Property| property.value.number("RA").unwrap();
+        assert_eq!(ra(&position.borrow()), 1.5);
+
+        client.get_properties(None, None).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), position.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ra(&position.borrow_and_update()), 2.25);
+        // The deletion closes the channel
+        let closed = tokio::time::timeout(Duration::from_secs(5), position.changed())
+            .await
+            .unwrap();
+        assert!(closed.is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//! Connections to several INDI servers behind one device namespace
+//!
+//! Observatories often split equipment over several computers, e.g. a mount
+//! box and a camera box, each running its own server. A [`ClientPool`]
+//! holds one [`Client`] per server and merges their devices so callers can
+//! address them by name without caring which server hosts them.
+
+use crate::client::{Client, ClientConfig};
+use crate::error::{Error, Result};
+use crate::property::{Property, SwitchState};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+/// Separator between member name and device name for prefixed devices
+pub const PREFIX_SEPARATOR: char = '/';
+
+/// Handling of devices with the same name on several servers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClashPolicy {
+    /// The member added first keeps the plain name, clashing devices of
+    /// later members are exposed as `member/device`
+    #[default]
+    Prefix,
+    /// The member added first keeps the device, clashing devices of later
+    /// members are hidden
+    FirstWins,
+}
+
+/// One server of a pool
+#[derive(Debug, Clone)]
+struct Member {
+    name: String,
+    client: Client,
+}
+
+/// Pool of clients with a merged device namespace
+///
+/// A device keeps the pooled name it got when the pool first saw it, so a
+/// `member/device` name stays valid even after the device that took the
+/// plain name disconnects.
+#[derive(Debug, Default)]
+pub struct ClientPool {
+    members: Vec<Member>,
+    policy: ClashPolicy,
+    /// Pooled names assigned so far, to member index and server-side name
+    assigned: Mutex<HashMap<String, (usize, String)>>,
+}
+
+impl Clone for ClientPool {
+    fn clone(&self) -> Self {
+        Self {
+            members: self.members.clone(),
+            policy: self.policy,
+            assigned: Mutex::new(self.assigned().clone()),
+        }
+    }
+}
+
+impl ClientPool {
+    /// Create an empty pool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how devices with the same name on several servers are exposed
+    pub fn with_clash_policy(mut self, policy: ClashPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Add a connected client under `name`
+    ///
+    /// Members added earlier take precedence when clashing devices are
+    /// first seen together; a name already assigned is never taken over.
+    pub fn add(&mut self, name: impl Into<String>, client: Client) -> Result<()> {
+        let name = name.into();
+        if name.contains(PREFIX_SEPARATOR) || self.member(&name).is_some() {
+            return Err(Error::Message(format!(
+                "Invalid or duplicate member '{}'",
+                name
+            )));
+        }
+        self.members.push(Member { name, client });
+        Ok(())
+    }
+
+    /// Connect to a server and add it under `name`
+    pub async fn connect(&mut self, name: impl Into<String>, config: ClientConfig) -> Result<()> {
+        let client = Client::new(config).await?;
+        self.add(name, client)
+    }
+
+    /// Names of the members in order of precedence
+    pub fn members(&self) -> Vec<&str> {
+        self.members.iter().map(|m| m.name.as_str()).collect()
+    }
+
+    /// Client of the member called `name`
+    pub fn member(&self, name: &str) -> Option<&Client> {
+        self.members
+            .iter()
+            .find(|m| m.name == name)
+            .map(|m| &m.client)
+    }
+
+    /// Request property definitions from every member
+    pub async fn get_properties(&self) -> Result<()> {
+        for member in &self.members {
+            member.client.get_properties(None, None).await?;
+        }
+        Ok(())
+    }
+
+    /// Pooled device names, sorted
+    pub async fn devices(&self) -> Vec<String> {
+        let mut devices = self.namespace().await.into_keys().collect::<Vec<_>>();
+        devices.sort();
+        devices
+    }
+
+    /// Client and server-side device name behind a pooled device name
+    ///
+    /// Use this to build device wrappers, e.g.
+    /// `Focuser::new(client, device)`.
+    pub async fn resolve(&self, device: &str) -> Result<(Client, String)> {
+        let (index, device) = self
+            .namespace()
+            .await
+            .remove(device)
+            .ok_or_else(|| Error::Property(format!("Unknown device '{}'", device)))?;
+        Ok((self.members[index].client.clone(), device))
+    }
+
+    /// Current value of a property, with the device renamed to its pooled
+    /// name
+    pub async fn get_property(&self, device: &str, name: &str) -> Result<Property> {
+        let (client, remote) = self.resolve(device).await?;
+        let state = client.state();
+        let state = state.lock().await;
+        let mut property = state
+            .get_property(&remote, name)
+            .cloned()
+            .ok_or_else(|| Error::Property(format!("{}.{} is not defined", device, name)))?;
+        property.device = device.to_string();
+        Ok(property)
+    }
+
+    /// Send new values for elements of a number vector
+    pub async fn set_number(&self, device: &str, name: &str, values: &[(&str, f64)]) -> Result<()> {
+        let (client, remote) = self.resolve(device).await?;
+        client.set_number(&remote, name, values).await
+    }
+
+    /// Send new states for elements of a switch vector
+    pub async fn set_switch(
+        &self,
+        device: &str,
+        name: &str,
+        values: &[(&str, SwitchState)],
+    ) -> Result<()> {
+        let (client, remote) = self.resolve(device).await?;
+        client.set_switch(&remote, name, values).await
+    }
+
+    /// Map of pooled device names to member index and server-side name,
+    /// for the devices currently defined
+    ///
+    /// Devices seen for the first time are assigned a name, which is kept
+    /// for the lifetime of the pool.
+    async fn namespace(&self) -> HashMap<String, (usize, String)> {
+        let mut defined = Vec::with_capacity(self.members.len());
+        for member in &self.members {
+            let state = member.client.state();
+            let state = state.lock().await;
+            let mut devices = state.properties.keys().cloned().collect::<Vec<_>>();
+            devices.sort();
+            defined.push(devices);
+        }
+
+        let mut assigned = self.assigned();
+        let known = assigned.values().cloned().collect::<HashSet<_>>();
+        for (index, devices) in defined.iter().enumerate() {
+            for device in devices {
+                if known.contains(&(index, device.clone())) {
+                    continue;
+                }
+                let pooled = if !assigned.contains_key(device) {
+                    device.clone()
+                } else if self.policy == ClashPolicy::Prefix {
+                    format!("{}{}{}", self.members[index].name, PREFIX_SEPARATOR, device)
+                } else {
+                    continue;
+                };
+                assigned.entry(pooled).or_insert((index, device.clone()));
+            }
+        }
+        assigned
+            .iter()
+            .filter(|(_, (index, device))| defined[*index].binary_search(device).is_ok())
+            .map(|(pooled, target)| (pooled.clone(), target.clone()))
+            .collect()
+    }
+
+    fn assigned(&self) -> std::sync::MutexGuard<'_, HashMap<String, (usize, String)>> {
+        self.assigned
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::{mock_server, wait_for_property};
+
+    const MOUNT_BOX: &str = r#"<defNumberVector device="Telescope" name="EQUATORIAL_EOD_COORD" state="Idle" perm="rw">
+<defNumber name="RA" format="%010.6m" min="0" max="24" step="0">1</defNumber>
+</defNumberVector>
+<defTextVector device="Weather" name="INFO" state="Idle" perm="ro">
+<defText name="SITE">mount box</defText>
+</defTextVector>
+"#;
+
+    const CAMERA_BOX: &str = r#"<defNumberVector device="CCD" name="CCD_TEMPERATURE" state="Idle" perm="rw">
+<defNumber name="CCD_TEMPERATURE_VALUE" format="%5.2f" min="-50" max="50" step="0">20</defNumber>
+</defNumberVector>
+<defTextVector device="Weather" name="INFO" state="Idle" perm="ro">
+<defText name="SITE">camera box</defText>
+</defTextVector>
+"#;
+
+    async fn two_boxes(policy: ClashPolicy) -> ClientPool {
+        let mut pool = ClientPool::new().with_clash_policy(policy);
+        for (name, greeting) in [("mount", MOUNT_BOX), ("camera", CAMERA_BOX)] {
+            let client = Client::new(mock_server(greeting, "never sent", "").await)
+                .await
+                .unwrap();
+            wait_for_property(&client, "Weather", "INFO").await;
+            pool.add(name, client).unwrap();
+        }
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_merged_namespace() {
+        let pool = two_boxes(ClashPolicy::Prefix).await;
+        assert_eq!(
+            pool.devices().await,
+            ["CCD", "Telescope", "Weather", "camera/Weather"]
+        );
+
+        let property = pool.get_property("camera/Weather", "INFO").await.unwrap();
+        assert_eq!(property.device, "camera/Weather");
+        let (_, remote) = pool.resolve("camera/Weather").await.unwrap();
+        assert_eq!(remote, "Weather");
+        assert!(pool.get_property("CCD", "CCD_TEMPERATURE").await.is_ok());
+
+        // Assigned names stick, whichever member defined its device first
+        let mut pool = ClientPool::new();
+        let camera = two_boxes(ClashPolicy::Prefix).await;
+        pool.add("camera", camera.member("camera").unwrap().clone())
+            .unwrap();
+        assert_eq!(pool.devices().await, ["CCD", "Weather"]);
+        pool.add("mount", camera.member("mount").unwrap().clone())
+            .unwrap();
+        assert_eq!(
+            pool.devices().await,
+            ["CCD", "Telescope", "Weather", "mount/Weather"]
+        );
+        let (_, remote) = pool.resolve("mount/Weather").await.unwrap();
+        assert_eq!(remote, "Weather");
+        let property = pool.get_property("Weather", "INFO").await.unwrap();
+        assert_eq!(property.value.text("SITE"), Some("camera box"));
+
+        let mut pool = two_boxes(ClashPolicy::FirstWins).await;
+        assert_eq!(pool.devices().await, ["CCD", "Telescope", "Weather"]);
+        let mount = pool.member("mount").unwrap().clone();
+        assert!(pool.add("mount", mount).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::client::OverflowPolicy;
+use crate::error::{Error, Result};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+/// Queued messages and whether the queue still accepts new ones
+#[derive(Debug, Default)]
+struct Items {
+    messages: VecDeque<String>,
+    closed: bool,
+}
+
+/// Bounded queue of outgoing messages, drained by the writer task
+#[derive(Debug)]
+pub(crate) struct SendQueue {
+    items: Mutex<Items>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    queued: Notify,
+    space: Notify,
+}
+
+/// Result of [`SendQueue::push`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Pushed {
+    /// The message was queued
+    Queued,
+    /// The message was queued after discarding the oldest one
+    DroppedOldest,
+}
+
+impl SendQueue {
+    /// Create an empty queue holding up to `capacity` messages
+    pub(crate) fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            items: Mutex::new(Items::default()),
+            capacity: capacity.max(1),
+            policy,
+            queued: Notify::new(),
+            space: Notify::new(),
+        }
+    }
+
+    /// Queue a message, applying the overflow policy when the queue is full
+    ///
+    /// With [`OverflowPolicy::Block`] this waits for space; callers bound the
+    /// wait with the write timeout.
+    pub(crate) async fn push(&self, message: String) -> Result<Pushed> {
+        loop {
+            // Register interest before checking so a pop in between is seen
+            let space = self.space.notified();
+            if let Some(pushed) = self.try_push(&message)? {
+                return Ok(pushed);
+            }
+            space.await;
+        }
+    }
+
+    /// Queue a message unless the queue is full and the policy is to block
+    fn try_push(&self, message: &str) -> Result<Option<Pushed>> {
+        let mut items = self.lock();
+        if items.closed {
+            return Err(Error::Protocol("Connection closed".to_string()));
+        }
+        let pushed = if items.messages.len() < self.capacity {
+            Pushed::Queued
+        } else {
+            match self.policy {
+                OverflowPolicy::Block => return Ok(None),
+                OverflowPolicy::DropOldest => {
+                    items.messages.pop_front();
+                    Pushed::DroppedOldest
+                }
+                OverflowPolicy::Error => {
+                    return Err(Error::Message(format!(
+                        "Send queue is full ({} messages)",
+                        self.capacity
+                    )))
+                }
+            }
+        };
+        items.messages.push_back(message.to_string());
+        self.queued.notify_one();
+        Ok(Some(pushed))
+    }
+
+    /// Take the next message, waiting until one is queued
+    ///
+    /// Returns `None` once the queue is closed and empty.
+    pub(crate) async fn pop(&self) -> Option<String> {
+        loop {
+            let queued = self.queued.notified();
+            {
+                let mut items = self.lock();
+                if let Some(message) = items.messages.pop_front() {
+                    self.space.notify_one();
+                    return Some(message);
+                }
+                if items.closed {
+                    return None;
+                }
+            }
+            queued.await;
+        }
+    }
+
+    /// Refuse further messages and wake all waiters
+    ///
+    /// Messages already queued are still returned by [`pop`](Self::pop).
+    pub(crate) fn close(&self) {
+        self.lock().closed = true;
+        self.queued.notify_waiters();
+        self.space.notify_waiters();
+    }
+
+    /// Number of queued messages
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.lock().messages.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Items> {
+        self.items
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_overflow_policies() {
+        let queue = SendQueue::new(2, OverflowPolicy::Error);
+        queue.push("a".to_string()).await.unwrap();
+        queue.push("b".to_string()).await.unwrap();
+        assert!(queue.push("c".to_string()).await.is_err());
+        assert_eq!(queue.len(), 2);
+
+        let queue = SendQueue::new(2, OverflowPolicy::DropOldest);
+        for message in ["a", "b", "c"] {
+            queue.push(message.to_string()).await.unwrap();
+        }
+        assert_eq!(queue.pop().await.as_deref(), Some("b"));
+        assert_eq!(queue.pop().await.as_deref(), Some("c"));
+
+        let queue = Arc::new(SendQueue::new(1, OverflowPolicy::Block));
+        queue.push("a".to_string()).await.unwrap();
+        let blocked = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.push("b".to_string()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!blocked.is_finished());
+        assert_eq!(queue.pop().await.as_deref(), Some("a"));
+        assert_eq!(blocked.await.unwrap().unwrap(), Pushed::Queued);
+        assert_eq!(queue.pop().await.as_deref(), Some("b"));
+
+        queue.close();
+        assert!(queue.push("c".to_string()).await.is_err());
+        assert_eq!(queue.pop().await, None);
+    }
+}
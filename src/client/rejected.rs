@@ -0,0 +1,244 @@
+use crate::message::MessageType;
+use crate::property::PropertyState;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Phrases that indicate a write to a read-only or locked property
+const PERMISSION_HINTS: &[&str] = &[
+    "permission",
+    "read-only",
+    "read only",
+    "not allowed",
+    "denied",
+    "forbidden",
+    "unauthori",
+    "locked",
+];
+/// Phrases that indicate the driver is occupied with another operation
+const BUSY_HINTS: &[&str] = &[
+    "busy",
+    "in progress",
+    "already",
+    "try again",
+    "not ready",
+    "wait",
+];
+/// Phrases that indicate a value outside the accepted range
+const RANGE_HINTS: &[&str] = &[
+    "out of range",
+    "range",
+    "exceed",
+    "too high",
+    "too low",
+    "limit",
+    "outside",
+    "minimum",
+    "maximum",
+    "invalid value",
+];
+
+/// Likely cause of a command rejected by a driver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionKind {
+    /// The property may not be written, or not in the current mode
+    PermissionDenied,
+    /// A value is outside the range the driver accepts
+    OutOfRange,
+    /// The device is occupied with another operation
+    Busy,
+    /// The message did not match any known pattern
+    Unknown,
+}
+
+impl RejectionKind {
+    /// Classify a driver message using keyword heuristics
+    ///
+    /// Drivers report failures as free text, so this is a best guess; the
+    /// original message is kept in [`CommandRejected::message`].
+    pub fn classify(message: &str) -> Self {
+        let message = message.to_lowercase();
+        let matches = |hints: &[&str]| hints.iter().any(|hint| message.contains(hint));
+        if matches(PERMISSION_HINTS) {
+            RejectionKind::PermissionDenied
+        } else if matches(BUSY_HINTS) {
+            RejectionKind::Busy
+        } else if matches(RANGE_HINTS) {
+            RejectionKind::OutOfRange
+        } else {
+            RejectionKind::Unknown
+        }
+    }
+}
+
+/// A driver answered one of our updates with `Alert`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandRejected {
+    /// Device the update was addressed to
+    pub device: String,
+    /// Property the update was addressed to
+    pub name: String,
+    /// Likely cause, derived from `message`
+    pub kind: RejectionKind,
+    /// Message sent by the driver, empty if there was none
+    pub message: String,
+}
+
+impl fmt::Display for CommandRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{} rejected ({:?}): {}",
+            self.device, self.name, self.kind, self.message
+        )
+    }
+}
+
+/// Outcome of an update, reported by [`PendingCommands::received`]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Completed {
+    /// Device the update was addressed to
+    pub(crate) device: String,
+    /// Property the update was addressed to
+    pub(crate) name: String,
+    /// Time from sending the update to the driver's final state
+    pub(crate) latency: Duration,
+    /// Classified rejection if the driver reported `Alert`
+    pub(crate) rejected: Option<CommandRejected>,
+}
+
+/// Properties with an update sent and no final state received yet
+#[derive(Debug, Default)]
+pub(crate) struct PendingCommands {
+    properties: HashMap<(String, String), Instant>,
+}
+
+impl PendingCommands {
+    /// Record an outgoing `new*Vector`
+    pub(crate) fn sent(&mut self, message: &MessageType) {
+        let (device, name) = match message {
+            MessageType::NewTextVector(new) => (&new.device, &new.name),
+            MessageType::NewNumberVector(new) => (&new.device, &new.name),
+            MessageType::NewSwitchVector(new) => (&new.device, &new.name),
+            MessageType::NewBlobVector(new) => (&new.device, &new.name),
+            _ => return,
+        };
+        self.properties
+            .insert((device.clone(), name.clone()), Instant::now());
+    }
+
+    /// Check an incoming `set*Vector` against the pending updates
+    ///
+    /// `Ok` and `Alert` complete a pending update; `Alert` also yields the
+    /// classified rejection.
+    pub(crate) fn received(&mut self, message: &MessageType) -> Option<Completed> {
+        let (device, name, state, text) = match message {
+            MessageType::SetTextVector(s) => (&s.device, &s.name, s.state, &s.message),
+            MessageType::SetNumberVector(s) => (&s.device, &s.name, s.state, &s.message),
+            MessageType::SetSwitchVector(s) => (&s.device, &s.name, s.state, &s.message),
+            MessageType::SetLightVector(s) => (&s.device, &s.name, s.state, &s.message),
+            MessageType::SetBlobVector(s) => (&s.device, &s.name, s.state, &s.message),
+            _ => return None,
+        };
+        if !matches!(state, Some(PropertyState::Ok | PropertyState::Alert)) {
+            return None;
+        }
+        let key = (device.clone(), name.clone());
+        let sent = self.properties.remove(&key)?;
+        let rejected = (state == Some(PropertyState::Alert)).then(|| {
+            let message = text.clone().unwrap_or_default();
+            CommandRejected {
+                device: key.0.clone(),
+                name: key.1.clone(),
+                kind: RejectionKind::classify(&message),
+                message,
+            }
+        });
+        Some(Completed {
+            device: key.0,
+            name: key.1,
+            latency: sent.elapsed(),
+            rejected,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::mock_server;
+    use crate::client::{Client, ClientEvent};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_classify() {
+        for (message, kind) in [
+            (
+                "Property is read-only in this mode",
+                RejectionKind::PermissionDenied,
+            ),
+            (
+                "Requested temperature out of range",
+                RejectionKind::OutOfRange,
+            ),
+            ("Position exceeds maximum travel", RejectionKind::OutOfRange),
+            ("Mount is busy slewing", RejectionKind::Busy),
+            ("Exposure already in progress", RejectionKind::Busy),
+            ("Serial timeout", RejectionKind::Unknown),
+            ("", RejectionKind::Unknown),
+        ] {
+            assert_eq!(RejectionKind::classify(message), kind, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_pending_commands() {
+        let mut pending = PendingCommands::default();
+        let alert = MessageType::from_str(
+            r#"<setNumberVector device="Focuser" name="ABS_FOCUS_POSITION" state="Alert" message="Target exceeds limit"><oneNumber name="FOCUS_ABSOLUTE_POSITION">0</oneNumber></setNumberVector>"#,
+        )
+        .unwrap();
+        // Alerts for properties we did not write are not our rejection
+        assert!(pending.received(&alert).is_none());
+
+        pending.sent(
+            &MessageType::from_str(
+                r#"<newNumberVector device="Focuser" name="ABS_FOCUS_POSITION"><oneNumber name="FOCUS_ABSOLUTE_POSITION">90000</oneNumber></newNumberVector>"#,
+            )
+            .unwrap(),
+        );
+        let rejected = pending.received(&alert).unwrap().rejected.unwrap();
+        assert_eq!(rejected.kind, RejectionKind::OutOfRange);
+        assert_eq!(rejected.message, "Target exceeds limit");
+        assert!(pending.received(&alert).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_command_rejected_event() {
+        let config = mock_server(
+            "",
+            "newSwitchVector",
+            r#"<setSwitchVector device="Mount" name="TELESCOPE_PARK" state="Alert" message="Permission denied while tracking"><oneSwitch name="PARK">Off</oneSwitch></setSwitchVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let mut events = client.subscribe();
+        client
+            .set_switch(
+                "Mount",
+                "TELESCOPE_PARK",
+                &[("PARK", crate::property::SwitchState::On)],
+            )
+            .await
+            .unwrap();
+        loop {
+            if let ClientEvent::CommandRejected(rejected) = events.recv().await.unwrap() {
+                assert_eq!(rejected.device, "Mount");
+                assert_eq!(rejected.kind, RejectionKind::PermissionDenied);
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use crate::client::Client;
+use crate::message::MessageType;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Property or element matching a search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    /// Device name
+    pub device: String,
+    /// Property name
+    pub property: String,
+    /// Element name, `None` if the property itself matched
+    pub element: Option<String>,
+    /// Relevance, higher is better
+    pub score: u32,
+}
+
+/// Indexed property or element
+#[derive(Debug, Clone)]
+struct Entry {
+    device: String,
+    property: String,
+    element: Option<String>,
+    tokens: Vec<String>,
+}
+
+/// Inverted index over device, property and element names and labels
+///
+/// Names and labels are split into lower case words, so `CCD_TEMPERATURE`
+/// is found by `temp`, `temperature` or `ccd temp`. Queries match words
+/// exactly, by prefix, as substring or with one typo, in decreasing order of
+/// relevance; every word of the query has to match.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    entries: Vec<Option<Entry>>,
+    postings: BTreeMap<String, HashSet<usize>>,
+    properties: HashMap<(String, String), Vec<usize>>,
+    /// Slots of removed entries, reused by later definitions
+    free: Vec<usize>,
+}
+
+impl SearchIndex {
+    /// Create an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of indexed properties and elements
+    pub fn len(&self) -> usize {
+        self.entries.iter().flatten().count()
+    }
+
+    /// Returns true if nothing is indexed
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Index a definition or drop the properties removed by `delProperty`
+    ///
+    /// Other messages are ignored.
+    pub fn update(&mut self, message: &MessageType) {
+        let (device, name, label, group, elements) = match message {
+            MessageType::DefTextVector(d) => (
+                &d.device,
+                &d.name,
+                &d.label,
+                &d.group,
+                d.texts.iter().map(|e| (&e.name, &e.label)).collect(),
+            ),
+            MessageType::DefNumberVector(d) => (
+                &d.device,
+                &d.name,
+                &d.label,
+                &d.group,
+                d.numbers.iter().map(|e| (&e.name, &e.label)).collect(),
+            ),
+            MessageType::DefSwitchVector(d) => (
+                &d.device,
+                &d.name,
+                &d.label,
+                &d.group,
+                d.switches.iter().map(|e| (&e.name, &e.label)).collect(),
+            ),
+            MessageType::DefLightVector(d) => (
+                &d.device,
+                &d.name,
+                &d.label,
+                &d.group,
+                d.lights.iter().map(|e| (&e.name, &e.label)).collect(),
+            ),
+            MessageType::DefBlobVector(d) => (
+                &d.device,
+                &d.name,
+                &d.label,
+                &d.group,
+                d.blobs
+                    .iter()
+                    .map(|e| (&e.name, &e.label))
+                    .collect::<Vec<_>>(),
+            ),
+            MessageType::DelProperty(del) => {
+                self.remove(&del.device, del.name.as_deref());
+                return;
+            }
+            _ => return,
+        };
+
+        self.remove(device, Some(name));
+        let property_tokens = tokenize([device.as_str(), name, label, group]);
+        let mut ids = vec![self.insert(device, name, None, property_tokens)];
+        for (element, element_label) in elements {
+            let tokens = tokenize([element.as_str(), element_label]);
+            ids.push(self.insert(device, name, Some(element), tokens));
+        }
+        self.properties.insert((device.clone(), name.clone()), ids);
+    }
+
+    /// Find properties and elements matching `query`, best matches first
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        let words = tokenize([query]);
+        if words.is_empty() {
+            return Vec::new();
+        }
+
+        // Best score of each entry for every query word
+        let mut scores: HashMap<usize, u32> = HashMap::new();
+        for (position, word) in words.iter().enumerate() {
+            let mut best: HashMap<usize, u32> = HashMap::new();
+            for (token, ids) in &self.postings {
+                let score = match_score(word, token);
+                if score == 0 {
+                    continue;
+                }
+                for id in ids {
+                    let entry = best.entry(*id).or_default();
+                    *entry = (*entry).max(score);
+                }
+            }
+            if position == 0 {
+                scores = best;
+            } else {
+                scores.retain(|id, score| match best.get(id) {
+                    Some(word_score) => {
+                        *score += word_score;
+                        true
+                    }
+                    None => false,
+                });
+            }
+        }
+
+        let mut hits = scores
+            .into_iter()
+            .filter_map(|(id, score)| {
+                let entry = self.entries[id].as_ref()?;
+                Some(SearchHit {
+                    device: entry.device.clone(),
+                    property: entry.property.clone(),
+                    element: entry.element.clone(),
+                    score,
+                })
+            })
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| {
+            b.score.cmp(&a.score).then_with(|| {
+                (&a.device, &a.property, &a.element).cmp(&(&b.device, &b.property, &b.element))
+            })
+        });
+        hits
+    }
+
+    /// Add an entry and its postings, returning its id
+    fn insert(
+        &mut self,
+        device: &str,
+        property: &str,
+        element: Option<&String>,
+        tokens: Vec<String>,
+    ) -> usize {
+        let id = self.free.pop().unwrap_or_else(|| {
+            self.entries.push(None);
+            self.entries.len() - 1
+        });
+        for token in &tokens {
+            self.postings.entry(token.clone()).or_default().insert(id);
+        }
+        self.entries[id] = Some(Entry {
+            device: device.to_string(),
+            property: property.to_string(),
+            element: element.cloned(),
+            tokens,
+        });
+        id
+    }
+
+    /// Remove a property, or all properties of a device if `name` is `None`
+    fn remove(&mut self, device: &str, name: Option<&str>) {
+        let keys = self
+            .properties
+            .keys()
+            .filter(|(d, n)| d == device && name.map_or(true, |name| n == name))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in keys {
+            for id in self.properties.remove(&key).unwrap_or_default() {
+                let Some(entry) = self.entries[id].take() else {
+                    continue;
+                };
+                self.free.push(id);
+                for token in entry.tokens {
+                    if let Some(ids) = self.postings.get_mut(&token) {
+                        ids.remove(&id);
+                        if ids.is_empty() {
+                            self.postings.remove(&token);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Split names and labels into distinct lower case words
+fn tokenize<'a>(texts: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for text in texts {
+        for word in text.split(|c: char| !c.is_alphanumeric()) {
+            let word = word.to_lowercase();
+            if !word.is_empty() && !tokens.contains(&word) {
+                tokens.push(word);
+            }
+        }
+    }
+    tokens
+}
+
+/// Relevance of indexed `token` for query `word`, 0 if it does not match
+fn match_score(word: &str, token: &str) -> u32 {
+    if token == word {
+        4
+    } else if token.starts_with(word) {
+        3
+    } else if token.contains(word) {
+        2
+    } else if word.chars().count() >= 4 && within_one_edit(word, token) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Returns true if `a` and `b` differ by at most one insertion, deletion or
+/// substitution
+fn within_one_edit(a: &str, b: &str) -> bool {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    if long.len() - short.len() > 1 {
+        return false;
+    }
+    let prefix = short.iter().zip(&long).take_while(|(x, y)| x == y).count();
+    if prefix == short.len() {
+        return true;
+    }
+    if short.len() == long.len() {
+        short[prefix + 1..] == long[prefix + 1..]
+    } else {
+        short[prefix..] == long[prefix + 1..]
+    }
+}
+
+impl Client {
+    /// Search device, property and element names and labels
+    ///
+    /// See [`SearchIndex`] for how queries match.
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        self.search
+            .read()
+            .map(|index| index.search(query))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn index() -> SearchIndex {
+        let mut index = SearchIndex::new();
+        for xml in [
+            r#"<defNumberVector device="CCD Simulator" name="CCD_TEMPERATURE" label="Temperature (C)" group="Main Control" state="Idle" perm="rw"><defNumber name="CCD_TEMPERATURE_VALUE" label="Temperature" format="%5.2f" min="-50" max="50" step="0">20</defNumber></defNumberVector>"#,
+            r#"<defNumberVector device="Focuser Simulator" name="FOCUS_TEMPERATURE" label="Temperature" group="Main Control" state="Idle" perm="ro"><defNumber name="TEMPERATURE" label="Celsius" format="%5.2f" min="-50" max="50" step="0">10</defNumber></defNumberVector>"#,
+            r#"<defSwitchVector device="CCD Simulator" name="CONNECTION" label="Connection" group="Main Control" state="Ok" perm="rw" rule="OneOfMany"><defSwitch name="CONNECT" label="Connect">On</defSwitch><defSwitch name="DISCONNECT" label="Disconnect">Off</defSwitch></defSwitchVector>"#,
+        ] {
+            index.update(&MessageType::from_str(xml).unwrap());
+        }
+        index
+    }
+
+    #[test]
+    fn test_search() {
+        let mut index = index();
+        assert_eq!(index.len(), 7);
+
+        let hits = index.search("temp");
+        assert_eq!(hits.len(), 4);
+        assert!(hits.iter().all(|hit| hit.property.contains("TEMPERATURE")));
+
+        // Every word has to match
+        let hits = index.search("ccd temp");
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].device, "CCD Simulator");
+
+        // Exact words rank above prefixes and substrings
+        let hits = index.search("connect");
+        assert_eq!(hits[0].element.as_deref(), Some("CONNECT"));
+        assert!(hits
+            .iter()
+            .any(|hit| hit.element.as_deref() == Some("DISCONNECT")));
+
+        // One typo is tolerated
+        assert!(!index.search("temprature").is_empty());
+        assert!(index.search("xyz").is_empty());
+
+        index.update(&MessageType::from_str(r#"<delProperty device="CCD Simulator"/>"#).unwrap());
+        assert_eq!(index.len(), 2);
+        assert!(index.search("connect").is_empty());
+    }
+
+    #[test]
+    fn test_within_one_edit() {
+        assert!(within_one_edit("focus", "focus"));
+        assert!(within_one_edit("focus", "focvs"));
+        assert!(within_one_edit("focus", "focuss"));
+        assert!(within_one_edit("focus", "fcus"));
+        assert!(!within_one_edit("focus", "fcvs"));
+    }
+}
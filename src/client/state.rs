@@ -1,7 +1,22 @@
-use crate::error::Result;
-use crate::message::definition::{DefNumberVector, DefSwitchVector, DefTextVector};
-use crate::property::{Property, PropertyValue, SwitchState};
-use std::collections::HashMap;
+use crate::client::history::{PropertyHistory, PropertySample};
+use crate::error::{Error, Result};
+use crate::format::parse_number;
+use crate::message::definition::{
+    DefBlobVector, DefLightVector, DefNumberVector, DefSwitchVector, DefTextVector,
+};
+use crate::message::set::{
+    SetBlobVector, SetLightVector, SetNumberVector, SetSwitchVector, SetTextVector,
+};
+use crate::message::MessageType;
+use crate::property::{
+    BlobElement, NumberElement, Property, PropertyPerm, PropertyState, PropertyValue,
+    SwitchElement, TextElement,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Format version written by [`ClientState::snapshot`]
+#[cfg(feature = "serde_json")]
+const SNAPSHOT_VERSION: u64 = 2;
 
 /// Client state
 #[derive(Debug, Default)]
@@ -10,6 +25,14 @@ pub struct ClientState {
     pub properties: HashMap<String, HashMap<String, Property>>,
     /// Last message received
     pub last_message: Option<crate::message::MessageType>,
+    /// Whether the connection to the server is open
+    pub connected: bool,
+    /// Properties loaded from a cache and not yet defined by the server
+    cached: HashSet<(String, String)>,
+    /// Recent values of each property
+    history: PropertyHistory,
+    /// Property names of each device in definition order
+    order: HashMap<String, Vec<String>>,
 }
 
 impl ClientState {
@@ -18,9 +41,27 @@ impl ClientState {
         Self {
             properties: HashMap::new(),
             last_message: None,
+            connected: false,
+            cached: HashSet::new(),
+            history: PropertyHistory::default(),
+            order: HashMap::new(),
+        }
+    }
+
+    /// Create a client state keeping the last `depth` values of every
+    /// property, see [`history`](Self::history)
+    pub fn with_history_depth(depth: usize) -> Self {
+        Self {
+            history: PropertyHistory::new(depth),
+            ..Self::new()
         }
     }
 
+    /// Recent values of a property, oldest first
+    pub fn history(&self, device: &str, name: &str) -> Vec<PropertySample> {
+        self.history.get(device, name)
+    }
+
     /// Get a property by device and name
     pub fn get_property(&self, device: &str, name: &str) -> Option<&Property> {
         self.properties
@@ -28,19 +69,60 @@ impl ClientState {
             .and_then(|props| props.get(name))
     }
 
+    /// Update state with a message received from the server
+    pub fn update(&mut self, message: &MessageType) -> Result<()> {
+        match message {
+            MessageType::DefTextVector(prop) => self.update_text_vector(prop.clone())?,
+            MessageType::DefNumberVector(prop) => self.update_number_vector(prop.clone())?,
+            MessageType::DefSwitchVector(prop) => self.update_switch_vector(prop.clone())?,
+            MessageType::DefLightVector(prop) => self.update_light_vector(prop.clone())?,
+            MessageType::DefBlobVector(prop) => self.update_blob_vector(prop.clone())?,
+            MessageType::SetTextVector(set) => self.apply_text_vector(set)?,
+            MessageType::SetNumberVector(set) => self.apply_number_vector(set)?,
+            MessageType::SetSwitchVector(set) => self.apply_switch_vector(set)?,
+            MessageType::SetLightVector(set) => self.apply_light_vector(set)?,
+            MessageType::SetBlobVector(set) => self.apply_blob_vector(set)?,
+            MessageType::DelProperty(del) => self.remove_property(&del.device, del.name.as_deref()),
+            _ => (),
+        }
+        if let Some(key) = super::definition_key(message) {
+            self.cached.remove(&key);
+        }
+        let key = super::definition_key(message).or_else(|| {
+            super::set_vector_state(message)
+                .map(|(device, name, ..)| (device.to_string(), name.to_string()))
+        });
+        let property = key.and_then(|(device, name)| self.properties.get(&device)?.get(&name));
+        if let Some(property) = property {
+            self.history.record(property);
+        }
+        self.last_message = Some(message.clone());
+        Ok(())
+    }
+
     /// Update state with a text vector definition
     pub fn update_text_vector(&mut self, prop: DefTextVector) -> Result<()> {
-        let values = prop.texts.into_iter().map(|t| t.value).collect::<Vec<_>>();
-        let value = values.join(","); // Join multiple values with commas
+        let values = prop
+            .texts
+            .into_iter()
+            .map(|t| TextElement {
+                name: t.name,
+                label: t.label,
+                value: t.value,
+            })
+            .collect();
 
         let property = Property::new(
-            prop.device.clone(),
-            prop.name.clone(),
-            PropertyValue::Text(value),
+            prop.device,
+            prop.name,
+            PropertyValue::TextVector(values),
             prop.state,
             prop.perm,
             prop.timestamp,
-        );
+        )
+        .with_label(prop.label)
+        .with_group(prop.group)
+        .with_message(prop.message);
         self.update_property(property);
         Ok(())
     }
@@ -50,18 +132,30 @@ impl ClientState {
         let values = prop
             .numbers
             .into_iter()
-            .map(|n| n.value.parse::<f64>().unwrap())
-            .collect::<Vec<_>>();
+            .map(|n| {
+                Ok(NumberElement {
+                    min: parse_number(&n.min)?,
+                    max: parse_number(&n.max)?,
+                    step: parse_number(&n.step)?,
+                    value: parse_number(&n.value)?,
+                    name: n.name,
+                    label: n.label,
+                    format: n.format,
+                })
+            })
+            .collect::<Result<_>>()?;
 
-        let value = values.first().copied().unwrap_or_default();
         let property = Property::new(
-            prop.device.clone(),
-            prop.name.clone(),
-            PropertyValue::Number(value, None),
+            prop.device,
+            prop.name,
+            PropertyValue::NumberVector(values),
             prop.state,
             prop.perm,
             prop.timestamp,
-        );
+        )
+        .with_label(prop.label)
+        .with_group(prop.group)
+        .with_message(prop.message);
         self.update_property(property);
         Ok(())
     }
@@ -71,34 +165,350 @@ impl ClientState {
         let values = prop
             .switches
             .into_iter()
-            .map(|s| s.state)
-            .collect::<Vec<_>>();
+            .map(|s| SwitchElement {
+                name: s.name,
+                label: s.label,
+                state: s.state,
+            })
+            .collect();
 
-        let value = values.first().copied().unwrap_or(SwitchState::Off);
         let property = Property::new(
-            prop.device.clone(),
-            prop.name.clone(),
-            PropertyValue::Switch(value),
+            prop.device,
+            prop.name,
+            PropertyValue::SwitchVector(prop.rule, values),
             prop.state,
             prop.perm,
             prop.timestamp,
-        );
+        )
+        .with_label(prop.label)
+        .with_group(prop.group)
+        .with_message(prop.message);
+        self.update_property(property);
+        Ok(())
+    }
+
+    /// Update state with a light vector definition
+    ///
+    /// The value holds the state of the vector; the lights are stored as
+    /// [`Property::elements`], each with its own state as value.
+    pub fn update_light_vector(&mut self, prop: DefLightVector) -> Result<()> {
+        let lights = prop
+            .lights
+            .into_iter()
+            .map(|light| {
+                Property::new(
+                    prop.device.clone(),
+                    light.name,
+                    PropertyValue::Light(light.state),
+                    light.state,
+                    PropertyPerm::Ro,
+                    prop.timestamp.clone(),
+                )
+                .with_label(light.label)
+            })
+            .collect();
+        let mut property = Property::new(
+            prop.device,
+            prop.name,
+            PropertyValue::Light(prop.state),
+            prop.state,
+            PropertyPerm::Ro,
+            prop.timestamp,
+        )
+        .with_label(prop.label)
+        .with_group(prop.group)
+        .with_message(prop.message);
+        property.elements = Some(lights);
+        self.update_property(property);
+        Ok(())
+    }
+
+    /// Update state with a BLOB vector definition
+    ///
+    /// BLOB payloads are delivered through events only, so only the
+    /// elements and their metadata are tracked.
+    pub fn update_blob_vector(&mut self, prop: DefBlobVector) -> Result<()> {
+        let blobs = prop
+            .blobs
+            .into_iter()
+            .map(|b| BlobElement {
+                name: b.name,
+                label: b.label,
+                format: String::new(),
+            })
+            .collect();
+        let property = Property::new(
+            prop.device,
+            prop.name,
+            PropertyValue::BlobVector(blobs),
+            prop.state,
+            prop.perm,
+            prop.timestamp,
+        )
+        .with_label(prop.label)
+        .with_group(prop.group)
+        .with_message(prop.message);
         self.update_property(property);
         Ok(())
     }
 
+    /// Apply a text vector update to a defined property
+    pub fn apply_text_vector(&mut self, set: &SetTextVector) -> Result<()> {
+        let property = self.property_mut(&set.device, &set.name)?;
+        if let PropertyValue::TextVector(values) = &mut property.value {
+            for element in &set.elements {
+                if let Some(text) = values.iter_mut().find(|t| t.name == element.name) {
+                    text.value = element.value.clone();
+                }
+            }
+        }
+        apply_common(
+            property,
+            set.state,
+            set.timestamp.as_deref(),
+            set.message.as_deref(),
+        );
+        Ok(())
+    }
+
+    /// Apply a number vector update to a defined property
+    pub fn apply_number_vector(&mut self, set: &SetNumberVector) -> Result<()> {
+        let updates = set
+            .elements
+            .iter()
+            .map(|n| parse_number(&n.value).map(|value| (n.name.clone(), value)))
+            .collect::<Result<Vec<_>>>()?;
+        let property = self.property_mut(&set.device, &set.name)?;
+        if let PropertyValue::NumberVector(values) = &mut property.value {
+            for (name, value) in updates {
+                if let Some(number) = values.iter_mut().find(|n| n.name == name) {
+                    number.value = value;
+                }
+            }
+        }
+        apply_common(
+            property,
+            set.state,
+            set.timestamp.as_deref(),
+            set.message.as_deref(),
+        );
+        Ok(())
+    }
+
+    /// Apply a switch vector update to a defined property
+    pub fn apply_switch_vector(&mut self, set: &SetSwitchVector) -> Result<()> {
+        let property = self.property_mut(&set.device, &set.name)?;
+        if let PropertyValue::SwitchVector(_, values) = &mut property.value {
+            for element in &set.elements {
+                if let Some(switch) = values.iter_mut().find(|s| s.name == element.name) {
+                    switch.state = element.value;
+                }
+            }
+        }
+        apply_common(
+            property,
+            set.state,
+            set.timestamp.as_deref(),
+            set.message.as_deref(),
+        );
+        Ok(())
+    }
+
+    /// Apply a light vector update to a defined property
+    pub fn apply_light_vector(&mut self, set: &SetLightVector) -> Result<()> {
+        let property = self.property_mut(&set.device, &set.name)?;
+        for element in &set.elements {
+            let light = property
+                .elements
+                .iter_mut()
+                .flatten()
+                .find(|light| light.name == element.name);
+            if let Some(light) = light {
+                light.value = PropertyValue::Light(element.value);
+                light.state = element.value;
+            }
+        }
+        apply_common(
+            property,
+            set.state,
+            set.timestamp.as_deref(),
+            set.message.as_deref(),
+        );
+        if let Some(state) = set.state {
+            property.value = PropertyValue::Light(state);
+        }
+        Ok(())
+    }
+
+    /// Apply a BLOB vector update to a defined property
+    ///
+    /// Records the format of each BLOB received; the payload itself is not
+    /// stored.
+    pub fn apply_blob_vector(&mut self, set: &SetBlobVector) -> Result<()> {
+        let property = self.property_mut(&set.device, &set.name)?;
+        if let PropertyValue::BlobVector(values) = &mut property.value {
+            for element in &set.elements {
+                if let Some(blob) = values.iter_mut().find(|b| b.name == element.name) {
+                    blob.format = element.data_format().to_string();
+                }
+            }
+        }
+        apply_common(
+            property,
+            set.state,
+            set.timestamp.as_deref(),
+            set.message.as_deref(),
+        );
+        Ok(())
+    }
+
+    /// Properties of `device` by group, as INDI control panels show them
+    ///
+    /// Groups and the properties within them are in definition order; a
+    /// group's position is that of its first property. Properties without
+    /// a group are listed under the empty name.
+    pub fn grouped(&self, device: &str) -> Vec<(String, Vec<&Property>)> {
+        let Some(properties) = self.properties.get(device) else {
+            return Vec::new();
+        };
+        let mut names = self
+            .order
+            .get(device)
+            .map(|order| order.iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        // Properties inserted into `properties` directly come last
+        let mut unordered = properties
+            .keys()
+            .filter(|name| !names.contains(name))
+            .collect::<Vec<_>>();
+        unordered.sort();
+        names.extend(unordered);
+
+        let mut groups: Vec<(String, Vec<&Property>)> = Vec::new();
+        for property in names.into_iter().filter_map(|name| properties.get(name)) {
+            let group = property.group.as_deref().unwrap_or_default();
+            match groups.iter_mut().find(|(name, _)| name == group) {
+                Some((_, members)) => members.push(property),
+                None => groups.push((group.to_string(), vec![property])),
+            }
+        }
+        groups
+    }
+
     /// Update a property in the state
     fn update_property(&mut self, property: Property) {
         let device = property.device.clone();
         let name = property.name.clone();
+        let order = self.order.entry(device.clone()).or_default();
+        if !order.contains(&name) {
+            order.push(name.clone());
+        }
         self.properties
             .entry(device)
             .or_default()
             .insert(name, property);
     }
 
+    /// Get a mutable reference to a defined property
+    fn property_mut(&mut self, device: &str, name: &str) -> Result<&mut Property> {
+        self.properties
+            .get_mut(device)
+            .and_then(|props| props.get_mut(name))
+            .ok_or_else(|| Error::Property(format!("Unknown property {}.{}", device, name)))
+    }
+
+    /// Export all properties as JSON
+    ///
+    /// The snapshot is an object with a format `version` and a `properties`
+    /// array sorted by device and name, suitable for persisting a GUI's view
+    /// of the device tree. Load it again with [`restore`](Self::restore).
+    #[cfg(feature = "serde_json")]
+    pub fn snapshot(&self) -> Result<serde_json::Value> {
+        let mut properties = self
+            .properties
+            .values()
+            .flat_map(|props| props.values())
+            .collect::<Vec<_>>();
+        properties.sort_by(|a, b| (&a.device, &a.name).cmp(&(&b.device, &b.name)));
+        Ok(serde_json::json!({
+            "version": SNAPSHOT_VERSION,
+            "properties": serde_json::to_value(properties)
+                .map_err(|e| Error::SerializationError(e.to_string()))?,
+        }))
+    }
+
+    /// Replace all properties with those of a [`snapshot`](Self::snapshot)
+    ///
+    /// The state is left unchanged if the snapshot cannot be read.
+    #[cfg(feature = "serde_json")]
+    pub fn restore(&mut self, snapshot: &serde_json::Value) -> Result<()> {
+        let properties = snapshot_properties(snapshot)?;
+        self.properties.clear();
+        self.cached.clear();
+        self.order.clear();
+        for property in properties {
+            self.update_property(property);
+        }
+        Ok(())
+    }
+
+    /// Add the properties of a [`snapshot`](Self::snapshot) that are not
+    /// defined yet, marking them as cached
+    ///
+    /// Cached properties are replaced as the server defines them; those it
+    /// never defines again are dropped by
+    /// [`reconcile_cache`](Self::reconcile_cache). Returns the number of
+    /// properties added.
+    #[cfg(feature = "serde_json")]
+    pub fn restore_cached(&mut self, snapshot: &serde_json::Value) -> Result<usize> {
+        let mut added = 0;
+        for property in snapshot_properties(snapshot)? {
+            if self
+                .get_property(&property.device, &property.name)
+                .is_none()
+            {
+                self.cached
+                    .insert((property.device.clone(), property.name.clone()));
+                self.update_property(property);
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Returns true if `device`/`name` was loaded from a cache and has not
+    /// been defined by the server since
+    pub fn is_cached(&self, device: &str, name: &str) -> bool {
+        self.cached
+            .contains(&(device.to_string(), name.to_string()))
+    }
+
+    /// Remove the cached properties the server has not defined, returning
+    /// their device and property names, sorted
+    pub fn reconcile_cache(&mut self) -> Vec<(String, String)> {
+        let mut stale = self.cached.drain().collect::<Vec<_>>();
+        stale.sort();
+        for (device, name) in &stale {
+            self.remove_property(device, Some(name));
+        }
+        stale
+    }
+
     /// Remove a property
     pub fn remove_property(&mut self, device: &str, name: Option<&str>) {
+        self.history.remove(device, name);
+        match name {
+            Some(name) => {
+                if let Some(order) = self.order.get_mut(device) {
+                    order.retain(|n| n != name);
+                }
+            }
+            None => {
+                self.order.remove(device);
+            }
+        }
+        self.cached
+            .retain(|(d, n)| d != device || name.is_some_and(|name| n != name));
         if let Some(device_props) = self.properties.get_mut(device) {
             if let Some(name) = name {
                 device_props.remove(name);
@@ -111,3 +521,121 @@ impl ClientState {
         }
     }
 }
+
+/// Properties of a [`ClientState::snapshot`]
+#[cfg(feature = "serde_json")]
+fn snapshot_properties(snapshot: &serde_json::Value) -> Result<Vec<Property>> {
+    let version = snapshot.get("version").and_then(serde_json::Value::as_u64);
+    if version != Some(SNAPSHOT_VERSION) {
+        return Err(Error::ParseError(format!(
+            "Unsupported snapshot version {:?}",
+            version
+        )));
+    }
+    let properties = snapshot
+        .get("properties")
+        .cloned()
+        .ok_or_else(|| Error::ParseError("Snapshot has no properties".to_string()))?;
+    serde_json::from_value(properties)
+        .map_err(|e| Error::ParseError(format!("Invalid snapshot: {}", e)))
+}
+
+/// Apply the state, timestamp and message attributes shared by all set
+/// vectors
+fn apply_common(
+    property: &mut Property,
+    state: Option<PropertyState>,
+    timestamp: Option<&str>,
+    message: Option<&str>,
+) {
+    property.message = message.map(str::to_string);
+    if let Some(state) = state {
+        property.state = state;
+    }
+    if let Some(timestamp) = timestamp {
+        property.timestamp = timestamp.to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_grouped() {
+        let mut state = ClientState::new();
+        for xml in [
+            r#"<defSwitchVector device="CCD" name="CONNECTION" state="Ok" perm="rw" rule="OneOfMany" group="Main Control"><defSwitch name="CONNECT">On</defSwitch></defSwitchVector>"#,
+            r#"<defNumberVector device="CCD" name="CCD_EXPOSURE" state="Idle" perm="rw" group="Main Control"><defNumber name="CCD_EXPOSURE_VALUE" format="%5.2f" min="0" max="3600" step="1">1</defNumber></defNumberVector>"#,
+            r#"<defTextVector device="CCD" name="DRIVER_INFO" state="Idle" perm="ro" group="General Info"><defText name="DRIVER_NAME">CCD</defText></defTextVector>"#,
+            r#"<defNumberVector device="CCD" name="CCD_TEMPERATURE" state="Idle" perm="rw" group="Main Control"><defNumber name="CCD_TEMPERATURE_VALUE" format="%5.2f" min="-50" max="50" step="0">20</defNumber></defNumberVector>"#,
+            r#"<defSwitchVector device="CCD" name="CONNECTION" state="Ok" perm="rw" rule="OneOfMany" group="Main Control"><defSwitch name="CONNECT">Off</defSwitch></defSwitchVector>"#,
+            r#"<delProperty device="CCD" name="CCD_EXPOSURE"/>"#,
+        ] {
+            state.update(&MessageType::from_str(xml).unwrap()).unwrap();
+        }
+        let groups = state
+            .grouped("CCD")
+            .into_iter()
+            .map(|(group, properties)| {
+                let names = properties
+                    .iter()
+                    .map(|p| p.name.as_str())
+                    .collect::<Vec<_>>();
+                (group, names)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            [
+                (
+                    "Main Control".to_string(),
+                    vec!["CONNECTION", "CCD_TEMPERATURE"]
+                ),
+                ("General Info".to_string(), vec!["DRIVER_INFO"]),
+            ]
+        );
+        assert!(state.grouped("Focuser").is_empty());
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut state = ClientState::new();
+        for xml in [
+            r#"<defNumberVector device="CCD" name="CCD_TEMPERATURE" state="Ok" perm="rw" group="Main"><defNumber name="CCD_TEMPERATURE_VALUE" format="%5.2f" min="-50" max="50" step="0">-10</defNumber></defNumberVector>"#,
+            r#"<defSwitchVector device="CCD" name="CONNECTION" state="Ok" perm="rw" rule="OneOfMany"><defSwitch name="CONNECT">On</defSwitch><defSwitch name="DISCONNECT">Off</defSwitch></defSwitchVector>"#,
+            r#"<defTextVector device="Focuser" name="INFO" state="Idle" perm="ro"><defText name="NAME">focuser</defText></defTextVector>"#,
+        ] {
+            state.update(&MessageType::from_str(xml).unwrap()).unwrap();
+        }
+        let snapshot = state.snapshot().unwrap();
+        assert_eq!(snapshot["properties"].as_array().unwrap().len(), 3);
+        assert_eq!(snapshot["properties"][0]["name"], "CCD_TEMPERATURE");
+
+        let mut restored = ClientState::new();
+        restored.restore(&snapshot).unwrap();
+        let property = restored.get_property("CCD", "CCD_TEMPERATURE").unwrap();
+        assert_eq!(property.state, PropertyState::Ok);
+        assert_eq!(property.group.as_deref(), Some("Main"));
+        assert_eq!(
+            property.value,
+            PropertyValue::NumberVector(vec![NumberElement {
+                name: "CCD_TEMPERATURE_VALUE".to_string(),
+                label: String::new(),
+                format: "%5.2f".to_string(),
+                min: -50.0,
+                max: 50.0,
+                step: 0.0,
+                value: -10.0,
+            }])
+        );
+        assert!(restored.get_property("Focuser", "INFO").is_some());
+
+        let mut unsupported = snapshot.clone();
+        unsupported["version"] = 99.into();
+        assert!(restored.restore(&unsupported).is_err());
+        assert_eq!(restored.properties.len(), 2);
+    }
+}
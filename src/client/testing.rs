@@ -0,0 +1,46 @@
+use crate::client::{Client, ClientConfig};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// Start a mock driver
+///
+/// Sends `greeting` on connect, then answers every line containing
+/// `trigger` with `reply`.
+pub(crate) async fn mock_server(
+    greeting: &'static str,
+    trigger: &'static str,
+    reply: &'static str,
+) -> ClientConfig {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = socket.into_split();
+        writer.write_all(greeting.as_bytes()).await.unwrap();
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            if line.contains(trigger) {
+                writer.write_all(reply.as_bytes()).await.unwrap();
+            }
+        }
+    });
+    ClientConfig::new(addr.ip().to_string(), addr.port())
+}
+
+/// Wait until the client state contains `device`/`name`
+pub(crate) async fn wait_for_property(client: &Client, device: &str, name: &str) {
+    for _ in 0..100 {
+        if client
+            .state()
+            .lock()
+            .await
+            .get_property(device, name)
+            .is_some()
+        {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Property {}.{} was never defined", device, name);
+}
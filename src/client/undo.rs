@@ -0,0 +1,208 @@
+use crate::client::{Client, ClientState};
+use crate::error::{Error, Result};
+use crate::message::new::{
+    NewNumberVector, NewSwitchVector, NewTextVector, OneNumber, OneSwitch, OneText,
+};
+use crate::message::MessageType;
+use crate::property::{PropertyPerm, PropertyValue};
+use std::collections::{HashMap, VecDeque};
+
+/// Number of earlier values kept per property
+const UNDO_DEPTH: usize = 16;
+
+/// Earlier values of properties written through the client, newest last
+#[derive(Debug, Default)]
+pub(crate) struct UndoHistory {
+    properties: HashMap<(String, String), VecDeque<MessageType>>,
+}
+
+impl UndoHistory {
+    /// Remember `restore`, the update undoing a write, dropping the oldest
+    /// entry beyond [`UNDO_DEPTH`]
+    pub(crate) fn push(&mut self, restore: MessageType) {
+        let Some(key) = update_key(&restore) else {
+            return;
+        };
+        let history = self.properties.entry(key).or_default();
+        if history.len() == UNDO_DEPTH {
+            history.pop_front();
+        }
+        history.push_back(restore);
+    }
+
+    /// Take the update undoing the latest write to `device`/`name`
+    fn pop(&mut self, device: &str, name: &str) -> Option<MessageType> {
+        let key = (device.to_string(), name.to_string());
+        let history = self.properties.get_mut(&key)?;
+        let restore = history.pop_back();
+        if history.is_empty() {
+            self.properties.remove(&key);
+        }
+        restore
+    }
+}
+
+/// Device and property name of a `new*Vector`
+fn update_key(message: &MessageType) -> Option<(String, String)> {
+    let (device, name) = match message {
+        MessageType::NewTextVector(new) => (&new.device, &new.name),
+        MessageType::NewNumberVector(new) => (&new.device, &new.name),
+        MessageType::NewSwitchVector(new) => (&new.device, &new.name),
+        _ => return None,
+    };
+    Some((device.clone(), name.clone()))
+}
+
+/// Update restoring the values `message` is about to overwrite
+///
+/// Numbers and texts restore the written elements only. Switches restore
+/// the whole vector, since turning one switch of a `OneOfMany` vector back
+/// off does not turn the previous one on. Returns `None` for read-only or
+/// unknown properties and elements.
+pub(crate) fn restoring_update(state: &ClientState, message: &MessageType) -> Option<MessageType> {
+    let (device, name) = update_key(message)?;
+    let property = state.get_property(&device, &name)?;
+    if property.perm == PropertyPerm::Ro {
+        return None;
+    }
+    match (message, &property.value) {
+        (MessageType::NewNumberVector(new), value @ PropertyValue::NumberVector(_)) => {
+            let elements = new
+                .elements
+                .iter()
+                .map(|one| {
+                    value.number(&one.name).map(|value| OneNumber {
+                        name: one.name.clone(),
+                        value: value.to_string(),
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(MessageType::NewNumberVector(NewNumberVector {
+                device,
+                name,
+                timestamp: None,
+                elements,
+            }))
+        }
+        (MessageType::NewTextVector(new), value @ PropertyValue::TextVector(_)) => {
+            let elements = new
+                .elements
+                .iter()
+                .map(|one| {
+                    value.text(&one.name).map(|value| OneText {
+                        name: one.name.clone(),
+                        value: value.to_string(),
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(MessageType::NewTextVector(NewTextVector {
+                device,
+                name,
+                timestamp: None,
+                elements,
+            }))
+        }
+        (MessageType::NewSwitchVector(_), PropertyValue::SwitchVector(_, switches)) => {
+            let elements = switches
+                .iter()
+                .map(|switch| OneSwitch {
+                    name: switch.name.clone(),
+                    value: switch.state,
+                })
+                .collect::<Vec<_>>();
+            Some(MessageType::NewSwitchVector(NewSwitchVector {
+                device,
+                name,
+                timestamp: None,
+                elements,
+            }))
+        }
+        _ => None,
+    }
+}
+
+impl Client {
+    /// Restore the values `device`/`name` had before the latest write
+    ///
+    /// Every write of a writable number, text or switch vector through this
+    /// client remembers the values it replaced, up to 16 per property.
+    /// Undoing sends them again and is not itself recorded, so repeated
+    /// calls step further back. Fails if there is nothing to undo.
+    pub async fn undo_last(&self, device: &str, name: &str) -> Result<()> {
+        let restore = self
+            .undo
+            .lock()
+            .ok()
+            .and_then(|mut history| history.pop(device, name))
+            .ok_or_else(|| Error::Property(format!("Nothing to undo for {}.{}", device, name)))?;
+        if let Err(e) = self.dispatch(&restore, false).await {
+            if let Ok(mut history) = self.undo.lock() {
+                history.push(restore);
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::{mock_server, wait_for_property};
+    use crate::property::SwitchState;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_restoring_update() {
+        let mut state = ClientState::new();
+        state
+            .update(
+                &MessageType::from_str(
+                    r#"<defSwitchVector device="Mount" name="SLEW_RATE" state="Idle" perm="rw" rule="OneOfMany"><defSwitch name="SLOW">On</defSwitch><defSwitch name="FAST">Off</defSwitch></defSwitchVector>"#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let write = MessageType::from_str(
+            r#"<newSwitchVector device="Mount" name="SLEW_RATE"><oneSwitch name="FAST">On</oneSwitch></newSwitchVector>"#,
+        )
+        .unwrap();
+        let Some(MessageType::NewSwitchVector(restore)) = restoring_update(&state, &write) else {
+            panic!("Expected a switch update");
+        };
+        assert_eq!(restore.elements.len(), 2);
+        assert_eq!(restore.elements[0].name, "SLOW");
+        assert_eq!(restore.elements[0].value, SwitchState::On);
+
+        let mut history = UndoHistory::default();
+        for _ in 0..UNDO_DEPTH + 5 {
+            history.push(MessageType::NewSwitchVector(restore.clone()));
+        }
+        assert_eq!(
+            history.properties.values().next().unwrap().len(),
+            UNDO_DEPTH
+        );
+    }
+
+    #[tokio::test]
+    async fn test_undo_last() {
+        let config = mock_server(
+            r#"<defNumberVector device="CCD" name="CCD_GAIN" state="Idle" perm="rw"><defNumber name="GAIN" format="%.0f" min="0" max="300" step="1">100</defNumber></defNumberVector>
+"#,
+            "newNumberVector",
+            "",
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        wait_for_property(&client, "CCD", "CCD_GAIN").await;
+        assert!(client.undo_last("CCD", "CCD_GAIN").await.is_err());
+
+        client
+            .set_number("CCD", "CCD_GAIN", &[("GAIN", 3000.0)])
+            .await
+            .unwrap();
+        client.undo_last("CCD", "CCD_GAIN").await.unwrap();
+        // The undo itself is not recorded
+        assert!(client.undo_last("CCD", "CCD_GAIN").await.is_err());
+    }
+}
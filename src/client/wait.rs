@@ -0,0 +1,151 @@
+use crate::client::ClientEvent;
+use crate::error::{Error, Result};
+use crate::message::MessageType;
+use crate::property::PropertyState;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+/// Extract device, property name, state and message from a set vector
+pub(crate) fn set_vector_state(
+    message: &MessageType,
+) -> Option<(&str, &str, Option<PropertyState>, Option<&str>)> {
+    let (state, text) = match message {
+        MessageType::SetTextVector(s) => (s.state, &s.message),
+        MessageType::SetNumberVector(s) => (s.state, &s.message),
+        MessageType::SetSwitchVector(s) => (s.state, &s.message),
+        MessageType::SetLightVector(s) => (s.state, &s.message),
+        MessageType::SetBlobVector(s) => (s.state, &s.message),
+        _ => return None,
+    };
+    Some((message.device()?, message.name()?, state, text.as_deref()))
+}
+
+/// Wait until the driver reports `Ok` for `device`/`name`
+///
+/// Intermediate `Busy` updates are skipped; an `Alert` fails with the
+/// driver's message.
+pub(crate) async fn wait_for_ok(
+    events: &mut broadcast::Receiver<ClientEvent>,
+    device: &str,
+    name: &str,
+    timeout: Duration,
+) -> Result<()> {
+    let pending = HashSet::from([(device.to_string(), name.to_string())]);
+    wait_for_all_ok(events, pending, timeout).await
+}
+
+/// Wait until the driver reports a final state for `device`/`name`
+///
+/// Any state but `Busy` is final. `Ok` and `Idle` are returned, `None` if
+/// the driver answered without a state, leaving it unchanged; an `Alert`
+/// fails with the driver's message.
+pub(crate) async fn wait_for_final_state(
+    events: &mut broadcast::Receiver<ClientEvent>,
+    device: &str,
+    name: &str,
+    timeout: Duration,
+) -> Result<Option<PropertyState>> {
+    let wait = async {
+        loop {
+            let message = match events.recv().await {
+                Ok(ClientEvent::Message(message)) => message,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Missed {} events while waiting for {}.{}",
+                        skipped, device, name
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => {
+                    return Err(Error::Protocol("Connection closed".to_string()))
+                }
+            };
+            let Some((d, n, state, message)) = set_vector_state(&message) else {
+                continue;
+            };
+            if d != device || n != name {
+                continue;
+            }
+            match state {
+                Some(PropertyState::Alert) => {
+                    return Err(Error::Property(
+                        message
+                            .map(str::to_string)
+                            .unwrap_or_else(|| format!("{}.{} reported Alert", device, name)),
+                    ))
+                }
+                Some(PropertyState::Busy) => (),
+                state => return Ok(state),
+            }
+        }
+    };
+
+    tokio::time::timeout(timeout, wait).await.map_err(|_| {
+        Error::Timeout(format!(
+            "{}.{} was not acknowledged within {:?}",
+            device, name, timeout
+        ))
+    })?
+}
+
+/// Wait until the driver reports `Ok` for every `(device, name)` in `pending`
+///
+/// The first `Alert` on any of the properties fails the wait with the
+/// driver's message.
+pub(crate) async fn wait_for_all_ok(
+    events: &mut broadcast::Receiver<ClientEvent>,
+    mut pending: HashSet<(String, String)>,
+    timeout: Duration,
+) -> Result<()> {
+    let wait = async {
+        while !pending.is_empty() {
+            let message = match events.recv().await {
+                Ok(ClientEvent::Message(message)) => message,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Missed {} events while waiting for {:?}", skipped, pending);
+                    continue;
+                }
+                Err(RecvError::Closed) => {
+                    return Err(Error::Protocol("Connection closed".to_string()))
+                }
+            };
+            let Some((device, name, state, message)) = set_vector_state(&message) else {
+                continue;
+            };
+            let key = (device.to_string(), name.to_string());
+            match state {
+                Some(PropertyState::Ok) => {
+                    pending.remove(&key);
+                }
+                Some(PropertyState::Alert) if pending.contains(&key) => {
+                    return Err(Error::Property(
+                        message
+                            .map(str::to_string)
+                            .unwrap_or_else(|| format!("{}.{} reported Alert", device, name)),
+                    ));
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    };
+
+    match tokio::time::timeout(timeout, wait).await {
+        Ok(result) => result,
+        Err(_) => {
+            let mut remaining = pending
+                .iter()
+                .map(|(device, name)| format!("{}.{}", device, name))
+                .collect::<Vec<_>>();
+            remaining.sort();
+            Err(Error::Timeout(format!(
+                "{} did not reach Ok",
+                remaining.join(", ")
+            )))
+        }
+    }
+}
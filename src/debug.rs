@@ -0,0 +1,139 @@
+//! Protocol debugging switches
+//!
+//! Client and server connections consult a shared [`DebugOptions`] before
+//! logging raw traffic, property state changes and BLOB metadata. The
+//! switches can be flipped while connected, so a misbehaving setup can be
+//! inspected without restarting it at a higher log level. Output is logged
+//! at `info` level so it shows up with the usual subscriber configuration.
+
+use crate::message::MessageType;
+use crate::property::PropertyState;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::info;
+
+/// A protocol debugging switch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DebugFlag {
+    /// Log every XML message sent and received
+    RawXml,
+    /// Log changes of property states
+    StateTransitions,
+    /// Log name, format and size of BLOBs, without their data
+    BlobMetadata,
+}
+
+impl DebugFlag {
+    /// All flags
+    pub const ALL: [DebugFlag; 3] = [
+        DebugFlag::RawXml,
+        DebugFlag::StateTransitions,
+        DebugFlag::BlobMetadata,
+    ];
+
+    /// Switch element name used on the server's control device
+    pub fn element(&self) -> &'static str {
+        match self {
+            DebugFlag::RawXml => "RAW_XML",
+            DebugFlag::StateTransitions => "STATE_TRANSITIONS",
+            DebugFlag::BlobMetadata => "BLOB_METADATA",
+        }
+    }
+
+    /// Flag controlled by switch element `name`
+    pub fn from_element(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|flag| flag.element() == name)
+    }
+}
+
+/// Protocol debugging switches of a connection, all off by default
+#[derive(Debug, Default)]
+pub struct DebugOptions {
+    raw_xml: AtomicBool,
+    state_transitions: AtomicBool,
+    blob_metadata: AtomicBool,
+}
+
+impl DebugOptions {
+    /// Create options with every flag off
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if `flag` is on
+    pub fn is_enabled(&self, flag: DebugFlag) -> bool {
+        self.flag(flag).load(Ordering::Relaxed)
+    }
+
+    /// Turn `flag` on or off
+    pub fn set(&self, flag: DebugFlag, enabled: bool) {
+        self.flag(flag).store(enabled, Ordering::Relaxed);
+    }
+
+    fn flag(&self, flag: DebugFlag) -> &AtomicBool {
+        match flag {
+            DebugFlag::RawXml => &self.raw_xml,
+            DebugFlag::StateTransitions => &self.state_transitions,
+            DebugFlag::BlobMetadata => &self.blob_metadata,
+        }
+    }
+
+    /// Log `xml` exchanged with `peer` if [`DebugFlag::RawXml`] is on
+    pub(crate) fn log_xml(&self, peer: &str, outgoing: bool, xml: &str) {
+        if self.is_enabled(DebugFlag::RawXml) {
+            let direction = if outgoing { "->" } else { "<-" };
+            info!("{} {} {}", peer, direction, xml.trim());
+        }
+    }
+
+    /// Log a property state change if [`DebugFlag::StateTransitions`] is on
+    pub(crate) fn log_transition(
+        &self,
+        device: &str,
+        name: &str,
+        from: PropertyState,
+        to: PropertyState,
+    ) {
+        if from != to && self.is_enabled(DebugFlag::StateTransitions) {
+            info!("{}.{}: {:?} -> {:?}", device, name, from, to);
+        }
+    }
+
+    /// Log the BLOBs of a `setBLOBVector` if [`DebugFlag::BlobMetadata`] is on
+    pub(crate) fn log_blobs(&self, message: &MessageType) {
+        let MessageType::SetBlobVector(set) = message else {
+            return;
+        };
+        if !self.is_enabled(DebugFlag::BlobMetadata) {
+            return;
+        }
+        for blob in &set.elements {
+            info!(
+                "BLOB {}.{}.{}: {} bytes, format {:?}, {} encoded",
+                set.device,
+                set.name,
+                blob.name,
+                blob.size,
+                blob.format,
+                blob.value.len()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_options() {
+        let options = DebugOptions::new();
+        assert!(DebugFlag::ALL.iter().all(|flag| !options.is_enabled(*flag)));
+        options.set(DebugFlag::RawXml, true);
+        assert!(options.is_enabled(DebugFlag::RawXml));
+        assert!(!options.is_enabled(DebugFlag::BlobMetadata));
+        for flag in DebugFlag::ALL {
+            assert_eq!(DebugFlag::from_element(flag.element()), Some(flag));
+        }
+        assert_eq!(DebugFlag::from_element("VERBOSE"), None);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::client::{Client, ClientEvent};
+use crate::devices::cooler::{self, CoolerRamp, RampHandle};
+use crate::error::{Error, Result};
+use crate::message::MessageType;
+use crate::property::{PropertyState, SwitchState};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+
+/// Exposure property of a CCD device
+const CCD_EXPOSURE: &str = "CCD_EXPOSURE";
+/// Exposure duration element of [`CCD_EXPOSURE`]
+const CCD_EXPOSURE_VALUE: &str = "CCD_EXPOSURE_VALUE";
+/// BLOB property carrying the primary chip image
+const CCD1: &str = "CCD1";
+/// Frame type property of a CCD device
+const CCD_FRAME_TYPE: &str = "CCD_FRAME_TYPE";
+
+/// Kind of frame recorded by a camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameType {
+    /// Regular exposure of the sky
+    Light,
+    /// Zero-length exposure with the shutter closed
+    Bias,
+    /// Exposure with the shutter closed
+    Dark,
+    /// Exposure of an evenly illuminated field
+    Flat,
+}
+
+impl FrameType {
+    /// All frame types
+    pub const ALL: [FrameType; 4] = [
+        FrameType::Light,
+        FrameType::Bias,
+        FrameType::Dark,
+        FrameType::Flat,
+    ];
+
+    /// Element of `CCD_FRAME_TYPE` selecting this frame type
+    pub(crate) fn element(self) -> &'static str {
+        match self {
+            FrameType::Light => "FRAME_LIGHT",
+            FrameType::Bias => "FRAME_BIAS",
+            FrameType::Dark => "FRAME_DARK",
+            FrameType::Flat => "FRAME_FLAT",
+        }
+    }
+
+    /// Lower case name, e.g. for directory names
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FrameType::Light => "light",
+            FrameType::Bias => "bias",
+            FrameType::Dark => "dark",
+            FrameType::Flat => "flat",
+        }
+    }
+}
+
+/// Image data downloaded from a camera
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blob {
+    /// Device that produced the image
+    pub device: String,
+    /// BLOB element name
+    pub name: String,
+    /// Format suffix of `data` (e.g. `.fits`), see [`OneBlob::data_format`]
+    ///
+    /// [`OneBlob::data_format`]: crate::message::new::OneBlob::data_format
+    pub format: String,
+    /// Decoded image bytes
+    pub data: Vec<u8>,
+}
+
+/// Camera/CCD device wrapper
+///
+/// Drives the standard `CCD_EXPOSURE` property and receives the resulting
+/// image from the `CCD1` BLOB property.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    client: Client,
+    device: String,
+    download_timeout: Duration,
+}
+
+impl Camera {
+    /// Default time allowed for image readout and transfer after the exposure
+    pub const DEFAULT_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// Create a new camera wrapper for `device`
+    pub fn new(client: Client, device: impl Into<String>) -> Self {
+        Self {
+            client,
+            device: device.into(),
+            download_timeout: Self::DEFAULT_DOWNLOAD_TIMEOUT,
+        }
+    }
+
+    /// Sets the time allowed for readout and download after the exposure ends
+    pub fn with_download_timeout(mut self, timeout: Duration) -> Self {
+        self.download_timeout = timeout;
+        self
+    }
+
+    /// Device name
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// Select the frame type recorded by the following exposures
+    pub async fn set_frame_type(&self, frame_type: FrameType) -> Result<()> {
+        let values = FrameType::ALL
+            .iter()
+            .map(|t| {
+                let state = if *t == frame_type {
+                    SwitchState::On
+                } else {
+                    SwitchState::Off
+                };
+                (t.element(), state)
+            })
+            .collect::<Vec<_>>();
+        self.client
+            .set_switch(&self.device, CCD_FRAME_TYPE, &values)
+            .await
+    }
+
+    /// Ramp the sensor temperature toward [`CoolerRamp::target`]
+    ///
+    /// The ramp starts from the measured temperature and runs in the
+    /// background until the target is reached or the handle is cancelled.
+    pub async fn ramp_temperature(&self, ramp: CoolerRamp) -> Result<RampHandle> {
+        cooler::start(self.client.clone(), self.device.clone(), ramp).await
+    }
+
+    /// Take an exposure and download the resulting image
+    ///
+    /// Enables BLOB delivery for the device, starts the exposure and waits
+    /// for the matching `setBLOBVector`. Fails if the driver reports the
+    /// exposure as `Alert` or no image arrives within the exposure time plus
+    /// the download timeout. With [`Client::stream_blobs`] active the image
+    /// is written to disk instead and the returned BLOB is empty.
+    pub async fn expose(&self, duration: Duration) -> Result<Blob> {
+        // Subscribe before sending so no reply can be missed
+        let mut events = self.client.subscribe();
+        self.client.enable_blob(&self.device, None, "Also").await?;
+        self.client
+            .set_number(
+                &self.device,
+                CCD_EXPOSURE,
+                &[(CCD_EXPOSURE_VALUE, duration.as_secs_f64())],
+            )
+            .await?;
+        debug!("Started {:?} exposure on {}", duration, self.device);
+
+        let wait = async {
+            loop {
+                let message = match events.recv().await {
+                    Ok(ClientEvent::Message(message)) => message,
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Camera {} missed {} events", self.device, skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => {
+                        return Err(Error::Protocol("Connection closed".to_string()))
+                    }
+                };
+                match message.as_ref() {
+                    MessageType::SetNumberVector(set)
+                        if set.device == self.device
+                            && set.name == CCD_EXPOSURE
+                            && set.state == Some(PropertyState::Alert) =>
+                    {
+                        return Err(Error::Property(
+                            set.message
+                                .clone()
+                                .unwrap_or_else(|| "Exposure failed".to_string()),
+                        ));
+                    }
+                    MessageType::SetBlobVector(set)
+                        if set.device == self.device && set.name == CCD1 =>
+                    {
+                        if let Some(blob) = set.elements.iter().find(|b| !b.value.is_empty()) {
+                            return Ok(Blob {
+                                device: set.device.clone(),
+                                name: blob.name.clone(),
+                                format: blob.data_format().to_string(),
+                                data: blob.get_data()?,
+                            });
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        };
+
+        tokio::time::timeout(duration + self.download_timeout, wait)
+            .await
+            .map_err(|_| Error::Timeout(format!("No image received from {}", self.device)))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::mock_server;
+
+    #[tokio::test]
+    async fn test_expose() {
+        let config = mock_server(
+            "",
+            "newNumberVector",
+            r#"<setNumberVector device="CCD Simulator" name="CCD_EXPOSURE" state="Busy">
+<oneNumber name="CCD_EXPOSURE_VALUE">0</oneNumber>
+</setNumberVector>
+<setBLOBVector device="CCD Simulator" name="CCD1" state="Ok">
+<oneBLOB name="CCD1" size="5" format=".fits">aGVsbG8=</oneBLOB>
+</setBLOBVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let camera = Camera::new(client, "CCD Simulator");
+
+        let blob = camera.expose(Duration::from_millis(10)).await.unwrap();
+        assert_eq!(blob.name, "CCD1");
+        assert_eq!(blob.format, ".fits");
+        assert_eq!(blob.data, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_expose_alert() {
+        let config = mock_server(
+            "",
+            "newNumberVector",
+            r#"<setNumberVector device="CCD Simulator" name="CCD_EXPOSURE" state="Alert" message="Shutter stuck">
+<oneNumber name="CCD_EXPOSURE_VALUE">0</oneNumber>
+</setNumberVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let camera = Camera::new(client, "CCD Simulator");
+
+        let err = camera.expose(Duration::from_millis(10)).await.unwrap_err();
+        assert!(matches!(err, Error::Property(msg) if msg == "Shutter stuck"));
+    }
+}
@@ -0,0 +1,244 @@
+use crate::client::Client;
+use crate::devices::number_value;
+use crate::error::{Error, Result};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// Temperature property of a CCD device, reporting the measured value
+const CCD_TEMPERATURE: &str = "CCD_TEMPERATURE";
+/// Temperature element of [`CCD_TEMPERATURE`]
+const CCD_TEMPERATURE_VALUE: &str = "CCD_TEMPERATURE_VALUE";
+/// Cooler power property of a CCD device
+const CCD_COOLER_POWER: &str = "CCD_COOLER_POWER";
+/// Power element of [`CCD_COOLER_POWER`], in percent
+const CCD_COOLER_VALUE: &str = "CCD_COOLER_VALUE";
+
+/// Gradual change of the sensor temperature
+///
+/// The setpoint moves toward `target` by at most `rate` degrees per minute.
+/// It is held while the sensor lags more than `max_lag` behind or, when
+/// cooling, while the cooler runs at `max_power` or above.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoolerRamp {
+    /// Final temperature in °C
+    pub target: f64,
+    /// Maximum setpoint change in °C per minute
+    pub rate: f64,
+    /// Time between setpoint updates
+    pub interval: Duration,
+    /// Accepted deviation from `target` in °C when finishing
+    pub tolerance: f64,
+    /// Largest accepted difference between setpoint and sensor in °C
+    pub max_lag: f64,
+    /// Cooler power in percent above which cooling is held
+    pub max_power: f64,
+}
+
+impl CoolerRamp {
+    /// Ramp to `target` at 3 °C per minute, updating every 15 s
+    pub fn new(target: f64) -> Self {
+        Self {
+            target,
+            rate: 3.0,
+            interval: Duration::from_secs(15),
+            tolerance: 0.5,
+            max_lag: 2.0,
+            max_power: 95.0,
+        }
+    }
+
+    /// Sets the maximum setpoint change in °C per minute
+    pub fn with_rate(mut self, rate: f64) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Sets the time between setpoint updates
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Setpoint to use after `setpoint` given the measured values
+    ///
+    /// Returns the new setpoint and whether the ramp is being held.
+    pub fn next_setpoint(
+        &self,
+        setpoint: f64,
+        temperature: f64,
+        power: Option<f64>,
+    ) -> (f64, bool) {
+        let cooling = self.target < setpoint;
+        let holding = (temperature - setpoint).abs() > self.max_lag
+            || (cooling && power.is_some_and(|power| power >= self.max_power));
+        if holding {
+            return (setpoint, true);
+        }
+        let step = self.rate * self.interval.as_secs_f64() / 60.0;
+        let next = if cooling {
+            (setpoint - step).max(self.target)
+        } else {
+            (setpoint + step).min(self.target)
+        };
+        (next, false)
+    }
+
+    /// Returns true once the setpoint and sensor have reached the target
+    fn finished(&self, setpoint: f64, temperature: f64) -> bool {
+        setpoint == self.target && (temperature - self.target).abs() <= self.tolerance
+    }
+}
+
+/// Progress of a running ramp
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RampStatus {
+    /// Setpoint last sent to the camera in °C
+    pub setpoint: f64,
+    /// Measured sensor temperature in °C
+    pub temperature: f64,
+    /// Cooler power in percent, if the camera reports it
+    pub power: Option<f64>,
+    /// True while the setpoint is held for the sensor or cooler to catch up
+    pub holding: bool,
+}
+
+/// Handle of a ramp started with [`Camera::ramp_temperature`](super::Camera::ramp_temperature)
+#[derive(Debug)]
+pub struct RampHandle {
+    status: watch::Receiver<RampStatus>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl RampHandle {
+    /// Latest progress of the ramp
+    pub fn status(&self) -> RampStatus {
+        *self.status.borrow()
+    }
+
+    /// Stop the ramp, leaving the camera at the last setpoint
+    ///
+    /// Returns the last progress. Starting a new ramp resumes from the
+    /// measured temperature.
+    pub fn cancel(self) -> RampStatus {
+        self.task.abort();
+        *self.status.borrow()
+    }
+
+    /// Wait until the target has been reached
+    pub async fn wait(self) -> Result<()> {
+        self.task
+            .await
+            .map_err(|e| Error::Message(format!("Cooler ramp failed: {}", e)))?
+    }
+}
+
+/// Start ramping the sensor of `device` according to `ramp`
+pub(crate) async fn start(client: Client, device: String, ramp: CoolerRamp) -> Result<RampHandle> {
+    let temperature =
+        number_value(&client, &device, CCD_TEMPERATURE, CCD_TEMPERATURE_VALUE).await?;
+    let (status, receiver) = watch::channel(RampStatus {
+        setpoint: temperature,
+        temperature,
+        power: number_value(&client, &device, CCD_COOLER_POWER, CCD_COOLER_VALUE)
+            .await
+            .ok(),
+        holding: false,
+    });
+
+    let task = tokio::spawn(async move {
+        let mut setpoint = temperature;
+        let mut ticker = tokio::time::interval(ramp.interval);
+        loop {
+            ticker.tick().await;
+            let temperature =
+                number_value(&client, &device, CCD_TEMPERATURE, CCD_TEMPERATURE_VALUE).await?;
+            let power = number_value(&client, &device, CCD_COOLER_POWER, CCD_COOLER_VALUE)
+                .await
+                .ok();
+            if ramp.finished(setpoint, temperature) {
+                debug!("{} reached {:.1} °C", device, ramp.target);
+                return Ok(());
+            }
+
+            let (next, holding) = ramp.next_setpoint(setpoint, temperature, power);
+            if next != setpoint {
+                client
+                    .set_number(&device, CCD_TEMPERATURE, &[(CCD_TEMPERATURE_VALUE, next)])
+                    .await?;
+                debug!("Set {} cooler setpoint to {:.1} °C", device, next);
+                setpoint = next;
+            }
+            status.send_replace(RampStatus {
+                setpoint,
+                temperature,
+                power,
+                holding,
+            });
+        }
+    });
+    Ok(RampHandle {
+        status: receiver,
+        task,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::{mock_server, wait_for_property};
+    use crate::devices::Camera;
+
+    #[test]
+    fn test_next_setpoint() {
+        let ramp = CoolerRamp::new(-10.0).with_interval(Duration::from_secs(60));
+        assert_eq!(ramp.next_setpoint(20.0, 20.0, Some(10.0)), (17.0, false));
+        assert_eq!(ramp.next_setpoint(-8.0, -8.5, None), (-10.0, false));
+        // Sensor lagging behind the setpoint
+        assert_eq!(ramp.next_setpoint(10.0, 14.0, Some(50.0)), (10.0, true));
+        // Cooler saturated
+        assert_eq!(ramp.next_setpoint(0.0, 0.0, Some(100.0)), (0.0, true));
+
+        // Saturation does not hold a warm-up
+        let ramp = CoolerRamp::new(20.0).with_interval(Duration::from_secs(60));
+        assert_eq!(ramp.next_setpoint(0.0, 0.0, Some(100.0)), (3.0, false));
+        assert!(ramp.finished(20.0, 19.7));
+        assert!(!ramp.finished(19.0, 19.0));
+    }
+
+    #[tokio::test]
+    async fn test_ramp_temperature() {
+        let config = mock_server(
+            r#"<defNumberVector device="CCD" name="CCD_TEMPERATURE" state="Idle" perm="rw">
+<defNumber name="CCD_TEMPERATURE_VALUE" format="%5.2f" min="-50" max="50" step="0">20</defNumber>
+</defNumberVector>
+"#,
+            "newNumberVector",
+            r#"<setNumberVector device="CCD" name="CCD_TEMPERATURE" state="Busy"><oneNumber name="CCD_TEMPERATURE_VALUE">10</oneNumber></setNumberVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        wait_for_property(&client, "CCD", CCD_TEMPERATURE).await;
+        let camera = Camera::new(client, "CCD");
+
+        let mut ramp = CoolerRamp::new(10.0)
+            .with_rate(30_000.0)
+            .with_interval(Duration::from_millis(20));
+        ramp.max_lag = 100.0;
+        let handle = camera.ramp_temperature(ramp).await.unwrap();
+        assert_eq!(handle.status().temperature, 20.0);
+        tokio::time::timeout(Duration::from_secs(5), handle.wait())
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Cancelling leaves the last setpoint in place
+        let handle = camera
+            .ramp_temperature(CoolerRamp::new(-20.0))
+            .await
+            .unwrap();
+        assert_eq!(handle.cancel().setpoint, 10.0);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::client::Client;
+use crate::error::{Error, Result};
+use crate::property::{Property, PropertyState, PropertyValue, SwitchState};
+
+/// Generic device wrapper with typed access to property elements
+///
+/// Reads come from the client state, so they reflect the last definition
+/// or update received. A property of another type than requested fails
+/// with [`Error::WrongPropertyType`].
+#[derive(Debug, Clone)]
+pub struct Device {
+    client: Client,
+    device: String,
+}
+
+impl Device {
+    /// Create a new wrapper for `device`
+    pub fn new(client: Client, device: impl Into<String>) -> Self {
+        Self {
+            client,
+            device: device.into(),
+        }
+    }
+
+    /// Device name
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// Current value of a number element
+    pub async fn number(&self, name: &str, element: &str) -> Result<f64> {
+        self.element(name, element, "number vector", |property| {
+            match &property.value {
+                PropertyValue::NumberVector(_) => Some(property.value.number(element)),
+                _ => None,
+            }
+        })
+        .await
+    }
+
+    /// Current value of a text element
+    pub async fn text(&self, name: &str, element: &str) -> Result<String> {
+        self.element(name, element, "text vector", |property| {
+            match &property.value {
+                PropertyValue::TextVector(_) => {
+                    Some(property.value.text(element).map(str::to_string))
+                }
+                _ => None,
+            }
+        })
+        .await
+    }
+
+    /// Current state of a switch element
+    pub async fn switch(&self, name: &str, element: &str) -> Result<SwitchState> {
+        self.element(name, element, "switch vector", |property| {
+            match &property.value {
+                PropertyValue::SwitchVector(..) => Some(property.value.switch(element)),
+                _ => None,
+            }
+        })
+        .await
+    }
+
+    /// Current state of a light element
+    pub async fn light(&self, name: &str, element: &str) -> Result<PropertyState> {
+        self.element(name, element, "light vector", |property| {
+            match &property.value {
+                PropertyValue::Light(_) => Some(
+                    property
+                        .elements
+                        .iter()
+                        .flatten()
+                        .find(|light| light.name == element)
+                        .map(|light| light.state),
+                ),
+                _ => None,
+            }
+        })
+        .await
+    }
+
+    /// Look up `name` and extract `element` with `extract`
+    ///
+    /// `extract` returns `None` if the property has the wrong type and
+    /// `Some(None)` if it lacks the element.
+    async fn element<T>(
+        &self,
+        name: &str,
+        element: &str,
+        expected: &'static str,
+        extract: impl FnOnce(&Property) -> Option<Option<T>>,
+    ) -> Result<T> {
+        let state = self.client.state();
+        let state = state.lock().await;
+        let property = state
+            .get_property(&self.device, name)
+            .ok_or_else(|| Error::Property(format!("{}.{} is not defined", self.device, name)))?;
+        match extract(property) {
+            Some(Some(value)) => Ok(value),
+            Some(None) => Err(Error::Property(format!(
+                "{}.{} has no element {}",
+                self.device, name, element
+            ))),
+            None => Err(Error::WrongPropertyType {
+                device: self.device.clone(),
+                name: name.to_string(),
+                expected,
+                found: type_name(&property.value),
+            }),
+        }
+    }
+}
+
+/// Human readable type of a stored property value
+fn type_name(value: &PropertyValue) -> &'static str {
+    match value {
+        PropertyValue::Text(_) => "text",
+        PropertyValue::Number(..) => "number",
+        PropertyValue::Switch(_) => "switch",
+        PropertyValue::Light(_) => "light vector",
+        PropertyValue::Blob(_) => "BLOB",
+        PropertyValue::BlobVector(_) => "BLOB vector",
+        PropertyValue::SwitchVector(..) => "switch vector",
+        PropertyValue::TextVector(_) => "text vector",
+        PropertyValue::NumberVector(_) => "number vector",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::{mock_server, wait_for_property};
+
+    #[tokio::test]
+    async fn test_typed_getters() {
+        let config = mock_server(
+            r#"<defNumberVector device="CCD" name="CCD_TEMPERATURE" state="Idle" perm="rw"><defNumber name="CCD_TEMPERATURE_VALUE" format="%5.2f" min="-50" max="50" step="0">-10.5</defNumber></defNumberVector>
+<defTextVector device="CCD" name="DRIVER_INFO" state="Idle" perm="ro"><defText name="DRIVER_NAME">CCD Simulator</defText></defTextVector>
+<defSwitchVector device="CCD" name="CONNECTION" state="Ok" perm="rw" rule="OneOfMany"><defSwitch name="CONNECT">On</defSwitch><defSwitch name="DISCONNECT">Off</defSwitch></defSwitchVector>
+<defLightVector device="CCD" name="STATUS" state="Idle"><defLight name="COOLER">Idle</defLight></defLightVector>
+<setLightVector device="CCD" name="STATUS" state="Alert"><oneLight name="COOLER">Alert</oneLight></setLightVector>
+"#,
+            "",
+            "",
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        wait_for_property(&client, "CCD", "STATUS").await;
+        let device = Device::new(client, "CCD");
+
+        assert_eq!(
+            device
+                .number("CCD_TEMPERATURE", "CCD_TEMPERATURE_VALUE")
+                .await
+                .unwrap(),
+            -10.5
+        );
+        assert_eq!(
+            device.text("DRIVER_INFO", "DRIVER_NAME").await.unwrap(),
+            "CCD Simulator"
+        );
+        assert_eq!(
+            device.switch("CONNECTION", "CONNECT").await.unwrap(),
+            SwitchState::On
+        );
+        // Wait for the light update queued behind the definition
+        for _ in 0..100 {
+            if device.light("STATUS", "COOLER").await.unwrap() == PropertyState::Alert {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            device.light("STATUS", "COOLER").await.unwrap(),
+            PropertyState::Alert
+        );
+
+        let error = device.number("CONNECTION", "CONNECT").await.unwrap_err();
+        assert!(matches!(
+            error,
+            Error::WrongPropertyType {
+                expected: "number vector",
+                found: "switch vector",
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "CCD.CONNECTION is a switch vector, not a number vector"
+        );
+        assert!(matches!(
+            device.text("DRIVER_INFO", "MISSING").await,
+            Err(Error::Property(_))
+        ));
+        assert!(matches!(
+            device.switch("UNDEFINED", "X").await,
+            Err(Error::Property(_))
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+use crate::client::{wait_for_ok, Client};
+use crate::devices::{number_value, switch_value};
+use crate::error::Result;
+use crate::property::SwitchState;
+use std::time::Duration;
+use tracing::debug;
+
+/// Motion direction property of a dome
+const DOME_MOTION: &str = "DOME_MOTION";
+/// Clockwise element of [`DOME_MOTION`]
+const DOME_CW: &str = "DOME_CW";
+/// Counter-clockwise element of [`DOME_MOTION`]
+const DOME_CCW: &str = "DOME_CCW";
+/// Absolute azimuth property of a dome
+const DOME_ABS_POSITION: &str = "DOME_ABS_POSITION";
+/// Azimuth element of [`DOME_ABS_POSITION`]
+const DOME_ABSOLUTE_POSITION: &str = "DOME_ABSOLUTE_POSITION";
+/// Shutter property of a dome
+const DOME_SHUTTER: &str = "DOME_SHUTTER";
+/// Open element of [`DOME_SHUTTER`]
+const SHUTTER_OPEN: &str = "SHUTTER_OPEN";
+/// Close element of [`DOME_SHUTTER`]
+const SHUTTER_CLOSE: &str = "SHUTTER_CLOSE";
+/// Park property of a dome
+const DOME_PARK: &str = "DOME_PARK";
+/// Park element of [`DOME_PARK`]
+const PARK: &str = "PARK";
+/// Unpark element of [`DOME_PARK`]
+const UNPARK: &str = "UNPARK";
+/// Abort property of a dome
+const DOME_ABORT_MOTION: &str = "DOME_ABORT_MOTION";
+/// Abort element of [`DOME_ABORT_MOTION`]
+const ABORT: &str = "ABORT";
+
+/// Direction of continuous dome rotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomeDirection {
+    /// Clockwise rotation
+    Clockwise,
+    /// Counter-clockwise rotation
+    CounterClockwise,
+}
+
+/// Dome device wrapper
+///
+/// Covers rotation, shutter control and parking. Slews, shutter and park
+/// operations are awaited until the driver reports the property as `Ok`.
+#[derive(Debug, Clone)]
+pub struct Dome {
+    client: Client,
+    device: String,
+    timeout: Duration,
+}
+
+impl Dome {
+    /// Default time allowed for a dome operation to complete
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+    /// Create a new dome wrapper for `device`
+    pub fn new(client: Client, device: impl Into<String>) -> Self {
+        Self {
+            client,
+            device: device.into(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets the time allowed for a dome operation to complete
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Device name
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// Current azimuth in degrees
+    pub async fn azimuth(&self) -> Result<f64> {
+        number_value(
+            &self.client,
+            &self.device,
+            DOME_ABS_POSITION,
+            DOME_ABSOLUTE_POSITION,
+        )
+        .await
+    }
+
+    /// Returns true if the shutter is reported open
+    pub async fn is_shutter_open(&self) -> Result<bool> {
+        let open = switch_value(&self.client, &self.device, DOME_SHUTTER, SHUTTER_OPEN).await?;
+        Ok(open == SwitchState::On)
+    }
+
+    /// Returns true if the dome is parked
+    pub async fn is_parked(&self) -> Result<bool> {
+        let parked = switch_value(&self.client, &self.device, DOME_PARK, PARK).await?;
+        Ok(parked == SwitchState::On)
+    }
+
+    /// Rotate to `azimuth` degrees and wait for the slew to complete
+    pub async fn slew_to(&self, azimuth: f64) -> Result<()> {
+        let mut events = self.client.subscribe();
+        self.client
+            .set_number(
+                &self.device,
+                DOME_ABS_POSITION,
+                &[(DOME_ABSOLUTE_POSITION, azimuth)],
+            )
+            .await?;
+        debug!("Slewing {} to {}", self.device, azimuth);
+        wait_for_ok(&mut events, &self.device, DOME_ABS_POSITION, self.timeout).await
+    }
+
+    /// Start continuous rotation in `direction`
+    ///
+    /// Returns as soon as the command is sent; use [`Dome::abort`] to stop.
+    pub async fn start_motion(&self, direction: DomeDirection) -> Result<()> {
+        let (cw, ccw) = match direction {
+            DomeDirection::Clockwise => (SwitchState::On, SwitchState::Off),
+            DomeDirection::CounterClockwise => (SwitchState::Off, SwitchState::On),
+        };
+        self.client
+            .set_switch(&self.device, DOME_MOTION, &[(DOME_CW, cw), (DOME_CCW, ccw)])
+            .await
+    }
+
+    /// Abort any motion in progress
+    pub async fn abort(&self) -> Result<()> {
+        self.set_and_wait(DOME_ABORT_MOTION, &[(ABORT, SwitchState::On)])
+            .await
+    }
+
+    /// Open the shutter and wait until it is open
+    pub async fn open_shutter(&self) -> Result<()> {
+        self.set_and_wait(
+            DOME_SHUTTER,
+            &[
+                (SHUTTER_OPEN, SwitchState::On),
+                (SHUTTER_CLOSE, SwitchState::Off),
+            ],
+        )
+        .await
+    }
+
+    /// Close the shutter and wait until it is closed
+    pub async fn close_shutter(&self) -> Result<()> {
+        self.set_and_wait(
+            DOME_SHUTTER,
+            &[
+                (SHUTTER_OPEN, SwitchState::Off),
+                (SHUTTER_CLOSE, SwitchState::On),
+            ],
+        )
+        .await
+    }
+
+    /// Park the dome and wait until it is parked
+    pub async fn park(&self) -> Result<()> {
+        self.set_and_wait(
+            DOME_PARK,
+            &[(PARK, SwitchState::On), (UNPARK, SwitchState::Off)],
+        )
+        .await
+    }
+
+    /// Unpark the dome and wait until it is released
+    pub async fn unpark(&self) -> Result<()> {
+        self.set_and_wait(
+            DOME_PARK,
+            &[(PARK, SwitchState::Off), (UNPARK, SwitchState::On)],
+        )
+        .await
+    }
+
+    /// Send a switch vector and wait for the driver to report `Ok`
+    async fn set_and_wait(&self, name: &str, values: &[(&str, SwitchState)]) -> Result<()> {
+        let mut events = self.client.subscribe();
+        self.client.set_switch(&self.device, name, values).await?;
+        debug!("Updated {}.{}", self.device, name);
+        wait_for_ok(&mut events, &self.device, name, self.timeout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::{mock_server, wait_for_property};
+
+    const GREETING: &str = r#"<defSwitchVector device="Dome Simulator" name="DOME_SHUTTER" state="Idle" perm="rw" rule="OneOfMany">
+<defSwitch name="SHUTTER_OPEN">Off</defSwitch>
+<defSwitch name="SHUTTER_CLOSE">On</defSwitch>
+</defSwitchVector>
+<defNumberVector device="Dome Simulator" name="DOME_ABS_POSITION" state="Idle" perm="rw">
+<defNumber name="DOME_ABSOLUTE_POSITION" format="%5.1f" min="0" max="360" step="1">90</defNumber>
+</defNumberVector>
+"#;
+
+    #[tokio::test]
+    async fn test_open_shutter() {
+        let config = mock_server(
+            GREETING,
+            "DOME_SHUTTER",
+            r#"<setSwitchVector device="Dome Simulator" name="DOME_SHUTTER" state="Busy"/>
+<setSwitchVector device="Dome Simulator" name="DOME_SHUTTER" state="Ok">
+<oneSwitch name="SHUTTER_OPEN">On</oneSwitch>
+<oneSwitch name="SHUTTER_CLOSE">Off</oneSwitch>
+</setSwitchVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        wait_for_property(&client, "Dome Simulator", DOME_ABS_POSITION).await;
+        let dome = Dome::new(client, "Dome Simulator");
+
+        assert_eq!(dome.azimuth().await.unwrap(), 90.0);
+        assert!(!dome.is_shutter_open().await.unwrap());
+
+        dome.open_shutter().await.unwrap();
+        assert!(dome.is_shutter_open().await.unwrap());
+    }
+}
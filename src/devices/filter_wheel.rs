@@ -0,0 +1,172 @@
+use crate::client::{wait_for_ok, Client};
+use crate::devices::number_value;
+use crate::error::{Error, Result};
+use crate::property::PropertyValue;
+use std::time::Duration;
+use tracing::debug;
+
+/// Filter names property of a filter wheel
+const FILTER_NAME: &str = "FILTER_NAME";
+/// Prefix of the per-slot elements of [`FILTER_NAME`]
+const FILTER_SLOT_NAME_PREFIX: &str = "FILTER_SLOT_NAME_";
+/// Current slot property of a filter wheel
+const FILTER_SLOT: &str = "FILTER_SLOT";
+/// Slot number element of [`FILTER_SLOT`]
+const FILTER_SLOT_VALUE: &str = "FILTER_SLOT_VALUE";
+
+/// Filter wheel device wrapper
+///
+/// Resolves filter names from `FILTER_NAME` to the 1-based slot numbers
+/// used by `FILTER_SLOT`.
+#[derive(Debug, Clone)]
+pub struct FilterWheel {
+    client: Client,
+    device: String,
+    timeout: Duration,
+}
+
+impl FilterWheel {
+    /// Default time allowed for the wheel to reach a new slot
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// Create a new filter wheel wrapper for `device`
+    pub fn new(client: Client, device: impl Into<String>) -> Self {
+        Self {
+            client,
+            device: device.into(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets the time allowed for the wheel to reach a new slot
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Device name
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// Filter names ordered by slot, starting with slot 1
+    pub async fn filters(&self) -> Result<Vec<String>> {
+        let state = self.client.state();
+        let state = state.lock().await;
+        let property = state
+            .get_property(&self.device, FILTER_NAME)
+            .ok_or_else(|| self.undefined(FILTER_NAME))?;
+        let PropertyValue::TextVector(names) = &property.value else {
+            return Err(self.wrong_type(FILTER_NAME));
+        };
+
+        let mut slots = names
+            .iter()
+            .filter_map(|text| {
+                text.name
+                    .strip_prefix(FILTER_SLOT_NAME_PREFIX)
+                    .and_then(|slot| slot.parse::<u32>().ok())
+                    .map(|slot| (slot, text.value.clone()))
+            })
+            .collect::<Vec<_>>();
+        slots.sort_by_key(|(slot, _)| *slot);
+        Ok(slots.into_iter().map(|(_, name)| name).collect())
+    }
+
+    /// Current slot number
+    pub async fn current_slot(&self) -> Result<u32> {
+        let slot = number_value(&self.client, &self.device, FILTER_SLOT, FILTER_SLOT_VALUE).await?;
+        Ok(slot.round() as u32)
+    }
+
+    /// Name of the filter in the current slot
+    pub async fn current_filter(&self) -> Result<String> {
+        let slot = self.current_slot().await?;
+        let filters = self.filters().await?;
+        filters
+            .get((slot as usize).wrapping_sub(1))
+            .cloned()
+            .ok_or_else(|| Error::Property(format!("No filter name for slot {}", slot)))
+    }
+
+    /// Move to `slot` and wait for the wheel to settle
+    pub async fn set_slot(&self, slot: u32) -> Result<()> {
+        let mut events = self.client.subscribe();
+        self.client
+            .set_number(
+                &self.device,
+                FILTER_SLOT,
+                &[(FILTER_SLOT_VALUE, slot as f64)],
+            )
+            .await?;
+        debug!("Moving {} to slot {}", self.device, slot);
+        wait_for_ok(&mut events, &self.device, FILTER_SLOT, self.timeout).await
+    }
+
+    /// Move to the slot holding the filter called `name` and wait for the
+    /// wheel to settle
+    ///
+    /// Names are matched case-insensitively.
+    pub async fn set_filter(&self, name: &str) -> Result<()> {
+        let filters = self.filters().await?;
+        let slot = filters
+            .iter()
+            .position(|filter| filter.trim().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| {
+                Error::Property(format!("Unknown filter '{}' on {}", name, self.device))
+            })?;
+        self.set_slot(slot as u32 + 1).await
+    }
+
+    fn undefined(&self, name: &str) -> Error {
+        Error::Property(format!("{}.{} is not defined", self.device, name))
+    }
+
+    fn wrong_type(&self, name: &str) -> Error {
+        Error::Property(format!("{}.{} has an unexpected type", self.device, name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::{mock_server, wait_for_property};
+
+    const GREETING: &str = r#"<defTextVector device="Filter Simulator" name="FILTER_NAME" label="Filter" group="Filter Wheel" state="Idle" perm="rw">
+<defText name="FILTER_SLOT_NAME_2" label="Filter #2">Ha</defText>
+<defText name="FILTER_SLOT_NAME_1" label="Filter #1">Luminance</defText>
+<defText name="FILTER_SLOT_NAME_3" label="Filter #3">OIII</defText>
+</defTextVector>
+<defNumberVector device="Filter Simulator" name="FILTER_SLOT" label="Filter Slot" group="Filter Wheel" state="Idle" perm="rw">
+<defNumber name="FILTER_SLOT_VALUE" label="Filter" format="%3.0f" min="1" max="3" step="1">1</defNumber>
+</defNumberVector>
+"#;
+
+    #[tokio::test]
+    async fn test_set_filter() {
+        let config = mock_server(
+            GREETING,
+            "newNumberVector",
+            r#"<setNumberVector device="Filter Simulator" name="FILTER_SLOT" state="Busy">
+<oneNumber name="FILTER_SLOT_VALUE">1</oneNumber>
+</setNumberVector>
+<setNumberVector device="Filter Simulator" name="FILTER_SLOT" state="Ok">
+<oneNumber name="FILTER_SLOT_VALUE">2</oneNumber>
+</setNumberVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        wait_for_property(&client, "Filter Simulator", FILTER_SLOT).await;
+        let wheel = FilterWheel::new(client, "Filter Simulator");
+
+        assert_eq!(wheel.filters().await.unwrap(), ["Luminance", "Ha", "OIII"]);
+        assert_eq!(wheel.current_filter().await.unwrap(), "Luminance");
+
+        wheel.set_filter("ha").await.unwrap();
+        assert_eq!(wheel.current_slot().await.unwrap(), 2);
+        assert_eq!(wheel.current_filter().await.unwrap(), "Ha");
+
+        assert!(wheel.set_filter("SII").await.is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::client::{wait_for_ok, Client};
+use crate::devices::number_value;
+use crate::error::Result;
+use crate::property::SwitchState;
+use std::time::Duration;
+use tracing::debug;
+
+/// Light on/off property of a light box
+const FLAT_LIGHT_CONTROL: &str = "FLAT_LIGHT_CONTROL";
+/// On element of [`FLAT_LIGHT_CONTROL`]
+const FLAT_LIGHT_ON: &str = "FLAT_LIGHT_ON";
+/// Off element of [`FLAT_LIGHT_CONTROL`]
+const FLAT_LIGHT_OFF: &str = "FLAT_LIGHT_OFF";
+/// Brightness property of a light box
+const FLAT_LIGHT_INTENSITY: &str = "FLAT_LIGHT_INTENSITY";
+/// Brightness element of [`FLAT_LIGHT_INTENSITY`]
+const FLAT_LIGHT_INTENSITY_VALUE: &str = "FLAT_LIGHT_INTENSITY_VALUE";
+/// Cover property of a dust cap
+const CAP_PARK: &str = "CAP_PARK";
+/// Closed element of [`CAP_PARK`]
+const PARK: &str = "PARK";
+/// Open element of [`CAP_PARK`]
+const UNPARK: &str = "UNPARK";
+
+/// Flat panel wrapper
+///
+/// Covers the light box and, for combined devices, the dust cover. Cover
+/// moves are awaited until the driver reports `CAP_PARK` as `Ok`.
+#[derive(Debug, Clone)]
+pub struct FlatPanel {
+    client: Client,
+    device: String,
+    timeout: Duration,
+}
+
+impl FlatPanel {
+    /// Default time allowed for the cover or light to settle
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// Create a new flat panel wrapper for `device`
+    pub fn new(client: Client, device: impl Into<String>) -> Self {
+        Self {
+            client,
+            device: device.into(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets the time allowed for the cover or light to settle
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Device name
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// Current brightness
+    pub async fn brightness(&self) -> Result<f64> {
+        number_value(
+            &self.client,
+            &self.device,
+            FLAT_LIGHT_INTENSITY,
+            FLAT_LIGHT_INTENSITY_VALUE,
+        )
+        .await
+    }
+
+    /// Set the brightness and wait for the panel to settle
+    pub async fn set_brightness(&self, brightness: f64) -> Result<()> {
+        let mut events = self.client.subscribe();
+        self.client
+            .set_number(
+                &self.device,
+                FLAT_LIGHT_INTENSITY,
+                &[(FLAT_LIGHT_INTENSITY_VALUE, brightness)],
+            )
+            .await?;
+        debug!("Set {} brightness to {}", self.device, brightness);
+        wait_for_ok(
+            &mut events,
+            &self.device,
+            FLAT_LIGHT_INTENSITY,
+            self.timeout,
+        )
+        .await
+    }
+
+    /// Switch the light on or off
+    pub async fn set_light(&self, on: bool) -> Result<()> {
+        let (on, off) = if on {
+            (SwitchState::On, SwitchState::Off)
+        } else {
+            (SwitchState::Off, SwitchState::On)
+        };
+        self.set_and_wait(
+            FLAT_LIGHT_CONTROL,
+            &[(FLAT_LIGHT_ON, on), (FLAT_LIGHT_OFF, off)],
+        )
+        .await
+    }
+
+    /// Close the dust cover and wait until it is closed
+    pub async fn close_cover(&self) -> Result<()> {
+        self.set_and_wait(
+            CAP_PARK,
+            &[(PARK, SwitchState::On), (UNPARK, SwitchState::Off)],
+        )
+        .await
+    }
+
+    /// Open the dust cover and wait until it is open
+    pub async fn open_cover(&self) -> Result<()> {
+        self.set_and_wait(
+            CAP_PARK,
+            &[(PARK, SwitchState::Off), (UNPARK, SwitchState::On)],
+        )
+        .await
+    }
+
+    /// Send a switch vector and wait for the driver to report `Ok`
+    async fn set_and_wait(&self, name: &str, values: &[(&str, SwitchState)]) -> Result<()> {
+        let mut events = self.client.subscribe();
+        self.client.set_switch(&self.device, name, values).await?;
+        debug!("Updated {}.{}", self.device, name);
+        wait_for_ok(&mut events, &self.device, name, self.timeout).await
+    }
+}
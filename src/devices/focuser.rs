@@ -0,0 +1,194 @@
+use crate::client::{wait_for_ok, Client};
+use crate::devices::number_value;
+use crate::error::Result;
+use crate::property::SwitchState;
+use std::time::Duration;
+use tracing::debug;
+
+/// Absolute position property of a focuser
+const ABS_FOCUS_POSITION: &str = "ABS_FOCUS_POSITION";
+/// Position element of [`ABS_FOCUS_POSITION`]
+const FOCUS_ABSOLUTE_POSITION: &str = "FOCUS_ABSOLUTE_POSITION";
+/// Relative move property of a focuser
+const REL_FOCUS_POSITION: &str = "REL_FOCUS_POSITION";
+/// Step count element of [`REL_FOCUS_POSITION`]
+const FOCUS_RELATIVE_POSITION: &str = "FOCUS_RELATIVE_POSITION";
+/// Direction property used by relative moves
+const FOCUS_MOTION: &str = "FOCUS_MOTION";
+/// Inward element of [`FOCUS_MOTION`]
+const FOCUS_INWARD: &str = "FOCUS_INWARD";
+/// Outward element of [`FOCUS_MOTION`]
+const FOCUS_OUTWARD: &str = "FOCUS_OUTWARD";
+/// Abort property of a focuser
+const FOCUS_ABORT_MOTION: &str = "FOCUS_ABORT_MOTION";
+/// Abort element of [`FOCUS_ABORT_MOTION`]
+const ABORT: &str = "ABORT";
+/// Temperature property of a focuser
+const FOCUS_TEMPERATURE: &str = "FOCUS_TEMPERATURE";
+/// Temperature element of [`FOCUS_TEMPERATURE`]
+const TEMPERATURE: &str = "TEMPERATURE";
+
+/// Focuser device wrapper
+///
+/// Moves are awaited until the driver reports the position property as
+/// `Ok`, so a returned move means the focuser has stopped.
+#[derive(Debug, Clone)]
+pub struct Focuser {
+    client: Client,
+    device: String,
+    timeout: Duration,
+}
+
+impl Focuser {
+    /// Default time allowed for a move to complete
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+    /// Create a new focuser wrapper for `device`
+    pub fn new(client: Client, device: impl Into<String>) -> Self {
+        Self {
+            client,
+            device: device.into(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets the time allowed for a move to complete
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Device name
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// Current absolute position in steps
+    pub async fn position(&self) -> Result<u32> {
+        let position = number_value(
+            &self.client,
+            &self.device,
+            ABS_FOCUS_POSITION,
+            FOCUS_ABSOLUTE_POSITION,
+        )
+        .await?;
+        Ok(position.round() as u32)
+    }
+
+    /// Current focuser temperature in degrees Celsius
+    pub async fn temperature(&self) -> Result<f64> {
+        number_value(&self.client, &self.device, FOCUS_TEMPERATURE, TEMPERATURE).await
+    }
+
+    /// Move to an absolute position and wait for the move to complete
+    pub async fn move_abs(&self, steps: u32) -> Result<()> {
+        let mut events = self.client.subscribe();
+        self.client
+            .set_number(
+                &self.device,
+                ABS_FOCUS_POSITION,
+                &[(FOCUS_ABSOLUTE_POSITION, steps as f64)],
+            )
+            .await?;
+        debug!("Moving {} to {}", self.device, steps);
+        wait_for_ok(&mut events, &self.device, ABS_FOCUS_POSITION, self.timeout).await
+    }
+
+    /// Move by `delta` steps and wait for the move to complete
+    ///
+    /// Negative values move inward, positive values outward.
+    pub async fn move_rel(&self, delta: i32) -> Result<()> {
+        let mut events = self.client.subscribe();
+        let (inward, outward) = if delta < 0 {
+            (SwitchState::On, SwitchState::Off)
+        } else {
+            (SwitchState::Off, SwitchState::On)
+        };
+        self.client
+            .set_switch(
+                &self.device,
+                FOCUS_MOTION,
+                &[(FOCUS_INWARD, inward), (FOCUS_OUTWARD, outward)],
+            )
+            .await?;
+        self.client
+            .set_number(
+                &self.device,
+                REL_FOCUS_POSITION,
+                &[(FOCUS_RELATIVE_POSITION, delta.unsigned_abs() as f64)],
+            )
+            .await?;
+        debug!("Moving {} by {}", self.device, delta);
+        wait_for_ok(&mut events, &self.device, REL_FOCUS_POSITION, self.timeout).await
+    }
+
+    /// Abort any motion in progress
+    pub async fn abort(&self) -> Result<()> {
+        let mut events = self.client.subscribe();
+        self.client
+            .set_switch(
+                &self.device,
+                FOCUS_ABORT_MOTION,
+                &[(ABORT, SwitchState::On)],
+            )
+            .await?;
+        debug!("Aborting motion of {}", self.device);
+        wait_for_ok(&mut events, &self.device, FOCUS_ABORT_MOTION, self.timeout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::{mock_server, wait_for_property};
+
+    const GREETING: &str = r#"<defNumberVector device="Focuser Simulator" name="ABS_FOCUS_POSITION" state="Idle" perm="rw">
+<defNumber name="FOCUS_ABSOLUTE_POSITION" format="%6.0f" min="0" max="100000" step="1">50000</defNumber>
+</defNumberVector>
+<defNumberVector device="Focuser Simulator" name="FOCUS_TEMPERATURE" state="Idle" perm="ro">
+<defNumber name="TEMPERATURE" format="%6.2f" min="-50" max="70" step="0">12.5</defNumber>
+</defNumberVector>
+"#;
+
+    #[tokio::test]
+    async fn test_move_abs() {
+        let config = mock_server(
+            GREETING,
+            "newNumberVector",
+            r#"<setNumberVector device="Focuser Simulator" name="ABS_FOCUS_POSITION" state="Busy">
+<oneNumber name="FOCUS_ABSOLUTE_POSITION">50500</oneNumber>
+</setNumberVector>
+<setNumberVector device="Focuser Simulator" name="ABS_FOCUS_POSITION" state="Ok">
+<oneNumber name="FOCUS_ABSOLUTE_POSITION">51000</oneNumber>
+</setNumberVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        wait_for_property(&client, "Focuser Simulator", FOCUS_TEMPERATURE).await;
+        let focuser = Focuser::new(client, "Focuser Simulator");
+
+        assert_eq!(focuser.position().await.unwrap(), 50000);
+        assert_eq!(focuser.temperature().await.unwrap(), 12.5);
+
+        focuser.move_abs(51000).await.unwrap();
+        assert_eq!(focuser.position().await.unwrap(), 51000);
+    }
+
+    #[tokio::test]
+    async fn test_move_rel_alert() {
+        let config = mock_server(
+            GREETING,
+            "REL_FOCUS_POSITION",
+            r#"<setNumberVector device="Focuser Simulator" name="REL_FOCUS_POSITION" state="Alert" message="Limit reached">
+<oneNumber name="FOCUS_RELATIVE_POSITION">0</oneNumber>
+</setNumberVector>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let focuser = Focuser::new(client, "Focuser Simulator");
+
+        assert!(focuser.move_rel(-200).await.is_err());
+    }
+}
@@ -0,0 +1,59 @@
+//! High-level device wrappers
+//!
+//! The wrappers in this module hide the INDI property plumbing behind
+//! task-oriented methods, e.g. taking an exposure and receiving the image
+//! instead of writing `CCD_EXPOSURE` and waiting for a `setBLOBVector`.
+
+use crate::client::Client;
+use crate::error::Result;
+use crate::property::SwitchState;
+
+/// Camera/CCD device wrapper
+mod camera;
+/// Temperature ramps for camera coolers
+mod cooler;
+/// Generic device wrapper with typed getters
+mod device;
+/// Dome device wrapper
+mod dome;
+/// Filter wheel device wrapper
+mod filter_wheel;
+/// Flat panel and dust cover wrapper
+mod flat_panel;
+/// Focuser device wrapper
+mod focuser;
+/// Telescope mount wrapper
+mod telescope;
+
+pub use camera::{Blob, Camera, FrameType};
+pub use cooler::{CoolerRamp, RampHandle, RampStatus};
+pub use device::Device;
+pub use dome::{Dome, DomeDirection};
+pub use filter_wheel::FilterWheel;
+pub use flat_panel::FlatPanel;
+pub use focuser::Focuser;
+pub use telescope::Telescope;
+
+/// Read the current value of a number element from the client state
+pub(crate) async fn number_value(
+    client: &Client,
+    device: &str,
+    name: &str,
+    element: &str,
+) -> Result<f64> {
+    Device::new(client.clone(), device)
+        .number(name, element)
+        .await
+}
+
+/// Read the current state of a switch element from the client state
+pub(crate) async fn switch_value(
+    client: &Client,
+    device: &str,
+    name: &str,
+    element: &str,
+) -> Result<SwitchState> {
+    Device::new(client.clone(), device)
+        .switch(name, element)
+        .await
+}
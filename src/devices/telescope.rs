@@ -0,0 +1,311 @@
+use crate::astro::{ALT, AZ, DEC, EQUATORIAL_EOD_COORD, HORIZONTAL_COORD, RA};
+use crate::client::{wait_for_ok, Client};
+use crate::devices::number_value;
+use crate::error::{Error, Result};
+use crate::property::SwitchState;
+use crate::safety::{LimitAction, SafetyLimits, TrackingAction};
+use crate::validation::Rejection;
+use chrono::Utc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Property selecting what a coordinate update does
+const ON_COORD_SET: &str = "ON_COORD_SET";
+/// Element of [`ON_COORD_SET`] to slew and keep tracking
+const TRACK: &str = "TRACK";
+/// Element of [`ON_COORD_SET`] to slew and stop
+const SLEW: &str = "SLEW";
+/// Element of [`ON_COORD_SET`] to sync
+const SYNC: &str = "SYNC";
+/// Tracking on/off property of a mount
+const TELESCOPE_TRACK_STATE: &str = "TELESCOPE_TRACK_STATE";
+/// Tracking enabled element of [`TELESCOPE_TRACK_STATE`]
+const TRACK_ON: &str = "TRACK_ON";
+/// Tracking disabled element of [`TELESCOPE_TRACK_STATE`]
+const TRACK_OFF: &str = "TRACK_OFF";
+/// Abort property of a mount
+const TELESCOPE_ABORT_MOTION: &str = "TELESCOPE_ABORT_MOTION";
+/// Abort element of [`TELESCOPE_ABORT_MOTION`]
+const ABORT: &str = "ABORT";
+/// Park property of a mount
+const TELESCOPE_PARK: &str = "TELESCOPE_PARK";
+/// Park element of [`TELESCOPE_PARK`]
+const PARK: &str = "PARK";
+/// Unpark element of [`TELESCOPE_PARK`]
+const UNPARK: &str = "UNPARK";
+
+/// Telescope mount wrapper
+///
+/// Slews are awaited until the driver reports the coordinates as `Ok`. With
+/// [`SafetyLimits`] configured, targets below the horizon profile are refused
+/// (or clamped, for horizontal targets) before anything is sent.
+#[derive(Debug, Clone)]
+pub struct Telescope {
+    client: Client,
+    device: String,
+    timeout: Duration,
+    limits: Option<SafetyLimits>,
+}
+
+impl Telescope {
+    /// Default time allowed for a slew or park to complete
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+    /// Create a new telescope wrapper for `device`
+    pub fn new(client: Client, device: impl Into<String>) -> Self {
+        Self {
+            client,
+            device: device.into(),
+            timeout: Self::DEFAULT_TIMEOUT,
+            limits: None,
+        }
+    }
+
+    /// Sets the time allowed for a slew or park to complete
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the altitude limits checked before slews and while tracking
+    pub fn with_safety_limits(mut self, limits: SafetyLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Device name
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// Current right ascension (hours) and declination (degrees) of date
+    pub async fn coordinates(&self) -> Result<(f64, f64)> {
+        let ra = number_value(&self.client, &self.device, EQUATORIAL_EOD_COORD, RA).await?;
+        let dec = number_value(&self.client, &self.device, EQUATORIAL_EOD_COORD, DEC).await?;
+        Ok((ra, dec))
+    }
+
+    /// Slew to `ra` hours / `dec` degrees, track, and wait for the slew to
+    /// complete
+    ///
+    /// Fails with [`Error::Rejected`] if the target is below the horizon
+    /// profile.
+    pub async fn slew_to(&self, ra: f64, dec: f64) -> Result<()> {
+        if let Some(limits) = &self.limits {
+            let clearance = limits.clearance(ra, dec, Utc::now());
+            if clearance < 0.0 {
+                return Err(self.rejected(
+                    EQUATORIAL_EOD_COORD,
+                    format!("Target is {:.1}° below the horizon limit", -clearance),
+                ));
+            }
+        }
+
+        let mut events = self.client.subscribe();
+        self.client
+            .set_switch(
+                &self.device,
+                ON_COORD_SET,
+                &[
+                    (TRACK, SwitchState::On),
+                    (SLEW, SwitchState::Off),
+                    (SYNC, SwitchState::Off),
+                ],
+            )
+            .await?;
+        self.client
+            .set_number(&self.device, EQUATORIAL_EOD_COORD, &[(RA, ra), (DEC, dec)])
+            .await?;
+        debug!("Slewing {} to RA {} Dec {}", self.device, ra, dec);
+        wait_for_ok(
+            &mut events,
+            &self.device,
+            EQUATORIAL_EOD_COORD,
+            self.timeout,
+        )
+        .await
+    }
+
+    /// Slew to `alt`/`az` degrees and wait for the slew to complete
+    ///
+    /// Targets below the horizon profile are refused or raised to the
+    /// minimum altitude depending on [`SafetyLimits::on_slew`].
+    pub async fn slew_to_horizontal(&self, alt: f64, az: f64) -> Result<()> {
+        let mut alt = alt;
+        if let Some(limits) = &self.limits {
+            let min_altitude = limits.horizon.min_altitude(az);
+            if alt < min_altitude {
+                match limits.on_slew {
+                    LimitAction::Refuse => {
+                        return Err(self.rejected(
+                            HORIZONTAL_COORD,
+                            format!("Altitude {:.1}° is below {:.1}°", alt, min_altitude),
+                        ))
+                    }
+                    LimitAction::Clamp => {
+                        debug!("Raising target altitude {} to {}", alt, min_altitude);
+                        alt = min_altitude;
+                    }
+                }
+            }
+        }
+
+        let mut events = self.client.subscribe();
+        self.client
+            .set_number(&self.device, HORIZONTAL_COORD, &[(ALT, alt), (AZ, az)])
+            .await?;
+        debug!("Slewing {} to Alt {} Az {}", self.device, alt, az);
+        wait_for_ok(&mut events, &self.device, HORIZONTAL_COORD, self.timeout).await
+    }
+
+    /// Abort any motion in progress
+    pub async fn abort(&self) -> Result<()> {
+        self.set_and_wait(TELESCOPE_ABORT_MOTION, &[(ABORT, SwitchState::On)])
+            .await
+    }
+
+    /// Turn sidereal tracking on or off
+    pub async fn set_tracking(&self, enabled: bool) -> Result<()> {
+        let (on, off) = if enabled {
+            (SwitchState::On, SwitchState::Off)
+        } else {
+            (SwitchState::Off, SwitchState::On)
+        };
+        self.set_and_wait(TELESCOPE_TRACK_STATE, &[(TRACK_ON, on), (TRACK_OFF, off)])
+            .await
+    }
+
+    /// Park the mount and wait until it is parked
+    pub async fn park(&self) -> Result<()> {
+        self.set_and_wait(
+            TELESCOPE_PARK,
+            &[(PARK, SwitchState::On), (UNPARK, SwitchState::Off)],
+        )
+        .await
+    }
+
+    /// Unpark the mount and wait until it is released
+    pub async fn unpark(&self) -> Result<()> {
+        self.set_and_wait(
+            TELESCOPE_PARK,
+            &[(PARK, SwitchState::Off), (UNPARK, SwitchState::On)],
+        )
+        .await
+    }
+
+    /// Watch the mount position every `interval` and stop or park it when
+    /// it gets within [`SafetyLimits::margin`] of the horizon profile
+    ///
+    /// The returned task ends after acting on the limit, or with an error
+    /// if the position cannot be read or the mount does not respond.
+    pub fn watch_limits(&self, interval: Duration) -> Result<JoinHandle<Result<()>>> {
+        let limits = self.limits.clone().ok_or_else(|| {
+            Error::Property(format!("No safety limits configured for {}", self.device))
+        })?;
+        let telescope = self.clone();
+        Ok(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let (ra, dec) = telescope.coordinates().await?;
+                let clearance = limits.clearance(ra, dec, Utc::now());
+                if clearance >= limits.margin {
+                    continue;
+                }
+
+                warn!(
+                    "{} is {:.1}° from the horizon limit, stopping",
+                    telescope.device, clearance
+                );
+                telescope.abort().await?;
+                return match limits.on_tracking {
+                    TrackingAction::Stop => telescope.set_tracking(false).await,
+                    TrackingAction::Park => telescope.park().await,
+                };
+            }
+        }))
+    }
+
+    /// Send a switch vector and wait for the driver to report `Ok`
+    async fn set_and_wait(&self, name: &str, values: &[(&str, SwitchState)]) -> Result<()> {
+        let mut events = self.client.subscribe();
+        self.client.set_switch(&self.device, name, values).await?;
+        debug!("Updated {}.{}", self.device, name);
+        wait_for_ok(&mut events, &self.device, name, self.timeout).await
+    }
+
+    fn rejected(&self, name: &str, reason: String) -> Error {
+        Error::Rejected(Rejection {
+            device: self.device.clone(),
+            name: name.to_string(),
+            reason,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::{mock_server, wait_for_property};
+    use crate::safety::{HorizonProfile, SiteLocation};
+
+    fn limits() -> SafetyLimits {
+        SafetyLimits::new(SiteLocation::new(50.0, 10.0), HorizonProfile::flat(20.0))
+    }
+
+    /// Definition of the mount pointing `hours` of hour angle west at `dec`
+    fn greeting(hours: f64, dec: f64) -> &'static str {
+        let ra = (limits().site.local_sidereal_time(Utc::now()) - hours).rem_euclid(24.0);
+        Box::leak(
+            format!(
+                r#"<defNumberVector device="Telescope Simulator" name="EQUATORIAL_EOD_COORD" state="Idle" perm="rw">
+<defNumber name="RA" format="%010.6m" min="0" max="24" step="0">{}</defNumber>
+<defNumber name="DEC" format="%010.6m" min="-90" max="90" step="0">{}</defNumber>
+</defNumberVector>
+"#,
+                ra, dec
+            )
+            .into_boxed_str(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_slew_below_horizon_is_refused() {
+        let config = mock_server(
+            greeting(0.0, 0.0),
+            "EQUATORIAL_EOD_COORD",
+            r#"<setNumberVector device="Telescope Simulator" name="EQUATORIAL_EOD_COORD" state="Ok"/>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        let telescope = Telescope::new(client, "Telescope Simulator").with_safety_limits(limits());
+
+        let lst = limits().site.local_sidereal_time(Utc::now());
+        let below = telescope.slew_to((lst + 12.0) % 24.0, -30.0).await;
+        assert!(matches!(below, Err(Error::Rejected(r)) if r.name == EQUATORIAL_EOD_COORD));
+        telescope.slew_to(lst, 10.0).await.unwrap();
+
+        assert!(telescope.slew_to_horizontal(5.0, 180.0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_watch_limits_parks() {
+        let config = mock_server(
+            greeting(6.0, 0.0),
+            "newSwitchVector",
+            r#"<setSwitchVector device="Telescope Simulator" name="TELESCOPE_ABORT_MOTION" state="Ok"/>
+<setSwitchVector device="Telescope Simulator" name="TELESCOPE_PARK" state="Ok"/>
+"#,
+        )
+        .await;
+        let client = Client::new(config).await.unwrap();
+        wait_for_property(&client, "Telescope Simulator", EQUATORIAL_EOD_COORD).await;
+        let telescope = Telescope::new(client, "Telescope Simulator")
+            .with_safety_limits(limits().with_tracking_action(TrackingAction::Park, 5.0));
+
+        let watch = telescope.watch_limits(Duration::from_millis(10)).unwrap();
+        watch.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,595 @@
+use super::connection::CONNECTION;
+use super::{Definitions, Driver, IndiDriver, TimerId};
+use crate::error::{Error, Result};
+use crate::message::definition::{DefSwitch, DefSwitchVector};
+use crate::message::new::{
+    NewBlobVector, NewNumberVector, NewSwitchVector, NewTextVector, OneNumber, OneSwitch, OneText,
+};
+use crate::message::set::SetSwitchVector;
+use crate::message::stream::Framer;
+use crate::message::MessageType;
+use crate::property::timestamp::{INDITimestamp, TimestampPolicy};
+use crate::property::{PropertyPerm, PropertyState, SwitchRule, SwitchState};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Switch vector loading and saving the configuration of a device
+pub const CONFIG_PROCESS: &str = "CONFIG_PROCESS";
+/// Element of [`CONFIG_PROCESS`] that loads the configuration file
+pub const CONFIG_LOAD: &str = "CONFIG_LOAD";
+/// Element of [`CONFIG_PROCESS`] that saves the configuration file
+pub const CONFIG_SAVE: &str = "CONFIG_SAVE";
+/// Element of [`CONFIG_PROCESS`] that loads the default configuration file
+pub const CONFIG_DEFAULT: &str = "CONFIG_DEFAULT";
+/// Element of [`CONFIG_PROCESS`] that deletes the configuration file
+pub const CONFIG_PURGE: &str = "CONFIG_PURGE";
+
+/// Root element of indilib configuration files
+const ROOT: &str = "INDIDriver";
+
+/// Configuration file of a device in indilib's format
+///
+/// The file holds a `newTextVector`, `newNumberVector` or
+/// `newSwitchVector` per saved property under an `INDIDriver` root, so
+/// configurations can be shared with C++ drivers of the same device.
+#[derive(Debug, Clone)]
+pub struct ConfigFile {
+    path: PathBuf,
+}
+
+impl ConfigFile {
+    /// Configuration file at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Configuration file indilib uses for `device`
+    ///
+    /// `$INDICONFIG` if set, `~/.indi/<device>_config.xml` otherwise.
+    pub fn for_device(device: &str) -> Result<Self> {
+        if let Some(path) = std::env::var_os("INDICONFIG") {
+            return Ok(Self::new(path));
+        }
+        let home = std::env::var_os("HOME")
+            .ok_or_else(|| Error::Message("HOME is not set".to_string()))?;
+        let path = Path::new(&home)
+            .join(".indi")
+            .join(format!("{}_config.xml", device));
+        Ok(Self::new(path))
+    }
+
+    /// Path of the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of the default configuration, the file with `.default`
+    /// appended
+    pub fn default_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".default");
+        PathBuf::from(path)
+    }
+
+    /// Saved property values, as the updates that restore them
+    pub async fn load(&self) -> Result<Vec<MessageType>> {
+        read(&self.path).await
+    }
+
+    /// Property values of the default configuration
+    pub async fn load_default(&self) -> Result<Vec<MessageType>> {
+        read(&self.default_path()).await
+    }
+
+    /// Save the values of the writable text, number and switch
+    /// `definitions`, replacing the file
+    ///
+    /// `CONNECTION` and `CONFIG_PROCESS` are never saved.
+    pub async fn save<'a>(
+        &self,
+        definitions: impl IntoIterator<Item = &'a MessageType>,
+    ) -> Result<()> {
+        let mut xml = format!("<{}>\n", ROOT);
+        for update in definitions.into_iter().filter_map(saved_value) {
+            xml.push_str(&update.to_indilib_xml()?);
+        }
+        xml.push_str(&format!("</{}>\n", ROOT));
+
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        tokio::fs::write(&temp, xml).await?;
+        tokio::fs::rename(&temp, &self.path).await?;
+        Ok(())
+    }
+
+    /// Delete the file, returns true if there was one
+    pub async fn purge(&self) -> Result<bool> {
+        match tokio::fs::remove_file(&self.path).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Updates stored in the configuration file at `path`
+async fn read(path: &Path) -> Result<Vec<MessageType>> {
+    let contents = tokio::fs::read(path).await?;
+    let contents = std::str::from_utf8(&contents)?;
+    let open = format!("<{}>", ROOT);
+    let body = match contents.find(&open) {
+        Some(start) => &contents[start + open.len()..],
+        None => contents,
+    };
+    let body = match body.rfind(&format!("</{}>", ROOT)) {
+        Some(end) => &body[..end],
+        None => body,
+    };
+    let mut framer = Framer::new();
+    framer.push(body.as_bytes());
+    let mut updates = Vec::new();
+    while let Some(frame) = framer.next_frame() {
+        match MessageType::from_slice(&frame) {
+            Ok(update) if update.kind().is_new() => updates.push(update),
+            Ok(_) => {}
+            Err(e) => debug!("Ignoring unparsable configuration entry: {}", e),
+        }
+    }
+    Ok(updates)
+}
+
+/// Update restoring the current value of a writable definition
+fn saved_value(definition: &MessageType) -> Option<MessageType> {
+    let update = match definition {
+        MessageType::DefTextVector(def) if def.perm != PropertyPerm::Ro => {
+            MessageType::NewTextVector(NewTextVector {
+                device: def.device.clone(),
+                name: def.name.clone(),
+                timestamp: None,
+                elements: def
+                    .texts
+                    .iter()
+                    .map(|text| OneText {
+                        name: text.name.clone(),
+                        value: text.value.clone(),
+                    })
+                    .collect(),
+            })
+        }
+        MessageType::DefNumberVector(def) if def.perm != PropertyPerm::Ro => {
+            MessageType::NewNumberVector(NewNumberVector {
+                device: def.device.clone(),
+                name: def.name.clone(),
+                timestamp: None,
+                elements: def
+                    .numbers
+                    .iter()
+                    .map(|number| OneNumber {
+                        name: number.name.clone(),
+                        value: number.value.clone(),
+                    })
+                    .collect(),
+            })
+        }
+        MessageType::DefSwitchVector(def) if def.perm != PropertyPerm::Ro => {
+            MessageType::NewSwitchVector(NewSwitchVector {
+                device: def.device.clone(),
+                name: def.name.clone(),
+                timestamp: None,
+                elements: def
+                    .switches
+                    .iter()
+                    .map(|switch| OneSwitch {
+                        name: switch.name.clone(),
+                        value: switch.state,
+                    })
+                    .collect(),
+            })
+        }
+        _ => return None,
+    };
+    match update.name() {
+        Some(CONNECTION) | Some(CONFIG_PROCESS) => None,
+        _ => Some(update),
+    }
+}
+
+/// Whether the value of `message`'s property is saved and restored, given
+/// the `excluded` properties
+fn saves(excluded: &[String], message: &MessageType) -> bool {
+    message.name().map_or(true, |name| {
+        !excluded.iter().any(|excluded| excluded == name)
+    })
+}
+
+/// Runs a driver with the standard `CONFIG_PROCESS` switch of a device
+///
+/// Defines `CONFIG_PROCESS` next to the driver's properties when started
+/// and loads the [`ConfigFile`]. Saved values are applied as client updates
+/// whenever their property gets defined, so properties defined on connect
+/// by a [`ConnectionGuard`](super::connection::ConnectionGuard) are
+/// restored as well. `CONFIG_SAVE` saves the current values of the
+/// device's writable properties, `CONFIG_LOAD` and `CONFIG_DEFAULT` apply
+/// the file or its default and `CONFIG_PURGE` deletes the file.
+/// Properties that move the hardware, such as a focuser's position, should
+/// be left out with [`ConfigGuard::exclude`].
+///
+/// Only messages returned by the driver's methods are followed, not those
+/// published through [`Driver::publish`].
+#[derive(Debug)]
+pub struct ConfigGuard<D> {
+    inner: D,
+    device: String,
+    file: ConfigFile,
+    /// Current definitions of the device
+    definitions: Definitions,
+    /// Updates applied when their property is defined
+    saved: Vec<MessageType>,
+    /// Properties never saved or restored
+    excluded: Vec<String>,
+}
+
+impl<D: IndiDriver> ConfigGuard<D> {
+    /// Guard `inner`, keeping the configuration of `device` in indilib's
+    /// [`ConfigFile::for_device`]
+    pub fn new(inner: D, device: impl Into<String>) -> Result<Self> {
+        let device = device.into();
+        let file = ConfigFile::for_device(&device)?;
+        Ok(Self::with_file(inner, device, file))
+    }
+
+    /// Guard `inner`, keeping the configuration of `device` in `file`
+    pub fn with_file(inner: D, device: impl Into<String>, file: ConfigFile) -> Self {
+        Self {
+            inner,
+            device: device.into(),
+            file,
+            definitions: Definitions::default(),
+            saved: Vec::new(),
+            excluded: Vec::new(),
+        }
+    }
+
+    /// Never save or restore the properties `names`
+    pub fn exclude(mut self, names: &[&str]) -> Self {
+        self.excluded
+            .extend(names.iter().map(|name| name.to_string()));
+        self
+    }
+
+    /// The guarded driver
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// The guarded driver, mutably
+    pub fn inner_mut(&mut self) -> &mut D {
+        &mut self.inner
+    }
+
+    /// The configuration file
+    pub fn file(&self) -> &ConfigFile {
+        &self.file
+    }
+
+    /// Definition of `CONFIG_PROCESS`
+    fn definition(&self) -> MessageType {
+        let switches = [
+            (CONFIG_LOAD, "Load"),
+            (CONFIG_SAVE, "Save"),
+            (CONFIG_DEFAULT, "Default"),
+            (CONFIG_PURGE, "Purge"),
+        ];
+        MessageType::DefSwitchVector(DefSwitchVector {
+            device: self.device.clone(),
+            name: CONFIG_PROCESS.to_string(),
+            label: "Configuration".to_string(),
+            group: "Options".to_string(),
+            state: PropertyState::Idle,
+            perm: PropertyPerm::Rw,
+            rule: SwitchRule::AtMostOne,
+            timeout: 0,
+            timestamp: INDITimestamp::now().to_string(),
+            message: None,
+            switches: switches
+                .into_iter()
+                .map(|(name, label)| DefSwitch {
+                    name: name.to_string(),
+                    label: label.to_string(),
+                    state: SwitchState::Off,
+                })
+                .collect(),
+        })
+    }
+
+    /// Update of `CONFIG_PROCESS` once an action is done
+    fn update(&self, state: PropertyState, message: String) -> MessageType {
+        MessageType::SetSwitchVector(SetSwitchVector {
+            device: self.device.clone(),
+            name: CONFIG_PROCESS.to_string(),
+            state: Some(state),
+            timeout: None,
+            timestamp: Some(INDITimestamp::now().to_string()),
+            message: Some(message),
+            elements: [CONFIG_LOAD, CONFIG_SAVE, CONFIG_DEFAULT, CONFIG_PURGE]
+                .into_iter()
+                .map(|name| OneSwitch {
+                    name: name.to_string(),
+                    value: SwitchState::Off,
+                })
+                .collect(),
+        })
+    }
+
+    /// Handle a client's `CONFIG_PROCESS` update
+    async fn process(&mut self, update: &NewSwitchVector) -> Vec<MessageType> {
+        let action = update
+            .elements
+            .iter()
+            .find(|element| element.value == SwitchState::On)
+            .map(|element| element.name.as_str());
+        let result = match action {
+            Some(CONFIG_LOAD) => {
+                let path = self.file.path().display().to_string();
+                self.apply(self.file.load().await)
+                    .await
+                    .map(|messages| (messages, format!("Loaded configuration {}", path)))
+            }
+            Some(CONFIG_DEFAULT) => {
+                let path = self.file.default_path().display().to_string();
+                self.apply(self.file.load_default().await)
+                    .await
+                    .map(|messages| (messages, format!("Loaded configuration {}", path)))
+            }
+            Some(CONFIG_SAVE) => {
+                let device = self.device.as_str();
+                let definitions = self.definitions.iter().filter(|definition| {
+                    definition.device() == Some(device) && saves(&self.excluded, definition)
+                });
+                self.file.save(definitions).await.map(|()| {
+                    let path = self.file.path().display();
+                    (Vec::new(), format!("Saved configuration {}", path))
+                })
+            }
+            Some(CONFIG_PURGE) => self.file.purge().await.map(|_| {
+                let path = self.file.path().display();
+                (Vec::new(), format!("Purged configuration {}", path))
+            }),
+            _ => Ok((Vec::new(), "No action requested".to_string())),
+        };
+        match result {
+            Ok((mut messages, message)) => {
+                messages.push(self.update(PropertyState::Ok, message));
+                messages
+            }
+            Err(e) => {
+                warn!("Configuration of {} failed: {}", self.device, e);
+                let message = format!("Configuration failed: {}", e);
+                vec![self.update(PropertyState::Alert, message)]
+            }
+        }
+    }
+
+    /// Make `loaded` the saved values and apply those of properties that
+    /// are defined
+    async fn apply(&mut self, loaded: Result<Vec<MessageType>>) -> Result<Vec<MessageType>> {
+        self.saved = self.restorable(loaded?);
+        let defined = self
+            .saved
+            .iter()
+            .filter(|update| {
+                self.definitions.iter().any(|definition| {
+                    definition.device() == update.device() && definition.name() == update.name()
+                })
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut messages = Vec::new();
+        for update in defined {
+            let answer = self.dispatch(update).await;
+            messages.extend(self.track(answer).await);
+        }
+        Ok(messages)
+    }
+
+    /// The updates of `loaded` that may be restored
+    fn restorable(&self, loaded: Vec<MessageType>) -> Vec<MessageType> {
+        loaded
+            .into_iter()
+            .filter(|update| saves(&self.excluded, update))
+            .collect()
+    }
+
+    /// Pass a restoring update to the driver
+    async fn dispatch(&mut self, update: MessageType) -> Vec<MessageType> {
+        match update {
+            MessageType::NewTextVector(update) => self.inner.on_new_text(update).await,
+            MessageType::NewNumberVector(update) => self.inner.on_new_number(update).await,
+            MessageType::NewSwitchVector(update) => self.inner.on_new_switch(update).await,
+            _ => Vec::new(),
+        }
+    }
+
+    /// Follow the driver's messages, applying the saved values of the
+    /// properties they define
+    async fn track(&mut self, messages: Vec<MessageType>) -> Vec<MessageType> {
+        let mut tracked = Vec::with_capacity(messages.len());
+        let mut pending = messages;
+        while !pending.is_empty() {
+            let mut restoring = Vec::new();
+            for message in pending {
+                let own = message.device() == Some(self.device.as_str());
+                if own && self.definitions.remember(&message) {
+                    restoring.extend(
+                        self.saved
+                            .iter()
+                            .filter(|update| update.name() == message.name())
+                            .cloned(),
+                    );
+                }
+                tracked.push(message);
+            }
+            pending = Vec::new();
+            for update in restoring {
+                pending.extend(self.dispatch(update).await);
+            }
+        }
+        tracked
+    }
+}
+
+#[async_trait]
+impl<D: IndiDriver> IndiDriver for ConfigGuard<D> {
+    fn attach(&mut self, driver: Driver) {
+        self.inner.attach(driver);
+    }
+
+    async fn define_properties(&mut self) -> Vec<MessageType> {
+        self.saved = match self.file.load().await {
+            Ok(saved) => self.restorable(saved),
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                warn!(
+                    "Failed to load configuration {}: {}",
+                    self.file.path().display(),
+                    e
+                );
+                Vec::new()
+            }
+        };
+        let mut definitions = self.inner.define_properties().await;
+        definitions.push(self.definition());
+        self.track(definitions).await
+    }
+
+    async fn on_new_text(&mut self, update: NewTextVector) -> Vec<MessageType> {
+        let messages = self.inner.on_new_text(update).await;
+        self.track(messages).await
+    }
+
+    async fn on_new_number(&mut self, update: NewNumberVector) -> Vec<MessageType> {
+        let messages = self.inner.on_new_number(update).await;
+        self.track(messages).await
+    }
+
+    async fn on_new_switch(&mut self, update: NewSwitchVector) -> Vec<MessageType> {
+        if update.name == CONFIG_PROCESS && update.device == self.device {
+            return self.process(&update).await;
+        }
+        let messages = self.inner.on_new_switch(update).await;
+        self.track(messages).await
+    }
+
+    async fn on_new_blob(&mut self, update: NewBlobVector) -> Vec<MessageType> {
+        let messages = self.inner.on_new_blob(update).await;
+        self.track(messages).await
+    }
+
+    fn poll_interval(&self) -> Option<Duration> {
+        self.inner.poll_interval()
+    }
+
+    fn timestamp_policy(&self) -> TimestampPolicy {
+        self.inner.timestamp_policy()
+    }
+
+    async fn poll(&mut self) -> Vec<MessageType> {
+        let messages = self.inner.poll().await;
+        self.track(messages).await
+    }
+
+    async fn on_timer(&mut self, id: TimerId) -> Vec<MessageType> {
+        let messages = self.inner.on_timer(id).await;
+        self.track(messages).await
+    }
+
+    async fn on_snoop(&mut self, message: MessageType) -> Vec<MessageType> {
+        let messages = self.inner.on_snoop(message).await;
+        self.track(messages).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    /// Focuser with a writable `Focuser.FOCUS_SPEED`
+    struct Focuser;
+
+    #[async_trait]
+    impl IndiDriver for Focuser {
+        async fn define_properties(&mut self) -> Vec<MessageType> {
+            vec![MessageType::from_str(
+                r#"<defNumberVector device="Focuser" name="FOCUS_SPEED" state="Idle" perm="rw"><defNumber name="FOCUS_SPEED_VALUE" format="%.0f" min="1" max="5" step="1">1</defNumber></defNumberVector>"#,
+            )
+            .unwrap()]
+        }
+
+        async fn on_new_number(&mut self, update: NewNumberVector) -> Vec<MessageType> {
+            vec![MessageType::from_str(&format!(
+                r#"<setNumberVector device="Focuser" name="FOCUS_SPEED" state="Ok"><oneNumber name="FOCUS_SPEED_VALUE">{}</oneNumber></setNumberVector>"#,
+                update.elements[0].value
+            ))
+            .unwrap()]
+        }
+    }
+
+    fn config_process(action: &str) -> NewSwitchVector {
+        NewSwitchVector {
+            device: "Focuser".to_string(),
+            name: CONFIG_PROCESS.to_string(),
+            timestamp: None,
+            elements: vec![OneSwitch {
+                name: action.to_string(),
+                value: SwitchState::On,
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_config_guard() {
+        let dir = std::env::temp_dir().join(format!("indi-config-{}", std::process::id()));
+        let file = ConfigFile::new(dir.join("Focuser_config.xml"));
+        let mut guard = ConfigGuard::with_file(Focuser, "Focuser", file.clone());
+
+        let definitions = guard.define_properties().await;
+        assert_eq!(definitions.len(), 2);
+        assert_eq!(definitions[1].name(), Some(CONFIG_PROCESS));
+        guard
+            .on_new_number(NewNumberVector {
+                device: "Focuser".to_string(),
+                name: "FOCUS_SPEED".to_string(),
+                timestamp: None,
+                elements: vec![OneNumber {
+                    name: "FOCUS_SPEED_VALUE".to_string(),
+                    value: "3".to_string(),
+                }],
+            })
+            .await;
+        let saved = guard.on_new_switch(config_process(CONFIG_SAVE)).await;
+        let Some(MessageType::SetSwitchVector(set)) = saved.last() else {
+            panic!("Expected a CONFIG_PROCESS update: {:?}", saved);
+        };
+        assert_eq!(set.state, Some(PropertyState::Ok));
+        let contents = std::fs::read_to_string(file.path()).unwrap();
+        assert!(contents.starts_with("<INDIDriver>\n<newNumberVector"));
+
+        // A new run restores the saved value once the property is defined
+        let mut guard = ConfigGuard::with_file(Focuser, "Focuser", file.clone());
+        let definitions = guard.define_properties().await;
+        let Some(MessageType::SetNumberVector(set)) = definitions.last() else {
+            panic!("Expected the saved speed: {:?}", definitions);
+        };
+        assert_eq!(set.elements[0].value, "3");
+
+        guard.on_new_switch(config_process(CONFIG_PURGE)).await;
+        assert!(!file.path().exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
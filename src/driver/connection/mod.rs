@@ -0,0 +1,429 @@
+use super::{Driver, IndiDriver, TimerId};
+use crate::client::definition_key;
+use crate::error::Result;
+use crate::message::basic::DelProperty;
+use crate::message::definition::{DefSwitch, DefSwitchVector};
+use crate::message::new::{
+    NewBlobVector, NewNumberVector, NewSwitchVector, NewTextVector, OneSwitch,
+};
+use crate::message::set::SetSwitchVector;
+use crate::message::MessageType;
+use crate::property::timestamp::{INDITimestamp, TimestampPolicy};
+use crate::property::{PropertyPerm, PropertyState, SwitchRule, SwitchState};
+use async_trait::async_trait;
+use std::time::Duration;
+use tracing::warn;
+
+#[cfg(feature = "serial")]
+mod serial;
+mod tcp;
+
+#[cfg(feature = "serial")]
+pub use serial::{SerialConnection, BAUD_RATES, DEVICE_BAUD_RATE, DEVICE_PORT};
+pub use tcp::{TcpConnection, ADDRESS, DEVICE_ADDRESS};
+
+/// Element with the serial port of `DEVICE_PORT`, and with the TCP port
+/// of [`DEVICE_ADDRESS`]
+pub const PORT: &str = "PORT";
+
+/// Group of the properties selecting the connection to the hardware
+const GROUP: &str = "Connection";
+
+/// Switch vector connecting a device to its hardware
+pub const CONNECTION: &str = "CONNECTION";
+/// Element of [`CONNECTION`] that connects
+pub const CONNECT: &str = "CONNECT";
+/// Element of [`CONNECTION`] that disconnects
+pub const DISCONNECT: &str = "DISCONNECT";
+
+/// Driver whose properties only exist while connected to its hardware,
+/// run by a [`ConnectionGuard`]
+///
+/// [`IndiDriver::define_properties`] is called on every connect and
+/// returns the properties to define for the connected device.
+#[async_trait]
+pub trait Connectable: IndiDriver {
+    /// Device the `CONNECTION` property is defined for
+    fn device(&self) -> &str;
+
+    /// Open the connection to the hardware
+    async fn connect(&mut self) -> Result<()>;
+
+    /// Close the connection to the hardware
+    async fn disconnect(&mut self) -> Result<()>;
+
+    /// Properties defined while disconnected as well, besides
+    /// `CONNECTION`, such as the port to connect to
+    async fn define_disconnected(&mut self) -> Vec<MessageType> {
+        Vec::new()
+    }
+}
+
+/// Runs a [`Connectable`] driver behind the standard `CONNECTION` switch
+///
+/// Defines `CONNECTION` and the [`Connectable::define_disconnected`]
+/// properties when started. Switching `CONNECT` on calls
+/// [`Connectable::connect`] and then defines the properties of
+/// [`IndiDriver::define_properties`]; switching `DISCONNECT` on calls
+/// [`Connectable::disconnect`] and deletes them again, along with any the
+/// driver defined while connected. A failed hook leaves the connection as
+/// it was and reports `Alert`. Client updates and timers are passed on;
+/// the driver is only polled while connected.
+#[derive(Debug)]
+pub struct ConnectionGuard<D> {
+    inner: D,
+    connected: bool,
+    /// Properties defined while disconnected, never deleted
+    permanent: Vec<String>,
+    /// Properties defined since connecting, deleted on disconnecting
+    gated: Vec<String>,
+}
+
+impl<D: Connectable> ConnectionGuard<D> {
+    /// Guard `inner`, starting disconnected
+    pub fn new(inner: D) -> Self {
+        Self {
+            inner,
+            connected: false,
+            permanent: Vec::new(),
+            gated: Vec::new(),
+        }
+    }
+
+    /// The guarded driver
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// The guarded driver, mutably
+    pub fn inner_mut(&mut self) -> &mut D {
+        &mut self.inner
+    }
+
+    /// Returns true while connected
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Definition of `CONNECTION` in the current state
+    fn definition(&self) -> MessageType {
+        let switches = [
+            (CONNECT, "Connect", self.connected),
+            (DISCONNECT, "Disconnect", !self.connected),
+        ];
+        MessageType::DefSwitchVector(DefSwitchVector {
+            device: self.inner.device().to_string(),
+            name: CONNECTION.to_string(),
+            label: "Connection".to_string(),
+            group: "Main Control".to_string(),
+            state: PropertyState::Idle,
+            perm: PropertyPerm::Rw,
+            rule: SwitchRule::OneOfMany,
+            timeout: 60,
+            timestamp: INDITimestamp::now().to_string(),
+            message: None,
+            switches: switches
+                .into_iter()
+                .map(|(name, label, on)| DefSwitch {
+                    name: name.to_string(),
+                    label: label.to_string(),
+                    state: switch_state(on),
+                })
+                .collect(),
+        })
+    }
+
+    /// Update of `CONNECTION` with the current state of its elements
+    fn update(&self, state: PropertyState, message: Option<String>) -> MessageType {
+        MessageType::SetSwitchVector(SetSwitchVector {
+            device: self.inner.device().to_string(),
+            name: CONNECTION.to_string(),
+            state: Some(state),
+            timeout: None,
+            timestamp: Some(INDITimestamp::now().to_string()),
+            message,
+            elements: [(CONNECT, self.connected), (DISCONNECT, !self.connected)]
+                .into_iter()
+                .map(|(name, on)| OneSwitch {
+                    name: name.to_string(),
+                    value: switch_state(on),
+                })
+                .collect(),
+        })
+    }
+
+    /// Handle a client's `CONNECTION` update
+    async fn switch(&mut self, update: &NewSwitchVector) -> Vec<MessageType> {
+        let Some(connect) = requested(&update.elements) else {
+            return vec![self.update(PropertyState::Ok, None)];
+        };
+        if connect == self.connected {
+            return vec![self.update(PropertyState::Ok, None)];
+        }
+        if connect {
+            if let Err(e) = self.inner.connect().await {
+                warn!("Failed to connect {}: {}", self.inner.device(), e);
+                let message = format!("Failed to connect: {}", e);
+                return vec![self.update(PropertyState::Alert, Some(message))];
+            }
+            self.connected = true;
+            let mut messages = vec![self.update(PropertyState::Ok, None)];
+            let definitions = self.inner.define_properties().await;
+            messages.extend(self.track(definitions));
+            messages
+        } else {
+            if let Err(e) = self.inner.disconnect().await {
+                warn!("Failed to disconnect {}: {}", self.inner.device(), e);
+                let message = format!("Failed to disconnect: {}", e);
+                return vec![self.update(PropertyState::Alert, Some(message))];
+            }
+            self.connected = false;
+            let device = self.inner.device().to_string();
+            let mut messages = std::mem::take(&mut self.gated)
+                .into_iter()
+                .map(|name| {
+                    MessageType::DelProperty(DelProperty {
+                        device: device.clone(),
+                        name: Some(name),
+                        timestamp: Some(INDITimestamp::now().to_string()),
+                        message: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+            messages.push(self.update(PropertyState::Ok, None));
+            messages
+        }
+    }
+
+    /// Remember the properties `messages` define while connected
+    fn track(&mut self, messages: Vec<MessageType>) -> Vec<MessageType> {
+        if self.connected {
+            for (device, name) in messages.iter().filter_map(definition_key) {
+                if device == self.inner.device()
+                    && name != CONNECTION
+                    && !self.permanent.contains(&name)
+                    && !self.gated.contains(&name)
+                {
+                    self.gated.push(name);
+                }
+            }
+        }
+        messages
+    }
+}
+
+/// Connection state a `CONNECTION` update asks for
+///
+/// The element switched on wins. Clients that only switch one element off
+/// ask for the other one, as the rule is `OneOfMany`.
+fn requested(elements: &[OneSwitch]) -> Option<bool> {
+    let on = |name: &str| {
+        elements
+            .iter()
+            .find(|element| element.name == name)
+            .map(|element| element.value == SwitchState::On)
+    };
+    match (on(CONNECT), on(DISCONNECT)) {
+        (Some(true), _) => Some(true),
+        (_, Some(true)) => Some(false),
+        (Some(false), None) => Some(false),
+        (None, Some(false)) => Some(true),
+        _ => None,
+    }
+}
+
+fn switch_state(on: bool) -> SwitchState {
+    if on {
+        SwitchState::On
+    } else {
+        SwitchState::Off
+    }
+}
+
+#[async_trait]
+impl<D: Connectable> IndiDriver for ConnectionGuard<D> {
+    fn attach(&mut self, driver: Driver) {
+        self.inner.attach(driver);
+    }
+
+    async fn define_properties(&mut self) -> Vec<MessageType> {
+        let mut definitions = vec![self.definition()];
+        let disconnected = self.inner.define_disconnected().await;
+        self.permanent = disconnected
+            .iter()
+            .filter_map(definition_key)
+            .map(|(_, name)| name)
+            .collect();
+        definitions.extend(disconnected);
+        definitions
+    }
+
+    async fn on_new_text(&mut self, update: NewTextVector) -> Vec<MessageType> {
+        let messages = self.inner.on_new_text(update).await;
+        self.track(messages)
+    }
+
+    async fn on_new_number(&mut self, update: NewNumberVector) -> Vec<MessageType> {
+        let messages = self.inner.on_new_number(update).await;
+        self.track(messages)
+    }
+
+    async fn on_new_switch(&mut self, update: NewSwitchVector) -> Vec<MessageType> {
+        if update.name == CONNECTION && update.device == self.inner.device() {
+            return self.switch(&update).await;
+        }
+        let messages = self.inner.on_new_switch(update).await;
+        self.track(messages)
+    }
+
+    async fn on_new_blob(&mut self, update: NewBlobVector) -> Vec<MessageType> {
+        let messages = self.inner.on_new_blob(update).await;
+        self.track(messages)
+    }
+
+    fn poll_interval(&self) -> Option<Duration> {
+        self.inner.poll_interval()
+    }
+
+    fn timestamp_policy(&self) -> TimestampPolicy {
+        self.inner.timestamp_policy()
+    }
+
+    async fn poll(&mut self) -> Vec<MessageType> {
+        if !self.connected {
+            return Vec::new();
+        }
+        let messages = self.inner.poll().await;
+        self.track(messages)
+    }
+
+    async fn on_timer(&mut self, id: TimerId) -> Vec<MessageType> {
+        let messages = self.inner.on_timer(id).await;
+        self.track(messages)
+    }
+
+    async fn on_snoop(&mut self, message: MessageType) -> Vec<MessageType> {
+        let messages = self.inner.on_snoop(message).await;
+        self.track(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use std::str::FromStr;
+
+    /// Focuser with a `POSITION` number that fails its first connect
+    #[derive(Default)]
+    struct Focuser {
+        attempts: u32,
+    }
+
+    #[async_trait]
+    impl IndiDriver for Focuser {
+        async fn define_properties(&mut self) -> Vec<MessageType> {
+            vec![MessageType::from_str(
+                r#"<defNumberVector device="Focuser" name="POSITION" state="Idle" perm="rw"><defNumber name="STEPS" format="%.0f" min="0" max="1000" step="1">0</defNumber></defNumberVector>"#,
+            )
+            .unwrap()]
+        }
+    }
+
+    #[async_trait]
+    impl Connectable for Focuser {
+        fn device(&self) -> &str {
+            "Focuser"
+        }
+
+        async fn connect(&mut self) -> Result<()> {
+            self.attempts += 1;
+            if self.attempts == 1 {
+                return Err(Error::Message("No reply".to_string()));
+            }
+            Ok(())
+        }
+
+        async fn disconnect(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn define_disconnected(&mut self) -> Vec<MessageType> {
+            vec![MessageType::from_str(
+                r#"<defTextVector device="Focuser" name="DEVICE_PORT" state="Idle" perm="rw"><defText name="PORT">/dev/ttyUSB0</defText></defTextVector>"#,
+            )
+            .unwrap()]
+        }
+    }
+
+    fn connection(element: &str, value: SwitchState) -> NewSwitchVector {
+        NewSwitchVector {
+            device: "Focuser".to_string(),
+            name: CONNECTION.to_string(),
+            timestamp: None,
+            elements: vec![OneSwitch {
+                name: element.to_string(),
+                value,
+            }],
+        }
+    }
+
+    fn names(messages: &[MessageType]) -> Vec<(String, Option<&str>)> {
+        messages
+            .iter()
+            .map(|message| (format!("{:?}", message.kind()), message.name()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_connection_guard() {
+        let mut guard = ConnectionGuard::new(Focuser::default());
+        let definitions = guard.define_properties().await;
+        assert_eq!(
+            names(&definitions),
+            [
+                ("DefSwitchVector".to_string(), Some(CONNECTION)),
+                ("DefTextVector".to_string(), Some("DEVICE_PORT")),
+            ]
+        );
+
+        // A failed connect reports Alert and stays disconnected
+        let reply = guard
+            .on_new_switch(connection(CONNECT, SwitchState::On))
+            .await;
+        let MessageType::SetSwitchVector(set) = &reply[0] else {
+            panic!("Expected a CONNECTION update");
+        };
+        assert_eq!(set.state, Some(PropertyState::Alert));
+        assert!(!guard.is_connected());
+
+        let reply = guard
+            .on_new_switch(connection(CONNECT, SwitchState::On))
+            .await;
+        assert_eq!(
+            names(&reply),
+            [
+                ("SetSwitchVector".to_string(), Some(CONNECTION)),
+                ("DefNumberVector".to_string(), Some("POSITION")),
+            ]
+        );
+        assert!(guard.is_connected());
+
+        // Switching CONNECT off asks for DISCONNECT
+        let reply = guard
+            .on_new_switch(connection(CONNECT, SwitchState::Off))
+            .await;
+        assert_eq!(
+            names(&reply),
+            [
+                ("DelProperty".to_string(), Some("POSITION")),
+                ("SetSwitchVector".to_string(), Some(CONNECTION)),
+            ]
+        );
+        let MessageType::SetSwitchVector(set) = &reply[1] else {
+            panic!("Expected a CONNECTION update");
+        };
+        assert_eq!(set.elements[1].value, SwitchState::On);
+        assert!(!guard.is_connected());
+    }
+}
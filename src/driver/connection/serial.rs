@@ -0,0 +1,231 @@
+use crate::error::Result;
+use crate::message::definition::{DefSwitch, DefSwitchVector, DefText, DefTextVector};
+use crate::message::new::{NewSwitchVector, NewTextVector, OneSwitch, OneText};
+use crate::message::set::{SetSwitchVector, SetTextVector};
+use crate::message::MessageType;
+use crate::property::timestamp::INDITimestamp;
+use crate::property::{PropertyPerm, PropertyState, SwitchRule, SwitchState};
+use tokio_serial::{SerialPortBuilderExt, SerialStream};
+
+use super::{GROUP, PORT};
+
+/// Text vector with the serial port to connect to
+pub const DEVICE_PORT: &str = "DEVICE_PORT";
+/// Switch vector with the baud rate of the serial port
+pub const DEVICE_BAUD_RATE: &str = "DEVICE_BAUD_RATE";
+/// Baud rates offered by [`DEVICE_BAUD_RATE`], its elements are named
+/// after them
+pub const BAUD_RATES: [u32; 6] = [9600, 19200, 38400, 57600, 115200, 230400];
+
+/// Serial port of a device, selected with the standard `DEVICE_PORT` and
+/// `DEVICE_BAUD_RATE` properties
+///
+/// A [`Connectable`](super::Connectable) driver returns
+/// [`SerialConnection::definitions`] from
+/// [`Connectable::define_disconnected`](super::Connectable::define_disconnected),
+/// passes client updates to [`SerialConnection::on_new_text`] and
+/// [`SerialConnection::on_new_switch`] first, and opens the port in
+/// [`Connectable::connect`](super::Connectable::connect). The selection
+/// takes effect on the next connect. Both properties are writable, so a
+/// [`ConfigGuard`](crate::driver::config::ConfigGuard) saves and restores
+/// them.
+#[derive(Debug, Clone)]
+pub struct SerialConnection {
+    device: String,
+    port: String,
+    baud_rate: u32,
+}
+
+impl SerialConnection {
+    /// Serial connection of `device`, defaulting to `port` at `baud_rate`,
+    /// one of [`BAUD_RATES`]
+    pub fn new(device: impl Into<String>, port: impl Into<String>, baud_rate: u32) -> Self {
+        Self {
+            device: device.into(),
+            port: port.into(),
+            baud_rate,
+        }
+    }
+
+    /// Path of the selected port
+    pub fn port(&self) -> &str {
+        &self.port
+    }
+
+    /// Selected baud rate
+    pub fn baud_rate(&self) -> u32 {
+        self.baud_rate
+    }
+
+    /// Definitions of `DEVICE_PORT` and `DEVICE_BAUD_RATE` with the current
+    /// selection
+    pub fn definitions(&self) -> Vec<MessageType> {
+        let port = MessageType::DefTextVector(DefTextVector {
+            device: self.device.clone(),
+            name: DEVICE_PORT.to_string(),
+            label: "Ports".to_string(),
+            group: GROUP.to_string(),
+            state: PropertyState::Idle,
+            perm: PropertyPerm::Rw,
+            timeout: 60,
+            timestamp: INDITimestamp::now().to_string(),
+            message: None,
+            texts: vec![DefText {
+                name: PORT.to_string(),
+                label: "Port".to_string(),
+                value: self.port.clone(),
+            }],
+        });
+        let baud_rate = MessageType::DefSwitchVector(DefSwitchVector {
+            device: self.device.clone(),
+            name: DEVICE_BAUD_RATE.to_string(),
+            label: "Baud Rate".to_string(),
+            group: GROUP.to_string(),
+            state: PropertyState::Idle,
+            perm: PropertyPerm::Rw,
+            rule: SwitchRule::OneOfMany,
+            timeout: 60,
+            timestamp: INDITimestamp::now().to_string(),
+            message: None,
+            switches: BAUD_RATES
+                .iter()
+                .map(|rate| DefSwitch {
+                    name: rate.to_string(),
+                    label: rate.to_string(),
+                    state: self.baud_rate_state(*rate),
+                })
+                .collect(),
+        });
+        vec![port, baud_rate]
+    }
+
+    /// Handle a client's `DEVICE_PORT` update, `None` for other properties
+    pub fn on_new_text(&mut self, update: &NewTextVector) -> Option<Vec<MessageType>> {
+        if update.device != self.device || update.name != DEVICE_PORT {
+            return None;
+        }
+        let port = update.elements.iter().find(|element| element.name == PORT);
+        let state = match port {
+            Some(port) if !port.value.trim().is_empty() => {
+                self.port = port.value.trim().to_string();
+                PropertyState::Ok
+            }
+            _ => PropertyState::Alert,
+        };
+        Some(vec![MessageType::SetTextVector(SetTextVector {
+            device: self.device.clone(),
+            name: DEVICE_PORT.to_string(),
+            state: Some(state),
+            timeout: None,
+            timestamp: Some(INDITimestamp::now().to_string()),
+            message: None,
+            elements: vec![OneText {
+                name: PORT.to_string(),
+                value: self.port.clone(),
+            }],
+        })])
+    }
+
+    /// Handle a client's `DEVICE_BAUD_RATE` update, `None` for other
+    /// properties
+    pub fn on_new_switch(&mut self, update: &NewSwitchVector) -> Option<Vec<MessageType>> {
+        if update.device != self.device || update.name != DEVICE_BAUD_RATE {
+            return None;
+        }
+        let selected = update
+            .elements
+            .iter()
+            .filter(|element| element.value == SwitchState::On)
+            .find_map(|element| element.name.parse::<u32>().ok())
+            .filter(|rate| BAUD_RATES.contains(rate));
+        let state = match selected {
+            Some(rate) => {
+                self.baud_rate = rate;
+                PropertyState::Ok
+            }
+            None => PropertyState::Alert,
+        };
+        Some(vec![MessageType::SetSwitchVector(SetSwitchVector {
+            device: self.device.clone(),
+            name: DEVICE_BAUD_RATE.to_string(),
+            state: Some(state),
+            timeout: None,
+            timestamp: Some(INDITimestamp::now().to_string()),
+            message: None,
+            elements: BAUD_RATES
+                .iter()
+                .map(|rate| OneSwitch {
+                    name: rate.to_string(),
+                    value: self.baud_rate_state(*rate),
+                })
+                .collect(),
+        })])
+    }
+
+    /// Open the selected port at the selected baud rate, 8N1 without flow
+    /// control
+    pub fn open(&self) -> Result<SerialStream> {
+        let stream = tokio_serial::new(&self.port, self.baud_rate)
+            .open_native_async()
+            .map_err(std::io::Error::from)?;
+        Ok(stream)
+    }
+
+    fn baud_rate_state(&self, rate: u32) -> SwitchState {
+        if rate == self.baud_rate {
+            SwitchState::On
+        } else {
+            SwitchState::Off
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serial_connection() {
+        let mut serial = SerialConnection::new("Focuser", "/dev/ttyUSB0", 9600);
+        let definitions = serial.definitions();
+        assert_eq!(definitions[0].name(), Some(DEVICE_PORT));
+        assert_eq!(definitions[1].name(), Some(DEVICE_BAUD_RATE));
+
+        let update = NewTextVector {
+            device: "Focuser".to_string(),
+            name: DEVICE_PORT.to_string(),
+            timestamp: None,
+            elements: vec![OneText {
+                name: PORT.to_string(),
+                value: "/dev/ttyACM0".to_string(),
+            }],
+        };
+        assert!(serial.on_new_text(&update).is_some());
+        assert_eq!(serial.port(), "/dev/ttyACM0");
+
+        let update = NewSwitchVector {
+            device: "Focuser".to_string(),
+            name: DEVICE_BAUD_RATE.to_string(),
+            timestamp: None,
+            elements: vec![OneSwitch {
+                name: "115200".to_string(),
+                value: SwitchState::On,
+            }],
+        };
+        let Some(answer) = serial.on_new_switch(&update) else {
+            panic!("DEVICE_BAUD_RATE not handled");
+        };
+        let MessageType::SetSwitchVector(set) = &answer[0] else {
+            panic!("Expected a switch update: {:?}", answer);
+        };
+        assert_eq!(set.state, Some(PropertyState::Ok));
+        assert_eq!(serial.baud_rate(), 115200);
+
+        // Other properties are left to the driver
+        let update = NewSwitchVector {
+            name: "CONNECTION".to_string(),
+            ..update
+        };
+        assert!(serial.on_new_switch(&update).is_none());
+    }
+}
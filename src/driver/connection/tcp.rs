@@ -0,0 +1,333 @@
+use crate::error::{Error, Result};
+use crate::message::definition::{DefText, DefTextVector};
+use crate::message::new::{NewTextVector, OneText};
+use crate::message::set::SetTextVector;
+use crate::message::MessageType;
+use crate::property::timestamp::INDITimestamp;
+use crate::property::{PropertyPerm, PropertyState};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tracing::{debug, warn};
+
+use super::{GROUP, PORT};
+
+/// Text vector with the network address of the device
+pub const DEVICE_ADDRESS: &str = "DEVICE_ADDRESS";
+/// Element of [`DEVICE_ADDRESS`] with the host name or IP address
+pub const ADDRESS: &str = "ADDRESS";
+
+/// Network connection of a device, selected with the standard
+/// `DEVICE_ADDRESS` property
+///
+/// Used like a `SerialConnection`: the driver
+/// defines [`TcpConnection::definitions`] while disconnected, passes
+/// client updates to [`TcpConnection::on_new_text`] first and calls
+/// [`TcpConnection::connect`] from
+/// [`Connectable::connect`](super::Connectable::connect). Connecting is
+/// retried as configured with [`TcpConnection::with_retries`].
+/// [`TcpConnection::send`] and [`TcpConnection::exchange`] reconnect once
+/// when the device dropped the connection, as WiFi devices do when they
+/// go to sleep.
+#[derive(Debug)]
+pub struct TcpConnection {
+    device: String,
+    host: String,
+    port: u16,
+    attempts: u32,
+    retry_delay: Duration,
+    timeout: Duration,
+    stream: Option<BufReader<TcpStream>>,
+}
+
+impl TcpConnection {
+    /// Network connection of `device`, defaulting to `host` and `port`
+    ///
+    /// Connects in one attempt and waits up to 5 seconds for the device.
+    pub fn new(device: impl Into<String>, host: impl Into<String>, port: u16) -> Self {
+        Self {
+            device: device.into(),
+            host: host.into(),
+            port,
+            attempts: 1,
+            retry_delay: Duration::from_secs(1),
+            timeout: Duration::from_secs(5),
+            stream: None,
+        }
+    }
+
+    /// Try to connect up to `attempts` times, `delay` apart
+    pub fn with_retries(mut self, attempts: u32, delay: Duration) -> Self {
+        self.attempts = attempts.max(1);
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Time to wait for connecting and for each reply
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Selected host
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Selected port
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Returns true while connected
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Definition of `DEVICE_ADDRESS` with the current selection
+    pub fn definitions(&self) -> Vec<MessageType> {
+        vec![MessageType::DefTextVector(DefTextVector {
+            device: self.device.clone(),
+            name: DEVICE_ADDRESS.to_string(),
+            label: "Server".to_string(),
+            group: GROUP.to_string(),
+            state: PropertyState::Idle,
+            perm: PropertyPerm::Rw,
+            timeout: 60,
+            timestamp: INDITimestamp::now().to_string(),
+            message: None,
+            texts: vec![
+                DefText {
+                    name: ADDRESS.to_string(),
+                    label: "Address".to_string(),
+                    value: self.host.clone(),
+                },
+                DefText {
+                    name: PORT.to_string(),
+                    label: "Port".to_string(),
+                    value: self.port.to_string(),
+                },
+            ],
+        })]
+    }
+
+    /// Handle a client's `DEVICE_ADDRESS` update, `None` for other
+    /// properties
+    ///
+    /// The new address is used from the next connect on.
+    pub fn on_new_text(&mut self, update: &NewTextVector) -> Option<Vec<MessageType>> {
+        if update.device != self.device || update.name != DEVICE_ADDRESS {
+            return None;
+        }
+        let value = |name: &str| {
+            update
+                .elements
+                .iter()
+                .find(|element| element.name == name)
+                .map(|element| element.value.trim())
+        };
+        let host = value(ADDRESS).unwrap_or(&self.host);
+        let port = match value(PORT) {
+            Some(port) => port.parse::<u16>().ok(),
+            None => Some(self.port),
+        };
+        let state = match port {
+            Some(port) if !host.is_empty() && port != 0 => {
+                self.host = host.to_string();
+                self.port = port;
+                PropertyState::Ok
+            }
+            _ => PropertyState::Alert,
+        };
+        Some(vec![MessageType::SetTextVector(SetTextVector {
+            device: self.device.clone(),
+            name: DEVICE_ADDRESS.to_string(),
+            state: Some(state),
+            timeout: None,
+            timestamp: Some(INDITimestamp::now().to_string()),
+            message: None,
+            elements: vec![
+                OneText {
+                    name: ADDRESS.to_string(),
+                    value: self.host.clone(),
+                },
+                OneText {
+                    name: PORT.to_string(),
+                    value: self.port.to_string(),
+                },
+            ],
+        })])
+    }
+
+    /// Connect to the selected address, replacing any current connection
+    pub async fn connect(&mut self) -> Result<()> {
+        self.stream = None;
+        let mut attempt = 1;
+        loop {
+            let connecting = TcpStream::connect((self.host.as_str(), self.port));
+            let error = match tokio::time::timeout(self.timeout, connecting).await {
+                Ok(Ok(stream)) => {
+                    stream.set_nodelay(true)?;
+                    self.stream = Some(BufReader::new(stream));
+                    return Ok(());
+                }
+                Ok(Err(e)) => Error::Io(e),
+                Err(_) => Error::Timeout(format!(
+                    "Connecting to {}:{} took longer than {:?}",
+                    self.host, self.port, self.timeout
+                )),
+            };
+            if attempt >= self.attempts {
+                return Err(error);
+            }
+            debug!(
+                "Connecting {} to {}:{} failed, attempt {} of {}: {}",
+                self.device, self.host, self.port, attempt, self.attempts, error
+            );
+            attempt += 1;
+            tokio::time::sleep(self.retry_delay).await;
+        }
+    }
+
+    /// Close the connection
+    pub fn disconnect(&mut self) {
+        self.stream = None;
+    }
+
+    /// The connection, for protocols [`TcpConnection::send`] and
+    /// [`TcpConnection::exchange`] do not fit
+    pub fn stream(&mut self) -> Option<&mut BufReader<TcpStream>> {
+        self.stream.as_mut()
+    }
+
+    /// Send `command` without waiting for a reply
+    pub async fn send(&mut self, command: &[u8]) -> Result<()> {
+        match self.try_send(command).await {
+            Err(Error::Io(e)) if reconnectable(&e) => {
+                warn!("Connection of {} lost, reconnecting: {}", self.device, e);
+                self.connect().await?;
+                self.try_send(command).await
+            }
+            result => result,
+        }
+    }
+
+    /// Send `command` and read the reply up to and including `terminator`,
+    /// such as `#` for LX200 style protocols
+    pub async fn exchange(&mut self, command: &[u8], terminator: u8) -> Result<Vec<u8>> {
+        match self.try_exchange(command, terminator).await {
+            Err(Error::Io(e)) if reconnectable(&e) => {
+                warn!("Connection of {} lost, reconnecting: {}", self.device, e);
+                self.connect().await?;
+                self.try_exchange(command, terminator).await
+            }
+            result => result,
+        }
+    }
+
+    async fn try_send(&mut self, command: &[u8]) -> Result<()> {
+        let stream = self.connected()?;
+        let sent = async {
+            stream.get_mut().write_all(command).await?;
+            stream.get_mut().flush().await
+        };
+        if let Err(e) = sent.await {
+            self.stream = None;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    async fn try_exchange(&mut self, command: &[u8], terminator: u8) -> Result<Vec<u8>> {
+        self.try_send(command).await?;
+        let timeout = self.timeout;
+        let stream = self.connected()?;
+        let mut reply = Vec::new();
+        let read =
+            match tokio::time::timeout(timeout, stream.read_until(terminator, &mut reply)).await {
+                Ok(read) => read,
+                Err(_) => {
+                    return Err(Error::Timeout(format!(
+                        "No reply from {} within {:?}",
+                        self.device, timeout
+                    )))
+                }
+            };
+        match read {
+            Ok(0) => {
+                self.stream = None;
+                Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into())
+            }
+            Ok(_) => Ok(reply),
+            Err(e) => {
+                self.stream = None;
+                Err(e.into())
+            }
+        }
+    }
+
+    fn connected(&mut self) -> Result<&mut BufReader<TcpStream>> {
+        self.stream
+            .as_mut()
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotConnected).into())
+    }
+}
+
+/// Whether `e` means the device dropped the connection
+fn reconnectable(e: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        e.kind(),
+        ErrorKind::BrokenPipe
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::UnexpectedEof
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_tcp_connection_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Answers one command per connection, then hangs up
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut command = [0u8; 4];
+                socket.read_exact(&mut command).await.unwrap();
+                socket.write_all(b"OK#").await.unwrap();
+            }
+        });
+
+        let mut tcp = TcpConnection::new("Mount", "localhost", 1);
+        let update = NewTextVector {
+            device: "Mount".to_string(),
+            name: DEVICE_ADDRESS.to_string(),
+            timestamp: None,
+            elements: vec![
+                OneText {
+                    name: ADDRESS.to_string(),
+                    value: "127.0.0.1".to_string(),
+                },
+                OneText {
+                    name: PORT.to_string(),
+                    value: port.to_string(),
+                },
+            ],
+        };
+        assert!(tcp.on_new_text(&update).is_some());
+        assert_eq!(tcp.port(), port);
+
+        tcp.connect().await.unwrap();
+        assert_eq!(tcp.exchange(b":GR#", b'#').await.unwrap(), b"OK#");
+        // The device hung up after answering, the next exchange reconnects
+        assert_eq!(tcp.exchange(b":GD#", b'#').await.unwrap(), b"OK#");
+        assert!(tcp.is_connected());
+    }
+}
@@ -0,0 +1,630 @@
+//! Drivers speaking the indilib driver protocol over stdin and stdout
+//!
+//! indiserver launches a driver executable and exchanges XML with it over
+//! pipes: `getProperties` and `new*Vector` arrive on stdin, `def*Vector`,
+//! `set*Vector` and `message` go out on stdout. [`run`] hosts an
+//! [`IndiDriver`] that way, so a driver written in Rust can be served by
+//! any indiserver, including [`Server::add_driver`](crate::server::Server::add_driver).
+//! The same driver can also run inside this crate's server with
+//! [`Server::register_driver`](crate::server::Server::register_driver).
+//!
+//! stdout carries the protocol, so logs must go to stderr.
+//!
+//! ```no_run
+//! # use indi_rs::driver::IndiDriver;
+//! # use indi_rs::message::MessageType;
+//! struct Focuser;
+//!
+//! #[async_trait::async_trait]
+//! impl IndiDriver for Focuser {
+//!     async fn define_properties(&mut self) -> Vec<MessageType> {
+//!         Vec::new()
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() -> indi_rs::Result<()> {
+//!     indi_rs::driver::run(Focuser).await
+//! }
+//! ```
+
+/// Standard `CONFIG_PROCESS` handling and indilib configuration files
+pub mod config;
+/// Standard `CONNECTION` handling and connections to the hardware
+pub mod connection;
+pub(crate) mod snoop;
+mod timer;
+
+pub(crate) use snoop::Snoops;
+pub use timer::TimerId;
+pub(crate) use timer::{Callback, Timers};
+
+use crate::client::definition_key;
+use crate::error::Result;
+use crate::message::basic::GetProperties;
+use crate::message::new::{NewBlobVector, NewNumberVector, NewSwitchVector, NewTextVector};
+use crate::message::stream::Framer;
+use crate::message::MessageType;
+use crate::property::timestamp::TimestampPolicy;
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// Bytes read from stdin at once
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Device driver
+///
+/// Hosted over stdin and stdout by [`run`], or inside the server by
+/// [`Server::register_driver`](crate::server::Server::register_driver).
+/// The host stores the definitions the driver publishes and answers
+/// `getProperties` from them, routes client updates of its devices to the
+/// `on_new_*` handlers and calls [`IndiDriver::poll`] every
+/// [`IndiDriver::poll_interval`]. Every message a method returns is
+/// published as if an external driver had written it.
+#[async_trait]
+pub trait IndiDriver: Send + 'static {
+    /// Receive the handle for publishing messages and adding timers
+    /// outside the other methods, called once before
+    /// [`IndiDriver::define_properties`]
+    fn attach(&mut self, _driver: Driver) {}
+
+    /// Definitions of the driver's properties, published when it starts
+    async fn define_properties(&mut self) -> Vec<MessageType>;
+
+    /// Handle a client's `newTextVector`
+    async fn on_new_text(&mut self, _update: NewTextVector) -> Vec<MessageType> {
+        Vec::new()
+    }
+
+    /// Handle a client's `newNumberVector`
+    async fn on_new_number(&mut self, _update: NewNumberVector) -> Vec<MessageType> {
+        Vec::new()
+    }
+
+    /// Handle a client's `newSwitchVector`
+    async fn on_new_switch(&mut self, _update: NewSwitchVector) -> Vec<MessageType> {
+        Vec::new()
+    }
+
+    /// Handle a client's `newBLOBVector`
+    async fn on_new_blob(&mut self, _update: NewBlobVector) -> Vec<MessageType> {
+        Vec::new()
+    }
+
+    /// Time between calls of [`IndiDriver::poll`], `None` to never poll
+    fn poll_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Stamping of published definitions and updates that carry no
+    /// timestamp, whole seconds by default like indilib drivers
+    fn timestamp_policy(&self) -> TimestampPolicy {
+        TimestampPolicy::Seconds
+    }
+
+    /// Periodic work, such as reading the hardware state
+    async fn poll(&mut self) -> Vec<MessageType> {
+        Vec::new()
+    }
+
+    /// Handle a timer added with [`Driver::add_timer`] that expired
+    async fn on_timer(&mut self, _id: TimerId) -> Vec<MessageType> {
+        Vec::new()
+    }
+
+    /// Handle a definition, update, deletion or message of another device
+    /// requested with [`Driver::snoop`]
+    async fn on_snoop(&mut self, _message: MessageType) -> Vec<MessageType> {
+        Vec::new()
+    }
+}
+
+/// Request from a [`Driver`] handle to its host
+#[derive(Debug)]
+pub(crate) enum Command {
+    Publish(MessageType),
+    AddTimer(TimerId, Duration, Option<Callback>),
+    RemoveTimer(TimerId),
+    Snoop(String, Option<String>),
+}
+
+/// Handle of a running driver, given to [`IndiDriver::attach`]
+///
+/// Publishes messages from background tasks, such as telemetry read from
+/// the hardware, schedules one-shot timers, runs tasks for as long as the
+/// driver and follows other devices.
+#[derive(Debug, Clone)]
+pub struct Driver {
+    commands: mpsc::UnboundedSender<Command>,
+    next_timer: Arc<AtomicU64>,
+}
+
+impl Driver {
+    pub(crate) fn new() -> (Self, mpsc::UnboundedReceiver<Command>) {
+        let (commands, received) = mpsc::unbounded_channel();
+        let driver = Self {
+            commands,
+            next_timer: Arc::new(AtomicU64::new(1)),
+        };
+        (driver, received)
+    }
+
+    /// Publish `message` as if an [`IndiDriver`] method had returned it
+    ///
+    /// Dropped once the driver stopped.
+    pub fn publish(&self, message: MessageType) {
+        let _ = self.commands.send(Command::Publish(message));
+    }
+
+    /// Call [`IndiDriver::on_timer`] once `delay` has passed
+    pub fn add_timer(&self, delay: Duration) -> TimerId {
+        self.schedule(delay, None)
+    }
+
+    /// Call `callback` once `delay` has passed and publish the messages it
+    /// returns, like `IEAddTimer` of indilib
+    ///
+    /// The callback runs in the driver's event loop, in between the
+    /// [`IndiDriver`] methods.
+    pub fn add_callback(
+        &self,
+        delay: Duration,
+        callback: impl FnOnce() -> Vec<MessageType> + Send + 'static,
+    ) -> TimerId {
+        self.schedule(delay, Some(Callback::new(callback)))
+    }
+
+    fn schedule(&self, delay: Duration, callback: Option<Callback>) -> TimerId {
+        let id = TimerId(self.next_timer.fetch_add(1, Ordering::Relaxed));
+        let _ = self.commands.send(Command::AddTimer(id, delay, callback));
+        id
+    }
+
+    /// Cancel a timer that has not expired yet
+    pub fn remove_timer(&self, id: TimerId) {
+        let _ = self.commands.send(Command::RemoveTimer(id));
+    }
+
+    /// Run `task` in the background until it completes or the driver stops
+    ///
+    /// For work that does not fit a timer, such as streaming telemetry from
+    /// the hardware and publishing it with a clone of this handle:
+    ///
+    /// ```no_run
+    /// # use indi_rs::driver::Driver;
+    /// # use std::time::Duration;
+    /// # fn read_temperature() -> indi_rs::message::MessageType { unimplemented!() }
+    /// # fn attach(driver: Driver) {
+    /// let publisher = driver.clone();
+    /// driver.spawn_task(async move {
+    ///     let mut interval = tokio::time::interval(Duration::from_secs(1));
+    ///     loop {
+    ///         interval.tick().await;
+    ///         publisher.publish(read_temperature());
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub fn spawn_task<F>(&self, task: F) -> JoinHandle<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let commands = self.commands.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                () = task => {}
+                () = commands.closed() => debug!("Driver stopped, ending its task"),
+            }
+        })
+    }
+
+    /// Follow the properties of another device, or only its property
+    /// `property`, like `IDSnoopDevice` of indilib
+    ///
+    /// Their current definitions and all later updates, deletions and
+    /// messages are passed to [`IndiDriver::on_snoop`]. BLOBs are not
+    /// snooped.
+    pub fn snoop(&self, device: &str, property: Option<&str>) {
+        let _ = self.commands.send(Command::Snoop(
+            device.to_string(),
+            property.map(str::to_string),
+        ));
+    }
+}
+
+/// Current definitions of a driver, in the order they were published, with
+/// the values of later updates merged in
+#[derive(Debug, Default)]
+pub(crate) struct Definitions(Vec<MessageType>);
+
+impl Definitions {
+    /// Follow a published message, returns true if it defines a property
+    /// that was not defined before
+    pub(crate) fn remember(&mut self, message: &MessageType) -> bool {
+        if let Some(key) = definition_key(message) {
+            let defined = self
+                .0
+                .iter_mut()
+                .find(|definition| definition_key(definition).as_ref() == Some(&key));
+            match defined {
+                Some(definition) => *definition = message.clone(),
+                None => {
+                    self.0.push(message.clone());
+                    return true;
+                }
+            }
+        } else if let MessageType::DelProperty(del) = message {
+            self.0.retain(|definition| {
+                definition.device() != Some(del.device.as_str())
+                    || del
+                        .name
+                        .as_deref()
+                        .is_some_and(|name| definition.name() != Some(name))
+            });
+        } else if message.kind().is_set() {
+            let defined = self.0.iter_mut().find(|definition| {
+                definition.device() == message.device() && definition.name() == message.name()
+            });
+            if let Some(definition) = defined {
+                definition.apply_set(message);
+            }
+        }
+        false
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &MessageType> {
+        self.0.iter()
+    }
+}
+
+/// Writes the driver's messages and keeps its current definitions
+struct Output<W> {
+    writer: W,
+    definitions: Definitions,
+    timestamp_policy: TimestampPolicy,
+    buf: Vec<u8>,
+}
+
+impl<W: AsyncWrite + Unpin> Output<W> {
+    async fn publish(&mut self, messages: Vec<MessageType>) -> Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        for mut message in messages {
+            message.stamp(self.timestamp_policy);
+            self.definitions.remember(&message);
+            self.write(&message).await?;
+        }
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    async fn write(&mut self, message: &MessageType) -> Result<()> {
+        self.buf.clear();
+        message.write_xml(&mut self.buf)?;
+        self.buf.push(b'\n');
+        self.writer.write_all(&self.buf).await?;
+        Ok(())
+    }
+
+    /// Answer `getProperties` with the matching definitions
+    async fn replay(&mut self, get: &GetProperties) -> Result<()> {
+        let matching = self
+            .definitions
+            .iter()
+            .filter(|definition| {
+                get.device.is_none() || definition.device() == get.device.as_deref()
+            })
+            .filter(|definition| get.name.is_none() || definition.name() == get.name.as_deref())
+            .cloned()
+            .collect::<Vec<_>>();
+        for definition in &matching {
+            self.write(definition).await?;
+        }
+        self.writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Host `driver` over stdin and stdout until stdin is closed
+///
+/// This is how indiserver runs driver executables, see the
+/// [module documentation](self).
+pub async fn run(driver: impl IndiDriver) -> Result<()> {
+    run_on(driver, tokio::io::stdin(), tokio::io::stdout()).await
+}
+
+/// Host `driver`, reading client messages from `input` and writing its
+/// own to `output`, until `input` is closed
+pub async fn run_on<D, R, W>(mut driver: D, mut input: R, output: W) -> Result<()>
+where
+    D: IndiDriver,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let (handle, mut commands) = Driver::new();
+    driver.attach(handle);
+    let mut output = Output {
+        writer: output,
+        definitions: Definitions::default(),
+        timestamp_policy: driver.timestamp_policy(),
+        buf: Vec::new(),
+    };
+    let definitions = driver.define_properties().await;
+    output.publish(definitions).await?;
+
+    let mut ticker = driver.poll_interval().map(|interval| {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        ticker
+    });
+    let mut timers = Timers::default();
+    let mut snoops = Snoops::default();
+    let mut framer = Framer::new();
+    let mut chunk = vec![0u8; READ_CHUNK_SIZE];
+    loop {
+        while let Some(frame) = framer.next_frame() {
+            let message = match MessageType::from_slice(&frame) {
+                Ok(message) => message,
+                Err(e) => {
+                    debug!("Ignoring unparsable input: {}", e);
+                    continue;
+                }
+            };
+            let messages = match message {
+                MessageType::GetProperties(get) => {
+                    output.replay(&get).await?;
+                    continue;
+                }
+                MessageType::NewTextVector(update) => driver.on_new_text(update).await,
+                MessageType::NewNumberVector(update) => driver.on_new_number(update).await,
+                MessageType::NewSwitchVector(update) => driver.on_new_switch(update).await,
+                MessageType::NewBlobVector(update) => driver.on_new_blob(update).await,
+                message if snoops.matches(&message) => driver.on_snoop(message).await,
+                _ => continue,
+            };
+            output.publish(messages).await?;
+        }
+
+        let messages = tokio::select! {
+            read = input.read(&mut chunk) => match read? {
+                0 => break,
+                read => {
+                    framer.push(&chunk[..read]);
+                    continue;
+                }
+            },
+            Some(command) = commands.recv() => match command {
+                Command::Publish(message) => vec![message],
+                Command::AddTimer(id, delay, callback) => {
+                    timers.add(id, delay, callback);
+                    continue;
+                }
+                Command::RemoveTimer(id) => {
+                    timers.remove(id);
+                    continue;
+                }
+                Command::Snoop(device, name) => {
+                    snoops.add(device.clone(), name.clone());
+                    // indiserver answers with the current definitions
+                    // and forwards later messages of the device
+                    let get = GetProperties {
+                        version: crate::PROTOCOL_VERSION.to_string(),
+                        device: Some(device),
+                        name,
+                    };
+                    vec![MessageType::GetProperties(get)]
+                }
+            },
+            (id, callback) = timers.next() => match callback {
+                Some(callback) => callback.call(),
+                None => driver.on_timer(id).await,
+            },
+            Some(_) = async { Some(ticker.as_mut()?.tick().await) } => driver.poll().await,
+        };
+        output.publish(messages).await?;
+    }
+    debug!("Driver input closed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use tokio::io::{AsyncBufReadExt, BufReader, DuplexStream, Lines};
+
+    /// Driver with a `Rusty.POWER` switch that reports `Rusty.TIMER` when
+    /// its timer expires
+    #[derive(Default)]
+    struct RustyDriver {
+        driver: Option<Driver>,
+    }
+
+    #[async_trait]
+    impl IndiDriver for RustyDriver {
+        fn attach(&mut self, driver: Driver) {
+            self.driver = Some(driver);
+        }
+
+        async fn define_properties(&mut self) -> Vec<MessageType> {
+            vec![MessageType::from_str(
+                r#"<defSwitchVector device="Rusty" name="POWER" state="Idle" perm="rw" rule="OneOfMany"><defSwitch name="ON">Off</defSwitch><defSwitch name="OFF">On</defSwitch></defSwitchVector>"#,
+            )
+            .unwrap()]
+        }
+
+        async fn on_new_switch(&mut self, _update: NewSwitchVector) -> Vec<MessageType> {
+            if let Some(driver) = &self.driver {
+                driver.add_timer(Duration::from_millis(10));
+            }
+            vec![MessageType::from_str(
+                r#"<setSwitchVector device="Rusty" name="POWER" state="Ok"><oneSwitch name="ON">On</oneSwitch><oneSwitch name="OFF">Off</oneSwitch></setSwitchVector>"#,
+            )
+            .unwrap()]
+        }
+
+        async fn on_timer(&mut self, id: TimerId) -> Vec<MessageType> {
+            vec![MessageType::from_str(&format!(
+                r#"<message device="Rusty" message="Timer {} expired"/>"#,
+                id.0
+            ))
+            .unwrap()]
+        }
+    }
+
+    async fn next(lines: &mut Lines<BufReader<DuplexStream>>) -> String {
+        tokio::time::timeout(Duration::from_secs(1), lines.next_line())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_run_on() {
+        let (mut server, input) = tokio::io::duplex(4096);
+        let (output, from_driver) = tokio::io::duplex(4096);
+        let driver = tokio::spawn(run_on(RustyDriver::default(), input, output));
+        let mut lines = BufReader::new(from_driver).lines();
+
+        // Stamped in the INDI format, which has no zone
+        let definition = next(&mut lines).await;
+        let Ok(MessageType::DefSwitchVector(def)) = MessageType::from_str(&definition) else {
+            panic!("Expected a switch definition: {}", definition);
+        };
+        assert_eq!(def.timestamp.len(), "2025-02-16T03:06:48".len());
+        server
+            .write_all(br#"<newSwitchVector device="Rusty" name="POWER"><oneSwitch name="ON">On</oneSwitch></newSwitchVector>"#)
+            .await
+            .unwrap();
+        assert!(next(&mut lines).await.starts_with("<setSwitchVector"));
+        assert!(next(&mut lines).await.contains("Timer 1 expired"));
+
+        // Definitions are replayed with the current values
+        server
+            .write_all(br#"<getProperties version="1.7" device="Rusty"/>"#)
+            .await
+            .unwrap();
+        let definition = next(&mut lines).await;
+        assert!(definition.starts_with("<defSwitchVector"));
+        assert!(definition.contains(r#"state="Ok""#));
+        let Ok(MessageType::DefSwitchVector(def)) = MessageType::from_str(&definition) else {
+            panic!("Expected a switch definition: {}", definition);
+        };
+        assert_eq!(def.switches[0].state, crate::property::SwitchState::On);
+
+        drop(server);
+        tokio::time::timeout(Duration::from_secs(1), driver)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    /// Driver following the coordinates of `Mount`
+    struct SnoopingDriver;
+
+    #[async_trait]
+    impl IndiDriver for SnoopingDriver {
+        fn attach(&mut self, driver: Driver) {
+            driver.snoop("Mount", Some("EQUATORIAL_EOD_COORD"));
+        }
+
+        async fn define_properties(&mut self) -> Vec<MessageType> {
+            Vec::new()
+        }
+
+        async fn on_snoop(&mut self, message: MessageType) -> Vec<MessageType> {
+            vec![MessageType::from_str(&format!(
+                r#"<message device="Dome" message="Snooped {}"/>"#,
+                message.name().unwrap_or_default()
+            ))
+            .unwrap()]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_on_snoop() {
+        let (mut server, input) = tokio::io::duplex(4096);
+        let (output, from_driver) = tokio::io::duplex(4096);
+        let driver = tokio::spawn(run_on(SnoopingDriver, input, output));
+        let mut lines = BufReader::new(from_driver).lines();
+
+        let get = next(&mut lines).await;
+        assert!(get.starts_with("<getProperties"));
+        assert!(get.contains(r#"device="Mount""#));
+        assert!(get.contains(r#"name="EQUATORIAL_EOD_COORD""#));
+
+        // Only the snooped property is passed on
+        server
+            .write_all(br#"<setNumberVector device="Mount" name="TARGET_EOD_COORD" state="Ok"><oneNumber name="RA">1</oneNumber></setNumberVector>"#)
+            .await
+            .unwrap();
+        server
+            .write_all(br#"<setNumberVector device="Mount" name="EQUATORIAL_EOD_COORD" state="Ok"><oneNumber name="RA">2</oneNumber></setNumberVector>"#)
+            .await
+            .unwrap();
+        assert!(next(&mut lines)
+            .await
+            .contains("Snooped EQUATORIAL_EOD_COORD"));
+
+        drop(server);
+        tokio::time::timeout(Duration::from_secs(1), driver)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    /// Driver publishing from a callback timer and a background task
+    struct BackgroundDriver;
+
+    #[async_trait]
+    impl IndiDriver for BackgroundDriver {
+        fn attach(&mut self, driver: Driver) {
+            driver.add_callback(Duration::from_millis(10), || {
+                vec![
+                    MessageType::from_str(r#"<message device="Rusty" message="Callback"/>"#)
+                        .unwrap(),
+                ]
+            });
+            let publisher = driver.clone();
+            driver.spawn_task(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                publisher.publish(
+                    MessageType::from_str(r#"<message device="Rusty" message="Task"/>"#).unwrap(),
+                );
+            });
+        }
+
+        async fn define_properties(&mut self) -> Vec<MessageType> {
+            Vec::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_callbacks_and_tasks() {
+        let (server, input) = tokio::io::duplex(4096);
+        let (output, from_driver) = tokio::io::duplex(4096);
+        let driver = tokio::spawn(run_on(BackgroundDriver, input, output));
+        let mut lines = BufReader::new(from_driver).lines();
+
+        assert!(next(&mut lines).await.contains(r#"message="Callback""#));
+        assert!(next(&mut lines).await.contains(r#"message="Task""#));
+
+        drop(server);
+        tokio::time::timeout(Duration::from_secs(1), driver)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,49 @@
+use crate::message::{MessageKind, MessageType};
+
+/// Device and property a snooping driver may receive `message` for
+///
+/// BLOBs are not snooped, they are left to clients that enable them.
+pub(crate) fn snooped(message: &MessageType) -> Option<(&str, Option<&str>)> {
+    let snooped = match message.kind() {
+        MessageKind::DefBlobVector | MessageKind::SetBlobVector => false,
+        MessageKind::DelProperty | MessageKind::Message => true,
+        kind => kind.is_definition() || kind.is_set(),
+    };
+    if !snooped {
+        return None;
+    }
+    Some((message.device()?, message.name()))
+}
+
+/// Whether a subscription to `device`, or its property `name`, covers a
+/// message about `snooped`
+pub(crate) fn covers(
+    (device, name): (&str, Option<&str>),
+    (snooped_device, snooped_name): (&str, Option<&str>),
+) -> bool {
+    device == snooped_device && (name.is_none() || snooped_name.is_none() || name == snooped_name)
+}
+
+/// Devices and properties a driver snoops on
+#[derive(Debug, Default)]
+pub(crate) struct Snoops {
+    subscriptions: Vec<(String, Option<String>)>,
+}
+
+impl Snoops {
+    pub(crate) fn add(&mut self, device: String, name: Option<String>) {
+        if !self.subscriptions.contains(&(device.clone(), name.clone())) {
+            self.subscriptions.push((device, name));
+        }
+    }
+
+    /// Whether `message` should be delivered to [`IndiDriver::on_snoop`](super::IndiDriver::on_snoop)
+    pub(crate) fn matches(&self, message: &MessageType) -> bool {
+        let Some(snooped) = snooped(message) else {
+            return false;
+        };
+        self.subscriptions
+            .iter()
+            .any(|(device, name)| covers((device, name.as_deref()), snooped))
+    }
+}
@@ -0,0 +1,63 @@
+use crate::message::MessageType;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Timer added with [`Driver::add_timer`](super::Driver::add_timer)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TimerId(pub(crate) u64);
+
+/// Function run when a timer added with
+/// [`Driver::add_callback`](super::Driver::add_callback) expires
+pub(crate) struct Callback(Box<dyn FnOnce() -> Vec<MessageType> + Send>);
+
+impl Callback {
+    pub(crate) fn new(callback: impl FnOnce() -> Vec<MessageType> + Send + 'static) -> Self {
+        Self(Box::new(callback))
+    }
+
+    pub(crate) fn call(self) -> Vec<MessageType> {
+        (self.0)()
+    }
+}
+
+impl std::fmt::Debug for Callback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Callback")
+    }
+}
+
+/// One-shot timers of a driver event loop
+#[derive(Debug, Default)]
+pub(crate) struct Timers {
+    pending: Vec<(Instant, TimerId)>,
+    /// Callbacks of the pending timers that have one
+    callbacks: HashMap<TimerId, Callback>,
+}
+
+impl Timers {
+    pub(crate) fn add(&mut self, id: TimerId, delay: Duration, callback: Option<Callback>) {
+        self.pending.push((Instant::now() + delay, id));
+        if let Some(callback) = callback {
+            self.callbacks.insert(id, callback);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, id: TimerId) {
+        self.pending.retain(|(_, pending)| *pending != id);
+        self.callbacks.remove(&id);
+    }
+
+    /// Wait for the earliest timer and remove it, along with its callback
+    ///
+    /// Never resolves without timers. Cancel safe, a timer is only removed
+    /// once it has expired.
+    pub(crate) async fn next(&mut self) -> (TimerId, Option<Callback>) {
+        let Some(&(deadline, id)) = self.pending.iter().min() else {
+            return std::future::pending().await;
+        };
+        tokio::time::sleep_until(deadline).await;
+        self.pending.retain(|(_, pending)| *pending != id);
+        (id, self.callbacks.remove(&id))
+    }
+}
@@ -0,0 +1,2 @@
+/// Simulated devices for testing clients without hardware
+pub mod simulator;
@@ -0,0 +1,662 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use tokio::time::Instant;
+
+use crate::devices::FrameType;
+use crate::driver::IndiDriver;
+use crate::message::new::{NewNumberVector, NewSwitchVector, OneBlob};
+use crate::message::set::SetBlobVector;
+use crate::message::MessageType;
+use crate::property::timestamp::INDITimestamp;
+use crate::property::{PropertyPerm, PropertyState, SwitchRule};
+
+use super::{
+    blob_vector, connect, connection, driver_info, number_element, number_vector, set_number,
+    set_switch, switch_on, switch_vector, Noise, CONNECTION,
+};
+
+/// Interface bit of a CCD in `DRIVER_INTERFACE`
+const CCD_INTERFACE: u32 = 2;
+/// Time between updates of the exposure countdown and the sensor temperature
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Ambient temperature the sensor warms up to, in °C
+const AMBIENT: f64 = 20.0;
+/// Rate the sensor temperature follows the setpoint, in °C per second
+const COOLING_RATE: f64 = 1.0;
+/// Offset of every pixel, in ADU
+const BIAS: f64 = 1000.0;
+/// Read noise, in ADU
+const READ_NOISE: f64 = 8.0;
+/// Sky background of light frames, in ADU per second
+const SKY: f64 = 40.0;
+/// Level of flat frames, in ADU
+const FLAT_LEVEL: f64 = 30000.0;
+/// Number of stars in the simulated field
+const STARS: usize = 40;
+/// Width of the simulated stars, in unbinned pixels
+const SEEING: f64 = 1.8;
+/// Size of a FITS block
+const FITS_BLOCK: usize = 2880;
+
+/// Exposure in progress
+#[derive(Debug, Clone)]
+struct Exposure {
+    duration: f64,
+    ends: Instant,
+    /// Whole seconds left at the last countdown update
+    reported: u64,
+}
+
+/// Star of the simulated field, in unbinned pixel coordinates
+#[derive(Debug, Clone)]
+struct Star {
+    x: f64,
+    y: f64,
+    /// Peak in ADU per second
+    flux: f64,
+}
+
+/// Simulated CCD camera
+///
+/// Defines the standard `CCD_EXPOSURE`, `CCD_ABORT_EXPOSURE`,
+/// `CCD_FRAME_TYPE`, `CCD_BINNING`, `CCD_INFO`, `CCD_TEMPERATURE`,
+/// `CCD_COOLER_POWER` and `CCD1` properties. Exposures count down in real
+/// time and end with a 16 bit FITS image of a fixed star field with sky
+/// background, dark current and read noise, according to the frame type.
+/// Works with [`Camera`](crate::devices::Camera).
+#[derive(Debug, Clone)]
+pub struct CcdSimulator {
+    device: String,
+    width: u32,
+    height: u32,
+    pixel_size: f64,
+    connected: bool,
+    frame_type: FrameType,
+    binning: (u32, u32),
+    exposure: Option<Exposure>,
+    temperature: f64,
+    setpoint: f64,
+    stars: Vec<Star>,
+    noise: Noise,
+    last_poll: Instant,
+}
+
+impl Default for CcdSimulator {
+    fn default() -> Self {
+        Self::new("CCD Simulator")
+    }
+}
+
+impl CcdSimulator {
+    /// Create a connected 1280×1024 camera named `device`
+    pub fn new(device: impl Into<String>) -> Self {
+        let mut simulator = Self {
+            device: device.into(),
+            width: 1280,
+            height: 1024,
+            pixel_size: 5.2,
+            connected: true,
+            frame_type: FrameType::Light,
+            binning: (1, 1),
+            exposure: None,
+            temperature: AMBIENT,
+            setpoint: AMBIENT,
+            stars: Vec::new(),
+            noise: Noise::new(0x5EED),
+            last_poll: Instant::now(),
+        };
+        simulator.scatter_stars();
+        simulator
+    }
+
+    /// Sets the sensor size in pixels
+    pub fn with_resolution(mut self, width: u32, height: u32) -> Self {
+        self.width = width.max(1);
+        self.height = height.max(1);
+        self.scatter_stars();
+        self
+    }
+
+    /// Device name
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    fn scatter_stars(&mut self) {
+        let mut field = Noise::new(0xF1E1D);
+        self.stars = (0..STARS)
+            .map(|_| Star {
+                x: field.uniform() * self.width as f64,
+                y: field.uniform() * self.height as f64,
+                // Few bright stars, many faint ones
+                flux: 2000.0 * field.uniform().powi(3) + 50.0,
+            })
+            .collect();
+    }
+
+    fn start_exposure(&mut self, update: &NewNumberVector) -> MessageType {
+        let duration = number_element(&update.elements, "CCD_EXPOSURE_VALUE");
+        let failure = if !self.connected {
+            Some(format!("{} is not connected", self.device))
+        } else if !duration.is_some_and(|duration| (0.0..=3600.0).contains(&duration)) {
+            Some("Exposure time must be between 0 and 3600 s".to_string())
+        } else {
+            None
+        };
+        let Some(duration) = duration.filter(|_| failure.is_none()) else {
+            self.exposure = None;
+            return self.exposure_state(PropertyState::Alert, failure, 0.0);
+        };
+        self.exposure = Some(Exposure {
+            duration,
+            ends: Instant::now() + Duration::from_secs_f64(duration),
+            reported: duration.ceil() as u64,
+        });
+        self.exposure_state(PropertyState::Busy, None, duration)
+    }
+
+    fn exposure_state(
+        &self,
+        state: PropertyState,
+        message: Option<String>,
+        remaining: f64,
+    ) -> MessageType {
+        set_number(
+            &self.device,
+            "CCD_EXPOSURE",
+            state,
+            message,
+            &[("CCD_EXPOSURE_VALUE", remaining)],
+        )
+    }
+
+    /// Countdown and completion of the exposure in progress
+    fn advance_exposure(&mut self) -> Vec<MessageType> {
+        let Some(exposure) = &mut self.exposure else {
+            return Vec::new();
+        };
+        let remaining = exposure.ends.saturating_duration_since(Instant::now());
+        if !remaining.is_zero() {
+            let seconds = remaining.as_secs_f64().ceil() as u64;
+            if seconds == exposure.reported {
+                return Vec::new();
+            }
+            exposure.reported = seconds;
+            return vec![self.exposure_state(PropertyState::Busy, None, seconds as f64)];
+        }
+        let duration = exposure.duration;
+        self.exposure = None;
+        let image = self.image(duration);
+        vec![
+            self.exposure_state(PropertyState::Ok, None, 0.0),
+            MessageType::SetBlobVector(SetBlobVector {
+                device: self.device.clone(),
+                name: "CCD1".to_string(),
+                state: Some(PropertyState::Ok),
+                timeout: None,
+                timestamp: Some(INDITimestamp::now().to_string()),
+                message: None,
+                elements: vec![OneBlob::new("CCD1", ".fits", &image)],
+            }),
+        ]
+    }
+
+    /// Sensor temperature following the setpoint
+    fn advance_temperature(&mut self, elapsed: f64) -> Vec<MessageType> {
+        let difference = self.setpoint - self.temperature;
+        if difference == 0.0 {
+            return Vec::new();
+        }
+        let step = COOLING_RATE * elapsed;
+        let state = if difference.abs() <= step {
+            self.temperature = self.setpoint;
+            PropertyState::Ok
+        } else {
+            self.temperature += step.copysign(difference);
+            PropertyState::Busy
+        };
+        vec![
+            set_number(
+                &self.device,
+                "CCD_TEMPERATURE",
+                state,
+                None,
+                &[(
+                    "CCD_TEMPERATURE_VALUE",
+                    (self.temperature * 100.0).round() / 100.0,
+                )],
+            ),
+            set_number(
+                &self.device,
+                "CCD_COOLER_POWER",
+                PropertyState::Ok,
+                None,
+                &[("CCD_COOLER_VALUE", self.cooler_power())],
+            ),
+        ]
+    }
+
+    fn cooler_power(&self) -> f64 {
+        ((AMBIENT - self.setpoint) * 2.5).clamp(0.0, 100.0)
+    }
+
+    /// Simulated frame of `duration` seconds as a FITS file
+    fn image(&mut self, duration: f64) -> Vec<u8> {
+        let (bin_x, bin_y) = self.binning;
+        let width = (self.width / bin_x).max(1) as usize;
+        let height = (self.height / bin_y).max(1) as usize;
+        let binned = (bin_x * bin_y) as f64;
+        // Dark current doubles every 6 °C
+        let dark = 0.5 * 2f64.powf(self.temperature / 6.0) * binned;
+        let (background, exposed) = match self.frame_type {
+            FrameType::Bias => (0.0, 0.0),
+            FrameType::Dark => (dark * duration, 0.0),
+            FrameType::Flat => (FLAT_LEVEL + dark * duration, 0.0),
+            FrameType::Light => ((SKY * binned + dark) * duration, duration * binned),
+        };
+
+        let mut pixels = vec![BIAS + background; width * height];
+        if exposed > 0.0 {
+            let sigma_x = SEEING / bin_x as f64;
+            let sigma_y = SEEING / bin_y as f64;
+            for star in &self.stars {
+                let (cx, cy) = (star.x / bin_x as f64, star.y / bin_y as f64);
+                let reach = (4.0 * sigma_x.max(sigma_y)).ceil() as isize;
+                for dy in -reach..=reach {
+                    for dx in -reach..=reach {
+                        let x = cx as isize + dx;
+                        let y = cy as isize + dy;
+                        if x < 0 || y < 0 || x >= width as isize || y >= height as isize {
+                            continue;
+                        }
+                        let ux = (x as f64 - cx) / sigma_x;
+                        let uy = (y as f64 - cy) / sigma_y;
+                        pixels[y as usize * width + x as usize] +=
+                            star.flux * exposed * (-(ux * ux + uy * uy) / 2.0).exp();
+                    }
+                }
+            }
+        }
+
+        let mut data = Vec::with_capacity(width * height * 2 + FITS_BLOCK);
+        for pixel in pixels {
+            // Shot noise of the signal on top of the read noise
+            let signal = (pixel - BIAS).max(0.0);
+            let noisy = pixel + self.noise.gaussian() * (READ_NOISE.powi(2) + signal).sqrt();
+            let value = noisy.round().clamp(0.0, 65535.0) as i32 - 32768;
+            data.extend_from_slice(&(value as i16).to_be_bytes());
+        }
+        data.resize(data.len().div_ceil(FITS_BLOCK) * FITS_BLOCK, 0);
+
+        let mut fits = self.fits_header(width, height, duration);
+        fits.append(&mut data);
+        fits
+    }
+
+    fn fits_header(&self, width: usize, height: usize, duration: f64) -> Vec<u8> {
+        let image_type = match self.frame_type {
+            FrameType::Light => "Light Frame",
+            FrameType::Bias => "Bias Frame",
+            FrameType::Dark => "Dark Frame",
+            FrameType::Flat => "Flat Field",
+        };
+        let cards = [
+            fits_card("SIMPLE", "T"),
+            fits_card("BITPIX", "16"),
+            fits_card("NAXIS", "2"),
+            fits_card("NAXIS1", &width.to_string()),
+            fits_card("NAXIS2", &height.to_string()),
+            fits_card("BZERO", "32768"),
+            fits_card("BSCALE", "1"),
+            fits_card("EXPTIME", &format!("{:.3}", duration)),
+            fits_card("CCD-TEMP", &format!("{:.2}", self.temperature)),
+            fits_card("XBINNING", &self.binning.0.to_string()),
+            fits_card("YBINNING", &self.binning.1.to_string()),
+            fits_card(
+                "XPIXSZ",
+                &format!("{:.2}", self.pixel_size * self.binning.0 as f64),
+            ),
+            fits_card(
+                "YPIXSZ",
+                &format!("{:.2}", self.pixel_size * self.binning.1 as f64),
+            ),
+            fits_string_card("IMAGETYP", image_type),
+            fits_string_card("INSTRUME", &self.device),
+            fits_string_card(
+                "DATE-OBS",
+                &Utc::now().format("%Y-%m-%dT%H:%M:%S%.3f").to_string(),
+            ),
+            format!("{:<80}", "END"),
+        ];
+        let mut header = cards.concat().into_bytes();
+        header.resize(header.len().div_ceil(FITS_BLOCK) * FITS_BLOCK, b' ');
+        header
+    }
+}
+
+/// Fixed format FITS header card with a numeric or logical value
+fn fits_card(keyword: &str, value: &str) -> String {
+    format!("{:<8}= {:>20}{:50}", keyword, value, "")
+}
+
+/// FITS header card with a string value
+fn fits_string_card(keyword: &str, value: &str) -> String {
+    let value = format!("'{:<8}'", value.replace('\'', "''"));
+    format!("{:<80.80}", format!("{:<8}= {}", keyword, value))
+}
+
+#[async_trait]
+impl IndiDriver for CcdSimulator {
+    async fn define_properties(&mut self) -> Vec<MessageType> {
+        let device = self.device.as_str();
+        let frame_types = FrameType::ALL.map(|frame_type| {
+            (
+                frame_type.element(),
+                match frame_type {
+                    FrameType::Light => "Light",
+                    FrameType::Bias => "Bias",
+                    FrameType::Dark => "Dark",
+                    FrameType::Flat => "Flat",
+                },
+                frame_type == self.frame_type,
+            )
+        });
+        vec![
+            connection(device, self.connected),
+            driver_info(device, "ccd_simulator", CCD_INTERFACE),
+            number_vector(
+                device,
+                "CCD_INFO",
+                "CCD Information",
+                "Image Info",
+                PropertyPerm::Ro,
+                &[
+                    (
+                        "CCD_MAX_X",
+                        "Max. Width",
+                        "%4.0f",
+                        1.0,
+                        16000.0,
+                        0.0,
+                        self.width as f64,
+                    ),
+                    (
+                        "CCD_MAX_Y",
+                        "Max. Height",
+                        "%4.0f",
+                        1.0,
+                        16000.0,
+                        0.0,
+                        self.height as f64,
+                    ),
+                    (
+                        "CCD_PIXEL_SIZE",
+                        "Pixel size (um)",
+                        "%5.2f",
+                        1.0,
+                        40.0,
+                        0.0,
+                        self.pixel_size,
+                    ),
+                    (
+                        "CCD_PIXEL_SIZE_X",
+                        "Pixel size X",
+                        "%5.2f",
+                        1.0,
+                        40.0,
+                        0.0,
+                        self.pixel_size,
+                    ),
+                    (
+                        "CCD_PIXEL_SIZE_Y",
+                        "Pixel size Y",
+                        "%5.2f",
+                        1.0,
+                        40.0,
+                        0.0,
+                        self.pixel_size,
+                    ),
+                    (
+                        "CCD_BITSPERPIXEL",
+                        "Bits per pixel",
+                        "%3.0f",
+                        8.0,
+                        64.0,
+                        0.0,
+                        16.0,
+                    ),
+                ],
+            ),
+            number_vector(
+                device,
+                "CCD_EXPOSURE",
+                "Expose",
+                "Main Control",
+                PropertyPerm::Rw,
+                &[(
+                    "CCD_EXPOSURE_VALUE",
+                    "Duration (s)",
+                    "%5.2f",
+                    0.0,
+                    3600.0,
+                    1.0,
+                    1.0,
+                )],
+            ),
+            switch_vector(
+                device,
+                "CCD_ABORT_EXPOSURE",
+                "Abort",
+                "Main Control",
+                SwitchRule::AtMostOne,
+                &[("ABORT", "Abort", false)],
+            ),
+            switch_vector(
+                device,
+                "CCD_FRAME_TYPE",
+                "Frame Type",
+                "Image Settings",
+                SwitchRule::OneOfMany,
+                &frame_types,
+            ),
+            number_vector(
+                device,
+                "CCD_BINNING",
+                "Binning",
+                "Image Settings",
+                PropertyPerm::Rw,
+                &[
+                    (
+                        "HOR_BIN",
+                        "X",
+                        "%2.0f",
+                        1.0,
+                        4.0,
+                        1.0,
+                        self.binning.0 as f64,
+                    ),
+                    (
+                        "VER_BIN",
+                        "Y",
+                        "%2.0f",
+                        1.0,
+                        4.0,
+                        1.0,
+                        self.binning.1 as f64,
+                    ),
+                ],
+            ),
+            number_vector(
+                device,
+                "CCD_TEMPERATURE",
+                "Temperature",
+                "Main Control",
+                PropertyPerm::Rw,
+                &[(
+                    "CCD_TEMPERATURE_VALUE",
+                    "Temperature (C)",
+                    "%5.2f",
+                    -50.0,
+                    50.0,
+                    0.0,
+                    self.temperature,
+                )],
+            ),
+            number_vector(
+                device,
+                "CCD_COOLER_POWER",
+                "Cooling Power",
+                "Main Control",
+                PropertyPerm::Ro,
+                &[(
+                    "CCD_COOLER_VALUE",
+                    "Power (%)",
+                    "%3.0f",
+                    0.0,
+                    100.0,
+                    0.0,
+                    self.cooler_power(),
+                )],
+            ),
+            blob_vector(device, "CCD1", "Image Data", "Image Info"),
+        ]
+    }
+
+    async fn on_new_number(&mut self, update: NewNumberVector) -> Vec<MessageType> {
+        match update.name.as_str() {
+            "CCD_EXPOSURE" => vec![self.start_exposure(&update)],
+            "CCD_TEMPERATURE" => {
+                let Some(setpoint) = number_element(&update.elements, "CCD_TEMPERATURE_VALUE")
+                else {
+                    return Vec::new();
+                };
+                self.setpoint = setpoint.clamp(-50.0, AMBIENT);
+                vec![set_number(
+                    &self.device,
+                    "CCD_TEMPERATURE",
+                    PropertyState::Busy,
+                    None,
+                    &[("CCD_TEMPERATURE_VALUE", self.temperature)],
+                )]
+            }
+            "CCD_BINNING" => {
+                let bin = |name, current: u32| {
+                    number_element(&update.elements, name)
+                        .map_or(current, |value| value.round().clamp(1.0, 4.0) as u32)
+                };
+                self.binning = (
+                    bin("HOR_BIN", self.binning.0),
+                    bin("VER_BIN", self.binning.1),
+                );
+                vec![set_number(
+                    &self.device,
+                    "CCD_BINNING",
+                    PropertyState::Ok,
+                    None,
+                    &[
+                        ("HOR_BIN", self.binning.0 as f64),
+                        ("VER_BIN", self.binning.1 as f64),
+                    ],
+                )]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    async fn on_new_switch(&mut self, update: NewSwitchVector) -> Vec<MessageType> {
+        match update.name.as_str() {
+            CONNECTION => {
+                let message = connect(&self.device, &mut self.connected, &update.elements);
+                if !self.connected {
+                    self.exposure = None;
+                }
+                vec![message]
+            }
+            "CCD_FRAME_TYPE" => {
+                if let Some(frame_type) = FrameType::ALL
+                    .into_iter()
+                    .find(|frame_type| switch_on(&update.elements, frame_type.element()))
+                {
+                    self.frame_type = frame_type;
+                }
+                let values = FrameType::ALL
+                    .map(|frame_type| (frame_type.element(), frame_type == self.frame_type));
+                vec![set_switch(
+                    &self.device,
+                    "CCD_FRAME_TYPE",
+                    PropertyState::Ok,
+                    None,
+                    &values,
+                )]
+            }
+            "CCD_ABORT_EXPOSURE" if switch_on(&update.elements, "ABORT") => {
+                let mut messages = vec![set_switch(
+                    &self.device,
+                    "CCD_ABORT_EXPOSURE",
+                    PropertyState::Ok,
+                    None,
+                    &[("ABORT", false)],
+                )];
+                if self.exposure.take().is_some() {
+                    messages.push(self.exposure_state(
+                        PropertyState::Alert,
+                        Some("Exposure aborted".to_string()),
+                        0.0,
+                    ));
+                }
+                messages
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn poll_interval(&self) -> Option<Duration> {
+        Some(POLL_INTERVAL)
+    }
+
+    async fn poll(&mut self) -> Vec<MessageType> {
+        let elapsed = self.last_poll.elapsed().as_secs_f64();
+        self.last_poll = Instant::now();
+        let mut messages = self.advance_exposure();
+        messages.extend(self.advance_temperature(elapsed));
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pixel values of a FITS image, without the BZERO offset
+    fn pixels(image: &[u8], count: usize) -> Vec<i32> {
+        image[FITS_BLOCK..FITS_BLOCK + count * 2]
+            .chunks(2)
+            .map(|pixel| i16::from_be_bytes([pixel[0], pixel[1]]) as i32 + 32768)
+            .collect()
+    }
+
+    #[test]
+    fn test_fits_image() {
+        let mut simulator = CcdSimulator::default().with_resolution(64, 48);
+        simulator.binning = (2, 2);
+        let image = simulator.image(2.0);
+        assert_eq!(image.len() % FITS_BLOCK, 0);
+        let header = String::from_utf8_lossy(&image[..FITS_BLOCK]);
+        assert!(header.starts_with("SIMPLE  =                    T"));
+        assert!(header.contains("NAXIS1  =                   32"));
+        assert!(header.contains("IMAGETYP= 'Light Frame'"));
+        assert_eq!(header.find("END ").unwrap() % 80, 0);
+        // Stars stand out of the background
+        let light = pixels(&image, 32 * 24);
+        assert!(light.iter().max().unwrap() - light.iter().min().unwrap() > 1000);
+
+        simulator.frame_type = FrameType::Bias;
+        let bias = pixels(&simulator.image(2.0), 32 * 24);
+        let noise = 6.0 * READ_NOISE;
+        assert!(bias
+            .iter()
+            .all(|&value| (value as f64 - BIAS).abs() < noise));
+    }
+}
@@ -0,0 +1,329 @@
+use crate::format::parse_number;
+use crate::message::definition::{
+    DefBlob, DefBlobVector, DefNumber, DefNumberVector, DefSwitch, DefSwitchVector, DefText,
+    DefTextVector,
+};
+use crate::message::new::{OneNumber, OneSwitch};
+use crate::message::set::{SetNumberVector, SetSwitchVector};
+use crate::message::MessageType;
+use crate::property::timestamp::INDITimestamp;
+use crate::property::{PropertyPerm, PropertyState, SwitchRule, SwitchState};
+
+/// Simulated CCD camera
+mod ccd;
+/// Simulated equatorial mount
+mod telescope;
+
+pub use ccd::CcdSimulator;
+pub use telescope::TelescopeSimulator;
+
+/// Connection switch every simulator defines
+const CONNECTION: &str = "CONNECTION";
+/// Connect element of [`CONNECTION`]
+const CONNECT: &str = "CONNECT";
+/// Disconnect element of [`CONNECTION`]
+const DISCONNECT: &str = "DISCONNECT";
+
+/// Number element: name, label, format, min, max, step, value
+type Number<'a> = (&'a str, &'a str, &'a str, f64, f64, f64, f64);
+
+/// Definition of a number vector
+fn number_vector(
+    device: &str,
+    name: &str,
+    label: &str,
+    group: &str,
+    perm: PropertyPerm,
+    numbers: &[Number<'_>],
+) -> MessageType {
+    MessageType::DefNumberVector(DefNumberVector {
+        device: device.to_string(),
+        name: name.to_string(),
+        label: label.to_string(),
+        group: group.to_string(),
+        state: PropertyState::Idle,
+        perm,
+        timeout: 60,
+        timestamp: INDITimestamp::now().to_string(),
+        message: None,
+        numbers: numbers
+            .iter()
+            .map(|(name, label, format, min, max, step, value)| DefNumber {
+                name: name.to_string(),
+                label: label.to_string(),
+                format: format.to_string(),
+                min: min.to_string(),
+                max: max.to_string(),
+                step: step.to_string(),
+                value: value.to_string(),
+            })
+            .collect(),
+    })
+}
+
+/// Definition of a switch vector from `(name, label, on)` elements
+fn switch_vector(
+    device: &str,
+    name: &str,
+    label: &str,
+    group: &str,
+    rule: SwitchRule,
+    switches: &[(&str, &str, bool)],
+) -> MessageType {
+    MessageType::DefSwitchVector(DefSwitchVector {
+        device: device.to_string(),
+        name: name.to_string(),
+        label: label.to_string(),
+        group: group.to_string(),
+        state: PropertyState::Idle,
+        perm: PropertyPerm::Rw,
+        rule,
+        timeout: 60,
+        timestamp: INDITimestamp::now().to_string(),
+        message: None,
+        switches: switches
+            .iter()
+            .map(|(name, label, on)| DefSwitch {
+                name: name.to_string(),
+                label: label.to_string(),
+                state: switch_state(*on),
+            })
+            .collect(),
+    })
+}
+
+/// Definition of the read-only `DRIVER_INFO` text vector
+fn driver_info(device: &str, exec: &str, interface: u32) -> MessageType {
+    let texts = [
+        ("DRIVER_NAME", "Name", device.to_string()),
+        ("DRIVER_EXEC", "Exec", exec.to_string()),
+        (
+            "DRIVER_VERSION",
+            "Version",
+            env!("CARGO_PKG_VERSION").to_string(),
+        ),
+        ("DRIVER_INTERFACE", "Interface", interface.to_string()),
+    ];
+    MessageType::DefTextVector(DefTextVector {
+        device: device.to_string(),
+        name: "DRIVER_INFO".to_string(),
+        label: "Driver Info".to_string(),
+        group: "General Info".to_string(),
+        state: PropertyState::Idle,
+        perm: PropertyPerm::Ro,
+        timeout: 0,
+        timestamp: INDITimestamp::now().to_string(),
+        message: None,
+        texts: texts
+            .into_iter()
+            .map(|(name, label, value)| DefText {
+                name: name.to_string(),
+                label: label.to_string(),
+                value,
+            })
+            .collect(),
+    })
+}
+
+/// Definition of the `CONNECTION` switch vector
+fn connection(device: &str, connected: bool) -> MessageType {
+    switch_vector(
+        device,
+        CONNECTION,
+        "Connection",
+        "Main Control",
+        SwitchRule::OneOfMany,
+        &[
+            (CONNECT, "Connect", connected),
+            (DISCONNECT, "Disconnect", !connected),
+        ],
+    )
+}
+
+/// Definition of a read-only BLOB vector with a single element
+fn blob_vector(device: &str, name: &str, label: &str, group: &str) -> MessageType {
+    MessageType::DefBlobVector(DefBlobVector {
+        device: device.to_string(),
+        name: name.to_string(),
+        label: label.to_string(),
+        group: group.to_string(),
+        state: PropertyState::Idle,
+        perm: PropertyPerm::Ro,
+        timeout: 60,
+        timestamp: INDITimestamp::now().to_string(),
+        message: None,
+        blobs: vec![DefBlob {
+            name: name.to_string(),
+            label: "Image".to_string(),
+        }],
+    })
+}
+
+/// Update of a number vector
+fn set_number(
+    device: &str,
+    name: &str,
+    state: PropertyState,
+    message: Option<String>,
+    values: &[(&str, f64)],
+) -> MessageType {
+    MessageType::SetNumberVector(SetNumberVector {
+        device: device.to_string(),
+        name: name.to_string(),
+        state: Some(state),
+        timeout: None,
+        timestamp: Some(INDITimestamp::now().to_string()),
+        message,
+        elements: values
+            .iter()
+            .map(|(name, value)| OneNumber {
+                name: name.to_string(),
+                value: value.to_string(),
+            })
+            .collect(),
+    })
+}
+
+/// Update of a switch vector from `(name, on)` elements
+fn set_switch(
+    device: &str,
+    name: &str,
+    state: PropertyState,
+    message: Option<String>,
+    values: &[(&str, bool)],
+) -> MessageType {
+    MessageType::SetSwitchVector(SetSwitchVector {
+        device: device.to_string(),
+        name: name.to_string(),
+        state: Some(state),
+        timeout: None,
+        timestamp: Some(INDITimestamp::now().to_string()),
+        message,
+        elements: values
+            .iter()
+            .map(|(name, on)| OneSwitch {
+                name: name.to_string(),
+                value: switch_state(*on),
+            })
+            .collect(),
+    })
+}
+
+/// Value of element `name` in a `newNumberVector`
+fn number_element(elements: &[OneNumber], name: &str) -> Option<f64> {
+    elements
+        .iter()
+        .find(|element| element.name == name)
+        .and_then(|element| parse_number(&element.value).ok())
+}
+
+/// Returns true if element `name` of a `newSwitchVector` is on
+fn switch_on(elements: &[OneSwitch], name: &str) -> bool {
+    elements
+        .iter()
+        .any(|element| element.name == name && element.value == SwitchState::On)
+}
+
+fn switch_state(on: bool) -> SwitchState {
+    if on {
+        SwitchState::On
+    } else {
+        SwitchState::Off
+    }
+}
+
+/// Handle a `CONNECTION` update, returning the acknowledgement
+fn connect(device: &str, connected: &mut bool, elements: &[OneSwitch]) -> MessageType {
+    if switch_on(elements, CONNECT) {
+        *connected = true;
+    } else if switch_on(elements, DISCONNECT) {
+        *connected = false;
+    }
+    set_switch(
+        device,
+        CONNECTION,
+        PropertyState::Ok,
+        None,
+        &[(CONNECT, *connected), (DISCONNECT, !*connected)],
+    )
+}
+
+/// Small deterministic random number generator for synthetic data
+#[derive(Debug, Clone)]
+struct Noise(u64);
+
+impl Noise {
+    fn new(seed: u64) -> Self {
+        Self(seed | 1)
+    }
+
+    /// Uniformly distributed in `[0, 1)`
+    fn uniform(&mut self) -> f64 {
+        // xorshift64*
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Approximately normally distributed with mean 0 and deviation 1
+    fn gaussian(&mut self) -> f64 {
+        (0..12).map(|_| self.uniform()).sum::<f64>() - 6.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::wait_for_property;
+    use crate::client::Client;
+    use crate::devices::{Camera, Telescope};
+    use crate::server::{Server, ServerConfig};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_simulators_end_to_end() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let server = Arc::new(Server::new(ServerConfig {
+            bind_addr: format!("127.0.0.1:{}", port),
+            ..Default::default()
+        }));
+        server.register_driver(CcdSimulator::default().with_resolution(64, 48));
+        server.register_driver(TelescopeSimulator::default().with_slew_rate(500.0));
+        tokio::spawn({
+            let server = server.clone();
+            async move { server.start().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let client = Client::builder()
+            .host("127.0.0.1")
+            .port(port)
+            .build()
+            .await
+            .unwrap();
+        client.get_properties(None, None).await.unwrap();
+        wait_for_property(&client, "CCD Simulator", "CCD1").await;
+        wait_for_property(&client, "Telescope Simulator", "TELESCOPE_PARK").await;
+
+        let image = Camera::new(client.clone(), "CCD Simulator")
+            .expose(Duration::from_millis(200))
+            .await
+            .unwrap();
+        assert_eq!(image.format, ".fits");
+        assert!(image.data.starts_with(b"SIMPLE  ="));
+
+        let telescope = Telescope::new(client, "Telescope Simulator");
+        telescope.slew_to(5.5, -20.0).await.unwrap();
+        let (ra, dec) = telescope.coordinates().await.unwrap();
+        assert!((ra - 5.5).abs() < 1e-6 && (dec + 20.0).abs() < 1e-6);
+        telescope.park().await.unwrap();
+
+        server.shutdown(Default::default()).await;
+    }
+}
@@ -0,0 +1,413 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::time::Instant;
+
+use crate::driver::IndiDriver;
+use crate::message::new::{NewNumberVector, NewSwitchVector};
+use crate::message::MessageType;
+use crate::property::{PropertyPerm, PropertyState, SwitchRule};
+
+use super::{
+    connect, connection, driver_info, number_element, number_vector, set_number, set_switch,
+    switch_on, switch_vector, CONNECTION,
+};
+
+/// Interface bit of a telescope in `DRIVER_INTERFACE`
+const TELESCOPE_INTERFACE: u32 = 1;
+/// Time between position updates while the mount moves
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Default slew speed, in degrees per second
+const DEFAULT_SLEW_RATE: f64 = 3.0;
+/// Hours of right ascension an untracked mount drifts per second
+const SIDEREAL_DRIFT: f64 = 1.002_737_909 / 3600.0;
+/// Time between position updates of a drifting mount, in seconds
+const DRIFT_REPORT_INTERVAL: f64 = 1.0;
+/// Declination of the park position
+const PARK_DEC: f64 = 90.0;
+
+/// What to do once the mount reaches its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Goal {
+    /// Slew, tracking afterwards if `ON_COORD_SET` says so
+    Slew,
+    /// Park, no longer tracking
+    Park,
+}
+
+/// Action of new coordinates, per `ON_COORD_SET`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CoordSet {
+    Track,
+    Slew,
+    Sync,
+}
+
+impl CoordSet {
+    const ALL: [(CoordSet, &'static str, &'static str); 3] = [
+        (CoordSet::Track, "TRACK", "Track"),
+        (CoordSet::Slew, "SLEW", "Slew"),
+        (CoordSet::Sync, "SYNC", "Sync"),
+    ];
+}
+
+/// Simulated equatorial mount
+///
+/// Defines the standard `EQUATORIAL_EOD_COORD`, `ON_COORD_SET`,
+/// `TELESCOPE_TRACK_STATE`, `TELESCOPE_ABORT_MOTION` and `TELESCOPE_PARK`
+/// properties. Slews move both axes at the slew rate, reporting the
+/// position as `Busy` until the target is reached; an untracked mount
+/// drifts in right ascension at the sidereal rate. Works with
+/// [`Telescope`](crate::devices::Telescope).
+#[derive(Debug, Clone)]
+pub struct TelescopeSimulator {
+    device: String,
+    connected: bool,
+    ra: f64,
+    dec: f64,
+    target: Option<(f64, f64, Goal)>,
+    coord_set: CoordSet,
+    tracking: bool,
+    parked: bool,
+    slew_rate: f64,
+    since_report: f64,
+    last_poll: Instant,
+}
+
+impl Default for TelescopeSimulator {
+    fn default() -> Self {
+        Self::new("Telescope Simulator")
+    }
+}
+
+impl TelescopeSimulator {
+    /// Create a connected, unparked mount named `device`, pointing at the
+    /// pole without tracking
+    pub fn new(device: impl Into<String>) -> Self {
+        Self {
+            device: device.into(),
+            connected: true,
+            ra: 0.0,
+            dec: PARK_DEC,
+            target: None,
+            coord_set: CoordSet::Track,
+            tracking: false,
+            parked: false,
+            slew_rate: DEFAULT_SLEW_RATE,
+            since_report: 0.0,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Sets the slew speed in degrees per second
+    pub fn with_slew_rate(mut self, degrees_per_second: f64) -> Self {
+        self.slew_rate = degrees_per_second.max(f64::EPSILON);
+        self
+    }
+
+    /// Device name
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    fn coordinates(&self, state: PropertyState, message: Option<String>) -> MessageType {
+        set_number(
+            &self.device,
+            "EQUATORIAL_EOD_COORD",
+            state,
+            message,
+            &[("RA", self.ra), ("DEC", self.dec)],
+        )
+    }
+
+    fn track_state(&self, state: PropertyState, message: Option<String>) -> MessageType {
+        set_switch(
+            &self.device,
+            "TELESCOPE_TRACK_STATE",
+            state,
+            message,
+            &[("TRACK_ON", self.tracking), ("TRACK_OFF", !self.tracking)],
+        )
+    }
+
+    fn park_state(&self, state: PropertyState, message: Option<String>) -> MessageType {
+        let parking = self.target.is_some_and(|(_, _, goal)| goal == Goal::Park);
+        let park = self.parked || parking;
+        set_switch(
+            &self.device,
+            "TELESCOPE_PARK",
+            state,
+            message,
+            &[("PARK", park), ("UNPARK", !park)],
+        )
+    }
+
+    /// Refusal of a command, `None` if the mount can move
+    fn immobile(&self) -> Option<String> {
+        if !self.connected {
+            Some(format!("{} is not connected", self.device))
+        } else if self.parked {
+            Some(format!("{} is parked", self.device))
+        } else {
+            None
+        }
+    }
+
+    fn goto(&mut self, update: &NewNumberVector) -> Vec<MessageType> {
+        if let Some(reason) = self.immobile() {
+            return vec![self.coordinates(PropertyState::Alert, Some(reason))];
+        }
+        let ra = number_element(&update.elements, "RA").unwrap_or(self.ra);
+        let dec = number_element(&update.elements, "DEC").unwrap_or(self.dec);
+        if !(0.0..24.0).contains(&ra) || !(-90.0..=90.0).contains(&dec) {
+            return vec![self.coordinates(
+                PropertyState::Alert,
+                Some(format!("Invalid coordinates RA {} Dec {}", ra, dec)),
+            )];
+        }
+        if self.coord_set == CoordSet::Sync {
+            self.ra = ra;
+            self.dec = dec;
+            return vec![self.coordinates(PropertyState::Ok, None)];
+        }
+        self.target = Some((ra, dec, Goal::Slew));
+        vec![self.coordinates(PropertyState::Busy, None)]
+    }
+
+    /// Move toward the target, or drift without tracking
+    fn advance(&mut self, elapsed: f64) -> Vec<MessageType> {
+        let Some((ra, dec, goal)) = self.target else {
+            if self.tracking {
+                return Vec::new();
+            }
+            self.ra = (self.ra + SIDEREAL_DRIFT * elapsed).rem_euclid(24.0);
+            self.since_report += elapsed;
+            if self.since_report < DRIFT_REPORT_INTERVAL {
+                return Vec::new();
+            }
+            self.since_report = 0.0;
+            return vec![self.coordinates(PropertyState::Idle, None)];
+        };
+
+        let step = self.slew_rate * elapsed;
+        // Right ascension in degrees, the short way around
+        let ra_offset = ((ra - self.ra + 12.0).rem_euclid(24.0) - 12.0) * 15.0;
+        let dec_offset = dec - self.dec;
+        if ra_offset.abs() > step || dec_offset.abs() > step {
+            self.ra = (self.ra + ra_offset.clamp(-step, step) / 15.0).rem_euclid(24.0);
+            self.dec += dec_offset.clamp(-step, step);
+            return vec![self.coordinates(PropertyState::Busy, None)];
+        }
+
+        self.ra = ra;
+        self.dec = dec;
+        self.target = None;
+        match goal {
+            Goal::Slew => {
+                self.tracking = self.coord_set == CoordSet::Track;
+                vec![
+                    self.coordinates(PropertyState::Ok, None),
+                    self.track_state(PropertyState::Ok, None),
+                ]
+            }
+            Goal::Park => {
+                self.parked = true;
+                self.tracking = false;
+                vec![
+                    self.coordinates(PropertyState::Idle, None),
+                    self.track_state(PropertyState::Ok, None),
+                    self.park_state(PropertyState::Ok, None),
+                ]
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl IndiDriver for TelescopeSimulator {
+    async fn define_properties(&mut self) -> Vec<MessageType> {
+        let device = self.device.as_str();
+        let coord_set =
+            CoordSet::ALL.map(|(action, name, label)| (name, label, action == self.coord_set));
+        vec![
+            connection(device, self.connected),
+            driver_info(device, "telescope_simulator", TELESCOPE_INTERFACE),
+            number_vector(
+                device,
+                "EQUATORIAL_EOD_COORD",
+                "Eq. Coordinates",
+                "Main Control",
+                PropertyPerm::Rw,
+                &[
+                    ("RA", "RA (hh:mm:ss)", "%010.6m", 0.0, 24.0, 0.0, self.ra),
+                    (
+                        "DEC",
+                        "DEC (dd:mm:ss)",
+                        "%010.6m",
+                        -90.0,
+                        90.0,
+                        0.0,
+                        self.dec,
+                    ),
+                ],
+            ),
+            switch_vector(
+                device,
+                "ON_COORD_SET",
+                "On Set",
+                "Main Control",
+                SwitchRule::OneOfMany,
+                &coord_set,
+            ),
+            switch_vector(
+                device,
+                "TELESCOPE_TRACK_STATE",
+                "Tracking",
+                "Main Control",
+                SwitchRule::OneOfMany,
+                &[
+                    ("TRACK_ON", "On", self.tracking),
+                    ("TRACK_OFF", "Off", !self.tracking),
+                ],
+            ),
+            switch_vector(
+                device,
+                "TELESCOPE_ABORT_MOTION",
+                "Abort Motion",
+                "Main Control",
+                SwitchRule::AtMostOne,
+                &[("ABORT", "Abort", false)],
+            ),
+            switch_vector(
+                device,
+                "TELESCOPE_PARK",
+                "Parking",
+                "Main Control",
+                SwitchRule::OneOfMany,
+                &[
+                    ("PARK", "Park", self.parked),
+                    ("UNPARK", "Unpark", !self.parked),
+                ],
+            ),
+        ]
+    }
+
+    async fn on_new_number(&mut self, update: NewNumberVector) -> Vec<MessageType> {
+        match update.name.as_str() {
+            "EQUATORIAL_EOD_COORD" => self.goto(&update),
+            _ => Vec::new(),
+        }
+    }
+
+    async fn on_new_switch(&mut self, update: NewSwitchVector) -> Vec<MessageType> {
+        match update.name.as_str() {
+            CONNECTION => {
+                let message = connect(&self.device, &mut self.connected, &update.elements);
+                if !self.connected {
+                    self.target = None;
+                }
+                vec![message]
+            }
+            "ON_COORD_SET" => {
+                if let Some((action, _, _)) = CoordSet::ALL
+                    .into_iter()
+                    .find(|(_, name, _)| switch_on(&update.elements, name))
+                {
+                    self.coord_set = action;
+                }
+                let values =
+                    CoordSet::ALL.map(|(action, name, _)| (name, action == self.coord_set));
+                vec![set_switch(
+                    &self.device,
+                    "ON_COORD_SET",
+                    PropertyState::Ok,
+                    None,
+                    &values,
+                )]
+            }
+            "TELESCOPE_TRACK_STATE" => {
+                let enable = switch_on(&update.elements, "TRACK_ON");
+                if enable {
+                    if let Some(reason) = self.immobile() {
+                        return vec![self.track_state(PropertyState::Alert, Some(reason))];
+                    }
+                }
+                if enable || switch_on(&update.elements, "TRACK_OFF") {
+                    self.tracking = enable;
+                }
+                vec![self.track_state(PropertyState::Ok, None)]
+            }
+            "TELESCOPE_ABORT_MOTION" if switch_on(&update.elements, "ABORT") => {
+                let mut messages = vec![set_switch(
+                    &self.device,
+                    "TELESCOPE_ABORT_MOTION",
+                    PropertyState::Ok,
+                    None,
+                    &[("ABORT", false)],
+                )];
+                if let Some((_, _, goal)) = self.target.take() {
+                    let message = Some("Motion aborted".to_string());
+                    messages.push(self.coordinates(PropertyState::Idle, message.clone()));
+                    if goal == Goal::Park {
+                        messages.push(self.park_state(PropertyState::Idle, message));
+                    }
+                }
+                messages
+            }
+            "TELESCOPE_PARK" => {
+                if switch_on(&update.elements, "PARK") {
+                    if self.parked {
+                        return vec![self.park_state(PropertyState::Ok, None)];
+                    }
+                    if let Some(reason) = self.immobile() {
+                        return vec![self.park_state(PropertyState::Alert, Some(reason))];
+                    }
+                    self.target = Some((self.ra, PARK_DEC, Goal::Park));
+                    vec![self.park_state(PropertyState::Busy, None)]
+                } else if switch_on(&update.elements, "UNPARK") {
+                    self.parked = false;
+                    vec![self.park_state(PropertyState::Ok, None)]
+                } else {
+                    Vec::new()
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn poll_interval(&self) -> Option<Duration> {
+        Some(POLL_INTERVAL)
+    }
+
+    async fn poll(&mut self) -> Vec<MessageType> {
+        let elapsed = self.last_poll.elapsed().as_secs_f64();
+        self.last_poll = Instant::now();
+        self.advance(elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slew_wraps_around() {
+        let mut simulator = TelescopeSimulator::default().with_slew_rate(15.0);
+        simulator.ra = 23.5;
+        simulator.dec = 10.0;
+        simulator.target = Some((0.5, 10.0, Goal::Slew));
+        // One second moves an hour of right ascension, crossing 0h
+        assert!(matches!(
+            simulator.advance(0.5).as_slice(),
+            [MessageType::SetNumberVector(_)]
+        ));
+        assert!((simulator.ra - 0.0).abs() < 1e-9, "{}", simulator.ra);
+        let messages = simulator.advance(0.5);
+        assert_eq!(simulator.ra, 0.5);
+        assert!(simulator.tracking);
+        let MessageType::SetNumberVector(set) = &messages[0] else {
+            panic!("Expected coordinates, got {:?}", messages[0]);
+        };
+        assert_eq!(set.state, Some(PropertyState::Ok));
+    }
+}
@@ -10,7 +10,11 @@ use thiserror::Error;
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// Error type for INDI protocol operations
+///
+/// New failure modes are added over time, so matches outside this crate
+/// need a wildcard arm.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// IO error
     #[error("IO error: {0}")]
@@ -55,4 +59,38 @@ pub enum Error {
     /// Serialization error
     #[error("Serialization error: {0}")]
     SerializationError(String),
+
+    /// Property update refused by a validator
+    #[error("Rejected: {0}")]
+    Rejected(crate::validation::Rejection),
+
+    /// Property exists but holds a different type than requested
+    #[error("{device}.{name} is a {found}, not a {expected}")]
+    WrongPropertyType {
+        /// Device name
+        device: String,
+        /// Property name
+        name: String,
+        /// Type that was requested
+        expected: &'static str,
+        /// Type the property actually has
+        found: &'static str,
+    },
+
+    /// Outgoing value not allowed by the property definition
+    #[error("{device}.{name}.{element}: {reason}")]
+    OutOfRange {
+        /// Device name
+        device: String,
+        /// Property name
+        name: String,
+        /// Element holding the value
+        element: String,
+        /// What the definition does not allow
+        reason: String,
+    },
+
+    /// Operation timed out
+    #[error("Timeout: {0}")]
+    Timeout(String),
 }
@@ -0,0 +1,347 @@
+//! Minimal FITS header parsing for BLOB payloads
+//!
+//! Reads the keywords of the primary header, such as `EXPTIME`, `NAXIS`
+//! and `DATE-OBS`, from the images cameras send, without a full FITS
+//! library. Extensions and the data unit are not read.
+
+use crate::error::{Error, Result};
+use chrono::NaiveDateTime;
+
+/// Length of a header card
+const CARD_LEN: usize = 80;
+/// FITS files are made of blocks of this size
+const BLOCK_LEN: usize = 2880;
+
+/// Value of a header keyword
+#[derive(Debug, Clone, PartialEq)]
+pub enum FitsValue {
+    /// Character string, without the quotes and trailing spaces
+    Text(String),
+    /// `T` or `F`
+    Logical(bool),
+    /// Integer number
+    Integer(i64),
+    /// Floating point number
+    Float(f64),
+}
+
+/// Card of a FITS header
+#[derive(Debug, Clone, PartialEq)]
+pub struct FitsCard {
+    /// Keyword, such as `EXPTIME`
+    pub keyword: String,
+    /// Value, `None` for commentary cards and undefined values
+    pub value: Option<FitsValue>,
+    /// Comment following the value, or the text of a commentary card
+    pub comment: Option<String>,
+}
+
+/// Primary header of a FITS file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FitsHeader {
+    cards: Vec<FitsCard>,
+    /// Bytes up to the data unit, including the padding of the last block
+    len: usize,
+}
+
+impl FitsHeader {
+    /// Parse the primary header at the start of `data`
+    ///
+    /// `data` only needs to hold the header, not the image after it.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if !data.starts_with(b"SIMPLE  =") {
+            return Err(Error::ParseError("Not a FITS file".to_string()));
+        }
+        let mut cards = Vec::new();
+        for (index, card) in data.chunks_exact(CARD_LEN).enumerate() {
+            if is_end(card) {
+                let len = (index + 1) * CARD_LEN;
+                return Ok(Self {
+                    cards,
+                    len: len.div_ceil(BLOCK_LEN) * BLOCK_LEN,
+                });
+            }
+            // Headers are ASCII, which also keeps the column slicing below
+            // on character boundaries
+            let card = std::str::from_utf8(card)
+                .ok()
+                .filter(|card| card.is_ascii())
+                .ok_or_else(|| {
+                    Error::ParseError(format!("FITS card {} is not ASCII", index + 1))
+                })?;
+            cards.push(parse_card(card[..8].trim_end(), &card[8..]));
+        }
+        Err(Error::ParseError("FITS header without END".to_string()))
+    }
+
+    /// All cards before `END`, in order
+    pub fn cards(&self) -> &[FitsCard] {
+        &self.cards
+    }
+
+    /// Offset of the data unit, the length of the header in whole blocks
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the header has no cards besides `END`
+    pub fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+
+    /// Value of the first card with `keyword`
+    pub fn get(&self, keyword: &str) -> Option<&FitsValue> {
+        self.cards
+            .iter()
+            .find(|card| card.keyword == keyword)
+            .and_then(|card| card.value.as_ref())
+    }
+
+    /// String value of `keyword`
+    pub fn text(&self, keyword: &str) -> Option<&str> {
+        match self.get(keyword)? {
+            FitsValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Integer value of `keyword`
+    pub fn integer(&self, keyword: &str) -> Option<i64> {
+        match self.get(keyword)? {
+            FitsValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Numeric value of `keyword`, integers included
+    pub fn float(&self, keyword: &str) -> Option<f64> {
+        match self.get(keyword)? {
+            FitsValue::Float(value) => Some(*value),
+            FitsValue::Integer(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    /// Logical value of `keyword`
+    pub fn logical(&self, keyword: &str) -> Option<bool> {
+        match self.get(keyword)? {
+            FitsValue::Logical(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Exposure time in seconds, from `EXPTIME` or `EXPOSURE`
+    pub fn exposure(&self) -> Option<f64> {
+        self.float("EXPTIME").or_else(|| self.float("EXPOSURE"))
+    }
+
+    /// Bits per pixel, negative for floating point pixels
+    pub fn bitpix(&self) -> Option<i64> {
+        self.integer("BITPIX")
+    }
+
+    /// Length of each axis, `NAXIS1` first, empty without image
+    pub fn axes(&self) -> Vec<usize> {
+        let count = self.integer("NAXIS").unwrap_or(0);
+        (1..=count)
+            .map_while(|axis| self.integer(&format!("NAXIS{}", axis)))
+            .map(|len| len.max(0) as usize)
+            .collect()
+    }
+
+    /// Start of the exposure, from `DATE-OBS`
+    ///
+    /// FITS dates carry no zone, they are UTC by convention.
+    pub fn date_obs(&self) -> Option<NaiveDateTime> {
+        let date = self.text("DATE-OBS")?;
+        NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f")
+            .ok()
+            .or_else(|| {
+                chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+            })
+    }
+}
+
+/// Returns true if `card` is the `END` card
+fn is_end(card: &[u8]) -> bool {
+    card.starts_with(b"END     ")
+}
+
+/// Returns true once `data` holds the whole header, or is no FITS file
+///
+/// Scans the cards after `scanned`, the length already scanned, and
+/// updates it, so the decoded data can be checked as it grows.
+pub(crate) fn header_read(data: &[u8], scanned: &mut usize) -> bool {
+    if data.len() >= 9 && !data.starts_with(b"SIMPLE  =") {
+        return true;
+    }
+    let cards = data.len() / CARD_LEN * CARD_LEN;
+    let found = data[*scanned..cards].chunks_exact(CARD_LEN).any(is_end);
+    *scanned = cards;
+    found
+}
+
+/// Parse the columns after the keyword of a card
+fn parse_card(keyword: &str, rest: &str) -> FitsCard {
+    let Some(field) = rest.strip_prefix("= ") else {
+        // Commentary card such as COMMENT or HISTORY
+        let text = rest.trim();
+        return FitsCard {
+            keyword: keyword.to_string(),
+            value: None,
+            comment: (!text.is_empty()).then(|| text.to_string()),
+        };
+    };
+    let field = field.trim_start();
+    let (value, comment) = match field.strip_prefix('\'') {
+        Some(quoted) => {
+            let (text, rest) = parse_string(quoted);
+            (Some(FitsValue::Text(text)), rest)
+        }
+        None => {
+            let (value, comment) = match field.split_once('/') {
+                Some((value, comment)) => (value, Some(comment)),
+                None => (field, None),
+            };
+            (parse_value(value.trim()), comment)
+        }
+    };
+    let comment = comment
+        .map(|comment| comment.trim_start_matches('/').trim())
+        .filter(|comment| !comment.is_empty());
+    FitsCard {
+        keyword: keyword.to_string(),
+        value,
+        comment: comment.map(str::to_string),
+    }
+}
+
+/// Read a string value after its opening quote, returning it and the
+/// comment after it
+fn parse_string(quoted: &str) -> (String, Option<&str>) {
+    let mut text = String::new();
+    let mut chars = quoted.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        if c != '\'' {
+            text.push(c);
+            continue;
+        }
+        // Quotes are doubled inside strings
+        if chars.peek().is_some_and(|(_, next)| *next == '\'') {
+            chars.next();
+            text.push('\'');
+            continue;
+        }
+        let rest = &quoted[index + 1..];
+        let comment = rest.split_once('/').map(|(_, comment)| comment);
+        return (text.trim_end().to_string(), comment);
+    }
+    (text.trim_end().to_string(), None)
+}
+
+/// Parse a logical or numeric value, `None` if undefined or unsupported
+fn parse_value(value: &str) -> Option<FitsValue> {
+    match value {
+        "" => None,
+        "T" => Some(FitsValue::Logical(true)),
+        "F" => Some(FitsValue::Logical(false)),
+        _ => value.parse().map(FitsValue::Integer).ok().or_else(|| {
+            value
+                .replace(['D', 'd'], "E")
+                .parse()
+                .map(FitsValue::Float)
+                .ok()
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::new::OneBlob;
+
+    fn card(card: &str) -> String {
+        format!("{:<80}", card)
+    }
+
+    #[test]
+    fn test_fits_header() {
+        let cards = [
+            card("SIMPLE  =                    T / conforms to FITS standard"),
+            card("BITPIX  =                   16"),
+            card("NAXIS   =                    2"),
+            card("NAXIS1  =                 1280"),
+            card("NAXIS2  =                  960"),
+            card("EXPTIME =             2.500D+0 / Total Exposure Time (s)"),
+            card("INSTRUME= 'CCD Simulator'      / CCD Name"),
+            card("OBJECT  = 'Barnard''s Star'"),
+            card("DATE-OBS= '2026-10-17T01:02:03.250'"),
+            card("COMMENT Generated by indi-rs"),
+            card("END"),
+        ];
+        let mut data = cards.concat().into_bytes();
+        data.resize(BLOCK_LEN, b' ');
+        data.extend_from_slice(&[0; 16]);
+        let blob = OneBlob::new("CCD1", ".fits", &data);
+
+        let header = blob.fits_header().unwrap();
+        assert_eq!(header.len(), BLOCK_LEN);
+        assert_eq!(header.bitpix(), Some(16));
+        assert_eq!(header.axes(), [1280, 960]);
+        assert_eq!(header.exposure(), Some(2.5));
+        assert_eq!(header.text("INSTRUME"), Some("CCD Simulator"));
+        assert_eq!(header.text("OBJECT"), Some("Barnard's Star"));
+        assert_eq!(
+            header.date_obs().unwrap().to_string(),
+            "2026-10-17 01:02:03.250"
+        );
+        assert_eq!(
+            header.cards()[5].comment.as_deref(),
+            Some("Total Exposure Time (s)")
+        );
+        assert_eq!(
+            header.cards()[9].comment.as_deref(),
+            Some("Generated by indi-rs")
+        );
+
+        assert!(FitsHeader::parse(b"not an image").is_err());
+        assert!(FitsHeader::parse(cards[0].as_bytes()).is_err());
+
+        // Corrupt frames are an error, not a panic
+        let mut corrupt = data.clone();
+        corrupt[CARD_LEN + 6] = 0xff;
+        assert!(matches!(
+            FitsHeader::parse(&corrupt),
+            Err(Error::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_fits_header_stops_at_end() {
+        let mut data = [card("SIMPLE  =                    T"), card("END")]
+            .concat()
+            .into_bytes();
+        data.resize(BLOCK_LEN, b' ');
+        let mut scanned = 0;
+        assert!(!header_read(&data[..CARD_LEN + 40], &mut scanned));
+        assert_eq!(scanned, CARD_LEN);
+        assert!(header_read(&data, &mut scanned));
+
+        // Only the header is decoded, the image after it may even be
+        // invalid base64
+        let mut blob = OneBlob::new("CCD1", ".fits", &data);
+        blob.value.push_str(&"A".repeat(4 * 100_000));
+        blob.value.push('!');
+        assert!(blob.get_data().is_err());
+        assert_eq!(blob.fits_header().unwrap().cards().len(), 1);
+
+        #[cfg(feature = "zlib")]
+        {
+            data.resize(1 << 20, 0);
+            let blob = OneBlob::new_compressed("CCD1", ".fits", &data);
+            assert_eq!(blob.fits_header().unwrap().len(), BLOCK_LEN);
+        }
+    }
+}
@@ -0,0 +1,453 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+
+/// Precision used by `%f`, `%e` and `%g` when the format gives none
+const DEFAULT_PRECISION: usize = 6;
+
+/// Most decimals of the seconds `%m` renders, beyond which an `f64` of a
+/// few hundred degrees has no digits left to give
+const MAX_SEXAGESIMAL_DECIMALS: usize = 9;
+
+/// printf-style format of an INDI number element
+///
+/// Supports the `%f`, `%e`, `%g` and `%d` conversions and their upper
+/// case forms with width, precision and the `-`, `+`, space, `0` and `#`
+/// flags, following C `printf`, and the INDI `%<w>.<f>m` sexagesimal
+/// conversion. The precision of `%m` selects the fields
+/// like C indilib does: 3 gives `:mm`, 5 `:mm.m`, 6 `:mm:ss`, 8
+/// `:mm:ss.s` and 9 `:mm:ss.ss`, and other precisions follow the same
+/// pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    width: usize,
+    precision: Option<usize>,
+    conversion: Conversion,
+    /// Upper case exponent, `inf` and `nan`
+    upper: bool,
+    left: bool,
+    plus: bool,
+    space: bool,
+    zero: bool,
+    /// `#`: keep the decimal point and, for `%g`, trailing zeros
+    alternate: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Conversion {
+    Fixed,
+    Exponent,
+    General,
+    Integer,
+    Sexagesimal,
+}
+
+impl FromStr for NumberFormat {
+    type Err = Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        let invalid = || Error::ParseError(format!("Unsupported number format '{}'", format));
+        let mut rest = format.trim().strip_prefix('%').ok_or_else(invalid)?;
+        let mut parsed = Self {
+            width: 0,
+            precision: None,
+            conversion: Conversion::General,
+            upper: false,
+            left: false,
+            plus: false,
+            space: false,
+            zero: false,
+            alternate: false,
+        };
+        while let Some(flag) = rest.chars().next() {
+            match flag {
+                '-' => parsed.left = true,
+                '+' => parsed.plus = true,
+                '0' => parsed.zero = true,
+                ' ' => parsed.space = true,
+                '#' => parsed.alternate = true,
+                '\'' => (),
+                _ => break,
+            }
+            rest = &rest[1..];
+        }
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        parsed.width = rest[..digits].parse().unwrap_or(0);
+        rest = &rest[digits..];
+        if let Some(precision) = rest.strip_prefix('.') {
+            let digits = precision.bytes().take_while(u8::is_ascii_digit).count();
+            parsed.precision = Some(precision[..digits].parse().unwrap_or(0));
+            rest = &precision[digits..];
+        }
+        let rest = rest.trim_start_matches(['l', 'L', 'h']);
+        parsed.upper = matches!(rest, "F" | "E" | "G");
+        parsed.conversion = match rest {
+            "f" | "F" => Conversion::Fixed,
+            "e" | "E" => Conversion::Exponent,
+            "g" | "G" => Conversion::General,
+            "d" | "i" | "u" => Conversion::Integer,
+            "m" => Conversion::Sexagesimal,
+            _ => return Err(invalid()),
+        };
+        Ok(parsed)
+    }
+}
+
+impl NumberFormat {
+    /// Render `value` in this format
+    pub fn format(&self, value: f64) -> String {
+        let precision = self.precision.unwrap_or(DEFAULT_PRECISION);
+        let mut zero = self.zero;
+        let text = match self.conversion {
+            Conversion::Sexagesimal => return self.sexagesimal(value),
+            _ if !value.is_finite() => {
+                // C pads inf and nan with spaces only
+                zero = false;
+                let text = match value.is_nan() {
+                    true => "nan",
+                    false if value > 0.0 => "inf",
+                    false => "-inf",
+                };
+                text.to_string()
+            }
+            Conversion::Fixed => {
+                let mut text = format!("{:.*}", precision, value);
+                if self.alternate && precision == 0 {
+                    text.push('.');
+                }
+                text
+            }
+            Conversion::Exponent => exponent(value, precision, self.alternate),
+            Conversion::General => general(value, precision, self.alternate),
+            Conversion::Integer => {
+                let rounded = value.round();
+                // A precision is the minimum number of digits and
+                // disables zero padding
+                let digits = match self.precision {
+                    Some(precision) => {
+                        zero = false;
+                        if precision == 0 && rounded == 0.0 {
+                            String::new()
+                        } else {
+                            format!("{:0>1$.0}", rounded.abs(), precision)
+                        }
+                    }
+                    None => format!("{:.0}", rounded.abs()),
+                };
+                match rounded < 0.0 {
+                    true => format!("-{}", digits),
+                    false => digits,
+                }
+            }
+        };
+        let text = match self.upper {
+            true => text.to_uppercase(),
+            false => text,
+        };
+        self.pad(text, zero && !self.left)
+    }
+
+    /// Apply sign flags and width
+    fn pad(&self, text: String, zero: bool) -> String {
+        let (sign, digits) = match text.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None if self.plus => ("+", text.as_str()),
+            None if self.space => (" ", text.as_str()),
+            None => ("", text.as_str()),
+        };
+        let len = sign.len() + digits.len();
+        if len >= self.width {
+            return format!("{}{}", sign, digits);
+        }
+        let fill = self.width - len;
+        if self.left {
+            format!("{}{}{}", sign, digits, " ".repeat(fill))
+        } else if zero {
+            format!("{}{}{}", sign, "0".repeat(fill), digits)
+        } else {
+            format!("{}{}{}", " ".repeat(fill), sign, digits)
+        }
+    }
+
+    /// `%<w>.<f>m`, like `fs_sexa` in C indilib
+    ///
+    /// `w - f` is the width of the whole part and `f` the number of
+    /// characters after it: below 5 gives `:mm`, 5 gives `:mm.m`, 6 and 7
+    /// give `:mm:ss`, and from 8 on each extra character adds a decimal
+    /// to the seconds.
+    fn sexagesimal(&self, value: f64) -> String {
+        let precision = self.precision.unwrap_or(0);
+        let (seconds, decimals) = match precision {
+            0..=4 => (false, 0),
+            5 => (false, 1),
+            6 | 7 => (true, 0),
+            _ => (true, (precision - 7).min(MAX_SEXAGESIMAL_DECIMALS)),
+        };
+        let width = self.width.saturating_sub(precision);
+        if value.is_nan() {
+            return format!("{:>1$}", "nan", width);
+        } else if value.is_infinite() {
+            return format!("{:>1$}", if value > 0.0 { "inf" } else { "-inf" }, width);
+        }
+        let scale = 10u64.pow(decimals as u32);
+        let field = 60 * scale;
+        let fracbase = if seconds { 60 * field } else { field };
+        // Rounding in whole output units carries 59.99.. over into the
+        // next minute or degree rather than printing 60
+        let negative = value < 0.0;
+        let scaled = (value.abs() * fracbase as f64).round() as u64;
+        let (whole, fraction) = (scaled / fracbase, scaled % fracbase);
+        let whole = match negative {
+            true => format!("-{}", whole),
+            false => whole.to_string(),
+        };
+        let mut out = format!("{:>1$}", whole, width);
+        if seconds {
+            out.push_str(&format!(":{:02}", fraction / field));
+        }
+        let last = fraction % field;
+        out.push_str(&format!(":{:02}", last / scale));
+        if decimals > 0 {
+            out.push_str(&format!(".{:01$}", last % scale, decimals));
+        }
+        out
+    }
+}
+
+impl fmt::Display for NumberFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("%")?;
+        if self.left {
+            f.write_str("-")?;
+        }
+        if self.plus {
+            f.write_str("+")?;
+        }
+        if self.space {
+            f.write_str(" ")?;
+        }
+        if self.zero {
+            f.write_str("0")?;
+        }
+        if self.alternate {
+            f.write_str("#")?;
+        }
+        if self.width > 0 {
+            write!(f, "{}", self.width)?;
+        }
+        if let Some(precision) = self.precision {
+            write!(f, ".{}", precision)?;
+        }
+        let conversion = match (self.conversion, self.upper) {
+            (Conversion::Fixed, false) => "f",
+            (Conversion::Fixed, true) => "F",
+            (Conversion::Exponent, false) => "e",
+            (Conversion::Exponent, true) => "E",
+            (Conversion::General, false) => "g",
+            (Conversion::General, true) => "G",
+            (Conversion::Integer, _) => "d",
+            (Conversion::Sexagesimal, _) => "m",
+        };
+        f.write_str(conversion)
+    }
+}
+
+/// `%e`: mantissa with `precision` decimals and an exponent of at least two
+/// digits, e.g. `1.500000e+03`
+///
+/// `alternate` keeps the decimal point of a mantissa without decimals.
+fn exponent(value: f64, precision: usize, alternate: bool) -> String {
+    let rust = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = rust.split_once('e').unwrap_or((&rust, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let sign = if exponent < 0 { '-' } else { '+' };
+    let point = if alternate && precision == 0 { "." } else { "" };
+    format!("{}{}e{}{:02}", mantissa, point, sign, exponent.abs())
+}
+
+/// `%g`: `%e` or `%f` with `precision` significant digits, whichever C
+/// would pick, without trailing zeros unless `alternate` is set
+fn general(value: f64, precision: usize, alternate: bool) -> String {
+    let precision = precision.max(1);
+    let rounded = format!("{:.*e}", precision - 1, value);
+    let exp: i32 = rounded
+        .split_once('e')
+        .and_then(|(_, exp)| exp.parse().ok())
+        .unwrap_or(0);
+    if exp < -4 || exp >= precision as i32 {
+        let text = exponent(value, precision - 1, alternate);
+        if alternate {
+            return text;
+        }
+        let (mantissa, exp) = text.split_once('e').unwrap_or((&text, ""));
+        format!("{}e{}", trim_zeros(mantissa), exp)
+    } else {
+        let decimals = (precision as i32 - 1 - exp).max(0) as usize;
+        let text = format!("{:.*}", decimals, value);
+        match alternate {
+            true if decimals == 0 => format!("{}.", text),
+            true => text,
+            false => trim_zeros(&text).to_string(),
+        }
+    }
+}
+
+/// Drop trailing zeros of a decimal fraction, and the point if nothing is
+/// left after it
+fn trim_zeros(text: &str) -> &str {
+    if !text.contains('.') {
+        return text;
+    }
+    text.trim_end_matches('0').trim_end_matches('.')
+}
+
+/// Parse a decimal or sexagesimal number
+///
+/// Accepts what C indilib's `f_scansexa` does: plain decimals like
+/// `-12.5` or `1e-3`, and up to three fields separated by `:`, `;` or
+/// spaces, like `12:34:56.7` or `-0 30`. A leading `-` negates the whole
+/// value.
+pub fn parse_number(text: &str) -> Result<f64> {
+    let invalid = || Error::ParseError(format!("Invalid number '{}'", text));
+    let trimmed = text.trim();
+    if let Ok(value) = trimmed.parse::<f64>() {
+        return Ok(value);
+    }
+    let (negative, unsigned) = match trimmed.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let fields = unsigned
+        .split([':', ';', ' '])
+        .filter(|field| !field.is_empty())
+        .collect::<Vec<_>>();
+    if fields.is_empty() || fields.len() > 3 {
+        return Err(invalid());
+    }
+    let mut value = 0.0;
+    for (i, field) in fields.iter().enumerate() {
+        if !field.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+            return Err(invalid());
+        }
+        let field: f64 = field.parse().map_err(|_| invalid())?;
+        value += field / 60f64.powi(i as i32);
+    }
+    Ok(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn format(format: &str, value: f64) -> String {
+        format.parse::<NumberFormat>().unwrap().format(value)
+    }
+
+    #[test]
+    fn test_printf_formats() {
+        assert_eq!(format("%5.2f", -10.0), "-10.00");
+        assert_eq!(format("%8.3f", 1.5), "   1.500");
+        assert_eq!(format("%-6.1f", 1.5), "1.5   ");
+        assert_eq!(format("%06.1f", -1.5), "-001.5");
+        assert_eq!(format("%+.0f", 3.0), "+3");
+        assert_eq!(format("%.0f", 299.6), "300");
+        assert_eq!(format("%d", 41.7), "42");
+        assert_eq!(format("%4d", 7.0), "   7");
+        assert_eq!(format("%e", 1500.0), "1.500000e+03");
+        assert_eq!(format("%.2e", 0.00012), "1.20e-04");
+        assert_eq!(format("%g", 100000.0), "100000");
+        assert_eq!(format("%g", 1000000.0), "1e+06");
+        assert_eq!(format("%g", 0.0001), "0.0001");
+        assert_eq!(format("%g", 0.00001234), "1.234e-05");
+        assert_eq!(format("%.3g", 1.23456), "1.23");
+        assert_eq!(format("%g", 0.0), "0");
+        assert_eq!(format("%#g", 1.5), "1.50000");
+        assert_eq!(format("%#.0f", 3.0), "3.");
+        assert_eq!(format("%#.0e", 3.0), "3.e+00");
+        assert_eq!(format("%E", 1500.0), "1.500000E+03");
+        assert_eq!(format("%G", 0.00001234), "1.234E-05");
+        assert_eq!(format("% .1f", 2.0), " 2.0");
+        assert_eq!(format("% .1f", -2.0), "-2.0");
+        assert_eq!(format("%+ .1f", 2.0), "+2.0");
+        assert_eq!(format("%-08.2f", 1.5), "1.50    ");
+        assert_eq!(format("%.3d", 7.0), "007");
+        assert_eq!(format("%05.3d", -7.0), " -007");
+        assert_eq!(format("%05d", -7.0), "-0007");
+        assert_eq!(format("%.0d", 0.0), "");
+        assert_eq!(format("%08.2f", f64::INFINITY), "     inf");
+        assert_eq!(format("%f", f64::NAN), "nan");
+        assert_eq!(format("%E", f64::NEG_INFINITY), "-INF");
+        assert_eq!(
+            "% #08.3G".parse::<NumberFormat>().unwrap().to_string(),
+            "% 0#8.3G"
+        );
+        assert_eq!(
+            "%10.6m".parse::<NumberFormat>().unwrap().to_string(),
+            "%10.6m"
+        );
+        assert!("%s".parse::<NumberFormat>().is_err());
+        assert!("5.2f".parse::<NumberFormat>().is_err());
+    }
+
+    #[test]
+    fn test_sexagesimal_format() {
+        let ra = 12.0 + 34.0 / 60.0 + 56.7 / 3600.0;
+        assert_eq!(format("%10.6m", ra), "  12:34:57");
+        assert_eq!(format("%10.8m", ra), "12:34:56.7");
+        assert_eq!(format("%11.9m", ra), "12:34:56.70");
+        assert_eq!(format("%9.5m", ra), "  12:34.9");
+        assert_eq!(format("%6.3m", ra), " 12:35");
+        assert_eq!(format("%9.6m", -20.5), "-20:30:00");
+        assert_eq!(format("%9.6m", -0.5), " -0:30:00");
+        assert_eq!(format("%.6m", 1.0), "1:00:00");
+        assert_eq!(format("%8.4m", ra), "  12:35");
+        assert_eq!(format("%10.7m", ra), " 12:34:57");
+        assert_eq!(format("%12.10m", ra), "12:34:56.700");
+        assert_eq!(format("%010.6m", 3.0), "   3:00:00");
+        assert_eq!(format("%9.6m", 23.9999999), " 24:00:00");
+        assert_eq!(format("%8.5m", 1.0 - 0.01 / 60.0), "  1:00.0");
+        assert_eq!(format("%9.6m", -0.0000001), " -0:00:00");
+    }
+
+    proptest! {
+        #[test]
+        fn test_sexagesimal_round_trip(
+            value in -360.0..360.0f64,
+            precision in prop::sample::select(vec![3usize, 5, 6, 8, 9, 10, 12]),
+            width in 0usize..14,
+        ) {
+            let text = format(&format!("%{}.{}m", width, precision), value);
+            let resolution = match precision {
+                3 | 4 => 1.0 / 60.0,
+                5 => 0.1 / 60.0,
+                6 | 7 => 1.0 / 3600.0,
+                _ => 10f64.powi(7 - precision as i32) / 3600.0,
+            };
+            let parsed = parse_number(&text).unwrap();
+            prop_assert!((parsed - value).abs() <= resolution / 2.0 + 1e-9, "{} -> {}", value, text);
+            prop_assert!(text.len() >= width);
+            prop_assert!(!text.contains(":60"));
+        }
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number(" -12.5 ").unwrap(), -12.5);
+        assert_eq!(parse_number("1e-3").unwrap(), 0.001);
+        assert_eq!(parse_number("12:30").unwrap(), 12.5);
+        assert_eq!(
+            parse_number("12:34:56.7").unwrap(),
+            12.0 + 34.0 / 60.0 + 56.7 / 3600.0
+        );
+        assert_eq!(parse_number("-0:30:00").unwrap(), -0.5);
+        assert_eq!(parse_number("-20 30").unwrap(), -20.5);
+        assert_eq!(parse_number("10;15").unwrap(), 10.25);
+        assert!(parse_number("").is_err());
+        assert!(parse_number("12:-30").is_err());
+        assert!(parse_number("1:2:3:4").is_err());
+        assert!(parse_number("abc").is_err());
+    }
+}
@@ -0,0 +1,248 @@
+//! Host environment probing
+//!
+//! Samples free memory, free disk space and CPU temperature of the computer
+//! running this crate and publishes them as the number vector `HOST_STATUS`
+//! of a virtual INDI device. On single board computers in remote
+//! observatories this lets operators watch the controller through the same
+//! protocol as the rest of the equipment.
+
+use crate::message::definition::{DefNumber, DefNumberVector};
+use crate::message::new::OneNumber;
+use crate::message::set::SetNumberVector;
+use crate::message::MessageType;
+use crate::property::timestamp::INDITimestamp;
+use crate::property::{PropertyPerm, PropertyState};
+use std::path::Path;
+use sysinfo::{Components, Disks, System};
+
+/// Host status property of the virtual device
+pub const HOST_STATUS: &str = "HOST_STATUS";
+
+const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Snapshot of the host environment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HostMetrics {
+    /// Available memory in bytes
+    pub free_memory: u64,
+    /// Total memory in bytes
+    pub total_memory: u64,
+    /// Available space on the root file system in bytes
+    pub free_disk: u64,
+    /// Total space on the root file system in bytes
+    pub total_disk: u64,
+    /// Hottest CPU sensor in degrees Celsius, if the host exposes one
+    pub cpu_temperature: Option<f64>,
+}
+
+/// Host environment probe publishing a virtual INDI device
+#[derive(Debug)]
+pub struct HostProbe {
+    device: String,
+    system: System,
+    disks: Disks,
+    components: Components,
+}
+
+impl HostProbe {
+    /// Default name of the virtual device
+    pub const DEFAULT_DEVICE: &'static str = "Host";
+
+    /// Create a new probe publishing as `device`
+    pub fn new(device: impl Into<String>) -> Self {
+        Self {
+            device: device.into(),
+            system: System::new(),
+            disks: Disks::new_with_refreshed_list(),
+            components: Components::new_with_refreshed_list(),
+        }
+    }
+
+    /// Virtual device name
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// Take a fresh sample of the host environment
+    pub fn sample(&mut self) -> HostMetrics {
+        self.system.refresh_memory();
+        self.disks.refresh(true);
+        self.components.refresh(true);
+
+        let root = self
+            .disks
+            .list()
+            .iter()
+            .find(|disk| disk.mount_point() == Path::new("/"))
+            .or_else(|| self.disks.list().first());
+
+        let sensors = self
+            .components
+            .list()
+            .iter()
+            .filter_map(|c| {
+                c.temperature()
+                    .map(|t| (c.label().to_lowercase(), t as f64))
+            })
+            .collect::<Vec<_>>();
+        let cpu_sensors = sensors
+            .iter()
+            .filter(|(label, _)| {
+                ["cpu", "core", "package", "soc"]
+                    .iter()
+                    .any(|needle| label.contains(needle))
+            })
+            .collect::<Vec<_>>();
+        let candidates = if cpu_sensors.is_empty() {
+            sensors.iter().collect()
+        } else {
+            cpu_sensors
+        };
+
+        HostMetrics {
+            free_memory: self.system.available_memory(),
+            total_memory: self.system.total_memory(),
+            free_disk: root.map(|d| d.available_space()).unwrap_or_default(),
+            total_disk: root.map(|d| d.total_space()).unwrap_or_default(),
+            cpu_temperature: candidates.into_iter().map(|(_, t)| *t).reduce(f64::max),
+        }
+    }
+
+    /// Definition of `HOST_STATUS` carrying a fresh sample
+    pub fn definition(&mut self) -> MessageType {
+        let values = element_values(&self.sample());
+        MessageType::DefNumberVector(DefNumberVector {
+            device: self.device.clone(),
+            name: HOST_STATUS.to_string(),
+            label: "Host Status".to_string(),
+            group: "Host".to_string(),
+            state: PropertyState::Ok,
+            perm: PropertyPerm::Ro,
+            timeout: 0,
+            timestamp: INDITimestamp::now().to_string(),
+            message: None,
+            numbers: values
+                .into_iter()
+                .map(|(name, label, value)| DefNumber {
+                    name: name.to_string(),
+                    label: label.to_string(),
+                    format: "%.1f".to_string(),
+                    min: "0".to_string(),
+                    max: "0".to_string(),
+                    step: "0".to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+        })
+    }
+
+    /// Update of `HOST_STATUS` carrying a fresh sample
+    pub fn update(&mut self) -> MessageType {
+        let values = element_values(&self.sample());
+        MessageType::SetNumberVector(SetNumberVector {
+            device: self.device.clone(),
+            name: HOST_STATUS.to_string(),
+            state: Some(PropertyState::Ok),
+            timeout: None,
+            timestamp: Some(INDITimestamp::now().to_string()),
+            message: None,
+            elements: values
+                .into_iter()
+                .map(|(name, _, value)| OneNumber {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+        })
+    }
+}
+
+impl Default for HostProbe {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_DEVICE)
+    }
+}
+
+/// Element names, labels and values of `HOST_STATUS`
+///
+/// `CPU_TEMPERATURE` is left out on hosts without a sensor.
+fn element_values(metrics: &HostMetrics) -> Vec<(&'static str, &'static str, f64)> {
+    let mut values = vec![
+        (
+            "MEMORY_FREE",
+            "Free memory (MB)",
+            metrics.free_memory as f64 / BYTES_PER_MB,
+        ),
+        (
+            "MEMORY_TOTAL",
+            "Total memory (MB)",
+            metrics.total_memory as f64 / BYTES_PER_MB,
+        ),
+        (
+            "DISK_FREE",
+            "Free disk (GB)",
+            metrics.free_disk as f64 / BYTES_PER_GB,
+        ),
+        (
+            "DISK_TOTAL",
+            "Total disk (GB)",
+            metrics.total_disk as f64 / BYTES_PER_GB,
+        ),
+    ];
+    if let Some(temperature) = metrics.cpu_temperature {
+        values.push(("CPU_TEMPERATURE", "CPU temperature (C)", temperature));
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_probe_messages() {
+        let mut probe = HostProbe::default();
+        let sample = probe.sample();
+        assert!(sample.total_memory > 0);
+        let elements = 4 + usize::from(sample.cpu_temperature.is_some());
+
+        match probe.definition() {
+            MessageType::DefNumberVector(def) => {
+                assert_eq!(def.device, "Host");
+                assert_eq!(def.name, HOST_STATUS);
+                assert_eq!(def.perm, PropertyPerm::Ro);
+                assert_eq!(def.numbers.len(), elements);
+            }
+            _ => panic!("Expected DefNumberVector"),
+        }
+        match probe.update() {
+            MessageType::SetNumberVector(set) => assert_eq!(set.elements.len(), elements),
+            _ => panic!("Expected SetNumberVector"),
+        }
+    }
+    #[test]
+    fn test_missing_cpu_temperature() {
+        let mut metrics = HostMetrics {
+            free_memory: 1 << 30,
+            total_memory: 4 << 30,
+            free_disk: 10 << 30,
+            total_disk: 32 << 30,
+            cpu_temperature: None,
+        };
+        let names = |metrics: &HostMetrics| {
+            element_values(metrics)
+                .into_iter()
+                .map(|(name, _, _)| name)
+                .collect::<Vec<_>>()
+        };
+        assert!(!names(&metrics).contains(&"CPU_TEMPERATURE"));
+
+        metrics.cpu_temperature = Some(48.5);
+        assert_eq!(
+            element_values(&metrics).last(),
+            Some(&("CPU_TEMPERATURE", "CPU temperature (C)", 48.5))
+        );
+        assert_eq!(element_values(&metrics)[0].2, 1024.0);
+    }
+}
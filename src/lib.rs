@@ -13,26 +13,68 @@
 //! - Error handling
 //! - Logging support
 
+/// Astronomical coordinate types and mount coordinate properties
+pub mod astro;
+/// Calibration frame capture
+pub mod capture;
 /// Client implementation for INDI protocol
 pub mod client;
+/// Runtime switches for verbose protocol logging
+pub mod debug;
+/// High-level device wrappers built on the client
+pub mod devices;
+/// Framework for drivers run by indiserver over stdin and stdout
+pub mod driver;
+/// Device drivers served in-process by the server
+pub mod drivers;
 /// Error types and handling
 pub mod error;
+/// Minimal FITS header parsing for BLOB payloads
+#[cfg(feature = "fits")]
+pub mod fits;
+/// printf-style and sexagesimal number formats
+pub mod format;
+/// Host environment probing published as a virtual device
+#[cfg(feature = "sysinfo")]
+pub mod host;
 /// Message types and handling
 pub mod message;
 /// Property types and handling
 pub mod property;
+/// Altitude safety limits for telescope mounts
+pub mod safety;
 /// Server implementation for the INDI protocol.
 /// This module provides functionality for running an INDI server that can handle
 /// device connections and property updates.
 pub mod server;
+/// Pluggable key-value storage for persistence features
+pub mod storage;
+/// User-defined validation of property updates
+pub mod validation;
 
 /// Common types and traits
+///
+/// Alias of the newest versioned prelude, currently [`prelude_v1`]. Code
+/// that must keep compiling across releases should import a versioned
+/// prelude instead.
 pub mod prelude {
-    pub use crate::client::{Client, ClientConfig};
+    pub use crate::prelude_v1::*;
+}
+
+/// Stable prelude, version 1
+///
+/// Items are only ever added to this module, never removed or changed in
+/// an incompatible way; a breaking reorganization gets a new
+/// `prelude_v2`. The set of items is pinned by `tests/public_api.rs`.
+pub mod prelude_v1 {
+    pub use crate::client::{Client, ClientBuilder, ClientConfig, ClientEvent};
     pub use crate::error::Error;
     pub use crate::message::MessageType;
-    pub use crate::property::{Property, PropertyPerm, PropertyState};
+    pub use crate::property::{
+        Property, PropertyPerm, PropertyState, PropertyValue, SwitchRule, SwitchState,
+    };
     pub use crate::server::{Server, ServerConfig};
+    pub use crate::Result;
 }
 
 /// Result type for INDI operations
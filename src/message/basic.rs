@@ -28,8 +28,21 @@ pub struct SetProperty {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename = "message")]
 pub struct Message {
+    /// Device the message originates from (optional, site-wide if absent)
+    #[serde(rename = "@device", default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    /// Message timestamp
+    #[serde(
+        rename = "@timestamp",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<String>,
+    /// Message text
+    #[serde(rename = "@message", default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
     /// Raw XML content of the message
-    #[serde(rename = "$value")]
+    #[serde(rename = "$text", default)]
     pub content: String,
 }
 
@@ -40,6 +53,19 @@ pub struct DelProperty {
     /// Device name
     #[serde(rename = "@device")]
     pub device: String,
+    /// Property name (optional, the whole device if absent)
+    #[serde(rename = "@name", default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Timestamp (optional)
+    #[serde(
+        rename = "@timestamp",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<String>,
+    /// Message (optional)
+    #[serde(rename = "@message", default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 /// Enable blob message
@@ -49,7 +75,30 @@ pub struct EnableBlob {
     /// Device name
     #[serde(rename = "@device")]
     pub device: String,
+    /// Property name (optional, all BLOB properties of the device if absent)
+    #[serde(rename = "@name", default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     /// Mode
-    #[serde(rename = "$value")]
+    #[serde(rename = "$text")]
     pub mode: String,
 }
+
+/// Keepalive request
+///
+/// The receiver answers with a [`PingReply`] carrying the same `uid`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename = "pingRequest")]
+pub struct PingRequest {
+    /// Identifier echoed in the reply
+    #[serde(rename = "@uid")]
+    pub uid: String,
+}
+
+/// Keepalive reply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename = "pingReply")]
+pub struct PingReply {
+    /// Identifier of the answered request
+    #[serde(rename = "@uid")]
+    pub uid: String,
+}
@@ -0,0 +1,566 @@
+//! Borrowed variants of the high-volume update messages
+//!
+//! Proxies and gateways mostly parse `set*Vector` and `new*Vector` messages
+//! only to inspect the device and property name and serialize them again.
+//! The types in this module hold their strings as [`Cow`] so attributes and
+//! element values without XML escapes, BLOB payloads included, borrow from
+//! the input instead of being copied. Names are borrowed rather than
+//! interned, so parsing allocates nothing for them; `benches/messages.rs`
+//! compares both parsers. [`MessageRef::into_owned`] converts to the
+//! regular [`MessageType`] when a message has to outlive its input buffer.
+//!
+//! Definitions and the other messages are only sent once per property or
+//! connection and are parsed as [`MessageType`].
+
+use crate::error::{Error, Result};
+use crate::message::new::{
+    NewBlobVector, NewNumberVector, NewSwitchVector, NewTextVector, OneBlob, OneLight, OneNumber,
+    OneSwitch, OneText,
+};
+use crate::message::set::{
+    SetBlobVector, SetLightVector, SetNumberVector, SetSwitchVector, SetTextVector,
+};
+use crate::message::MessageType;
+use crate::property::{PropertyState, SwitchState};
+use quick_xml::de::from_str;
+use quick_xml::se::to_string;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::borrow::Cow;
+
+fn owned(value: Option<Cow<'_, str>>) -> Option<String> {
+    value.map(Cow::into_owned)
+}
+
+/// Deserialize an optional attribute borrowing from the input, which serde
+/// only does by itself for a bare `Cow`
+fn borrowed<'de: 'a, 'a, D>(deserializer: D) -> std::result::Result<Option<Cow<'a, str>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Borrowed<'a>(#[serde(borrow)] Cow<'a, str>);
+
+    Ok(Option::<Borrowed<'a>>::deserialize(deserializer)?.map(|value| value.0))
+}
+
+/// Borrowed text element
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OneTextRef<'a> {
+    /// Element name
+    #[serde(rename = "@name", borrow)]
+    pub name: Cow<'a, str>,
+    /// Element value
+    #[serde(rename = "$text", default, borrow)]
+    pub value: Cow<'a, str>,
+}
+
+/// Borrowed number element
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OneNumberRef<'a> {
+    /// Element name
+    #[serde(rename = "@name", borrow)]
+    pub name: Cow<'a, str>,
+    /// Element value as sent by the driver
+    #[serde(rename = "$text", borrow)]
+    pub value: Cow<'a, str>,
+}
+
+/// Borrowed switch element
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OneSwitchRef<'a> {
+    /// Element name
+    #[serde(rename = "@name", borrow)]
+    pub name: Cow<'a, str>,
+    /// Switch state
+    #[serde(rename = "$text")]
+    pub value: SwitchState,
+}
+
+/// Borrowed light element
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OneLightRef<'a> {
+    /// Element name
+    #[serde(rename = "@name", borrow)]
+    pub name: Cow<'a, str>,
+    /// Light state
+    #[serde(rename = "$text")]
+    pub value: PropertyState,
+}
+
+/// Borrowed BLOB element
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OneBlobRef<'a> {
+    /// BLOB name
+    #[serde(rename = "@name", borrow)]
+    pub name: Cow<'a, str>,
+    /// BLOB size
+    #[serde(rename = "@size")]
+    pub size: usize,
+    /// BLOB format
+    #[serde(rename = "@format", borrow)]
+    pub format: Cow<'a, str>,
+    /// Length of the base64 encoded payload (optional)
+    #[serde(rename = "@enclen", default, skip_serializing_if = "Option::is_none")]
+    pub enclen: Option<usize>,
+    /// Base64 encoded BLOB value
+    #[serde(rename = "$text", default, borrow)]
+    pub value: Cow<'a, str>,
+}
+
+impl OneBlobRef<'_> {
+    fn into_owned(self) -> OneBlob {
+        OneBlob {
+            name: self.name.into_owned(),
+            size: self.size,
+            format: self.format.into_owned(),
+            enclen: self.enclen,
+            value: self.value.into_owned(),
+        }
+    }
+}
+
+/// Attributes shared by all borrowed set vectors
+macro_rules! set_vector_ref {
+    ($(#[$meta:meta])* $name:ident, $element:ident, $tag:literal) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct $name<'a> {
+            /// Device name
+            #[serde(rename = "@device", borrow)]
+            pub device: Cow<'a, str>,
+            /// Property name
+            #[serde(rename = "@name", borrow)]
+            pub name: Cow<'a, str>,
+            /// Property state (optional, unchanged if absent)
+            #[serde(rename = "@state", default, skip_serializing_if = "Option::is_none")]
+            pub state: Option<PropertyState>,
+            /// Property timeout (optional)
+            #[serde(rename = "@timeout", default, skip_serializing_if = "Option::is_none")]
+            pub timeout: Option<i32>,
+            /// Property timestamp (optional)
+            #[serde(
+                rename = "@timestamp",
+                default,
+                borrow,
+                deserialize_with = "borrowed",
+                skip_serializing_if = "Option::is_none"
+            )]
+            pub timestamp: Option<Cow<'a, str>>,
+            /// Message (optional)
+            #[serde(
+                rename = "@message",
+                default,
+                borrow,
+                deserialize_with = "borrowed",
+                skip_serializing_if = "Option::is_none"
+            )]
+            pub message: Option<Cow<'a, str>>,
+            /// Elements
+            #[serde(rename = $tag, default, borrow)]
+            pub elements: Vec<$element<'a>>,
+        }
+    };
+}
+
+set_vector_ref!(
+    /// Borrowed set text vector
+    SetTextVectorRef,
+    OneTextRef,
+    "oneText"
+);
+set_vector_ref!(
+    /// Borrowed set number vector
+    SetNumberVectorRef,
+    OneNumberRef,
+    "oneNumber"
+);
+set_vector_ref!(
+    /// Borrowed set switch vector
+    SetSwitchVectorRef,
+    OneSwitchRef,
+    "oneSwitch"
+);
+set_vector_ref!(
+    /// Borrowed set BLOB vector
+    SetBlobVectorRef,
+    OneBlobRef,
+    "oneBLOB"
+);
+set_vector_ref!(
+    /// Borrowed set light vector
+    ///
+    /// Light vectors have no timeout; the attribute is accepted and ignored
+    /// when converting to [`SetLightVector`].
+    SetLightVectorRef,
+    OneLightRef,
+    "oneLight"
+);
+
+/// Attributes shared by all borrowed new vectors
+macro_rules! new_vector_ref {
+    ($(#[$meta:meta])* $name:ident, $element:ident, $tag:literal) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct $name<'a> {
+            /// Device name
+            #[serde(rename = "@device", borrow)]
+            pub device: Cow<'a, str>,
+            /// Property name
+            #[serde(rename = "@name", borrow)]
+            pub name: Cow<'a, str>,
+            /// Property timestamp (optional)
+            #[serde(
+                rename = "@timestamp",
+                default,
+                borrow,
+                deserialize_with = "borrowed",
+                skip_serializing_if = "Option::is_none"
+            )]
+            pub timestamp: Option<Cow<'a, str>>,
+            /// Elements
+            #[serde(rename = $tag, default, borrow)]
+            pub elements: Vec<$element<'a>>,
+        }
+    };
+}
+
+new_vector_ref!(
+    /// Borrowed new text vector
+    NewTextVectorRef,
+    OneTextRef,
+    "oneText"
+);
+new_vector_ref!(
+    /// Borrowed new number vector
+    NewNumberVectorRef,
+    OneNumberRef,
+    "oneNumber"
+);
+new_vector_ref!(
+    /// Borrowed new switch vector
+    NewSwitchVectorRef,
+    OneSwitchRef,
+    "oneSwitch"
+);
+new_vector_ref!(
+    /// Borrowed new BLOB vector
+    NewBlobVectorRef,
+    OneBlobRef,
+    "oneBLOB"
+);
+
+/// Borrowed INDI update message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MessageRef<'a> {
+    /// Set text vector
+    #[serde(borrow)]
+    SetTextVector(SetTextVectorRef<'a>),
+    /// Set number vector
+    #[serde(borrow)]
+    SetNumberVector(SetNumberVectorRef<'a>),
+    /// Set switch vector
+    #[serde(borrow)]
+    SetSwitchVector(SetSwitchVectorRef<'a>),
+    /// Set light vector
+    #[serde(borrow)]
+    SetLightVector(SetLightVectorRef<'a>),
+    /// Set BLOB vector
+    #[serde(rename = "setBLOBVector", borrow)]
+    SetBlobVector(SetBlobVectorRef<'a>),
+    /// New text vector
+    #[serde(borrow)]
+    NewTextVector(NewTextVectorRef<'a>),
+    /// New number vector
+    #[serde(borrow)]
+    NewNumberVector(NewNumberVectorRef<'a>),
+    /// New switch vector
+    #[serde(borrow)]
+    NewSwitchVector(NewSwitchVectorRef<'a>),
+    /// New BLOB vector
+    #[serde(rename = "newBLOBVector", borrow)]
+    NewBlobVector(NewBlobVectorRef<'a>),
+}
+
+impl<'a> MessageRef<'a> {
+    /// Parse a message borrowing from `xml`
+    ///
+    /// Only `set*Vector` and `new*Vector` are supported; anything else is
+    /// an error and should be parsed as a [`MessageType`] instead.
+    pub fn parse(xml: &'a str) -> Result<Self> {
+        from_str(xml.trim()).map_err(|e| Error::ParseError(e.to_string()))
+    }
+
+    /// Parse a message borrowing from raw bytes, such as a frame read from
+    /// a socket
+    pub fn from_slice(bytes: &'a [u8]) -> Result<Self> {
+        Self::parse(std::str::from_utf8(bytes)?)
+    }
+
+    /// Device name
+    pub fn device(&self) -> &str {
+        match self {
+            MessageRef::SetTextVector(m) => &m.device,
+            MessageRef::SetNumberVector(m) => &m.device,
+            MessageRef::SetSwitchVector(m) => &m.device,
+            MessageRef::SetLightVector(m) => &m.device,
+            MessageRef::SetBlobVector(m) => &m.device,
+            MessageRef::NewTextVector(m) => &m.device,
+            MessageRef::NewNumberVector(m) => &m.device,
+            MessageRef::NewSwitchVector(m) => &m.device,
+            MessageRef::NewBlobVector(m) => &m.device,
+        }
+    }
+
+    /// Property name
+    pub fn name(&self) -> &str {
+        match self {
+            MessageRef::SetTextVector(m) => &m.name,
+            MessageRef::SetNumberVector(m) => &m.name,
+            MessageRef::SetSwitchVector(m) => &m.name,
+            MessageRef::SetLightVector(m) => &m.name,
+            MessageRef::SetBlobVector(m) => &m.name,
+            MessageRef::NewTextVector(m) => &m.name,
+            MessageRef::NewNumberVector(m) => &m.name,
+            MessageRef::NewSwitchVector(m) => &m.name,
+            MessageRef::NewBlobVector(m) => &m.name,
+        }
+    }
+
+    /// Convert message to XML
+    pub fn to_xml(&self) -> Result<String> {
+        to_string(self).map_err(|e| Error::SerializationError(e.to_string()))
+    }
+
+    /// Convert to an owned [`MessageType`]
+    pub fn into_owned(self) -> MessageType {
+        match self {
+            MessageRef::SetTextVector(m) => MessageType::SetTextVector(SetTextVector {
+                device: m.device.into_owned(),
+                name: m.name.into_owned(),
+                state: m.state,
+                timeout: m.timeout,
+                timestamp: owned(m.timestamp),
+                message: owned(m.message),
+                elements: m
+                    .elements
+                    .into_iter()
+                    .map(|e| OneText {
+                        name: e.name.into_owned(),
+                        value: e.value.into_owned(),
+                    })
+                    .collect(),
+            }),
+            MessageRef::SetNumberVector(m) => MessageType::SetNumberVector(SetNumberVector {
+                device: m.device.into_owned(),
+                name: m.name.into_owned(),
+                state: m.state,
+                timeout: m.timeout,
+                timestamp: owned(m.timestamp),
+                message: owned(m.message),
+                elements: m
+                    .elements
+                    .into_iter()
+                    .map(|e| OneNumber {
+                        name: e.name.into_owned(),
+                        value: e.value.into_owned(),
+                    })
+                    .collect(),
+            }),
+            MessageRef::SetSwitchVector(m) => MessageType::SetSwitchVector(SetSwitchVector {
+                device: m.device.into_owned(),
+                name: m.name.into_owned(),
+                state: m.state,
+                timeout: m.timeout,
+                timestamp: owned(m.timestamp),
+                message: owned(m.message),
+                elements: m
+                    .elements
+                    .into_iter()
+                    .map(|e| OneSwitch {
+                        name: e.name.into_owned(),
+                        value: e.value,
+                    })
+                    .collect(),
+            }),
+            MessageRef::SetLightVector(m) => MessageType::SetLightVector(SetLightVector {
+                device: m.device.into_owned(),
+                name: m.name.into_owned(),
+                state: m.state,
+                timestamp: owned(m.timestamp),
+                message: owned(m.message),
+                elements: m
+                    .elements
+                    .into_iter()
+                    .map(|e| OneLight {
+                        name: e.name.into_owned(),
+                        value: e.value,
+                    })
+                    .collect(),
+            }),
+            MessageRef::SetBlobVector(m) => MessageType::SetBlobVector(SetBlobVector {
+                device: m.device.into_owned(),
+                name: m.name.into_owned(),
+                state: m.state,
+                timeout: m.timeout,
+                timestamp: owned(m.timestamp),
+                message: owned(m.message),
+                elements: m.elements.into_iter().map(OneBlobRef::into_owned).collect(),
+            }),
+            MessageRef::NewTextVector(m) => MessageType::NewTextVector(NewTextVector {
+                device: m.device.into_owned(),
+                name: m.name.into_owned(),
+                timestamp: owned(m.timestamp),
+                elements: m
+                    .elements
+                    .into_iter()
+                    .map(|e| OneText {
+                        name: e.name.into_owned(),
+                        value: e.value.into_owned(),
+                    })
+                    .collect(),
+            }),
+            MessageRef::NewNumberVector(m) => MessageType::NewNumberVector(NewNumberVector {
+                device: m.device.into_owned(),
+                name: m.name.into_owned(),
+                timestamp: owned(m.timestamp),
+                elements: m
+                    .elements
+                    .into_iter()
+                    .map(|e| OneNumber {
+                        name: e.name.into_owned(),
+                        value: e.value.into_owned(),
+                    })
+                    .collect(),
+            }),
+            MessageRef::NewSwitchVector(m) => MessageType::NewSwitchVector(NewSwitchVector {
+                device: m.device.into_owned(),
+                name: m.name.into_owned(),
+                timestamp: owned(m.timestamp),
+                elements: m
+                    .elements
+                    .into_iter()
+                    .map(|e| OneSwitch {
+                        name: e.name.into_owned(),
+                        value: e.value,
+                    })
+                    .collect(),
+            }),
+            MessageRef::NewBlobVector(m) => MessageType::NewBlobVector(NewBlobVector {
+                device: m.device.into_owned(),
+                name: m.name.into_owned(),
+                timestamp: owned(m.timestamp),
+                elements: m.elements.into_iter().map(OneBlobRef::into_owned).collect(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_borrows_from_input() {
+        let xml = r#"<setNumberVector device="CCD Simulator" name="CCD_TEMPERATURE" state="Busy" timestamp="2024-01-01T00:00:00">
+<oneNumber name="CCD_TEMPERATURE_VALUE">-10.5</oneNumber>
+</setNumberVector>"#;
+        let message = MessageRef::parse(xml).unwrap();
+        assert_eq!(message.device(), "CCD Simulator");
+        assert_eq!(message.name(), "CCD_TEMPERATURE");
+
+        let MessageRef::SetNumberVector(set) = &message else {
+            panic!("Expected SetNumberVector");
+        };
+        assert!(matches!(set.device, Cow::Borrowed(_)));
+        assert!(matches!(set.elements[0].name, Cow::Borrowed(_)));
+        assert_eq!(set.elements[0].value.trim(), "-10.5");
+
+        let reparsed = MessageRef::parse(&message.to_xml().unwrap())
+            .unwrap()
+            .into_owned();
+        match reparsed {
+            MessageType::SetNumberVector(set) => {
+                assert_eq!(set.state, Some(PropertyState::Busy));
+                assert_eq!(set.elements[0].name, "CCD_TEMPERATURE_VALUE");
+            }
+            _ => panic!("Expected SetNumberVector"),
+        }
+    }
+
+    #[test]
+    fn test_escaped_values_are_owned() {
+        let xml = r#"<setTextVector device="Mount" name="INFO" message="c &lt; d"><oneText name="NOTE">a &amp; b</oneText></setTextVector>"#;
+        let MessageRef::SetTextVector(set) = MessageRef::parse(xml).unwrap() else {
+            panic!("Expected SetTextVector");
+        };
+        assert_eq!(set.elements[0].value, "a & b");
+        assert_eq!(set.message.as_deref(), Some("c < d"));
+        assert!(MessageRef::parse(r#"<getProperties version="1.7"/>"#).is_err());
+    }
+
+    #[test]
+    fn test_from_slice() {
+        let frame = b"<setSwitchVector device=\"Mount\" name=\"TELESCOPE_PARK\" state=\"Ok\"><oneSwitch name=\"PARK\">On</oneSwitch></setSwitchVector>\n";
+        let MessageRef::SetSwitchVector(set) = MessageRef::from_slice(frame).unwrap() else {
+            panic!("Expected SetSwitchVector");
+        };
+        assert!(matches!(set.name, Cow::Borrowed("TELESCOPE_PARK")));
+        assert!(matches!(
+            MessageType::from_slice(frame).unwrap(),
+            MessageType::SetSwitchVector(_)
+        ));
+        assert!(MessageRef::from_slice(b"<setTextVector device=\"\xff\"/>").is_err());
+    }
+
+    #[test]
+    fn test_blob_payload_is_borrowed() {
+        let message = MessageType::SetBlobVector(SetBlobVector {
+            device: "CCD Simulator".to_string(),
+            name: "CCD1".to_string(),
+            state: Some(PropertyState::Ok),
+            timeout: None,
+            timestamp: None,
+            message: None,
+            elements: vec![OneBlob::new("CCD1", ".fits", b"not really an image")],
+        });
+        let xml = message.to_xml().unwrap();
+        let MessageRef::SetBlobVector(set) = MessageRef::parse(&xml).unwrap() else {
+            panic!("Expected SetBlobVector");
+        };
+        assert!(matches!(set.elements[0].value, Cow::Borrowed(_)));
+        assert_eq!(set.elements[0].size, 19);
+
+        let owned = MessageRef::SetBlobVector(set).into_owned();
+        let MessageType::SetBlobVector(set) = owned else {
+            panic!("Expected SetBlobVector");
+        };
+        assert_eq!(set.elements[0].get_data().unwrap(), b"not really an image");
+    }
+
+    #[test]
+    fn test_new_vectors() {
+        let xml = r#"<newNumberVector device="Mount" name="EQUATORIAL_EOD_COORD"><oneNumber name="RA">5.5</oneNumber><oneNumber name="DEC">-10</oneNumber></newNumberVector>"#;
+        let message = MessageRef::parse(xml).unwrap();
+        assert_eq!(message.device(), "Mount");
+        assert_eq!(message.name(), "EQUATORIAL_EOD_COORD");
+        let MessageType::NewNumberVector(new) = message.into_owned() else {
+            panic!("Expected NewNumberVector");
+        };
+        assert_eq!(new.elements[1].value, "-10");
+
+        let xml = r#"<newSwitchVector device="Mount" name="TELESCOPE_PARK"><oneSwitch name="PARK">On</oneSwitch></newSwitchVector>"#;
+        let message = MessageRef::parse(xml).unwrap();
+        let reparsed: MessageType = message.to_xml().unwrap().parse().unwrap();
+        let MessageType::NewSwitchVector(new) = reparsed else {
+            panic!("Expected NewSwitchVector");
+        };
+        assert_eq!(new.elements[0].value, SwitchState::On);
+
+        let xml = r#"<newBLOBVector device="CCD" name="UPLOAD"><oneBLOB name="FILE" size="3" format=".txt">YWJj</oneBLOB></newBLOBVector>"#;
+        assert!(matches!(
+            MessageRef::parse(xml).unwrap(),
+            MessageRef::NewBlobVector(_)
+        ));
+    }
+}
@@ -1,4 +1,8 @@
 use crate::error::Result;
+use crate::format::{parse_number, NumberFormat};
+use crate::message::set::{
+    SetBlobVector, SetLightVector, SetNumberVector, SetSwitchVector, SetTextVector,
+};
 use crate::prelude::PropertyPerm;
 use crate::property::{PropertyState, SwitchRule, SwitchState};
 use serde::{Deserialize, Serialize};
@@ -14,10 +18,10 @@ pub struct DefTextVector {
     #[serde(rename = "@name")]
     pub name: String,
     /// Property label
-    #[serde(rename = "@label")]
+    #[serde(rename = "@label", default)]
     pub label: String,
     /// Property group
-    #[serde(rename = "@group")]
+    #[serde(rename = "@group", default)]
     pub group: String,
     /// Property state
     #[serde(rename = "@state")]
@@ -26,13 +30,16 @@ pub struct DefTextVector {
     #[serde(rename = "@perm")]
     pub perm: PropertyPerm,
     /// Property timeout
-    #[serde(rename = "@timeout")]
+    #[serde(rename = "@timeout", default)]
     pub timeout: i32,
     /// Property timestamp
-    #[serde(rename = "@timestamp")]
+    #[serde(rename = "@timestamp", default)]
     pub timestamp: String,
+    /// Message
+    #[serde(rename = "@message", default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
     /// Text elements
-    #[serde(rename = "defText")]
+    #[serde(rename = "defText", default)]
     pub texts: Vec<DefText>,
 }
 
@@ -47,10 +54,10 @@ pub struct DefNumberVector {
     #[serde(rename = "@name")]
     pub name: String,
     /// Property label
-    #[serde(rename = "@label")]
+    #[serde(rename = "@label", default)]
     pub label: String,
     /// Property group
-    #[serde(rename = "@group")]
+    #[serde(rename = "@group", default)]
     pub group: String,
     /// Property state
     #[serde(rename = "@state")]
@@ -59,13 +66,16 @@ pub struct DefNumberVector {
     #[serde(rename = "@perm")]
     pub perm: PropertyPerm,
     /// Property timeout
-    #[serde(rename = "@timeout")]
+    #[serde(rename = "@timeout", default)]
     pub timeout: i32,
     /// Property timestamp
-    #[serde(rename = "@timestamp")]
+    #[serde(rename = "@timestamp", default)]
     pub timestamp: String,
+    /// Message
+    #[serde(rename = "@message", default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
     /// Number elements
-    #[serde(rename = "defNumber")]
+    #[serde(rename = "defNumber", default)]
     pub numbers: Vec<DefNumber>,
 }
 
@@ -77,7 +87,7 @@ pub struct DefSwitch {
     #[serde(rename = "@name")]
     pub name: String,
     /// Switch label
-    #[serde(rename = "@label")]
+    #[serde(rename = "@label", default)]
     pub label: String,
     /// Switch state
     #[serde(rename = "$text")]
@@ -91,10 +101,10 @@ pub struct DefText {
     #[serde(rename = "@name")]
     pub name: String,
     /// Text label
-    #[serde(rename = "@label")]
+    #[serde(rename = "@label", default)]
     pub label: String,
     /// Text value
-    #[serde(rename = "$text")]
+    #[serde(rename = "$text", default)]
     pub value: String,
 }
 
@@ -105,7 +115,7 @@ pub struct DefNumber {
     #[serde(rename = "@name")]
     pub name: String,
     /// Number label
-    #[serde(rename = "@label")]
+    #[serde(rename = "@label", default)]
     pub label: String,
     /// Number format
     #[serde(rename = "@format")]
@@ -137,10 +147,10 @@ pub struct DefSwitchVector {
     #[serde(rename = "@name")]
     pub name: String,
     /// Property label
-    #[serde(rename = "@label")]
+    #[serde(rename = "@label", default)]
     pub label: String,
     /// Property group
-    #[serde(rename = "@group")]
+    #[serde(rename = "@group", default)]
     pub group: String,
     /// Property state
     #[serde(rename = "@state")]
@@ -152,20 +162,208 @@ pub struct DefSwitchVector {
     #[serde(rename = "@rule")]
     pub rule: SwitchRule,
     /// Property timeout
-    #[serde(rename = "@timeout")]
+    #[serde(rename = "@timeout", default)]
     pub timeout: i32,
     /// Property timestamp
-    #[serde(rename = "@timestamp")]
+    #[serde(rename = "@timestamp", default)]
     pub timestamp: String,
     /// Message
-    #[serde(rename = "@message")]
-    pub message: String,
+    #[serde(rename = "@message", default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
     /// Switch elements
-    #[serde(rename = "defSwitch")]
+    #[serde(rename = "defSwitch", default)]
     pub switches: Vec<DefSwitch>,
 }
 
+/// Light element in a light vector
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefLight {
+    /// Light name
+    #[serde(rename = "@name")]
+    pub name: String,
+    /// Light label
+    #[serde(rename = "@label", default)]
+    pub label: String,
+    /// Light state
+    #[serde(rename = "$text")]
+    pub state: PropertyState,
+}
+
+/// Light vector definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "defLightVector")]
+pub struct DefLightVector {
+    /// Device name
+    #[serde(rename = "@device")]
+    pub device: String,
+    /// Property name
+    #[serde(rename = "@name")]
+    pub name: String,
+    /// Property label
+    #[serde(rename = "@label", default)]
+    pub label: String,
+    /// Property group
+    #[serde(rename = "@group", default)]
+    pub group: String,
+    /// Property state
+    #[serde(rename = "@state")]
+    pub state: PropertyState,
+    /// Property timestamp
+    #[serde(rename = "@timestamp", default)]
+    pub timestamp: String,
+    /// Message
+    #[serde(rename = "@message", default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Light elements
+    #[serde(rename = "defLight", default)]
+    pub lights: Vec<DefLight>,
+}
+
+/// BLOB element in a BLOB vector
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefBlob {
+    /// BLOB name
+    #[serde(rename = "@name")]
+    pub name: String,
+    /// BLOB label
+    #[serde(rename = "@label", default)]
+    pub label: String,
+}
+
+/// BLOB vector definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "defBLOBVector")]
+pub struct DefBlobVector {
+    /// Device name
+    #[serde(rename = "@device")]
+    pub device: String,
+    /// Property name
+    #[serde(rename = "@name")]
+    pub name: String,
+    /// Property label
+    #[serde(rename = "@label", default)]
+    pub label: String,
+    /// Property group
+    #[serde(rename = "@group", default)]
+    pub group: String,
+    /// Property state
+    #[serde(rename = "@state")]
+    pub state: PropertyState,
+    /// Property permission
+    #[serde(rename = "@perm")]
+    pub perm: PropertyPerm,
+    /// Property timeout
+    #[serde(rename = "@timeout", default)]
+    pub timeout: i32,
+    /// Property timestamp
+    #[serde(rename = "@timestamp", default)]
+    pub timestamp: String,
+    /// Message
+    #[serde(rename = "@message", default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// BLOB elements
+    #[serde(rename = "defBLOB", default)]
+    pub blobs: Vec<DefBlob>,
+}
+
+impl DefTextVector {
+    /// Merge an update of this property into the definition
+    ///
+    /// Only the elements present in the update change, unknown element
+    /// names are ignored. State, timeout, timestamp and message are
+    /// replaced when the update carries them. The caller matches device
+    /// and property names.
+    pub fn apply(&mut self, set: &SetTextVector) {
+        for one in &set.elements {
+            if let Some(text) = self.texts.iter_mut().find(|t| t.name == one.name) {
+                text.value = one.value.clone();
+            }
+        }
+        merge_attributes(
+            (&mut self.state, &mut self.timestamp, &mut self.message),
+            (set.state, set.timestamp.as_deref(), set.message.as_deref()),
+        );
+        self.timeout = set.timeout.unwrap_or(self.timeout);
+    }
+}
+
+impl DefNumberVector {
+    /// Merge an update of this property into the definition
+    ///
+    /// See [`DefTextVector::apply`].
+    pub fn apply(&mut self, set: &SetNumberVector) {
+        for one in &set.elements {
+            if let Some(number) = self.numbers.iter_mut().find(|n| n.name == one.name) {
+                number.value = one.value.clone();
+            }
+        }
+        merge_attributes(
+            (&mut self.state, &mut self.timestamp, &mut self.message),
+            (set.state, set.timestamp.as_deref(), set.message.as_deref()),
+        );
+        self.timeout = set.timeout.unwrap_or(self.timeout);
+    }
+}
+
+impl DefNumber {
+    /// Current value rendered with the element's format
+    ///
+    /// The value may be decimal or sexagesimal.
+    pub fn format_value(&self) -> Result<String> {
+        let format = self.format.parse::<NumberFormat>()?;
+        Ok(format.format(parse_number(&self.value)?))
+    }
+}
+
+impl DefLightVector {
+    /// Merge an update of this property into the definition
+    ///
+    /// See [`DefTextVector::apply`]. Lights have no timeout.
+    pub fn apply(&mut self, set: &SetLightVector) {
+        for one in &set.elements {
+            if let Some(light) = self.lights.iter_mut().find(|l| l.name == one.name) {
+                light.state = one.value;
+            }
+        }
+        merge_attributes(
+            (&mut self.state, &mut self.timestamp, &mut self.message),
+            (set.state, set.timestamp.as_deref(), set.message.as_deref()),
+        );
+    }
+}
+
+impl DefBlobVector {
+    /// Merge an update of this property into the definition
+    ///
+    /// Definitions carry no BLOB data, so only the attributes change. See
+    /// [`DefTextVector::apply`].
+    pub fn apply(&mut self, set: &SetBlobVector) {
+        merge_attributes(
+            (&mut self.state, &mut self.timestamp, &mut self.message),
+            (set.state, set.timestamp.as_deref(), set.message.as_deref()),
+        );
+        self.timeout = set.timeout.unwrap_or(self.timeout);
+    }
+}
+
 impl DefSwitchVector {
+    /// Merge an update of this property into the definition
+    ///
+    /// See [`DefTextVector::apply`]. The switch rule is not enforced, the
+    /// device is the authority on switch states.
+    pub fn apply(&mut self, set: &SetSwitchVector) {
+        for one in &set.elements {
+            if let Some(switch) = self.switches.iter_mut().find(|s| s.name == one.name) {
+                switch.state = one.value;
+            }
+        }
+        merge_attributes(
+            (&mut self.state, &mut self.timestamp, &mut self.message),
+            (set.state, set.timestamp.as_deref(), set.message.as_deref()),
+        );
+        self.timeout = set.timeout.unwrap_or(self.timeout);
+    }
+
     /// Validates the switch vector according to its rule
     pub fn validate(&self) -> Result<()> {
         match self.rule {
@@ -198,3 +396,19 @@ impl DefSwitchVector {
         Ok(())
     }
 }
+
+/// Replace state, timestamp and message with those of an update, where set
+fn merge_attributes(
+    (state, timestamp, message): (&mut PropertyState, &mut String, &mut Option<String>),
+    (new_state, new_timestamp, new_message): (Option<PropertyState>, Option<&str>, Option<&str>),
+) {
+    if let Some(new_state) = new_state {
+        *state = new_state;
+    }
+    if let Some(new_timestamp) = new_timestamp {
+        *timestamp = new_timestamp.to_string();
+    }
+    if let Some(new_message) = new_message {
+        *message = Some(new_message.to_string());
+    }
+}
@@ -0,0 +1,92 @@
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use crate::error::{Error, Result};
+
+/// Indentation of the elements of a vector
+const INDENT: &str = "    ";
+
+/// Reformat a compact message the way C indilib writes it
+///
+/// Children of the root go on their own lines, indented, with their value
+/// on a separate line in between their tags. Root elements with only text,
+/// like `enableBLOB`, stay on one line. The output ends with a newline.
+pub(crate) fn format(xml: &str) -> Result<String> {
+    let mut reader = Reader::from_str(xml);
+    let mut out = String::with_capacity(xml.len() + xml.len() / 4);
+    let mut depth = 0usize;
+    // Root start tag whose line is not finished yet
+    let mut open_root = false;
+    let invalid = |e: quick_xml::Error| Error::SerializationError(e.to_string());
+    loop {
+        let event = reader.read_event().map_err(invalid)?;
+        let raw = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+        match event {
+            Event::Start(tag) => {
+                if depth == 0 {
+                    out.push('<');
+                    out.push_str(&raw(&tag));
+                    out.push('>');
+                    open_root = true;
+                } else {
+                    if std::mem::take(&mut open_root) {
+                        out.push('\n');
+                    }
+                    out.push_str(INDENT);
+                    out.push('<');
+                    out.push_str(&raw(&tag));
+                    out.push_str(">\n");
+                }
+                depth += 1;
+            }
+            Event::Empty(tag) if depth == 1 => {
+                // indilib always writes the value lines, even when empty
+                if std::mem::take(&mut open_root) {
+                    out.push('\n');
+                }
+                let tag = raw(&tag);
+                let name = tag.split_whitespace().next().unwrap_or_default();
+                out.push_str(&format!("{}<{}>\n\n{}</{}>\n", INDENT, tag, INDENT, name));
+            }
+            Event::Empty(tag) => {
+                out.push('<');
+                out.push_str(&raw(&tag));
+                out.push_str("/>");
+                if depth == 0 {
+                    out.push('\n');
+                }
+            }
+            Event::Text(text) => {
+                let text = raw(&text);
+                if depth == 1 && open_root {
+                    out.push_str(&text);
+                } else if depth == 2 {
+                    out.push_str(text.trim());
+                    out.push('\n');
+                }
+            }
+            Event::CData(data) => {
+                out.push_str("<![CDATA[");
+                out.push_str(&raw(&data));
+                out.push_str("]]>");
+            }
+            Event::End(tag) => {
+                depth = depth.saturating_sub(1);
+                let name = raw(tag.name().as_ref());
+                match depth {
+                    0 => {
+                        if !std::mem::take(&mut open_root) && !out.ends_with('\n') {
+                            out.push('\n');
+                        }
+                        out.push_str(&format!("</{}>\n", name));
+                    }
+                    1 => out.push_str(&format!("{}</{}>\n", INDENT, name)),
+                    _ => out.push_str(&format!("</{}>", name)),
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+    Ok(out)
+}
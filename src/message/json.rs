@@ -0,0 +1,169 @@
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use serde_json::{Map, Value};
+
+use crate::error::{Error, Result};
+
+/// Key holding the root element name
+const TYPE: &str = "type";
+/// Key holding the elements of a vector
+const ELEMENTS: &str = "elements";
+/// Key holding text content
+const VALUE: &str = "value";
+
+/// Convert a serialized message to its JSON form
+///
+/// The schema mirrors the wire format: `type` is the root element name,
+/// attributes keep their INDI names, the elements of a vector are listed
+/// under `elements` and text content is stored as `value`. All values are
+/// strings, exactly as sent, so sexagesimal numbers survive unchanged.
+pub(crate) fn to_json(xml: &str) -> Result<Value> {
+    let mut reader = Reader::from_str(xml);
+    let invalid = |e: quick_xml::Error| Error::SerializationError(e.to_string());
+    let mut root: Option<Map<String, Value>> = None;
+    let mut elements = Vec::new();
+    // Element being read, with its text so far
+    let mut open: Option<Map<String, Value>> = None;
+    loop {
+        let event = reader.read_event().map_err(invalid)?;
+        match event {
+            Event::Start(start) | Event::Empty(start) if root.is_none() => {
+                let mut object = attributes(&start)?;
+                let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+                object.insert(TYPE.to_string(), Value::String(name));
+                root = Some(object);
+            }
+            Event::Start(start) => open = Some(attributes(&start)?),
+            Event::Empty(start) => elements.push(Value::Object(attributes(&start)?)),
+            Event::Text(text) => {
+                let text = text.unescape().map_err(invalid)?;
+                let Some(target) = open.as_mut().or(root.as_mut()) else {
+                    continue;
+                };
+                let value = target
+                    .entry(VALUE)
+                    .or_insert_with(|| Value::String(String::new()));
+                if let Value::String(value) = value {
+                    value.push_str(&text);
+                }
+            }
+            Event::End(_) => match open.take() {
+                Some(element) => elements.push(Value::Object(element)),
+                None => break,
+            },
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+    let mut root = root.ok_or_else(|| Error::SerializationError("Empty message".to_string()))?;
+    if !elements.is_empty() {
+        root.insert(ELEMENTS.to_string(), Value::Array(elements));
+    }
+    Ok(Value::Object(root))
+}
+
+/// Convert the JSON form of a message back to XML
+pub(crate) fn from_json(json: &Value) -> Result<String> {
+    let invalid = |reason: &str| Error::ParseError(format!("Invalid JSON message: {}", reason));
+    let object = json.as_object().ok_or_else(|| invalid("not an object"))?;
+    let root = object
+        .get(TYPE)
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("no type"))?;
+    let element_name = element_name(root);
+    let elements = match object.get(ELEMENTS) {
+        Some(Value::Array(elements)) => elements.as_slice(),
+        Some(_) => return Err(invalid("elements is not a list")),
+        None => &[],
+    };
+
+    let mut out = Vec::new();
+    let mut writer = Writer::new(&mut out);
+    let start = start_tag(root, object, &[TYPE, ELEMENTS, VALUE])?;
+    let text = object.get(VALUE).and_then(Value::as_str);
+    if elements.is_empty() && text.is_none() {
+        write(&mut writer, Event::Empty(start))?;
+    } else {
+        write(&mut writer, Event::Start(start))?;
+        if let Some(text) = text {
+            write(&mut writer, Event::Text(BytesText::new(text)))?;
+        }
+        for element in elements {
+            let element = element
+                .as_object()
+                .ok_or_else(|| invalid("element is not an object"))?;
+            let name = element_name
+                .as_deref()
+                .ok_or_else(|| invalid("elements of a message without any"))?;
+            write(
+                &mut writer,
+                Event::Start(start_tag(name, element, &[VALUE])?),
+            )?;
+            if let Some(value) = element.get(VALUE).and_then(Value::as_str) {
+                write(&mut writer, Event::Text(BytesText::new(value)))?;
+            }
+            write(&mut writer, Event::End(BytesEnd::new(name)))?;
+        }
+        write(&mut writer, Event::End(BytesEnd::new(root)))?;
+    }
+    String::from_utf8(out).map_err(|e| Error::SerializationError(e.to_string()))
+}
+
+/// Object with the attributes of an element
+fn attributes(start: &BytesStart<'_>) -> Result<Map<String, Value>> {
+    let mut object = Map::new();
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+        let value = attribute.unescape_value()?.into_owned();
+        object.insert(key, Value::String(value));
+    }
+    Ok(object)
+}
+
+/// Start tag with the scalar members of `object` as attributes, except
+/// those in `skip`
+fn start_tag<'a>(
+    name: &'a str,
+    object: &'a Map<String, Value>,
+    skip: &[&str],
+) -> Result<BytesStart<'a>> {
+    let mut start = BytesStart::new(name);
+    for (key, value) in object
+        .iter()
+        .filter(|(key, _)| !skip.contains(&key.as_str()))
+    {
+        let value = match value {
+            Value::String(value) => value.clone(),
+            Value::Number(_) | Value::Bool(_) => value.to_string(),
+            Value::Null => continue,
+            _ => {
+                return Err(Error::ParseError(format!(
+                    "Invalid JSON message: attribute {} is not a scalar",
+                    key
+                )))
+            }
+        };
+        start.push_attribute((key.as_str(), value.as_str()));
+    }
+    Ok(start)
+}
+
+fn write(writer: &mut Writer<&mut Vec<u8>>, event: Event<'_>) -> Result<()> {
+    writer
+        .write_event(event)
+        .map_err(|e| Error::SerializationError(e.to_string()))
+}
+
+/// Element name of a vector, e.g. `defNumber` for `defNumberVector` and
+/// `oneNumber` for `setNumberVector`
+fn element_name(root: &str) -> Option<String> {
+    let kind = root.strip_suffix("Vector")?;
+    if kind.starts_with("def") {
+        return Some(kind.to_string());
+    }
+    let kind = kind
+        .strip_prefix("set")
+        .or_else(|| kind.strip_prefix("new"))?;
+    Some(format!("one{}", kind))
+}
@@ -0,0 +1,109 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::error::Result;
+
+/// Content of a message that parsing ignored
+///
+/// Drivers add vendor attributes and elements to standard messages. The
+/// default parser drops them silently, [`super::MessageType::from_str_lenient`]
+/// reports them as warnings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    /// Name of the element the unknown content was found in, or of the
+    /// unknown element itself
+    pub element: String,
+    /// Name of the unknown attribute, `None` for an unknown element
+    pub attribute: Option<String>,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.attribute {
+            Some(attribute) => write!(f, "unknown attribute {} of <{}>", attribute, self.element),
+            None => write!(f, "unknown element <{}>", self.element),
+        }
+    }
+}
+
+/// Attribute names by element path, like `defTextVector/defText`
+type Shape = HashMap<String, HashSet<String>>;
+
+/// Content of `input` missing from `parsed`, the same message serialized
+/// again after parsing
+///
+/// Whatever the message types know survives the round trip, so anything
+/// else was ignored. Each unknown name is reported once.
+pub(crate) fn ignored_content(input: &str, parsed: &str) -> Result<Vec<ParseWarning>> {
+    let known = shape(parsed)?;
+    let mut warnings = Vec::new();
+    for (path, attributes) in elements(input)? {
+        let element = path.rsplit('/').next().unwrap_or(&path).to_string();
+        let found = match known.get(&path) {
+            None => vec![ParseWarning {
+                element,
+                attribute: None,
+            }],
+            Some(known) => attributes
+                .into_iter()
+                .filter(|attribute| !known.contains(attribute))
+                .map(|attribute| ParseWarning {
+                    element: element.clone(),
+                    attribute: Some(attribute),
+                })
+                .collect(),
+        };
+        for warning in found {
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+    }
+    Ok(warnings)
+}
+
+fn shape(xml: &str) -> Result<Shape> {
+    let mut shape = Shape::new();
+    for (path, attributes) in elements(xml)? {
+        shape.entry(path).or_default().extend(attributes);
+    }
+    Ok(shape)
+}
+
+/// Path and attribute names of every element, in document order
+fn elements(xml: &str) -> Result<Vec<(String, Vec<String>)>> {
+    let mut reader = Reader::from_str(xml);
+    let mut open = Vec::new();
+    let mut elements = Vec::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) => {
+                elements.push(element(&open, &start)?);
+                open.push(String::from_utf8_lossy(start.name().as_ref()).into_owned());
+            }
+            Event::Empty(start) => elements.push(element(&open, &start)?),
+            Event::End(_) => {
+                open.pop();
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+    Ok(elements)
+}
+
+fn element(open: &[String], start: &BytesStart<'_>) -> Result<(String, Vec<String>)> {
+    let mut path = open.join("/");
+    if !path.is_empty() {
+        path.push('/');
+    }
+    path.push_str(&String::from_utf8_lossy(start.name().as_ref()));
+    let mut attributes = Vec::new();
+    for attribute in start.attributes() {
+        attributes.push(String::from_utf8_lossy(attribute?.key.as_ref()).into_owned());
+    }
+    Ok((path, attributes))
+}
@@ -1,4 +1,5 @@
 use crate::error::{Error, Result};
+use crate::property::timestamp::TimestampPolicy;
 use quick_xml::de::from_str;
 use quick_xml::se::to_string;
 use serde::{Deserialize, Serialize};
@@ -6,20 +7,35 @@ use std::str::FromStr;
 
 /// Message handling for the INDI protocol
 pub mod basic;
+/// Borrowed message types for parse-and-forward paths
+pub mod borrowed;
 /// Message definitions for the INDI protocol
 pub mod definition;
+/// Formatting of messages like C indilib
+mod indilib;
+/// JSON form of messages
+#[cfg(feature = "serde_json")]
+mod json;
+/// Lenient parsing that reports ignored content
+pub mod lenient;
 /// Message types for creating new properties
 pub mod new;
 /// Message types for setting property values
 pub mod set;
+/// Framing of messages in a byte stream
+pub mod stream;
+/// Serialization of update vectors without serde
+mod writer;
 
 /// Raw message content
+#[deprecated(note = "use `message::basic::Message`, which carries the device, timestamp and text")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     /// The raw XML content of the message
     pub content: String,
 }
 
+#[allow(deprecated)]
 impl Message {
     /// Create a new message
     pub fn new(content: String) -> Self {
@@ -27,37 +43,152 @@ impl Message {
     }
 }
 
+#[allow(deprecated)]
+impl From<Message> for basic::Message {
+    fn from(message: Message) -> Self {
+        Self {
+            device: None,
+            timestamp: None,
+            message: None,
+            content: message.content,
+        }
+    }
+}
+
 /// INDI message type
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
+#[serde(rename_all = "camelCase")]
 pub enum MessageType {
     /// Get properties request
-    GetProperties(GetProperties),
+    GetProperties(basic::GetProperties),
     /// General message
-    Message(Message),
+    Message(basic::Message),
+    /// Delete property or device
+    DelProperty(basic::DelProperty),
     /// Enable BLOB transfer
-    EnableBLOB(EnableBLOB),
+    #[serde(rename = "enableBLOB")]
+    EnableBlob(basic::EnableBlob),
+    /// Keepalive request
+    PingRequest(basic::PingRequest),
+    /// Keepalive reply
+    PingReply(basic::PingReply),
     /// Define text vector
     DefTextVector(definition::DefTextVector),
     /// Define number vector
     DefNumberVector(definition::DefNumberVector),
     /// Define switch vector
     DefSwitchVector(definition::DefSwitchVector),
+    /// Define light vector
+    DefLightVector(definition::DefLightVector),
+    /// Define BLOB vector
+    #[serde(rename = "defBLOBVector")]
+    DefBlobVector(definition::DefBlobVector),
     /// New text vector
     NewTextVector(new::NewTextVector),
     /// New number vector
     NewNumberVector(new::NewNumberVector),
     /// New switch vector
     NewSwitchVector(new::NewSwitchVector),
+    /// New BLOB vector
+    #[serde(rename = "newBLOBVector")]
+    NewBlobVector(new::NewBlobVector),
     /// Set text vector
     SetTextVector(set::SetTextVector),
     /// Set number vector
     SetNumberVector(set::SetNumberVector),
     /// Set switch vector
     SetSwitchVector(set::SetSwitchVector),
+    /// Set light vector
+    SetLightVector(set::SetLightVector),
+    /// Set BLOB vector
+    #[serde(rename = "setBLOBVector")]
+    SetBlobVector(set::SetBlobVector),
+}
+
+/// Kind of an INDI message, named after its root element
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    /// `getProperties`
+    GetProperties,
+    /// `message`
+    Message,
+    /// `delProperty`
+    DelProperty,
+    /// `enableBLOB`
+    EnableBlob,
+    /// `pingRequest`
+    PingRequest,
+    /// `pingReply`
+    PingReply,
+    /// `defTextVector`
+    DefTextVector,
+    /// `defNumberVector`
+    DefNumberVector,
+    /// `defSwitchVector`
+    DefSwitchVector,
+    /// `defLightVector`
+    DefLightVector,
+    /// `defBLOBVector`
+    DefBlobVector,
+    /// `newTextVector`
+    NewTextVector,
+    /// `newNumberVector`
+    NewNumberVector,
+    /// `newSwitchVector`
+    NewSwitchVector,
+    /// `newBLOBVector`
+    NewBlobVector,
+    /// `setTextVector`
+    SetTextVector,
+    /// `setNumberVector`
+    SetNumberVector,
+    /// `setSwitchVector`
+    SetSwitchVector,
+    /// `setLightVector`
+    SetLightVector,
+    /// `setBLOBVector`
+    SetBlobVector,
+}
+
+impl MessageKind {
+    /// Returns true for `def*Vector`, sent by drivers to define a property
+    pub fn is_definition(self) -> bool {
+        matches!(
+            self,
+            Self::DefTextVector
+                | Self::DefNumberVector
+                | Self::DefSwitchVector
+                | Self::DefLightVector
+                | Self::DefBlobVector
+        )
+    }
+
+    /// Returns true for `new*Vector`, sent by clients to change a property
+    pub fn is_new(self) -> bool {
+        matches!(
+            self,
+            Self::NewTextVector
+                | Self::NewNumberVector
+                | Self::NewSwitchVector
+                | Self::NewBlobVector
+        )
+    }
+
+    /// Returns true for `set*Vector`, sent by drivers when a property changes
+    pub fn is_set(self) -> bool {
+        matches!(
+            self,
+            Self::SetTextVector
+                | Self::SetNumberVector
+                | Self::SetSwitchVector
+                | Self::SetLightVector
+                | Self::SetBlobVector
+        )
+    }
 }
 
 /// Get properties message
+#[deprecated(note = "use `message::basic::GetProperties`")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetProperties {
     /// Protocol version
@@ -70,7 +201,19 @@ pub struct GetProperties {
     pub name: Option<String>,
 }
 
+#[allow(deprecated)]
+impl From<GetProperties> for basic::GetProperties {
+    fn from(get: GetProperties) -> Self {
+        Self {
+            version: get.version,
+            device: get.device,
+            name: get.name,
+        }
+    }
+}
+
 /// Enable BLOB message
+#[deprecated(note = "use `message::basic::EnableBlob`, which names the value `mode`")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnableBLOB {
     /// Device name
@@ -82,15 +225,260 @@ pub struct EnableBLOB {
     pub value: String,
 }
 
+#[allow(deprecated)]
+impl From<EnableBLOB> for basic::EnableBlob {
+    fn from(enable: EnableBLOB) -> Self {
+        Self {
+            device: enable.device,
+            name: enable.name,
+            mode: enable.value,
+        }
+    }
+}
+
 impl MessageType {
     /// Convert message to XML string
     pub fn to_xml(&self) -> Result<String> {
+        let mut out = Vec::new();
+        if writer::write(self, &mut out)? {
+            return String::from_utf8(out).map_err(|e| Error::SerializationError(e.to_string()));
+        }
         to_string(&self).map_err(|e| Error::SerializationError(e.to_string()))
     }
 
+    /// Append the message as XML to `out`
+    ///
+    /// Set and new vectors, the bulk of the traffic, are written straight
+    /// from quick-xml events instead of going through serde, and reusing
+    /// `out` saves an allocation per message.
+    pub fn write_xml(&self, out: &mut Vec<u8>) -> Result<()> {
+        if !writer::write(self, out)? {
+            out.extend_from_slice(self.to_xml()?.as_bytes());
+        }
+        Ok(())
+    }
+
+    /// Kind of the message
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            MessageType::GetProperties(_) => MessageKind::GetProperties,
+            MessageType::Message(_) => MessageKind::Message,
+            MessageType::DelProperty(_) => MessageKind::DelProperty,
+            MessageType::EnableBlob(_) => MessageKind::EnableBlob,
+            MessageType::PingRequest(_) => MessageKind::PingRequest,
+            MessageType::PingReply(_) => MessageKind::PingReply,
+            MessageType::DefTextVector(_) => MessageKind::DefTextVector,
+            MessageType::DefNumberVector(_) => MessageKind::DefNumberVector,
+            MessageType::DefSwitchVector(_) => MessageKind::DefSwitchVector,
+            MessageType::DefLightVector(_) => MessageKind::DefLightVector,
+            MessageType::DefBlobVector(_) => MessageKind::DefBlobVector,
+            MessageType::NewTextVector(_) => MessageKind::NewTextVector,
+            MessageType::NewNumberVector(_) => MessageKind::NewNumberVector,
+            MessageType::NewSwitchVector(_) => MessageKind::NewSwitchVector,
+            MessageType::NewBlobVector(_) => MessageKind::NewBlobVector,
+            MessageType::SetTextVector(_) => MessageKind::SetTextVector,
+            MessageType::SetNumberVector(_) => MessageKind::SetNumberVector,
+            MessageType::SetSwitchVector(_) => MessageKind::SetSwitchVector,
+            MessageType::SetLightVector(_) => MessageKind::SetLightVector,
+            MessageType::SetBlobVector(_) => MessageKind::SetBlobVector,
+        }
+    }
+
+    /// Device the message is about, `None` for every device or none
+    pub fn device(&self) -> Option<&str> {
+        let device = match self {
+            MessageType::GetProperties(m) => return m.device.as_deref(),
+            MessageType::Message(m) => return m.device.as_deref(),
+            MessageType::PingRequest(_) | MessageType::PingReply(_) => return None,
+            MessageType::DelProperty(m) => &m.device,
+            MessageType::EnableBlob(m) => &m.device,
+            MessageType::DefTextVector(m) => &m.device,
+            MessageType::DefNumberVector(m) => &m.device,
+            MessageType::DefSwitchVector(m) => &m.device,
+            MessageType::DefLightVector(m) => &m.device,
+            MessageType::DefBlobVector(m) => &m.device,
+            MessageType::NewTextVector(m) => &m.device,
+            MessageType::NewNumberVector(m) => &m.device,
+            MessageType::NewSwitchVector(m) => &m.device,
+            MessageType::NewBlobVector(m) => &m.device,
+            MessageType::SetTextVector(m) => &m.device,
+            MessageType::SetNumberVector(m) => &m.device,
+            MessageType::SetSwitchVector(m) => &m.device,
+            MessageType::SetLightVector(m) => &m.device,
+            MessageType::SetBlobVector(m) => &m.device,
+        };
+        Some(device)
+    }
+
+    /// Property the message is about, `None` for the whole device or none
+    pub fn name(&self) -> Option<&str> {
+        let name = match self {
+            MessageType::GetProperties(m) => return m.name.as_deref(),
+            MessageType::DelProperty(m) => return m.name.as_deref(),
+            MessageType::EnableBlob(m) => return m.name.as_deref(),
+            MessageType::Message(_) | MessageType::PingRequest(_) | MessageType::PingReply(_) => {
+                return None
+            }
+            MessageType::DefTextVector(m) => &m.name,
+            MessageType::DefNumberVector(m) => &m.name,
+            MessageType::DefSwitchVector(m) => &m.name,
+            MessageType::DefLightVector(m) => &m.name,
+            MessageType::DefBlobVector(m) => &m.name,
+            MessageType::NewTextVector(m) => &m.name,
+            MessageType::NewNumberVector(m) => &m.name,
+            MessageType::NewSwitchVector(m) => &m.name,
+            MessageType::NewBlobVector(m) => &m.name,
+            MessageType::SetTextVector(m) => &m.name,
+            MessageType::SetNumberVector(m) => &m.name,
+            MessageType::SetSwitchVector(m) => &m.name,
+            MessageType::SetLightVector(m) => &m.name,
+            MessageType::SetBlobVector(m) => &m.name,
+        };
+        Some(name)
+    }
+
+    /// Timestamp sent with the message, if any
+    pub fn timestamp(&self) -> Option<&str> {
+        let timestamp = match self {
+            MessageType::GetProperties(_)
+            | MessageType::EnableBlob(_)
+            | MessageType::PingRequest(_)
+            | MessageType::PingReply(_) => return None,
+            MessageType::Message(m) => m.timestamp.as_deref()?,
+            MessageType::DelProperty(m) => m.timestamp.as_deref()?,
+            MessageType::DefTextVector(m) => m.timestamp.as_str(),
+            MessageType::DefNumberVector(m) => m.timestamp.as_str(),
+            MessageType::DefSwitchVector(m) => m.timestamp.as_str(),
+            MessageType::DefLightVector(m) => m.timestamp.as_str(),
+            MessageType::DefBlobVector(m) => m.timestamp.as_str(),
+            MessageType::NewTextVector(m) => m.timestamp.as_deref()?,
+            MessageType::NewNumberVector(m) => m.timestamp.as_deref()?,
+            MessageType::NewSwitchVector(m) => m.timestamp.as_deref()?,
+            MessageType::NewBlobVector(m) => m.timestamp.as_deref()?,
+            MessageType::SetTextVector(m) => m.timestamp.as_deref()?,
+            MessageType::SetNumberVector(m) => m.timestamp.as_deref()?,
+            MessageType::SetSwitchVector(m) => m.timestamp.as_deref()?,
+            MessageType::SetLightVector(m) => m.timestamp.as_deref()?,
+            MessageType::SetBlobVector(m) => m.timestamp.as_deref()?,
+        };
+        Some(timestamp).filter(|timestamp| !timestamp.is_empty())
+    }
+
+    /// Stamp a vector definition or update that has no timestamp with the
+    /// current time as `policy` asks
+    ///
+    /// Other messages and those already stamped are left alone.
+    pub fn stamp(&mut self, policy: TimestampPolicy) {
+        if self.timestamp().is_some() {
+            return;
+        }
+        let Some(now) = policy.now() else {
+            return;
+        };
+        match self {
+            MessageType::DefTextVector(m) => m.timestamp = now,
+            MessageType::DefNumberVector(m) => m.timestamp = now,
+            MessageType::DefSwitchVector(m) => m.timestamp = now,
+            MessageType::DefLightVector(m) => m.timestamp = now,
+            MessageType::DefBlobVector(m) => m.timestamp = now,
+            MessageType::NewTextVector(m) => m.timestamp = Some(now),
+            MessageType::NewNumberVector(m) => m.timestamp = Some(now),
+            MessageType::NewSwitchVector(m) => m.timestamp = Some(now),
+            MessageType::NewBlobVector(m) => m.timestamp = Some(now),
+            MessageType::SetTextVector(m) => m.timestamp = Some(now),
+            MessageType::SetNumberVector(m) => m.timestamp = Some(now),
+            MessageType::SetSwitchVector(m) => m.timestamp = Some(now),
+            MessageType::SetLightVector(m) => m.timestamp = Some(now),
+            MessageType::SetBlobVector(m) => m.timestamp = Some(now),
+            MessageType::GetProperties(_)
+            | MessageType::Message(_)
+            | MessageType::DelProperty(_)
+            | MessageType::EnableBlob(_)
+            | MessageType::PingRequest(_)
+            | MessageType::PingReply(_) => (),
+        }
+    }
+
+    /// Merge the values of `set`, a `set*Vector`, into this definition
+    ///
+    /// Does nothing unless the two are of the same vector type; device and
+    /// name are not compared. BLOB definitions carry no values, only their
+    /// attributes change.
+    pub(crate) fn apply_set(&mut self, set: &MessageType) {
+        match (set, self) {
+            (MessageType::SetTextVector(set), MessageType::DefTextVector(def)) => def.apply(set),
+            (MessageType::SetNumberVector(set), MessageType::DefNumberVector(def)) => {
+                def.apply(set)
+            }
+            (MessageType::SetSwitchVector(set), MessageType::DefSwitchVector(def)) => {
+                def.apply(set)
+            }
+            (MessageType::SetLightVector(set), MessageType::DefLightVector(def)) => def.apply(set),
+            (MessageType::SetBlobVector(set), MessageType::DefBlobVector(def)) => def.apply(set),
+            _ => {}
+        }
+    }
+
+    /// Convert message to XML formatted exactly like C indilib writes it
+    ///
+    /// One element per line with indented children and their values on
+    /// lines of their own, for byte-level comparisons with indilib output
+    /// and clients that expect its layout.
+    pub fn to_indilib_xml(&self) -> Result<String> {
+        indilib::format(&self.to_xml()?)
+    }
+
+    /// Convert message to JSON
+    ///
+    /// `type` holds the element name, attributes keep their INDI names,
+    /// vector elements are listed under `elements` and text content is
+    /// stored as `value`, all as strings:
+    ///
+    /// ```json
+    /// {"type": "setNumberVector", "device": "Mount", "name": "EQUATORIAL_EOD_COORD",
+    ///  "state": "Ok", "elements": [{"name": "RA", "value": "12:30:00"}]}
+    /// ```
+    #[cfg(feature = "serde_json")]
+    pub fn to_json(&self) -> Result<serde_json::Value> {
+        json::to_json(&self.to_xml()?)
+    }
+
+    /// Parse a message from the JSON form written by [`MessageType::to_json`]
+    #[cfg(feature = "serde_json")]
+    pub fn from_json(json: &serde_json::Value) -> Result<Self> {
+        json::from_json(json)?.parse()
+    }
+
     /// Parse a message from bytes asynchronously
     pub async fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        from_str(std::str::from_utf8(bytes).unwrap()).map_err(Error::XmlDe)
+        from_str(std::str::from_utf8(bytes)?).map_err(Error::XmlDe)
+    }
+
+    /// Parse a message from bytes
+    ///
+    /// See [`borrowed::MessageRef::from_slice`] for parsing updates without
+    /// allocating their strings.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        from_str(std::str::from_utf8(bytes)?.trim()).map_err(Error::XmlDe)
+    }
+
+    /// Parse a message and report the attributes and elements it ignored
+    ///
+    /// Unknown content never fails parsing, this makes vendor extensions
+    /// visible, e.g. to log them.
+    pub fn from_str_lenient(s: &str) -> Result<(Self, Vec<lenient::ParseWarning>)> {
+        let message: Self = s.parse()?;
+        let warnings = lenient::ignored_content(s, &message.to_xml()?)?;
+        Ok((message, warnings))
+    }
+
+    /// Parse a message, failing on attributes or elements it would ignore
+    pub fn from_str_strict(s: &str) -> Result<Self> {
+        let (message, warnings) = Self::from_str_lenient(s)?;
+        if let Some(warning) = warnings.first() {
+            return Err(Error::ParseError(warning.to_string()));
+        }
+        Ok(message)
     }
 }
 
@@ -101,3 +489,60 @@ impl FromStr for MessageType {
         from_str(s).map_err(Error::XmlDe)
     }
 }
+
+/// Top-level elements the client understands
+const ROOT_ELEMENTS: &[&[u8]] = &[
+    b"getProperties",
+    b"message",
+    b"delProperty",
+    b"enableBLOB",
+    b"pingRequest",
+    b"pingReply",
+    b"defTextVector",
+    b"defNumberVector",
+    b"defSwitchVector",
+    b"defLightVector",
+    b"defBLOBVector",
+    b"newTextVector",
+    b"newNumberVector",
+    b"newSwitchVector",
+    b"newBLOBVector",
+    b"setTextVector",
+    b"setNumberVector",
+    b"setSwitchVector",
+    b"setLightVector",
+    b"setBLOBVector",
+];
+
+/// Number of leading bytes of `buf` that cannot start a known message
+///
+/// Skips text, XML declarations, stray closing tags and unknown elements
+/// such as an `<INDI>` stream wrapper, which would otherwise keep
+/// [`stream::Framer`] waiting for a closing tag that never comes. A tag name
+/// cut off at the end of the buffer is kept until more data arrives.
+pub(crate) fn skip_garbage(buf: &[u8]) -> usize {
+    let mut i = 0;
+    while i < buf.len() {
+        if buf[i] != b'<' {
+            i += 1;
+            continue;
+        }
+        let name_len = buf[i + 1..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .count();
+        let end = i + 1 + name_len;
+        if end == buf.len() {
+            return i;
+        }
+        let name = &buf[i + 1..end];
+        if ROOT_ELEMENTS.contains(&name) {
+            return i;
+        }
+        i += 1;
+    }
+    buf.len()
+}
+
+#[cfg(test)]
+mod tests;
@@ -1,4 +1,7 @@
+use crate::error::{Error, Result};
 use crate::property::{PropertyState, SwitchState};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use serde::{Deserialize, Serialize};
 
 /// Switch element in a new switch vector
@@ -24,10 +27,14 @@ pub struct NewSwitchVector {
     #[serde(rename = "@name")]
     pub name: String,
     /// Property timestamp
-    #[serde(rename = "@timestamp")]
-    pub timestamp: String,
+    #[serde(
+        rename = "@timestamp",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<String>,
     /// Switch elements
-    #[serde(rename = "oneSwitch")]
+    #[serde(rename = "oneSwitch", default)]
     pub elements: Vec<OneSwitch>,
 }
 
@@ -42,10 +49,14 @@ pub struct NewTextVector {
     #[serde(rename = "@name")]
     pub name: String,
     /// Property timestamp
-    #[serde(rename = "@timestamp")]
-    pub timestamp: String,
+    #[serde(
+        rename = "@timestamp",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<String>,
     /// Text elements
-    #[serde(rename = "oneText")]
+    #[serde(rename = "oneText", default)]
     pub elements: Vec<OneText>,
 }
 
@@ -60,13 +71,39 @@ pub struct NewNumberVector {
     #[serde(rename = "@name")]
     pub name: String,
     /// Property timestamp
-    #[serde(rename = "@timestamp")]
-    pub timestamp: String,
+    #[serde(
+        rename = "@timestamp",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<String>,
     /// Number elements
-    #[serde(rename = "oneNumber")]
+    #[serde(rename = "oneNumber", default)]
     pub elements: Vec<OneNumber>,
 }
 
+/// New BLOB vector message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "newBLOBVector")]
+pub struct NewBlobVector {
+    /// Device name
+    #[serde(rename = "@device")]
+    pub device: String,
+    /// Property name
+    #[serde(rename = "@name")]
+    pub name: String,
+    /// Property timestamp
+    #[serde(
+        rename = "@timestamp",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<String>,
+    /// BLOB elements
+    #[serde(rename = "oneBLOB", default)]
+    pub elements: Vec<OneBlob>,
+}
+
 /// Text element in a new text vector
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename = "oneText")]
@@ -75,7 +112,7 @@ pub struct OneText {
     #[serde(rename = "@name")]
     pub name: String,
     /// Text value
-    #[serde(rename = "$text")]
+    #[serde(rename = "$text", default)]
     pub value: String,
 }
 
@@ -103,6 +140,14 @@ pub struct OneLight {
     pub value: PropertyState,
 }
 
+/// Base64 characters decoded at once, a multiple of four
+const DECODE_CHUNK: usize = 64 * 1024;
+/// Format suffix of zlib compressed BLOBs
+const COMPRESSED_SUFFIX: &str = ".z";
+/// zlib level used by [`OneBlob::new_compressed`], favouring speed
+#[cfg(feature = "zlib")]
+const COMPRESSION_LEVEL: u8 = 3;
+
 /// BLOB element in a new BLOB vector
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename = "oneBLOB")]
@@ -116,7 +161,237 @@ pub struct OneBlob {
     /// BLOB format
     #[serde(rename = "@format")]
     pub format: String,
-    /// BLOB value
-    #[serde(rename = "$text")]
-    pub value: Vec<u8>,
+    /// Length of the base64 encoded payload (optional)
+    #[serde(rename = "@enclen", default, skip_serializing_if = "Option::is_none")]
+    pub enclen: Option<usize>,
+    /// Base64 encoded BLOB value
+    ///
+    /// The parser keeps the whole encoded text, about 4/3 of the decoded
+    /// size; the decoding methods only avoid further copies of it.
+    #[serde(rename = "$text", default)]
+    pub value: String,
+}
+
+impl OneBlob {
+    /// Create a BLOB element from raw bytes, base64 encoding the payload
+    pub fn new(name: impl Into<String>, format: impl Into<String>, data: &[u8]) -> Self {
+        let value = STANDARD.encode(data);
+        Self {
+            name: name.into(),
+            size: data.len(),
+            format: format.into(),
+            enclen: Some(value.len()),
+            value,
+        }
+    }
+
+    /// Create a BLOB element from raw bytes, compressing them with zlib
+    ///
+    /// `.z` is appended to `format` and `size` is the uncompressed size, as
+    /// indilib drivers do for compressed frames.
+    #[cfg(feature = "zlib")]
+    pub fn new_compressed(name: impl Into<String>, format: impl Into<String>, data: &[u8]) -> Self {
+        let mut format = format.into();
+        if !format.ends_with(COMPRESSED_SUFFIX) {
+            format.push_str(COMPRESSED_SUFFIX);
+        }
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(data, COMPRESSION_LEVEL);
+        Self {
+            size: data.len(),
+            ..Self::new(name, format, &compressed)
+        }
+    }
+
+    /// Returns true if the payload is zlib compressed, i.e. the format ends
+    /// in `.z`
+    pub fn is_compressed(&self) -> bool {
+        self.format.ends_with(COMPRESSED_SUFFIX)
+    }
+
+    /// Format of the bytes returned by [`OneBlob::get_data`]
+    ///
+    /// Without `.z` if the payload is decompressed.
+    pub fn data_format(&self) -> &str {
+        if cfg!(feature = "zlib") && self.is_compressed() {
+            &self.format[..self.format.len() - COMPRESSED_SUFFIX.len()]
+        } else {
+            &self.format
+        }
+    }
+
+    /// Decode the payload into raw bytes
+    ///
+    /// With the `zlib` feature, compressed payloads are inflated as well;
+    /// without it they are returned as sent, like
+    /// [`OneBlob::get_raw_data`]. Inflating stops at `size`, the
+    /// uncompressed size the driver announced, so a payload that inflates
+    /// beyond it is rejected rather than exhausting memory.
+    pub fn get_data(&self) -> Result<Vec<u8>> {
+        let data = self.get_raw_data()?;
+        #[cfg(feature = "zlib")]
+        if self.is_compressed() {
+            return miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(&data, self.size)
+                .map_err(|e| self.inflate_error(e.status));
+        }
+        Ok(data)
+    }
+
+    #[cfg(feature = "zlib")]
+    fn inflate_error(&self, status: miniz_oxide::inflate::TINFLStatus) -> Error {
+        match status {
+            miniz_oxide::inflate::TINFLStatus::HasMoreOutput => Error::ParseError(format!(
+                "Compressed BLOB {} inflates beyond its size of {} bytes",
+                self.name, self.size
+            )),
+            status => Error::ParseError(format!(
+                "Invalid compressed BLOB {}: {:?}",
+                self.name, status
+            )),
+        }
+    }
+
+    /// Primary header of a FITS payload
+    ///
+    /// Decodes the payload in chunks, inflating it with the `zlib` feature,
+    /// and stops after the chunk holding the `END` card, so the image after
+    /// the header is not decoded.
+    #[cfg(feature = "fits")]
+    pub fn fits_header(&self) -> Result<crate::fits::FitsHeader> {
+        let mut data = Vec::new();
+        let mut scanned = 0;
+        self.decode_until(&mut data, |data| {
+            crate::fits::header_read(data, &mut scanned)
+        })?;
+        crate::fits::FitsHeader::parse(&data)
+    }
+
+    /// Decode the payload into `buffer`, inflating it with the `zlib`
+    /// feature, until `done` returns true for the data decoded so far
+    #[cfg(feature = "fits")]
+    fn decode_until(
+        &self,
+        buffer: &mut Vec<u8>,
+        mut done: impl FnMut(&[u8]) -> bool,
+    ) -> Result<()> {
+        #[cfg(feature = "zlib")]
+        if self.is_compressed() {
+            use miniz_oxide::inflate::stream::{inflate, InflateState};
+            use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus};
+
+            let mut state = InflateState::new_boxed(DataFormat::Zlib);
+            let mut decoded = Vec::with_capacity(DECODE_CHUNK / 4 * 3);
+            let mut inflated = vec![0; DECODE_CHUNK];
+            return self.for_each_chunk(|groups| {
+                decoded.clear();
+                STANDARD
+                    .decode_vec(groups, &mut decoded)
+                    .map_err(invalid_payload)?;
+                let mut input = decoded.as_slice();
+                loop {
+                    let result = inflate(&mut state, input, &mut inflated, MZFlush::None);
+                    input = &input[result.bytes_consumed..];
+                    buffer.extend_from_slice(&inflated[..result.bytes_written]);
+                    if buffer.len() > self.size {
+                        return Err(
+                            self.inflate_error(miniz_oxide::inflate::TINFLStatus::HasMoreOutput)
+                        );
+                    }
+                    match result.status {
+                        Ok(MZStatus::StreamEnd) | Err(MZError::Buf) => break,
+                        Ok(_) if input.is_empty() && result.bytes_written < inflated.len() => break,
+                        Ok(_) => {}
+                        Err(e) => {
+                            return Err(Error::ParseError(format!(
+                                "Invalid compressed BLOB {}: {:?}",
+                                self.name, e
+                            )))
+                        }
+                    }
+                }
+                Ok(!done(buffer))
+            });
+        }
+        self.for_each_chunk(|groups| {
+            STANDARD
+                .decode_vec(groups, buffer)
+                .map_err(invalid_payload)?;
+            Ok(!done(buffer))
+        })
+    }
+
+    /// Decode the base64 payload without decompressing it
+    ///
+    /// Drivers commonly wrap the encoded payload across several lines, so any
+    /// whitespace is stripped before decoding.
+    pub fn get_raw_data(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.decode_into(&mut data)?;
+        Ok(data)
+    }
+
+    /// Decode the base64 payload, appending it to `buffer`
+    ///
+    /// Decodes in chunks straight into `buffer`, without a stripped copy of
+    /// the payload; the encoded [`value`](Self::value) is still held while
+    /// decoding. Returns the number of bytes appended. Compressed payloads
+    /// are not inflated.
+    pub fn decode_into(&self, buffer: &mut Vec<u8>) -> Result<usize> {
+        let start = buffer.len();
+        // `enclen` leaves out the line breaks drivers wrap the payload with
+        let encoded = self
+            .enclen
+            .map_or(self.value.len(), |enclen| enclen.min(self.value.len()));
+        buffer.reserve(encoded / 4 * 3);
+        self.for_each_chunk(|groups| {
+            STANDARD
+                .decode_vec(groups, buffer)
+                .map_err(invalid_payload)?;
+            Ok(true)
+        })?;
+        Ok(buffer.len() - start)
+    }
+
+    /// Decode the base64 payload chunk by chunk into `writer`
+    ///
+    /// At most one chunk of the decoded data is held in memory, besides the
+    /// encoded [`value`](Self::value). Returns the number of bytes written.
+    /// Compressed payloads are not inflated.
+    pub fn decode_to(&self, mut writer: impl std::io::Write) -> Result<u64> {
+        let mut decoded = Vec::with_capacity(DECODE_CHUNK / 4 * 3);
+        let mut written = 0;
+        self.for_each_chunk(|groups| {
+            decoded.clear();
+            STANDARD
+                .decode_vec(groups, &mut decoded)
+                .map_err(invalid_payload)?;
+            writer.write_all(&decoded)?;
+            written += decoded.len() as u64;
+            Ok(true)
+        })?;
+        writer.flush()?;
+        Ok(written)
+    }
+
+    /// Call `f` with the payload split into chunks of whole base64 groups,
+    /// whitespace removed, until it returns false
+    fn for_each_chunk(&self, mut f: impl FnMut(&[u8]) -> Result<bool>) -> Result<()> {
+        let mut groups = Vec::with_capacity(DECODE_CHUNK.min(self.value.len()));
+        for c in self.value.bytes().filter(|c| !c.is_ascii_whitespace()) {
+            groups.push(c);
+            if groups.len() == DECODE_CHUNK {
+                if !f(&groups)? {
+                    return Ok(());
+                }
+                groups.clear();
+            }
+        }
+        if !groups.is_empty() {
+            f(&groups)?;
+        }
+        Ok(())
+    }
+}
+
+fn invalid_payload(e: base64::DecodeError) -> Error {
+    Error::ParseError(format!("Invalid BLOB payload: {}", e))
 }
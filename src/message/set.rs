@@ -1,57 +1,155 @@
 use crate::message::new::{OneBlob, OneLight, OneNumber, OneSwitch, OneText};
+use crate::property::PropertyState;
 use serde::{Deserialize, Serialize};
 
 /// Set text vector message
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "setTextVector")]
 pub struct SetTextVector {
     /// Device name
+    #[serde(rename = "@device")]
     pub device: String,
     /// Property name
+    #[serde(rename = "@name")]
     pub name: String,
+    /// Property state (optional, unchanged if absent)
+    #[serde(rename = "@state", default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<PropertyState>,
+    /// Property timeout (optional)
+    #[serde(rename = "@timeout", default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<i32>,
+    /// Property timestamp (optional)
+    #[serde(
+        rename = "@timestamp",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<String>,
+    /// Message (optional)
+    #[serde(rename = "@message", default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
     /// Text elements
-    pub texts: Vec<OneText>,
+    #[serde(rename = "oneText", default)]
+    pub elements: Vec<OneText>,
 }
 
 /// Set number vector message
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "setNumberVector")]
 pub struct SetNumberVector {
     /// Device name
+    #[serde(rename = "@device")]
     pub device: String,
     /// Property name
+    #[serde(rename = "@name")]
     pub name: String,
+    /// Property state (optional, unchanged if absent)
+    #[serde(rename = "@state", default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<PropertyState>,
+    /// Property timeout (optional)
+    #[serde(rename = "@timeout", default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<i32>,
+    /// Property timestamp (optional)
+    #[serde(
+        rename = "@timestamp",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<String>,
+    /// Message (optional)
+    #[serde(rename = "@message", default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
     /// Number elements
-    pub numbers: Vec<OneNumber>,
+    #[serde(rename = "oneNumber", default)]
+    pub elements: Vec<OneNumber>,
 }
 
 /// Set switch vector message
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "setSwitchVector")]
 pub struct SetSwitchVector {
     /// Device name
+    #[serde(rename = "@device")]
     pub device: String,
     /// Property name
+    #[serde(rename = "@name")]
     pub name: String,
+    /// Property state (optional, unchanged if absent)
+    #[serde(rename = "@state", default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<PropertyState>,
+    /// Property timeout (optional)
+    #[serde(rename = "@timeout", default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<i32>,
+    /// Property timestamp (optional)
+    #[serde(
+        rename = "@timestamp",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<String>,
+    /// Message (optional)
+    #[serde(rename = "@message", default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
     /// Switch elements
-    pub switches: Vec<OneSwitch>,
+    #[serde(rename = "oneSwitch", default)]
+    pub elements: Vec<OneSwitch>,
 }
 
 /// Set light vector message
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "setLightVector")]
 pub struct SetLightVector {
     /// Device name
+    #[serde(rename = "@device")]
     pub device: String,
     /// Property name
+    #[serde(rename = "@name")]
     pub name: String,
+    /// Property state (optional, unchanged if absent)
+    #[serde(rename = "@state", default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<PropertyState>,
+    /// Property timestamp (optional)
+    #[serde(
+        rename = "@timestamp",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<String>,
+    /// Message (optional)
+    #[serde(rename = "@message", default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
     /// Light elements
-    pub lights: Vec<OneLight>,
+    #[serde(rename = "oneLight", default)]
+    pub elements: Vec<OneLight>,
 }
 
 /// Set blob vector message
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "setBLOBVector")]
 pub struct SetBlobVector {
     /// Device name
+    #[serde(rename = "@device")]
     pub device: String,
     /// Property name
+    #[serde(rename = "@name")]
     pub name: String,
+    /// Property state (optional, unchanged if absent)
+    #[serde(rename = "@state", default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<PropertyState>,
+    /// Property timeout (optional)
+    #[serde(rename = "@timeout", default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<i32>,
+    /// Property timestamp (optional)
+    #[serde(
+        rename = "@timestamp",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<String>,
+    /// Message (optional)
+    #[serde(rename = "@message", default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
     /// BLOB elements
-    pub blobs: Vec<OneBlob>,
+    #[serde(rename = "oneBLOB", default)]
+    pub elements: Vec<OneBlob>,
 }
@@ -0,0 +1,220 @@
+use quick_xml::errors::{Error as XmlError, SyntaxError};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use tracing::debug;
+
+use super::skip_garbage;
+
+/// Splits a byte stream into complete top-level INDI messages
+///
+/// Bytes are fed in as they arrive with [`Framer::push`], and
+/// [`Framer::next_frame`] yields each message once its root element is
+/// closed. Tags split across reads, `>` in attribute values, comments and
+/// CDATA sections are left to quick-xml. Scanning resumes where it
+/// stopped, so a large message is only read once. Text between messages
+/// and unknown top-level elements are skipped.
+#[derive(Debug, Default)]
+pub struct Framer {
+    buf: Vec<u8>,
+    /// Bytes of the current message already scanned
+    scanned: usize,
+    /// Elements open at `scanned`
+    depth: usize,
+    /// Drop the current message instead of returning it
+    discarding: bool,
+}
+
+/// Outcome of scanning the buffered part of a message
+enum Scan {
+    /// The message ends at this offset
+    Complete(usize),
+    /// The message continues beyond the buffer
+    Incomplete,
+    /// The message is not well-formed XML up to this offset
+    Malformed(usize),
+}
+
+impl Framer {
+    /// Create an empty framer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append bytes read from the stream
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Bytes received but not yet returned as part of a message
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Next complete message, `None` until more bytes are needed
+    pub fn next_frame(&mut self) -> Option<Vec<u8>> {
+        loop {
+            self.skip_unsupported();
+            let end = match self.scan() {
+                Scan::Complete(end) => end,
+                Scan::Incomplete => return None,
+                Scan::Malformed(end) => {
+                    debug!("Dropping {} bytes of malformed XML", end);
+                    self.buf.drain(..end);
+                    self.reset();
+                    continue;
+                }
+            };
+            let frame = self.buf.drain(..end).collect();
+            let discarded = self.discarding;
+            self.reset();
+            if !discarded {
+                return Some(frame);
+            }
+        }
+    }
+
+    /// Drop the message being received, and the rest of it as it arrives
+    ///
+    /// Keeps a stream with an oversized message from growing the buffer
+    /// without bound.
+    pub fn discard(&mut self) {
+        if self.depth == 0 {
+            // Not even the root start tag is complete; whatever follows is
+            // skipped as text up to the next message
+            self.buf.clear();
+            self.reset();
+            return;
+        }
+        self.buf.drain(..self.scanned);
+        self.scanned = 0;
+        self.discarding = true;
+    }
+
+    /// Drop anything before the next known message, unless one is already
+    /// being received
+    pub(crate) fn skip_unsupported(&mut self) {
+        if self.scanned > 0 || self.depth > 0 {
+            return;
+        }
+        let skip = skip_garbage(&self.buf);
+        if self.buf[..skip].iter().any(|c| !c.is_ascii_whitespace()) {
+            debug!("Skipping {} bytes of unsupported data", skip);
+        }
+        self.buf.drain(..skip);
+    }
+
+    /// Returns true if no part of the next message has been scanned yet
+    pub(crate) fn at_message_start(&self) -> bool {
+        self.scanned == 0 && self.depth == 0
+    }
+
+    /// The raw buffer, for callers that take over parsing at a message
+    /// start
+    pub(crate) fn buffer_mut(&mut self) -> &mut Vec<u8> {
+        self.reset();
+        &mut self.buf
+    }
+
+    fn reset(&mut self) {
+        self.scanned = 0;
+        self.depth = 0;
+        self.discarding = false;
+    }
+
+    /// Continue scanning the current message
+    fn scan(&mut self) -> Scan {
+        let start = self.scanned;
+        let mut reader = Reader::from_reader(&self.buf[start..]);
+        // Scanning may resume inside the root element, whose start tag the
+        // reader has not seen
+        reader.config_mut().check_end_names = false;
+        reader.config_mut().allow_unmatched_ends = true;
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(_)) => self.depth += 1,
+                Ok(Event::End(_)) => self.depth = self.depth.saturating_sub(1),
+                Ok(Event::Eof) => break,
+                Ok(_) => (),
+                Err(XmlError::Syntax(SyntaxError::InvalidBangMarkup))
+                    if start + reader.error_position() as usize + 2 < self.buf.len() =>
+                {
+                    return Scan::Malformed(start + reader.error_position() as usize + 1);
+                }
+                // Markup cut off by the end of the buffer
+                Err(XmlError::Syntax(_)) => break,
+                Err(_) => return Scan::Malformed(start + reader.error_position() as usize + 1),
+            }
+            self.scanned = start + reader.buffer_position() as usize;
+            if self.depth == 0 {
+                return Scan::Complete(self.scanned);
+            }
+        }
+        if self.discarding {
+            self.buf.drain(..self.scanned);
+            self.scanned = 0;
+        }
+        Scan::Incomplete
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames of `input` fed `chunk` bytes at a time
+    fn frames(input: &[u8], chunk: usize) -> Vec<String> {
+        let mut framer = Framer::new();
+        let mut frames = Vec::new();
+        for bytes in input.chunks(chunk) {
+            framer.push(bytes);
+            while let Some(frame) = framer.next_frame() {
+                frames.push(String::from_utf8(frame).unwrap());
+            }
+        }
+        frames
+    }
+
+    #[test]
+    fn test_framer() {
+        let input = concat!(
+            "<?xml version=\"1.0\"?>\n<INDI>",
+            r#"<defTextVector device="A" name="B" label="a > b"><defText name="C"><![CDATA[</defTextVector>]]></defText><!-- <x> --></defTextVector>"#,
+            "\n",
+            r#"<getProperties version="1.7"/><message message="hi"/>"#,
+            "\n</INDI>"
+        )
+        .as_bytes();
+        for chunk in [1, 2, 7, input.len()] {
+            let frames = frames(input, chunk);
+            assert_eq!(frames.len(), 3, "{:?}", frames);
+            assert!(frames[0].starts_with("<defTextVector"));
+            assert!(frames[0].ends_with("--></defTextVector>"));
+            assert_eq!(frames[1], r#"<getProperties version="1.7"/>"#);
+            assert_eq!(frames[2], r#"<message message="hi"/>"#);
+        }
+    }
+
+    #[test]
+    fn test_framer_discard() {
+        let mut framer = Framer::new();
+        framer.push(b"<setBLOBVector device=\"A\" name=\"B\"><oneBLOB name=\"C\">AAAA");
+        assert_eq!(framer.next_frame(), None);
+        framer.discard();
+        framer.push(b"AAAAAAAA");
+        assert_eq!(framer.next_frame(), None);
+        assert_eq!(framer.buffered(), 0);
+        framer.push(b"</oneBLOB></setBLOBVector><pingRequest uid=\"1\"/>");
+        assert_eq!(
+            framer.next_frame().unwrap(),
+            b"<pingRequest uid=\"1\"/>".to_vec()
+        );
+
+        framer.push(b"<getProperties device=\"xxxx");
+        framer.discard();
+        framer.push(b"xxxx\"/><pingReply uid=\"1\"/>");
+        assert_eq!(
+            framer.next_frame().unwrap(),
+            b"<pingReply uid=\"1\"/>".to_vec()
+        );
+    }
+}
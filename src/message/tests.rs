@@ -1,6 +1,6 @@
 use super::*;
-use std::str::FromStr;
 use crate::property::{PropertyState, SwitchRule, SwitchState};
+use std::str::FromStr;
 
 #[test]
 fn test_parse_message() {
@@ -114,3 +114,491 @@ fn test_set_switch_vector() {
         _ => panic!("Expected SetSwitchVector variant"),
     }
 }
+
+#[test]
+fn test_set_blob_vector() {
+    let xml = r#"<setBLOBVector device="CCD Simulator" name="CCD1" state="Ok" timestamp="2024-01-01T00:00:00">
+        <oneBLOB name="CCD1" size="11" format=".fits">
+aGVsbG8g
+d29ybGQ=
+        </oneBLOB>
+    </setBLOBVector>"#;
+
+    let message = MessageType::from_str(xml).unwrap();
+    match message {
+        MessageType::SetBlobVector(v) => {
+            assert_eq!(v.device, "CCD Simulator");
+            assert_eq!(v.state, Some(PropertyState::Ok));
+            assert_eq!(v.elements.len(), 1);
+            assert_eq!(v.elements[0].format, ".fits");
+            assert_eq!(v.elements[0].get_data().unwrap(), b"hello world");
+        }
+        _ => panic!("Expected SetBlobVector variant"),
+    }
+}
+
+#[test]
+fn test_new_number_vector_to_xml() {
+    let message = MessageType::NewNumberVector(new::NewNumberVector {
+        device: "CCD Simulator".to_string(),
+        name: "CCD_EXPOSURE".to_string(),
+        timestamp: None,
+        elements: vec![new::OneNumber {
+            name: "CCD_EXPOSURE_VALUE".to_string(),
+            value: "1.5".to_string(),
+        }],
+    });
+    assert_eq!(
+        message.to_xml().unwrap(),
+        r#"<newNumberVector device="CCD Simulator" name="CCD_EXPOSURE"><oneNumber name="CCD_EXPOSURE_VALUE">1.5</oneNumber></newNumberVector>"#
+    );
+}
+
+#[test]
+fn test_new_blob_vector_round_trip() {
+    let message = MessageType::NewBlobVector(new::NewBlobVector {
+        device: "Guider".to_string(),
+        name: "DARK_FRAME".to_string(),
+        timestamp: None,
+        elements: vec![new::OneBlob::new("DARK", ".fits", b"hello world")],
+    });
+    let xml = message.to_xml().unwrap();
+    assert!(xml.starts_with("<newBLOBVector "), "{}", xml);
+    match MessageType::from_str(&xml).unwrap() {
+        MessageType::NewBlobVector(v) => {
+            assert_eq!(v.name, "DARK_FRAME");
+            assert_eq!(v.elements[0].get_data().unwrap(), b"hello world");
+        }
+        message => panic!("Expected NewBlobVector, got {:?}", message),
+    }
+}
+
+#[test]
+fn test_new_text_vector_round_trip() {
+    let message = MessageType::NewTextVector(new::NewTextVector {
+        device: "Telescope Simulator".to_string(),
+        name: "SITE".to_string(),
+        timestamp: Some("2024-01-01T00:00:00".to_string()),
+        elements: vec![new::OneText {
+            name: "NAME".to_string(),
+            value: "Backyard & Co".to_string(),
+        }],
+    });
+    let xml = message.to_xml().unwrap();
+    assert!(xml.starts_with("<newTextVector "), "{}", xml);
+    match MessageType::from_str(&xml).unwrap() {
+        MessageType::NewTextVector(v) => {
+            assert_eq!(v.timestamp.as_deref(), Some("2024-01-01T00:00:00"));
+            assert_eq!(v.elements[0].name, "NAME");
+            assert_eq!(v.elements[0].value, "Backyard & Co");
+        }
+        message => panic!("Expected NewTextVector, got {:?}", message),
+    }
+}
+
+#[test]
+fn test_new_switch_vector_round_trip() {
+    let message = MessageType::NewSwitchVector(new::NewSwitchVector {
+        device: "CCD Simulator".to_string(),
+        name: "CONNECTION".to_string(),
+        timestamp: None,
+        elements: vec![new::OneSwitch {
+            name: "CONNECT".to_string(),
+            value: SwitchState::On,
+        }],
+    });
+    let xml = message.to_xml().unwrap();
+    match MessageType::from_str(&xml).unwrap() {
+        MessageType::NewSwitchVector(v) => {
+            assert_eq!(v.name, "CONNECTION");
+            assert_eq!(v.elements[0].value, SwitchState::On);
+        }
+        message => panic!("Expected NewSwitchVector, got {:?}", message),
+    }
+}
+
+#[test]
+fn test_del_property_round_trip() {
+    for name in [None, Some("CCD_TEMPERATURE".to_string())] {
+        let message = MessageType::DelProperty(basic::DelProperty {
+            device: "CCD Simulator".to_string(),
+            name: name.clone(),
+            timestamp: None,
+            message: Some("Cooler removed".to_string()),
+        });
+        let xml = message.to_xml().unwrap();
+        assert!(xml.starts_with("<delProperty "), "{}", xml);
+        match MessageType::from_str(&xml).unwrap() {
+            MessageType::DelProperty(v) => {
+                assert_eq!(v.device, "CCD Simulator");
+                assert_eq!(v.name, name);
+                assert_eq!(v.message.as_deref(), Some("Cooler removed"));
+            }
+            message => panic!("Expected DelProperty, got {:?}", message),
+        }
+    }
+}
+
+#[test]
+fn test_skip_garbage() {
+    let buf = b"<?xml version=\"1.0\"?>\n<INDI>junk</foo><message message=\"hi\"/>";
+    let skip = skip_garbage(buf);
+    assert!(buf[skip..].starts_with(b"<message "));
+    assert_eq!(skip_garbage(&buf[skip..]), 0);
+    assert_eq!(skip_garbage(b"text only"), 9);
+    // A tag name cut off at the end of the buffer is kept
+    assert_eq!(skip_garbage(b"junk<defText"), 4);
+    assert_eq!(skip_garbage(b"junk<"), 4);
+}
+
+#[test]
+fn test_compressed_blob() {
+    let blob = new::OneBlob::new("CCD1", ".fits.z", b"not really deflated");
+    assert!(blob.is_compressed());
+    assert_eq!(blob.get_raw_data().unwrap(), b"not really deflated");
+
+    #[cfg(feature = "zlib")]
+    {
+        let image = vec![42u8; 4096];
+        let blob = new::OneBlob::new_compressed("CCD1", ".fits", &image);
+        assert_eq!(blob.format, ".fits.z");
+        assert_eq!(blob.size, image.len());
+        assert_eq!(blob.data_format(), ".fits");
+        assert!(blob.get_raw_data().unwrap().len() < image.len());
+        assert_eq!(blob.get_data().unwrap(), image);
+
+        // A payload inflating beyond its announced size is rejected
+        let bomb = new::OneBlob { size: 1024, ..blob };
+        assert!(matches!(bomb.get_data(), Err(Error::ParseError(_))));
+    }
+}
+
+#[test]
+fn test_chunked_blob_decode() {
+    let image = (0..200_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    let mut blob = new::OneBlob::new("CCD1", ".fits", &image);
+    // Wrapped like indilib drivers do, so chunks straddle the line breaks
+    blob.value = blob
+        .value
+        .as_bytes()
+        .chunks(72)
+        .map(|line| std::str::from_utf8(line).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut buffer = b"header".to_vec();
+    assert_eq!(blob.decode_into(&mut buffer).unwrap(), image.len());
+    assert_eq!(&buffer[6..], image.as_slice());
+
+    let mut written = Vec::new();
+    assert_eq!(blob.decode_to(&mut written).unwrap(), image.len() as u64);
+    assert_eq!(written, image);
+    assert_eq!(blob.get_data().unwrap(), image);
+
+    blob.value.push('!');
+    assert!(blob.decode_to(std::io::sink()).is_err());
+}
+
+#[test]
+fn test_indilib_formatting() {
+    // As captured from indiserver
+    let captured = r#"<defSwitchVector device="QHY CCD QHY5III290C-1ca" name="CONNECTION" label="Connection" group="Main Control" state="Ok" perm="rw" rule="OneOfMany" timeout="60" timestamp="2025-02-16T03:06:48">
+    <defSwitch name="CONNECT" label="Connect">
+On
+    </defSwitch>
+    <defSwitch name="DISCONNECT" label="Disconnect">
+Off
+    </defSwitch>
+</defSwitchVector>
+"#;
+    let message = MessageType::from_str(captured).unwrap();
+    assert_eq!(message.to_indilib_xml().unwrap(), captured);
+
+    let captured = r#"<setNumberVector device="QHY CCD QHY5III290C-1ca" name="SCOPE_INFO" state="Ok" timeout="60" timestamp="2025-02-16T03:06:49">
+    <oneNumber name="FOCAL_LENGTH">
+300
+    </oneNumber>
+    <oneNumber name="APERTURE">
+50
+    </oneNumber>
+</setNumberVector>
+"#;
+    let message = MessageType::from_str(captured).unwrap();
+    assert_eq!(message.to_indilib_xml().unwrap(), captured);
+
+    let message = MessageType::from_str(
+        r#"<defTextVector device="A" name="B" label="B" group="G" state="Idle" perm="ro" timeout="0"><defText name="C" label="C"/></defTextVector>"#,
+    )
+    .unwrap();
+    assert!(message
+        .to_indilib_xml()
+        .unwrap()
+        .contains("    <defText name=\"C\" label=\"C\">\n\n    </defText>\n"));
+
+    let enable = MessageType::from_str(r#"<enableBLOB device="CCD">Also</enableBLOB>"#).unwrap();
+    assert_eq!(
+        enable.to_indilib_xml().unwrap(),
+        "<enableBLOB device=\"CCD\">Also</enableBLOB>\n"
+    );
+    let get = MessageType::from_str(r#"<getProperties version="1.7"/>"#).unwrap();
+    assert_eq!(
+        get.to_indilib_xml().unwrap(),
+        "<getProperties version=\"1.7\"/>\n"
+    );
+}
+
+#[test]
+fn test_message_accessors() {
+    let set = MessageType::from_str(
+        r#"<setNumberVector device="Mount" name="EQUATORIAL_EOD_COORD" timestamp="2024-01-01T00:00:00"><oneNumber name="RA">1</oneNumber></setNumberVector>"#,
+    )
+    .unwrap();
+    assert_eq!(set.kind(), MessageKind::SetNumberVector);
+    assert!(set.kind().is_set() && !set.kind().is_definition() && !set.kind().is_new());
+    assert_eq!(set.device(), Some("Mount"));
+    assert_eq!(set.name(), Some("EQUATORIAL_EOD_COORD"));
+    assert_eq!(set.timestamp(), Some("2024-01-01T00:00:00"));
+
+    let del = MessageType::from_str(r#"<delProperty device="Mount"/>"#).unwrap();
+    assert_eq!(del.kind(), MessageKind::DelProperty);
+    assert_eq!(
+        (del.device(), del.name(), del.timestamp()),
+        (Some("Mount"), None, None)
+    );
+
+    let get = MessageType::from_str(r#"<getProperties version="1.7"/>"#).unwrap();
+    assert_eq!((get.device(), get.name()), (None, None));
+
+    let ping = MessageType::from_str(r#"<pingRequest uid="1"/>"#).unwrap();
+    assert_eq!(ping.kind(), MessageKind::PingRequest);
+    assert_eq!(ping.device(), None);
+}
+
+#[test]
+fn test_lenient_parsing() {
+    let xml = r#"<defSwitchVector device="Mount" name="PARK" label="Park" group="Main" state="Idle" perm="rw" rule="OneOfMany" timeout="0" vendor="x">
+        <defSwitch name="PARK" label="Park" color="red">Off</defSwitch>
+        <defSwitch name="UNPARK" label="Unpark" color="red">On</defSwitch>
+        <vendorHint>slow</vendorHint>
+    </defSwitchVector>"#;
+    let (message, warnings) = MessageType::from_str_lenient(xml).unwrap();
+    assert_eq!(message.name(), Some("PARK"));
+    assert_eq!(
+        warnings,
+        vec![
+            lenient::ParseWarning {
+                element: "defSwitchVector".to_string(),
+                attribute: Some("vendor".to_string()),
+            },
+            lenient::ParseWarning {
+                element: "defSwitch".to_string(),
+                attribute: Some("color".to_string()),
+            },
+            lenient::ParseWarning {
+                element: "vendorHint".to_string(),
+                attribute: None,
+            },
+        ]
+    );
+    assert_eq!(
+        MessageType::from_str_strict(xml).unwrap_err().to_string(),
+        "Parse error: unknown attribute vendor of <defSwitchVector>"
+    );
+
+    let xml = r#"<setNumberVector device="Mount" name="EQUATORIAL_EOD_COORD" state="Ok" timeout="60" timestamp="2024-01-01T00:00:00" message="Slewing"><oneNumber name="RA">1.5</oneNumber></setNumberVector>"#;
+    assert!(MessageType::from_str_lenient(xml).unwrap().1.is_empty());
+    assert!(MessageType::from_str_strict(xml).is_ok());
+}
+
+#[test]
+fn test_event_writer_matches_serde() {
+    let messages = [
+        r#"<setTextVector device="A &amp; B" name="INFO" state="Ok" timeout="60" timestamp="2024-01-01T00:00:00" message="a &lt;b&gt; &quot;c&quot;"><oneText name="T">x &lt; y &amp; 'z'</oneText><oneText name="E"></oneText></setTextVector>"#,
+        r#"<setNumberVector device="Mount" name="EQUATORIAL_EOD_COORD" state="Busy"><oneNumber name="RA">1.5</oneNumber><oneNumber name="DEC">-20</oneNumber></setNumberVector>"#,
+        r#"<setSwitchVector device="Mount" name="PARK" timeout="0"><oneSwitch name="PARK">On</oneSwitch><oneSwitch name="UNPARK">Off</oneSwitch></setSwitchVector>"#,
+        r#"<setLightVector device="Mount" name="STATUS" state="Alert"><oneLight name="L">Idle</oneLight></setLightVector>"#,
+        r#"<setBLOBVector device="CCD" name="CCD1" state="Ok"><oneBLOB name="CCD1" size="3" format=".fits" enclen="4">AQID</oneBLOB></setBLOBVector>"#,
+        r#"<setNumberVector device="Mount" name="EMPTY"/>"#,
+        r#"<newTextVector device="A" name="B" timestamp="2024-01-01T00:00:00"><oneText name="C">"quoted"</oneText></newTextVector>"#,
+        r#"<newNumberVector device="A" name="B"><oneNumber name="C">2</oneNumber></newNumberVector>"#,
+        r#"<newSwitchVector device="A" name="B"><oneSwitch name="C">On</oneSwitch></newSwitchVector>"#,
+        r#"<newBLOBVector device="A" name="B"><oneBLOB name="C" size="3" format=".raw">AQID</oneBLOB></newBLOBVector>"#,
+    ];
+    for xml in messages {
+        let message = MessageType::from_str(xml).unwrap();
+        let mut written = Vec::new();
+        assert!(writer::write(&message, &mut written).unwrap());
+        let written = String::from_utf8(written).unwrap();
+        assert_eq!(written, to_string(&message).unwrap());
+        assert_eq!(message.to_xml().unwrap(), written);
+    }
+
+    let mut out = b"<getProperties version=\"1.7\"/>".to_vec();
+    let ping = MessageType::from_str(r#"<pingRequest uid="1"/>"#).unwrap();
+    assert!(!writer::write(&ping, &mut out).unwrap());
+    ping.write_xml(&mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        r#"<getProperties version="1.7"/><pingRequest uid="1"/>"#
+    );
+}
+
+#[test]
+fn test_apply_set_to_definition() {
+    let MessageType::DefNumberVector(mut def) = MessageType::from_str(
+        r#"<defNumberVector device="Mount" name="COORD" label="Coord" group="Main" state="Idle" perm="rw" timeout="60" timestamp="2024-01-01T00:00:00"><defNumber name="RA" format="%f" min="0" max="24" step="0">1</defNumber><defNumber name="DEC" format="%f" min="-90" max="90" step="0">2</defNumber></defNumberVector>"#,
+    )
+    .unwrap() else {
+        panic!("Expected DefNumberVector");
+    };
+    let MessageType::SetNumberVector(set) = MessageType::from_str(
+        r#"<setNumberVector device="Mount" name="COORD" state="Busy" timestamp="2024-01-01T00:00:01" message="Slewing"><oneNumber name="DEC">3</oneNumber><oneNumber name="ALT">4</oneNumber></setNumberVector>"#,
+    )
+    .unwrap() else {
+        panic!("Expected SetNumberVector");
+    };
+    def.apply(&set);
+    assert_eq!(def.numbers[0].value.trim(), "1");
+    assert_eq!(def.numbers[1].value.trim(), "3");
+    assert_eq!(def.numbers.len(), 2);
+    assert_eq!(def.state, PropertyState::Busy);
+    assert_eq!(def.timeout, 60);
+    assert_eq!(def.timestamp, "2024-01-01T00:00:01");
+    assert_eq!(def.message.as_deref(), Some("Slewing"));
+
+    let MessageType::DefSwitchVector(mut def) = MessageType::from_str(
+        r#"<defSwitchVector device="Mount" name="PARK" label="Park" group="Main" state="Ok" perm="rw" rule="OneOfMany" timeout="0" timestamp=""><defSwitch name="PARK" label="Park">On</defSwitch><defSwitch name="UNPARK" label="Unpark">Off</defSwitch></defSwitchVector>"#,
+    )
+    .unwrap() else {
+        panic!("Expected DefSwitchVector");
+    };
+    let MessageType::SetSwitchVector(set) = MessageType::from_str(
+        r#"<setSwitchVector device="Mount" name="PARK" timeout="5"><oneSwitch name="PARK">Off</oneSwitch><oneSwitch name="UNPARK">On</oneSwitch></setSwitchVector>"#,
+    )
+    .unwrap() else {
+        panic!("Expected SetSwitchVector");
+    };
+    def.apply(&set);
+    assert_eq!(def.switches[0].state, SwitchState::Off);
+    assert_eq!(def.switches[1].state, SwitchState::On);
+    assert_eq!(def.state, PropertyState::Ok);
+    assert_eq!(def.timeout, 5);
+    assert_eq!(def.message, None);
+}
+
+#[test]
+fn test_stamp() {
+    use crate::property::timestamp::{INDITimestamp, TimestampPolicy};
+
+    let unstamped = r#"<setNumberVector device="Focuser" name="ABS_FOCUS_POSITION" state="Ok"><oneNumber name="FOCUS_ABSOLUTE_POSITION">100</oneNumber></setNumberVector>"#;
+    let mut message = MessageType::from_str(unstamped).unwrap();
+    message.stamp(TimestampPolicy::Off);
+    assert_eq!(message.timestamp(), None);
+    message.stamp(TimestampPolicy::Seconds);
+    let stamped = message.timestamp().unwrap().to_string();
+    assert_eq!(stamped.len(), "2024-01-01T00:00:00".len());
+    assert!(
+        stamped.parse::<INDITimestamp>().unwrap().elapsed() < std::time::Duration::from_secs(60)
+    );
+    message.stamp(TimestampPolicy::Decimals(3));
+    assert_eq!(message.timestamp(), Some(stamped.as_str()));
+
+    let mut message = MessageType::from_str(
+        r#"<defSwitchVector device="Mount" name="PARK" state="Ok" perm="rw" rule="OneOfMany" timestamp=""><defSwitch name="PARK">On</defSwitch></defSwitchVector>"#,
+    )
+    .unwrap();
+    message.stamp(TimestampPolicy::Decimals(2));
+    assert_eq!(
+        message.timestamp().unwrap().len(),
+        "2024-01-01T00:00:00.00".len()
+    );
+
+    let mut message = MessageType::from_str(r#"<getProperties version="1.7"/>"#).unwrap();
+    message.stamp(TimestampPolicy::Seconds);
+    assert_eq!(message.timestamp(), None);
+}
+
+#[test]
+fn test_def_number_format_value() {
+    let MessageType::DefNumberVector(def) = MessageType::from_str(
+        r#"<defNumberVector device="Mount" name="COORD" state="Idle" perm="rw"><defNumber name="RA" format="%10.6m" min="0" max="24" step="0">12:30:15</defNumber><defNumber name="DEC" format="%6.2f" min="-90" max="90" step="0">-20.5</defNumber></defNumberVector>"#,
+    )
+    .unwrap() else {
+        panic!("Expected DefNumberVector");
+    };
+    assert_eq!(def.numbers[0].format_value().unwrap(), "  12:30:15");
+    assert_eq!(def.numbers[1].format_value().unwrap(), "-20.50");
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn test_json_round_trip() {
+    let message = MessageType::from_str(
+        r#"<setNumberVector device="Mount" name="EQUATORIAL_EOD_COORD" state="Ok" timeout="60"><oneNumber name="RA">12:30:00</oneNumber><oneNumber name="DEC">-20 &lt; x</oneNumber></setNumberVector>"#,
+    )
+    .unwrap();
+    let json = message.to_json().unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "type": "setNumberVector",
+            "device": "Mount",
+            "name": "EQUATORIAL_EOD_COORD",
+            "state": "Ok",
+            "timeout": "60",
+            "elements": [
+                {"name": "RA", "value": "12:30:00"},
+                {"name": "DEC", "value": "-20 < x"},
+            ],
+        })
+    );
+    let parsed = MessageType::from_json(&json).unwrap();
+    assert_eq!(parsed.to_xml().unwrap(), message.to_xml().unwrap());
+
+    for xml in [
+        r#"<defSwitchVector device="Mount" name="PARK" label="Park" group="Main" state="Ok" perm="rw" rule="OneOfMany" timeout="0" timestamp="2024-01-01T00:00:00"><defSwitch name="PARK" label="Park">On</defSwitch></defSwitchVector>"#,
+        r#"<defBLOBVector device="CCD" name="CCD1" label="Image" group="Main" state="Idle" perm="ro" timeout="0" timestamp=""><defBLOB name="CCD1" label="Image"/></defBLOBVector>"#,
+        r#"<enableBLOB device="CCD">Also</enableBLOB>"#,
+        r#"<getProperties version="1.7"/>"#,
+        r#"<message device="Mount" message="Slewing &quot;fast&quot;"/>"#,
+    ] {
+        let message = MessageType::from_str(xml).unwrap();
+        let parsed = MessageType::from_json(&message.to_json().unwrap()).unwrap();
+        assert_eq!(parsed.to_xml().unwrap(), message.to_xml().unwrap());
+    }
+
+    let json = serde_json::json!({"type": "enableBLOB", "device": "CCD", "value": "Only"});
+    assert_eq!(
+        MessageType::from_json(&json).unwrap().to_xml().unwrap(),
+        r#"<enableBLOB device="CCD">Only</enableBLOB>"#
+    );
+    assert!(MessageType::from_json(&serde_json::json!({"device": "CCD"})).is_err());
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_deprecated_types() {
+    // The former top-level types convert into the ones MessageType carries
+    let get = basic::GetProperties::from(super::GetProperties {
+        version: "1.7".to_string(),
+        device: Some("CCD".to_string()),
+        name: None,
+    });
+    let xml = MessageType::GetProperties(get).to_xml().unwrap();
+    assert_eq!(xml, r#"<getProperties version="1.7" device="CCD"/>"#);
+
+    let enable = basic::EnableBlob::from(super::EnableBLOB {
+        device: "CCD".to_string(),
+        name: Some("CCD1".to_string()),
+        value: "Also".to_string(),
+    });
+    let xml = MessageType::EnableBlob(enable).to_xml().unwrap();
+    assert_eq!(
+        xml,
+        r#"<enableBLOB device="CCD" name="CCD1">Also</enableBLOB>"#
+    );
+
+    let message = basic::Message::from(super::Message::new("Hello".to_string()));
+    assert_eq!(message.content, "Hello");
+    assert!(message.device.is_none() && message.timestamp.is_none());
+}
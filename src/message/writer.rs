@@ -0,0 +1,239 @@
+use quick_xml::escape::partial_escape;
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+
+use crate::error::{Error, Result};
+use crate::property::{PropertyState, SwitchState};
+
+use super::new::{OneBlob, OneLight, OneNumber, OneSwitch, OneText};
+use super::MessageType;
+
+/// Attributes of an update vector, in the order serde writes them
+struct Vector<'a> {
+    root: &'a str,
+    device: &'a str,
+    name: &'a str,
+    state: Option<PropertyState>,
+    timeout: Option<i32>,
+    timestamp: Option<&'a str>,
+    message: Option<&'a str>,
+}
+
+/// An element of an update vector
+trait Element {
+    const NAME: &'static str;
+
+    fn attributes<'a>(&'a self, start: &mut BytesStart<'a>);
+
+    fn value(&self) -> &str;
+}
+
+/// Write a `set*Vector` or `new*Vector` message without serde
+///
+/// Produces the same XML as the serde path. Returns false without writing
+/// anything for other messages.
+pub(crate) fn write(message: &MessageType, out: &mut Vec<u8>) -> Result<bool> {
+    let written = match message {
+        MessageType::SetTextVector(m) => write_vector(
+            out,
+            Vector::new("setTextVector", &m.device, &m.name, m.state, m.timeout)
+                .with(m.timestamp.as_deref(), m.message.as_deref()),
+            &m.elements,
+        ),
+        MessageType::SetNumberVector(m) => write_vector(
+            out,
+            Vector::new("setNumberVector", &m.device, &m.name, m.state, m.timeout)
+                .with(m.timestamp.as_deref(), m.message.as_deref()),
+            &m.elements,
+        ),
+        MessageType::SetSwitchVector(m) => write_vector(
+            out,
+            Vector::new("setSwitchVector", &m.device, &m.name, m.state, m.timeout)
+                .with(m.timestamp.as_deref(), m.message.as_deref()),
+            &m.elements,
+        ),
+        MessageType::SetLightVector(m) => write_vector(
+            out,
+            Vector::new("setLightVector", &m.device, &m.name, m.state, None)
+                .with(m.timestamp.as_deref(), m.message.as_deref()),
+            &m.elements,
+        ),
+        MessageType::SetBlobVector(m) => write_vector(
+            out,
+            Vector::new("setBLOBVector", &m.device, &m.name, m.state, m.timeout)
+                .with(m.timestamp.as_deref(), m.message.as_deref()),
+            &m.elements,
+        ),
+        MessageType::NewTextVector(m) => write_vector(
+            out,
+            Vector::new("newTextVector", &m.device, &m.name, None, None)
+                .with(m.timestamp.as_deref(), None),
+            &m.elements,
+        ),
+        MessageType::NewNumberVector(m) => write_vector(
+            out,
+            Vector::new("newNumberVector", &m.device, &m.name, None, None)
+                .with(m.timestamp.as_deref(), None),
+            &m.elements,
+        ),
+        MessageType::NewSwitchVector(m) => write_vector(
+            out,
+            Vector::new("newSwitchVector", &m.device, &m.name, None, None)
+                .with(m.timestamp.as_deref(), None),
+            &m.elements,
+        ),
+        MessageType::NewBlobVector(m) => write_vector(
+            out,
+            Vector::new("newBLOBVector", &m.device, &m.name, None, None)
+                .with(m.timestamp.as_deref(), None),
+            &m.elements,
+        ),
+        _ => return Ok(false),
+    };
+    written.map_err(|e| Error::SerializationError(e.to_string()))?;
+    Ok(true)
+}
+
+impl<'a> Vector<'a> {
+    fn new(
+        root: &'a str,
+        device: &'a str,
+        name: &'a str,
+        state: Option<PropertyState>,
+        timeout: Option<i32>,
+    ) -> Self {
+        Self {
+            root,
+            device,
+            name,
+            state,
+            timeout,
+            timestamp: None,
+            message: None,
+        }
+    }
+
+    fn with(mut self, timestamp: Option<&'a str>, message: Option<&'a str>) -> Self {
+        self.timestamp = timestamp;
+        self.message = message;
+        self
+    }
+}
+
+fn write_vector<E: Element>(
+    out: &mut Vec<u8>,
+    vector: Vector<'_>,
+    elements: &[E],
+) -> std::io::Result<()> {
+    let mut writer = Writer::new(out);
+    let mut start = BytesStart::new(vector.root);
+    start.push_attribute(("device", vector.device));
+    start.push_attribute(("name", vector.name));
+    if let Some(state) = vector.state {
+        start.push_attribute(("state", state_str(state)));
+    }
+    if let Some(timeout) = vector.timeout {
+        start.push_attribute(("timeout", timeout.to_string().as_str()));
+    }
+    if let Some(timestamp) = vector.timestamp {
+        start.push_attribute(("timestamp", timestamp));
+    }
+    if let Some(message) = vector.message {
+        start.push_attribute(("message", message));
+    }
+    if elements.is_empty() {
+        return writer.write_event(Event::Empty(start));
+    }
+    writer.write_event(Event::Start(start))?;
+    for element in elements {
+        let mut start = BytesStart::new(E::NAME);
+        element.attributes(&mut start);
+        let value = element.value();
+        if value.is_empty() {
+            writer.write_event(Event::Empty(start))?;
+            continue;
+        }
+        writer.write_event(Event::Start(start))?;
+        // Like serde, quotes are only escaped in attributes
+        writer.write_event(Event::Text(BytesText::from_escaped(partial_escape(value))))?;
+        writer.write_event(Event::End(BytesEnd::new(E::NAME)))?;
+    }
+    writer.write_event(Event::End(BytesEnd::new(vector.root)))
+}
+
+fn state_str(state: PropertyState) -> &'static str {
+    match state {
+        PropertyState::Idle => "Idle",
+        PropertyState::Ok => "Ok",
+        PropertyState::Busy => "Busy",
+        PropertyState::Alert => "Alert",
+    }
+}
+
+impl Element for OneText {
+    const NAME: &'static str = "oneText";
+
+    fn attributes<'a>(&'a self, start: &mut BytesStart<'a>) {
+        start.push_attribute(("name", self.name.as_str()));
+    }
+
+    fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl Element for OneNumber {
+    const NAME: &'static str = "oneNumber";
+
+    fn attributes<'a>(&'a self, start: &mut BytesStart<'a>) {
+        start.push_attribute(("name", self.name.as_str()));
+    }
+
+    fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl Element for OneSwitch {
+    const NAME: &'static str = "oneSwitch";
+
+    fn attributes<'a>(&'a self, start: &mut BytesStart<'a>) {
+        start.push_attribute(("name", self.name.as_str()));
+    }
+
+    fn value(&self) -> &str {
+        match self.value {
+            SwitchState::Off => "Off",
+            SwitchState::On => "On",
+        }
+    }
+}
+
+impl Element for OneLight {
+    const NAME: &'static str = "oneLight";
+
+    fn attributes<'a>(&'a self, start: &mut BytesStart<'a>) {
+        start.push_attribute(("name", self.name.as_str()));
+    }
+
+    fn value(&self) -> &str {
+        state_str(self.value)
+    }
+}
+
+impl Element for OneBlob {
+    const NAME: &'static str = "oneBLOB";
+
+    fn attributes<'a>(&'a self, start: &mut BytesStart<'a>) {
+        start.push_attribute(("name", self.name.as_str()));
+        start.push_attribute(("size", self.size.to_string().as_str()));
+        start.push_attribute(("format", self.format.as_str()));
+        if let Some(enclen) = self.enclen {
+            start.push_attribute(("enclen", enclen.to_string().as_str()));
+        }
+    }
+
+    fn value(&self) -> &str {
+        &self.value
+    }
+}
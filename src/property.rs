@@ -6,9 +6,9 @@
 //! and permissions (RO, WO, RW).
 
 use crate::error::{Error, Result};
+use crate::format::NumberFormat;
 use quick_xml::se::Serializer;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
 
@@ -52,7 +52,6 @@ impl fmt::Display for PropertyPerm {
 
 /// Property state
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
 pub enum PropertyState {
     /// Property is idle
     Idle,
@@ -91,7 +90,6 @@ impl fmt::Display for PropertyState {
 
 /// Switch state
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
 pub enum SwitchState {
     /// Switch is off
     Off,
@@ -122,7 +120,6 @@ impl fmt::Display for SwitchState {
 
 /// Switch rule
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
 pub enum SwitchRule {
     /// Only one switch can be On at a time
     OneOfMany,
@@ -145,12 +142,114 @@ pub enum PropertyValue {
     Light(PropertyState),
     /// BLOB value
     Blob(Vec<u8>),
-    /// Switch vector value
-    SwitchVector(HashMap<String, SwitchState>),
-    /// Text vector value
-    TextVector(HashMap<String, String>),
-    /// Number vector value
-    NumberVector(HashMap<String, f64>),
+    /// Switch vector value, in definition order
+    SwitchVector(SwitchRule, Vec<SwitchElement>),
+    /// Text vector value, in definition order
+    TextVector(Vec<TextElement>),
+    /// Number vector value, in definition order
+    NumberVector(Vec<NumberElement>),
+    /// BLOB vector, in definition order; payloads are delivered as events
+    BlobVector(Vec<BlobElement>),
+}
+
+/// Element of a text vector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextElement {
+    /// Element name
+    pub name: String,
+    /// Element label
+    pub label: String,
+    /// Current text
+    pub value: String,
+}
+
+/// Element of a number vector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NumberElement {
+    /// Element name
+    pub name: String,
+    /// Element label
+    pub label: String,
+    /// printf-style display format, e.g. `%10.6m`
+    pub format: String,
+    /// Smallest allowed value
+    pub min: f64,
+    /// Largest allowed value
+    pub max: f64,
+    /// Increment, 0 for none
+    pub step: f64,
+    /// Current value
+    pub value: f64,
+}
+
+impl NumberElement {
+    /// Current value rendered with the element's format
+    ///
+    /// Falls back to the plain value if the format is not supported.
+    pub fn format_value(&self) -> String {
+        match self.format.parse::<NumberFormat>() {
+            Ok(format) => format.format(self.value),
+            Err(_) => self.value.to_string(),
+        }
+    }
+}
+
+/// Element of a switch vector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwitchElement {
+    /// Element name
+    pub name: String,
+    /// Element label
+    pub label: String,
+    /// Current state
+    pub state: SwitchState,
+}
+
+/// Element of a BLOB vector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlobElement {
+    /// Element name
+    pub name: String,
+    /// Element label
+    pub label: String,
+    /// Format of the last BLOB received, e.g. `.fits`, empty before the
+    /// first one
+    pub format: String,
+}
+
+impl PropertyValue {
+    /// Value of a number vector element
+    pub fn number(&self, element: &str) -> Option<f64> {
+        match self {
+            PropertyValue::NumberVector(numbers) => numbers
+                .iter()
+                .find(|number| number.name == element)
+                .map(|number| number.value),
+            _ => None,
+        }
+    }
+
+    /// Value of a text vector element
+    pub fn text(&self, element: &str) -> Option<&str> {
+        match self {
+            PropertyValue::TextVector(texts) => texts
+                .iter()
+                .find(|text| text.name == element)
+                .map(|text| text.value.as_str()),
+            _ => None,
+        }
+    }
+
+    /// State of a switch vector element
+    pub fn switch(&self, element: &str) -> Option<SwitchState> {
+        match self {
+            PropertyValue::SwitchVector(_, switches) => switches
+                .iter()
+                .find(|switch| switch.name == element)
+                .map(|switch| switch.state),
+            _ => None,
+        }
+    }
 }
 
 impl Default for PropertyValue {
@@ -159,6 +258,20 @@ impl Default for PropertyValue {
     }
 }
 
+/// Write `name=value` pairs separated by commas
+fn write_elements<'a, T: fmt::Display + 'a>(
+    f: &mut fmt::Formatter<'_>,
+    elements: impl Iterator<Item = (&'a str, T)>,
+) -> fmt::Result {
+    for (i, (name, value)) in elements.enumerate() {
+        if i > 0 {
+            f.write_str(",")?;
+        }
+        write!(f, "{}={}", name, value)?;
+    }
+    Ok(())
+}
+
 impl fmt::Display for PropertyValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -167,42 +280,15 @@ impl fmt::Display for PropertyValue {
             PropertyValue::Number(num, Some(fmt_str)) => write!(f, "{} {}", num, fmt_str),
             PropertyValue::Switch(state) => write!(f, "{}", state),
             PropertyValue::Light(state) => write!(f, "{}", state),
-            PropertyValue::Blob(_) => write!(f, "[BLOB]"),
-            PropertyValue::SwitchVector(switches) => {
-                let mut entries: Vec<_> = switches.iter().collect();
-                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
-                let mut result = String::new();
-                for (name, state) in entries {
-                    if !result.is_empty() {
-                        result.push(',');
-                    }
-                    result.push_str(&format!("{}={}", name, state));
-                }
-                write!(f, "{}", result)
+            PropertyValue::Blob(_) | PropertyValue::BlobVector(_) => write!(f, "[BLOB]"),
+            PropertyValue::SwitchVector(_, switches) => {
+                write_elements(f, switches.iter().map(|s| (s.name.as_str(), s.state)))
             }
             PropertyValue::TextVector(texts) => {
-                let mut entries: Vec<_> = texts.iter().collect();
-                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
-                let mut result = String::new();
-                for (name, text) in entries {
-                    if !result.is_empty() {
-                        result.push(',');
-                    }
-                    result.push_str(&format!("{}={}", name, text));
-                }
-                write!(f, "{}", result)
+                write_elements(f, texts.iter().map(|t| (t.name.as_str(), &t.value)))
             }
             PropertyValue::NumberVector(numbers) => {
-                let mut entries: Vec<_> = numbers.iter().collect();
-                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
-                let mut result = String::new();
-                for (name, num) in entries {
-                    if !result.is_empty() {
-                        result.push(',');
-                    }
-                    result.push_str(&format!("{}={}", name, num));
-                }
-                write!(f, "{}", result)
+                write_elements(f, numbers.iter().map(|n| (n.name.as_str(), n.value)))
             }
         }
     }
@@ -231,6 +317,9 @@ pub struct Property {
     pub timeout: Option<u32>,
     /// Child elements (optional)
     pub elements: Option<Vec<Property>>,
+    /// Message of the last definition or update, if it had one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 impl Property {
@@ -254,6 +343,7 @@ impl Property {
             group: None,
             timeout: None,
             elements: None,
+            message: None,
         }
     }
 
@@ -278,6 +368,7 @@ impl Property {
             group: None,
             timeout: None,
             elements: None,
+            message: None,
         }
     }
 
@@ -301,6 +392,7 @@ impl Property {
             group: None,
             timeout: None,
             elements: Some(elements),
+            message: None,
         }
     }
 
@@ -322,6 +414,12 @@ impl Property {
         self
     }
 
+    /// Sets the message sent with the property
+    pub fn with_message(mut self, message: Option<String>) -> Self {
+        self.message = message;
+        self
+    }
+
     /// Returns true if the property is readable
     pub fn is_readable(&self) -> bool {
         use crate::property::PropertyPerm;
@@ -334,6 +432,84 @@ impl Property {
         matches!(self.perm, PropertyPerm::Wo | PropertyPerm::Rw)
     }
 
+    /// Value of number element `element` rendered with its format, without
+    /// the padding, e.g. `12:34:57` for a right ascension in `%10.6m`
+    pub fn format_value(&self, element: &str) -> Option<String> {
+        match &self.value {
+            PropertyValue::NumberVector(numbers) => numbers
+                .iter()
+                .find(|number| number.name == element)
+                .map(|number| number.format_value().trim_start().to_string()),
+            _ => None,
+        }
+    }
+
+    /// Changes from `old` to `new`, two versions of the same property
+    ///
+    /// Compares element values, the state and the message, so a GUI or
+    /// logger can show only what a `set*Vector` changed.
+    pub fn diff(old: &Property, new: &Property) -> PropertyDelta {
+        let old_values = old.element_values();
+        let new_values = new.element_values();
+        let mut elements = Vec::new();
+        for (name, value) in &new_values {
+            let previous = old_values.iter().find(|(old_name, _)| old_name == name);
+            if previous.map(|(_, old)| old) != Some(value) {
+                elements.push(ElementChange {
+                    name: name.to_string(),
+                    old: previous.map(|(_, old)| old.clone()),
+                    new: Some(value.clone()),
+                });
+            }
+        }
+        for (name, value) in &old_values {
+            if !new_values.iter().any(|(new_name, _)| new_name == name) {
+                elements.push(ElementChange {
+                    name: name.to_string(),
+                    old: Some(value.clone()),
+                    new: None,
+                });
+            }
+        }
+        PropertyDelta {
+            elements,
+            state: (old.state != new.state).then_some((old.state, new.state)),
+            message: (old.message != new.message).then(|| new.message.clone()),
+        }
+    }
+
+    /// Name and value as text of each element
+    ///
+    /// Lights are the child elements; a scalar value is a single element
+    /// named like the property.
+    fn element_values(&self) -> Vec<(&str, String)> {
+        match &self.value {
+            PropertyValue::TextVector(texts) => texts
+                .iter()
+                .map(|t| (t.name.as_str(), t.value.clone()))
+                .collect(),
+            PropertyValue::NumberVector(numbers) => numbers
+                .iter()
+                .map(|n| (n.name.as_str(), n.value.to_string()))
+                .collect(),
+            PropertyValue::SwitchVector(_, switches) => switches
+                .iter()
+                .map(|s| (s.name.as_str(), s.state.to_string()))
+                .collect(),
+            PropertyValue::BlobVector(blobs) => blobs
+                .iter()
+                .map(|b| (b.name.as_str(), b.format.clone()))
+                .collect(),
+            _ => match &self.elements {
+                Some(elements) => elements
+                    .iter()
+                    .map(|e| (e.name.as_str(), e.value.to_string()))
+                    .collect(),
+                None => vec![(self.name.as_str(), self.value.to_string())],
+            },
+        }
+    }
+
     /// Convert property to XML string
     pub fn to_xml(&self) -> Result<String> {
         let mut writer = String::new();
@@ -344,10 +520,50 @@ impl Property {
     }
 }
 
+/// Changes between two versions of a property, see [`Property::diff`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PropertyDelta {
+    /// Elements whose value changed, in the order of the new version
+    /// followed by removed elements
+    pub elements: Vec<ElementChange>,
+    /// Old and new state, if the state changed
+    pub state: Option<(PropertyState, PropertyState)>,
+    /// New message, if the message changed; `Some(None)` if it was cleared
+    pub message: Option<Option<String>>,
+}
+
+impl PropertyDelta {
+    /// Returns true if nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty() && self.state.is_none() && self.message.is_none()
+    }
+}
+
+/// Change of a single element value
+///
+/// Values are text: switch and light states by name, numbers in plain
+/// decimal and BLOBs by their format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElementChange {
+    /// Element name
+    pub name: String,
+    /// Previous value, `None` for an added element
+    pub old: Option<String>,
+    /// Current value, `None` for a removed element
+    pub new: Option<String>,
+}
+
+impl fmt::Display for ElementChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let old = self.old.as_deref().unwrap_or("-");
+        let new = self.new.as_deref().unwrap_or("-");
+        write!(f, "{}: {} -> {}", self.name, old, new)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
 
     #[test]
     fn test_property_creation() {
@@ -448,18 +664,38 @@ mod tests {
         let switch_off = PropertyValue::Switch(SwitchState::Off);
         let light = PropertyValue::Light(PropertyState::Ok);
         let blob = PropertyValue::Blob(vec![0; 100]);
-        let switch_vector = PropertyValue::SwitchVector(HashMap::from([
-            ("switch1".to_string(), SwitchState::On),
-            ("switch2".to_string(), SwitchState::Off),
-        ]));
-        let text_vector = PropertyValue::TextVector(HashMap::from([
-            ("text1".to_string(), "text1".to_string()),
-            ("text2".to_string(), "text2".to_string()),
-        ]));
-        let number_vector = PropertyValue::NumberVector(HashMap::from([
-            ("number1".to_string(), 42.0),
-            ("number2".to_string(), 24.0),
-        ]));
+        let switch_element = |name: &str, state| SwitchElement {
+            name: name.to_string(),
+            label: String::new(),
+            state,
+        };
+        let switch_vector = PropertyValue::SwitchVector(
+            SwitchRule::AnyOfMany,
+            vec![
+                switch_element("switch1", SwitchState::On),
+                switch_element("switch2", SwitchState::Off),
+            ],
+        );
+        let text_element = |name: &str| TextElement {
+            name: name.to_string(),
+            label: String::new(),
+            value: name.to_string(),
+        };
+        let text_vector =
+            PropertyValue::TextVector(vec![text_element("text2"), text_element("text1")]);
+        let number_element = |name: &str, value| NumberElement {
+            name: name.to_string(),
+            label: String::new(),
+            format: "%g".to_string(),
+            min: 0.0,
+            max: 100.0,
+            step: 0.0,
+            value,
+        };
+        let number_vector = PropertyValue::NumberVector(vec![
+            number_element("number1", 42.0),
+            number_element("number2", 24.0),
+        ]);
 
         assert_eq!(text.to_string(), "test");
         assert_eq!(num.to_string(), "42");
@@ -469,24 +705,303 @@ mod tests {
         assert_eq!(light.to_string(), "Ok");
         assert_eq!(blob.to_string(), "[BLOB]");
         assert_eq!(switch_vector.to_string(), "switch1=On,switch2=Off");
-        assert_eq!(text_vector.to_string(), "text1=text1,text2=text2");
+        assert_eq!(text_vector.to_string(), "text2=text2,text1=text1");
         assert_eq!(number_vector.to_string(), "number1=42,number2=24");
+        assert_eq!(number_vector.number("number2"), Some(24.0));
+        assert_eq!(text_vector.text("text1"), Some("text1"));
+        assert_eq!(switch_vector.switch("switch1"), Some(SwitchState::On));
+        assert_eq!(switch_vector.number("switch1"), None);
+    }
+
+    #[test]
+    fn test_format_value() {
+        let number_element = |name: &str, format: &str, value| NumberElement {
+            name: name.to_string(),
+            label: String::new(),
+            format: format.to_string(),
+            min: 0.0,
+            max: 0.0,
+            step: 0.0,
+            value,
+        };
+        let property = Property::new(
+            "Mount".to_string(),
+            "EQUATORIAL_EOD_COORD".to_string(),
+            PropertyValue::NumberVector(vec![
+                number_element("RA", "%10.8m", 12.0 + 34.0 / 60.0 + 56.7 / 3600.0),
+                number_element("DEC", "%9.6m", -20.5),
+                number_element("EPOCH", "%s", 2000.0),
+            ]),
+            PropertyState::Ok,
+            PropertyPerm::Rw,
+            timestamp::generate(),
+        );
+        assert_eq!(property.format_value("RA").as_deref(), Some("12:34:56.7"));
+        assert_eq!(property.format_value("DEC").as_deref(), Some("-20:30:00"));
+        assert_eq!(property.format_value("EPOCH").as_deref(), Some("2000"));
+        assert_eq!(property.format_value("ALT"), None);
+    }
+
+    #[test]
+    fn test_property_diff() {
+        let switch_element = |name: &str, state| SwitchElement {
+            name: name.to_string(),
+            label: String::new(),
+            state,
+        };
+        let old = Property::new(
+            "Mount".to_string(),
+            "PARK".to_string(),
+            PropertyValue::SwitchVector(
+                SwitchRule::OneOfMany,
+                vec![
+                    switch_element("PARK", SwitchState::On),
+                    switch_element("UNPARK", SwitchState::Off),
+                ],
+            ),
+            PropertyState::Ok,
+            PropertyPerm::Rw,
+            timestamp::generate(),
+        );
+        assert!(Property::diff(&old, &old).is_empty());
+
+        let mut new = old.clone();
+        new.value = PropertyValue::SwitchVector(
+            SwitchRule::OneOfMany,
+            vec![
+                switch_element("PARK", SwitchState::Off),
+                switch_element("UNPARK", SwitchState::On),
+            ],
+        );
+        new.state = PropertyState::Busy;
+        new.message = Some("Unparking".to_string());
+        let delta = Property::diff(&old, &new);
+        assert_eq!(delta.elements.len(), 2);
+        assert_eq!(delta.elements[0].to_string(), "PARK: On -> Off");
+        assert_eq!(delta.elements[1].to_string(), "UNPARK: Off -> On");
+        assert_eq!(delta.state, Some((PropertyState::Ok, PropertyState::Busy)));
+        assert_eq!(delta.message, Some(Some("Unparking".to_string())));
+
+        let mut removed = new.clone();
+        removed.value = PropertyValue::SwitchVector(
+            SwitchRule::OneOfMany,
+            vec![switch_element("PARK", SwitchState::Off)],
+        );
+        removed.message = None;
+        let delta = Property::diff(&new, &removed);
+        assert_eq!(delta.elements[0].to_string(), "UNPARK: On -> -");
+        assert_eq!(delta.state, None);
+        assert_eq!(delta.message, Some(None));
+    }
+
+    #[test]
+    fn test_indi_timestamp() {
+        use std::time::Duration;
+        use timestamp::INDITimestamp;
+
+        let start: INDITimestamp = "2024-02-21T19:30:00".parse().unwrap();
+        let later: INDITimestamp = "2024-02-21T19:30:01.250".parse().unwrap();
+        assert!(start < later);
+        assert_eq!(start + Duration::from_millis(1250), later);
+        assert_eq!(later - Duration::from_millis(1250), start);
+        assert_eq!((later - start).num_milliseconds(), 1250);
+        assert_eq!((start - later).num_milliseconds(), -1250);
+        assert_eq!(later.to_string(), "2024-02-21T19:30:01");
+        assert_eq!(format!("{:.1}", later), "2024-02-21T19:30:01.2");
+        assert_eq!(format!("{:.4}", later), "2024-02-21T19:30:01.2500");
+
+        let mut sorted = vec![later, start];
+        sorted.sort();
+        assert_eq!(sorted, vec![start, later]);
+        assert!(start.elapsed() > Duration::from_secs(3600));
+        assert_eq!(
+            (INDITimestamp::now() + Duration::from_secs(60)).elapsed(),
+            Duration::ZERO
+        );
+        assert!("2024-02-21".parse::<INDITimestamp>().is_err());
+
+        assert_eq!(
+            "2024-02-21T19:30:00Z".parse::<INDITimestamp>().unwrap(),
+            start
+        );
+        assert_eq!(
+            "2024-02-21T20:30:01.25+01:00"
+                .parse::<INDITimestamp>()
+                .unwrap(),
+            later
+        );
+        assert_eq!(
+            "2024-02-21T14:30:00-0500".parse::<INDITimestamp>().unwrap(),
+            start
+        );
+        let generated = timestamp::generate();
+        assert!(timestamp::validate(&generated).is_ok());
+        assert!(!generated.ends_with('Z') && !generated.contains('+'));
+        let stamped: INDITimestamp = generated.parse().unwrap();
+        assert!(stamped.elapsed() < Duration::from_secs(60));
+        assert_eq!(
+            "2024-02-21T19:30:00.000+00:00"
+                .parse::<INDITimestamp>()
+                .unwrap()
+                .to_string(),
+            "2024-02-21T19:30:00"
+        );
+        assert!("2024-02-21T19:30:00+25:00"
+            .parse::<INDITimestamp>()
+            .is_err());
+        assert!("2024-02-21T19:30:00 UTC".parse::<INDITimestamp>().is_err());
     }
 }
 
 /// Timestamp format validation and generation
 pub mod timestamp {
     use crate::error::{Error, Result};
-    use chrono::{DateTime, Utc};
+    use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+    use std::fmt;
+    use std::ops::{Add, Sub};
+    use std::str::FromStr;
+    use std::time::Duration;
 
-    /// Validate timestamp format
+    /// Validate timestamp format, see [`INDITimestamp`] for the accepted forms
     pub fn validate(timestamp: &str) -> Result<()> {
-        DateTime::parse_from_rfc3339(timestamp).map_err(|e| Error::ParseError(e.to_string()))?;
+        timestamp.parse::<INDITimestamp>()?;
         Ok(())
     }
 
-    /// Generate current timestamp
+    /// Generate current timestamp in the INDI format, in whole seconds
     pub fn generate() -> String {
-        Utc::now().to_rfc3339()
+        INDITimestamp::now().to_string()
+    }
+
+    /// Format of INDI timestamps, always UTC and without a zone suffix
+    const INDI_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+    /// UTC time in the INDI `YYYY-MM-DDTHH:MM:SS.S` format
+    ///
+    /// Displays with whole seconds like indiserver; a precision adds
+    /// decimals, so `{:.3}` gives milliseconds. Subtracting two timestamps
+    /// gives the signed [`TimeDelta`] between them.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct INDITimestamp(DateTime<Utc>);
+
+    impl INDITimestamp {
+        /// Current time
+        pub fn now() -> Self {
+            Self(Utc::now())
+        }
+
+        /// The time as a chrono [`DateTime`]
+        pub fn datetime(&self) -> DateTime<Utc> {
+            self.0
+        }
+
+        /// Time passed since this timestamp, zero if it is in the future
+        pub fn elapsed(&self) -> Duration {
+            (Utc::now() - self.0).to_std().unwrap_or_default()
+        }
+    }
+
+    impl From<DateTime<Utc>> for INDITimestamp {
+        fn from(datetime: DateTime<Utc>) -> Self {
+            Self(datetime)
+        }
+    }
+
+    impl From<INDITimestamp> for DateTime<Utc> {
+        fn from(timestamp: INDITimestamp) -> Self {
+            timestamp.0
+        }
+    }
+
+    impl FromStr for INDITimestamp {
+        type Err = Error;
+
+        /// Parse an INDI timestamp
+        ///
+        /// Besides the plain UTC form, accepts the `Z` suffix and UTC
+        /// offsets like `+01:00` or `-0500` some drivers send, converting
+        /// them to UTC.
+        fn from_str(text: &str) -> Result<Self> {
+            let trimmed = text.trim();
+            let invalid = |e: chrono::ParseError| {
+                Error::ParseError(format!("Invalid INDI timestamp '{}': {}", text, e))
+            };
+            if let Ok(naive) =
+                NaiveDateTime::parse_from_str(trimmed, &format!("{}%.f", INDI_FORMAT))
+            {
+                return Ok(Self(naive.and_utc()));
+            }
+            let zoned = match trimmed.strip_suffix(['Z', 'z']) {
+                Some(utc) => format!("{}+00:00", utc),
+                None => trimmed.to_string(),
+            };
+            let datetime = DateTime::parse_from_str(&zoned, &format!("{}%.f%#z", INDI_FORMAT))
+                .map_err(invalid)?;
+            Ok(Self(datetime.with_timezone(&Utc)))
+        }
+    }
+
+    impl fmt::Display for INDITimestamp {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.0.format(INDI_FORMAT))?;
+            match f.precision() {
+                Some(decimals) if decimals > 0 => {
+                    let nanos = format!("{:09}", self.0.timestamp_subsec_nanos());
+                    write!(f, ".{:0<1$}", &nanos[..decimals.min(9)], decimals)
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+
+    /// Stamping of outgoing definitions and updates that carry no timestamp
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum TimestampPolicy {
+        /// Send messages as they are
+        #[default]
+        Off,
+        /// Stamp with the current time in whole seconds
+        Seconds,
+        /// Stamp with the current time and this many decimals of the
+        /// seconds
+        Decimals(usize),
+    }
+
+    impl TimestampPolicy {
+        /// Current time formatted as the policy asks, `None` when it is
+        /// [`TimestampPolicy::Off`]
+        pub fn now(&self) -> Option<String> {
+            match *self {
+                TimestampPolicy::Off => None,
+                TimestampPolicy::Seconds => Some(INDITimestamp::now().to_string()),
+                TimestampPolicy::Decimals(decimals) => {
+                    Some(format!("{:.*}", decimals, INDITimestamp::now()))
+                }
+            }
+        }
+    }
+
+    impl Add<Duration> for INDITimestamp {
+        type Output = Self;
+
+        fn add(self, duration: Duration) -> Self {
+            Self(self.0 + duration)
+        }
+    }
+
+    impl Sub<Duration> for INDITimestamp {
+        type Output = Self;
+
+        fn sub(self, duration: Duration) -> Self {
+            Self(self.0 - duration)
+        }
+    }
+
+    impl Sub for INDITimestamp {
+        type Output = TimeDelta;
+
+        fn sub(self, earlier: Self) -> TimeDelta {
+            self.0 - earlier.0
+        }
     }
 }
@@ -0,0 +1,214 @@
+//! Altitude safety limits for telescope mounts
+//!
+//! A [`HorizonProfile`] gives the lowest safe altitude for every azimuth,
+//! for example to keep the tube above trees or the roof of a roll-off
+//! observatory. Together with the [`SiteLocation`] it lets
+//! [`Telescope`](crate::devices::Telescope) check equatorial targets before
+//! slewing and watch the mount while it tracks.
+
+use chrono::{DateTime, Utc};
+
+/// Observing site
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SiteLocation {
+    /// Geographic latitude in degrees, north positive
+    pub latitude: f64,
+    /// Geographic longitude in degrees, east positive
+    pub longitude: f64,
+}
+
+impl SiteLocation {
+    /// Create a site from latitude and east longitude in degrees
+    pub fn new(latitude: f64, longitude: f64) -> Self {
+        Self {
+            latitude,
+            longitude,
+        }
+    }
+
+    /// Local sidereal time in hours at `time`
+    pub fn local_sidereal_time(&self, time: DateTime<Utc>) -> f64 {
+        let julian_date = time.timestamp_millis() as f64 / 86_400_000.0 + 2_440_587.5;
+        let gmst = 280.460_618_37 + 360.985_647_366_29 * (julian_date - 2_451_545.0);
+        (gmst + self.longitude).rem_euclid(360.0) / 15.0
+    }
+
+    /// Convert equatorial coordinates of date to horizontal ones
+    ///
+    /// `ra` is in hours, `dec` in degrees. Returns `(altitude, azimuth)` in
+    /// degrees with azimuth measured from north through east.
+    pub fn to_horizontal(&self, ra: f64, dec: f64, time: DateTime<Utc>) -> (f64, f64) {
+        let hour_angle = ((self.local_sidereal_time(time) - ra) * 15.0).to_radians();
+        let dec = dec.to_radians();
+        let lat = self.latitude.to_radians();
+
+        let sin_alt = dec.sin() * lat.sin() + dec.cos() * lat.cos() * hour_angle.cos();
+        let altitude = sin_alt.clamp(-1.0, 1.0).asin();
+        let azimuth = (-hour_angle.sin() * dec.cos())
+            .atan2(lat.cos() * dec.sin() - lat.sin() * dec.cos() * hour_angle.cos());
+        (
+            altitude.to_degrees(),
+            azimuth.to_degrees().rem_euclid(360.0),
+        )
+    }
+}
+
+/// Minimum safe altitude as a function of azimuth
+///
+/// Points are interpolated linearly, wrapping around north.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HorizonProfile {
+    /// `(azimuth, minimum altitude)` pairs in degrees, sorted by azimuth
+    points: Vec<(f64, f64)>,
+}
+
+impl HorizonProfile {
+    /// Profile with the same minimum altitude in every direction
+    pub fn flat(min_altitude: f64) -> Self {
+        Self {
+            points: vec![(0.0, min_altitude)],
+        }
+    }
+
+    /// Profile from `(azimuth, minimum altitude)` pairs in degrees
+    ///
+    /// Azimuths are normalized to `0..360`; an empty list behaves like a
+    /// flat horizon at 0°.
+    pub fn from_points(points: impl IntoIterator<Item = (f64, f64)>) -> Self {
+        let mut points = points
+            .into_iter()
+            .map(|(az, alt)| (az.rem_euclid(360.0), alt))
+            .collect::<Vec<_>>();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if points.is_empty() {
+            points.push((0.0, 0.0));
+        }
+        Self { points }
+    }
+
+    /// Minimum safe altitude at `azimuth` in degrees
+    pub fn min_altitude(&self, azimuth: f64) -> f64 {
+        let azimuth = azimuth.rem_euclid(360.0);
+        let n = self.points.len();
+        // The segment containing `azimuth` starts at the last point at or
+        // before it, wrapping to the last point of the profile
+        let next = self.points.partition_point(|(az, _)| *az <= azimuth);
+        let (az0, alt0) = self.points[(next + n - 1) % n];
+        let (az1, alt1) = self.points[next % n];
+
+        let span = (az1 - az0).rem_euclid(360.0);
+        if span == 0.0 {
+            return alt0;
+        }
+        let offset = (azimuth - az0).rem_euclid(360.0);
+        alt0 + (alt1 - alt0) * offset / span
+    }
+}
+
+/// What to do with a slew target below the horizon profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LimitAction {
+    /// Refuse the slew
+    #[default]
+    Refuse,
+    /// Raise horizontal targets to the minimum altitude; equatorial targets
+    /// are refused
+    Clamp,
+}
+
+/// What to do when a tracking mount approaches the horizon profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrackingAction {
+    /// Abort motion and stop tracking
+    #[default]
+    Stop,
+    /// Abort motion and park the mount
+    Park,
+}
+
+/// Safety configuration of a telescope
+#[derive(Debug, Clone, PartialEq)]
+pub struct SafetyLimits {
+    /// Observing site
+    pub site: SiteLocation,
+    /// Minimum altitude per azimuth
+    pub horizon: HorizonProfile,
+    /// Handling of slew targets below the horizon
+    pub on_slew: LimitAction,
+    /// Handling of a tracking mount getting close to the horizon
+    pub on_tracking: TrackingAction,
+    /// Distance in degrees above the horizon at which tracking is stopped
+    pub margin: f64,
+}
+
+impl SafetyLimits {
+    /// Limits for `site` and `horizon`, refusing unsafe slews and stopping
+    /// at the horizon
+    pub fn new(site: SiteLocation, horizon: HorizonProfile) -> Self {
+        Self {
+            site,
+            horizon,
+            on_slew: LimitAction::default(),
+            on_tracking: TrackingAction::default(),
+            margin: 0.0,
+        }
+    }
+
+    /// Sets the handling of slew targets below the horizon
+    pub fn with_slew_action(mut self, action: LimitAction) -> Self {
+        self.on_slew = action;
+        self
+    }
+
+    /// Sets the handling of a tracking mount reaching the horizon
+    pub fn with_tracking_action(mut self, action: TrackingAction, margin: f64) -> Self {
+        self.on_tracking = action;
+        self.margin = margin;
+        self
+    }
+
+    /// Altitude of `ra`/`dec` at `time` minus the minimum altitude at its
+    /// azimuth; negative values are below the horizon profile
+    pub fn clearance(&self, ra: f64, dec: f64, time: DateTime<Utc>) -> f64 {
+        let (altitude, azimuth) = self.site.to_horizontal(ra, dec, time);
+        altitude - self.horizon.min_altitude(azimuth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_horizon_interpolation() {
+        let horizon = HorizonProfile::from_points([(90.0, 30.0), (0.0, 10.0), (270.0, 10.0)]);
+        assert_eq!(horizon.min_altitude(0.0), 10.0);
+        assert_eq!(horizon.min_altitude(45.0), 20.0);
+        assert_eq!(horizon.min_altitude(180.0), 20.0);
+        assert_eq!(horizon.min_altitude(315.0), 10.0);
+        assert_eq!(horizon.min_altitude(-45.0), 10.0);
+        assert_eq!(HorizonProfile::flat(15.0).min_altitude(200.0), 15.0);
+    }
+
+    #[test]
+    fn test_to_horizontal() {
+        let site = SiteLocation::new(50.0, 10.0);
+        let time = Utc.with_ymd_and_hms(2024, 3, 20, 22, 0, 0).unwrap();
+
+        // The celestial pole sits at the latitude due north
+        let (alt, az) = site.to_horizontal(0.0, 90.0, time);
+        assert!((alt - 50.0).abs() < 1e-6);
+        assert!(az < 1e-6 || (360.0 - az) < 1e-6);
+
+        // An object on the meridian culminates at 90° - lat + dec due south
+        let lst = site.local_sidereal_time(time);
+        let (alt, az) = site.to_horizontal(lst, 0.0, time);
+        assert!((alt - 40.0).abs() < 1e-6);
+        assert!((az - 180.0).abs() < 1e-6);
+
+        let limits = SafetyLimits::new(site, HorizonProfile::flat(30.0));
+        assert!((limits.clearance(lst, 0.0, time) - 10.0).abs() < 1e-6);
+        assert!(limits.clearance(lst, -30.0, time) < 0.0);
+    }
+}
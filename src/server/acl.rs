@@ -0,0 +1,228 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use crate::message::{MessageKind, MessageType};
+use crate::property::PropertyPerm;
+
+use super::mirror::make_read_only;
+
+/// Access a client has to a device or property, from least to most
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    /// Not sent to the client at all
+    Hidden,
+    /// Sent as read-only, writes are rejected
+    ReadOnly,
+    /// Unrestricted
+    ReadWrite,
+}
+
+/// Clients an [`AclRule`] applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Principal {
+    /// Every client
+    Any,
+    /// Clients authenticated with this identity, see
+    /// [`AuthConfig`](super::auth::AuthConfig)
+    Identity(String),
+    /// Clients connecting from this address
+    Address(IpAddr),
+}
+
+/// Access granted to some clients for a device or property
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AclRule {
+    /// Clients the rule applies to
+    pub principal: Principal,
+    /// Device the rule applies to, `None` for every device
+    pub device: Option<String>,
+    /// Property the rule applies to, `None` for every property of `device`
+    pub name: Option<String>,
+    /// Access granted
+    pub access: Access,
+}
+
+/// Access control lists of a server
+///
+/// The first rule matching the client, device and property decides;
+/// anything no rule matches is [`Access::ReadWrite`]. Hidden devices and
+/// properties are never sent to the client, read-only ones are defined
+/// with `perm="ro"`, and writes without [`Access::ReadWrite`] are answered
+/// with a `message` instead of reaching the driver.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Acl {
+    /// Rules in the order they are checked
+    pub rules: Vec<AclRule>,
+}
+
+impl Acl {
+    /// Create an empty list, granting everyone full access
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule granting `access` to `device`, or one of its properties,
+    /// for `principal`
+    pub fn rule(
+        mut self,
+        principal: Principal,
+        device: Option<&str>,
+        name: Option<&str>,
+        access: Access,
+    ) -> Self {
+        self.rules.push(AclRule {
+            principal,
+            device: device.map(str::to_string),
+            name: name.map(str::to_string),
+            access,
+        });
+        self
+    }
+
+    /// Access of a client to `device`, or its property `name`
+    ///
+    /// Without a property name, rules for single properties are skipped.
+    pub fn access(
+        &self,
+        identity: Option<&str>,
+        address: Option<IpAddr>,
+        device: &str,
+        name: Option<&str>,
+    ) -> Access {
+        self.rules
+            .iter()
+            .find(|rule| {
+                let principal = match &rule.principal {
+                    Principal::Any => true,
+                    Principal::Identity(expected) => identity == Some(expected.as_str()),
+                    Principal::Address(expected) => address == Some(*expected),
+                };
+                principal
+                    && rule.device.as_deref().map_or(true, |d| d == device)
+                    && rule.name.as_deref().map_or(true, |n| Some(n) == name)
+            })
+            .map_or(Access::ReadWrite, |rule| rule.access)
+    }
+}
+
+/// The access control lists as they apply to one client connection
+#[derive(Debug, Clone)]
+pub(crate) struct ClientAccess {
+    pub(crate) acl: Acl,
+    pub(crate) identity: Option<String>,
+    pub(crate) address: Option<IpAddr>,
+}
+
+impl ClientAccess {
+    /// Access to `device`, or its property `name`
+    pub(crate) fn access(&self, device: &str, name: Option<&str>) -> Access {
+        self.acl
+            .access(self.identity.as_deref(), self.address, device, name)
+    }
+
+    /// `message` as the client may see it, `None` if it is hidden
+    pub(crate) fn filter(&self, message: Arc<MessageType>) -> Option<Arc<MessageType>> {
+        let Some((device, name)) = subject(&message) else {
+            return Some(message);
+        };
+        match self.access(device, name) {
+            Access::Hidden => None,
+            Access::ReadOnly if is_writable_definition(&message) => {
+                let mut message = message.as_ref().clone();
+                make_read_only(&mut message);
+                Some(Arc::new(message))
+            }
+            _ => Some(message),
+        }
+    }
+}
+
+/// Device and property a message is about
+fn subject(message: &MessageType) -> Option<(&str, Option<&str>)> {
+    match message.kind() {
+        MessageKind::GetProperties
+        | MessageKind::EnableBlob
+        | MessageKind::PingRequest
+        | MessageKind::PingReply => None,
+        _ => Some((message.device()?, message.name())),
+    }
+}
+
+/// Device and property a client update writes to
+pub(crate) fn write_target(message: &MessageType) -> Option<(&str, &str)> {
+    if !message.kind().is_new() {
+        return None;
+    }
+    Some((message.device()?, message.name()?))
+}
+
+fn is_writable_definition(message: &MessageType) -> bool {
+    match message {
+        MessageType::DefTextVector(def) => def.perm != PropertyPerm::Ro,
+        MessageType::DefNumberVector(def) => def.perm != PropertyPerm::Ro,
+        MessageType::DefSwitchVector(def) => def.perm != PropertyPerm::Ro,
+        MessageType::DefBlobVector(def) => def.perm != PropertyPerm::Ro,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_client_access() {
+        let guest: IpAddr = "192.168.1.50".parse().unwrap();
+        let acl = Acl::new()
+            .rule(
+                Principal::Identity("admin".to_string()),
+                None,
+                None,
+                Access::ReadWrite,
+            )
+            .rule(Principal::Any, Some("Weather"), None, Access::ReadOnly)
+            .rule(Principal::Address(guest), None, None, Access::Hidden)
+            .rule(
+                Principal::Any,
+                Some("Mount"),
+                Some("TELESCOPE_PARK"),
+                Access::ReadOnly,
+            );
+        assert_eq!(
+            acl.access(Some("admin"), Some(guest), "Mount", None),
+            Access::ReadWrite
+        );
+        assert_eq!(
+            acl.access(None, Some(guest), "Weather", Some("RAIN")),
+            Access::ReadOnly
+        );
+        assert_eq!(acl.access(None, Some(guest), "Mount", None), Access::Hidden);
+        assert_eq!(
+            acl.access(None, None, "Mount", Some("TELESCOPE_PARK")),
+            Access::ReadOnly
+        );
+        assert_eq!(acl.access(None, None, "Mount", None), Access::ReadWrite);
+
+        let access = ClientAccess {
+            acl,
+            identity: None,
+            address: None,
+        };
+        let definition = Arc::new(
+            MessageType::from_str(
+                r#"<defSwitchVector device="Weather" name="ROOF" state="Idle" perm="rw" rule="OneOfMany"><defSwitch name="OPEN">Off</defSwitch></defSwitchVector>"#,
+            )
+            .unwrap(),
+        );
+        match access.filter(definition).unwrap().as_ref() {
+            MessageType::DefSwitchVector(def) => assert_eq!(def.perm, PropertyPerm::Ro),
+            message => panic!("Expected DefSwitchVector, got {:?}", message),
+        }
+        let write = MessageType::from_str(
+            r#"<newSwitchVector device="Weather" name="ROOF"><oneSwitch name="OPEN">On</oneSwitch></newSwitchVector>"#,
+        )
+        .unwrap();
+        assert_eq!(write_target(&write), Some(("Weather", "ROOF")));
+    }
+}
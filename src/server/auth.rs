@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+use crate::client::Credential;
+
+/// Credential accepted or refused by a server, and the identity it grants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthRule {
+    /// Name the client is known by once authenticated
+    pub identity: String,
+    /// Credential the client presents
+    pub credential: Credential,
+    /// Whether the credential grants access
+    pub allow: bool,
+}
+
+/// Authentication of client connections
+///
+/// Clients must send a [`Credential`] as their first line, see
+/// [`ClientConfig::credential`](crate::client::ClientConfig::credential).
+/// The first rule with a matching credential decides; connections with an
+/// unknown, refused, malformed or late credential are answered with a
+/// `message` and closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthConfig {
+    /// Rules in the order they are checked
+    pub rules: Vec<AuthRule>,
+    /// Time a client has to send its credential
+    pub timeout: Duration,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+impl AuthConfig {
+    /// Default time a client has to send its credential
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Create a configuration refusing every client
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Grant access as `identity` to clients presenting `credential`
+    pub fn allow(mut self, identity: impl Into<String>, credential: Credential) -> Self {
+        self.rules.push(AuthRule {
+            identity: identity.into(),
+            credential,
+            allow: true,
+        });
+        self
+    }
+
+    /// Refuse clients presenting `credential`, e.g. a revoked token
+    pub fn deny(mut self, identity: impl Into<String>, credential: Credential) -> Self {
+        self.rules.push(AuthRule {
+            identity: identity.into(),
+            credential,
+            allow: false,
+        });
+        self
+    }
+
+    /// Identity granted to `credential`, or the reason it is refused
+    pub fn check(&self, credential: &Credential) -> Result<&str, String> {
+        let rule = self
+            .rules
+            .iter()
+            .find(|rule| credentials_match(&rule.credential, credential))
+            .ok_or_else(|| "Authentication failed".to_string())?;
+        if rule.allow {
+            Ok(&rule.identity)
+        } else {
+            Err(format!("Access denied for {}", rule.identity))
+        }
+    }
+}
+
+/// Read the credential line from a new connection and check it
+///
+/// Returns the granted identity, or the reason the client is refused.
+pub(crate) async fn authenticate(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    config: &AuthConfig,
+    limit: u64,
+) -> Result<String, String> {
+    let mut line = Vec::new();
+    let mut reader = reader.take(limit);
+    match tokio::time::timeout(config.timeout, reader.read_until(b'\n', &mut line)).await {
+        Ok(Ok(_)) => (),
+        Ok(Err(e)) => return Err(format!("Authentication failed: {}", e)),
+        Err(_) => return Err("Authentication timed out".to_string()),
+    }
+    let line = String::from_utf8_lossy(&line);
+    let credential = Credential::from_preamble(&line).map_err(|e| e.to_string())?;
+    config.check(&credential).map(str::to_string)
+}
+
+/// Compare credentials without revealing through timing how much of a
+/// secret matched
+fn credentials_match(expected: &Credential, presented: &Credential) -> bool {
+    match (expected, presented) {
+        (
+            Credential::Password { user, password },
+            Credential::Password {
+                user: presented_user,
+                password: presented_password,
+            },
+        ) => user == presented_user && secrets_match(password, presented_password),
+        (Credential::Token(token), Credential::Token(presented)) => secrets_match(token, presented),
+        _ => false,
+    }
+}
+
+fn secrets_match(expected: &str, presented: &str) -> bool {
+    expected.len() == presented.len()
+        && expected
+            .bytes()
+            .zip(presented.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_credentials() {
+        let config = AuthConfig::new()
+            .deny("old-token", Credential::token("revoked"))
+            .allow("observer", Credential::password("observer", "secret"))
+            .allow("automation", Credential::token("revoked"))
+            .allow("automation", Credential::token("current"));
+        assert_eq!(
+            config.check(&Credential::password("observer", "secret")),
+            Ok("observer")
+        );
+        assert_eq!(
+            config.check(&Credential::token("current")),
+            Ok("automation")
+        );
+        assert_eq!(
+            config.check(&Credential::token("revoked")),
+            Err("Access denied for old-token".to_string())
+        );
+        assert!(config
+            .check(&Credential::password("observer", "secreT"))
+            .is_err());
+        assert!(config.check(&Credential::token("secret")).is_err());
+    }
+}
@@ -0,0 +1,425 @@
+use crate::debug::{DebugFlag, DebugOptions};
+use crate::message::definition::{DefNumber, DefNumberVector, DefSwitch, DefSwitchVector};
+use crate::message::new::{NewSwitchVector, OneNumber, OneSwitch};
+use crate::message::set::{SetNumberVector, SetSwitchVector};
+use crate::message::MessageType;
+use crate::property::{timestamp, PropertyPerm, PropertyState, SwitchRule, SwitchState};
+use crate::server::process::DriverProcess;
+use crate::server::ServerState;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex};
+use tokio::time::Instant;
+
+/// Name of the virtual device controlling the server itself
+pub const CONTROL_DEVICE: &str = "INDI Server";
+/// Switch vector of [`CONTROL_DEVICE`] holding the [`DebugFlag`]s
+pub const DEBUG: &str = "DEBUG";
+/// Number vector of [`CONTROL_DEVICE`] with the client message counters
+pub const MESSAGE_STATISTICS: &str = "MESSAGE_STATISTICS";
+/// Number vector of [`CONTROL_DEVICE`] with messages per second of each
+/// driver, one element per driver
+pub const DRIVER_THROUGHPUT: &str = "DRIVER_THROUGHPUT";
+/// Number vector of [`CONTROL_DEVICE`] with the connected clients, running
+/// drivers and server uptime
+pub const SERVER_STATUS: &str = "SERVER_STATUS";
+/// Number vector of [`CONTROL_DEVICE`] with the restarts of each driver,
+/// one element per driver
+pub const DRIVER_RESTARTS: &str = "DRIVER_RESTARTS";
+/// Time between updates of the statistics properties
+pub const STATISTICS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Switch state of `flag`
+fn switch_state(options: &DebugOptions, flag: DebugFlag) -> SwitchState {
+    if options.is_enabled(flag) {
+        SwitchState::On
+    } else {
+        SwitchState::Off
+    }
+}
+
+/// Definition of the [`DEBUG`] property reflecting `options`
+pub(crate) fn debug_definition(options: &DebugOptions) -> MessageType {
+    MessageType::DefSwitchVector(DefSwitchVector {
+        device: CONTROL_DEVICE.to_string(),
+        name: DEBUG.to_string(),
+        label: "Debug".to_string(),
+        group: "Options".to_string(),
+        state: PropertyState::Idle,
+        perm: PropertyPerm::Rw,
+        rule: SwitchRule::AnyOfMany,
+        timeout: 0,
+        timestamp: timestamp::generate(),
+        message: None,
+        switches: DebugFlag::ALL
+            .into_iter()
+            .map(|flag| DefSwitch {
+                name: flag.element().to_string(),
+                label: match flag {
+                    DebugFlag::RawXml => "Raw XML",
+                    DebugFlag::StateTransitions => "State transitions",
+                    DebugFlag::BlobMetadata => "BLOB metadata",
+                }
+                .to_string(),
+                state: switch_state(options, flag),
+            })
+            .collect(),
+    })
+}
+
+/// Apply a client's update of the [`DEBUG`] property to `options`
+///
+/// Returns the `setSwitchVector` acknowledging the new flags. Unknown
+/// elements are ignored.
+pub(crate) fn apply_debug(options: &DebugOptions, update: &NewSwitchVector) -> MessageType {
+    for switch in &update.elements {
+        if let Some(flag) = DebugFlag::from_element(&switch.name) {
+            options.set(flag, switch.value == SwitchState::On);
+        }
+    }
+    MessageType::SetSwitchVector(SetSwitchVector {
+        device: CONTROL_DEVICE.to_string(),
+        name: DEBUG.to_string(),
+        state: Some(PropertyState::Ok),
+        timeout: None,
+        timestamp: Some(timestamp::generate()),
+        message: None,
+        elements: DebugFlag::ALL
+            .into_iter()
+            .map(|flag| OneSwitch {
+                name: flag.element().to_string(),
+                value: switch_state(options, flag),
+            })
+            .collect(),
+    })
+}
+
+/// Message counters of the client connections
+#[derive(Debug, Default)]
+pub(crate) struct Traffic {
+    /// Currently connected clients
+    pub(crate) clients: AtomicU64,
+    /// Messages received from clients
+    pub(crate) received: AtomicU64,
+    /// Messages written to clients
+    pub(crate) sent: AtomicU64,
+}
+
+/// Counter element of a statistics property: name, label, format, value
+type Element = (String, String, &'static str, f64);
+
+/// State of one driver when the statistics are sampled
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DriverSample {
+    /// Driver executable
+    pub(crate) program: String,
+    /// Messages sent by the driver so far
+    pub(crate) messages: u64,
+    /// Times the driver was restarted
+    pub(crate) restarts: u32,
+    /// Whether the driver process is running, not waiting to restart
+    pub(crate) running: bool,
+}
+
+impl DriverSample {
+    fn of(driver: &DriverProcess) -> Self {
+        Self {
+            program: driver.program().to_string(),
+            messages: driver.messages(),
+            restarts: driver.restarts(),
+            running: driver.pid().is_some(),
+        }
+    }
+}
+
+/// Previous sample, used to turn counters into rates
+#[derive(Debug, Default)]
+pub(crate) struct Statistics {
+    received: u64,
+    sent: u64,
+    /// Messages of each driver by element name and label
+    drivers: HashMap<(String, String), u64>,
+    /// Driver elements last defined, redefined when drivers come and go
+    driver_elements: Option<Vec<(String, String)>>,
+}
+
+impl Statistics {
+    /// Take a sample of `traffic` and the `drivers`, `elapsed` after the
+    /// previous one and `uptime` after the server started
+    ///
+    /// Returns, for each statistics property, its definition carrying the
+    /// new values and the message to publish: a `setNumberVector`, or the
+    /// definition itself when the drivers changed.
+    pub(crate) fn sample(
+        &mut self,
+        traffic: &Traffic,
+        drivers: &[DriverSample],
+        elapsed: Duration,
+        uptime: Duration,
+    ) -> Vec<(MessageType, MessageType)> {
+        let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+        let received = traffic.received.load(Ordering::Relaxed);
+        let sent = traffic.sent.load(Ordering::Relaxed);
+        let messages = vec![
+            (
+                "CLIENTS".to_string(),
+                "Connected clients".to_string(),
+                "%.0f",
+                traffic.clients.load(Ordering::Relaxed) as f64,
+            ),
+            (
+                "RECEIVED".to_string(),
+                "Messages received".to_string(),
+                "%.0f",
+                received as f64,
+            ),
+            (
+                "SENT".to_string(),
+                "Messages sent".to_string(),
+                "%.0f",
+                sent as f64,
+            ),
+            (
+                "RECEIVED_RATE".to_string(),
+                "Received per second".to_string(),
+                "%.1f",
+                received.saturating_sub(self.received) as f64 / seconds,
+            ),
+            (
+                "SENT_RATE".to_string(),
+                "Sent per second".to_string(),
+                "%.1f",
+                sent.saturating_sub(self.sent) as f64 / seconds,
+            ),
+        ];
+        self.received = received;
+        self.sent = sent;
+        let status = vec![
+            (
+                "CLIENTS".to_string(),
+                "Connected clients".to_string(),
+                "%.0f",
+                traffic.clients.load(Ordering::Relaxed) as f64,
+            ),
+            (
+                "DRIVERS".to_string(),
+                "Running drivers".to_string(),
+                "%.0f",
+                drivers.iter().filter(|driver| driver.running).count() as f64,
+            ),
+            (
+                "UPTIME".to_string(),
+                "Uptime (s)".to_string(),
+                "%.0f",
+                uptime.as_secs() as f64,
+            ),
+        ];
+
+        let driver_elements = drivers
+            .iter()
+            .enumerate()
+            .map(|(index, driver)| {
+                let label = Path::new(&driver.program)
+                    .file_name()
+                    .map_or(driver.program.clone(), |file| {
+                        file.to_string_lossy().into_owned()
+                    });
+                (format!("DRIVER_{}", index + 1), label)
+            })
+            .collect::<Vec<_>>();
+        let mut counts = HashMap::new();
+        let throughput = driver_elements
+            .iter()
+            .zip(drivers)
+            .map(|(key, driver)| {
+                let previous = self.drivers.get(key).copied().unwrap_or_default();
+                counts.insert(key.clone(), driver.messages);
+                let rate = driver.messages.saturating_sub(previous) as f64 / seconds;
+                (key.0.clone(), key.1.clone(), "%.1f", rate)
+            })
+            .collect::<Vec<_>>();
+        let restarts = driver_elements
+            .iter()
+            .zip(drivers)
+            .map(|((name, label), driver)| {
+                (name.clone(), label.clone(), "%.0f", driver.restarts as f64)
+            })
+            .collect::<Vec<_>>();
+        let redefine = self.driver_elements.as_ref() != Some(&driver_elements);
+        self.drivers = counts;
+        let first = self.driver_elements.is_none();
+        self.driver_elements = Some(driver_elements);
+
+        vec![
+            statistics_messages(MESSAGE_STATISTICS, "Message statistics", messages, first),
+            statistics_messages(DRIVER_THROUGHPUT, "Driver throughput", throughput, redefine),
+            statistics_messages(SERVER_STATUS, "Server status", status, first),
+            statistics_messages(DRIVER_RESTARTS, "Driver restarts", restarts, redefine),
+        ]
+    }
+}
+
+/// Definition of a statistics property and the message publishing it
+fn statistics_messages(
+    name: &str,
+    label: &str,
+    elements: Vec<Element>,
+    redefine: bool,
+) -> (MessageType, MessageType) {
+    let definition = MessageType::DefNumberVector(DefNumberVector {
+        device: CONTROL_DEVICE.to_string(),
+        name: name.to_string(),
+        label: label.to_string(),
+        group: "Statistics".to_string(),
+        state: PropertyState::Ok,
+        perm: PropertyPerm::Ro,
+        timeout: 0,
+        timestamp: timestamp::generate(),
+        message: None,
+        numbers: elements
+            .iter()
+            .map(|(name, label, format, value)| DefNumber {
+                name: name.clone(),
+                label: label.clone(),
+                format: format.to_string(),
+                min: "0".to_string(),
+                max: "0".to_string(),
+                step: "0".to_string(),
+                value: value.to_string(),
+            })
+            .collect(),
+    });
+    if redefine {
+        return (definition.clone(), definition);
+    }
+    let update = MessageType::SetNumberVector(SetNumberVector {
+        device: CONTROL_DEVICE.to_string(),
+        name: name.to_string(),
+        state: Some(PropertyState::Ok),
+        timeout: None,
+        timestamp: Some(timestamp::generate()),
+        message: None,
+        elements: elements
+            .into_iter()
+            .map(|(name, _, _, value)| OneNumber {
+                name,
+                value: value.to_string(),
+            })
+            .collect(),
+    });
+    (definition, update)
+}
+
+/// Publish the statistics and status properties every
+/// [`STATISTICS_INTERVAL`]
+pub(crate) async fn publish_statistics(
+    state: Arc<Mutex<ServerState>>,
+    drivers: Arc<Mutex<Vec<DriverProcess>>>,
+    outbound: broadcast::Sender<Arc<MessageType>>,
+    traffic: Arc<Traffic>,
+) {
+    let mut statistics = Statistics::default();
+    let mut ticker = tokio::time::interval(STATISTICS_INTERVAL);
+    let started = Instant::now();
+    let mut last = started;
+    loop {
+        ticker.tick().await;
+        let samples = drivers
+            .lock()
+            .await
+            .iter()
+            .map(DriverSample::of)
+            .collect::<Vec<_>>();
+        let messages = statistics.sample(&traffic, &samples, last.elapsed(), started.elapsed());
+        last = Instant::now();
+
+        let mut state = state.lock().await;
+        for (definition, message) in messages {
+            if let MessageType::DefNumberVector(def) = &definition {
+                state
+                    .devices
+                    .entry(CONTROL_DEVICE.to_string())
+                    .or_default()
+                    .insert(def.name.clone(), definition.clone());
+            }
+            // Having no connected clients is not an error
+            let _ = outbound.send(Arc::new(message));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statistics() {
+        let driver = |program: &str, messages, restarts| DriverSample {
+            program: program.to_string(),
+            messages,
+            restarts,
+            running: true,
+        };
+        let interval = Duration::from_secs(5);
+        let traffic = Traffic::default();
+        let mut statistics = Statistics::default();
+        let drivers = vec![driver("/usr/bin/indi_simulator_ccd", 10, 0)];
+        let messages = statistics.sample(&traffic, &drivers, interval, interval);
+        // The first sample defines all properties
+        assert!(messages
+            .iter()
+            .all(|(_, message)| matches!(message, MessageType::DefNumberVector(_))));
+
+        traffic.clients.store(2, Ordering::Relaxed);
+        traffic.received.store(50, Ordering::Relaxed);
+        let drivers = vec![driver("/usr/bin/indi_simulator_ccd", 30, 1)];
+        let messages = statistics.sample(&traffic, &drivers, interval, interval * 2);
+        let MessageType::SetNumberVector(set) = &messages[0].1 else {
+            panic!("Expected an update, got {:?}", messages[0].1);
+        };
+        let value = |name: &str| {
+            set.elements
+                .iter()
+                .find(|e| e.name == name)
+                .map(|e| e.value.clone())
+                .unwrap()
+        };
+        assert_eq!(value("CLIENTS"), "2");
+        assert_eq!(value("RECEIVED_RATE"), "10");
+        let MessageType::SetNumberVector(set) = &messages[1].1 else {
+            panic!("Expected an update, got {:?}", messages[1].1);
+        };
+        assert_eq!(set.elements[0].value, "4");
+        let MessageType::SetNumberVector(set) = &messages[2].1 else {
+            panic!("Expected an update, got {:?}", messages[2].1);
+        };
+        assert_eq!(set.name, SERVER_STATUS);
+        let status = set
+            .elements
+            .iter()
+            .map(|e| e.value.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(status, ["2", "1", "10"]);
+        let MessageType::SetNumberVector(set) = &messages[3].1 else {
+            panic!("Expected an update, got {:?}", messages[3].1);
+        };
+        assert_eq!(set.elements[0].value, "1");
+
+        // A new driver redefines the throughput property
+        let drivers = vec![
+            driver("/usr/bin/indi_simulator_ccd", 30, 1),
+            driver("indi_simulator_telescope", 5, 0),
+        ];
+        let messages = statistics.sample(&traffic, &drivers, interval, interval * 3);
+        assert!(matches!(messages[0].1, MessageType::SetNumberVector(_)));
+        let MessageType::DefNumberVector(def) = &messages[1].1 else {
+            panic!("Expected a definition, got {:?}", messages[1].1);
+        };
+        assert_eq!(def.numbers.len(), 2);
+        assert_eq!(def.numbers[0].label, "indi_simulator_ccd");
+        assert!(matches!(messages[3].1, MessageType::DefNumberVector(_)));
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use tokio::sync::{broadcast, mpsc};
+use tracing::debug;
+
+use crate::client::definition_key;
+use crate::driver::{Command, Driver, Snoops, Timers};
+use crate::message::MessageType;
+use crate::property::timestamp::TimestampPolicy;
+
+use super::drivers::{self, DriverContext};
+
+/// Device driver, run inside the server process by
+/// [`Server::register_driver`](super::Server::register_driver)
+pub use crate::driver::IndiDriver;
+
+/// Run `driver` until the server forgets all of its devices
+pub(crate) async fn host(mut driver: Box<dyn IndiDriver>, context: DriverContext) {
+    let (updates, mut received) = mpsc::unbounded_channel();
+    let routes = updates.downgrade();
+    let (handle, mut commands) = Driver::new();
+    driver.attach(handle);
+    let timestamp_policy = driver.timestamp_policy();
+    let definitions = driver.define_properties().await;
+    // Devices of the driver itself, never snooped
+    let mut devices = HashSet::new();
+    remember_devices(&mut devices, &definitions);
+    publish(&context, &updates, definitions, timestamp_policy).await;
+    // Only the routes of its devices in the server state keep the driver
+    // running
+    drop(updates);
+
+    let mut ticker = driver.poll_interval().map(|interval| {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        ticker
+    });
+    let mut timers = Timers::default();
+    let mut snoops = Snoops::default();
+    let mut snooped: Option<broadcast::Receiver<Arc<MessageType>>> = None;
+    loop {
+        let messages = tokio::select! {
+            update = received.recv() => match update {
+                Some(MessageType::NewTextVector(update)) => driver.on_new_text(update).await,
+                Some(MessageType::NewNumberVector(update)) => driver.on_new_number(update).await,
+                Some(MessageType::NewSwitchVector(update)) => driver.on_new_switch(update).await,
+                Some(MessageType::NewBlobVector(update)) => driver.on_new_blob(update).await,
+                Some(_) => continue,
+                None => break,
+            },
+            Some(command) = commands.recv() => match command {
+                Command::Publish(message) => vec![message],
+                Command::AddTimer(id, delay, callback) => {
+                    timers.add(id, delay, callback);
+                    continue;
+                }
+                Command::RemoveTimer(id) => {
+                    timers.remove(id);
+                    continue;
+                }
+                Command::Snoop(device, name) => {
+                    // Subscribe before reading the stored definitions so no
+                    // update falls in between
+                    snooped.get_or_insert_with(|| context.outbound.subscribe());
+                    let definitions = context
+                        .state
+                        .lock()
+                        .await
+                        .definitions(Some(&device), name.as_deref());
+                    snoops.add(device, name);
+                    let mut messages = Vec::new();
+                    for definition in definitions {
+                        messages.extend(driver.on_snoop(definition).await);
+                    }
+                    messages
+                }
+            },
+            Some(message) = async { snooped.as_mut()?.recv().await.ok() } => {
+                let own = message.device().is_some_and(|device| devices.contains(device));
+                if own || !snoops.matches(&message) {
+                    continue;
+                }
+                driver.on_snoop(MessageType::clone(&message)).await
+            }
+            (id, callback) = timers.next() => match callback {
+                Some(callback) => callback.call(),
+                None => driver.on_timer(id).await,
+            },
+            Some(_) = async { Some(ticker.as_mut()?.tick().await) } => driver.poll().await,
+        };
+        let Some(updates) = routes.upgrade() else {
+            break;
+        };
+        remember_devices(&mut devices, &messages);
+        publish(&context, &updates, messages, timestamp_policy).await;
+    }
+    debug!("In-process driver stopped");
+}
+
+/// Add the devices `messages` define to `devices`
+fn remember_devices(devices: &mut HashSet<String>, messages: &[MessageType]) {
+    devices.extend(
+        messages
+            .iter()
+            .filter_map(definition_key)
+            .map(|(device, _)| device),
+    );
+}
+
+/// Publish the driver's messages, routing updates of the devices it defines
+/// to `updates`
+///
+/// Messages are stamped as the driver asks before the server's own
+/// [`TimestampPolicy`] applies.
+async fn publish(
+    context: &DriverContext,
+    updates: &mpsc::UnboundedSender<MessageType>,
+    messages: Vec<MessageType>,
+    timestamp_policy: TimestampPolicy,
+) {
+    for mut message in messages {
+        message.stamp(timestamp_policy);
+        {
+            let mut state = context.state.lock().await;
+            if let Some((device, _)) = definition_key(&message) {
+                state.hosted.insert(device, updates.clone());
+            } else if let MessageType::DelProperty(del) = &message {
+                if del.name.is_none() {
+                    state.hosted.remove(&del.device);
+                }
+            }
+        }
+        drivers::publish(context, drivers::NO_DRIVER, message).await;
+    }
+}
@@ -0,0 +1,351 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{broadcast, mpsc, Mutex, Notify};
+use tracing::{debug, warn};
+
+use crate::client::definition_key;
+use crate::error::Result;
+use crate::message::basic::DelProperty;
+use crate::message::MessageType;
+use crate::property::timestamp::{self, TimestampPolicy};
+use crate::server::process::{
+    DriverProcess, DriverShutdown, RestartPolicy, ShutdownOutcome, ShutdownPolicy,
+};
+use crate::server::ServerState;
+
+/// Time an exited driver gets to be reaped before it is killed
+const EXIT_GRACE: Duration = Duration::from_millis(500);
+
+/// Source of messages published without a local driver, which no driver id
+/// equals
+pub(crate) const NO_DRIVER: u64 = 0;
+
+/// Driver processes of a server, in the order they were added
+pub(crate) type Drivers = Arc<Mutex<Vec<DriverProcess>>>;
+
+/// Shared server state the driver tasks work with
+#[derive(Debug, Clone)]
+pub(crate) struct DriverContext {
+    pub(crate) drivers: Drivers,
+    pub(crate) state: Arc<Mutex<ServerState>>,
+    pub(crate) outbound: broadcast::Sender<Arc<MessageType>>,
+    pub(crate) timestamp_policy: TimestampPolicy,
+}
+
+/// Handle to a driver process managed by a [`Server`](super::Server)
+///
+/// The handle stays valid across restarts of the driver. Once the driver
+/// is stopped, or given up on after exiting too often, it is no longer
+/// managed and the queries return `None`.
+#[derive(Debug, Clone)]
+pub struct DriverHandle {
+    id: u64,
+    program: String,
+    context: DriverContext,
+}
+
+impl DriverHandle {
+    /// Driver executable
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Returns true while the server manages the driver, including while
+    /// it waits to restart it
+    pub async fn is_managed(&self) -> bool {
+        self.with(|_| ()).await.is_some()
+    }
+
+    /// Operating system process id of the running driver
+    pub async fn pid(&self) -> Option<u32> {
+        self.with(DriverProcess::pid).await.flatten()
+    }
+
+    /// Number of times the driver was restarted after exiting
+    pub async fn restarts(&self) -> Option<u32> {
+        self.with(DriverProcess::restarts).await
+    }
+
+    /// Number of messages the driver has sent, over all its restarts
+    pub async fn messages(&self) -> Option<u64> {
+        self.with(DriverProcess::messages).await
+    }
+
+    /// Devices defined by the driver so far
+    pub async fn devices(&self) -> Option<Vec<String>> {
+        let drivers = self.context.drivers.lock().await;
+        let driver = drivers.iter().find(|driver| driver.id() == self.id)?;
+        Some(driver.devices().await)
+    }
+
+    /// Stop the driver and delete its devices on connected clients
+    ///
+    /// Returns `None` if the driver was no longer managed.
+    pub async fn stop(&self, policy: ShutdownPolicy) -> Option<DriverShutdown> {
+        let driver = {
+            let mut drivers = self.context.drivers.lock().await;
+            let index = drivers.iter().position(|driver| driver.id() == self.id)?;
+            drivers.remove(index)
+        };
+        let report = driver.shutdown(policy).await;
+        delete_devices(&self.context.state, &self.context.outbound, &report).await;
+        Some(report)
+    }
+
+    async fn with<T>(&self, query: impl FnOnce(&DriverProcess) -> T) -> Option<T> {
+        let drivers = self.context.drivers.lock().await;
+        drivers
+            .iter()
+            .find(|driver| driver.id() == self.id)
+            .map(query)
+    }
+}
+
+/// Launch a driver and start routing its messages and restarting it
+/// according to `restart`
+///
+/// Without a restart policy a driver that exits is removed and its devices
+/// are deleted right away.
+pub(crate) async fn start(
+    context: &DriverContext,
+    program: &str,
+    args: &[String],
+    env: &[(String, String)],
+    restart: Option<RestartPolicy>,
+) -> Result<DriverHandle> {
+    let (output, routed) = mpsc::unbounded_channel();
+    let driver = DriverProcess::launch(program, args, env, Some(output))?;
+    tokio::spawn(route_output(driver.id(), routed, context.clone()));
+    tokio::spawn(supervise(
+        driver.id(),
+        driver.exited(),
+        restart.unwrap_or(RestartPolicy {
+            max_restarts: Some(0),
+            ..RestartPolicy::default()
+        }),
+        context.clone(),
+    ));
+    let handle = DriverHandle {
+        id: driver.id(),
+        program: driver.program().to_string(),
+        context: context.clone(),
+    };
+    context.drivers.lock().await.push(driver);
+    Ok(handle)
+}
+
+/// Stop the drivers selected by `matches` and delete their devices on
+/// connected clients
+pub(crate) async fn stop(
+    context: &DriverContext,
+    matches: impl Fn(&DriverProcess) -> bool,
+    policy: ShutdownPolicy,
+) -> Vec<DriverShutdown> {
+    let stopped = {
+        let mut drivers = context.drivers.lock().await;
+        let (stopped, kept) = std::mem::take(&mut *drivers)
+            .into_iter()
+            .partition::<Vec<_>, _>(|driver| matches(driver));
+        *drivers = kept;
+        stopped
+    };
+    let mut reports = Vec::new();
+    for driver in stopped {
+        let report = driver.shutdown(policy).await;
+        delete_devices(&context.state, &context.outbound, &report).await;
+        reports.push(report);
+    }
+    reports
+}
+
+/// Remove the devices of a stopped driver and send `delProperty` for each
+pub(crate) async fn delete_devices(
+    state: &Mutex<ServerState>,
+    outbound: &broadcast::Sender<Arc<MessageType>>,
+    report: &DriverShutdown,
+) {
+    let reason = format!("Driver {} stopped", report.program);
+    remove_devices(state, outbound, &report.devices, &reason).await;
+}
+
+/// Remove `devices` and send a `delProperty` with `reason` for each
+pub(crate) async fn remove_devices(
+    state: &Mutex<ServerState>,
+    outbound: &broadcast::Sender<Arc<MessageType>>,
+    devices: &[String],
+    reason: &str,
+) {
+    let mut state = state.lock().await;
+    for device in devices {
+        state.devices.remove(device);
+        state.hosted.remove(device);
+        // Having no connected clients is not an error
+        let _ = outbound.send(Arc::new(MessageType::DelProperty(DelProperty {
+            device: device.clone(),
+            name: None,
+            timestamp: Some(timestamp::generate()),
+            message: Some(reason.to_string()),
+        })));
+    }
+}
+
+/// Store the messages of driver `id` and forward them to connected clients
+/// and snooping drivers
+///
+/// A `getProperties` from the driver subscribes it to another device, see
+/// [`ServerState::snoop`], and is answered with the stored definitions.
+/// Ends when the driver is dropped, removing its subscriptions.
+async fn route_output(
+    id: u64,
+    mut output: mpsc::UnboundedReceiver<MessageType>,
+    context: DriverContext,
+) {
+    while let Some(message) = output.recv().await {
+        let MessageType::GetProperties(get) = &message else {
+            publish(&context, id, message).await;
+            continue;
+        };
+        let Some(device) = get.device.as_deref() else {
+            debug!("Ignoring getProperties without device from driver");
+            continue;
+        };
+        let definitions = {
+            let mut state = context.state.lock().await;
+            state.snoop(id, device, get.name.as_deref());
+            state
+                .definitions(Some(device), get.name.as_deref())
+                .into_iter()
+                .map(Arc::new)
+                .collect::<Vec<_>>()
+        };
+        send_to(&context.drivers, &[id], &definitions).await;
+    }
+    context.state.lock().await.forget_snooper(id);
+}
+
+/// Store a device message from driver `source` and forward it to connected
+/// clients and to the drivers snooping on the device
+///
+/// Definitions and updates without a timestamp are stamped first as the
+/// [`ServerConfig::timestamp_policy`](super::ServerConfig::timestamp_policy)
+/// asks.
+pub(crate) async fn publish(context: &DriverContext, source: u64, mut message: MessageType) {
+    message.stamp(context.timestamp_policy);
+    let snoopers = {
+        let mut state = context.state.lock().await;
+        match &message {
+            MessageType::DelProperty(del) => match &del.name {
+                Some(name) => {
+                    if let Some(properties) = state.devices.get_mut(&del.device) {
+                        properties.remove(name);
+                        if properties.is_empty() {
+                            state.devices.remove(&del.device);
+                        }
+                    }
+                }
+                None => {
+                    state.devices.remove(&del.device);
+                }
+            },
+            MessageType::SetTextVector(_)
+            | MessageType::SetNumberVector(_)
+            | MessageType::SetSwitchVector(_)
+            | MessageType::SetLightVector(_)
+            | MessageType::SetBlobVector(_) => state.apply_set(&message),
+            MessageType::Message(_) => (),
+            _ => match definition_key(&message) {
+                Some((device, name)) => {
+                    state
+                        .devices
+                        .entry(device)
+                        .or_default()
+                        .insert(name, message.clone());
+                }
+                None => {
+                    debug!("Ignoring driver message {:?}", message);
+                    return;
+                }
+            },
+        }
+        state.snoopers(&message, source)
+    };
+    let message = Arc::new(message);
+    send_to(&context.drivers, &snoopers, std::slice::from_ref(&message)).await;
+    // Having no connected clients is not an error
+    let _ = context.outbound.send(message);
+}
+
+/// Write `messages` to the drivers in `ids`
+async fn send_to(drivers: &Drivers, ids: &[u64], messages: &[Arc<MessageType>]) {
+    if ids.is_empty() {
+        return;
+    }
+    for driver in drivers.lock().await.iter() {
+        if ids.contains(&driver.id()) {
+            for message in messages {
+                driver.send(message.clone());
+            }
+        }
+    }
+}
+
+/// Restart driver `id` whenever it exits, until `policy` gives up
+///
+/// A driver that is given up on is removed and its devices are deleted.
+/// Ends as soon as the driver is no longer managed.
+async fn supervise(id: u64, exited: Arc<Notify>, policy: RestartPolicy, context: DriverContext) {
+    let DriverContext {
+        drivers,
+        state,
+        outbound,
+        ..
+    } = context;
+    let mut delay = policy.initial_delay;
+    loop {
+        exited.notified().await;
+        {
+            let mut guard = drivers.lock().await;
+            let Some(index) = guard.iter().position(|driver| driver.id() == id) else {
+                return;
+            };
+            let driver = &mut guard[index];
+            let status = driver.reap(EXIT_GRACE).await;
+            warn!("Driver {} exited: {:?}", driver.program(), status);
+            if driver.uptime() > policy.max_delay {
+                delay = policy.initial_delay;
+            }
+            if policy
+                .max_restarts
+                .is_some_and(|max| driver.restarts() >= max)
+            {
+                let driver = guard.remove(index);
+                drop(guard);
+                warn!(
+                    "Giving up on driver {} after {} restarts",
+                    driver.program(),
+                    driver.restarts()
+                );
+                let report = DriverShutdown {
+                    program: driver.program().to_string(),
+                    devices: driver.devices().await,
+                    outcome: status.map(ShutdownOutcome::Exited),
+                };
+                delete_devices(&state, &outbound, &report).await;
+                return;
+            }
+        }
+
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(policy.max_delay);
+        let mut guard = drivers.lock().await;
+        let Some(driver) = guard.iter_mut().find(|driver| driver.id() == id) else {
+            return;
+        };
+        if let Err(e) = driver.respawn() {
+            warn!("Failed to restart driver {}: {}", driver.program(), e);
+            // Count the failed start as another exit
+            exited.notify_one();
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::str::FromStr;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::unix::pipe;
+use tracing::{debug, warn};
+
+use crate::error::{Error, Result};
+
+use super::drivers::{self, DriverContext};
+use super::process::{DriverProcess, ShutdownPolicy};
+
+/// Environment variable passing the device name to a driver
+pub const DEVICE_ENV: &str = "INDIDEV";
+
+/// Environment variable passing the configuration file to a driver
+pub const CONFIG_ENV: &str = "INDICONFIG";
+
+/// Environment variable passing the skeleton file to a driver
+pub const SKELETON_ENV: &str = "INDISKEL";
+
+/// Environment variable passing the device name prefix to a driver
+pub const PREFIX_ENV: &str = "INDIPREFIX";
+
+/// Command read from the control FIFO, in `indiserver` syntax
+///
+/// `start indi_simulator_ccd -n "CCD Simulator"` starts a driver, passing
+/// the options to it as environment variables. `stop indi_simulator_ccd`
+/// stops every driver started from that executable, or only the one with
+/// the given `-n` device name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FifoCommand {
+    /// Start a driver
+    Start {
+        /// Driver executable
+        driver: String,
+        /// Device name, `-n`
+        name: Option<String>,
+        /// Configuration file, `-c`
+        config: Option<String>,
+        /// Skeleton file, `-s`
+        skeleton: Option<String>,
+        /// Device name prefix, `-p`
+        prefix: Option<String>,
+    },
+    /// Stop matching drivers
+    Stop {
+        /// Driver executable, with or without its directory
+        driver: String,
+        /// Device name, `-n`
+        name: Option<String>,
+    },
+}
+
+impl FifoCommand {
+    /// Environment variables the started driver receives
+    fn env(&self) -> Vec<(String, String)> {
+        let Self::Start {
+            name,
+            config,
+            skeleton,
+            prefix,
+            ..
+        } = self
+        else {
+            return Vec::new();
+        };
+        [
+            (DEVICE_ENV, name),
+            (CONFIG_ENV, config),
+            (SKELETON_ENV, skeleton),
+            (PREFIX_ENV, prefix),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value.clone()?)))
+        .collect()
+    }
+
+    /// Returns true if `driver` is selected by a `stop` command
+    fn stops(&self, process: &DriverProcess) -> bool {
+        let Self::Stop { driver, name } = self else {
+            return false;
+        };
+        let program = process.program();
+        let basename = Path::new(program)
+            .file_name()
+            .and_then(|name| name.to_str());
+        (program == driver || basename == Some(driver.as_str()))
+            && name
+                .as_deref()
+                .map_or(true, |name| process.env(DEVICE_ENV) == Some(name))
+    }
+}
+
+impl FromStr for FifoCommand {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::ParseError(format!("{}: {}", reason, line));
+        let mut words = split_words(line)
+            .ok_or_else(|| invalid("Unterminated quote"))?
+            .into_iter();
+        let verb = words.next().ok_or_else(|| invalid("Empty command"))?;
+        let driver = words.next().ok_or_else(|| invalid("Missing driver"))?;
+        let (mut name, mut config, mut skeleton, mut prefix) = (None, None, None, None);
+        while let Some(option) = words.next() {
+            let slot = match option.as_str() {
+                "-n" => &mut name,
+                "-c" => &mut config,
+                "-s" => &mut skeleton,
+                "-p" => &mut prefix,
+                _ => return Err(invalid("Unknown option")),
+            };
+            *slot = Some(
+                words
+                    .next()
+                    .ok_or_else(|| invalid("Missing option value"))?,
+            );
+        }
+        match verb.as_str() {
+            "start" => Ok(Self::Start {
+                driver,
+                name,
+                config,
+                skeleton,
+                prefix,
+            }),
+            "stop" => Ok(Self::Stop { driver, name }),
+            _ => Err(invalid("Unknown command")),
+        }
+    }
+}
+
+/// Split `line` at whitespace, keeping single or double quoted words
+/// together
+///
+/// Returns `None` if a quote is not closed.
+fn split_words(line: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            return Some(words);
+        };
+        let mut word = String::new();
+        if first == '"' || first == '\'' {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    c if c == first => break,
+                    c => word.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+        }
+        words.push(word);
+    }
+}
+
+/// Open the FIFO at `path`, creating it if it does not exist
+///
+/// The returned sender only keeps the FIFO from reaching end of file
+/// whenever the last external writer closes it.
+pub(crate) fn open(path: &Path) -> Result<(pipe::Receiver, pipe::Sender)> {
+    if !path.exists() {
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| Error::Message(format!("Invalid FIFO path: {}", path.display())))?;
+        // SAFETY: `c_path` is a valid NUL terminated string
+        if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    let receiver = pipe::OpenOptions::new().open_receiver(path)?;
+    let sender = pipe::OpenOptions::new().open_sender(path)?;
+    Ok((receiver, sender))
+}
+
+/// Run the commands written to `fifo`, one per line
+pub(crate) async fn serve(
+    (fifo, _keep_open): (pipe::Receiver, pipe::Sender),
+    context: DriverContext,
+) {
+    let mut lines = BufReader::new(fifo).lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Failed to read control FIFO: {}", e);
+                return;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let command = match FifoCommand::from_str(&line) {
+            Ok(command) => command,
+            Err(e) => {
+                warn!("Ignoring control FIFO command: {}", e);
+                continue;
+            }
+        };
+        debug!("Control FIFO command {:?}", command);
+        match &command {
+            FifoCommand::Start { driver, .. } => {
+                if let Err(e) = drivers::start(
+                    &context,
+                    driver,
+                    &[],
+                    &command.env(),
+                    Some(Default::default()),
+                )
+                .await
+                {
+                    warn!("Failed to start driver {}: {}", driver, e);
+                }
+            }
+            FifoCommand::Stop { driver, .. } => {
+                let reports = drivers::stop(
+                    &context,
+                    |process| command.stops(process),
+                    ShutdownPolicy::default(),
+                )
+                .await;
+                if reports.is_empty() {
+                    warn!("No running driver matches {}", driver);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::testing::wait_until;
+    use crate::server::{Server, ServerConfig};
+    use tokio::io::AsyncWriteExt;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            FifoCommand::from_str(r#"start indi_simulator_ccd -n "CCD Simulator" -p 'Obs '"#)
+                .unwrap(),
+            FifoCommand::Start {
+                driver: "indi_simulator_ccd".to_string(),
+                name: Some("CCD Simulator".to_string()),
+                config: None,
+                skeleton: None,
+                prefix: Some("Obs ".to_string()),
+            }
+        );
+        assert_eq!(
+            FifoCommand::from_str("  stop indi_simulator_ccd").unwrap(),
+            FifoCommand::Stop {
+                driver: "indi_simulator_ccd".to_string(),
+                name: None,
+            }
+        );
+        assert!(FifoCommand::from_str("restart indi_simulator_ccd").is_err());
+        assert!(FifoCommand::from_str("start indi_simulator_ccd -n").is_err());
+        assert!(FifoCommand::from_str("start indi_simulator_ccd -x 1").is_err());
+        assert!(FifoCommand::from_str(r#"start indi_simulator_ccd -n "CCD"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fifo_start_stop() {
+        let dir = std::env::temp_dir().join(format!("indi-fifo-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let driver = dir.join("indi_fake");
+        std::fs::write(
+            &driver,
+            "#!/bin/sh\necho \"<defTextVector device=\\\"$INDIDEV\\\" name=\\\"INFO\\\" state=\\\"Idle\\\" perm=\\\"ro\\\"></defTextVector>\"\ncat > /dev/null\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&driver, std::os::unix::fs::PermissionsExt::from_mode(0o755))
+            .unwrap();
+        let path = dir.join("control");
+
+        let server = Server::new(ServerConfig {
+            bind_addr: String::new(),
+            ..Default::default()
+        });
+        let _listener = server.listen_fifo(&path).unwrap();
+        let mut fifo = tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .await
+            .unwrap();
+        let has_device = |name: &'static str| {
+            let state = server.state.clone();
+            async move { state.lock().await.devices.contains_key(name) }
+        };
+
+        for name in ["Fake A", "Fake B"] {
+            fifo.write_all(format!("start {} -n \"{}\"\n", driver.display(), name).as_bytes())
+                .await
+                .unwrap();
+        }
+        wait_until("both drivers to start", || async {
+            has_device("Fake A").await && has_device("Fake B").await
+        })
+        .await;
+
+        fifo.write_all(b"stop indi_fake -n \"Fake A\"\n")
+            .await
+            .unwrap();
+        wait_until("Fake A to stop", || async {
+            !has_device("Fake A").await && server.drivers.lock().await.len() == 1
+        })
+        .await;
+        assert!(has_device("Fake B").await);
+
+        server.shutdown_drivers(ShutdownPolicy::default()).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
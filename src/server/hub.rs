@@ -0,0 +1,449 @@
+use std::collections::{HashSet, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, Mutex, Notify};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::message::MessageType;
+use crate::server::ServerState;
+
+/// Source of client connection ids
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Messages queued for one client before it is treated as lagging
+const MAX_QUEUED_MESSAGES: usize = 1024;
+
+/// What to do when the BLOBs queued for a slow client exceed
+/// [`OutboundQueue::max_blob_bytes`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SlowClientPolicy {
+    /// Drop the oldest queued BLOBs, as `indiserver` does
+    #[default]
+    DropOldestBlob,
+    /// Drop BLOBs superseded by a newer BLOB of the same property first,
+    /// then the oldest ones
+    Coalesce,
+    /// Close the connection
+    Disconnect,
+}
+
+/// Limits of the messages queued for each client connection
+///
+/// Every client has its own queue, so a client that stalls while
+/// receiving large BLOBs only holds up its own delivery. The newest BLOB is
+/// always kept, even if it alone exceeds the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutboundQueue {
+    /// Encoded BLOB bytes a client may have queued
+    pub max_blob_bytes: usize,
+    /// What to do once the limit is exceeded
+    pub policy: SlowClientPolicy,
+}
+
+impl Default for OutboundQueue {
+    fn default() -> Self {
+        Self {
+            max_blob_bytes: Self::DEFAULT_MAX_BLOB_BYTES,
+            policy: SlowClientPolicy::default(),
+        }
+    }
+}
+
+impl OutboundQueue {
+    /// Default limit of queued BLOB bytes per client
+    pub const DEFAULT_MAX_BLOB_BYTES: usize = 64 * 1024 * 1024;
+}
+
+/// Messages waiting to be written, with the BLOB bytes of each
+#[derive(Debug, Default)]
+struct Pending {
+    messages: VecDeque<(Arc<MessageType>, usize)>,
+    blob_bytes: usize,
+    /// Set once no more messages will be queued
+    closed: bool,
+}
+
+impl Pending {
+    /// Queue `message`, returning false if the client must be disconnected
+    fn push(&mut self, message: Arc<MessageType>, limits: &OutboundQueue) -> bool {
+        let size = blob_bytes(&message);
+        self.messages.push_back((message, size));
+        self.blob_bytes += size;
+        if self.blob_bytes <= limits.max_blob_bytes {
+            return true;
+        }
+        match limits.policy {
+            SlowClientPolicy::Disconnect => return false,
+            SlowClientPolicy::Coalesce => self.drop_superseded(),
+            SlowClientPolicy::DropOldestBlob => (),
+        }
+        while self.blob_bytes > limits.max_blob_bytes && self.drop_oldest_blob() {}
+        true
+    }
+
+    fn pop(&mut self) -> Option<Arc<MessageType>> {
+        let (message, size) = self.messages.pop_front()?;
+        self.blob_bytes -= size;
+        Some(message)
+    }
+
+    /// Replace everything queued with `messages`
+    fn replace(&mut self, messages: Vec<MessageType>) {
+        self.messages = messages
+            .into_iter()
+            .map(|message| (Arc::new(message), 0))
+            .collect();
+        self.blob_bytes = 0;
+    }
+
+    /// Drop BLOBs of properties with a newer BLOB queued
+    fn drop_superseded(&mut self) {
+        let mut newer = HashSet::new();
+        let mut kept = VecDeque::with_capacity(self.messages.len());
+        while let Some((message, size)) = self.messages.pop_back() {
+            if let MessageType::SetBlobVector(set) = message.as_ref() {
+                if !newer.insert((set.device.clone(), set.name.clone())) {
+                    self.blob_bytes -= size;
+                    continue;
+                }
+            }
+            kept.push_front((message, size));
+        }
+        self.messages = kept;
+    }
+
+    /// Drop the oldest BLOB other than the newest message, returning false
+    /// if there is none
+    fn drop_oldest_blob(&mut self) -> bool {
+        let newest = self.messages.len().saturating_sub(1);
+        let Some(index) = self
+            .messages
+            .iter()
+            .take(newest)
+            .position(|(_, size)| *size > 0)
+        else {
+            return false;
+        };
+        if let Some((_, size)) = self.messages.remove(index) {
+            self.blob_bytes -= size;
+        }
+        true
+    }
+}
+
+/// Encoded payload bytes of a BLOB message, 0 for other messages
+fn blob_bytes(message: &MessageType) -> usize {
+    match message {
+        MessageType::SetBlobVector(set) => set
+            .elements
+            .iter()
+            .map(|blob| blob.value.len())
+            .sum::<usize>()
+            .max(1),
+        _ => 0,
+    }
+}
+
+/// Queue shared between a client's [`ClientFeed`] and the task filling it
+#[derive(Debug, Default)]
+struct Queue {
+    pending: std::sync::Mutex<Pending>,
+    ready: Notify,
+    disconnect: Notify,
+}
+
+impl Queue {
+    fn update<T>(&self, change: impl FnOnce(&mut Pending) -> T) -> Option<T> {
+        let result = self
+            .pending
+            .lock()
+            .ok()
+            .map(|mut pending| change(&mut pending));
+        self.ready.notify_one();
+        result
+    }
+}
+
+/// Messages to write to one client connection
+///
+/// Merges the replies meant for this client alone with the messages
+/// fanned out to every client. Fanned out messages are moved to a queue of
+/// the client's own as soon as they are sent, skipping those the client
+/// did not ask for per [`ServerState::forwards`]; BLOBs piling up in the
+/// queue are shed according to [`OutboundQueue`]. A client too slow to
+/// keep up otherwise misses messages; it is then sent every stored
+/// definition again, which carries the latest values, so its view
+/// converges instead of silently going stale.
+pub(crate) struct ClientFeed {
+    client: u64,
+    replies: mpsc::UnboundedReceiver<MessageType>,
+    queue: Arc<Queue>,
+    fill: JoinHandle<()>,
+}
+
+impl ClientFeed {
+    pub(crate) fn new(
+        outbound: broadcast::Receiver<Arc<MessageType>>,
+        replies: mpsc::UnboundedReceiver<MessageType>,
+        state: Arc<Mutex<ServerState>>,
+        limits: OutboundQueue,
+    ) -> Self {
+        let client = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+        let queue = Arc::new(Queue::default());
+        let fill = tokio::spawn(fill(client, outbound, state, queue.clone(), limits));
+        Self {
+            client,
+            replies,
+            queue,
+            fill,
+        }
+    }
+
+    /// Id of the client connection, used for its [`ServerState`] entries
+    pub(crate) fn client(&self) -> u64 {
+        self.client
+    }
+
+    /// Next message for the client, `None` once the server is gone or the
+    /// client is disconnected for falling behind
+    pub(crate) async fn next(&mut self) -> Option<Arc<MessageType>> {
+        loop {
+            match self.try_next().await {
+                Some(message) => return Some(message),
+                None if self.is_closed() => return None,
+                None => (),
+            }
+            // Every change of the queue leaves a permit, so none is missed
+            tokio::select! {
+                Some(reply) = self.replies.recv() => return Some(Arc::new(reply)),
+                _ = self.queue.ready.notified() => (),
+            }
+        }
+    }
+
+    /// Next message already queued for the client, `None` once all are
+    /// taken
+    pub(crate) async fn try_next(&mut self) -> Option<Arc<MessageType>> {
+        if let Ok(reply) = self.replies.try_recv() {
+            return Some(Arc::new(reply));
+        }
+        self.queue.pending.lock().ok()?.pop()
+    }
+
+    /// Completes once the client is disconnected for falling behind
+    pub(crate) fn disconnected(&self) -> impl Future<Output = ()> + Send + 'static {
+        let queue = self.queue.clone();
+        async move { queue.disconnect.notified().await }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.queue
+            .pending
+            .lock()
+            .map_or(true, |pending| pending.closed)
+    }
+}
+
+impl Drop for ClientFeed {
+    fn drop(&mut self) {
+        self.fill.abort();
+    }
+}
+
+/// Move the fanned out messages meant for `client` to its queue
+async fn fill(
+    client: u64,
+    mut outbound: broadcast::Receiver<Arc<MessageType>>,
+    state: Arc<Mutex<ServerState>>,
+    queue: Arc<Queue>,
+    limits: OutboundQueue,
+) {
+    loop {
+        match outbound.recv().await {
+            Ok(message) => {
+                if !state.lock().await.forwards(client, &message) {
+                    continue;
+                }
+                let queued = queue.update(|pending| {
+                    if pending.messages.len() >= MAX_QUEUED_MESSAGES {
+                        return None;
+                    }
+                    Some(pending.push(message, &limits))
+                });
+                match queued {
+                    Some(Some(true)) => (),
+                    Some(Some(false)) => {
+                        warn!("Disconnecting client that cannot keep up with BLOBs");
+                        queue.update(|pending| pending.replace(Vec::new()));
+                        queue.disconnect.notify_one();
+                        break;
+                    }
+                    Some(None) => {
+                        warn!(
+                            "Client fell {} messages behind, resending definitions",
+                            MAX_QUEUED_MESSAGES
+                        );
+                        let definitions = state.lock().await.definitions(None, None);
+                        queue.update(|pending| pending.replace(definitions));
+                    }
+                    None => break,
+                }
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Client missed {} messages, resending definitions", skipped);
+                let definitions = state.lock().await.definitions(None, None);
+                queue.update(|pending| pending.replace(definitions));
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+    queue.update(|pending| pending.closed = true);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_resync_after_lag() {
+        let definition = MessageType::from_str(
+            r#"<defNumberVector device="Mount" name="EQUATORIAL_EOD_COORD" state="Busy" perm="rw"><defNumber name="RA" format="%010.6m" min="0" max="24" step="0">3</defNumber></defNumberVector>"#,
+        )
+        .unwrap();
+        let mut state = ServerState::new();
+        state
+            .devices
+            .entry("Mount".to_string())
+            .or_default()
+            .insert("EQUATORIAL_EOD_COORD".to_string(), definition);
+        let (outbound, receiver) = broadcast::channel(2);
+        let (_replies_tx, replies) = mpsc::unbounded_channel();
+        let mut feed = ClientFeed::new(
+            receiver,
+            replies,
+            Arc::new(Mutex::new(state)),
+            OutboundQueue::default(),
+        );
+
+        for _ in 0..3 {
+            let message = MessageType::from_str(
+                r#"<setNumberVector device="Mount" name="EQUATORIAL_EOD_COORD"><oneNumber name="RA">3</oneNumber></setNumberVector>"#,
+            )
+            .unwrap();
+            outbound.send(Arc::new(message)).unwrap();
+        }
+        // The overwritten update is replaced by the stored definition
+        assert!(matches!(
+            feed.next().await.unwrap().as_ref(),
+            MessageType::DefNumberVector(_)
+        ));
+        assert!(matches!(
+            feed.next().await.unwrap().as_ref(),
+            MessageType::SetNumberVector(_)
+        ));
+        drop(outbound);
+        assert!(feed.next().await.is_some());
+        assert!(feed.next().await.is_none());
+    }
+
+    #[test]
+    fn test_blob_shedding() {
+        let blob = |name: &str, payload: &str| {
+            Arc::new(
+                MessageType::from_str(&format!(
+                    r#"<setBLOBVector device="CCD" name="{}"><oneBLOB name="{}" size="1" format=".fits">{}</oneBLOB></setBLOBVector>"#,
+                    name, name, payload
+                ))
+                .unwrap(),
+            )
+        };
+        let names = |pending: &Pending| {
+            pending
+                .messages
+                .iter()
+                .map(|(message, _)| match message.as_ref() {
+                    MessageType::SetBlobVector(set) => set.name.clone(),
+                    _ => "other".to_string(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let limits = |policy| OutboundQueue {
+            max_blob_bytes: 8,
+            policy,
+        };
+
+        let mut pending = Pending::default();
+        let drop_oldest = limits(SlowClientPolicy::DropOldestBlob);
+        assert!(pending.push(blob("CCD1", "AAAA"), &drop_oldest));
+        assert!(pending.push(blob("CCD2", "BBBB"), &drop_oldest));
+        assert!(pending.push(blob("CCD1", "CCCC"), &drop_oldest));
+        assert_eq!(names(&pending), ["CCD2", "CCD1"]);
+        assert_eq!(pending.blob_bytes, 8);
+        // The newest BLOB is kept even if it alone exceeds the limit
+        assert!(pending.push(blob("CCD3", "DDDDDDDDDDDD"), &drop_oldest));
+        assert_eq!(names(&pending), ["CCD3"]);
+
+        let mut pending = Pending::default();
+        let coalesce = limits(SlowClientPolicy::Coalesce);
+        assert!(pending.push(blob("CCD1", "AAAA"), &coalesce));
+        assert!(pending.push(blob("CCD2", "BBBB"), &coalesce));
+        assert!(pending.push(blob("CCD1", "CCCC"), &coalesce));
+        assert_eq!(names(&pending), ["CCD2", "CCD1"]);
+
+        let mut pending = Pending::default();
+        let disconnect = limits(SlowClientPolicy::Disconnect);
+        assert!(pending.push(blob("CCD1", "AAAA"), &disconnect));
+        assert!(!pending.push(blob("CCD1", "BBBBBBBB"), &disconnect));
+    }
+
+    #[tokio::test]
+    async fn test_blob_routing() {
+        use crate::client::BlobPolicy;
+
+        let blob = MessageType::from_str(
+            r#"<setBLOBVector device="CCD" name="CCD1"><oneBLOB name="CCD1" size="1" format=".fits">AA==</oneBLOB></setBLOBVector>"#,
+        )
+        .unwrap();
+        let temperature = MessageType::from_str(
+            r#"<setNumberVector device="CCD" name="CCD_TEMPERATURE"><oneNumber name="CCD_TEMPERATURE_VALUE">-10</oneNumber></setNumberVector>"#,
+        )
+        .unwrap();
+
+        let mut state = ServerState::new();
+        assert!(!state.forwards(1, &blob));
+        assert!(state.forwards(1, &temperature));
+        state.enable_blob(1, "CCD", None, BlobPolicy::Only);
+        assert!(state.forwards(1, &blob));
+        assert!(!state.forwards(1, &temperature));
+        assert!(!state.forwards(2, &blob));
+        // The property request takes precedence over the device request
+        state.enable_blob(1, "CCD", Some("CCD1"), BlobPolicy::Never);
+        assert!(!state.forwards(1, &blob));
+        // A device request replaces earlier property requests
+        state.enable_blob(1, "CCD", None, BlobPolicy::Also);
+        assert_eq!(state.blob_policy(1, "CCD", "CCD1"), BlobPolicy::Also);
+        assert!(state.forwards(1, &temperature));
+        state.forget_client(1);
+        assert_eq!(state.blob_policy(1, "CCD", "CCD1"), BlobPolicy::Never);
+
+        let (outbound, receiver) = broadcast::channel(16);
+        let (_replies_tx, replies) = mpsc::unbounded_channel();
+        let mut feed = ClientFeed::new(
+            receiver,
+            replies,
+            Arc::new(Mutex::new(state)),
+            OutboundQueue::default(),
+        );
+        outbound.send(Arc::new(blob)).unwrap();
+        outbound.send(Arc::new(temperature)).unwrap();
+        assert!(matches!(
+            feed.next().await.unwrap().as_ref(),
+            MessageType::SetNumberVector(_)
+        ));
+    }
+}
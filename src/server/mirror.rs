@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::client::{Client, ClientEvent};
+use crate::error::Result;
+use crate::message::MessageType;
+use crate::property::timestamp::INDITimestamp;
+use crate::property::PropertyPerm;
+
+use super::process::DriverProcess;
+use super::ServerState;
+
+/// Devices and properties republished by a mirror
+///
+/// An empty selection mirrors everything the primary server defines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MirrorSelection {
+    /// Selected `(device, property)` pairs, `None` selects the whole device
+    entries: Vec<(String, Option<String>)>,
+}
+
+impl MirrorSelection {
+    /// Create an empty selection, mirroring everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add every property of `device`
+    pub fn with_device(mut self, device: impl Into<String>) -> Self {
+        self.entries.push((device.into(), None));
+        self
+    }
+
+    /// Add a single property of `device`
+    pub fn with_property(mut self, device: impl Into<String>, name: impl Into<String>) -> Self {
+        self.entries.push((device.into(), Some(name.into())));
+        self
+    }
+
+    /// Returns true if `device`/`name` is selected
+    pub fn matches(&self, device: &str, name: &str) -> bool {
+        self.entries.is_empty()
+            || self
+                .entries
+                .iter()
+                .any(|(d, n)| d == device && n.as_deref().map_or(true, |n| n == name))
+    }
+}
+
+/// Counters of a running mirror
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MirrorStats {
+    /// Messages republished to local clients
+    pub forwarded: u64,
+    /// Selected messages dropped by loop prevention
+    pub dropped: u64,
+    /// Messages missed because the mirror fell behind the primary
+    pub missed: u64,
+    /// Delay between the primary's timestamp and republishing of the last
+    /// forwarded message
+    ///
+    /// Based on wall clocks, so it is only meaningful if both hosts are
+    /// synchronized.
+    pub last_lag: Option<Duration>,
+    /// Largest lag observed so far
+    pub max_lag: Duration,
+}
+
+/// Handle to a running mirror
+#[derive(Debug)]
+pub struct MirrorHandle {
+    stats: Arc<Mutex<MirrorStats>>,
+    task: JoinHandle<()>,
+}
+
+impl MirrorHandle {
+    /// Snapshot of the mirror counters
+    pub async fn stats(&self) -> MirrorStats {
+        *self.stats.lock().await
+    }
+
+    /// Stop mirroring
+    ///
+    /// Devices already republished stay defined on the local server.
+    pub fn stop(self) {
+        self.task.abort();
+    }
+}
+
+/// One-way replication of a primary server's properties
+pub(super) struct Mirror {
+    pub(super) selection: MirrorSelection,
+    pub(super) state: Arc<Mutex<ServerState>>,
+    pub(super) drivers: Arc<Mutex<Vec<DriverProcess>>>,
+    pub(super) outbound: broadcast::Sender<Arc<MessageType>>,
+    pub(super) stats: Arc<Mutex<MirrorStats>>,
+}
+
+impl Mirror {
+    /// Subscribe to `client` and spawn the replication task
+    pub(super) async fn start(self, client: Client) -> Result<MirrorHandle> {
+        let mut events = client.subscribe();
+        if self.selection.entries.is_empty() {
+            client.get_properties(None, None).await?;
+        } else {
+            for (device, name) in &self.selection.entries {
+                client.get_properties(Some(device), name.as_deref()).await?;
+            }
+        }
+
+        let stats = self.stats.clone();
+        let task = tokio::spawn(async move {
+            let mut owned = HashSet::new();
+            loop {
+                match events.recv().await {
+                    Ok(ClientEvent::Message(message)) => {
+                        self.forward(&message, &mut owned).await;
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Mirror missed {} messages", skipped);
+                        self.stats.lock().await.missed += skipped;
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+            debug!("Mirror stopped, primary connection closed");
+        });
+        Ok(MirrorHandle { stats, task })
+    }
+
+    /// Republish one message from the primary if it is selected
+    async fn forward(&self, message: &MessageType, owned: &mut HashSet<String>) {
+        let Some((device, name, timestamp)) = mirrored_key(message) else {
+            return;
+        };
+        if !self.selection.matches(device, name) {
+            return;
+        }
+
+        // A device this mirror did not introduce is either local or arrived
+        // through another path; republishing it could feed it back to its
+        // origin, so it is left alone
+        if !owned.contains(device) {
+            let defined = self.state.lock().await.devices.contains_key(device);
+            if defined || self.driver_owns(device).await {
+                debug!(
+                    "Not mirroring {}.{}, device is defined locally",
+                    device, name
+                );
+                self.stats.lock().await.dropped += 1;
+                return;
+            }
+            if !message.kind().is_definition() {
+                return;
+            }
+            owned.insert(device.to_string());
+        }
+
+        let mut message = message.clone();
+        make_read_only(&mut message);
+        {
+            let mut state = self.state.lock().await;
+            if message.kind().is_definition() {
+                state
+                    .devices
+                    .entry(device.to_string())
+                    .or_default()
+                    .insert(name.to_string(), message.clone());
+            } else {
+                state.apply_set(&message);
+            }
+        }
+
+        let lag = timestamp
+            .and_then(|ts| ts.parse::<INDITimestamp>().ok())
+            .map(|ts| ts.elapsed());
+        {
+            let mut stats = self.stats.lock().await;
+            stats.forwarded += 1;
+            if let Some(lag) = lag {
+                stats.last_lag = Some(lag);
+                stats.max_lag = stats.max_lag.max(lag);
+            }
+        }
+        // Having no connected clients is not an error
+        let _ = self.outbound.send(Arc::new(message));
+    }
+
+    async fn driver_owns(&self, device: &str) -> bool {
+        for driver in self.drivers.lock().await.iter() {
+            if driver.devices().await.iter().any(|d| d == device) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Device, property name and timestamp of a mirrorable message
+fn mirrored_key(message: &MessageType) -> Option<(&str, &str, Option<&str>)> {
+    let kind = message.kind();
+    if !kind.is_definition() && !kind.is_set() {
+        return None;
+    }
+    Some((message.device()?, message.name()?, message.timestamp()))
+}
+
+/// Downgrade a definition so consumers of the mirror cannot write to it
+pub(super) fn make_read_only(message: &mut MessageType) {
+    match message {
+        MessageType::DefTextVector(def) => def.perm = PropertyPerm::Ro,
+        MessageType::DefNumberVector(def) => def.perm = PropertyPerm::Ro,
+        MessageType::DefSwitchVector(def) => def.perm = PropertyPerm::Ro,
+        MessageType::DefBlobVector(def) => def.perm = PropertyPerm::Ro,
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::testing::mock_server;
+    use crate::server::{Server, ServerConfig};
+
+    const REPLY: &str = r#"<defNumberVector device="Mount" name="EQUATORIAL_EOD_COORD" state="Idle" perm="rw" timestamp="2024-01-01T00:00:00Z">
+<defNumber name="RA" format="%010.6m" min="0" max="24" step="0">1</defNumber>
+</defNumberVector>
+<defTextVector device="Host" name="INFO" state="Idle" perm="ro">
+<defText name="NAME">primary</defText>
+</defTextVector>
+<setNumberVector device="Mount" name="EQUATORIAL_EOD_COORD" state="Ok">
+<oneNumber name="RA">2.5</oneNumber>
+</setNumberVector>
+"#;
+
+    #[tokio::test]
+    async fn test_mirror_republishes_read_only() {
+        let server = Server::new(ServerConfig {
+            bind_addr: "127.0.0.1:0".to_string(),
+            ..Default::default()
+        });
+        server
+            .state
+            .lock()
+            .await
+            .devices
+            .insert("Host".to_string(), Default::default());
+        let mut outbound = server.outbound.subscribe();
+
+        let config = mock_server("", "\"Mount\"", REPLY).await;
+        let client = Client::new(config).await.unwrap();
+        let selection = MirrorSelection::new()
+            .with_device("Mount")
+            .with_device("Host");
+        let handle = server.mirror(client, selection).await.unwrap();
+
+        match outbound.recv().await.unwrap().as_ref() {
+            MessageType::DefNumberVector(def) => {
+                assert_eq!(def.device, "Mount");
+                assert_eq!(def.perm, PropertyPerm::Ro);
+            }
+            message => panic!("Expected DefNumberVector, got {:?}", message),
+        }
+        assert!(matches!(
+            outbound.recv().await.unwrap().as_ref(),
+            MessageType::SetNumberVector(_)
+        ));
+
+        let stats = handle.stats().await;
+        assert_eq!(stats.forwarded, 2);
+        assert_eq!(stats.dropped, 1);
+        assert!(stats.last_lag.is_some());
+
+        let state = server.state.lock().await;
+        match &state.devices["Mount"]["EQUATORIAL_EOD_COORD"] {
+            MessageType::DefNumberVector(def) => assert_eq!(def.numbers[0].value, "2.5"),
+            message => panic!("Expected DefNumberVector, got {:?}", message),
+        }
+    }
+}
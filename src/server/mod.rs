@@ -0,0 +1,1750 @@
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, watch, Mutex};
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::debug::DebugOptions;
+use crate::driver::snoop::{covers, snooped};
+use crate::error::{Error, Result};
+use crate::message::basic;
+use crate::message::stream::Framer;
+use crate::message::{MessageKind, MessageType};
+use crate::property::timestamp::{self, TimestampPolicy};
+use crate::validation::{Rejection, Validators};
+use quick_xml::de::from_str;
+use tracing::debug;
+
+/// Per-client access control lists
+pub mod acl;
+/// Authentication of client connections
+pub mod auth;
+/// Virtual device controlling the server
+pub mod control;
+/// Device drivers running inside the server process
+pub mod driver;
+/// Routing and supervision of driver processes
+mod drivers;
+/// `indiserver` compatible control FIFO
+#[cfg(unix)]
+pub mod fifo;
+/// Fan-out of messages to client connections
+mod hub;
+/// One-way replication from a primary server
+pub mod mirror;
+/// External driver process management
+pub mod process;
+/// Recording and replay of client sessions
+pub mod recorder;
+/// Devices chained in from other INDI servers
+pub mod remote;
+/// Test helpers for INDI servers
+#[cfg(test)]
+pub(crate) mod testing;
+/// TLS termination of client connections
+#[cfg(feature = "tls")]
+mod tls;
+/// WebSocket transport of client connections
+#[cfg(feature = "ws")]
+mod ws;
+
+use crate::client::{BlobPolicy, Client};
+use acl::{Acl, ClientAccess};
+use auth::AuthConfig;
+pub use drivers::DriverHandle;
+use drivers::Drivers;
+pub use hub::{OutboundQueue, SlowClientPolicy};
+use mirror::{Mirror, MirrorHandle, MirrorSelection};
+use process::{DriverShutdown, RestartPolicy, ShutdownPolicy};
+use recorder::{Direction, Recorder};
+use remote::RemoteDriver;
+
+/// Capacity of the channel carrying messages to connected clients
+const OUTBOUND_CHANNEL_CAPACITY: usize = 1024;
+/// Bytes read from a client connection at once
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+/// Capacity of the channel reporting refused updates
+const REJECTION_CHANNEL_CAPACITY: usize = 64;
+/// Time client connections get to write their queued messages on shutdown
+const CLOSE_GRACE: Duration = Duration::from_secs(2);
+/// Interval of checking whether all clients are gone on shutdown
+const CLOSE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Server configuration
+///
+/// New options are added over time, so outside this crate the struct is
+/// created with [`ServerConfig::new`] or `Default` and then adjusted.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ServerConfig {
+    /// Server address
+    pub bind_addr: String,
+    /// Devices of other servers to re-export, see [`RemoteDriver`]
+    pub remote_drivers: Vec<RemoteDriver>,
+    /// Most clients connected at once, further connections are refused
+    pub max_clients: Option<usize>,
+    /// Longest message accepted from a client, in bytes
+    ///
+    /// Longer messages are discarded without being parsed.
+    pub max_message_size: Option<usize>,
+    /// Accept TLS connections only, requires the `tls` feature
+    pub tls: Option<TlsConfig>,
+    /// Require clients to authenticate, `None` accepts everyone
+    pub auth: Option<AuthConfig>,
+    /// Limit what clients may see and write, `None` allows everything
+    pub acl: Option<Acl>,
+    /// BLOBs each client may have queued and how slow clients are handled
+    pub outbound_queue: OutboundQueue,
+    /// Also accept WebSocket connections on this address, requires the `ws`
+    /// feature
+    pub websocket_addr: Option<String>,
+    /// Stamping of driver definitions and updates that arrive without a
+    /// timestamp, before they are stored and forwarded
+    pub timestamp_policy: TimestampPolicy,
+}
+
+impl ServerConfig {
+    /// Configuration listening on `bind_addr`, everything else at its
+    /// default
+    pub fn new(bind_addr: impl Into<String>) -> Self {
+        Self {
+            bind_addr: bind_addr.into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Certificate and private key of a TLS listener
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// PEM file with the certificate chain, leaf certificate first
+    pub cert_path: PathBuf,
+    /// PEM file with the private key
+    pub key_path: PathBuf,
+}
+
+/// Server state
+#[derive(Debug, Default)]
+pub struct ServerState {
+    /// Devices and their properties
+    pub devices: HashMap<String, HashMap<String, MessageType>>,
+    /// Last message received
+    pub last_message: Option<MessageType>,
+    /// BLOB handling requested by each client connection, by device and
+    /// optional property name
+    blob_policies: HashMap<u64, HashMap<(String, Option<String>), BlobPolicy>>,
+    /// Devices, or single properties, each driver snoops on
+    snoops: HashMap<u64, HashSet<(String, Option<String>)>>,
+    /// Connections to the servers of chained remote devices, by device
+    remotes: HashMap<String, Client>,
+    /// Updates routed to in-process drivers, by device
+    hosted: HashMap<String, mpsc::UnboundedSender<MessageType>>,
+}
+
+impl ServerState {
+    /// Create a new server state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update state with a message
+    pub fn update(&mut self, message: &MessageType) {
+        match message {
+            MessageType::GetProperties(get_props) => {
+                debug!("Got get properties for device '{:?}'", get_props.device);
+                // Handle get properties request
+            }
+            _ => {
+                debug!("Got message: {:?}", message);
+            }
+        }
+        self.last_message = Some(message.clone());
+    }
+
+    /// Stored definitions matching a `getProperties` request
+    pub fn definitions(&self, device: Option<&str>, name: Option<&str>) -> Vec<MessageType> {
+        self.devices
+            .iter()
+            .filter(|(d, _)| device.map_or(true, |device| device == d.as_str()))
+            .flat_map(|(_, properties)| properties.iter())
+            .filter(|(n, _)| name.map_or(true, |name| name == n.as_str()))
+            .map(|(_, definition)| definition.clone())
+            .collect()
+    }
+
+    /// Merge the values of a `set*Vector` into the stored definition
+    ///
+    /// Updates for undefined properties are ignored. BLOB definitions carry
+    /// no values, only their attributes change.
+    pub fn apply_set(&mut self, message: &MessageType) {
+        let (Some(device), Some(name)) = (message.device(), message.name()) else {
+            return;
+        };
+        if let Some(definition) = self.stored(device, name) {
+            definition.apply_set(message);
+        }
+    }
+
+    /// Record a client's `enableBLOB` for `device`, or one of its properties
+    ///
+    /// A request for the whole device replaces earlier requests for its
+    /// properties.
+    pub fn enable_blob(
+        &mut self,
+        client: u64,
+        device: &str,
+        name: Option<&str>,
+        policy: BlobPolicy,
+    ) {
+        let policies = self.blob_policies.entry(client).or_default();
+        if name.is_none() {
+            policies.retain(|(d, _), _| d != device);
+        }
+        policies.insert((device.to_string(), name.map(str::to_string)), policy);
+    }
+
+    /// BLOB handling requested by `client` for `device`/`name`
+    ///
+    /// A request for the property takes precedence over one for the device.
+    /// Clients that asked for nothing get [`BlobPolicy::Never`], as the INDI
+    /// protocol requires.
+    pub fn blob_policy(&self, client: u64, device: &str, name: &str) -> BlobPolicy {
+        let Some(policies) = self.blob_policies.get(&client) else {
+            return BlobPolicy::Never;
+        };
+        policies
+            .get(&(device.to_string(), Some(name.to_string())))
+            .or_else(|| policies.get(&(device.to_string(), None)))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Forget the BLOB handling of a disconnected client
+    pub fn forget_client(&mut self, client: u64) {
+        self.blob_policies.remove(&client);
+    }
+
+    /// Returns true if `message` should be forwarded to `client`
+    ///
+    /// `setBLOBVector` is only forwarded if the client enabled BLOBs for it.
+    /// A client that asked for `Only` BLOBs of a device gets no other
+    /// updates or definitions of that device, except BLOB definitions and
+    /// deletions.
+    pub fn forwards(&self, client: u64, message: &MessageType) -> bool {
+        let kind = message.kind();
+        let (Some(device), Some(name)) = (message.device(), message.name()) else {
+            return true;
+        };
+        if kind == MessageKind::SetBlobVector {
+            return self.blob_policy(client, device, name) != BlobPolicy::Never;
+        }
+        if kind == MessageKind::DefBlobVector || !(kind.is_definition() || kind.is_set()) {
+            return true;
+        }
+        self.blob_policies
+            .get(&client)
+            .and_then(|policies| policies.get(&(device.to_string(), None)))
+            != Some(&BlobPolicy::Only)
+    }
+
+    /// Subscribe `driver` to the messages of `device`, or one of its
+    /// properties, as requested by a `getProperties` from the driver
+    pub fn snoop(&mut self, driver: u64, device: &str, name: Option<&str>) {
+        self.snoops
+            .entry(driver)
+            .or_default()
+            .insert((device.to_string(), name.map(str::to_string)));
+    }
+
+    /// Drivers other than `source` snooping on the device `message` is about
+    ///
+    /// BLOBs are not forwarded to snooping drivers.
+    pub fn snoopers(&self, message: &MessageType, source: u64) -> Vec<u64> {
+        let Some(snooped) = snooped(message) else {
+            return Vec::new();
+        };
+        let mut snoopers = self
+            .snoops
+            .iter()
+            .filter(|(driver, subscriptions)| {
+                **driver != source
+                    && subscriptions
+                        .iter()
+                        .any(|(device, name)| covers((device, name.as_deref()), snooped))
+            })
+            .map(|(driver, _)| *driver)
+            .collect::<Vec<_>>();
+        snoopers.sort_unstable();
+        snoopers
+    }
+
+    /// Remove the subscriptions of a driver that went away
+    pub fn forget_snooper(&mut self, driver: u64) {
+        self.snoops.remove(&driver);
+    }
+
+    fn stored(&mut self, device: &str, name: &str) -> Option<&mut MessageType> {
+        self.devices.get_mut(device)?.get_mut(name)
+    }
+}
+
+/// Device a client's `new*Vector` is addressed to
+fn update_device(message: &MessageType) -> Option<&str> {
+    message.kind().is_new().then(|| message.device()).flatten()
+}
+
+/// Server resources shared with a client connection
+#[derive(Clone)]
+struct ClientContext {
+    state: Arc<Mutex<ServerState>>,
+    drivers: Drivers,
+    validators: Arc<RwLock<Validators>>,
+    rejections: broadcast::Sender<Rejection>,
+    debug: Arc<DebugOptions>,
+    traffic: Arc<control::Traffic>,
+    max_message_size: Option<usize>,
+    recorder: Arc<RwLock<Option<Recorder>>>,
+    auth: Option<AuthConfig>,
+    acl: Option<Acl>,
+    outbound_queue: OutboundQueue,
+    closing: watch::Receiver<bool>,
+}
+
+/// INDI server
+#[derive(Debug)]
+pub struct Server {
+    /// Server configuration
+    config: ServerConfig,
+    /// Server state
+    state: Arc<Mutex<ServerState>>,
+    /// Managed driver processes
+    drivers: Drivers,
+    /// Messages sent to every connected client
+    outbound: broadcast::Sender<Arc<MessageType>>,
+    /// Validators for updates received from clients
+    validators: Arc<RwLock<Validators>>,
+    /// Updates refused by a validator
+    rejections: broadcast::Sender<Rejection>,
+    /// Protocol debugging switches, shared by all client connections
+    debug: Arc<DebugOptions>,
+    /// Message counters of the client connections
+    traffic: Arc<control::Traffic>,
+    /// Session recording of all client connections, if enabled
+    recorder: Arc<RwLock<Option<Recorder>>>,
+    /// Set once the server shuts down
+    closing: watch::Sender<bool>,
+}
+
+impl Server {
+    /// Create new server
+    pub fn new(config: ServerConfig) -> Self {
+        let (outbound, _) = broadcast::channel(OUTBOUND_CHANNEL_CAPACITY);
+        let (rejections, _) = broadcast::channel(REJECTION_CHANNEL_CAPACITY);
+        let debug = Arc::new(DebugOptions::new());
+        let mut state = ServerState::new();
+        state
+            .devices
+            .entry(control::CONTROL_DEVICE.to_string())
+            .or_default()
+            .insert(
+                control::DEBUG.to_string(),
+                control::debug_definition(&debug),
+            );
+        Self {
+            config,
+            state: Arc::new(Mutex::new(state)),
+            drivers: Arc::new(Mutex::new(Vec::new())),
+            outbound,
+            validators: Arc::new(RwLock::new(Validators::new())),
+            rejections,
+            debug,
+            traffic: Arc::new(control::Traffic::default()),
+            recorder: Arc::new(RwLock::new(None)),
+            closing: watch::channel(false).0,
+        }
+    }
+
+    /// Protocol debugging switches of all client connections
+    ///
+    /// Clients can flip them too, through the `DEBUG` property of the
+    /// [`control::CONTROL_DEVICE`].
+    pub fn debug_options(&self) -> &DebugOptions {
+        &self.debug
+    }
+
+    /// Launch an external driver executable managed by the server
+    ///
+    /// The driver is asked for its properties, which are stored and
+    /// forwarded to connected clients along with its updates. Client
+    /// updates for its devices are written to its stdin. A driver that
+    /// exits is restarted according to [`RestartPolicy::default`].
+    pub async fn add_driver(&self, program: &str, args: &[&str]) -> Result<DriverHandle> {
+        self.add_driver_with_restart(program, args, Some(RestartPolicy::default()))
+            .await
+    }
+
+    /// Launch an external driver executable with its own restart policy
+    ///
+    /// With `None` a driver that exits is not restarted; its devices are
+    /// deleted right away. See [`Server::add_driver`].
+    pub async fn add_driver_with_restart(
+        &self,
+        program: &str,
+        args: &[&str],
+        restart: Option<RestartPolicy>,
+    ) -> Result<DriverHandle> {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        drivers::start(&self.driver_context(), program, &args, &[], restart).await
+    }
+
+    /// Run a driver implemented in Rust inside the server process
+    ///
+    /// Its properties are defined right away and served like those of an
+    /// external driver, see [`driver::IndiDriver`]. The driver runs until
+    /// its devices are deleted, e.g. by [`Server::shutdown`].
+    pub fn register_driver(&self, driver: impl driver::IndiDriver) -> JoinHandle<()> {
+        tokio::spawn(driver::host(Box::new(driver), self.driver_context()))
+    }
+
+    /// Accept `start` and `stop` driver commands on the FIFO at `path`
+    ///
+    /// The FIFO is created if it does not exist. Commands use the
+    /// `indiserver` syntax, see [`fifo::FifoCommand`]; started drivers are
+    /// restarted with the default [`RestartPolicy`]. Commands are read until
+    /// the returned task is aborted.
+    #[cfg(unix)]
+    pub fn listen_fifo(&self, path: impl AsRef<std::path::Path>) -> Result<JoinHandle<()>> {
+        let fifo = fifo::open(path.as_ref())?;
+        Ok(tokio::spawn(fifo::serve(fifo, self.driver_context())))
+    }
+
+    /// Record every frame read from or written to a client to `path`
+    ///
+    /// Replaces a recording already in progress. See [`recorder::Recorder`]
+    /// for the file format.
+    pub async fn start_recording(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let recorder = Recorder::create(path).await?;
+        if let Ok(mut current) = self.recorder.write() {
+            *current = Some(recorder);
+        }
+        Ok(())
+    }
+
+    /// Stop recording client sessions
+    pub fn stop_recording(&self) {
+        if let Ok(mut current) = self.recorder.write() {
+            *current = None;
+        }
+    }
+
+    /// Feed a recorded session to this server's clients
+    ///
+    /// The messages the server wrote to the first client of the recording
+    /// are published again with their original spacing divided by `speed`,
+    /// so `2.0` replays twice as fast. They are stored like driver messages,
+    /// so clients connecting during the replay see the devices too.
+    pub async fn replay(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        speed: f64,
+    ) -> Result<JoinHandle<()>> {
+        if !(speed > 0.0 && speed.is_finite()) {
+            return Err(Error::Message(format!("Invalid replay speed {}", speed)));
+        }
+        let frames = recorder::read_session(path).await?;
+        let peer = frames
+            .iter()
+            .find(|frame| frame.direction == Direction::Outbound)
+            .map(|frame| frame.peer.clone())
+            .ok_or_else(|| Error::Message("Recording has no outbound frames".to_string()))?;
+        Ok(tokio::spawn(recorder::replay(
+            frames,
+            peer,
+            speed,
+            self.driver_context(),
+        )))
+    }
+
+    /// Stop all managed drivers
+    ///
+    /// Drivers are shut down concurrently, each escalating from closing
+    /// stdin to `SIGTERM` to `SIGKILL` according to `policy`. Once a driver
+    /// is gone a `delProperty` is sent to connected clients for every device
+    /// it defined. Reports are returned in the order the drivers were added.
+    pub async fn shutdown_drivers(&self, policy: ShutdownPolicy) -> Vec<DriverShutdown> {
+        let drivers = std::mem::take(&mut *self.drivers.lock().await);
+        let mut tasks = JoinSet::new();
+        for (index, driver) in drivers.into_iter().enumerate() {
+            tasks.spawn(async move { (index, driver.shutdown(policy).await) });
+        }
+
+        let mut reports = Vec::new();
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok(report) => reports.push(report),
+                Err(e) => debug!("Driver shutdown task failed: {}", e),
+            }
+        }
+        reports.sort_by_key(|(index, _)| *index);
+
+        for (_, report) in &reports {
+            drivers::delete_devices(&self.state, &self.outbound, report).await;
+        }
+        reports.into_iter().map(|(_, report)| report).collect()
+    }
+
+    /// Shut the server down
+    ///
+    /// Stops accepting connections, ending [`Server::start`], and stops the
+    /// drivers as [`Server::shutdown_drivers`] does. Every device still
+    /// defined is then deleted and a site-wide `message` announces the
+    /// shutdown. Connected clients are sent everything queued for them
+    /// before their connections are closed.
+    pub async fn shutdown(&self, policy: ShutdownPolicy) -> Vec<DriverShutdown> {
+        let reports = self.shutdown_drivers(policy).await;
+        let devices = self
+            .state
+            .lock()
+            .await
+            .devices
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        drivers::remove_devices(
+            &self.state,
+            &self.outbound,
+            &devices,
+            "Server shutting down",
+        )
+        .await;
+        // Having no connected clients is not an error
+        let _ = self
+            .outbound
+            .send(Arc::new(MessageType::Message(basic::Message {
+                device: None,
+                timestamp: Some(timestamp::generate()),
+                message: Some("Server shutting down".to_string()),
+                content: String::new(),
+            })));
+
+        self.closing.send_replace(true);
+        let deadline = Instant::now() + CLOSE_GRACE;
+        while self.traffic.clients.load(Ordering::Relaxed) > 0 && Instant::now() < deadline {
+            tokio::time::sleep(CLOSE_POLL_INTERVAL).await;
+        }
+        reports
+    }
+
+    /// Publish the host environment as a virtual device
+    ///
+    /// Spawns a task that defines the `HOST_STATUS` property of `probe` and
+    /// sends connected clients a fresh sample every `interval`, until
+    /// [`Server::shutdown`]. Abort the returned task to stop publishing
+    /// earlier.
+    #[cfg(feature = "sysinfo")]
+    pub fn enable_host_probe(
+        &self,
+        mut probe: crate::host::HostProbe,
+        interval: std::time::Duration,
+    ) -> JoinHandle<()> {
+        let state = self.state.clone();
+        let outbound = self.outbound.clone();
+        let mut closing = self.closing.subscribe();
+        tokio::spawn(async move {
+            let definition = probe.definition();
+            state
+                .lock()
+                .await
+                .devices
+                .entry(probe.device().to_string())
+                .or_default()
+                .insert(crate::host::HOST_STATUS.to_string(), definition.clone());
+            // Having no connected clients is not an error
+            let _ = outbound.send(Arc::new(definition));
+
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes right away, the definition covers it
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    Ok(_) = closing.wait_for(|closing| *closing) => return,
+                }
+                let update = probe.update();
+                state.lock().await.apply_set(&update);
+                let _ = outbound.send(Arc::new(update));
+            }
+        })
+    }
+
+    /// Republish properties of a primary server to this server's clients
+    ///
+    /// Definitions and updates from `client` that match `selection` are
+    /// stored and forwarded to every connected client, with their permission
+    /// downgraded to read-only. Devices that are already defined locally,
+    /// by a driver or another mirror, are never mirrored, so two servers
+    /// mirroring each other do not loop.
+    pub async fn mirror(&self, client: Client, selection: MirrorSelection) -> Result<MirrorHandle> {
+        Mirror {
+            selection,
+            state: self.state.clone(),
+            drivers: self.drivers.clone(),
+            outbound: self.outbound.clone(),
+            stats: Default::default(),
+        }
+        .start(client)
+        .await
+    }
+
+    /// Register a validator run on client updates to `device`/`name`
+    ///
+    /// The validator receives the `new*Vector` message and returns
+    /// `Err(reason)` to refuse it. Refused updates are answered with a
+    /// `message` to the sending client and reported through
+    /// [`Server::subscribe_rejections`].
+    pub fn add_validator<F>(&self, device: &str, name: &str, validator: F)
+    where
+        F: Fn(&MessageType) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        if let Ok(mut validators) = self.validators.write() {
+            validators.add(device, name, validator);
+        }
+    }
+
+    /// Subscribe to updates refused by a validator
+    pub fn subscribe_rejections(&self) -> broadcast::Receiver<Rejection> {
+        self.rejections.subscribe()
+    }
+
+    fn driver_context(&self) -> drivers::DriverContext {
+        drivers::DriverContext {
+            drivers: self.drivers.clone(),
+            state: self.state.clone(),
+            outbound: self.outbound.clone(),
+            timestamp_policy: self.config.timestamp_policy,
+        }
+    }
+
+    /// Start server
+    ///
+    /// Also connects to the [`ServerConfig::remote_drivers`] and publishes
+    /// the statistics and status properties of the
+    /// [`control::CONTROL_DEVICE`] every [`control::STATISTICS_INTERVAL`].
+    /// Fails right away if [`ServerConfig::tls`] is set but cannot be
+    /// loaded, or the `tls` feature is disabled, and likewise for
+    /// [`ServerConfig::websocket_addr`] and the `ws` feature. Returns once
+    /// [`Server::shutdown`] is called.
+    pub async fn start(&self) -> Result<()> {
+        #[cfg(feature = "tls")]
+        let acceptor = self.config.tls.as_ref().map(tls::acceptor).transpose()?;
+        #[cfg(not(feature = "tls"))]
+        if self.config.tls.is_some() {
+            return Err(Error::Message(
+                "TLS requires the tls feature of indi-rs".to_string(),
+            ));
+        }
+        #[cfg(not(feature = "ws"))]
+        if self.config.websocket_addr.is_some() {
+            return Err(Error::Message(
+                "WebSocket requires the ws feature of indi-rs".to_string(),
+            ));
+        }
+        let listener = TcpListener::bind(&self.config.bind_addr).await?;
+        debug!("Server listening on {}", self.config.bind_addr);
+        #[cfg(feature = "ws")]
+        if let Some(addr) = &self.config.websocket_addr {
+            let listener = TcpListener::bind(addr).await?;
+            debug!("Server listening for WebSocket clients on {}", addr);
+            tokio::spawn(ws::serve(
+                listener,
+                self.outbound.clone(),
+                self.client_context(),
+                self.config.max_clients,
+            ));
+        }
+        for remote in &self.config.remote_drivers {
+            tokio::spawn(remote::chain(remote.clone(), self.driver_context()));
+        }
+        tokio::spawn(control::publish_statistics(
+            self.state.clone(),
+            self.drivers.clone(),
+            self.outbound.clone(),
+            self.traffic.clone(),
+        ));
+
+        let mut closing = self.closing.subscribe();
+        loop {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                Ok(_) = closing.wait_for(|closing| *closing) => return Ok(()),
+            };
+            match accepted {
+                Ok((socket, addr)) => {
+                    debug!("New client connection from {}", addr);
+                    let connected = self.traffic.clients.fetch_add(1, Ordering::Relaxed);
+                    if let Some(max) = self.config.max_clients {
+                        if connected >= max as u64 {
+                            self.traffic.clients.fetch_sub(1, Ordering::Relaxed);
+                            debug!("Refusing client {}, {} clients connected", addr, max);
+                            tokio::spawn(Self::refuse_client(socket, max));
+                            continue;
+                        }
+                    }
+                    let outbound = self.outbound.subscribe();
+                    let context = self.client_context();
+                    #[cfg(feature = "tls")]
+                    let acceptor = acceptor.clone();
+                    tokio::spawn(async move {
+                        let traffic = context.traffic.clone();
+                        #[cfg(feature = "tls")]
+                        let handled = match acceptor {
+                            Some(acceptor) => match acceptor.accept(socket).await {
+                                Ok(stream) => {
+                                    Self::handle_client(stream, addr, outbound, context).await
+                                }
+                                Err(e) => Err(e.into()),
+                            },
+                            None => Self::handle_client(socket, addr, outbound, context).await,
+                        };
+                        #[cfg(not(feature = "tls"))]
+                        let handled = Self::handle_client(socket, addr, outbound, context).await;
+                        if let Err(e) = handled {
+                            debug!("Error handling client: {}", e);
+                        }
+                        traffic.clients.fetch_sub(1, Ordering::Relaxed);
+                    });
+                }
+                Err(e) => {
+                    debug!("Error accepting connection: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Resources handed to a new client connection
+    fn client_context(&self) -> ClientContext {
+        ClientContext {
+            state: self.state.clone(),
+            drivers: self.drivers.clone(),
+            validators: self.validators.clone(),
+            rejections: self.rejections.clone(),
+            debug: self.debug.clone(),
+            traffic: self.traffic.clone(),
+            max_message_size: self.config.max_message_size,
+            recorder: self.recorder.clone(),
+            auth: self.config.auth.clone(),
+            acl: self.config.acl.clone(),
+            outbound_queue: self.config.outbound_queue,
+            closing: self.closing.subscribe(),
+        }
+    }
+
+    /// Message telling a client that the server is full
+    fn refusal(max: usize) -> MessageType {
+        MessageType::Message(basic::Message {
+            device: None,
+            timestamp: Some(timestamp::generate()),
+            message: Some(format!("Server is full, {} clients already connected", max)),
+            content: String::new(),
+        })
+    }
+
+    /// Tell a client that the server is full and close the connection
+    async fn refuse_client(mut socket: TcpStream, max: usize) {
+        if let Ok(xml) = Self::refusal(max).to_xml() {
+            // The client may already be gone, there is nobody to report to
+            let _ = socket.write_all(format!("{}\n", xml).as_bytes()).await;
+        }
+        let _ = socket.shutdown().await;
+    }
+
+    /// Handle client connection
+    async fn handle_client(
+        socket: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        addr: SocketAddr,
+        outbound: broadcast::Receiver<Arc<MessageType>>,
+        context: ClientContext,
+    ) -> Result<()> {
+        let ClientContext {
+            state,
+            drivers,
+            validators,
+            rejections,
+            debug,
+            traffic,
+            max_message_size,
+            recorder,
+            auth,
+            acl,
+            outbound_queue,
+            mut closing,
+        } = context;
+        let peer = addr.to_string();
+        let limit = max_message_size.map_or(u64::MAX, |max| max as u64);
+        let mut socket = BufReader::new(socket);
+        let mut identity = None;
+        if let Some(auth) = &auth {
+            match auth::authenticate(&mut socket, auth, limit).await {
+                Ok(granted) => {
+                    debug!("Client {} authenticated as {}", peer, granted);
+                    identity = Some(granted);
+                }
+                Err(reason) => {
+                    debug!("Refusing client {}: {}", peer, reason);
+                    let refusal = MessageType::Message(basic::Message {
+                        device: None,
+                        timestamp: Some(timestamp::generate()),
+                        message: Some(reason),
+                        content: String::new(),
+                    });
+                    socket
+                        .write_all(format!("{}\n", refusal.to_xml()?).as_bytes())
+                        .await?;
+                    socket.shutdown().await?;
+                    return Ok(());
+                }
+            }
+        }
+        let record = move |peer: &str, direction, xml: &str| {
+            if let Ok(recorder) = recorder.read() {
+                if let Some(recorder) = recorder.as_ref() {
+                    recorder.record(peer, direction, xml);
+                }
+            }
+        };
+        let writer_record = record.clone();
+        let access = acl.map(|acl| ClientAccess {
+            acl,
+            identity,
+            address: Some(addr.ip()),
+        });
+        let writer_access = access.clone();
+        let (reader, mut writer) = tokio::io::split(socket);
+        let writer_debug = debug.clone();
+        let writer_peer = peer.clone();
+        let writer_traffic = traffic.clone();
+        let (replies_tx, replies) = mpsc::unbounded_channel::<MessageType>();
+        let mut feed = hub::ClientFeed::new(outbound, replies, state.clone(), outbound_queue);
+        let client = feed.client();
+        let disconnected = feed.disconnected();
+        tokio::pin!(disconnected);
+        let mut writer_closing = closing.clone();
+        let writer_task = tokio::spawn(async move {
+            let mut draining = false;
+            loop {
+                let next = if draining {
+                    feed.try_next().await
+                } else {
+                    tokio::select! {
+                        next = feed.next() => next,
+                        Ok(_) = writer_closing.wait_for(|closing| *closing) => {
+                            draining = true;
+                            continue;
+                        }
+                    }
+                };
+                let Some(message) = next else {
+                    break;
+                };
+                let message = match &writer_access {
+                    Some(access) => match access.filter(message) {
+                        Some(message) => message,
+                        None => continue,
+                    },
+                    None => message,
+                };
+                let xml = match message.to_xml() {
+                    Ok(xml) => xml,
+                    Err(e) => {
+                        debug!("Failed to serialize message: {}", e);
+                        continue;
+                    }
+                };
+                writer_debug.log_xml(&writer_peer, true, &xml);
+                writer_record(&writer_peer, Direction::Outbound, &xml);
+                writer_debug.log_blobs(&message);
+                if writer.write_all(xml.as_bytes()).await.is_err()
+                    || writer.write_all(b"\n").await.is_err()
+                {
+                    break;
+                }
+                writer_traffic.sent.fetch_add(1, Ordering::Relaxed);
+            }
+            let _ = writer.shutdown().await;
+        });
+        let mut reader = reader;
+        let mut framer = Framer::new();
+        let mut chunk = vec![0u8; READ_CHUNK_SIZE];
+        let discarded = || {
+            debug!("Discarding message of more than {} bytes", limit);
+            MessageType::Message(basic::Message {
+                device: None,
+                timestamp: Some(timestamp::generate()),
+                message: Some(format!("Discarded message longer than {} bytes", limit)),
+                content: String::new(),
+            })
+        };
+
+        // Every way out of the loop ends up in the cleanup below
+        let served: Result<()> = async {
+            loop {
+                let Some(frame) = framer.next_frame() else {
+                    if framer.buffered() as u64 > limit {
+                        framer.discard();
+                        let _ = replies_tx.send(discarded());
+                    }
+                    let read = tokio::select! {
+                        read = reader.read(&mut chunk) => read,
+                        Ok(_) = closing.wait_for(|closing| *closing) => break,
+                        _ = &mut disconnected => break,
+                    };
+                    match read {
+                        Ok(0) => {
+                            debug!("Client disconnected");
+                            break;
+                        }
+                        Ok(read) => framer.push(&chunk[..read]),
+                        Err(e) => {
+                            debug!("Error reading from socket: {}", e);
+                            break;
+                        }
+                    }
+                    continue;
+                };
+                if frame.len() as u64 > limit {
+                    let _ = replies_tx.send(discarded());
+                    continue;
+                }
+                let Ok(line) = std::str::from_utf8(&frame) else {
+                    debug!("Dropping message from {} that is not UTF-8", peer);
+                    continue;
+                };
+                debug.log_xml(&peer, false, line);
+                record(&peer, Direction::Inbound, line);
+                if let Ok(message) = from_str::<MessageType>(line) {
+                    traffic.received.fetch_add(1, Ordering::Relaxed);
+                    let verdict = match validators.read() {
+                        Ok(validators) => validators.validate(&message),
+                        Err(_) => Ok(()),
+                    };
+                    if let Err(rejection) = verdict {
+                        debug!("Refused client update: {}", rejection);
+                        let _ = replies_tx.send(MessageType::Message(basic::Message {
+                            device: Some(rejection.device.clone()),
+                            timestamp: Some(timestamp::generate()),
+                            message: Some(format!("Rejected {}", rejection)),
+                            content: String::new(),
+                        }));
+                        // Having no subscribers is not an error
+                        let _ = rejections.send(rejection);
+                        continue;
+                    }
+                    if let (Some(access), Some((device, name))) =
+                        (&access, acl::write_target(&message))
+                    {
+                        if access.access(device, Some(name)) != acl::Access::ReadWrite {
+                            debug!("Refused write to {}.{} from {}", device, name, peer);
+                            let _ = replies_tx.send(MessageType::Message(basic::Message {
+                                device: Some(device.to_string()),
+                                timestamp: Some(timestamp::generate()),
+                                message: Some(format!(
+                                    "Access denied: {}.{} is not writable",
+                                    device, name
+                                )),
+                                content: String::new(),
+                            }));
+                            continue;
+                        }
+                    }
+                    match &message {
+                        MessageType::PingRequest(ping) => {
+                            let _ = replies_tx.send(MessageType::PingReply(basic::PingReply {
+                                uid: ping.uid.clone(),
+                            }));
+                            continue;
+                        }
+                        // Keepalives are between the client and the server,
+                        // drivers never see them
+                        MessageType::PingReply(_) => continue,
+                        _ => {}
+                    }
+                    let mut state = state.lock().await;
+                    if let MessageType::NewSwitchVector(update) = &message {
+                        if update.device == control::CONTROL_DEVICE && update.name == control::DEBUG
+                        {
+                            let reply = control::apply_debug(&debug, update);
+                            state.apply_set(&reply);
+                            let _ = replies_tx.send(reply);
+                            continue;
+                        }
+                    }
+                    if let MessageType::EnableBlob(enable) = &message {
+                        match enable.mode.parse() {
+                            Ok(policy) => state.enable_blob(
+                                client,
+                                &enable.device,
+                                enable.name.as_deref(),
+                                policy,
+                            ),
+                            Err(e) => debug!("Ignoring enableBLOB: {}", e),
+                        }
+                        continue;
+                    }
+                    if let MessageType::GetProperties(get) = &message {
+                        for definition in
+                            state.definitions(get.device.as_deref(), get.name.as_deref())
+                        {
+                            let _ = replies_tx.send(definition);
+                        }
+                    }
+                    let remote = update_device(&message)
+                        .and_then(|device| state.remotes.get(device).cloned());
+                    let hosted = update_device(&message)
+                        .and_then(|device| state.hosted.get(device).cloned());
+                    state.update(&message);
+                    drop(state);
+                    if let Some(remote) = remote {
+                        if let Err(e) = remote.send(&message).await {
+                            debug!("Failed to relay update to remote driver: {}", e);
+                        }
+                    }
+                    if let Some(hosted) = hosted {
+                        if hosted.send(message.clone()).is_err() {
+                            debug!("In-process driver is not running");
+                        }
+                    }
+                    if let Some(device) = update_device(&message) {
+                        let message = Arc::new(message.clone());
+                        for driver in drivers.lock().await.iter() {
+                            if driver.owns(device).await && !driver.send(message.clone()) {
+                                debug!("Driver {} is not running", driver.program());
+                            }
+                        }
+                    }
+                } else {
+                    debug!("Failed to parse XML message");
+                }
+            }
+            Ok(())
+        }
+        .await;
+        if *closing.borrow() {
+            // Let the writer send what is queued, ending with the shutdown
+            // notice, before the connection is closed
+            let abort = writer_task.abort_handle();
+            if tokio::time::timeout(CLOSE_GRACE, writer_task)
+                .await
+                .is_err()
+            {
+                abort.abort();
+            }
+        } else {
+            writer_task.abort();
+        }
+        state.lock().await.forget_client(client);
+        served
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::testing::{wait_for_server, wait_until};
+    use super::*;
+    use std::str::FromStr;
+    use std::time::Duration;
+    use tokio::io::AsyncBufReadExt;
+
+    /// Driver defining a `Fake.POWER` switch that turns on when written
+    const POWER_DRIVER: &str = r#"while read -r line; do
+  case "$line" in
+    *getProperties*) echo '<defSwitchVector device="Fake" name="POWER" state="Idle" perm="rw" rule="OneOfMany"><defSwitch name="ON">Off</defSwitch><defSwitch name="OFF">On</defSwitch></defSwitchVector>' ;;
+    *newSwitchVector*) echo '<setSwitchVector device="Fake" name="POWER" state="Ok"><oneSwitch name="ON">On</oneSwitch><oneSwitch name="OFF">Off</oneSwitch></setSwitchVector>' ;;
+  esac
+done"#;
+
+    /// Start `server` on a free local port, returning the port
+    async fn start(mut server: Server) -> u16 {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let addr = format!("127.0.0.1:{}", port);
+        server.config.bind_addr = addr.clone();
+        let traffic = server.traffic.clone();
+        tokio::spawn(async move { server.start().await });
+        wait_for_server(&addr, &traffic).await;
+        port
+    }
+
+    #[tokio::test]
+    async fn test_debug_control_device() {
+        use crate::debug::DebugFlag;
+        use crate::property::{PropertyState, SwitchState};
+
+        let server = Server::new(ServerConfig {
+            bind_addr: String::new(),
+            ..Default::default()
+        });
+        let debug = server.debug.clone();
+        let port = start(server).await;
+
+        let client = Client::builder()
+            .host("127.0.0.1")
+            .port(port)
+            .build()
+            .await
+            .unwrap();
+        let state = client
+            .set_switch_and_wait(
+                control::CONTROL_DEVICE,
+                control::DEBUG,
+                DebugFlag::RawXml.element(),
+                SwitchState::On,
+            )
+            .await
+            .unwrap();
+        assert_eq!(state, PropertyState::Ok);
+        assert!(debug.is_enabled(DebugFlag::RawXml));
+        assert!(!debug.is_enabled(DebugFlag::BlobMetadata));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drivers_deletes_devices() {
+        let server = Server::new(ServerConfig {
+            bind_addr: "127.0.0.1:0".to_string(),
+            ..Default::default()
+        });
+        let mut outbound = server.outbound.subscribe();
+        server
+            .add_driver(
+                "sh",
+                &[
+                    "-c",
+                    r#"echo '<defTextVector device="Fake" name="INFO" state="Idle" perm="ro"></defTextVector>'; cat > /dev/null"#,
+                ],
+            )
+            .await
+            .unwrap();
+        wait_until("Fake to be defined", || async {
+            server.state.lock().await.devices.contains_key("Fake")
+        })
+        .await;
+
+        let reports = server.shutdown_drivers(ShutdownPolicy::default()).await;
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].devices, ["Fake"]);
+        assert!(matches!(
+            reports[0].outcome,
+            Ok(process::ShutdownOutcome::Exited(_))
+        ));
+
+        // The definition was forwarded before the deletion
+        assert!(matches!(
+            outbound.recv().await.unwrap().as_ref(),
+            MessageType::DefTextVector(_)
+        ));
+        match outbound.recv().await.unwrap().as_ref() {
+            MessageType::DelProperty(del) => assert_eq!(del.device, "Fake"),
+            message => panic!("Expected DelProperty, got {:?}", message),
+        }
+    }
+
+    #[cfg(feature = "sysinfo")]
+    #[tokio::test]
+    async fn test_host_probe() {
+        use crate::message::MessageKind;
+
+        let server = Server::new(ServerConfig::default());
+        let mut outbound = server.outbound.subscribe();
+        let probe =
+            server.enable_host_probe(crate::host::HostProbe::default(), Duration::from_millis(10));
+
+        for expected in [MessageKind::DefNumberVector, MessageKind::SetNumberVector] {
+            let message = tokio::time::timeout(Duration::from_secs(5), outbound.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(message.name(), Some(crate::host::HOST_STATUS));
+            assert_eq!(message.kind(), expected);
+        }
+        assert!(server
+            .state
+            .lock()
+            .await
+            .devices
+            .get(crate::host::HostProbe::DEFAULT_DEVICE)
+            .is_some_and(|properties| properties.contains_key(crate::host::HOST_STATUS)));
+
+        server.shutdown(ShutdownPolicy::default()).await;
+        tokio::time::timeout(Duration::from_secs(5), probe)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let mut server = Server::new(ServerConfig::default());
+        server
+            .add_driver("sh", &["-c", POWER_DRIVER])
+            .await
+            .unwrap();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        server.config.bind_addr = format!("127.0.0.1:{}", port);
+        let server = Arc::new(server);
+        let running = tokio::spawn({
+            let server = server.clone();
+            async move { server.start().await }
+        });
+        wait_for_server(&server.config.bind_addr, &server.traffic).await;
+
+        let mut client = BufReader::new(TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        client
+            .get_mut()
+            .write_all(b"<getProperties version=\"1.7\"/>\n")
+            .await
+            .unwrap();
+        let mut line = String::new();
+        while !line.contains("device=\"Fake\"") {
+            line.clear();
+            client.read_line(&mut line).await.unwrap();
+        }
+
+        let reports = server.shutdown(ShutdownPolicy::default()).await;
+        assert_eq!(reports.len(), 1);
+        let mut received = String::new();
+        while client.read_line(&mut received).await.unwrap() > 0 {}
+        assert!(
+            received.contains("<delProperty device=\"Fake\""),
+            "{}",
+            received
+        );
+        assert!(received.contains("Server shutting down"), "{}", received);
+        tokio::time::timeout(Duration::from_secs(1), running)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_driver_routing() {
+        use crate::property::{PropertyState, SwitchState};
+
+        let server = Server::new(ServerConfig {
+            bind_addr: String::new(),
+            ..Default::default()
+        });
+        let driver = server
+            .add_driver("sh", &["-c", POWER_DRIVER])
+            .await
+            .unwrap();
+        let state = server.state.clone();
+        let port = start(server).await;
+
+        let client = Client::builder()
+            .host("127.0.0.1")
+            .port(port)
+            .build()
+            .await
+            .unwrap();
+        client.get_properties(None, None).await.unwrap();
+        crate::client::testing::wait_for_property(&client, "Fake", "POWER").await;
+        let result = client
+            .set_switch_and_wait("Fake", "POWER", "ON", SwitchState::On)
+            .await
+            .unwrap();
+        assert_eq!(result, PropertyState::Ok);
+        assert_eq!(driver.devices().await.unwrap(), ["Fake"]);
+        // The server state follows the driver's updates
+        let Some(MessageType::DefSwitchVector(def)) = state
+            .lock()
+            .await
+            .definitions(Some("Fake"), Some("POWER"))
+            .pop()
+        else {
+            panic!("POWER is not stored");
+        };
+        assert_eq!(def.state, PropertyState::Ok);
+    }
+
+    /// In-process driver with a `Rusty.POWER` switch and a `Rusty.POLLS`
+    /// counter
+    #[derive(Default)]
+    struct RustyDriver {
+        polls: u32,
+    }
+
+    #[async_trait::async_trait]
+    impl driver::IndiDriver for RustyDriver {
+        async fn define_properties(&mut self) -> Vec<MessageType> {
+            [
+                r#"<defSwitchVector device="Rusty" name="POWER" state="Idle" perm="rw" rule="OneOfMany"><defSwitch name="ON">Off</defSwitch><defSwitch name="OFF">On</defSwitch></defSwitchVector>"#,
+                r#"<defNumberVector device="Rusty" name="POLLS" state="Idle" perm="ro"><defNumber name="COUNT" format="%.0f" min="0" max="0" step="0">0</defNumber></defNumberVector>"#,
+            ]
+            .into_iter()
+            .map(|xml| MessageType::from_str(xml).unwrap())
+            .collect()
+        }
+
+        async fn on_new_switch(
+            &mut self,
+            update: crate::message::new::NewSwitchVector,
+        ) -> Vec<MessageType> {
+            let xml = format!(
+                r#"<setSwitchVector device="Rusty" name="{}" state="Ok"><oneSwitch name="ON">On</oneSwitch><oneSwitch name="OFF">Off</oneSwitch></setSwitchVector>"#,
+                update.name
+            );
+            vec![MessageType::from_str(&xml).unwrap()]
+        }
+
+        fn poll_interval(&self) -> Option<Duration> {
+            Some(Duration::from_millis(20))
+        }
+
+        async fn poll(&mut self) -> Vec<MessageType> {
+            self.polls += 1;
+            let xml = format!(
+                r#"<setNumberVector device="Rusty" name="POLLS" state="Ok"><oneNumber name="COUNT">{}</oneNumber></setNumberVector>"#,
+                self.polls
+            );
+            vec![MessageType::from_str(&xml).unwrap()]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_process_driver() {
+        use crate::property::{PropertyState, SwitchState};
+
+        let server = Server::new(ServerConfig::default());
+        let hosted = server.register_driver(RustyDriver::default());
+        let state = server.state.clone();
+        let context = server.driver_context();
+        let port = start(server).await;
+
+        let client = Client::builder()
+            .host("127.0.0.1")
+            .port(port)
+            .build()
+            .await
+            .unwrap();
+        client.get_properties(None, None).await.unwrap();
+        crate::client::testing::wait_for_property(&client, "Rusty", "POWER").await;
+        let result = client
+            .set_switch_and_wait("Rusty", "POWER", "ON", SwitchState::On)
+            .await
+            .unwrap();
+        assert_eq!(result, PropertyState::Ok);
+        let Some(MessageType::DefNumberVector(def)) = state
+            .lock()
+            .await
+            .definitions(Some("Rusty"), Some("POLLS"))
+            .pop()
+        else {
+            panic!("POLLS is not stored");
+        };
+        assert_ne!(def.numbers[0].value, "0");
+
+        // Deleting its devices stops the driver
+        drivers::remove_devices(
+            &context.state,
+            &context.outbound,
+            &["Rusty".to_string()],
+            "Done",
+        )
+        .await;
+        tokio::time::timeout(Duration::from_secs(1), hosted)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remote_driver() {
+        use crate::property::{PropertyState, SwitchState};
+
+        let upstream = Server::new(ServerConfig::default());
+        upstream
+            .add_driver("sh", &["-c", POWER_DRIVER])
+            .await
+            .unwrap();
+        let upstream_port = start(upstream).await;
+
+        let remote = format!("Fake@127.0.0.1:{}", upstream_port);
+        let server = Server::new(ServerConfig {
+            remote_drivers: vec![remote.parse().unwrap()],
+            ..Default::default()
+        });
+        let port = start(server).await;
+
+        let client = Client::builder()
+            .host("127.0.0.1")
+            .port(port)
+            .build()
+            .await
+            .unwrap();
+        client.get_properties(None, None).await.unwrap();
+        crate::client::testing::wait_for_property(&client, "Fake", "POWER").await;
+        // The update is relayed to the upstream driver and its answer back
+        let result = client
+            .set_switch_and_wait("Fake", "POWER", "ON", SwitchState::On)
+            .await
+            .unwrap();
+        assert_eq!(result, PropertyState::Ok);
+    }
+
+    #[tokio::test]
+    async fn test_connection_limits() {
+        let port = start(Server::new(ServerConfig {
+            max_clients: Some(1),
+            max_message_size: Some(64),
+            ..Default::default()
+        }))
+        .await;
+
+        let mut first = BufReader::new(TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        let mut line = String::new();
+        // Make sure the first connection is counted before the second one
+        first
+            .get_mut()
+            .write_all(b"<getProperties version=\"1.7\"/>\n")
+            .await
+            .unwrap();
+        first.read_line(&mut line).await.unwrap();
+
+        let mut second = BufReader::new(TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        line.clear();
+        second.read_line(&mut line).await.unwrap();
+        assert!(line.contains("Server is full"), "{}", line);
+        // Stamped like indiserver does, in UTC without a zone suffix
+        let Ok(MessageType::Message(refusal)) = MessageType::from_str(line.trim()) else {
+            panic!("Not a message: {}", line);
+        };
+        let stamp = refusal.timestamp.unwrap();
+        assert!(!stamp.ends_with('Z') && !stamp.contains('+'), "{}", stamp);
+        assert_eq!(
+            stamp
+                .parse::<timestamp::INDITimestamp>()
+                .unwrap()
+                .to_string(),
+            stamp
+        );
+        line.clear();
+        assert_eq!(second.read_line(&mut line).await.unwrap(), 0);
+
+        let oversized = format!("<getProperties device=\"{}\"/>\n", "x".repeat(200));
+        first
+            .get_mut()
+            .write_all(oversized.as_bytes())
+            .await
+            .unwrap();
+        loop {
+            line.clear();
+            first.read_line(&mut line).await.unwrap();
+            if line.contains("Discarded message longer than 64 bytes") {
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        let port = start(Server::new(ServerConfig::default())).await;
+
+        let mut client = BufReader::new(TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        client
+            .get_mut()
+            .write_all(b"<pingRequest uid=\"42\"/>\n")
+            .await
+            .unwrap();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            client.read_line(&mut line).await.unwrap();
+            if let Ok(MessageType::PingReply(reply)) = MessageType::from_str(line.trim()) {
+                assert_eq!(reply.uid, "42");
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_utf8() {
+        let server = Server::new(ServerConfig::default());
+        let state = server.state.clone();
+        let port = start(server).await;
+
+        let mut client = BufReader::new(TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        client
+            .get_mut()
+            .write_all(b"<enableBLOB device=\"CCD\">Also</enableBLOB>\n")
+            .await
+            .unwrap();
+        wait_until("BLOBs to be enabled", || async {
+            !state.lock().await.blob_policies.is_empty()
+        })
+        .await;
+
+        // Frames that are not UTF-8 are skipped, the connection is served on
+        client
+            .get_mut()
+            .write_all(b"<getProperties version=\"1.7\"/>\xff\xfe\n")
+            .await
+            .unwrap();
+        client
+            .get_mut()
+            .write_all(b"<getProperties version=\"1.7\" device=\"\xff\xfe\"/>\n")
+            .await
+            .unwrap();
+        client
+            .get_mut()
+            .write_all(b"<pingRequest uid=\"after\"/>\n")
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            let mut line = String::new();
+            while !line.contains("pingReply") {
+                line.clear();
+                assert!(client.read_line(&mut line).await.unwrap() > 0);
+            }
+        })
+        .await
+        .unwrap();
+
+        drop(client);
+        wait_until("the client to be forgotten", || async {
+            state.lock().await.blob_policies.is_empty()
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let path = std::env::temp_dir().join(format!("indi-replay-{}", std::process::id()));
+        let server = Server::new(ServerConfig::default());
+        server
+            .add_driver("sh", &["-c", POWER_DRIVER])
+            .await
+            .unwrap();
+        server.start_recording(&path).await.unwrap();
+        let recorder = server.recorder.clone();
+        let port = start(server).await;
+
+        let client = Client::builder()
+            .host("127.0.0.1")
+            .port(port)
+            .build()
+            .await
+            .unwrap();
+        client.get_properties(None, None).await.unwrap();
+        crate::client::testing::wait_for_property(&client, "Fake", "POWER").await;
+        *recorder.write().unwrap() = None;
+        wait_until("the session to be written", || async {
+            recorder::read_session(&path)
+                .await
+                .is_ok_and(|frames| frames.iter().any(|frame| frame.xml.contains("POWER")))
+        })
+        .await;
+
+        let replayed = Server::new(ServerConfig::default());
+        let mut outbound = replayed.outbound.subscribe();
+        replayed.replay(&path, 100.0).await.unwrap().await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(replayed.state.lock().await.devices.contains_key("Fake"));
+        assert!(outbound.try_recv().is_ok());
+        assert!(replayed.replay(&path, 0.0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_authentication() {
+        use crate::client::Credential;
+
+        let port = start(Server::new(ServerConfig {
+            auth: Some(auth::AuthConfig::new().allow("observer", Credential::token("secret"))),
+            ..Default::default()
+        }))
+        .await;
+
+        let client = Client::builder()
+            .host("127.0.0.1")
+            .port(port)
+            .credential(Credential::token("secret"))
+            .build()
+            .await
+            .unwrap();
+        client.get_properties(None, None).await.unwrap();
+        crate::client::testing::wait_for_property(&client, control::CONTROL_DEVICE, control::DEBUG)
+            .await;
+
+        let mut refused = BufReader::new(TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        refused
+            .get_mut()
+            .write_all(b"<getProperties version=\"1.7\"/>\n")
+            .await
+            .unwrap();
+        let mut line = String::new();
+        refused.read_line(&mut line).await.unwrap();
+        assert!(line.contains("Expected an auth preamble"), "{}", line);
+        line.clear();
+        assert_eq!(refused.read_line(&mut line).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_access_control() {
+        use acl::{Access, Principal};
+
+        let server = Server::new(ServerConfig {
+            acl: Some(
+                Acl::new()
+                    .rule(Principal::Any, Some("Fake"), None, Access::ReadOnly)
+                    .rule(
+                        Principal::Any,
+                        Some(control::CONTROL_DEVICE),
+                        None,
+                        Access::Hidden,
+                    ),
+            ),
+            ..Default::default()
+        });
+        server
+            .add_driver("sh", &["-c", POWER_DRIVER])
+            .await
+            .unwrap();
+        wait_until("Fake to be defined", || async {
+            server.state.lock().await.devices.contains_key("Fake")
+        })
+        .await;
+        let port = start(server).await;
+
+        let mut client = BufReader::new(TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+        client
+            .get_mut()
+            .write_all(b"<getProperties version=\"1.7\"/>\n")
+            .await
+            .unwrap();
+        let mut line = String::new();
+        client.read_line(&mut line).await.unwrap();
+        // The control device is hidden, so the only definition is POWER
+        assert!(line.contains(r#"name="POWER""#), "{}", line);
+        assert!(line.contains(r#"perm="ro""#), "{}", line);
+
+        client
+            .get_mut()
+            .write_all(b"<newSwitchVector device=\"Fake\" name=\"POWER\"><oneSwitch name=\"ON\">On</oneSwitch></newSwitchVector>\n")
+            .await
+            .unwrap();
+        line.clear();
+        client.read_line(&mut line).await.unwrap();
+        assert!(line.contains("Access denied: Fake.POWER"), "{}", line);
+    }
+
+    #[tokio::test]
+    async fn test_snooping() {
+        let server = Server::new(ServerConfig {
+            bind_addr: String::new(),
+            ..Default::default()
+        });
+        let mut outbound = server.outbound.subscribe();
+        // The guider snoops on the mount and reports each coordinate update
+        server
+            .add_driver(
+                "sh",
+                &[
+                    "-c",
+                    r#"echo '<getProperties version="1.7" device="Mount" name="EQUATORIAL_EOD_COORD"/>'
+while read -r line; do
+  case "$line" in
+    *setNumberVector*) echo '<message device="Guider" message="snooped"/>' ;;
+  esac
+done"#,
+                ],
+            )
+            .await
+            .unwrap();
+        wait_until("the guider to snoop", || async {
+            !server.state.lock().await.snoops.is_empty()
+        })
+        .await;
+        server
+            .add_driver(
+                "sh",
+                &[
+                    "-c",
+                    r#"echo '<defNumberVector device="Mount" name="EQUATORIAL_EOD_COORD" state="Idle" perm="rw"><defNumber name="RA" format="%f" min="0" max="24" step="0">1</defNumber></defNumberVector>'
+echo '<defNumberVector device="Mount" name="TARGET_EOD_COORD" state="Idle" perm="rw"><defNumber name="RA" format="%f" min="0" max="24" step="0">1</defNumber></defNumberVector>'
+echo '<setNumberVector device="Mount" name="TARGET_EOD_COORD"><oneNumber name="RA">2</oneNumber></setNumberVector>'
+echo '<setNumberVector device="Mount" name="EQUATORIAL_EOD_COORD"><oneNumber name="RA">2</oneNumber></setNumberVector>'
+cat > /dev/null"#,
+                ],
+            )
+            .await
+            .unwrap();
+
+        let mut snooped = 0;
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(500);
+        while let Ok(Ok(message)) = tokio::time::timeout_at(deadline, outbound.recv()).await {
+            if let MessageType::Message(message) = message.as_ref() {
+                assert_eq!(message.device.as_deref(), Some("Guider"));
+                snooped += 1;
+            }
+        }
+        // Only the subscribed property reaches the guider
+        assert_eq!(snooped, 1);
+    }
+
+    #[tokio::test]
+    async fn test_driver_restart() {
+        let server = Server::new(ServerConfig {
+            bind_addr: String::new(),
+            ..Default::default()
+        });
+        let mut outbound = server.outbound.subscribe();
+        let policy = RestartPolicy {
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+            max_restarts: Some(2),
+        };
+        let driver = server
+            .add_driver_with_restart(
+                "sh",
+                &[
+                    "-c",
+                    r#"echo '<defTextVector device="Crashy" name="INFO" state="Idle" perm="ro"></defTextVector>'; exit 1"#,
+                ],
+                Some(policy),
+            )
+            .await
+            .unwrap();
+
+        // Defined once per start, then deleted when given up on
+        let mut definitions = 0;
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), outbound.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            match message.as_ref() {
+                MessageType::DefTextVector(_) => definitions += 1,
+                MessageType::DelProperty(del) => {
+                    assert_eq!(del.device, "Crashy");
+                    break;
+                }
+                _ => (),
+            }
+        }
+        assert_eq!(definitions, 3);
+        assert!(!driver.is_managed().await);
+        assert!(server.drivers.lock().await.is_empty());
+    }
+}
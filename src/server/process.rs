@@ -0,0 +1,368 @@
+use std::collections::BTreeSet;
+use std::process::{ExitStatus, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, Mutex, Notify};
+use tokio::time::{timeout, Instant};
+use tracing::debug;
+
+use crate::error::Result;
+use crate::message::basic::GetProperties;
+use crate::message::stream::Framer;
+use crate::message::MessageType;
+use crate::PROTOCOL_VERSION;
+
+/// Timeouts for the escalating driver shutdown sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownPolicy {
+    /// Time the driver gets to exit on its own after stdin is closed
+    pub stdin_timeout: Duration,
+    /// Time the driver gets to exit after `SIGTERM` before it is killed
+    pub term_timeout: Duration,
+}
+
+impl Default for ShutdownPolicy {
+    fn default() -> Self {
+        Self {
+            stdin_timeout: Duration::from_secs(2),
+            term_timeout: Duration::from_secs(3),
+        }
+    }
+}
+
+/// How a driver process ended during shutdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownOutcome {
+    /// The driver exited after its stdin was closed
+    Exited(ExitStatus),
+    /// The driver exited after `SIGTERM`
+    Terminated(ExitStatus),
+    /// The driver had to be killed
+    Killed(ExitStatus),
+}
+
+/// Result of shutting down one driver process
+#[derive(Debug)]
+pub struct DriverShutdown {
+    /// Driver executable
+    pub program: String,
+    /// Devices the driver had defined
+    pub devices: Vec<String>,
+    /// How the process ended, or why waiting for it failed
+    pub outcome: Result<ShutdownOutcome>,
+}
+
+/// Restart of crashed drivers with exponential backoff
+///
+/// After a driver exits on its own it is started again after
+/// `initial_delay`, doubling the wait after every further crash up to
+/// `max_delay`. A driver that stayed up for longer than `max_delay` starts
+/// over at `initial_delay`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Wait before the first restart
+    pub initial_delay: Duration,
+    /// Longest wait between restarts
+    pub max_delay: Duration,
+    /// Restarts before giving up, `None` restarts forever
+    pub max_restarts: Option<u32>,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            max_restarts: Some(10),
+        }
+    }
+}
+
+/// Source of [`DriverProcess::id`]s
+static NEXT_DRIVER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// External INDI driver process managed by the server
+///
+/// The driver's stdout is parsed for definitions so the server knows which
+/// devices to delete when the driver goes away. Messages sent with
+/// [`send`](Self::send) are written to its stdin.
+#[derive(Debug)]
+pub struct DriverProcess {
+    id: u64,
+    program: String,
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    child: Child,
+    input: Option<mpsc::UnboundedSender<Arc<MessageType>>>,
+    output: Option<mpsc::UnboundedSender<MessageType>>,
+    devices: Arc<Mutex<BTreeSet<String>>>,
+    messages: Arc<AtomicU64>,
+    exited: Arc<Notify>,
+    started: Instant,
+    restarts: u32,
+}
+
+impl DriverProcess {
+    /// Spawn a driver executable
+    pub fn spawn(program: &str, args: &[&str]) -> Result<Self> {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        Self::launch(program, &args, &[], None)
+    }
+
+    /// Spawn a driver executable with extra environment variables, passing
+    /// the messages it sends to `output`
+    pub(crate) fn launch(
+        program: &str,
+        args: &[String],
+        env: &[(String, String)],
+        output: Option<mpsc::UnboundedSender<MessageType>>,
+    ) -> Result<Self> {
+        let mut driver = Self {
+            id: NEXT_DRIVER_ID.fetch_add(1, Ordering::Relaxed),
+            program: program.to_string(),
+            args: args.to_vec(),
+            env: env.to_vec(),
+            child: start_child(program, args, env)?,
+            input: None,
+            output,
+            devices: Arc::new(Mutex::new(BTreeSet::new())),
+            messages: Arc::new(AtomicU64::new(0)),
+            exited: Arc::new(Notify::new()),
+            started: Instant::now(),
+            restarts: 0,
+        };
+        driver.attach();
+        Ok(driver)
+    }
+
+    /// Start the driver again after it exited
+    pub(crate) fn respawn(&mut self) -> Result<()> {
+        // Failed starts count too, so a missing executable is given up on
+        self.restarts += 1;
+        self.child = start_child(&self.program, &self.args, &self.env)?;
+        self.started = Instant::now();
+        self.attach();
+        Ok(())
+    }
+
+    /// Connect the pipes of a freshly spawned child and ask the driver for
+    /// its properties
+    fn attach(&mut self) {
+        debug!(
+            "Spawned driver {} (pid {:?})",
+            self.program,
+            self.child.id()
+        );
+        if let Some(mut stdin) = self.child.stdin.take() {
+            let (input, mut messages) = mpsc::unbounded_channel::<Arc<MessageType>>();
+            let program = self.program.clone();
+            tokio::spawn(async move {
+                // Ends when the input sender is dropped, closing stdin
+                while let Some(message) = messages.recv().await {
+                    let xml = match message.to_xml() {
+                        Ok(xml) => xml,
+                        Err(e) => {
+                            debug!("Failed to serialize message for {}: {}", program, e);
+                            continue;
+                        }
+                    };
+                    if stdin.write_all(xml.as_bytes()).await.is_err()
+                        || stdin.write_all(b"\n").await.is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+            self.input = Some(input);
+        }
+        if let Some(mut stdout) = self.child.stdout.take() {
+            let devices = self.devices.clone();
+            let messages = self.messages.clone();
+            let output = self.output.clone();
+            let exited = self.exited.clone();
+            let program = self.program.clone();
+            tokio::spawn(async move {
+                let mut framer = Framer::new();
+                let mut chunk = vec![0u8; 64 * 1024];
+                while let Ok(n) = stdout.read(&mut chunk).await {
+                    if n == 0 {
+                        break;
+                    }
+                    framer.push(&chunk[..n]);
+                    while let Some(frame) = framer.next_frame() {
+                        messages.fetch_add(1, Ordering::Relaxed);
+                        let frame = String::from_utf8_lossy(&frame);
+                        let Ok(message) = MessageType::from_str(frame.trim()) else {
+                            debug!("Driver {} sent unparsable message", program);
+                            continue;
+                        };
+                        if let Some(device) =
+                            message.device().filter(|_| message.kind().is_definition())
+                        {
+                            devices.lock().await.insert(device.to_string());
+                        }
+                        if let Some(output) = &output {
+                            let _ = output.send(message);
+                        }
+                    }
+                }
+                debug!("Driver {} closed stdout", program);
+                exited.notify_one();
+            });
+        }
+        self.send(Arc::new(MessageType::GetProperties(GetProperties {
+            version: PROTOCOL_VERSION.to_string(),
+            device: None,
+            name: None,
+        })));
+    }
+
+    /// Identifier of the driver, unique within the process
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Driver executable
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Value of environment variable `name` set for the driver by the server
+    pub fn env(&self, name: &str) -> Option<&str> {
+        self.env
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Operating system process id, `None` once the driver has exited
+    pub fn pid(&self) -> Option<u32> {
+        self.child.id()
+    }
+
+    /// Number of times the driver was restarted after exiting
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    /// Devices defined by the driver so far
+    pub async fn devices(&self) -> Vec<String> {
+        self.devices.lock().await.iter().cloned().collect()
+    }
+
+    /// Returns true if the driver defined `device`
+    pub async fn owns(&self, device: &str) -> bool {
+        self.devices.lock().await.contains(device)
+    }
+
+    /// Number of messages the driver has sent so far
+    pub fn messages(&self) -> u64 {
+        self.messages.load(Ordering::Relaxed)
+    }
+
+    /// Queue `message` for the driver's stdin
+    ///
+    /// Returns false if the driver's stdin is closed.
+    pub fn send(&self, message: Arc<MessageType>) -> bool {
+        self.input
+            .as_ref()
+            .is_some_and(|input| input.send(message).is_ok())
+    }
+
+    /// Notified when the driver closes its stdout, usually by exiting
+    pub(crate) fn exited(&self) -> Arc<Notify> {
+        self.exited.clone()
+    }
+
+    /// Time since the driver was last started
+    pub(crate) fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Wait for the driver to exit, killing it if it does not within `grace`
+    pub(crate) async fn reap(&mut self, grace: Duration) -> Result<ExitStatus> {
+        self.input = None;
+        if let Ok(status) = timeout(grace, self.child.wait()).await {
+            return Ok(status?);
+        }
+        self.child.start_kill()?;
+        Ok(self.child.wait().await?)
+    }
+
+    /// Stop the driver, escalating from closing stdin to `SIGTERM` to `SIGKILL`
+    pub async fn shutdown(mut self, policy: ShutdownPolicy) -> DriverShutdown {
+        let devices = self.devices().await;
+        let outcome = self.terminate(policy).await;
+        debug!("Driver {} shut down: {:?}", self.program, outcome);
+        DriverShutdown {
+            program: self.program,
+            devices,
+            outcome,
+        }
+    }
+
+    async fn terminate(&mut self, policy: ShutdownPolicy) -> Result<ShutdownOutcome> {
+        // Closing stdin is the orderly way to ask an INDI driver to exit
+        self.input = None;
+        if let Ok(status) = timeout(policy.stdin_timeout, self.child.wait()).await {
+            return Ok(ShutdownOutcome::Exited(status?));
+        }
+
+        #[cfg(unix)]
+        if let Some(pid) = self.child.id() {
+            // SAFETY: kill(2) has no memory safety requirements, `pid` is our own child
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGTERM);
+            }
+            if let Ok(status) = timeout(policy.term_timeout, self.child.wait()).await {
+                return Ok(ShutdownOutcome::Terminated(status?));
+            }
+        }
+
+        self.child.start_kill()?;
+        Ok(ShutdownOutcome::Killed(self.child.wait().await?))
+    }
+}
+
+/// Start a driver executable with piped stdin and stdout
+fn start_child(program: &str, args: &[String], env: &[(String, String)]) -> Result<Child> {
+    Ok(Command::new(program)
+        .args(args)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    const POLICY: ShutdownPolicy = ShutdownPolicy {
+        stdin_timeout: Duration::from_millis(200),
+        term_timeout: Duration::from_millis(200),
+    };
+
+    #[tokio::test]
+    async fn test_shutdown_escalation() {
+        let driver = DriverProcess::spawn("cat", &[]).unwrap();
+        let report = driver.shutdown(POLICY).await;
+        assert!(matches!(report.outcome, Ok(ShutdownOutcome::Exited(_))));
+
+        let driver = DriverProcess::spawn("sleep", &["30"]).unwrap();
+        let report = driver.shutdown(POLICY).await;
+        assert!(matches!(report.outcome, Ok(ShutdownOutcome::Terminated(_))));
+
+        let driver =
+            DriverProcess::spawn("sh", &["-c", "trap '' TERM; while :; do sleep 0.05; done"])
+                .unwrap();
+        let report = driver.shutdown(POLICY).await;
+        assert!(matches!(report.outcome, Ok(ShutdownOutcome::Killed(_))));
+    }
+}
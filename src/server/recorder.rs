@@ -0,0 +1,196 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::error::{Error, Result};
+use crate::message::MessageType;
+
+use super::drivers::{self, DriverContext};
+
+/// Direction of a recorded frame, as seen from the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Received from a client
+    Inbound,
+    /// Written to a client
+    Outbound,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Inbound => "in",
+            Self::Outbound => "out",
+        }
+    }
+}
+
+/// XML frame of a recorded session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Time since the recording started
+    pub offset: Duration,
+    /// Whether the frame was received or sent
+    pub direction: Direction,
+    /// Address of the client connection
+    pub peer: String,
+    /// The frame as it was read or written
+    pub xml: String,
+}
+
+/// Writes the frames of all client connections to a session file
+///
+/// Each frame is stored as a header line with the offset in milliseconds,
+/// the direction, the peer and the XML length in bytes, followed by the
+/// XML itself and a newline. See [`read_session`].
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    frames: mpsc::UnboundedSender<Frame>,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Create the session file at `path`, replacing an existing one
+    pub async fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::create(path).await?;
+        let (frames, received) = mpsc::unbounded_channel();
+        tokio::spawn(write_frames(BufWriter::new(file), received));
+        Ok(Self {
+            frames,
+            started: Instant::now(),
+        })
+    }
+
+    /// Record a frame read from or written to `peer`
+    pub fn record(&self, peer: &str, direction: Direction, xml: &str) {
+        // A failed writer has already reported its error
+        let _ = self.frames.send(Frame {
+            offset: self.started.elapsed(),
+            direction,
+            peer: peer.to_string(),
+            xml: xml.trim_end().to_string(),
+        });
+    }
+}
+
+/// Append frames to `file` until every [`Recorder`] clone is dropped
+async fn write_frames(mut file: BufWriter<File>, mut frames: mpsc::UnboundedReceiver<Frame>) {
+    while let Some(frame) = frames.recv().await {
+        let header = format!(
+            "{} {} {} {}\n",
+            frame.offset.as_millis(),
+            frame.direction.as_str(),
+            frame.peer,
+            frame.xml.len()
+        );
+        let written = async {
+            file.write_all(header.as_bytes()).await?;
+            file.write_all(frame.xml.as_bytes()).await?;
+            file.write_all(b"\n").await?;
+            // Flush once the queue is drained, not for every frame
+            if frames.is_empty() {
+                file.flush().await?;
+            }
+            std::io::Result::Ok(())
+        };
+        if let Err(e) = written.await {
+            warn!("Failed to record session: {}", e);
+            return;
+        }
+    }
+    if let Err(e) = file.flush().await {
+        warn!("Failed to record session: {}", e);
+    }
+}
+
+/// Read the frames of a session file written by a [`Recorder`]
+pub async fn read_session(path: impl AsRef<Path>) -> Result<Vec<Frame>> {
+    let data = tokio::fs::read_to_string(path).await?;
+    let invalid = |reason: &str| Error::ParseError(format!("Invalid session file: {}", reason));
+    let mut frames = Vec::new();
+    let mut rest = data.as_str();
+    while !rest.is_empty() {
+        let (header, body) = rest
+            .split_once('\n')
+            .ok_or_else(|| invalid("truncated header"))?;
+        let fields = header.split(' ').collect::<Vec<_>>();
+        let [offset, direction, peer, len] = fields[..] else {
+            return Err(invalid(header));
+        };
+        let len = usize::from_str(len).map_err(|_| invalid(header))?;
+        let xml = body.get(..len).ok_or_else(|| invalid("truncated frame"))?;
+        frames.push(Frame {
+            offset: Duration::from_millis(u64::from_str(offset).map_err(|_| invalid(header))?),
+            direction: match direction {
+                "in" => Direction::Inbound,
+                "out" => Direction::Outbound,
+                _ => return Err(invalid(header)),
+            },
+            peer: peer.to_string(),
+            xml: xml.to_string(),
+        });
+        rest = body[len..].strip_prefix('\n').unwrap_or(&body[len..]);
+    }
+    Ok(frames)
+}
+
+/// Publish the frames written to `peer` again, `speed` times faster than
+/// recorded
+pub(crate) async fn replay(frames: Vec<Frame>, peer: String, speed: f64, context: DriverContext) {
+    let started = Instant::now();
+    for frame in frames {
+        if frame.direction != Direction::Outbound || frame.peer != peer {
+            continue;
+        }
+        tokio::time::sleep_until(started + frame.offset.div_f64(speed)).await;
+        match MessageType::from_str(&frame.xml) {
+            Ok(message) => drivers::publish(&context, drivers::NO_DRIVER, message).await,
+            Err(e) => debug!("Skipping recorded frame: {}", e),
+        }
+    }
+    debug!("Replay of {} finished", peer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::testing::wait_until;
+
+    #[tokio::test]
+    async fn test_session_round_trip() {
+        let path = std::env::temp_dir().join(format!("indi-session-{}", std::process::id()));
+        let recorder = Recorder::create(&path).await.unwrap();
+        recorder.record(
+            "10.0.0.2:5000",
+            Direction::Inbound,
+            "<getProperties version=\"1.7\"/>\n",
+        );
+        recorder.record(
+            "10.0.0.2:5000",
+            Direction::Outbound,
+            "<message message=\"two\nlines\"/>",
+        );
+        drop(recorder);
+        wait_until("the session to be written", || async {
+            read_session(&path)
+                .await
+                .is_ok_and(|frames| frames.len() == 2)
+        })
+        .await;
+
+        let frames = read_session(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].direction, Direction::Inbound);
+        assert_eq!(frames[0].xml, "<getProperties version=\"1.7\"/>");
+        assert_eq!(frames[1].peer, "10.0.0.2:5000");
+        assert_eq!(frames[1].xml, "<message message=\"two\nlines\"/>");
+        assert!(frames[0].offset <= frames[1].offset);
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+
+use crate::client::{Client, ClientConfig, ClientEvent};
+use crate::error::{Error, Result};
+use crate::message::{MessageKind, MessageType};
+
+use super::drivers::{self, DriverContext};
+
+/// Wait before connecting to a remote server again
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Device of another INDI server chained into this one
+///
+/// The server connects to the remote server as a client, re-exports the
+/// device to its own clients and relays their `new*Vector` messages back.
+/// Parsed from `indiserver` syntax, `device@host:port`; the port defaults to
+/// [`ClientConfig::DEFAULT_PORT`] and an empty device chains every device of
+/// the remote server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteDriver {
+    /// Chained device, `None` for all devices of the remote server
+    pub device: Option<String>,
+    /// Remote server host
+    pub host: String,
+    /// Remote server port
+    pub port: u16,
+}
+
+impl RemoteDriver {
+    /// Returns true if messages about `device` are relayed
+    fn chains(&self, device: &str) -> bool {
+        self.device
+            .as_deref()
+            .map_or(true, |chained| chained == device)
+    }
+}
+
+impl fmt::Display for RemoteDriver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}@{}:{}",
+            self.device.as_deref().unwrap_or_default(),
+            self.host,
+            self.port
+        )
+    }
+}
+
+impl FromStr for RemoteDriver {
+    type Err = Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let invalid = || Error::ParseError(format!("Invalid remote driver: {}", spec));
+        let (device, address) = spec.rsplit_once('@').ok_or_else(invalid)?;
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (address, ClientConfig::DEFAULT_PORT),
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            device: (!device.is_empty()).then(|| device.to_string()),
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+/// Relay `remote` into the local server, reconnecting whenever the
+/// connection fails or is lost
+pub(crate) async fn chain(remote: RemoteDriver, context: DriverContext) {
+    loop {
+        match Client::new(ClientConfig::new(&remote.host, remote.port)).await {
+            Ok(client) => relay(&remote, client, &context).await,
+            Err(e) => warn!("Failed to connect to remote driver {}: {}", remote, e),
+        }
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+}
+
+/// Relay the messages of one connection until it is lost, then delete the
+/// relayed devices
+async fn relay(remote: &RemoteDriver, client: Client, context: &DriverContext) {
+    let mut events = client.subscribe();
+    if let Err(e) = client.get_properties(remote.device.as_deref(), None).await {
+        warn!("Failed to query remote driver {}: {}", remote, e);
+        return;
+    }
+    debug!("Chained remote driver {}", remote);
+
+    let mut relayed = HashSet::new();
+    loop {
+        let message = match events.recv().await {
+            Ok(ClientEvent::Message(message)) => message,
+            Ok(ClientEvent::Disconnected | ClientEvent::ConnectionLost)
+            | Err(RecvError::Closed) => break,
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Remote driver {} missed {} messages", remote, skipped);
+                continue;
+            }
+        };
+        let Some(device) = device_of(&message) else {
+            continue;
+        };
+        if !remote.chains(device) {
+            continue;
+        }
+        if !relayed.contains(device) {
+            relayed.insert(device.to_string());
+            context
+                .state
+                .lock()
+                .await
+                .remotes
+                .insert(device.to_string(), client.clone());
+            if let Err(e) = client.enable_blob(device, None, "Also").await {
+                debug!("Failed to enable BLOBs of {}: {}", device, e);
+            }
+        }
+        drivers::publish(context, drivers::NO_DRIVER, message.as_ref().clone()).await;
+    }
+
+    warn!("Lost connection to remote driver {}", remote);
+    let devices = relayed.into_iter().collect::<Vec<_>>();
+    {
+        let mut state = context.state.lock().await;
+        for device in &devices {
+            state.remotes.remove(device);
+        }
+    }
+    let reason = format!("Remote driver {} disconnected", remote);
+    drivers::remove_devices(&context.state, &context.outbound, &devices, &reason).await;
+}
+
+/// Device a message from a remote server is about
+fn device_of(message: &MessageType) -> Option<&str> {
+    let kind = message.kind();
+    if kind.is_definition()
+        || kind.is_set()
+        || kind == MessageKind::DelProperty
+        || kind == MessageKind::Message
+    {
+        message.device()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote_driver() {
+        let remote = RemoteDriver::from_str("Telescope Simulator@dome.local:7625").unwrap();
+        assert_eq!(remote.device.as_deref(), Some("Telescope Simulator"));
+        assert_eq!(remote.host, "dome.local");
+        assert_eq!(remote.port, 7625);
+        assert_eq!(remote.to_string(), "Telescope Simulator@dome.local:7625");
+
+        let remote = RemoteDriver::from_str("@dome.local").unwrap();
+        assert_eq!(remote.device, None);
+        assert_eq!(remote.port, ClientConfig::DEFAULT_PORT);
+        assert!(remote.chains("CCD Simulator"));
+
+        assert!(RemoteDriver::from_str("dome.local:7624").is_err());
+        assert!(RemoteDriver::from_str("Mount@").is_err());
+        assert!(RemoteDriver::from_str("Mount@dome.local:port").is_err());
+    }
+}
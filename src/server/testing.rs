@@ -0,0 +1,47 @@
+use super::control::Traffic;
+use std::future::Future;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// Poll `condition` until it holds, failing the test after five seconds
+pub(crate) async fn wait_until<F, Fut>(what: &str, mut condition: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    for _ in 0..500 {
+        if condition().await {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Timed out waiting for {}", what);
+}
+
+/// Wait until a server accepts connections on `addr`
+///
+/// Returns once the server has counted a silent probe connection and seen
+/// it close again, so the probe neither counts against `max_clients` nor
+/// shows up in recordings.
+pub(crate) async fn wait_for_server(addr: &str, traffic: &Traffic) {
+    let connected = || traffic.clients.load(Ordering::Relaxed);
+    let mut probe = None;
+    for _ in 0..500 {
+        probe = TcpStream::connect(addr).await.ok();
+        if probe.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(probe.is_some(), "Timed out waiting for {}", addr);
+    wait_until("the probe connection to be accepted", || async {
+        connected() > 0
+    })
+    .await;
+    drop(probe);
+    wait_until("the probe connection to close", || async {
+        connected() == 0
+    })
+    .await;
+}
@@ -0,0 +1,270 @@
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+use tracing::debug;
+
+use crate::error::{Error, Result};
+use crate::message::MessageType;
+
+use super::{ClientContext, Server};
+
+/// Size of the pipe between a WebSocket and its client handler
+const PIPE_CAPACITY: usize = 64 * 1024;
+/// Root element sent as a binary frame
+const BLOB_ROOT: &[u8] = b"setBLOBVector";
+
+/// Accept WebSocket clients on `listener` until the server shuts down
+///
+/// Every connection is served like a TCP client, one INDI message per
+/// frame. BLOBs are sent as binary frames, everything else as text.
+pub(crate) async fn serve(
+    listener: TcpListener,
+    outbound: broadcast::Sender<Arc<MessageType>>,
+    context: ClientContext,
+    max_clients: Option<usize>,
+) {
+    let mut closing = context.closing.clone();
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            Ok(_) = closing.wait_for(|closing| *closing) => return,
+        };
+        let (socket, addr) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                debug!("Error accepting WebSocket connection: {}", e);
+                continue;
+            }
+        };
+        debug!("New WebSocket connection from {}", addr);
+        let traffic = context.traffic.clone();
+        let connected = traffic.clients.fetch_add(1, Ordering::Relaxed);
+        if let Some(max) = max_clients {
+            if connected >= max as u64 {
+                traffic.clients.fetch_sub(1, Ordering::Relaxed);
+                debug!(
+                    "Refusing WebSocket client {}, {} clients connected",
+                    addr, max
+                );
+                tokio::spawn(refuse(socket, max));
+                continue;
+            }
+        }
+        let outbound = outbound.subscribe();
+        let context = context.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(socket, addr, outbound, context).await {
+                debug!("Error handling WebSocket client: {}", e);
+            }
+            traffic.clients.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+}
+
+fn websocket_error(e: tokio_tungstenite::tungstenite::Error) -> Error {
+    Error::Message(format!("WebSocket error: {}", e))
+}
+
+/// Tell a WebSocket client that the server is full and close the connection
+async fn refuse(socket: TcpStream, max: usize) {
+    let Ok(mut websocket) = tokio_tungstenite::accept_async(socket).await else {
+        return;
+    };
+    if let Ok(xml) = Server::refusal(max).to_xml() {
+        // The client may already be gone, there is nobody to report to
+        let _ = websocket.send(Message::text(xml)).await;
+    }
+    let _ = websocket.close(None).await;
+}
+
+/// Complete the handshake and serve the client through a pipe
+async fn handle(
+    socket: TcpStream,
+    addr: SocketAddr,
+    outbound: broadcast::Receiver<Arc<MessageType>>,
+    context: ClientContext,
+) -> Result<()> {
+    let websocket = tokio_tungstenite::accept_async(socket)
+        .await
+        .map_err(websocket_error)?;
+    let (pipe, client) = tokio::io::duplex(PIPE_CAPACITY);
+    let bridge = tokio::spawn(bridge(websocket, pipe));
+    let handled = Server::handle_client(client, addr, outbound, context).await;
+    // The bridge finishes once the handler has closed its end of the pipe
+    let _ = bridge.await;
+    handled
+}
+
+/// Copy frames into the pipe and messages out of it until either side ends
+async fn bridge(websocket: WebSocketStream<TcpStream>, pipe: DuplexStream) {
+    let (mut sink, mut frames) = websocket.split();
+    let (mut from_client, mut to_client) = tokio::io::split(pipe);
+
+    let inbound = async move {
+        while let Some(Ok(frame)) = frames.next().await {
+            let data = match frame {
+                Message::Text(_) | Message::Binary(_) => frame.into_data(),
+                Message::Close(_) => break,
+                _ => continue,
+            };
+            if to_client.write_all(&data).await.is_err() {
+                break;
+            }
+        }
+        let _ = to_client.shutdown().await;
+    };
+
+    let outbound = async move {
+        let mut buffer = Vec::new();
+        let mut chunk = vec![0; PIPE_CAPACITY];
+        loop {
+            while let Some(len) = message_len(&buffer) {
+                let message = buffer.drain(..len).collect::<Vec<_>>();
+                if sink.send(frame(message)).await.is_err() {
+                    return;
+                }
+            }
+            match from_client.read(&mut chunk).await {
+                Ok(0) | Err(_) => break,
+                Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+            }
+        }
+        let _ = sink.close().await;
+    };
+
+    tokio::pin!(outbound);
+    tokio::select! {
+        _ = inbound => outbound.await,
+        _ = &mut outbound => {}
+    }
+}
+
+/// WebSocket frame carrying one serialized message
+fn frame(message: Vec<u8>) -> Message {
+    let message = message.trim_ascii_end().to_vec();
+    if root(&message) == Some(BLOB_ROOT) {
+        return Message::binary(message);
+    }
+    match String::from_utf8(message) {
+        Ok(text) => Message::text(text),
+        Err(e) => Message::binary(e.into_bytes()),
+    }
+}
+
+/// Name of the root element of a serialized message
+fn root(message: &[u8]) -> Option<&[u8]> {
+    let name = message.strip_prefix(b"<")?;
+    let end = name
+        .iter()
+        .position(|b| b.is_ascii_whitespace() || *b == b'>' || *b == b'/')
+        .unwrap_or(name.len());
+    Some(&name[..end])
+}
+
+/// Length of the first complete message in `buffer`, including its newline
+///
+/// The handler ends every message with a newline, but text content may
+/// contain newlines too, so a line only ends the message once the root
+/// element is closed.
+fn message_len(buffer: &[u8]) -> Option<usize> {
+    buffer
+        .iter()
+        .enumerate()
+        .filter(|(_, b)| **b == b'\n')
+        .map(|(i, _)| i + 1)
+        .find(|len| {
+            let message = buffer[..*len].trim_ascii();
+            let Some(root) = root(message) else {
+                return true;
+            };
+            let closing = [b"</", root, b">"].concat();
+            message.ends_with(&closing)
+                || (message.ends_with(b"/>")
+                    && message.iter().position(|b| *b == b'>') == Some(message.len() - 1))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::testing::wait_for_server;
+    use crate::server::ServerConfig;
+    use std::time::Duration;
+
+    #[test]
+    fn test_message_len() {
+        let buffer = b"<defTextVector device=\"A\" name=\"B\"><defText name=\"C\">\nline\n</defText></defTextVector>\n<getProperties version=\"1.7\"/>\n<delProperty";
+        let first = message_len(buffer).unwrap();
+        assert!(buffer[..first].ends_with(b"</defTextVector>\n"));
+        let second = message_len(&buffer[first..]).unwrap();
+        assert_eq!(
+            &buffer[first..first + second],
+            b"<getProperties version=\"1.7\"/>\n"
+        );
+        assert_eq!(message_len(&buffer[first + second..]), None);
+
+        assert!(matches!(
+            frame(b"<setBLOBVector device=\"A\" name=\"B\"></setBLOBVector>\n".to_vec()),
+            Message::Binary(_)
+        ));
+        assert!(matches!(
+            frame(b"<getProperties version=\"1.7\"/>\n".to_vec()),
+            Message::Text(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_websocket_client() {
+        let port = || {
+            std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port()
+        };
+        let websocket_addr = format!("127.0.0.1:{}", port());
+        let server = Arc::new(Server::new(ServerConfig {
+            bind_addr: format!("127.0.0.1:{}", port()),
+            websocket_addr: Some(websocket_addr.clone()),
+            ..Default::default()
+        }));
+        tokio::spawn({
+            let server = server.clone();
+            async move { server.start().await }
+        });
+        wait_for_server(&websocket_addr, &server.traffic).await;
+
+        let (mut websocket, _) =
+            tokio_tungstenite::connect_async(format!("ws://{}", websocket_addr))
+                .await
+                .unwrap();
+        websocket
+            .send(Message::text(r#"<getProperties version="1.7"/>"#))
+            .await
+            .unwrap();
+        let definition = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                match websocket.next().await.unwrap().unwrap() {
+                    Message::Text(text) if text.contains("<defSwitchVector") => {
+                        return text;
+                    }
+                    _ => continue,
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert!(definition.starts_with("<defSwitchVector"));
+        assert!(definition.trim_end().ends_with("</defSwitchVector>"));
+
+        server.shutdown(Default::default()).await;
+        assert_eq!(server.traffic.clients.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,219 @@
+//! Key-value storage used by the persistence features
+//!
+//! Keys are `/` separated paths such as `cache/CCD Simulator`. The crate
+//! ships a [`FileStorage`] keeping one file per key and a [`MemoryStorage`]
+//! for tests and short-lived processes; embedders can implement [`Storage`]
+//! on top of a database such as sqlite or sled.
+
+use crate::error::{Error, Result};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// Suffix of files being written by [`FileStorage::put`]
+const TEMP_SUFFIX: &str = ".tmp";
+
+/// Asynchronous key-value store
+pub trait Storage {
+    /// Value stored under `key`, `None` if there is none
+    fn get(&self, key: &str) -> impl Future<Output = Result<Option<Vec<u8>>>> + Send;
+
+    /// Store `value` under `key`, replacing any previous value
+    fn put(&self, key: &str, value: &[u8]) -> impl Future<Output = Result<()>> + Send;
+
+    /// Remove the value under `key`, returning true if there was one
+    fn remove(&self, key: &str) -> impl Future<Output = Result<bool>> + Send;
+
+    /// Keys starting with `prefix`, sorted
+    fn list(&self, prefix: &str) -> impl Future<Output = Result<Vec<String>>> + Send;
+}
+
+/// Check that `key` is a relative path without empty or dot segments
+fn validate_key(key: &str) -> Result<()> {
+    let valid = !key.is_empty()
+        && key
+            .split('/')
+            .all(|segment| !segment.is_empty() && segment != "." && segment != "..")
+        && !key.contains('\\')
+        && !key.ends_with(TEMP_SUFFIX);
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Message(format!("Invalid storage key: {:?}", key)))
+    }
+}
+
+/// Storage keeping each value in a file below a root directory
+///
+/// Values are written to a temporary file first and renamed into place, so
+/// readers never see a partially written value.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    root: PathBuf,
+}
+
+impl FileStorage {
+    /// Store values below `root`, created on first write
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Root directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// File holding `key`
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        validate_key(key)?;
+        Ok(key
+            .split('/')
+            .fold(self.root.clone(), |path, segment| path.join(segment)))
+    }
+}
+
+impl Storage for FileStorage {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let mut temp = path.clone().into_os_string();
+        temp.push(TEMP_SUFFIX);
+        tokio::fs::write(&temp, value).await?;
+        tokio::fs::rename(&temp, &path).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<bool> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut dirs = vec![(self.root.clone(), String::new())];
+        while let Some((dir, key_prefix)) = dirs.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                let key = format!("{}{}", key_prefix, name);
+                if entry.file_type().await?.is_dir() {
+                    dirs.push((entry.path(), format!("{}/", key)));
+                } else if key.starts_with(prefix) && !key.ends_with(TEMP_SUFFIX) {
+                    keys.push(key);
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// Storage keeping values in memory, shared between clones
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    values: Arc<RwLock<BTreeMap<String, Vec<u8>>>>,
+}
+
+impl MemoryStorage {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn values(&self) -> Result<std::sync::RwLockWriteGuard<'_, BTreeMap<String, Vec<u8>>>> {
+        self.values
+            .write()
+            .map_err(|_| Error::Message("Memory storage lock poisoned".to_string()))
+    }
+}
+
+impl Storage for MemoryStorage {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        validate_key(key)?;
+        Ok(self.values()?.get(key).cloned())
+    }
+
+    async fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        validate_key(key)?;
+        self.values()?.insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<bool> {
+        validate_key(key)?;
+        Ok(self.values()?.remove(key).is_some())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .values()?
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn exercise(storage: impl Storage) {
+        assert_eq!(storage.get("cache/CCD").await.unwrap(), None);
+        storage.put("cache/CCD", b"ccd").await.unwrap();
+        storage.put("cache/Mount", b"mount").await.unwrap();
+        storage.put("profiles/default", b"{}").await.unwrap();
+        storage.put("cache/CCD", b"ccd 2").await.unwrap();
+
+        assert_eq!(
+            storage.get("cache/CCD").await.unwrap().as_deref(),
+            Some(&b"ccd 2"[..])
+        );
+        assert_eq!(
+            storage.list("cache/").await.unwrap(),
+            ["cache/CCD", "cache/Mount"]
+        );
+        assert_eq!(storage.list("").await.unwrap().len(), 3);
+
+        assert!(storage.remove("cache/Mount").await.unwrap());
+        assert!(!storage.remove("cache/Mount").await.unwrap());
+        assert_eq!(storage.list("cache/").await.unwrap(), ["cache/CCD"]);
+
+        for key in ["", "/etc/passwd", "../escape", "cache//CCD", "cache/x.tmp"] {
+            assert!(storage.put(key, b"").await.is_err(), "{:?}", key);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_storage() {
+        exercise(MemoryStorage::new()).await;
+    }
+
+    #[tokio::test]
+    async fn test_file_storage() {
+        let root = std::env::temp_dir().join(format!("indi-rs-storage-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        exercise(FileStorage::new(&root)).await;
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,140 @@
+//! User-defined validation of outgoing property updates
+//!
+//! Validators are closures registered per device/property. The client runs
+//! them before sending a `new*Vector`, the server before accepting one from a
+//! connected client. A validator returns `Err(reason)` to refuse the update,
+//! for example to keep `CCD_TEMPERATURE` targets above what the cooler can
+//! reach.
+
+use crate::message::MessageType;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Validation closure for a `new*Vector` message
+pub type Validator = Arc<dyn Fn(&MessageType) -> Result<(), String> + Send + Sync>;
+
+/// Refused property update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    /// Device the update was addressed to
+    pub device: String,
+    /// Property the update was addressed to
+    pub name: String,
+    /// Reason given by the validator
+    pub reason: String,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}: {}", self.device, self.name, self.reason)
+    }
+}
+
+/// Registry of validators keyed by device and property name
+#[derive(Clone, Default)]
+pub struct Validators {
+    rules: HashMap<(String, String), Vec<Validator>>,
+}
+
+impl fmt::Debug for Validators {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Validators")
+            .field("properties", &self.rules.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Validators {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `validator` for `device`/`name`
+    ///
+    /// Several validators may be registered for the same property; all of
+    /// them must accept an update.
+    pub fn add<F>(&mut self, device: impl Into<String>, name: impl Into<String>, validator: F)
+    where
+        F: Fn(&MessageType) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.rules
+            .entry((device.into(), name.into()))
+            .or_default()
+            .push(Arc::new(validator));
+    }
+
+    /// Remove all validators of `device`/`name`
+    pub fn remove(&mut self, device: &str, name: &str) {
+        self.rules.remove(&(device.to_string(), name.to_string()));
+    }
+
+    /// Run the validators registered for a `new*Vector` message
+    ///
+    /// Other message types and properties without validators pass.
+    pub fn validate(&self, message: &MessageType) -> Result<(), Rejection> {
+        let (device, name) = match message {
+            MessageType::NewTextVector(new) => (&new.device, &new.name),
+            MessageType::NewNumberVector(new) => (&new.device, &new.name),
+            MessageType::NewSwitchVector(new) => (&new.device, &new.name),
+            MessageType::NewBlobVector(new) => (&new.device, &new.name),
+            _ => return Ok(()),
+        };
+        let Some(validators) = self.rules.get(&(device.clone(), name.clone())) else {
+            return Ok(());
+        };
+        for validator in validators {
+            validator(message).map_err(|reason| Rejection {
+                device: device.clone(),
+                name: name.clone(),
+                reason,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::new::{NewNumberVector, OneNumber};
+
+    fn temperature(value: &str) -> MessageType {
+        MessageType::NewNumberVector(NewNumberVector {
+            device: "CCD Simulator".to_string(),
+            name: "CCD_TEMPERATURE".to_string(),
+            timestamp: None,
+            elements: vec![OneNumber {
+                name: "CCD_TEMPERATURE_VALUE".to_string(),
+                value: value.to_string(),
+            }],
+        })
+    }
+
+    #[test]
+    fn test_validators() {
+        let mut validators = Validators::new();
+        validators.add("CCD Simulator", "CCD_TEMPERATURE", |message| {
+            let MessageType::NewNumberVector(new) = message else {
+                return Ok(());
+            };
+            match new
+                .elements
+                .iter()
+                .find_map(|e| e.value.parse::<f64>().ok())
+            {
+                Some(target) if target < -40.0 => Err(format!("{} is below -40", target)),
+                _ => Ok(()),
+            }
+        });
+
+        assert!(validators.validate(&temperature("-10")).is_ok());
+        let rejection = validators.validate(&temperature("-50")).unwrap_err();
+        assert_eq!(rejection.name, "CCD_TEMPERATURE");
+        assert_eq!(rejection.reason, "-50 is below -40");
+
+        validators.remove("CCD Simulator", "CCD_TEMPERATURE");
+        assert!(validators.validate(&temperature("-50")).is_ok());
+    }
+}
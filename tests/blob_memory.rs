@@ -0,0 +1,58 @@
+//! Memory used to decode BLOB payloads
+//!
+//! Counts the bytes allocated while decoding a frame. The base64 text of
+//! the frame is parsed into a `String` before decoding and is not counted.
+
+mod common;
+
+use common::{allocated, Counting};
+use indi_rs::message::new::OneBlob;
+
+/// Size of the test frame, a 16 bit image of about 3 megapixels, a
+/// multiple of three so the payload has no padding
+const FRAME: usize = 3 << 21;
+/// Allowance for the chunk buffers of the decoder
+const CHUNKS: usize = 256 << 10;
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn frame() -> OneBlob {
+    let image = (0..FRAME).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    let mut blob = OneBlob::new("CCD1", ".fits", &image);
+    // Wrapped like indilib drivers do
+    blob.value = blob
+        .value
+        .as_bytes()
+        .chunks(72)
+        .map(|line| std::str::from_utf8(line).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n");
+    blob
+}
+
+#[test]
+fn decode_to_holds_one_chunk() {
+    let blob = frame();
+    let (written, bytes) = allocated(|| blob.decode_to(std::io::sink()).unwrap());
+    assert_eq!(written, FRAME as u64);
+    assert!(bytes < CHUNKS, "allocated {} bytes", bytes);
+}
+
+#[test]
+fn decode_into_reuses_buffer() {
+    let blob = frame();
+    let mut buffer = Vec::with_capacity(FRAME);
+    let (len, bytes) = allocated(|| blob.decode_into(&mut buffer).unwrap());
+    assert_eq!(len, FRAME);
+    assert!(bytes < CHUNKS, "allocated {} bytes", bytes);
+}
+
+#[test]
+fn get_raw_data_allocates_the_frame_once() {
+    let blob = frame();
+    let (data, bytes) = allocated(|| blob.get_raw_data().unwrap());
+    assert_eq!(data.len(), FRAME);
+    // Without a whitespace-stripped copy of the base64 text
+    assert!(bytes < FRAME + CHUNKS, "allocated {} bytes", bytes);
+}
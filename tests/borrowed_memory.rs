@@ -0,0 +1,65 @@
+//! Memory used to parse updates borrowed and owned
+//!
+//! Counts the bytes allocated by [`MessageRef`] and [`MessageType`] for the
+//! same messages; `benches/messages.rs` measures the time taken.
+
+mod common;
+
+use common::{allocated, Counting};
+use indi_rs::message::borrowed::MessageRef;
+use indi_rs::message::new::OneBlob;
+use indi_rs::message::set::SetBlobVector;
+use indi_rs::message::MessageType;
+use indi_rs::property::PropertyState;
+use std::borrow::Cow;
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const NUMBER_UPDATE: &str = r#"<setNumberVector device="Telescope Simulator" name="EQUATORIAL_EOD_COORD" state="Busy" timeout="60" timestamp="2026-10-17T01:02:03">
+<oneNumber name="RA">5.5916666666666668</oneNumber>
+<oneNumber name="DEC">-5.3911111111111110</oneNumber>
+</setNumberVector>"#;
+
+fn blob_update() -> String {
+    let image = (0..1 << 20).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    MessageType::SetBlobVector(SetBlobVector {
+        device: "CCD Simulator".to_string(),
+        name: "CCD1".to_string(),
+        state: Some(PropertyState::Ok),
+        timeout: None,
+        timestamp: None,
+        message: None,
+        elements: vec![OneBlob::new("CCD1", ".fits", &image)],
+    })
+    .to_xml()
+    .unwrap()
+}
+
+#[test]
+fn borrowed_update_allocates_no_strings() {
+    let (message, borrowed) = allocated(|| MessageRef::parse(NUMBER_UPDATE).unwrap());
+    let (_, owned) = allocated(|| NUMBER_UPDATE.parse::<MessageType>().unwrap());
+
+    let MessageRef::SetNumberVector(set) = message else {
+        panic!("Expected SetNumberVector");
+    };
+    let strings = [&set.device, &set.name, set.timestamp.as_ref().unwrap()]
+        .into_iter()
+        .chain(set.elements.iter().flat_map(|e| [&e.name, &e.value]));
+    for string in strings {
+        assert!(matches!(string, Cow::Borrowed(_)), "{} was copied", string);
+    }
+    assert!(borrowed < owned, "borrowed {} owned {}", borrowed, owned);
+}
+
+#[test]
+fn borrowed_blob_keeps_payload_in_input() {
+    let xml = blob_update();
+    let (message, borrowed) = allocated(|| MessageRef::parse(&xml).unwrap());
+    assert_eq!(message.device(), "CCD Simulator");
+    let (_, owned) = allocated(|| xml.parse::<MessageType>().unwrap());
+
+    assert!(owned > 1 << 20, "allocated {} bytes", owned);
+    assert!(borrowed < 1 << 10, "allocated {} bytes", borrowed);
+}
@@ -0,0 +1,328 @@
+//! Public API stability checks
+//!
+//! `prelude_v1_items` pins the contents of `indi_rs::prelude_v1`: removing
+//! or renaming an item fails to compile here.
+//!
+//! The other tests wrap external tools and only run when opted in, since
+//! the tools are not part of the regular toolchain:
+//!
+//! - `INDI_RS_PUBLIC_API=1` lists every public item, from the rustdoc JSON
+//!   of a nightly toolchain (requires `rustup toolchain install nightly`),
+//!   and diffs the list against the committed `public-api.txt`. The list
+//!   holds item paths and `#[non_exhaustive]` markers, not signatures.
+//!   `INDI_RS_PUBLIC_API=bless` rewrites the file after an intended change.
+//! - `INDI_RS_SEMVER_CHECKS=1` runs `cargo semver-checks` (requires
+//!   `cargo install cargo-semver-checks`) against the latest release.
+
+use serde_json::Value;
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn prelude_v1_items() {
+    #[allow(unused_imports)]
+    use indi_rs::prelude_v1::{
+        Client, ClientBuilder, ClientConfig, ClientEvent, Error, MessageType, Property,
+        PropertyPerm, PropertyState, PropertyValue, Result, Server, ServerConfig, SwitchRule,
+        SwitchState,
+    };
+
+    // Constructors and variants downstream code is known to rely on
+    let _ = ClientConfig::new("localhost", ClientConfig::DEFAULT_PORT);
+    let _ = Client::builder()
+        .host("localhost")
+        .port(ClientConfig::DEFAULT_PORT);
+    let mut config = ServerConfig::new("127.0.0.1:7624");
+    config.max_clients = None;
+    config.timestamp_policy = indi_rs::property::timestamp::TimestampPolicy::Off;
+    let _ = [
+        PropertyState::Idle,
+        PropertyState::Ok,
+        PropertyState::Busy,
+        PropertyState::Alert,
+    ];
+    let _ = [PropertyPerm::Ro, PropertyPerm::Wo, PropertyPerm::Rw];
+    let _ = [SwitchState::On, SwitchState::Off];
+    let _ = [
+        SwitchRule::OneOfMany,
+        SwitchRule::AtMostOne,
+        SwitchRule::AnyOfMany,
+    ];
+    let _: Result<()> = Err(Error::Message(String::new()));
+}
+
+/// Run `cargo <args>` in the crate root, returning stdout on success
+///
+/// A leading `+toolchain` argument runs the rustup proxy, the `cargo` of
+/// the current toolchain does not understand it.
+fn cargo(args: &[&str]) -> String {
+    let program = match args.first() {
+        Some(toolchain) if toolchain.starts_with('+') => "cargo",
+        _ => env!("CARGO"),
+    };
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap_or_else(|e| panic!("Failed to run cargo {}: {}", args.join(" "), e));
+    assert!(
+        output.status.success(),
+        "cargo {} failed:\n{}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Public items of the crate, one `kind path` line each, sorted
+fn public_api() -> String {
+    let target = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/public-api");
+    cargo(&[
+        "+nightly",
+        "rustdoc",
+        "--lib",
+        "--all-features",
+        "--target-dir",
+        target.to_str().unwrap(),
+        "--",
+        "-Z",
+        "unstable-options",
+        "--output-format",
+        "json",
+    ]);
+    let json = std::fs::read_to_string(target.join("doc/indi_rs.json")).unwrap();
+    let doc: Value = serde_json::from_str(&json).unwrap();
+    let mut api = PublicApi {
+        index: &doc["index"],
+        lines: BTreeSet::new(),
+        visited: HashSet::new(),
+    };
+    api.item(&doc["root"], "indi_rs");
+    api.lines.into_iter().map(|line| line + "\n").collect()
+}
+
+/// Walk of the rustdoc JSON index from the crate root
+struct PublicApi<'a> {
+    index: &'a Value,
+    lines: BTreeSet<String>,
+    visited: HashSet<(String, String)>,
+}
+
+impl<'a> PublicApi<'a> {
+    fn get(&self, id: &Value) -> Option<&'a Value> {
+        let key = match id {
+            Value::Number(id) => id.to_string(),
+            Value::String(id) => id.clone(),
+            _ => return None,
+        };
+        self.index.get(key)
+    }
+
+    fn emit(&mut self, kind: &str, path: &str, item: &Value) {
+        let marker = if item["attrs"].to_string().contains("non_exhaustive") {
+            " #[non_exhaustive]"
+        } else {
+            ""
+        };
+        self.lines
+            .insert(format!("pub {} {}{}", kind, path, marker));
+    }
+
+    fn item(&mut self, id: &Value, path: &str) {
+        let Some(item) = self.get(id) else {
+            return;
+        };
+        if !self.visited.insert((id.to_string(), path.to_string())) {
+            return;
+        }
+        let Some((kind, inner)) = item["inner"]
+            .as_object()
+            .and_then(|inner| inner.iter().next())
+        else {
+            return;
+        };
+        match kind.as_str() {
+            "module" => {
+                self.emit("mod", path, item);
+                for child in inner["items"].as_array().into_iter().flatten() {
+                    self.child(child, path);
+                }
+            }
+            "struct" => {
+                self.emit("struct", path, item);
+                let fields = &inner["kind"];
+                let fields = fields["plain"]["fields"]
+                    .as_array()
+                    .or_else(|| fields["tuple"].as_array());
+                self.fields(fields.into_iter().flatten(), path);
+                self.impls(&inner["impls"], path);
+            }
+            "enum" => {
+                self.emit("enum", path, item);
+                for variant in inner["variants"].as_array().into_iter().flatten() {
+                    let Some(item) = self.get(variant) else {
+                        continue;
+                    };
+                    let variant_path = format!("{}::{}", path, item["name"].as_str().unwrap());
+                    self.emit("variant", &variant_path, item);
+                    let fields = &item["inner"]["variant"]["kind"];
+                    let fields = fields["struct"]["fields"]
+                        .as_array()
+                        .or_else(|| fields["tuple"].as_array());
+                    self.fields(fields.into_iter().flatten(), &variant_path);
+                }
+                self.impls(&inner["impls"], path);
+            }
+            "trait" => {
+                self.emit("trait", path, item);
+                for member in inner["items"].as_array().into_iter().flatten() {
+                    self.member(member, path);
+                }
+            }
+            "function" => self.emit("fn", path, item),
+            "constant" => self.emit("const", path, item),
+            "static" => self.emit("static", path, item),
+            "type_alias" => self.emit("type", path, item),
+            "macro" => self.emit("macro", path, item),
+            kind => self.emit(kind, path, item),
+        }
+    }
+
+    /// Public item of the module at `path`, following re-exports
+    fn child(&mut self, id: &Value, path: &str) {
+        let Some(item) = self.get(id) else {
+            return;
+        };
+        if item["visibility"] != "public" {
+            return;
+        }
+        let Some(reexport) = item["inner"].get("use") else {
+            let child_path = format!("{}::{}", path, item["name"].as_str().unwrap_or("_"));
+            return self.item(id, &child_path);
+        };
+        if reexport["is_glob"] == true {
+            let items = self
+                .get(&reexport["id"])
+                .and_then(|module| module["inner"]["module"]["items"].as_array());
+            for child in items.into_iter().flatten() {
+                self.child(child, path);
+            }
+            return;
+        }
+        let child_path = format!("{}::{}", path, reexport["name"].as_str().unwrap());
+        match self.get(&reexport["id"]) {
+            Some(_) => self.item(&reexport["id"], &child_path),
+            // Re-exported from another crate
+            None => {
+                let source = reexport["source"].as_str().unwrap_or_default();
+                self.lines
+                    .insert(format!("pub use {} = {}", child_path, source));
+            }
+        }
+    }
+
+    fn fields<'f>(&mut self, fields: impl Iterator<Item = &'f Value>, path: &str) {
+        for (position, field) in fields.enumerate() {
+            let Some(item) = self.get(field) else {
+                continue;
+            };
+            if item["visibility"] == "public" {
+                let name = item["name"].as_str().map(str::to_string);
+                let name = name.unwrap_or_else(|| position.to_string());
+                self.emit("field", &format!("{}::{}", path, name), item);
+            }
+        }
+    }
+
+    /// Function, constant or type of an impl or trait
+    fn member(&mut self, id: &Value, path: &str) {
+        let Some(item) = self.get(id) else {
+            return;
+        };
+        let kind = match item["inner"]
+            .as_object()
+            .and_then(|inner| inner.keys().next())
+        {
+            Some(kind) if kind == "function" => "fn",
+            Some(kind) if kind == "assoc_const" => "const",
+            Some(kind) if kind == "assoc_type" => "type",
+            _ => return,
+        };
+        let name = item["name"].as_str().unwrap_or("_");
+        self.emit(kind, &format!("{}::{}", path, name), item);
+    }
+
+    /// Inherent members and trait implementations of the type at `path`,
+    /// without auto traits and blanket implementations
+    fn impls(&mut self, impls: &Value, path: &str) {
+        for id in impls.as_array().into_iter().flatten() {
+            let Some(implementation) = self.get(id).map(|item| &item["inner"]["impl"]) else {
+                continue;
+            };
+            if implementation["is_synthetic"] == true || !implementation["blanket_impl"].is_null() {
+                continue;
+            }
+            match implementation["trait"]["path"].as_str() {
+                Some(trait_path) => {
+                    self.lines
+                        .insert(format!("impl {} for {}", trait_path, path));
+                }
+                None => {
+                    for member in implementation["items"].as_array().into_iter().flatten() {
+                        if self
+                            .get(member)
+                            .is_some_and(|item| item["visibility"] == "public")
+                        {
+                            self.member(member, path);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn public_api_matches_baseline() {
+    let Ok(mode) = std::env::var("INDI_RS_PUBLIC_API") else {
+        return;
+    };
+    let api = public_api();
+    let baseline = Path::new(env!("CARGO_MANIFEST_DIR")).join("public-api.txt");
+
+    if mode == "bless" {
+        std::fs::write(&baseline, &api).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&baseline).unwrap_or_else(|e| {
+        panic!(
+            "Cannot read {}: {}, create it with INDI_RS_PUBLIC_API=bless",
+            baseline.display(),
+            e
+        )
+    });
+    if api != expected {
+        let removed = expected
+            .lines()
+            .filter(|line| !api.lines().any(|l| l == *line))
+            .collect::<Vec<_>>();
+        let added = api
+            .lines()
+            .filter(|line| !expected.lines().any(|l| l == *line))
+            .collect::<Vec<_>>();
+        panic!(
+            "Public API changed, rerun with INDI_RS_PUBLIC_API=bless if intended\nremoved:\n  {}\nadded:\n  {}",
+            removed.join("\n  "),
+            added.join("\n  ")
+        );
+    }
+}
+
+#[test]
+fn semver_checks() {
+    if std::env::var_os("INDI_RS_SEMVER_CHECKS").is_none() {
+        return;
+    }
+    cargo(&["semver-checks", "check-release"]);
+}